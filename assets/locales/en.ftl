@@ -0,0 +1,207 @@
+# English strings for the gui. This is the fallback for every
+# other language, so every key used by the gui must appear here.
+
+## Tabs
+tab-card-preview = Card preview
+tab-field = Field
+tab-effects = Effects
+tab-history = History
+tab-debug-info = Debug info
+tab-settings = Settings
+tab-beliefs = Beliefs
+tab-training = Training
+
+## Start screen
+new-game = New game
+opponent = Opponent
+opponent-random = Random
+opponent-opponent-model = Opponent model
+opponent-blueprint = CFR blueprint
+opponent-unavailable = The { $opponent } opponent is not available
+start = Start
+loading-art = Loading card art
+
+## Field
+game-ended = Game ended! Game result: { $result }
+result-lost = Lost
+result-tied = Tied
+result-won = Won
+rematch = Rematch
+back-to-start = Back to start screen
+resign = Resign
+show-strategy-hints = Show strategy hints
+opponents-board = Opponent's board
+your-board = Your board
+edict = Edict
+sabotage = Sabotage
+creature = Creature
+creatures = Creatures
+score-difference = Your score - opponent's score = { $score }
+confirm = Confirm
+clear = Clear
+
+## Strategy hints
+strategy-hints = Strategy hints
+decision = Decision
+probability = Probability
+
+## Effects
+your-effects = Your effects
+opponents-effects = Opponent's effects
+
+## History
+battlefields = Battlefields
+your-creature = Your creature
+your-edict = Your edict
+your-sabotage = Your sabotage
+opponents-sabotage = Opponent's sabotage
+opponents-edict = Opponent's edict
+opponents-creature = Opponent's creature
+export = Export
+score-chart = Your score - Opponent's score
+export-saved = Saved to { $path } and copied a summary to the clipboard
+export-failed = Failed to save { $path }: { $error }
+export-copied = Copied a summary to the clipboard
+
+## Card preview
+strength-bonuses = Strength = { $strength } + bonuses from:
+battlefield-bonuses = Battlefield bonuses
+reward = Reward: { $reward }
+description-missing = unwritten
+
+## Debug info
+decision-sent = Decision sent
+phase = Phase
+hovered = Hovered
+last-reveal = Last reveal
+
+## Settings
+card-size = Card size
+zoom = Zoom
+confirm-main-choices = Confirm main phase choices
+animations = Animations
+colorblind-cues = Patterns and badges alongside colors
+theme = Theme
+theme-dark = Dark
+theme-light = Light
+log-verbosity = Log verbosity (applied on restart)
+custom-art = Custom art folder (applied on restart)
+language = Language
+
+## Beliefs
+opponents-hand = Opponent's likely hand
+beliefs-weighted = Weighted by how likely the strategy was to play the way the opponent did.
+beliefs-uniform = Only rules out hands contradicting what has been revealed.
+beliefs-unavailable = Lost track of the game, will resume next phase.
+
+## Game review
+review-game = Review game
+review-failed = Failed to review the game: { $error }
+review-summary = Utility lost: { $lost } over { $count } decisions, with { $deviations } deviations from the strategy
+turn = Turn
+your-decision = Your decision
+best-decision = Best decision
+utility-lost = Utility lost
+verdict = Verdict
+phase-main = Main
+phase-sabotage = Sabotage
+phase-seer = Seer
+verdict-best = Best
+verdict-good = Good
+verdict-inaccuracy = Inaccuracy
+verdict-mistake = Mistake
+verdict-blunder = Blunder
+deviation = Deviates from the strategy
+
+## Training
+turns = Turns
+train = Train
+stop = Stop
+training-generating = Generating the tree
+training-stopping = Stopping
+training-failed = Training crashed: { $error }
+training-unexplored = { $count } subtrees did not fit in memory
+training-unexplored-hint = These get valued using the score so far. Train fewer turns for a more accurate strategy.
+training-unavailable = Training is not available in the browser
+iterations = Iterations
+iterations-per-second = Iterations per second
+memory-use = Memory use
+elapsed = Elapsed
+training-strategy = Strategy for your hand at the start of turn { $turn }
+
+## Clocks
+clock = Clock
+no-clock = None
+on-timeout = On timeout
+timeout-forfeit = Lose the game
+timeout-random-decision = Play random decisions
+applies-next-game = Applies from the next game on
+your-clock = Your clock
+opponents-clock = Opponent's clock
+out-of-time = Out of time
+
+## Session
+session-results = Session: { $wins } won, { $losses } lost, { $ties } tied
+session-average-score = Average score: { $score }
+session-ratings = Rating: { $mine } (opponent: { $yours })
+session-ratings-hint = Elo ratings, starting at 1500 when picking an opponent
+
+## Badges
+badge-negated = Negated
+badge-sabotaged = Sabotaged
+badge-battlefield-bonus = Battlefield bonus active
+
+## Creatures
+creature-wall = Wall
+creature-wall-description = The battle this card is involved in ends in a tie.
+creature-seer = Seer
+creature-seer-description = Next battle, play two creatures instead of one. After the opponent reveals their creature, choose one creature to reveal, and return the other to your hand.
+creature-rogue = Rogue
+creature-rogue-description = Negates the seer character. Wins against the monarch and the wall.
+creature-bard = Bard
+creature-bard-description = Next battle, gain +1 strength. Furthermore, winning the next battle awards you +1 victory points.
+creature-diplomat = Diplomat
+creature-diplomat-description = Wins the battle if both players played the same edict.
+creature-ranger = Ranger
+creature-ranger-description = Gains +2 strength if you receive a battlefield bonus and the opponent does not.
+creature-steward = Steward
+creature-steward-description = Edicts are twice as effective. At the end of the turn, return all your edicts back to the hand.
+creature-barbarian = Barbarian
+creature-barbarian-description = Gains +2 strength if you lost last battle.
+creature-witch = Witch
+creature-witch-description = Negates the opponent's creature. Cannot gain strength from edicts.
+creature-mercenary = Mercenary
+creature-mercenary-description = Next turn, lose 1 strength.
+creature-monarch = Monarch
+creature-monarch-description = If you do not win this battle, your opponent gains +2 additional victory points.
+
+## Edicts
+edict-rile-the-public = Rile the public
+edict-rile-the-public-description =
+    - The current battlefield is worth +1 victory points.
+    - Negates "divert attention"
+edict-divert-attention = Divert attention
+edict-divert-attention-description = The current battlefield is worth -1 victory points.
+edict-sabotage = Sabotage
+edict-sabotage-description = Write down a guess for what the opponent's creature could be. When said creature is revealed, gain +2 strength if your guess was correct.
+edict-gambit = Gambit
+edict-gambit-description = Gain +1 strength. You lose on ties.
+edict-ambush = Ambush
+edict-ambush-description = Gain an additional +1 strength if your creature has a battlefield bonus.
+
+## Battlefields
+battlefield-mountain = Mountain
+battlefield-glade = Glade
+battlefield-urban = Urban
+battlefield-last-strand = Last strand
+battlefield-night = Night
+battlefield-plains = Plains
+
+## Status effects
+effect-mountain = Mountain
+effect-glade = Glade
+effect-night = Night
+effect-seer = Seer
+effect-bard = Bard
+effect-mercenary = Mercenary
+effect-barbarian = Barbarian
@@ -0,0 +1,206 @@
+# French strings for the gui. Missing keys fall back to english.
+
+## Tabs
+tab-card-preview = Aperçu de la carte
+tab-field = Terrain
+tab-effects = Effets
+tab-history = Historique
+tab-debug-info = Débogage
+tab-settings = Paramètres
+tab-beliefs = Croyances
+tab-training = Entraînement
+
+## Start screen
+new-game = Nouvelle partie
+opponent = Adversaire
+opponent-random = Aléatoire
+opponent-opponent-model = Modèle de l'adversaire
+opponent-blueprint = Stratégie CFR
+opponent-unavailable = L'adversaire { $opponent } n'est pas disponible
+start = Commencer
+loading-art = Chargement des illustrations
+
+## Field
+game-ended = Partie terminée ! Résultat : { $result }
+result-lost = Défaite
+result-tied = Égalité
+result-won = Victoire
+rematch = Revanche
+back-to-start = Retour à l'écran d'accueil
+resign = Abandonner
+show-strategy-hints = Afficher les conseils de stratégie
+opponents-board = Plateau de l'adversaire
+your-board = Votre plateau
+edict = Édit
+sabotage = Sabotage
+creature = Créature
+creatures = Créatures
+score-difference = Votre score - score de l'adversaire = { $score }
+confirm = Confirmer
+clear = Effacer
+
+## Strategy hints
+strategy-hints = Conseils de stratégie
+decision = Décision
+probability = Probabilité
+
+## Effects
+your-effects = Vos effets
+opponents-effects = Effets de l'adversaire
+
+## History
+battlefields = Champs de bataille
+your-creature = Votre créature
+your-edict = Votre édit
+your-sabotage = Votre sabotage
+opponents-sabotage = Sabotage de l'adversaire
+opponents-edict = Édit de l'adversaire
+opponents-creature = Créature de l'adversaire
+export = Exporter
+score-chart = Votre score - score de l'adversaire
+export-saved = Enregistré dans { $path }, résumé copié dans le presse-papiers
+export-failed = Impossible d'enregistrer { $path } : { $error }
+export-copied = Résumé copié dans le presse-papiers
+
+## Card preview
+strength-bonuses = Force = { $strength } + bonus de :
+battlefield-bonuses = Bonus du champ de bataille
+reward = Récompense : { $reward }
+description-missing = non rédigé
+
+## Debug info
+decision-sent = Décision envoyée
+phase = Phase
+hovered = Survolé
+last-reveal = Dernière révélation
+
+## Settings
+card-size = Taille des cartes
+zoom = Zoom
+confirm-main-choices = Confirmer les choix de la phase principale
+animations = Animations
+colorblind-cues = Motifs et badges en plus des couleurs
+theme = Thème
+theme-dark = Sombre
+theme-light = Clair
+log-verbosity = Verbosité des journaux (au redémarrage)
+custom-art = Dossier d'illustrations (au redémarrage)
+language = Langue
+
+## Beliefs
+opponents-hand = Main probable de l'adversaire
+beliefs-weighted = Pondérée par la probabilité que la stratégie joue comme l'adversaire l'a fait.
+beliefs-uniform = Exclut seulement les mains contredisant ce qui a été révélé.
+beliefs-unavailable = Partie désynchronisée, reprise à la prochaine phase.
+
+## Game review
+review-game = Analyser la partie
+review-failed = Échec de l'analyse de la partie : { $error }
+review-summary = Utilité perdue : { $lost } sur { $count } décisions, avec { $deviations } écarts par rapport à la stratégie
+turn = Tour
+your-decision = Votre décision
+best-decision = Meilleure décision
+utility-lost = Utilité perdue
+verdict = Verdict
+phase-main = Principale
+phase-sabotage = Sabotage
+phase-seer = Voyant
+verdict-best = Meilleur coup
+verdict-good = Bon
+verdict-inaccuracy = Imprécision
+verdict-mistake = Erreur
+verdict-blunder = Gaffe
+deviation = S'écarte de la stratégie
+
+## Training
+turns = Tours
+train = Entraîner
+stop = Arrêter
+training-generating = Génération de l'arbre
+training-stopping = Arrêt en cours
+training-failed = L'entraînement a planté : { $error }
+training-unexplored = { $count } sous-arbres ne tenaient pas en mémoire
+training-unexplored-hint = Ils sont évalués à partir du score actuel. Entraînez moins de tours pour une stratégie plus précise.
+training-unavailable = L'entraînement n'est pas disponible dans le navigateur
+iterations = Itérations
+iterations-per-second = Itérations par seconde
+memory-use = Mémoire utilisée
+elapsed = Temps écoulé
+training-strategy = Stratégie pour votre main au début du tour { $turn }
+
+## Clocks
+clock = Pendule
+no-clock = Aucune
+on-timeout = Temps écoulé
+timeout-forfeit = Perdre la partie
+timeout-random-decision = Jouer des décisions au hasard
+applies-next-game = S'applique à partir de la prochaine partie
+your-clock = Votre pendule
+opponents-clock = Pendule de l'adversaire
+out-of-time = Temps écoulé
+
+## Session
+session-results = Session : { $wins } victoires, { $losses } défaites, { $ties } égalités
+session-average-score = Score moyen : { $score }
+session-ratings = Classement : { $mine } (adversaire : { $yours })
+session-ratings-hint = Classements Elo, qui repartent de 1500 à chaque choix d'adversaire
+
+## Badges
+badge-negated = Annulée
+badge-sabotaged = Sabotée
+badge-battlefield-bonus = Bonus du champ de bataille actif
+
+## Creatures
+creature-wall = Mur
+creature-wall-description = La bataille à laquelle participe cette carte se termine par une égalité.
+creature-seer = Voyant
+creature-seer-description = À la prochaine bataille, jouez deux créatures au lieu d'une. Après que l'adversaire a révélé sa créature, choisissez-en une à révéler et reprenez l'autre en main.
+creature-rogue = Voleur
+creature-rogue-description = Annule le voyant. Gagne contre le monarque et le mur.
+creature-bard = Barde
+creature-bard-description = À la prochaine bataille, gagnez +1 de force. De plus, gagner la prochaine bataille vous rapporte +1 point de victoire.
+creature-diplomat = Diplomate
+creature-diplomat-description = Gagne la bataille si les deux joueurs ont joué le même édit.
+creature-ranger = Rôdeur
+creature-ranger-description = Gagne +2 de force si vous recevez un bonus de champ de bataille et pas l'adversaire.
+creature-steward = Intendant
+creature-steward-description = Les édits sont deux fois plus efficaces. À la fin du tour, reprenez tous vos édits en main.
+creature-barbarian = Barbare
+creature-barbarian-description = Gagne +2 de force si vous avez perdu la bataille précédente.
+creature-witch = Sorcière
+creature-witch-description = Annule la créature adverse. Ne peut pas gagner de force grâce aux édits.
+creature-mercenary = Mercenaire
+creature-mercenary-description = Au prochain tour, perdez 1 de force.
+creature-monarch = Monarque
+creature-monarch-description = Si vous ne gagnez pas cette bataille, l'adversaire gagne +2 points de victoire supplémentaires.
+
+## Edicts
+edict-rile-the-public = Agiter la foule
+edict-rile-the-public-description =
+    - Le champ de bataille actuel vaut +1 point de victoire.
+    - Annule « Détourner l'attention »
+edict-divert-attention = Détourner l'attention
+edict-divert-attention-description = Le champ de bataille actuel vaut -1 point de victoire.
+edict-sabotage = Sabotage
+edict-sabotage-description = Notez la créature que l'adversaire pourrait jouer. Lorsqu'elle est révélée, gagnez +2 de force si vous aviez raison.
+edict-gambit = Coup de poker
+edict-gambit-description = Gagnez +1 de force. Vous perdez en cas d'égalité.
+edict-ambush = Embuscade
+edict-ambush-description = Gagnez +1 de force supplémentaire si votre créature a un bonus de champ de bataille.
+
+## Battlefields
+battlefield-mountain = Montagne
+battlefield-glade = Clairière
+battlefield-urban = Ville
+battlefield-last-strand = Dernier rempart
+battlefield-night = Nuit
+battlefield-plains = Plaines
+
+## Status effects
+effect-mountain = Montagne
+effect-glade = Clairière
+effect-night = Nuit
+effect-seer = Voyant
+effect-bard = Barde
+effect-mercenary = Mercenaire
+effect-barbarian = Barbare
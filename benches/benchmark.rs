@@ -1,13 +1,21 @@
 use bumpalo::Bump;
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use echo::cfr::decision::DecisionVector;
+use echo::cfr::decision_index::DecisionIndex;
 use echo::cfr::generate::{EstimationContext, GenerationContext};
+use echo::cfr::hidden_index::{HiddenIndex, HiddenState, PerPhaseInfo};
+use echo::cfr::reveal_index::RevealIndex;
 use echo::cfr::train::TrainingContext;
-use echo::game::battlefield::Battlefield;
-use echo::game::creature::Creature;
+use echo::game::battlefield::{Battlefield, Battlefields};
+use echo::game::choice::FinalMainPhaseChoice;
+use echo::game::creature::{Creature, CreatureSet};
 use echo::game::edict::Edict;
 use echo::game::known_state::KnownState;
 use echo::game::known_state_summary::KnownStateEssentials;
+use echo::game::simulate::BattleContext;
+use echo::game::types::Player;
 use echo::helpers::bitfield::{Bitfield, Bitfield16};
+use echo::helpers::normalize_vec;
 use std::time::Duration;
 
 pub fn subsets_of_size(c: &mut Criterion) {
@@ -33,7 +41,7 @@ pub fn subsets_of_size(c: &mut Criterion) {
     // {{{ Estimate first two turns
     group.bench_function("estimate first two turns", |b| {
         b.iter(|| {
-            let state = KnownState::new_starting([Battlefield::Plains; 4]);
+            let state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
             let estimator = EstimationContext::new(2, state);
 
             estimator.estimate()
@@ -44,14 +52,15 @@ pub fn subsets_of_size(c: &mut Criterion) {
     group.bench_function("train last two turns", |b| {
         b.iter(|| {
             // {{{ State creation
-            let mut state = KnownState::new_starting([Battlefield::Plains; 4]);
-            state.battlefields.current = 2;
-            for creature in Creature::CREATURES.into_iter().take(4) {
+            let turn = Battlefields::COUNT - 2;
+            let mut state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+            state.battlefields.current = turn;
+            for creature in Creature::CREATURES.into_iter().take(2 * turn) {
                 state.graveyard.insert(creature);
             }
 
             for state in state.player_states.iter_mut() {
-                for edict in Edict::EDICTS.into_iter().take(2) {
+                for edict in Edict::EDICTS.into_iter().take(turn) {
                     state.edicts.remove(edict);
                 }
             }
@@ -62,7 +71,7 @@ pub fn subsets_of_size(c: &mut Criterion) {
             let mut scope = generator.generate();
             // }}}
             // {{{ Training
-            let ctx = TrainingContext::new(false);
+            let mut ctx = TrainingContext::new(false);
             ctx.cfr(&mut scope, state.to_summary(), 10);
             // }}}
         })
@@ -72,5 +81,277 @@ pub fn subsets_of_size(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, subsets_of_size);
+pub fn hot_paths(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hot paths");
+
+    // {{{ Common setup
+    let state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+    let summary = state.to_summary();
+    let hands: Vec<_> = (!state.graveyard)
+        .subsets_of_size(state.hand_size())
+        .collect();
+    let edict_pairs: Vec<_> = Edict::EDICTS
+        .into_iter()
+        .flat_map(|mine| Edict::EDICTS.map(|yours| [mine, yours]))
+        .collect();
+    // }}}
+    // {{{ Hidden index
+    let hidden_indices: Vec<_> = hands
+        .iter()
+        .map(|hand| HiddenIndex::encode(&summary, Player::Me, PerPhaseInfo::Main(*hand)).unwrap())
+        .collect();
+
+    group.bench_function("hidden index encode", |b| {
+        b.iter(|| {
+            for hand in &hands {
+                let _ = black_box(HiddenIndex::encode(
+                    &summary,
+                    Player::Me,
+                    PerPhaseInfo::Main(*hand),
+                ));
+            }
+        })
+    });
+
+    group.bench_function("hidden index decode", |b| {
+        b.iter(|| {
+            for index in &hidden_indices {
+                let _ = black_box(index.decode(&summary, Player::Me, PerPhaseInfo::Main(())));
+            }
+        })
+    });
+
+    let hidden_infos: Vec<_> = hands.iter().map(|hand| PerPhaseInfo::Main(*hand)).collect();
+
+    group.bench_function("hidden index encode (batch)", |b| {
+        let mut out = vec![HiddenIndex::from(0); hidden_infos.len()];
+        b.iter(|| {
+            let _ =
+                HiddenIndex::encode_all(&summary, Player::Me, black_box(&hidden_infos), &mut out);
+        })
+    });
+
+    group.bench_function("hidden index decode (batch)", |b| {
+        let mut out = vec![HiddenState::new(CreatureSet::empty(), None); hidden_indices.len()];
+        b.iter(|| {
+            let _ = HiddenIndex::decode_all(
+                &summary,
+                Player::Me,
+                PerPhaseInfo::Main(()),
+                black_box(&hidden_indices),
+                &mut out,
+            );
+        })
+    });
+    // }}}
+    // {{{ Decision index
+    let hand = hands[0];
+    let decision_count = DecisionIndex::main_phase_index_count(&summary, Player::Me).unwrap();
+    let decisions: Vec<_> = (0..decision_count)
+        .filter_map(|index| {
+            DecisionIndex(index)
+                .decode_main_phase_index(&summary, Player::Me, hand)
+                .ok()
+        })
+        .collect();
+
+    group.bench_function("decision index encode", |b| {
+        b.iter(|| {
+            for (creatures, edict) in &decisions {
+                let _ = black_box(DecisionIndex::encode_main_phase_index(
+                    &summary,
+                    Player::Me,
+                    hand,
+                    *creatures,
+                    *edict,
+                ));
+            }
+        })
+    });
+
+    group.bench_function("decision index decode", |b| {
+        b.iter(|| {
+            for index in 0..decision_count {
+                let _ = black_box(DecisionIndex(index).decode_main_phase_index(
+                    &summary,
+                    Player::Me,
+                    hand,
+                ));
+            }
+        })
+    });
+
+    let decision_indices: Vec<_> = (0..decision_count).map(DecisionIndex).collect();
+
+    group.bench_function("decision index encode (batch)", |b| {
+        let mut out = vec![DecisionIndex(0); decisions.len()];
+        b.iter(|| {
+            let _ = DecisionIndex::encode_main_phase_all(
+                &summary,
+                Player::Me,
+                hand,
+                black_box(&decisions),
+                &mut out,
+            );
+        })
+    });
+
+    group.bench_function("decision index decode (batch)", |b| {
+        let mut out = vec![(CreatureSet::empty(), Edict::Gambit); decision_indices.len()];
+        b.iter(|| {
+            let _ = DecisionIndex::decode_main_phase_all(
+                &summary,
+                Player::Me,
+                hand,
+                black_box(&decision_indices),
+                &mut out,
+            );
+        })
+    });
+    // }}}
+    // {{{ Reveal index
+    let edict_sets = state.edict_sets();
+
+    group.bench_function("reveal index encode", |b| {
+        b.iter(|| {
+            for edicts in &edict_pairs {
+                let _ = black_box(RevealIndex::encode_main_phase_reveal(*edicts, edict_sets));
+            }
+
+            for creature in Creature::CREATURES {
+                let _ = black_box(RevealIndex::encode_sabotage_phase_reveal(
+                    [Some(creature), None],
+                    Player::Me,
+                    creature,
+                    state.graveyard,
+                ));
+            }
+        })
+    });
+
+    group.bench_function("reveal index decode", |b| {
+        let main_count = RevealIndex::main_phase_count(edict_sets).unwrap();
+        let sabotage_count =
+            RevealIndex::sabotage_phase_count([true, true], Player::Me, state.graveyard).unwrap();
+
+        b.iter(|| {
+            for index in 0..main_count {
+                let _ = black_box(RevealIndex(index).decode_main_phase_reveal(edict_sets));
+            }
+
+            for index in 0..sabotage_count {
+                let _ = black_box(RevealIndex(index).decode_sabotage_phase_reveal(
+                    [true, true],
+                    Player::Me,
+                    state.graveyard,
+                ));
+            }
+        })
+    });
+    // }}}
+    // {{{ Bitfield subsets
+    group.bench_function("creature subsets", |b| {
+        b.iter(|| {
+            let all = !CreatureSet::empty();
+            let mut res = 0;
+            for size in 0..=all.len() {
+                res += all.subsets_of_size(size).count();
+            }
+
+            res
+        })
+    });
+    // }}}
+    // {{{ Battle simulation
+    let contexts: Vec<_> = Creature::CREATURES
+        .into_iter()
+        .flat_map(|mine| Creature::CREATURES.map(|yours| [mine, yours]))
+        .filter(|[mine, yours]| mine != yours)
+        .flat_map(|creatures| {
+            edict_pairs.iter().map(move |edicts| {
+                let choices = [0, 1].map(|i| FinalMainPhaseChoice::new(creatures[i], edicts[i]));
+                BattleContext::new(choices, [None, None], state, false)
+            })
+        })
+        .collect();
+
+    group.bench_function("advance known state", |b| {
+        b.iter(|| {
+            for context in &contexts {
+                black_box(context.advance_known_state());
+            }
+        })
+    });
+    // }}}
+
+    group.finish();
+}
+
+/// Compares the chunked implementations of the regret matching
+/// helpers against naive versions of the same computations.
+pub fn regret_matching(c: &mut Criterion) {
+    let mut group = c.benchmark_group("regret matching");
+    let values: Vec<f32> = (0..64).map(|i| ((i * 37) % 23) as f32 - 7.0).collect();
+
+    // {{{ Normalization
+    group.bench_function("normalize vec (naive)", |b| {
+        b.iter(|| {
+            let mut vec = black_box(values.clone());
+            let sum: f32 = vec.iter().sum();
+            for value in &mut vec {
+                *value /= sum;
+            }
+
+            vec
+        })
+    });
+
+    group.bench_function("normalize vec", |b| {
+        b.iter(|| {
+            let mut vec = black_box(values.clone());
+            normalize_vec(&mut vec);
+            vec
+        })
+    });
+    // }}}
+    // {{{ Decision vectors
+    let allocator = Bump::new();
+    let vector = DecisionVector::new(values.len(), (&allocator).into());
+    for (index, value) in values.iter().enumerate() {
+        vector.accumulate_regret(index, *value);
+    }
+
+    group.bench_function("regret magnitude (naive)", |b| {
+        b.iter(|| {
+            let mut sum = 0.0;
+            for value in black_box(&values) {
+                sum += f32::max(*value, 0.0);
+            }
+
+            sum
+        })
+    });
+
+    group.bench_function("regret magnitude", |b| {
+        b.iter(|| black_box(&vector).recompute_regret_magnitude())
+    });
+
+    group.bench_function("strategy sum update (naive)", |b| {
+        let mut strategy_sum = vec![0.0; values.len()];
+        b.iter(|| {
+            for (i, sum) in strategy_sum.iter_mut().enumerate() {
+                *sum += 0.5 * black_box(&vector).strategy(i);
+            }
+        })
+    });
+
+    group.bench_function("strategy sum update", |b| {
+        b.iter(|| black_box(&vector).update_strategy_sum(0.5))
+    });
+    // }}}
+
+    group.finish();
+}
+
+criterion_group!(benches, subsets_of_size, hot_paths, regret_matching);
 criterion_main!(benches);
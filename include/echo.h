@@ -0,0 +1,159 @@
+#ifndef ECHO_H
+#define ECHO_H
+
+/* Generated by cbindgen from src/capi.rs. Do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef enum EchoPhase {
+  EchoMain = 0,
+  EchoSabotage = 1,
+  EchoSeer = 2,
+} EchoPhase;
+
+typedef enum EchoPlayer {
+  EchoMe = 0,
+  EchoYou = 1,
+} EchoPlayer;
+
+typedef enum EchoStatus {
+  // The game moved on to the next phase.
+  EchoOk = 0,
+  // The game is over. The final score is available via `echo_game_score`.
+  EchoFinished = 1,
+  // Some argument was null or otherwise malformed.
+  EchoInvalidArgument = -1,
+  // The decisions were rejected by the engine.
+  EchoInvalidDecision = -2,
+} EchoStatus;
+
+// Opaque handle to a blueprint file.
+typedef struct EchoBlueprint EchoBlueprint;
+
+// Opaque handle to a game in progress.
+typedef struct EchoGame EchoGame;
+
+// Returns a description of the last error which occurred on the current
+// thread, or `NULL` if no error occurred yet. The string stays valid until
+// the next failing call on the same thread.
+const char *echo_last_error(void);
+
+// Starts a new game on the given comma separated battlefields
+// (eg: `"Night,Glade,Urban,LastStrand"`), dealing random hands
+// based on the given seed.
+//
+// Returns `NULL` on failure. The game must be freed using `echo_game_free`.
+//
+// # Safety
+//
+// The battlefields must be a nul terminated string.
+struct EchoGame *echo_game_new(const char *battlefields, uint64_t seed);
+
+// Frees a game created by `echo_game_new`. Passing `NULL` is a no-op.
+//
+// # Safety
+//
+// The game must not be used after this call.
+void echo_game_free(struct EchoGame *game);
+
+// # Safety
+//
+// The game must be a valid handle.
+enum EchoPhase echo_game_phase(const struct EchoGame *game);
+
+// # Safety
+//
+// The game must be a valid handle.
+size_t echo_game_turn(const struct EchoGame *game);
+
+// The score, from the perspective of the first player.
+//
+// # Safety
+//
+// The game must be a valid handle.
+int8_t echo_game_score(const struct EchoGame *game);
+
+// # Safety
+//
+// The game must be a valid handle.
+bool echo_game_is_finished(const struct EchoGame *game);
+
+// Returns the number of legal decisions the given player can currently make.
+// Returns zero once the game is over.
+//
+// # Safety
+//
+// The game must be a valid handle.
+size_t echo_game_decision_count(const struct EchoGame *game, enum EchoPlayer player);
+
+// Writes a short description of what taking some decision means
+// (eg: `"Play Wall + Gambit"`) into the given buffer.
+//
+// Returns the length of the full description (see `snprintf`),
+// or a negative value if the decision is out of range.
+//
+// # Safety
+//
+// The game must be a valid handle, and the buffer must be
+// valid for writes of `capacity` bytes.
+ptrdiff_t echo_game_describe_decision(const struct EchoGame *game,
+                                      enum EchoPlayer player,
+                                      size_t decision,
+                                      char *buffer,
+                                      size_t capacity);
+
+// Writes the position from the perspective of the given player
+// into the given buffer, using the notation from `game::notation`.
+//
+// Returns the length of the full position (see `snprintf`).
+//
+// # Safety
+//
+// The game must be a valid handle, and the buffer must be
+// valid for writes of `capacity` bytes.
+size_t echo_game_notation(const struct EchoGame *game,
+                          enum EchoPlayer player,
+                          char *buffer,
+                          size_t capacity);
+
+// Advances the game to the next phase, given the decisions of both players.
+//
+// # Safety
+//
+// The game must be a valid handle.
+enum EchoStatus echo_game_advance(struct EchoGame *game, size_t my_decision, size_t your_decision);
+
+// Opens a blueprint file, returning `NULL` on failure.
+// The blueprint must be freed using `echo_blueprint_free`.
+//
+// # Safety
+//
+// The path must be a nul terminated string.
+struct EchoBlueprint *echo_blueprint_open(const char *path);
+
+// Frees a blueprint opened by `echo_blueprint_open`. Passing `NULL` is a no-op.
+//
+// # Safety
+//
+// The blueprint must not be used after this call.
+void echo_blueprint_free(struct EchoBlueprint *blueprint);
+
+// Writes the probability of the given player taking every decision into
+// the output buffer, which must have room for `echo_game_decision_count` values.
+//
+// Returns the number of decisions, or a negative value if the
+// blueprint does not cover the current position.
+//
+// # Safety
+//
+// The blueprint and game must be valid handles, and the output
+// must be valid for writes of `capacity` floats.
+ptrdiff_t echo_blueprint_strategy(struct EchoBlueprint *blueprint,
+                                  const struct EchoGame *game,
+                                  enum EchoPlayer player,
+                                  float *output,
+                                  size_t capacity);
+
+#endif /* ECHO_H */
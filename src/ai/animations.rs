@@ -0,0 +1,84 @@
+use crate::game::creature::Creature;
+use instant::{Duration, Instant};
+
+// {{{ Animation state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationKind {
+    /// A creature getting flipped face up.
+    Flip(Creature),
+    /// A popup showing how much the score changed by
+    /// (from the perspective of the human player).
+    ScoreDelta(i8),
+}
+
+impl AnimationKind {
+    pub fn duration(self) -> Duration {
+        match self {
+            AnimationKind::Flip(_) => Duration::from_millis(600),
+            AnimationKind::ScoreDelta(_) => Duration::from_millis(1500),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Animation {
+    kind: AnimationKind,
+    started: Instant,
+}
+
+impl Animation {
+    /// Returns a number between 0 and 1 representing how far along the animation is.
+    fn progress(&self) -> f32 {
+        let elapsed = self.started.elapsed().as_secs_f32();
+        (elapsed / self.kind.duration().as_secs_f32()).min(1.0)
+    }
+}
+
+/// Keeps track of all the animations currently playing.
+///
+/// Animations get started whenever something gets revealed,
+/// and are dropped by `update` once they are done.
+#[derive(Debug, Clone, Default)]
+pub struct Animations {
+    running: Vec<Animation>,
+}
+
+impl Animations {
+    pub fn start(&mut self, kind: AnimationKind) {
+        self.running.retain(|animation| animation.kind != kind);
+        self.running.push(Animation {
+            kind,
+            started: Instant::now(),
+        });
+    }
+
+    /// Drops all the animations which have finished playing.
+    pub fn update(&mut self) {
+        self.running.retain(|animation| animation.progress() < 1.0);
+    }
+
+    #[inline(always)]
+    pub fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Returns how far along the flip animation for some creature is.
+    pub fn flip_progress(&self, creature: Creature) -> Option<f32> {
+        self.running
+            .iter()
+            .find(|animation| animation.kind == AnimationKind::Flip(creature))
+            .map(Animation::progress)
+    }
+
+    /// Returns the score delta being shown, together
+    /// with how far along its animation is.
+    pub fn score_delta(&self) -> Option<(i8, f32)> {
+        self.running
+            .iter()
+            .find_map(|animation| match animation.kind {
+                AnimationKind::ScoreDelta(delta) => Some((delta, animation.progress())),
+                _ => None,
+            })
+    }
+}
+// }}}
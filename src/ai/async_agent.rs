@@ -0,0 +1,217 @@
+//! Agents which decide asynchronously (for instance, by waiting on the network).
+//!
+//! The runner itself stays synchronous: `AsyncAdapter` turns any async agent
+//! into a regular `EchoAgent`. Games stepped through `EchoRunner::step` (like
+//! the ones on the web) poll the future without ever blocking, while games
+//! run using `EchoRunner::run_game` park the thread until the future is ready.
+//! No executor is needed either way.
+use super::clock::ClockState;
+use super::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::types::Score;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// A decision which might not have been made yet. The future must own
+/// everything it needs, as it can outlive the call creating it.
+pub type DecisionFuture = Pin<Box<dyn Future<Output = DecisionIndex>>>;
+
+/// Async counterpart of `EchoAgent`. Only deciding is asynchronous,
+/// every other method mirrors the one in `EchoAgent`.
+pub trait AsyncEchoAgent {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionFuture;
+
+    #[inline(always)]
+    fn reveal_info(&mut self, _reveal_index: RevealIndex, _updated_score: Score) {}
+
+    #[inline(always)]
+    fn game_finished(&mut self) {}
+
+    #[inline(always)]
+    fn clock_updated(&mut self, _clock: ClockState) {}
+
+    #[inline(always)]
+    fn decision_rejected(&mut self, _decision: DecisionIndex) {}
+
+    #[inline(always)]
+    fn search_effort(&mut self) -> Option<u64> {
+        None
+    }
+
+    #[inline(always)]
+    fn has_resigned(&mut self) -> bool {
+        false
+    }
+}
+
+// {{{ Waker
+/// Wakes up the thread waiting on some future.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+// }}}
+// {{{ Adapter
+/// Turns an async agent into a regular one (see the module docs).
+pub struct AsyncAdapter<A> {
+    agent: A,
+
+    /// The decision currently being made, when running step by step.
+    pending: Option<DecisionFuture>,
+}
+
+impl<A: AsyncEchoAgent> AsyncAdapter<A> {
+    pub fn new(agent: A) -> Self {
+        Self {
+            agent,
+            pending: None,
+        }
+    }
+
+    pub fn into_inner(self) -> A {
+        self.agent
+    }
+}
+
+impl<A: AsyncEchoAgent> EchoAgent for AsyncAdapter<A> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        let mut future = self
+            .pending
+            .take()
+            .unwrap_or_else(|| self.agent.choose(agent_input));
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut context = Context::from_waker(&waker);
+
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(decision) => return decision,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn poll_choice(&mut self, agent_input: AgentInput) -> Option<DecisionIndex> {
+        let future = self
+            .pending
+            .get_or_insert_with(|| self.agent.choose(agent_input));
+
+        // The future gets polled again during the next step anyway.
+        match future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        {
+            Poll::Ready(decision) => {
+                self.pending = None;
+                Some(decision)
+            }
+            Poll::Pending => None,
+        }
+    }
+
+    #[inline(always)]
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        self.agent.reveal_info(reveal_index, updated_score)
+    }
+
+    #[inline(always)]
+    fn game_finished(&mut self) {
+        self.pending = None;
+        self.agent.game_finished()
+    }
+
+    #[inline(always)]
+    fn clock_updated(&mut self, clock: ClockState) {
+        self.agent.clock_updated(clock)
+    }
+
+    #[inline(always)]
+    fn decision_rejected(&mut self, decision: DecisionIndex) {
+        self.agent.decision_rejected(decision)
+    }
+
+    #[inline(always)]
+    fn search_effort(&mut self) -> Option<u64> {
+        self.agent.search_effort()
+    }
+
+    #[inline(always)]
+    fn has_resigned(&mut self) -> bool {
+        self.agent.has_resigned()
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::always_zero_agent::AlwaysZeroAgent;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::cfr::phase::{MainPhase, Phase};
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::known_state::KnownState;
+    use crate::game::known_state_summary::KnownStateEssentials;
+
+    /// Becomes ready after getting polled a number of times.
+    struct Countdown(usize);
+
+    impl Future for Countdown {
+        type Output = DecisionIndex;
+
+        fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.0 == 0 {
+                return Poll::Ready(DecisionIndex(0));
+            }
+
+            self.0 -= 1;
+            context.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    struct SlowAgent;
+
+    impl AsyncEchoAgent for SlowAgent {
+        fn choose(&mut self, _agent_input: AgentInput) -> DecisionFuture {
+            Box::pin(Countdown(3))
+        }
+    }
+
+    fn runner() -> EchoRunner<AlwaysZeroAgent, AsyncAdapter<SlowAgent>> {
+        let state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+        let phase = MainPhase::new();
+        let hidden = phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        let agents = (AlwaysZeroAgent::default(), AsyncAdapter::new(SlowAgent));
+        EchoRunner::new(state, phase.to_some_phase(), agents, hidden)
+    }
+
+    #[test]
+    fn async_agents_can_block() {
+        assert!(runner().run_game_with_score().is_ok());
+    }
+
+    #[test]
+    fn async_agents_can_be_stepped() {
+        let mut runner = runner();
+        let mut steps = 0;
+
+        while runner.step().unwrap().is_none() {
+            steps += 1;
+            assert!(steps < 1000, "The game never finished");
+        }
+
+        // Every decision takes a few steps.
+        assert!(steps > 3);
+    }
+}
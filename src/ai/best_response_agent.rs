@@ -0,0 +1,185 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::best_response::{decision_children, decision_values, Children, Deal};
+use crate::cfr::blueprint::BlueprintReader;
+use crate::cfr::decision::Scope;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::evaluate::{Cursor, FrozenStrategy};
+use crate::cfr::generate::GenerationContext;
+use crate::cfr::phase::{PerPhase, Phase};
+use crate::cfr::reveal_index::RevealIndex;
+use crate::error::EchoResult;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::Score;
+use bumpalo::Bump;
+use std::io::{Read, Seek};
+
+/// An agent which plays the best response to some frozen strategy of the
+/// opponent (see `best_response`). Playing it against the agent following
+/// that strategy shows how exploitable the strategy really is.
+///
+/// The agent keeps track of every deal consistent with what it has seen so far,
+/// weighted by the probability of the opponent playing the way it did. Games
+/// must start at the root of the given tree. Once the game leaves the tree (or
+/// the opponent does something the strategy never would), the agent falls back
+/// to the first decision, respectively to assuming every deal is equally likely.
+pub struct BestResponseAgent<'a> {
+    root: &'a Scope<'a>,
+    opponent: FrozenStrategy<'a>,
+
+    /// The scope of the current phase, together with where in its strategy the
+    /// opponent is. `None` once the game goes deeper than the tree does.
+    current: Option<(&'a Scope<'a>, Cursor)>,
+
+    /// Deals consistent with everything revealed so far.
+    /// Empty until the first decision of every game.
+    beliefs: Vec<Deal>,
+
+    /// The deals each reveal leads to, given the last decision we took.
+    children: Children,
+
+    /// How many (deal, decision) pairs got evaluated for the last decision.
+    searched: u64,
+}
+
+impl<'a> BestResponseAgent<'a> {
+    /// Fails if the opponent strategy cannot be loaded.
+    pub fn new(root: &'a Scope<'a>, mut opponent: FrozenStrategy<'a>) -> EchoResult<Self> {
+        let cursor = opponent.root()?;
+
+        Ok(Self {
+            root,
+            opponent,
+            current: Some((root, cursor)),
+            beliefs: vec![],
+            children: vec![],
+            searched: 0,
+        })
+    }
+
+    /// Best responds to the strategy stored in some blueprint, generating the
+    /// tree from the root of the blueprint. The tree must not span more turns
+    /// than the blueprint was trained for.
+    pub fn from_blueprint<R: Read + Seek>(
+        blueprint: &'a mut BlueprintReader<R>,
+        turns: usize,
+        allocator: &'a Bump,
+    ) -> EchoResult<Self> {
+        let state = blueprint.root_state();
+        let scope = allocator.alloc(GenerationContext::new(turns, state, allocator).generate());
+
+        Self::new(scope, FrozenStrategy::Blueprint(blueprint))
+    }
+
+    /// Picks the decision with the highest utility given our current beliefs.
+    /// Returns `None` if the tree does not reach the current phase.
+    fn respond<P: Phase>(
+        &mut self,
+        phase: P,
+        input: &AgentInput,
+    ) -> EchoResult<Option<DecisionIndex>> {
+        let Some((scope, cursor)) = &self.current else {
+            return Ok(None);
+        };
+
+        let Some(scope) = scope.get_explored() else {
+            return Ok(None);
+        };
+
+        let state = input.state.to_summary();
+
+        if self.beliefs.is_empty() {
+            self.beliefs = phase
+                .valid_hidden_states(state)
+                .filter(|hidden| input.player.select(*hidden) == input.hidden)
+                .map(|hidden| (hidden, 1.0))
+                .collect();
+        }
+
+        let values = decision_values(
+            scope,
+            phase,
+            state,
+            input.player,
+            &mut self.opponent,
+            cursor,
+            &self.beliefs,
+        )?;
+
+        self.searched = (self.beliefs.len() * values.len()) as u64;
+        let decision = DecisionIndex(
+            values
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map_or(0, |(index, _)| index),
+        );
+
+        self.children = decision_children(
+            scope,
+            phase,
+            state,
+            input.player,
+            decision,
+            &self.opponent,
+            cursor,
+            &self.beliefs,
+        )?;
+
+        Ok(Some(decision))
+    }
+}
+
+impl<'a> EchoAgent for BestResponseAgent<'a> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        self.searched = 0;
+        let result = match agent_input.phase {
+            PerPhase::Main(phase) => self.respond(phase, &agent_input),
+            PerPhase::Sabotage(phase) => self.respond(phase, &agent_input),
+            PerPhase::Seer(phase) => self.respond(phase, &agent_input),
+        };
+
+        match result {
+            Ok(Some(decision)) => decision,
+            Ok(None) => DecisionIndex::default(),
+            Err(error) => {
+                tracing::warn!("Failed to compute a best response: {error}");
+                DecisionIndex::default()
+            }
+        }
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, _updated_score: Score) {
+        self.beliefs = std::mem::take(&mut self.children)
+            .into_iter()
+            .find(|(index, _, _)| *index == reveal_index)
+            .map_or(vec![], |(_, _, deals)| deals);
+
+        self.current = self.current.take().and_then(|(scope, cursor)| {
+            let next = scope.descend(reveal_index)?;
+
+            match self.opponent.descend(&cursor, reveal_index) {
+                Ok(cursor) => Some((next, cursor)),
+                Err(error) => {
+                    tracing::warn!("Failed to follow the opponent strategy: {error}");
+                    None
+                }
+            }
+        });
+    }
+
+    fn search_effort(&mut self) -> Option<u64> {
+        Some(self.searched)
+    }
+
+    fn game_finished(&mut self) {
+        self.beliefs.clear();
+        self.children.clear();
+        self.current = match self.opponent.root() {
+            Ok(cursor) => Some((self.root, cursor)),
+            Err(error) => {
+                tracing::warn!("Failed to reload the opponent strategy: {error}");
+                None
+            }
+        };
+    }
+}
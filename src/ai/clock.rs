@@ -0,0 +1,290 @@
+//! Chess style clocks, limiting the time agents can spend on their decisions.
+//!
+//! Both players decide at the same time during every phase, so both clocks
+//! tick at once until the respective player has decided. Every decision adds
+//! an increment to the clock of the player taking it. Agents never get
+//! interrupted, so running out of time only gets noticed once the agent has
+//! decided, at which point the timeout behaviour kicks in.
+use crate::game::types::{Player, Score};
+use crate::helpers::pair::Pair;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::Duration;
+
+// std::time::Instant panics on the web, where only the gui runs games.
+#[cfg(feature = "gui")]
+use instant::Instant;
+#[cfg(not(feature = "gui"))]
+use std::time::Instant;
+
+// {{{ Time controls
+/// What happens once some player runs out of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeoutBehaviour {
+    /// The player loses the game.
+    #[serde(rename = "forfeit")]
+    Forfeit,
+    /// Every decision the player takes from then on gets replaced by a random one.
+    #[serde(rename = "random")]
+    RandomDecision,
+}
+
+impl TimeoutBehaviour {
+    pub const BEHAVIOURS: [TimeoutBehaviour; 2] =
+        [TimeoutBehaviour::Forfeit, TimeoutBehaviour::RandomDecision];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeoutBehaviour::Forfeit => "forfeit",
+            TimeoutBehaviour::RandomDecision => "random",
+        }
+    }
+}
+
+impl FromStr for TimeoutBehaviour {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TimeoutBehaviour::BEHAVIOURS
+            .into_iter()
+            .find(|behaviour| behaviour.name() == s)
+            .ok_or(())
+    }
+}
+
+/// How much time each player gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    /// The time on the clock of each player at the start of the game.
+    pub total: Duration,
+
+    /// Time added to the clock of a player after each of their decisions.
+    pub increment: Duration,
+    pub on_timeout: TimeoutBehaviour,
+}
+
+impl TimeControl {
+    /// A few common time controls, for the gui to pick from.
+    pub const PRESETS: [TimeControl; 4] = [
+        TimeControl::new(Duration::from_secs(60), Duration::ZERO),
+        TimeControl::new(Duration::from_secs(3 * 60), Duration::from_secs(2)),
+        TimeControl::new(Duration::from_secs(5 * 60), Duration::from_secs(3)),
+        TimeControl::new(Duration::from_secs(10 * 60), Duration::from_secs(5)),
+    ];
+
+    /// Creates a time control which forfeits the game once a player runs out of time.
+    pub const fn new(total: Duration, increment: Duration) -> Self {
+        Self {
+            total,
+            increment,
+            on_timeout: TimeoutBehaviour::Forfeit,
+        }
+    }
+
+    pub fn with_timeout_behaviour(mut self, on_timeout: TimeoutBehaviour) -> Self {
+        self.on_timeout = on_timeout;
+        self
+    }
+}
+
+/// Formats time controls the way chess does: minutes + seconds of increment.
+impl Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let minutes = self.total.as_secs_f64() / 60.0;
+        write!(f, "{minutes}+{}", self.increment.as_secs())
+    }
+}
+
+/// Parses time controls of the form `minutes+seconds`, where the increment is optional.
+impl FromStr for TimeControl {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (minutes, increment) = s.split_once('+').unwrap_or((s, "0"));
+        let minutes: f64 = minutes.trim().parse().map_err(|_| ())?;
+        let increment: u64 = increment.trim().parse().map_err(|_| ())?;
+
+        if !minutes.is_finite() || minutes <= 0.0 {
+            return Err(());
+        }
+
+        Ok(Self::new(
+            Duration::from_secs_f64(minutes * 60.0),
+            Duration::from_secs(increment),
+        ))
+    }
+}
+// }}}
+// {{{ Clock state
+/// The time left on both clocks at some point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockState {
+    pub control: TimeControl,
+    pub remaining: Pair<Duration>,
+
+    /// Whose clocks were running at the time.
+    pub ticking: Pair<bool>,
+}
+
+impl ClockState {
+    #[inline(always)]
+    pub fn has_flagged(&self, player: Player) -> bool {
+        player.select(self.remaining).is_zero()
+    }
+
+    /// The final score once some player has run out of time, if that
+    /// forfeits the game (see `Score::forfeited_by`).
+    pub fn forfeit_score(&self, score: Score) -> Option<Score> {
+        if self.control.on_timeout != TimeoutBehaviour::Forfeit {
+            return None;
+        }
+
+        let player = Player::PLAYERS
+            .into_iter()
+            .find(|player| self.has_flagged(*player))?;
+
+        Some(score.forfeited_by(player))
+    }
+}
+// }}}
+// {{{ Game clock
+/// The clocks of both players.
+#[derive(Debug, Clone)]
+pub struct GameClock {
+    control: TimeControl,
+    remaining: Pair<Duration>,
+
+    /// When the clock of each player was started, if it is running.
+    started: Pair<Option<Instant>>,
+}
+
+impl GameClock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining: [control.total; 2],
+            started: [None; 2],
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_running(&self, player: Player) -> bool {
+        player.select(self.started).is_some()
+    }
+
+    /// Starts the clock of the given player, unless it is already running.
+    pub fn start(&mut self, player: Player) {
+        player
+            .select_mut(&mut self.started)
+            .get_or_insert_with(Instant::now);
+    }
+
+    /// Stops the clock of the given player, adding the increment unless the
+    /// player ran out of time. Returns whether the player ran out of time.
+    pub fn stop(&mut self, player: Player) -> bool {
+        let remaining = self.remaining(player);
+        player.set_selection(&mut self.started, None);
+
+        let flagged = remaining.is_zero();
+        let increment = if flagged {
+            Duration::ZERO
+        } else {
+            self.control.increment
+        };
+
+        player.set_selection(&mut self.remaining, remaining + increment);
+        flagged
+    }
+
+    /// The time left on the clock of the given player right now.
+    pub fn remaining(&self, player: Player) -> Duration {
+        let remaining = player.select(self.remaining);
+
+        match player.select(self.started) {
+            Some(started) => remaining.saturating_sub(started.elapsed()),
+            None => remaining,
+        }
+    }
+
+    pub fn state(&self) -> ClockState {
+        ClockState {
+            control: self.control,
+            remaining: Player::PLAYERS.map(|player| self.remaining(player)),
+            ticking: Player::PLAYERS.map(|player| self.is_running(player)),
+        }
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::always_zero_agent::AlwaysZeroAgent;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::cfr::phase::{MainPhase, Phase};
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::known_state::KnownState;
+    use crate::game::known_state_summary::KnownStateEssentials;
+
+    fn runner(control: TimeControl) -> EchoRunner<AlwaysZeroAgent, AlwaysZeroAgent> {
+        let state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+        let phase = MainPhase::new();
+        let hidden = phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        EchoRunner::new(
+            state,
+            phase.to_some_phase(),
+            (AlwaysZeroAgent::default(), AlwaysZeroAgent::default()),
+            hidden,
+        )
+        .with_clock(control)
+    }
+
+    #[test]
+    fn time_controls_round_trip() {
+        for control in TimeControl::PRESETS {
+            assert_eq!(control.to_string().parse(), Ok(control));
+        }
+
+        assert_eq!(
+            "2.5".parse(),
+            Ok(TimeControl::new(Duration::from_secs(150), Duration::ZERO))
+        );
+        assert!("0+3".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn increments_get_added_after_every_decision() {
+        let control = TimeControl::new(Duration::from_secs(60), Duration::from_secs(5));
+        let mut clock = GameClock::new(control);
+
+        clock.start(Player::Me);
+        assert!(clock.state().ticking[0]);
+        assert!(!clock.stop(Player::Me));
+
+        let remaining = clock.remaining(Player::Me);
+        assert!(remaining > Duration::from_secs(60));
+        assert_eq!(clock.remaining(Player::You), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn running_out_of_time_forfeits_the_game() {
+        let control = TimeControl::new(Duration::ZERO, Duration::from_secs(5));
+        let score = runner(control).run_game_with_score().unwrap();
+
+        // The first player runs out of time first.
+        assert_eq!(score, Score(-1));
+    }
+
+    #[test]
+    fn running_out_of_time_can_lead_to_random_decisions() {
+        let control = TimeControl::new(Duration::ZERO, Duration::ZERO)
+            .with_timeout_behaviour(TimeoutBehaviour::RandomDecision);
+
+        assert!(runner(control).run_game_with_score().is_ok());
+    }
+}
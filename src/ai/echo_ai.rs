@@ -1,15 +1,34 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tracing::Level;
 
+// std::time::Instant panics on the web, where only the gui runs games.
+#[cfg(feature = "gui")]
+use instant::Instant;
+#[cfg(not(feature = "gui"))]
+use std::time::Instant;
+
+use super::clock::{ClockState, GameClock, TimeControl, TimeoutBehaviour};
+use super::observer::GameObserver;
+use super::transcript::{DecisionStats, PhaseTranscript, Transcript};
 use crate::cfr::decision_index::DecisionIndex;
-use crate::cfr::hidden_index::{self, HiddenState};
-use crate::cfr::phase::SomePhase;
+use crate::cfr::hidden_index;
+use crate::cfr::phase::{PerPhase, SomePhase};
+use crate::cfr::position::GamePosition;
 use crate::cfr::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
 use crate::game::known_state::KnownState;
+use crate::game::record::{GameRecord, TurnRecord};
 use crate::game::types::{BattleResult, Player, Score, TurnResult};
+use crate::helpers::bitfield::Bitfield;
 use crate::helpers::pair::Pair;
 
 // {{{ Agent input
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AgentInput {
     pub phase: SomePhase,
     pub state: KnownState,
@@ -31,6 +50,16 @@ impl AgentInput {
             player,
         }
     }
+
+    /// The input some player receives in the given position.
+    pub fn from_position(position: &GamePosition, player: Player) -> Self {
+        Self::new(
+            position.phase,
+            position.state,
+            player.select(position.hidden),
+            player,
+        )
+    }
 }
 // }}}
 // {{{ Main trait
@@ -40,93 +69,1012 @@ impl AgentInput {
 pub trait EchoAgent {
     fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex;
 
+    /// Non-blocking version of `choose`, used when the game gets advanced
+    /// step by step (for instance, on the ui thread of a web build, where
+    /// no other threads are available). Returns `None` when no decision has
+    /// been made yet, in which case the same input will be provided again later.
+    ///
+    /// Agents which never block can rely on the default implementation.
+    #[inline(always)]
+    fn poll_choice(&mut self, agent_input: AgentInput) -> Option<DecisionIndex> {
+        Some(self.choose(agent_input))
+    }
+
     #[inline(always)]
     fn reveal_info(&mut self, _reveal_index: RevealIndex, _updated_score: Score) {}
 
     #[inline(always)]
     fn game_finished(&mut self) {}
+
+    /// Called whenever some clock starts or stops, when playing
+    /// with clocks (see `EchoRunner::with_clock`).
+    #[inline(always)]
+    fn clock_updated(&mut self, _clock: ClockState) {}
+
+    /// Called whenever the agent makes an illegal decision (one out of range),
+    /// right before the same input gets provided again (see `EchoRunner::with_retries`).
+    #[inline(always)]
+    fn decision_rejected(&mut self, _decision: DecisionIndex) {}
+
+    /// How much searching (nodes visited, rollouts played, ...) went into
+    /// the last decision, for agents which search. Reported in transcripts.
+    #[inline(always)]
+    fn search_effort(&mut self) -> Option<u64> {
+        None
+    }
+
+    /// Whether the agent has given up on the current game, which counts as
+    /// forfeiting it. Checked every time the agent gets asked to decide.
+    #[inline(always)]
+    fn has_resigned(&mut self) -> bool {
+        false
+    }
+}
+
+/// Allows agents to be lent to a runner, such that the same
+/// agent (and whatever it learned) can be reused across games.
+impl<A: EchoAgent + ?Sized> EchoAgent for &mut A {
+    #[inline(always)]
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        (**self).choose(agent_input)
+    }
+
+    #[inline(always)]
+    fn poll_choice(&mut self, agent_input: AgentInput) -> Option<DecisionIndex> {
+        (**self).poll_choice(agent_input)
+    }
+
+    #[inline(always)]
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        (**self).reveal_info(reveal_index, updated_score)
+    }
+
+    #[inline(always)]
+    fn game_finished(&mut self) {
+        (**self).game_finished()
+    }
+
+    #[inline(always)]
+    fn clock_updated(&mut self, clock: ClockState) {
+        (**self).clock_updated(clock)
+    }
+
+    #[inline(always)]
+    fn decision_rejected(&mut self, decision: DecisionIndex) {
+        (**self).decision_rejected(decision)
+    }
+
+    #[inline(always)]
+    fn search_effort(&mut self) -> Option<u64> {
+        (**self).search_effort()
+    }
+
+    #[inline(always)]
+    fn has_resigned(&mut self) -> bool {
+        (**self).has_resigned()
+    }
 }
 // }}}
 // {{{ Game runner
+/// Shared flag used to stop games from the outside (for instance, once the
+/// window a human is playing in gets closed). Cancelled games end with an
+/// `EchoError::Cancelled` error the next time some agent decides.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    #[inline(always)]
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// What happens once some agent keeps making illegal decisions, even after retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IllegalDecisionBehaviour {
+    /// The game gets aborted with an `EchoError::IllegalDecision` error.
+    #[default]
+    Fail,
+    /// The agent loses the game (see `Score::forfeited_by`).
+    Forfeit,
+}
+
+/// What to do with a decision received from some agent.
+enum ReceivedDecision {
+    Accepted(DecisionIndex),
+    /// The decision was illegal, and the agent should be asked again.
+    Rejected,
+    /// The game ended, with the given final score.
+    GameOver(Score),
+}
+
 /// Struct containing the data required to make two agents fight eachother.
 pub struct EchoRunner<A, B> {
-    state: KnownState,
-    phase: SomePhase,
+    position: GamePosition,
     agents: (A, B),
-    hidden_state: Pair<hidden_index::EncodingInfo>,
+
+    /// Decisions received so far during the current phase, when running step by step.
+    pending: Pair<Option<DecisionIndex>>,
+
+    /// Record of the game so far, handed to every sink once the game is over.
+    recorder: Option<(GameRecord, Vec<RecordSink>)>,
+
+    /// Limits the time agents can spend on their decisions (see `clock`).
+    clock: Option<GameClock>,
+
+    /// How many illegal decisions each agent can make in a row before the
+    /// game ends, together with what happens then.
+    retries: usize,
+    on_illegal_decision: IllegalDecisionBehaviour,
+
+    /// Illegal decisions made in a row by each agent.
+    rejections: Pair<usize>,
+
+    /// Notified whenever something happens during the game.
+    observers: Vec<Box<dyn GameObserver>>,
+
+    /// Whether the observers have been told about the start of the current phase.
+    phase_started: bool,
+
+    /// Stops the game once cancelled (see `with_cancellation`).
+    cancellation: Option<CancellationToken>,
+
+    /// Everything that happened during the game so far.
+    transcript: Transcript,
+
+    /// When each agent first got asked to decide during the current
+    /// phase, together with the stats of the decisions accepted so far.
+    asked_at: Pair<Option<Instant>>,
+    decision_stats: Pair<DecisionStats>,
+
+    /// The seed the randomness of the runner itself derives from, such
+    /// that games can be reproduced exactly (together with seeded agents).
+    seed: u64,
+    rng: StdRng,
 }
 
+/// Function the record of a game gets handed to once the game is over.
+pub type RecordSink = Box<dyn FnMut(&GameRecord)>;
+
 impl<A: EchoAgent, B: EchoAgent> EchoRunner<A, B> {
+    /// How many times agents get asked again after making an illegal decision by default.
+    pub const DEFAULT_RETRIES: usize = 2;
+
     pub fn new(
         state: KnownState,
         phase: SomePhase,
         agents: (A, B),
         hidden_state: Pair<hidden_index::EncodingInfo>,
     ) -> Self {
+        Self::from_position_unchecked(GamePosition::new(state, phase, hidden_state), agents)
+    }
+
+    /// Starts the game in an arbitrary (possibly mid-game) position, making
+    /// sure the position is consistent first (see `GamePosition::validate`).
+    pub fn from_position(position: GamePosition, agents: (A, B)) -> EchoResult<Self> {
+        position.validate()?;
+        Ok(Self::from_position_unchecked(position, agents))
+    }
+
+    fn from_position_unchecked(position: GamePosition, agents: (A, B)) -> Self {
+        let seed = rand::random();
+
         Self {
-            state,
-            phase,
+            position,
             agents,
-            hidden_state,
+            pending: [None; 2],
+            recorder: None,
+            clock: None,
+            retries: Self::DEFAULT_RETRIES,
+            on_illegal_decision: IllegalDecisionBehaviour::default(),
+            rejections: [0; 2],
+            observers: Vec::new(),
+            phase_started: false,
+            cancellation: None,
+            transcript: Transcript::new(position, seed),
+            asked_at: [None; 2],
+            decision_stats: Default::default(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
         }
     }
 
-    fn input_for(&self, player: Player) -> Option<AgentInput> {
-        let hidden = player.select(self.hidden_state);
-        let input = AgentInput::new(self.phase, self.state, hidden, player);
+    /// Seeds the randomness of the runner itself (used for instance when
+    /// replacing the decisions of agents which ran out of time). Random
+    /// otherwise, see `seed`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.transcript.seed = seed;
+        self
+    }
 
-        Some(input)
+    /// The seed the randomness of the runner derives from.
+    #[inline(always)]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Notifies the given observer whenever something happens during the game.
+    pub fn with_observer(mut self, observer: impl GameObserver + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Sets how many times agents get asked again after making an illegal
+    /// decision, and what happens once they run out of retries.
+    pub fn with_retries(mut self, retries: usize, behaviour: IllegalDecisionBehaviour) -> Self {
+        self.retries = retries;
+        self.on_illegal_decision = behaviour;
+        self
+    }
+
+    /// Stops the game once the given token gets cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Plays the game with chess style clocks (see `clock`).
+    pub fn with_clock(mut self, control: TimeControl) -> Self {
+        self.clock = Some(GameClock::new(control));
+        self
+    }
+
+    /// The time left on the clocks of both agents, when playing with clocks.
+    pub fn clock(&self) -> Option<ClockState> {
+        self.clock.as_ref().map(GameClock::state)
+    }
+
+    /// The position the game is currently in.
+    pub fn position(&self) -> &GamePosition {
+        &self.position
+    }
+
+    /// Everything that happened during the game so far.
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    /// Writes a record of the game to the given writer once the game is over.
+    /// The turns and result of the given record get filled in by the runner.
+    pub fn record_to(mut self, record: GameRecord, mut writer: impl io::Write + 'static) -> Self {
+        let sink: RecordSink = Box::new(move |record| {
+            if let Err(error) = write!(writer, "{record}").and_then(|_| writer.flush()) {
+                tracing::event!(Level::WARN, "Failed to write game record: {error}");
+            }
+        });
+
+        self.recorder = Some((record, vec![sink]));
+        self
+    }
+
+    /// Additionally hands the record of the game to the given function once
+    /// the game is over. Does nothing unless the record was set up using `record_to`.
+    pub fn also_record_with(mut self, sink: impl FnMut(&GameRecord) + 'static) -> Self {
+        if let Some((_, sinks)) = &mut self.recorder {
+            sinks.push(Box::new(sink));
+        }
+
+        self
+    }
+
+    /// Appends the choices made during the current turn to the record.
+    /// Must be called during the seer phase (the last phase of every turn).
+    fn record_turn(&mut self, decisions: Pair<DecisionIndex>) -> EchoResult<()> {
+        let Some((record, _)) = &mut self.recorder else {
+            return Ok(());
+        };
+
+        let PerPhase::Seer(phase) = self.position.phase else {
+            return Ok(());
+        };
+
+        let creatures = self.position.hidden.try_map(|hidden| {
+            hidden.get_sabotage().ok_or(EchoError::InvalidState(
+                "The creature choices must be known during the seer phase".to_string(),
+            ))
+        })?;
+        let seer_player = Player::PLAYERS
+            .into_iter()
+            .find(|player| player.select(creatures).len() == 2);
+
+        let seer_pick = match seer_player {
+            Some(player) => Some(
+                player
+                    .select(decisions)
+                    .decode_seer_index(player.select(creatures))?,
+            ),
+            None => None,
+        };
+
+        record.turns.push(TurnRecord {
+            creatures,
+            edicts: phase.edict_choices,
+            sabotage_guesses: phase.sabotage_choices,
+            seer_pick,
+        });
+
+        Ok(())
+    }
+
+    fn write_record(&mut self, score: Score) {
+        if let Some((mut record, sinks)) = self.recorder.take() {
+            record.result = Some(score);
+
+            for mut sink in sinks {
+                sink(&record);
+            }
+        }
+    }
+
+    /// Makes sure the state of the game is consistent. Only checked in debug builds.
+    fn debug_validate(&self) {
+        debug_assert_eq!(self.position.validate(), Ok(()));
     }
 
-    pub fn run_game(mut self) -> Option<BattleResult> {
+    fn input_for(&self, player: Player) -> AgentInput {
+        AgentInput::from_position(&self.position, player)
+    }
+
+    // {{{ Clocks
+    fn notify_clock(&mut self, clock: ClockState) {
+        self.agents.0.clock_updated(clock);
+        self.agents.1.clock_updated(clock);
+    }
+
+    /// Starts the clock of the given player, unless it is already running.
+    fn start_clock(&mut self, player: Player) {
+        let Some(clock) = &mut self.clock else {
+            return;
+        };
+
+        if !clock.is_running(player) {
+            clock.start(player);
+
+            let state = clock.state();
+            self.notify_clock(state);
+        }
+    }
+
+    /// Stops the clock of some player once they have decided. Returns the
+    /// decision to play, which gets replaced by a random one if the player
+    /// ran out of time, or `None` if running out of time forfeits the game.
+    fn punch_clock(&mut self, player: Player, decision: DecisionIndex) -> Option<DecisionIndex> {
+        let Some(clock) = &mut self.clock else {
+            return Some(decision);
+        };
+
+        let flagged = clock.stop(player);
+        let state = clock.state();
+        self.notify_clock(state);
+
+        if !flagged {
+            return Some(decision);
+        }
+
+        tracing::event!(Level::INFO, "{player:?} ran out of time");
+
+        match state.control.on_timeout {
+            TimeoutBehaviour::Forfeit => None,
+            TimeoutBehaviour::RandomDecision => {
+                let count =
+                    player.select(self.position.phase.decision_counts(&self.position.state));
+                Some(DecisionIndex(self.rng.gen_range(0..count)))
+            }
+        }
+    }
+
+    // }}}
+    // {{{ Observers
+    /// Tells the observers about the start of the current phase, unless they know already.
+    fn start_phase(&mut self) {
+        if self.phase_started {
+            return;
+        }
+
+        self.phase_started = true;
+        for observer in &mut self.observers {
+            observer.on_phase_start(&self.position);
+        }
+    }
+
+    /// Notifies everyone once the game is over.
+    fn finish_game(&mut self, score: Score) {
+        self.transcript.result = Some(score);
+        self.agents.0.game_finished();
+        self.agents.1.game_finished();
+        self.write_record(score);
+
+        for observer in &mut self.observers {
+            observer.on_game_end(score);
+        }
+    }
+    // }}}
+    // {{{ Decisions
+    /// Ends the game with the given player losing, returning the final score.
+    fn forfeit(&mut self, player: Player) -> Score {
+        let score = self.position.state.score.forfeited_by(player);
+        tracing::event!(Level::INFO, "{player:?} forfeited the game");
+        self.transcript.forfeited_by = Some(player);
+
+        self.finish_game(score);
+        score
+    }
+
+    /// Checks whether the game should stop after asking some player to decide,
+    /// either because it got cancelled, or because the player resigned (in
+    /// which case the final score gets returned).
+    fn interrupted(&mut self, player: Player) -> EchoResult<Option<Score>> {
+        if self
+            .cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+        {
+            tracing::event!(Level::INFO, "The game got cancelled");
+
+            // Agents might keep state across games, which must be reset either way.
+            self.agents.0.game_finished();
+            self.agents.1.game_finished();
+
+            return Err(EchoError::Cancelled);
+        }
+
+        let resigned = match player {
+            Player::Me => self.agents.0.has_resigned(),
+            Player::You => self.agents.1.has_resigned(),
+        };
+
+        if resigned {
+            tracing::event!(Level::INFO, "{player:?} resigned");
+            return Ok(Some(self.forfeit(player)));
+        }
+
+        Ok(None)
+    }
+
+    /// Starts timing the decision of some player, unless already timing it.
+    fn start_timer(&mut self, player: Player) {
+        let asked_at = player.select_mut(&mut self.asked_at);
+        if asked_at.is_none() {
+            *asked_at = Some(Instant::now());
+        }
+    }
+
+    /// Asks some agent for a decision, blocking until it has made one.
+    #[inline(always)]
+    fn choose(&mut self, player: Player) -> DecisionIndex {
+        let input = self.input_for(player);
+        match player {
+            Player::Me => self.agents.0.choose(input),
+            Player::You => self.agents.1.choose(input),
+        }
+    }
+
+    /// Asks some agent for a decision, without blocking.
+    #[inline(always)]
+    fn poll_choice(&mut self, player: Player) -> Option<DecisionIndex> {
+        let input = self.input_for(player);
+        match player {
+            Player::Me => self.agents.0.poll_choice(input),
+            Player::You => self.agents.1.poll_choice(input),
+        }
+    }
+
+    /// Makes sure the decision some agent made is legal, and punches their
+    /// clock once it is. Illegal decisions get rejected until the agent runs
+    /// out of retries, after which the game either fails or gets forfeited.
+    fn receive_decision(
+        &mut self,
+        player: Player,
+        decision: DecisionIndex,
+    ) -> EchoResult<ReceivedDecision> {
+        let count = player.select(self.position.phase.decision_counts(&self.position.state));
+
+        if decision.0 >= count {
+            let rejections = player.select_mut(&mut self.rejections);
+            *rejections += 1;
+
+            tracing::event!(
+                Level::WARN,
+                "{player:?} made the illegal decision {} (out of {count})",
+                decision.0
+            );
+
+            if *rejections <= self.retries {
+                match player {
+                    Player::Me => self.agents.0.decision_rejected(decision),
+                    Player::You => self.agents.1.decision_rejected(decision),
+                }
+
+                return Ok(ReceivedDecision::Rejected);
+            }
+
+            return match self.on_illegal_decision {
+                IllegalDecisionBehaviour::Fail => Err(EchoError::IllegalDecision {
+                    player,
+                    decision: decision.0,
+                    count,
+                }),
+                IllegalDecisionBehaviour::Forfeit => {
+                    Ok(ReceivedDecision::GameOver(self.forfeit(player)))
+                }
+            };
+        }
+
+        player.set_selection(&mut self.rejections, 0);
+
+        let stats = DecisionStats {
+            time: player
+                .select_mut(&mut self.asked_at)
+                .take()
+                .map_or_else(Default::default, |asked_at| asked_at.elapsed()),
+            nodes: match player {
+                Player::Me => self.agents.0.search_effort(),
+                Player::You => self.agents.1.search_effort(),
+            },
+        };
+        player.set_selection(&mut self.decision_stats, stats);
+
+        let Some(decision) = self.punch_clock(player, decision) else {
+            return Ok(ReceivedDecision::GameOver(self.forfeit(player)));
+        };
+
+        for observer in &mut self.observers {
+            observer.on_decision(&self.position, player, decision);
+        }
+
+        Ok(ReceivedDecision::Accepted(decision))
+    }
+    // }}}
+
+    /// Runs the game until the end, returning the result
+    /// from the perspective of the first agent.
+    ///
+    /// Fails if any of the agents keeps making illegal decisions
+    /// (unless configured otherwise, see `with_retries`).
+    pub fn run_game(self) -> EchoResult<BattleResult> {
+        self.run_game_with_score().map(Score::to_battle_result)
+    }
+
+    /// Similar to `run_game`, but returns the final score instead.
+    pub fn run_game_with_score(mut self) -> EchoResult<Score> {
+        self.play()
+    }
+
+    /// Similar to `run_game`, but returns a transcript of the entire game instead.
+    pub fn run_game_with_transcript(mut self) -> EchoResult<Transcript> {
+        self.play()?;
+        Ok(self.transcript)
+    }
+
+    /// Runs the game until the end, returning the final score.
+    fn play(&mut self) -> EchoResult<Score> {
         let _guard = tracing::span!(Level::DEBUG, "Echo fight");
+        tracing::event!(Level::DEBUG, "Playing with seed {}", self.seed);
+
         loop {
             let _guard = tracing::span!(
                 Level::DEBUG,
                 "Phase",
-                kind = format!("{:?}", self.phase.tag())
+                kind = format!("{:?}", self.position.phase.tag())
             );
 
-            let my = self.agents.0.choose(self.input_for(Player::Me)?);
-            let yours = self.agents.1.choose(self.input_for(Player::You)?);
-            let decisions = [my, yours];
+            self.start_phase();
 
-            tracing::event!(Level::DEBUG, "Received both inputs");
+            let mut decisions = [DecisionIndex(0); 2];
+            for player in Player::PLAYERS {
+                self.start_clock(player);
+                self.start_timer(player);
 
-            let (reveal_index, result) = self.phase.advance(
-                self.state,
-                self.hidden_state.map(HiddenState::from_encoding_info),
-                decisions,
-                false,
-            )?;
+                let decision = loop {
+                    let decision = self.choose(player);
+                    if let Some(score) = self.interrupted(player)? {
+                        return Ok(score);
+                    }
 
-            tracing::event!(Level::DEBUG, "Advanced state");
+                    match self.receive_decision(player, decision)? {
+                        ReceivedDecision::Accepted(decision) => break decision,
+                        ReceivedDecision::Rejected => {}
+                        ReceivedDecision::GameOver(score) => return Ok(score),
+                    }
+                };
 
-            let score = match result {
-                TurnResult::Finished(score) => score,
-                TurnResult::Unfinished((state, _, _)) => state.score,
-            };
+                player.set_selection(&mut decisions, decision);
+            }
+
+            if let Some(score) = self.advance(decisions)? {
+                return Ok(score);
+            }
+        }
+    }
 
-            self.agents.0.reveal_info(reveal_index, score);
-            self.agents.1.reveal_info(reveal_index, score);
-            tracing::event!(Level::DEBUG, "Pushed reveal indices");
+    /// Advances the game without blocking, asking the agents for
+    /// their decisions using `EchoAgent::poll_choice`. The phase only
+    /// changes once both agents have made up their minds.
+    ///
+    /// Returns the final score once the game is over, after which
+    /// the runner must not be stepped anymore.
+    pub fn step(&mut self) -> EchoResult<Option<Score>> {
+        self.start_phase();
 
-            match result {
-                TurnResult::Finished(_) => {
-                    tracing::event!(Level::DEBUG, "Game finished");
+        for player in Player::PLAYERS {
+            if player.select(self.pending).is_some() {
+                continue;
+            }
 
-                    self.agents.0.game_finished();
-                    self.agents.1.game_finished();
+            self.start_clock(player);
+            self.start_timer(player);
 
-                    return Some(score.to_battle_result());
-                }
-                TurnResult::Unfinished((state, hidden, phase)) => {
-                    self.state = state;
-                    self.hidden_state = hidden;
-                    self.phase = phase;
+            let decision = self.poll_choice(player);
+            if let Some(score) = self.interrupted(player)? {
+                return Ok(Some(score));
+            }
+
+            let Some(decision) = decision else {
+                continue;
+            };
+
+            // Rejected agents get asked again during the next step.
+            match self.receive_decision(player, decision)? {
+                ReceivedDecision::Accepted(decision) => {
+                    player.set_selection(&mut self.pending, Some(decision));
                 }
+                ReceivedDecision::Rejected => {}
+                ReceivedDecision::GameOver(score) => return Ok(Some(score)),
+            }
+        }
+
+        match self.pending {
+            [Some(my), Some(yours)] => {
+                self.pending = [None; 2];
+                self.advance([my, yours])
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Hands back the agents, such that they can be reused in other games.
+    pub fn into_agents(self) -> (A, B) {
+        self.agents
+    }
+
+    /// Moves on to the next phase, once both agents have made their decisions.
+    /// Returns the final score if the game is over.
+    fn advance(&mut self, decisions: Pair<DecisionIndex>) -> EchoResult<Option<Score>> {
+        self.debug_validate();
+        tracing::event!(
+            Level::DEBUG,
+            "Received both inputs: {}",
+            Player::PLAYERS
+                .map(|player| player.select(decisions).describe(
+                    &self.position.state,
+                    &self.position.phase,
+                    player,
+                    player.select(self.position.hidden),
+                ))
+                .join(" / ")
+        );
+
+        self.record_turn(decisions)?;
+
+        let turn_ends = matches!(self.position.phase, PerPhase::Seer(_));
+        let (reveal_index, result) = self.position.advance(decisions)?;
+
+        tracing::event!(Level::DEBUG, "Advanced state");
+
+        let score = match result {
+            TurnResult::Finished(score) => score,
+            TurnResult::Unfinished(position) => position.state.score,
+        };
+
+        self.agents.0.reveal_info(reveal_index, score);
+        self.agents.1.reveal_info(reveal_index, score);
+        tracing::event!(
+            Level::DEBUG,
+            "Pushed reveal indices: {}",
+            reveal_index.describe(&self.position.state, &self.position.phase)
+        );
+
+        self.transcript.phases.push(PhaseTranscript {
+            position: self.position,
+            decisions,
+            reveal: reveal_index,
+            score,
+            stats: self.decision_stats,
+        });
+
+        let finished = matches!(result, TurnResult::Finished(_));
+        for observer in &mut self.observers {
+            observer.on_reveal(&self.position, reveal_index, score);
+
+            if turn_ends || finished {
+                observer.on_turn_end(self.position.state.battlefields.current, score);
+            }
+        }
+
+        match result {
+            TurnResult::Finished(_) => {
+                tracing::event!(Level::DEBUG, "Game finished");
+                self.finish_game(score);
+
+                Ok(Some(score))
+            }
+            TurnResult::Unfinished(position) => {
+                self.position = position;
+                self.phase_started = false;
+
+                Ok(None)
             }
         }
     }
 }
 // }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::always_zero_agent::AlwaysZeroAgent;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::cfr::phase::{MainPhase, Phase};
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// Makes a number of illegal decisions, before always playing the first
+    /// choice. Pretends to search a single node for every decision.
+    #[derive(Debug, Default)]
+    struct StubbornAgent {
+        illegal: usize,
+        rejected: usize,
+    }
+
+    impl EchoAgent for StubbornAgent {
+        fn choose(&mut self, _agent_input: AgentInput) -> DecisionIndex {
+            if self.illegal == 0 {
+                return DecisionIndex(0);
+            }
+
+            self.illegal -= 1;
+            DecisionIndex(usize::MAX)
+        }
+
+        fn decision_rejected(&mut self, _decision: DecisionIndex) {
+            self.rejected += 1;
+        }
+
+        fn search_effort(&mut self) -> Option<u64> {
+            Some(1)
+        }
+    }
+
+    fn runner(agent: &mut StubbornAgent) -> EchoRunner<AlwaysZeroAgent, &mut StubbornAgent> {
+        let state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+        let phase = MainPhase::new();
+        let hidden = phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        EchoRunner::new(
+            state,
+            phase.to_some_phase(),
+            (AlwaysZeroAgent::default(), agent),
+            hidden,
+        )
+    }
+
+    #[test]
+    fn illegal_decisions_get_retried() {
+        let mut agent = StubbornAgent {
+            illegal: EchoRunner::<AlwaysZeroAgent, AlwaysZeroAgent>::DEFAULT_RETRIES,
+            rejected: 0,
+        };
+
+        assert!(runner(&mut agent).run_game_with_score().is_ok());
+        assert_eq!(agent.rejected, 2);
+    }
+
+    #[test]
+    fn running_out_of_retries_ends_the_game() {
+        let mut agent = StubbornAgent {
+            illegal: usize::MAX,
+            rejected: 0,
+        };
+
+        let error = runner(&mut agent).run_game_with_score().unwrap_err();
+        assert!(matches!(
+            error,
+            EchoError::IllegalDecision {
+                player: Player::You,
+                ..
+            }
+        ));
+
+        let score = runner(&mut agent)
+            .with_retries(0, IllegalDecisionBehaviour::Forfeit)
+            .run_game_with_score()
+            .unwrap();
+        assert_eq!(score, Score(1));
+    }
+
+    /// Plays the first choice a number of times, before either
+    /// cancelling the game (when given a token), or resigning.
+    struct QuittingAgent {
+        decisions: usize,
+        cancellation: Option<CancellationToken>,
+    }
+
+    impl EchoAgent for QuittingAgent {
+        fn choose(&mut self, _agent_input: AgentInput) -> DecisionIndex {
+            if self.decisions == 0 {
+                if let Some(token) = &self.cancellation {
+                    token.cancel();
+                }
+            } else {
+                self.decisions -= 1;
+            }
+
+            DecisionIndex(0)
+        }
+
+        fn has_resigned(&mut self) -> bool {
+            self.decisions == 0 && self.cancellation.is_none()
+        }
+    }
+
+    /// Counts the events of every kind it gets notified about.
+    #[derive(Debug, Default)]
+    struct CountingObserver {
+        counts: Rc<RefCell<[usize; 5]>>,
+    }
+
+    impl GameObserver for CountingObserver {
+        fn on_phase_start(&mut self, _position: &GamePosition) {
+            self.counts.borrow_mut()[0] += 1;
+        }
+
+        fn on_decision(
+            &mut self,
+            _position: &GamePosition,
+            _player: Player,
+            _decision: DecisionIndex,
+        ) {
+            self.counts.borrow_mut()[1] += 1;
+        }
+
+        fn on_reveal(&mut self, _position: &GamePosition, _reveal: RevealIndex, _score: Score) {
+            self.counts.borrow_mut()[2] += 1;
+        }
+
+        fn on_turn_end(&mut self, _turn: usize, _score: Score) {
+            self.counts.borrow_mut()[3] += 1;
+        }
+
+        fn on_game_end(&mut self, _score: Score) {
+            self.counts.borrow_mut()[4] += 1;
+        }
+    }
+
+    #[test]
+    fn observers_get_notified_about_everything() {
+        let mut agent = StubbornAgent::default();
+        let observer = CountingObserver::default();
+        let counts = observer.counts.clone();
+
+        runner(&mut agent)
+            .with_observer(observer)
+            .run_game_with_score()
+            .unwrap();
+
+        let [phases, decisions, reveals, turns, games] = *counts.borrow();
+        assert_eq!(phases, reveals);
+        assert_eq!(decisions, 2 * phases);
+        assert!(turns > 0 && turns <= Battlefields::COUNT);
+        assert_eq!(games, 1);
+    }
+
+    #[test]
+    fn transcripts_contain_every_phase() {
+        let mut agent = StubbornAgent::default();
+        let transcript = runner(&mut agent).run_game_with_transcript().unwrap();
+
+        assert!(transcript.is_finished());
+        assert_eq!(transcript.forfeited_by, None);
+
+        let first = transcript.phases[0].position;
+        assert_eq!(first.state, transcript.initial.state);
+
+        let trajectory = transcript.score_trajectory();
+        assert!(!trajectory.is_empty() && trajectory.len() <= Battlefields::COUNT);
+        assert_eq!(trajectory.last().copied(), transcript.result);
+
+        let [mine, yours] = transcript.agent_stats();
+        assert_eq!(mine.decisions, transcript.phases.len());
+        assert_eq!(mine.nodes_per_decision(), None);
+        assert_eq!(yours.nodes_per_decision(), Some(1.0));
+        assert!(yours.max_time <= yours.total_time);
+    }
+
+    #[test]
+    fn games_can_start_mid_game() {
+        let mut position = runner(&mut StubbornAgent::default()).position;
+        let (_, result) = position.advance([DecisionIndex(0); 2]).unwrap();
+        let TurnResult::Unfinished(mid_game) = result else {
+            panic!("The game cannot end after a single phase");
+        };
+
+        let agents = (AlwaysZeroAgent::default(), AlwaysZeroAgent::default());
+        let runner = EchoRunner::from_position(mid_game, agents).unwrap();
+        assert!(runner.run_game_with_score().is_ok());
+
+        position.hidden[1] = position.hidden[0];
+        assert!(EchoRunner::from_position(position, agents).is_err());
+    }
+
+    #[test]
+    fn seeded_games_can_be_reproduced() {
+        let position = *runner(&mut StubbornAgent::default()).position();
+        let play = |seed: u64| {
+            let agents = (
+                RandomAgent::new(StdRng::seed_from_u64(seed)),
+                RandomAgent::new(StdRng::seed_from_u64(seed + 1)),
+            );
+
+            EchoRunner::from_position(position, agents)
+                .unwrap()
+                .with_seed(seed)
+                .run_game_with_transcript()
+                .unwrap()
+        };
+
+        let decisions = |transcript: &Transcript| {
+            let phases = transcript.phases.iter();
+            phases.map(|phase| phase.decisions).collect::<Vec<_>>()
+        };
+
+        let transcript = play(7);
+        assert_eq!(transcript.seed, 7);
+        assert_eq!(decisions(&transcript), decisions(&play(7)));
+    }
+
+    #[test]
+    fn games_can_be_cancelled_or_resigned() {
+        let state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+        let phase = MainPhase::new();
+        let hidden = phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        let token = CancellationToken::default();
+        let agent = QuittingAgent {
+            decisions: 3,
+            cancellation: Some(token.clone()),
+        };
+        let agents = (AlwaysZeroAgent::default(), agent);
+        let runner = EchoRunner::new(state, phase.to_some_phase(), agents, hidden);
+        let result = runner.with_cancellation(token).run_game_with_score();
+        assert_eq!(result, Err(EchoError::Cancelled));
+
+        let agent = QuittingAgent {
+            decisions: 3,
+            cancellation: None,
+        };
+        let agents = (AlwaysZeroAgent::default(), agent);
+        let transcript = EchoRunner::new(state, phase.to_some_phase(), agents, hidden)
+            .run_game_with_transcript()
+            .unwrap();
+
+        assert_eq!(transcript.forfeited_by, Some(Player::You));
+        // The agent resigns while deciding during the third phase.
+        assert_eq!(transcript.phases.len(), 2);
+        assert!(transcript.result.unwrap().0 > 0);
+    }
+}
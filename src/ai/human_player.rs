@@ -1,20 +1,41 @@
-use super::echo_ai::{AgentInput, EchoAgent};
-use super::textures::AppTextures;
+use super::animations::{AnimationKind, Animations};
+use super::clock::{ClockState, TimeControl, TimeoutBehaviour};
+use super::echo_ai::{AgentInput, CancellationToken, EchoAgent};
+use super::locale::{Language, Locale};
+use super::session::SessionStats;
+use super::settings::{Settings, Theme};
+use super::strategy_hints::StrategyProvider;
+use super::textures::{AppTextures, CardTexture};
+use crate::cfr::background::BackgroundTraining;
+use crate::cfr::belief::Beliefs;
+use crate::cfr::decision::Probability;
 use crate::cfr::decision_index::DecisionIndex;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cfr::hidden_index::EncodingInfo;
+use crate::cfr::history::{History, PlayerTurnHistory};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::cfr::phase::{MainPhase, Phase};
 use crate::cfr::phase::{PerPhase, PhaseTag};
 use crate::cfr::reveal_index::RevealIndex;
-use crate::game::battlefield::Battlefield;
+use crate::cfr::review::{review, DecisionRecord, ReviewedDecision, Verdict};
+use crate::game::battlefield::{Battlefield, Battlefields};
 use crate::game::creature::{Creature, CreatureSet};
 use crate::game::edict::{Edict, EdictSet};
+use crate::game::known_state::KnownState;
 use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::simulate::negated_by;
 use crate::game::status_effect::{StatusEffect, StatusEffectSet};
 use crate::game::types::{Player, Score};
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::pair::Pair;
-use egui::{Grid, Ui, Vec2, Widget};
-use egui_extras::RetainedImage;
+use egui::{Grid, Key, Modifiers, Rect, Sense, Ui, Vec2, Widget};
+use instant::Instant;
+use rand::Rng;
+use std::fmt::{Display, Write};
 use std::format;
-use std::sync::mpsc::{Receiver, Sender};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::time::Duration;
 use tracing::Level;
 
 // {{{ Agent type
@@ -24,11 +45,29 @@ enum RequestPayload {
     StateAdvanced(AgentInput),
     Reveal(RevealIndex, Score),
     GameFinished,
+    ClockUpdated(ClockState),
+}
+
+/// The type of payloads sent from the gui to the human agent.
+#[derive(Debug, Clone, Copy)]
+enum ResponsePayload {
+    Decision(DecisionIndex),
+    Resign,
 }
 
 pub struct HumanAgent {
     sender: Sender<RequestPayload>,
-    receiver: Receiver<DecisionIndex>,
+    receiver: Receiver<ResponsePayload>,
+
+    /// Whether the human gave up on the game (see `EchoAgent::has_resigned`).
+    resigned: bool,
+
+    /// Shared with the bus, see `UIBus::cancellation`.
+    cancellation: CancellationToken,
+
+    /// Whether the current input has already been sent to the gui
+    /// (only relevant when the agent gets polled instead of blocked on).
+    awaiting_decision: bool,
 }
 // }}}
 // {{{ UI types
@@ -38,13 +77,98 @@ struct PartialMainPhaseChoice {
     edict: Option<Edict>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UITab {
     CardPreview,
     Field,
     Effects,
     History,
     DebugInfo,
+    Settings,
+    Beliefs,
+    Training,
+}
+
+impl UITab {
+    /// Tabs in the order they can be focused using F1..F8.
+    pub const SHORTCUT_ORDER: [UITab; 8] = [
+        UITab::Field,
+        UITab::Effects,
+        UITab::History,
+        UITab::CardPreview,
+        UITab::DebugInfo,
+        UITab::Settings,
+        UITab::Beliefs,
+        UITab::Training,
+    ];
+
+    pub const SHORTCUT_KEYS: [Key; 8] = [
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+    ];
+}
+
+/// The kinds of opponents the human can pick on the start screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpponentKind {
+    Random,
+    OpponentModel,
+    Blueprint,
+}
+
+impl OpponentKind {
+    pub const OPPONENTS: [OpponentKind; 3] = [
+        OpponentKind::Random,
+        OpponentKind::OpponentModel,
+        OpponentKind::Blueprint,
+    ];
+
+    /// The opponents which do not need anything to be loaded first.
+    pub const BUILTIN: [OpponentKind; 2] = [OpponentKind::Random, OpponentKind::OpponentModel];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OpponentKind::Random => "Random",
+            OpponentKind::OpponentModel => "Opponent model",
+            OpponentKind::Blueprint => "CFR blueprint",
+        }
+    }
+}
+
+/// Starts a new game against some opponent (played with the given time
+/// control, if any), returning the bus the gui can use to talk to the
+/// human agent taking part in it.
+///
+/// Returns `None` if the given opponent is not available.
+pub type GameLauncher = Box<dyn FnMut(OpponentKind, Option<TimeControl>) -> Option<UIBus>>;
+
+/// Advances a game running on the ui thread as far as it can go without
+/// blocking. Returns `false` once the game is over.
+///
+/// Used on the web, where games cannot be run on a separate thread.
+pub type GameDriver = Box<dyn FnMut() -> bool>;
+
+/// Things the user can ask for once a game is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MenuRequest {
+    Rematch,
+    StartScreen,
+}
+
+/// State of the screen shown before a game starts.
+struct StartScreen {
+    launcher: GameLauncher,
+    opponent: OpponentKind,
+
+    /// The opponents the launcher can start games against.
+    opponents: Vec<OpponentKind>,
+    error: Option<String>,
 }
 
 /// Holds all the state of the gui!
@@ -54,7 +178,49 @@ pub enum UITab {
 /// the main state at the same time afaik.
 pub struct GUIApp {
     tab_tree: egui_dock::Tree<UITab>,
-    state: UIState,
+    start_screen: StartScreen,
+
+    /// The game currently in progress (`None` while on the start screen).
+    state: Option<UIState>,
+
+    // Resources which outlive individual games. These get
+    // moved inside the `UIState` while a game is in progress.
+    textures: Option<AppTextures>,
+    strategy_provider: Option<Box<dyn StrategyProvider>>,
+    settings: Settings,
+
+    /// Results of the games played against the current opponent, counting
+    /// every rematch (the human being the first agent, see `session`).
+    session: SessionStats,
+}
+
+/// Cards which can be dragged from the hand onto the play area.
+#[derive(Debug, Clone, Copy)]
+enum DraggedCard {
+    Creature(Creature),
+    Edict(Edict),
+}
+
+/// Card states which must stay readable without perceiving colors,
+/// drawn on top of the cards (see `Settings::colorblind_cues`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CardBadge {
+    /// The effect of the creature got negated by the opposing creature.
+    Negated,
+    /// The creature got guessed by the sabotage of the opponent.
+    Sabotaged,
+    /// The creature got the bonus of the battlefield it was played on.
+    BattlefieldBonus,
+}
+
+impl CardBadge {
+    fn icon(self) -> &'static str {
+        match self {
+            CardBadge::Negated => "🚫",
+            CardBadge::Sabotaged => "🎯",
+            CardBadge::BattlefieldBonus => "⭐",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -67,25 +233,23 @@ enum HoveredCard {
 
 // Holds stuff required for communication with the ui thread.
 pub struct UIBus {
-    sender: Sender<DecisionIndex>,
+    sender: Sender<ResponsePayload>,
     receiver: Receiver<RequestPayload>,
-}
 
-/// A value summarizing all the choices a player made during an entire turn.
-///
-/// Some of the data might not yet be available, so we use options everywhere.
-#[derive(Debug, Clone, Copy, Default)]
-struct PlayerHistoryEntry {
-    creature: Option<Creature>,
-    edict: Option<Edict>,
-    sabotage: Option<Creature>,
+    /// Cancelled once the bus gets dropped (for instance, when the window gets
+    /// closed mid-game), such that the runner does not wait on the gui forever.
+    cancellation: CancellationToken,
+
+    /// Runs the game, if it does not have a thread of its own.
+    driver: Option<GameDriver>,
 }
 
-/// A value summarizing data about a given turn, be it in the past or the future.
-#[derive(Debug, Clone, Copy, Default)]
-struct HistoryEntry {
-    score: Option<Score>,
-    choices: Pair<PlayerHistoryEntry>,
+/// Training started from the training tab, together
+/// with the position the strategy gets shown for.
+struct TrainingRun {
+    state: KnownState,
+    hand: CreatureSet,
+    training: BackgroundTraining,
 }
 
 /// State used to render the contents of the individual ui tabs.
@@ -93,35 +257,146 @@ struct UIState {
     // Received from the agent
     input: AgentInput,
     game_finished: bool,
+    menu_request: Option<MenuRequest>,
+
+    /// Whether the human gave up on the game.
+    resigned: bool,
 
     // Internal state
-    history: [HistoryEntry; 4],
+    history: History,
     partial_main_choice: Option<PartialMainPhaseChoice>,
     communication: UIBus,
     decision_sent: bool,
 
+    /// Description of the information revealed at the end of the last phase.
+    last_reveal: Option<String>,
+
+    /// The last known state of the clocks (when playing with clocks),
+    /// together with the time it was received at.
+    clock: Option<(ClockState, Instant)>,
+
+    /// What the opponent might be holding (see `cfr::belief`). Resets
+    /// to the start of the current phase if it gets out of sync.
+    beliefs: Option<Beliefs>,
+    last_decision: Option<DecisionIndex>,
+
+    /// Every decision taken so far (apart from forced ones), which
+    /// can get reviewed once the game ends (see `cfr::review`).
+    decision_records: Vec<DecisionRecord>,
+    review: Option<Result<Vec<ReviewedDecision>, String>>,
+
+    /// Training started from the training tab (see `cfr::background`).
+    training: Option<TrainingRun>,
+    training_turns: usize,
+
     // Ui state
     textures: AppTextures,
+    settings: Settings,
+
+    /// The size cards currently get drawn at (see `fit_cards`).
+    card_size: f32,
     hovered_card: Option<HoveredCard>,
+    animations: Animations,
+
+    /// Message describing the outcome of the last match export.
+    export_status: Option<String>,
+
+    /// Results of the session this game is part of (see `GUIApp::session`).
+    session: SessionStats,
+
+    // Strategy hints
+    strategy_provider: Option<Box<dyn StrategyProvider>>,
+    strategy_hints: Option<Vec<Probability>>,
+    show_strategy_hints: bool,
 }
 // }}}
 // {{{ Agent implementation
-impl RequestPayload {
-    pub fn get_input(&self) -> Option<AgentInput> {
-        match self {
-            Self::StateAdvanced(input) => Some(*input),
-            _ => None,
+impl UIBus {
+    fn new(sender: Sender<ResponsePayload>, receiver: Receiver<RequestPayload>) -> Self {
+        Self {
+            sender,
+            receiver,
+            cancellation: CancellationToken::default(),
+            driver: None,
+        }
+    }
+
+    /// The token the runner of the game must be stopped by
+    /// (see `EchoRunner::with_cancellation`).
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Makes the gui advance the game itself every frame,
+    /// instead of relying on it running on a different thread.
+    pub fn driven_by(mut self, driver: GameDriver) -> Self {
+        self.driver = Some(driver);
+        self
+    }
+
+    /// Returns the next payload, if one is available right away.
+    fn try_recv(&mut self) -> Option<RequestPayload> {
+        if let Some(driver) = &mut self.driver {
+            driver();
+        }
+
+        self.receiver.try_recv().ok()
+    }
+
+    /// Waits for the next payload.
+    fn recv(&mut self) -> RequestPayload {
+        let Some(driver) = &mut self.driver else {
+            return self.receiver.recv().unwrap();
+        };
+
+        loop {
+            let running = driver();
+            if let Ok(payload) = self.receiver.try_recv() {
+                return payload;
+            }
+
+            assert!(
+                running,
+                "The game finished without sending anything to the gui"
+            );
         }
     }
 }
 
-impl UIBus {
-    fn new(sender: Sender<DecisionIndex>, receiver: Receiver<RequestPayload>) -> Self {
-        Self { sender, receiver }
+impl Drop for UIBus {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
     }
 }
 
 impl HumanAgent {
+    /// Sends something to the gui. The gui might have been closed already, in
+    /// which case the runner finds out through the cancellation token instead.
+    fn send(&self, payload: RequestPayload) {
+        if self.sender.send(payload).is_err() {
+            tracing::event!(Level::DEBUG, "The gui is gone, dropping {payload:?}");
+        }
+    }
+
+    /// Handles some message received from the gui, returning the decision it
+    /// contains (if any). Resigning still requires some decision to be returned
+    /// to the runner, which is going to be ignored anyway.
+    fn accept(&mut self, payload: ResponsePayload) -> DecisionIndex {
+        match payload {
+            ResponsePayload::Decision(decision) => decision,
+            ResponsePayload::Resign => {
+                self.resigned = true;
+                DecisionIndex::default()
+            }
+        }
+    }
+
+    /// The token the runner of the game must be stopped by (the same one
+    /// the bus holds, see `UIBus::cancellation`).
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
     pub fn create() -> (Self, UIBus) {
         let decisions = std::sync::mpsc::channel();
         let input = std::sync::mpsc::channel();
@@ -130,6 +405,9 @@ impl HumanAgent {
         let res = Self {
             sender: input.0,
             receiver: decisions.1,
+            resigned: false,
+            cancellation: ui_bus.cancellation(),
+            awaiting_decision: false,
         };
 
         (res, ui_bus)
@@ -141,37 +419,133 @@ impl EchoAgent for HumanAgent {
         let _guard = tracing::span!(Level::DEBUG, "human agent choose method");
         tracing::trace!("Sending input");
 
-        self.sender
-            .send(RequestPayload::StateAdvanced(agent_input))
-            .unwrap();
+        self.send(RequestPayload::StateAdvanced(agent_input));
 
         tracing::trace!("Input sent");
-        let decision = self.receiver.recv().unwrap();
+        match self.receiver.recv() {
+            Ok(payload) => {
+                tracing::trace!("Received decision");
+                self.accept(payload)
+            }
+            // The gui got closed, and the game with it (see `UIBus::cancellation`).
+            Err(_) => DecisionIndex::default(),
+        }
+    }
+
+    fn poll_choice(&mut self, agent_input: AgentInput) -> Option<DecisionIndex> {
+        if !self.awaiting_decision {
+            tracing::trace!("Sending input");
+
+            self.send(RequestPayload::StateAdvanced(agent_input));
+            self.awaiting_decision = true;
+        }
+
+        let payload = match self.receiver.try_recv() {
+            Ok(payload) => payload,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => return Some(DecisionIndex::default()),
+        };
+
         tracing::trace!("Received decision");
+        self.awaiting_decision = false;
+
+        Some(self.accept(payload))
+    }
 
-        decision
+    fn has_resigned(&mut self) -> bool {
+        self.resigned
     }
 
     fn game_finished(&mut self) {
         let _guard = tracing::span!(Level::DEBUG, "human agent game finished method");
         tracing::trace!("Game finished");
 
-        self.sender.send(RequestPayload::GameFinished).unwrap();
+        self.send(RequestPayload::GameFinished);
     }
 
     fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
         let _guard = tracing::span!(Level::DEBUG, "human agent reveal info method");
         tracing::trace!("Received revealed info, with score={updated_score:?}.");
 
-        self.sender
-            .send(RequestPayload::Reveal(reveal_index, updated_score))
-            .unwrap();
+        self.send(RequestPayload::Reveal(reveal_index, updated_score));
+    }
+
+    fn clock_updated(&mut self, clock: ClockState) {
+        self.send(RequestPayload::ClockUpdated(clock));
     }
 }
 // }}}
 // {{{ UI implementation
 impl UIState {
-    const CARD_SIZE: [f32; 2] = [50.0, 50.0];
+    /// Keys used to pick the n-th card out of some row.
+    const NUMBER_KEYS: [Key; 9] = [
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+    ];
+
+    /// Blocks until the first input of a game arrives on the bus.
+    fn new(
+        mut communication: UIBus,
+        textures: AppTextures,
+        settings: Settings,
+        mut strategy_provider: Option<Box<dyn StrategyProvider>>,
+        session: SessionStats,
+    ) -> Self {
+        // Clocks get started before the first input gets sent.
+        let mut clock = None;
+        let input = loop {
+            match communication.recv() {
+                RequestPayload::StateAdvanced(input) => break input,
+                RequestPayload::ClockUpdated(state) => clock = Some((state, Instant::now())),
+                payload => panic!("Expected the first input of the game, got {payload:?}"),
+            }
+        };
+
+        let strategy_hints = strategy_provider
+            .as_mut()
+            .and_then(|provider| provider.strategy(&input));
+
+        Self {
+            input,
+            history: History::default(),
+            partial_main_choice: Some(PartialMainPhaseChoice::default()),
+            decision_sent: false,
+            last_reveal: None,
+            clock,
+            beliefs: Some(Beliefs::new(
+                &input.state,
+                &input.phase,
+                input.player,
+                input.hidden,
+            )),
+            last_decision: None,
+            decision_records: Vec::new(),
+            review: None,
+            training: None,
+            training_turns: 1,
+            textures,
+            card_size: settings.card_size,
+            settings,
+            hovered_card: None,
+            animations: Animations::default(),
+            export_status: None,
+            session,
+            game_finished: false,
+            menu_request: None,
+            resigned: false,
+            communication,
+            show_strategy_hints: strategy_provider.is_some(),
+            strategy_provider,
+            strategy_hints,
+        }
+    }
 
     // {{{ Data helpers
     fn my_creatures(&self) -> Option<CreatureSet> {
@@ -208,39 +582,202 @@ impl UIState {
             _ => [None; 2],
         }
     }
+
+    /// Creatures the player could be trying to guess during the sabotage phase.
+    fn opponent_creature_possibilities(&self) -> CreatureSet {
+        !(self.input.hidden.get_main() | self.input.state.graveyard)
+    }
+
+    // The following three functions return true when the player
+    // is expected to make a choice for the respective phase.
+    fn can_make_main_choice(&self) -> bool {
+        self.input.phase.tag() == PhaseTag::Main && !self.decision_sent
+    }
+
+    fn can_make_sabotage_choice(&self) -> bool {
+        self.input.phase.tag() == PhaseTag::Sabotage
+            && !self.decision_sent
+            && self.input.phase.sabotage_status(self.input.player)
+    }
+
+    fn can_make_seer_choice(&self) -> bool {
+        self.input.phase.tag() == PhaseTag::Seer
+            && !self.decision_sent
+            && self.input.state.seer_status(self.input.player)
+    }
     // }}}
     // {{{ Drawing helpers
+    /// The translations for the language picked in the settings.
     #[inline(always)]
-    fn draw_gray_image(ui: &mut Ui, image: &RetainedImage) -> egui::Response {
-        let texture = image.texture_id(ui.ctx());
-        egui::Image::new(texture, image.size_vec2())
-            .tint(egui::Color32::DARK_GRAY)
-            .ui(ui)
+    fn locale(&self) -> &'static Locale {
+        Locale::of(self.settings.language)
     }
 
-    /// Renders a retained image as an image inside a button.
+    /// The size cards get drawn at on the field.
     #[inline(always)]
-    fn draw_clickable_image_size(
+    fn card_size(&self) -> Vec2 {
+        Vec2::splat(self.card_size)
+    }
+
+    /// Shrinks the cards such that rows of the given length fit the available
+    /// width, never growing them past the size picked in the settings. The
+    /// size gets rounded to whole physical pixels, which keeps the art crisp
+    /// on high dpi screens.
+    fn fit_cards(&mut self, ui: &Ui, columns: usize) {
+        let columns = columns.max(1) as f32;
+        let spacing = ui.spacing();
+        let fitting = (ui.available_width() - spacing.item_spacing.x * (columns - 1.0)) / columns
+            - 2.0 * spacing.button_padding.x;
+
+        let min_size = *Settings::CARD_SIZE_RANGE.start() / 2.0;
+        let size = fitting.min(self.settings.card_size).max(min_size);
+        self.card_size = ui.painter().round_to_pixel(size);
+    }
+
+    #[inline(always)]
+    fn draw_image(ui: &mut Ui, texture: CardTexture, size: impl Into<Vec2>) -> egui::Response {
+        egui::Image::new(texture.id, size).uv(texture.uv).ui(ui)
+    }
+
+    /// Draws a card which is not available, grayed out (and
+    /// striped, unless colors are enough to tell it apart).
+    #[inline(always)]
+    fn draw_gray_image(
+        &self,
         ui: &mut Ui,
-        image: &RetainedImage,
+        texture: CardTexture,
         size: impl Into<Vec2>,
     ) -> egui::Response {
-        let texture = image.texture_id(ui.ctx());
-        egui::ImageButton::new(texture, size).ui(ui)
+        let res = egui::Image::new(texture.id, size)
+            .uv(texture.uv)
+            .tint(egui::Color32::DARK_GRAY)
+            .ui(ui);
+
+        if self.settings.colorblind_cues {
+            Self::paint_stripes(ui, res.rect);
+        }
+
+        res
     }
 
+    /// Covers some rect in diagonal stripes.
+    fn paint_stripes(ui: &Ui, rect: Rect) {
+        const SPACING: f32 = 12.0;
+
+        let painter = ui.painter_at(rect);
+        let stroke = egui::Stroke::new(2.0, ui.visuals().strong_text_color());
+
+        let mut offset = 0.0;
+        while offset < rect.width() + rect.height() {
+            let start = rect.left_top() + Vec2::new(offset, 0.0);
+            let end = start + Vec2::new(-rect.height(), rect.height());
+            painter.line_segment([start, end], stroke);
+            offset += SPACING;
+        }
+    }
+
+    /// Draws badges in the top right corner of some card, naming
+    /// them once the card gets hovered. Only drawn when colorblind
+    /// cues are enabled.
+    fn draw_badges(&self, ui: &Ui, res: &egui::Response, badges: &[CardBadge]) {
+        if !self.settings.colorblind_cues || badges.is_empty() {
+            return;
+        }
+
+        let radius = (res.rect.width() / 8.0).clamp(8.0, 16.0);
+        let visuals = ui.visuals();
+        let painter = ui.painter();
+
+        for (index, badge) in badges.iter().enumerate() {
+            let offset = radius + 2.0 + index as f32 * (2.0 * radius + 2.0);
+            let center = res.rect.right_top() + Vec2::new(-offset, radius + 2.0);
+
+            painter.circle_filled(center, radius, visuals.extreme_bg_color);
+            painter.circle_stroke(center, radius, visuals.widgets.noninteractive.fg_stroke);
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                badge.icon(),
+                egui::FontId::proportional(radius * 1.2),
+                visuals.strong_text_color(),
+            );
+        }
+
+        if res.hovered() {
+            let locale = self.locale();
+            let names: Vec<_> = badges
+                .iter()
+                .map(|badge| format!("{} {}", badge.icon(), locale.variant("badge", badge)))
+                .collect();
+
+            res.clone().on_hover_text(names.join("\n"));
+        }
+    }
+
+    /// The badges of the creatures both players played during some past turn.
+    fn creature_badges(
+        battlefield: Battlefield,
+        choices: Pair<PlayerTurnHistory>,
+    ) -> Pair<Vec<CardBadge>> {
+        let [mine, yours] = choices;
+
+        [(mine, yours), (yours, mine)].map(|(own, other)| {
+            let mut badges = Vec::new();
+            let Some(creature) = own.creature else {
+                return badges;
+            };
+
+            if other
+                .creature
+                .is_some_and(|other| negated_by(creature, other).is_some())
+            {
+                badges.push(CardBadge::Negated);
+            }
+
+            if other.sabotage == Some(creature) {
+                badges.push(CardBadge::Sabotaged);
+            }
+
+            if battlefield.bonus(creature) {
+                badges.push(CardBadge::BattlefieldBonus);
+            }
+
+            badges
+        })
+    }
+
+    /// Renders a texture inside a button.
     #[inline(always)]
-    fn draw_clickable_image(ui: &mut Ui, image: &RetainedImage) -> egui::Response {
-        Self::draw_clickable_image_size(ui, image, image.size_vec2())
+    fn draw_clickable_image_size(
+        ui: &mut Ui,
+        texture: CardTexture,
+        size: impl Into<Vec2>,
+    ) -> egui::Response {
+        let res = egui::ImageButton::new(texture.id, size)
+            .uv(texture.uv)
+            .ui(ui);
+
+        // Make it obvious which card is selected when navigating with the keyboard.
+        if res.has_focus() {
+            let stroke = egui::Stroke {
+                width: 2.0,
+                ..ui.visuals().selection.stroke
+            };
+
+            ui.painter().rect_stroke(res.rect.expand(2.0), 2.0, stroke);
+        }
+
+        res
     }
 
     #[inline(always)]
     fn draw_battlefield(&mut self, ui: &mut Ui, battlefield: Battlefield, disabled: bool) {
-        let retained_image = &self.textures.battlefields[battlefield as usize];
+        let size = self.card_size();
+        let tex = self.textures.battlefield(battlefield);
         let res = if disabled {
-            Self::draw_gray_image(ui, retained_image)
+            self.draw_gray_image(ui, tex, size)
         } else {
-            retained_image.show(ui)
+            Self::draw_image(ui, tex, size)
         };
 
         if res.hovered() {
@@ -250,7 +787,7 @@ impl UIState {
 
     #[inline(always)]
     fn draw_status_effect(&mut self, ui: &mut Ui, status_effect: StatusEffect) {
-        let res = ui.label(format!("{status_effect:?}"));
+        let res = ui.label(self.locale().status_effect(status_effect));
         if res.hovered() {
             self.hovered_card = Some(HoveredCard::StatusEffect(status_effect));
         }
@@ -265,11 +802,12 @@ impl UIState {
 
     #[inline(always)]
     fn draw_edict(&mut self, ui: &mut Ui, edict: Edict, clickable: bool) -> egui::Response {
-        let tex = &self.textures.edicts[edict as usize];
+        let size = self.card_size();
+        let tex = self.textures.edict(edict);
         let res = if clickable {
-            Self::draw_clickable_image(ui, tex)
+            Self::draw_clickable_image_size(ui, tex, size)
         } else {
-            tex.show(ui)
+            Self::draw_image(ui, tex, size)
         };
 
         if res.hovered() {
@@ -284,7 +822,7 @@ impl UIState {
         if let Some(edict) = edict {
             Self::draw_edict(self, ui, edict, false);
         } else {
-            self.textures.card_back.show(ui);
+            Self::draw_image(ui, self.textures.card_back(), self.card_size());
         };
     }
 
@@ -295,11 +833,12 @@ impl UIState {
         creature: Creature,
         clickable: bool,
     ) -> egui::Response {
-        let tex = &self.textures.creatures[creature as usize];
+        let size = self.card_size();
+        let tex = self.textures.creature(creature);
         let res = if clickable {
-            Self::draw_clickable_image(ui, tex)
+            Self::draw_clickable_image_size(ui, tex, size)
         } else {
-            tex.show(ui)
+            Self::draw_image(ui, tex, size)
         };
 
         if res.hovered() {
@@ -310,11 +849,11 @@ impl UIState {
     }
 
     #[inline(always)]
-    fn draw_opt_creature(&mut self, ui: &mut Ui, creature: Option<Creature>) {
+    fn draw_opt_creature(&mut self, ui: &mut Ui, creature: Option<Creature>) -> egui::Response {
         if let Some(creature) = creature {
-            self.draw_creature(ui, creature, false);
+            self.draw_creature(ui, creature, false)
         } else {
-            self.textures.card_back.show(ui);
+            Self::draw_image(ui, self.textures.card_back(), self.card_size())
         }
     }
 
@@ -329,171 +868,1019 @@ impl UIState {
     /// Communicates a choice, and marks the decision as sent.
     #[inline(always)]
     fn send(&mut self, index: DecisionIndex) {
-        self.communication.sender.send(index).unwrap();
+        self.communication
+            .sender
+            .send(ResponsePayload::Decision(index))
+            .unwrap();
         self.decision_sent = true;
+        self.last_decision = Some(index);
+        self.record_decision(index);
     }
 
-    /// Attempts to send the main phase choice the user has made.
+    /// Gives up on the game, which the opponent wins.
+    fn resign(&mut self) {
+        tracing::event!(Level::INFO, "Resigning");
+
+        self.communication
+            .sender
+            .send(ResponsePayload::Resign)
+            .unwrap();
+        self.decision_sent = true;
+        self.resigned = true;
+    }
+
+    /// Remembers everything needed to review the given decision after the game.
+    fn record_decision(&mut self, index: DecisionIndex) {
+        let Some(beliefs) = &self.beliefs else {
+            return;
+        };
+
+        let input = self.input;
+        if input
+            .player
+            .select(input.phase.decision_counts(&input.state))
+            == 1
+        {
+            return;
+        }
+
+        self.decision_records.push(DecisionRecord {
+            state: input.state,
+            phase: input.phase,
+            player: input.player,
+            hidden: input.hidden,
+            decision: index,
+            strategy: self.strategy_hints.clone(),
+            deals: beliefs.deals().to_vec(),
+        });
+    }
+
+    /// Returns the main phase choice the user has made, if they've
+    /// selected both an edict and the right amount of creatures.
+    fn complete_main_choice(&self) -> Option<(CreatureSet, Edict)> {
+        match self.partial_main_choice {
+            Some(PartialMainPhaseChoice {
+                creatures,
+                edict: Some(edict),
+            }) if creatures.len() == self.input.state.creature_choice_size(self.input.player) => {
+                Some((creatures, edict))
+            }
+            _ => None,
+        }
+    }
+
+    /// Selects / deselects a creature during the main phase.
     ///
-    /// Communicates the choice the playe rmade for the sabotage phase.
+    /// If the maximum amount of creatures has already been
+    /// selected, the oldest selection gets replaced.
+    fn toggle_main_creature(&mut self, creature: Creature) {
+        let max_size = self.input.state.creature_choice_size(self.input.player);
+
+        if let Some(choice) = &mut self.partial_main_choice {
+            if choice.creatures.has(creature) {
+                choice.creatures.remove(creature);
+            } else if choice.creatures.len() == max_size {
+                choice.creatures.remove(choice.creatures.index(0).unwrap());
+                choice.creatures.insert(creature);
+            } else {
+                choice.creatures.insert(creature);
+            }
+        }
+    }
+
+    fn select_main_edict(&mut self, edict: Edict) {
+        if let Some(choice) = &mut self.partial_main_choice {
+            choice.edict = Some(edict);
+        }
+    }
+
+    /// Lets cards get dragged from the hand onto the play area, which selects
+    /// them just like clicking them does (without ever deselecting them).
+    fn drag_card(&mut self, ui: &Ui, res: &egui::Response, card: DraggedCard, play_area: Rect) {
+        let res = res.interact(Sense::drag());
+
+        if res.dragged() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+
+            // Draw the card under the pointer, on top of everything else.
+            let painter = ui.ctx().layer_painter(egui::LayerId::new(
+                egui::Order::Tooltip,
+                res.id.with("dragged card"),
+            ));
+
+            painter.rect_stroke(play_area.expand(4.0), 4.0, ui.visuals().selection.stroke);
+
+            if let Some(pos) = ui.ctx().pointer_interact_pos() {
+                let texture = match card {
+                    DraggedCard::Creature(creature) => self.textures.creature(creature),
+                    DraggedCard::Edict(edict) => self.textures.edict(edict),
+                };
+
+                painter.image(
+                    texture.id,
+                    Rect::from_center_size(pos, self.card_size()),
+                    texture.uv,
+                    egui::Color32::from_white_alpha(192),
+                );
+            }
+        }
+
+        let dropped = res.drag_released()
+            && ui
+                .ctx()
+                .pointer_interact_pos()
+                .is_some_and(|pos| play_area.contains(pos));
+
+        if !dropped {
+            return;
+        }
+
+        match card {
+            DraggedCard::Creature(creature) => {
+                let selected = self
+                    .partial_main_choice
+                    .is_some_and(|choice| choice.creatures.has(creature));
+
+                if !selected {
+                    self.toggle_main_creature(creature);
+                }
+            }
+            DraggedCard::Edict(edict) => self.select_main_edict(edict),
+        }
+    }
+
+    /// Forgets everything the user has selected so far during the main phase.
+    fn clear_main_choice(&mut self) {
+        if let Some(choice) = &mut self.partial_main_choice {
+            *choice = PartialMainPhaseChoice::default();
+        }
+    }
+
+    /// Attempts to send the main phase choice the user has made.
     ///
     /// Acts as a noop if the phase isn't correct, or if the user
     /// hasn't finished choosing the input just yet.
     fn try_communicate_main(&mut self) {
         if self.decision_sent || self.input.phase.tag() != PhaseTag::Main {
             return;
-        }
+        }
+
+        if let Some((creatures, edict)) = self.complete_main_choice() {
+            tracing::event!(Level::INFO, "Sending main phase decision to agent");
+
+            let index = DecisionIndex::encode_main_phase_index(
+                &self.input.state,
+                self.input.player,
+                self.input.hidden.get_main(),
+                creatures,
+                edict,
+            )
+            .unwrap();
+
+            self.send(index);
+        }
+    }
+
+    /// Communicates the choice the playe rmade for the seer phase.
+    ///
+    /// Acts as a noop if the phase isn't correct, if the user
+    /// hasn't finished choosing the input just yet, or if the choice
+    /// has already been sent.
+    fn communicate_sabotage(&mut self, guess: Creature) {
+        if self.decision_sent || self.input.phase.tag() != PhaseTag::Sabotage {
+            return;
+        }
+
+        tracing::event!(Level::INFO, "Sending sabotage phase decision to agent");
+
+        let index = DecisionIndex::encode_sabotage_index(
+            &self.input.state,
+            self.input.hidden.get_main(),
+            Some(guess),
+        );
+
+        self.send(index);
+    }
+
+    /// Communicates the choice the playe rmade for the seer phase.
+    ///
+    /// Acts as a noop if the phase isn't correct, if the user
+    /// hasn't finished choosing the input just yet, or if the choice
+    /// has already been sent.
+    fn communicate_seer(&mut self, final_choice: Creature) {
+        if self.decision_sent || self.input.phase.tag() != PhaseTag::Seer {
+            return;
+        }
+
+        tracing::event!(Level::INFO, "Sending seer phase decision to agent");
+
+        let all_choices = self.input.hidden.get_sabotage().unwrap();
+        let index = DecisionIndex::encode_seer_index(all_choices, final_choice).unwrap();
+
+        self.send(index);
+    }
+
+    /// Weighs the deals by how likely the opponent was to reveal the given
+    /// information, according to the strategy provider (if there is one).
+    fn update_beliefs(&mut self, reveal_index: RevealIndex) {
+        let (Some(beliefs), Some(decision)) = (&mut self.beliefs, self.last_decision) else {
+            self.beliefs = None;
+            return;
+        };
+
+        let input = self.input;
+        let provider = &mut self.strategy_provider;
+        let result = beliefs.update(
+            &input.state,
+            &input.phase,
+            decision,
+            reveal_index,
+            |hidden| {
+                let opponent_input = AgentInput {
+                    player: !input.player,
+                    hidden,
+                    ..input
+                };
+
+                provider.as_mut()?.strategy(&opponent_input)
+            },
+        );
+
+        if let Err(error) = result {
+            tracing::event!(Level::WARN, "Failed to update the beliefs: {error}");
+            self.beliefs = None;
+        }
+    }
+
+    /// Attempts to read data coming from the bus, and updates the internal state accordingly.
+    fn try_accept_input(&mut self) {
+        match self.communication.try_recv() {
+            // {{{ State advanced
+            Some(RequestPayload::StateAdvanced(input)) => {
+                tracing::event!(Level::INFO, "Received unfinished input from agent");
+
+                self.input = input;
+                self.last_decision = None;
+                if self.beliefs.is_none() {
+                    self.beliefs = Some(Beliefs::new(
+                        &input.state,
+                        &input.phase,
+                        input.player,
+                        input.hidden,
+                    ));
+                }
+
+                self.strategy_hints = self
+                    .strategy_provider
+                    .as_mut()
+                    .and_then(|provider| provider.strategy(&input));
+
+                self.partial_main_choice = if input.phase.tag() == PhaseTag::Main {
+                    Some(PartialMainPhaseChoice::default())
+                } else {
+                    None
+                };
+
+                // {{{ Take single choice decisions
+                // If we have a single valid decision we can take, we take it right away.
+                if self
+                    .input
+                    .player
+                    .select(self.input.phase.decision_counts(&self.input.state))
+                    == 1
+                {
+                    tracing::event!(Level::INFO, "Sending single choice decision to agent");
+                    // Send the only possible decision right away!
+                    self.send(DecisionIndex::default());
+                } else {
+                    self.decision_sent = false;
+                }
+                // }}}
+            }
+            // }}}
+            // {{{ Reveal
+            Some(RequestPayload::Reveal(reveal_index, updated_score)) => {
+                let _guard = tracing::span!(Level::TRACE, "Updating history");
+                tracing::event!(Level::TRACE, "Updating history");
+
+                // The provider must still be looking at the phase the decisions were taken in.
+                self.update_beliefs(reveal_index);
+
+                if let Some(provider) = &mut self.strategy_provider {
+                    provider.reveal_info(reveal_index);
+                }
+
+                self.last_reveal =
+                    Some(reveal_index.describe(&self.input.state, &self.input.phase));
+
+                let opponent = !self.input.player;
+                let flipped_creature = self
+                    .history
+                    .record(
+                        &self.input.state,
+                        &self.input.phase,
+                        reveal_index,
+                        updated_score,
+                    )
+                    .unwrap()
+                    .filter(|(owner, _)| *owner == opponent)
+                    .map(|(_, creature)| creature);
+
+                // {{{ Animations
+                if let Some(creature) = flipped_creature {
+                    self.start_animation(AnimationKind::Flip(creature));
+                }
+
+                let delta = Score(updated_score.0 - self.input.state.score.0)
+                    .from_perspective(self.input.player)
+                    .0;
+
+                if delta != 0 {
+                    self.start_animation(AnimationKind::ScoreDelta(delta));
+                }
+                // }}}
+
+                tracing::event!(Level::TRACE, "Succesfully updated history");
+            }
+            // }}}
+            // {{{ Clock updated
+            Some(RequestPayload::ClockUpdated(clock)) => {
+                self.clock = Some((clock, Instant::now()));
+            }
+            // }}}
+            // {{{ Game finished
+            Some(RequestPayload::GameFinished) => {
+                self.game_finished = true;
+                self.strategy_hints = None;
+                self.session.record(self.final_score().unwrap_or_default());
+
+                if let Some(provider) = &mut self.strategy_provider {
+                    provider.game_finished();
+                }
+            }
+            // }}}
+            _ => {}
+        }
+    }
+    // }}}
+    // {{{ Animations
+    fn start_animation(&mut self, kind: AnimationKind) {
+        if self.settings.animations {
+            self.animations.start(kind);
+        }
+    }
+
+    /// Draws a creature which might be in the process of getting flipped face up.
+    fn draw_revealed_creature(
+        &mut self,
+        ui: &mut Ui,
+        creature: Option<Creature>,
+    ) -> egui::Response {
+        let progress = creature.and_then(|creature| self.animations.flip_progress(creature));
+
+        let (Some(creature), Some(progress)) = (creature, progress) else {
+            return self.draw_opt_creature(ui, creature);
+        };
+
+        // The card shrinks horizontally until it's invisible, and
+        // then grows back, this time showing the other side.
+        let size = self.card_size();
+        let (rect, res) = ui.allocate_exact_size(size, Sense::hover());
+        let width = size.x * (1.0 - 2.0 * progress).abs();
+        let rect = Rect::from_center_size(rect.center(), Vec2::new(width, size.y));
+        let tex = if progress < 0.5 {
+            self.textures.card_back()
+        } else {
+            self.textures.creature(creature)
+        };
+
+        egui::Image::new(tex.id, rect.size())
+            .uv(tex.uv)
+            .paint_at(ui, rect);
+
+        if res.hovered() {
+            self.hovered_card = Some(HoveredCard::Creature(creature));
+        }
+
+        res
+    }
+
+    /// Draws a fading popup showing how much the score changed by last turn.
+    fn draw_score_delta(&self, ui: &mut Ui) {
+        let Some((delta, progress)) = self.animations.score_delta() else {
+            return;
+        };
+
+        let color = if delta > 0 {
+            egui::Color32::GREEN
+        } else {
+            egui::Color32::RED
+        };
+
+        ui.label(
+            egui::RichText::new(format!("{delta:+}"))
+                .heading()
+                .color(color.gamma_multiply(1.0 - progress)),
+        );
+    }
+    // }}}
+    // {{{ Clocks
+    /// The time left on the clock of the given player right now.
+    fn remaining_time(&self, player: Player) -> Option<Duration> {
+        let (clock, received) = self.clock?;
+        let remaining = player.select(clock.remaining);
+
+        if player.select(clock.ticking) && !self.game_finished {
+            Some(remaining.saturating_sub(received.elapsed()))
+        } else {
+            Some(remaining)
+        }
+    }
+
+    /// Draws the time left on both clocks, when playing with clocks.
+    fn draw_clocks(&self, ui: &mut Ui) {
+        let Some((clock, _)) = self.clock else {
+            return;
+        };
+
+        let locale = self.locale();
+        ui.horizontal(|ui| {
+            for (player, key) in [
+                (self.input.player, "your-clock"),
+                (!self.input.player, "opponents-clock"),
+            ] {
+                let remaining = self.remaining_time(player).unwrap_or_default();
+                let seconds = remaining.as_secs();
+                let mut text = format!("{}: {}:{:02}", locale.get(key), seconds / 60, seconds % 60);
+                if remaining.is_zero() && self.settings.colorblind_cues {
+                    text.insert_str(0, "⌛ ");
+                }
+
+                let text = egui::RichText::new(text).monospace();
+
+                if remaining.is_zero() {
+                    ui.label(text.color(egui::Color32::RED))
+                        .on_hover_text(locale.get("out-of-time"));
+                } else if player.select(clock.ticking) && !self.game_finished {
+                    ui.label(text.strong());
+                } else {
+                    ui.label(text.weak());
+                }
+            }
+        });
+
+        if clock.ticking.contains(&true) && !self.game_finished {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    /// Shows the results of the session so far (once at least one game is over).
+    fn draw_session(&self, ui: &mut Ui) {
+        let session = self.session;
+        if session.games() == 0 {
+            return;
+        }
+
+        let locale = self.locale();
+        ui.horizontal(|ui| {
+            ui.label(locale.format(
+                "session-results",
+                &[
+                    ("wins", &session.wins()),
+                    ("losses", &session.losses()),
+                    ("ties", &session.ties()),
+                ],
+            ));
+
+            ui.separator();
+            ui.label(locale.format(
+                "session-average-score",
+                &[("score", &format!("{:+.2}", session.average_score()))],
+            ));
+
+            ui.separator();
+            let [mine, yours] = session.ratings;
+            ui.label(locale.format(
+                "session-ratings",
+                &[("mine", &mine.round()), ("yours", &yours.round())],
+            ))
+            .on_hover_text(locale.get("session-ratings-hint"));
+        });
+    }
+
+    /// Once the clock of the player runs out, sends a random decision on their
+    /// behalf, such that the game does not wait on them forever. The runner
+    /// then decides what running out of time leads to (see `clock`).
+    fn handle_timeout(&mut self) {
+        if self.game_finished || self.decision_sent {
+            return;
+        }
+
+        if self.remaining_time(self.input.player) != Some(Duration::ZERO) {
+            return;
+        }
+
+        let count = self
+            .input
+            .player
+            .select(self.input.phase.decision_counts(&self.input.state));
+        let decision = DecisionIndex(rand::thread_rng().gen_range(0..count));
+
+        tracing::event!(Level::INFO, "Ran out of time, sending a random decision");
+        self.send(decision);
+    }
+    // }}}
+    // {{{ Match export
+    /// Returns the final score from the perspective of the
+    /// human player, or `None` if the game is still going.
+    fn final_score(&self) -> Option<Score> {
+        if !self.game_finished {
+            return None;
+        }
+
+        // Running out of time (or resigning) can end the game in the middle of a turn.
+        let forfeit = if self.resigned {
+            Some(self.input.state.score.forfeited_by(self.input.player))
+        } else {
+            self.clock
+                .and_then(|(clock, _)| clock.forfeit_score(self.input.state.score))
+        };
+        let score = match forfeit {
+            Some(score) => score,
+            None => self.history.turns.last()?.score?,
+        };
+
+        Some(score.from_perspective(self.input.player))
+    }
+
+    /// Formats an optional value as a json string (or null).
+    fn json_opt<T: Display>(value: Option<T>) -> String {
+        value.map_or_else(|| "null".to_string(), |value| format!("\"{value}\""))
+    }
+
+    /// Serializes the history of the current match as json. Everything
+    /// (including scores) is from the perspective of the human player.
+    fn match_summary_json(&self) -> String {
+        let mut result = String::new();
+        let battlefields = self.input.state.battlefields.all;
+
+        writeln!(result, "{{").unwrap();
+        writeln!(
+            result,
+            "  \"battlefields\": [{}],",
+            battlefields.map(|b| format!("\"{b}\"")).join(", ")
+        )
+        .unwrap();
+        writeln!(
+            result,
+            "  \"final_score\": {},",
+            self.final_score()
+                .map_or_else(|| "null".to_string(), |score| score.0.to_string())
+        )
+        .unwrap();
+        writeln!(result, "  \"turns\": [").unwrap();
+
+        for (index, entry) in self.history.turns.iter().enumerate() {
+            let [me, you] = self.input.player.order_as(entry.choices);
+            let score = entry
+                .score
+                .map(|score| score.from_perspective(self.input.player).0);
+            let player_json = |choices: PlayerTurnHistory| {
+                format!(
+                    "{{ \"creature\": {}, \"edict\": {}, \"sabotage\": {} }}",
+                    Self::json_opt(choices.creature),
+                    Self::json_opt(choices.edict),
+                    Self::json_opt(choices.sabotage)
+                )
+            };
+
+            writeln!(result, "    {{").unwrap();
+            writeln!(
+                result,
+                "      \"battlefield\": \"{}\",",
+                battlefields[index]
+            )
+            .unwrap();
+            writeln!(
+                result,
+                "      \"score\": {},",
+                score.map_or_else(|| "null".to_string(), |score| score.to_string())
+            )
+            .unwrap();
+            writeln!(result, "      \"you\": {},", player_json(me)).unwrap();
+            writeln!(result, "      \"opponent\": {}", player_json(you)).unwrap();
+
+            let separator = if index + 1 == self.history.turns.len() {
+                ""
+            } else {
+                ","
+            };
+
+            writeln!(result, "    }}{separator}").unwrap();
+        }
+
+        writeln!(result, "  ]").unwrap();
+        writeln!(result, "}}").unwrap();
+
+        result
+    }
+
+    /// A single line summary of the match, meant to be pasted in chats.
+    fn match_summary_text(&self) -> String {
+        let turns = self
+            .history
+            .turns
+            .iter()
+            .zip(self.input.state.battlefields.all)
+            .filter_map(|(entry, battlefield)| {
+                let [me, you] = self.input.player.order_as(entry.choices);
+                let score = entry.score?.from_perspective(self.input.player);
+
+                Some(format!(
+                    "{battlefield}: {}+{} vs {}+{} ({:+})",
+                    me.creature?, me.edict?, you.creature?, you.edict?, score.0
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join(" | ");
+
+        match self.final_score() {
+            Some(score) => format!(
+                "{turns} | Final: {:+} ({:?})",
+                score.0,
+                score.to_battle_result()
+            ),
+            None => turns,
+        }
+    }
+
+    /// Saves the match summary (where possible), and
+    /// copies a compact version to the clipboard.
+    fn export_match(&mut self, ctx: &egui::Context) {
+        self.export_status = Some(self.save_match_summary());
+
+        let summary = self.match_summary_text();
+        ctx.output_mut(|output| output.copied_text = summary);
+    }
+
+    /// Writes the match summary to a json file in the working
+    /// directory, returning a message describing the outcome.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_match_summary(&self) -> String {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let path = format!("match_{timestamp}.json");
+
+        match std::fs::write(&path, self.match_summary_json()) {
+            Ok(()) => {
+                tracing::event!(Level::INFO, "Exported match summary to {path}");
+                self.locale().format("export-saved", &[("path", &path)])
+            }
+            Err(error) => {
+                tracing::event!(Level::ERROR, "Failed to export match summary: {error}");
+                self.locale()
+                    .format("export-failed", &[("path", &path), ("error", &error)])
+            }
+        }
+    }
+
+    /// Browsers give us no file system to save the json summary to.
+    #[cfg(target_arch = "wasm32")]
+    fn save_match_summary(&self) -> String {
+        self.locale().get("export-copied").to_string()
+    }
+    // }}}
+    // {{{ Strategy hints
+    /// Draws a floating window containing the recommended
+    /// probability for each decision the player can take.
+    fn draw_strategy_hints(&self, ctx: &egui::Context) {
+        if !self.show_strategy_hints || self.decision_sent || self.game_finished {
+            return;
+        }
+
+        let Some(hints) = &self.strategy_hints else {
+            return;
+        };
+
+        let mut entries: Vec<_> = hints.iter().copied().enumerate().collect();
+        entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        let locale = self.locale();
+
+        egui::Window::new(locale.get("strategy-hints")).show(ctx, |ui| {
+            Grid::new("strategy hints grid").show(ui, |ui| {
+                ui.label(locale.get("decision"));
+                ui.label(locale.get("probability"));
+                ui.end_row();
+
+                for (index, probability) in entries {
+                    let description = DecisionIndex(index).describe(
+                        &self.input.state,
+                        &self.input.phase,
+                        self.input.player,
+                        self.input.hidden,
+                    );
+
+                    ui.label(description);
+                    ui.label(format!("{:.1}%", probability * 100.0));
+                    ui.end_row();
+                }
+            });
+        });
+    }
+    // }}}
+    // {{{ Game review
+    /// How many rollouts to estimate the value of each decision with.
+    const REVIEW_ROLLOUTS: usize = 32;
+
+    fn verdict_color(verdict: Verdict) -> egui::Color32 {
+        match verdict {
+            Verdict::Best | Verdict::Good => egui::Color32::GREEN,
+            Verdict::Inaccuracy => egui::Color32::YELLOW,
+            Verdict::Mistake => egui::Color32::from_rgb(255, 140, 0),
+            Verdict::Blunder => egui::Color32::RED,
+        }
+    }
+
+    /// Draws a button reviewing the game once clicked, or the review itself
+    /// (each decision next to the best one, with the utility lost in-between).
+    fn draw_review(&mut self, ui: &mut Ui) {
+        let locale = self.locale();
+
+        let Some(reviewed) = &self.review else {
+            if ui.button(locale.get("review-game")).clicked() {
+                let records = std::mem::take(&mut self.decision_records);
+                self.review = Some(
+                    review(records, Self::REVIEW_ROLLOUTS, 0).map_err(|error| error.to_string()),
+                );
+            }
+
+            return;
+        };
+
+        let reviewed = match reviewed {
+            Ok(reviewed) => reviewed,
+            Err(error) => {
+                let message = locale.format("review-failed", &[("error", error)]);
+                ui.colored_label(egui::Color32::RED, message);
+                return;
+            }
+        };
+
+        let lost: f32 = reviewed.iter().map(ReviewedDecision::utility_lost).sum();
+        let deviations = reviewed.iter().filter(|r| r.is_deviation()).count();
+        ui.label(locale.format(
+            "review-summary",
+            &[
+                ("lost", &format!("{lost:.2}")),
+                ("count", &reviewed.len()),
+                ("deviations", &deviations),
+            ],
+        ));
+
+        Grid::new("game review").striped(true).show(ui, |ui| {
+            ui.label(locale.get("turn"));
+            ui.label(locale.get("phase"));
+            ui.label(locale.get("your-decision"));
+            ui.label(locale.get("best-decision"));
+            ui.label(locale.get("utility-lost"));
+            ui.label(locale.get("probability"));
+            ui.label(locale.get("verdict"));
+            ui.end_row();
+
+            for entry in reviewed {
+                let record = &entry.record;
+                let describe = |index: DecisionIndex| {
+                    index.describe(&record.state, &record.phase, record.player, record.hidden)
+                };
+
+                ui.label((record.state.battlefields.current + 1).to_string());
+                ui.label(locale.variant("phase", record.phase.tag()));
+                ui.label(describe(record.decision));
+                ui.label(describe(entry.best_decision()));
+                ui.label(format!("{:.2}", entry.utility_lost()));
+
+                let probability = entry
+                    .strategy_probability()
+                    .map_or_else(|| "-".to_string(), |p| format!("{:.1}%", p * 100.0));
+                if entry.is_deviation() {
+                    let probability = if self.settings.colorblind_cues {
+                        format!("⚠ {probability}")
+                    } else {
+                        probability
+                    };
+
+                    ui.colored_label(egui::Color32::RED, probability)
+                        .on_hover_text(locale.get("deviation"));
+                } else {
+                    ui.label(probability);
+                }
+
+                let verdict = entry.verdict();
+                ui.colored_label(
+                    Self::verdict_color(verdict),
+                    locale.variant("verdict", verdict),
+                );
+                ui.end_row();
+            }
+        });
+    }
+    // }}}
+    // {{{ Training dashboard
+    /// Lets the user train the rest of the current turn (and possibly a few
+    /// more) in the background, watching the strategy for their hand evolve.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn draw_training(&mut self, ui: &mut Ui) {
+        let locale = self.locale();
+        let remaining = Battlefields::COUNT.saturating_sub(self.input.state.battlefields.current);
+        self.training_turns = self.training_turns.clamp(1, remaining.max(1));
+
+        let running = self
+            .training
+            .as_ref()
+            .is_some_and(|run| !run.training.is_stopping());
+
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                !running && remaining > 0,
+                egui::Slider::new(&mut self.training_turns, 1..=remaining.max(1))
+                    .text(locale.get("turns")),
+            );
+
+            if running {
+                if ui.button(locale.get("stop")).clicked() {
+                    if let Some(run) = &self.training {
+                        run.training.stop();
+                    }
+                }
+            } else if ui
+                .add_enabled(remaining > 0, egui::Button::new(locale.get("train")))
+                .clicked()
+            {
+                let hand = self.input.hidden.get_main();
+                self.training = Some(TrainingRun {
+                    state: self.input.state,
+                    hand,
+                    training: BackgroundTraining::start(
+                        self.input.state,
+                        self.training_turns,
+                        self.input.player,
+                        hand,
+                    ),
+                });
+            }
+        });
+
+        let Some(run) = &self.training else {
+            return;
+        };
 
-        match self.partial_main_choice {
-            Some(PartialMainPhaseChoice {
-                creatures,
-                edict: Some(edict),
-            }) if creatures.len() == self.input.state.creature_choice_size(self.input.player) => {
-                tracing::event!(Level::INFO, "Sending main phase decision to agent");
+        let snapshot = run.training.snapshot();
+        if !snapshot.finished {
+            ui.ctx()
+                .request_repaint_after(BackgroundTraining::SNAPSHOT_INTERVAL);
+        }
 
-                let index = DecisionIndex::encode_main_phase_index(
-                    &self.input.state,
-                    self.input.player,
-                    self.input.hidden.get_main(),
-                    creatures,
-                    edict,
-                )
-                .unwrap();
+        if let Some(error) = &snapshot.error {
+            ui.colored_label(
+                egui::Color32::RED,
+                locale.format("training-failed", &[("error", error)]),
+            );
 
-                self.send(index);
-            }
-            _ => {}
+            return;
         }
-    }
 
-    /// Communicates the choice the playe rmade for the seer phase.
-    ///
-    /// Acts as a noop if the phase isn't correct, if the user
-    /// hasn't finished choosing the input just yet, or if the choice
-    /// has already been sent.
-    fn communicate_sabotage(&mut self, guess: Creature) {
-        if self.decision_sent || self.input.phase.tag() != PhaseTag::Sabotage {
+        if !snapshot.generated {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(locale.get("training-generating"));
+            });
+
             return;
         }
 
-        tracing::event!(Level::INFO, "Sending sabotage phase decision to agent");
+        if !snapshot.finished && run.training.is_stopping() {
+            ui.label(locale.get("training-stopping"));
+        }
 
-        let index = DecisionIndex::encode_sabotage_index(
-            &self.input.state,
-            self.input.hidden.get_main(),
-            Some(guess),
-        );
+        if snapshot.unexplored > 0 {
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                locale.format("training-unexplored", &[("count", &snapshot.unexplored)]),
+            )
+            .on_hover_text(locale.get("training-unexplored-hint"));
+        }
 
-        self.send(index);
-    }
+        Grid::new("training stats").show(ui, |ui| {
+            ui.label(locale.get("iterations"));
+            ui.label(snapshot.iterations.to_string());
+            ui.end_row();
+            ui.label(locale.get("iterations-per-second"));
+            ui.label(format!("{:.2}", snapshot.iterations_per_second()));
+            ui.end_row();
+            ui.label(locale.get("memory-use"));
+            ui.label(format!(
+                "{:.1} MB",
+                snapshot.memory as f64 / 1024.0 / 1024.0
+            ));
+            ui.end_row();
+            ui.label(locale.get("elapsed"));
+            ui.label(format!("{:.1}s", snapshot.elapsed.as_secs_f64()));
+            ui.end_row();
+        });
 
-    /// Communicates the choice the playe rmade for the seer phase.
-    ///
-    /// Acts as a noop if the phase isn't correct, if the user
-    /// hasn't finished choosing the input just yet, or if the choice
-    /// has already been sent.
-    fn communicate_seer(&mut self, final_choice: Creature) {
-        if self.decision_sent || self.input.phase.tag() != PhaseTag::Seer {
+        let Some(strategy) = snapshot.strategy else {
             return;
-        }
+        };
 
-        tracing::event!(Level::INFO, "Sending seer phase decision to agent");
+        let turn = run.state.battlefields.current + 1;
+        ui.heading(locale.format("training-strategy", &[("turn", &turn)]));
 
-        let all_choices = self.input.hidden.get_sabotage().unwrap();
-        let index = DecisionIndex::encode_seer_index(all_choices, final_choice).unwrap();
+        let mut entries: Vec<_> = strategy.into_iter().enumerate().collect();
+        entries.sort_by(|a, b| b.1.total_cmp(&a.1));
 
-        self.send(index);
+        let phase = MainPhase::new().to_some_phase();
+        Grid::new("training strategy").show(ui, |ui| {
+            for (index, probability) in entries {
+                let description = DecisionIndex(index).describe(
+                    &run.state,
+                    &phase,
+                    self.input.player,
+                    EncodingInfo::Main(run.hand),
+                );
+
+                ui.label(description);
+                ui.add(
+                    egui::ProgressBar::new(probability)
+                        .text(format!("{:.1}%", probability * 100.0)),
+                );
+                ui.end_row();
+            }
+        });
     }
 
-    /// Attempts to read data coming from the bus, and updates the internal state accordingly.
-    fn try_accept_input(&mut self) {
-        match self.communication.receiver.try_recv() {
-            // {{{ State advanced
-            Ok(RequestPayload::StateAdvanced(input)) => {
-                tracing::event!(Level::INFO, "Received unfinished input from agent");
+    /// Browsers give us no threads to train on.
+    #[cfg(target_arch = "wasm32")]
+    fn draw_training(&mut self, ui: &mut Ui) {
+        ui.label(self.locale().get("training-unavailable"));
+    }
+    // }}}
+    // {{{ Keyboard shortcuts
+    /// Returns the index of the number key pressed this frame (if any),
+    /// consuming the key press in the process.
+    fn consume_number_key(ctx: &egui::Context, modifiers: Modifiers) -> Option<usize> {
+        ctx.input_mut(|input| {
+            Self::NUMBER_KEYS
+                .iter()
+                .position(|key| input.consume_key(modifiers, *key))
+        })
+    }
 
-                self.input = input;
-                self.partial_main_choice = if input.phase.tag() == PhaseTag::Main {
-                    Some(PartialMainPhaseChoice::default())
-                } else {
-                    None
-                };
+    /// Lets the user play the game without touching the mouse:
+    /// - 1..9 pick the n-th creature in the row the current phase is about
+    /// - shift + 1..9 pick the n-th edict during the main phase
+    /// - enter confirms the main phase choice, escape clears it
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        if self.game_finished {
+            return;
+        }
 
-                // {{{ Take single choice decisions
-                // If we have a single valid decision we can take, we take it right away.
-                if self
-                    .input
-                    .player
-                    .select(self.input.phase.decision_counts(&self.input.state))
-                    == 1
-                {
-                    tracing::event!(Level::INFO, "Sending single choice decision to agent");
-                    // Send the only possible decision right away!
-                    self.send(DecisionIndex::default());
-                } else {
-                    self.decision_sent = false;
+        if self.can_make_main_choice() {
+            if let Some(index) = Self::consume_number_key(ctx, Modifiers::SHIFT) {
+                let edicts = self.input.state.player_edicts(self.input.player);
+                if let Some(edict) = edicts.index(index) {
+                    self.select_main_edict(edict);
+                }
+            } else if let Some(index) = Self::consume_number_key(ctx, Modifiers::NONE) {
+                if let Some(creature) = self.input.hidden.get_main().index(index) {
+                    self.toggle_main_creature(creature);
                 }
-                // }}}
             }
-            // }}}
-            // {{{ Reveal
-            Ok(RequestPayload::Reveal(reveal_index, updated_score)) => {
-                let _guard = tracing::span!(Level::TRACE, "Updating history");
-                tracing::event!(Level::TRACE, "Updating history");
-
-                let entry = &mut self.history[self.input.state.battlefields.current];
-
-                match self
-                    .input
-                    .phase
-                    .advance_phase(&self.input.state, reveal_index)
-                    .unwrap()
-                {
-                    PerPhase::Sabotage(sabotage) => {
-                        for player in Player::PLAYERS {
-                            let player_entry = player.select_mut(&mut entry.choices);
-                            player_entry.edict = Some(player.select(sabotage.edict_choices));
-                        }
-                    }
-                    PerPhase::Seer(seer) => {
-                        for player in Player::PLAYERS {
-                            let player_entry = player.select_mut(&mut entry.choices);
-                            player_entry.sabotage = player.select(seer.sabotage_choices);
 
-                            if player != self.input.state.last_creature_revealer() {
-                                player_entry.creature = Some(seer.revealed_creature);
-                            }
-                        }
-                    }
-                    PerPhase::Main(_) => {
-                        let first_revealer = !self.input.state.last_creature_revealer();
-                        let revealed_creature =
-                            first_revealer.select(entry.choices).creature.unwrap();
-
-                        let decoded = reveal_index
-                            .decode_seer_phase_reveal(self.input.state.graveyard, revealed_creature)
-                            .unwrap();
-
-                        let player_entry = self
-                            .input
-                            .state
-                            .last_creature_revealer()
-                            .select_mut(&mut entry.choices);
-
-                        player_entry.creature = Some(decoded);
-                        entry.score = Some(updated_score);
-                    }
-                };
+            // Enter is also used to click on the focused widget,
+            // so we only treat it as a shortcut when nothing is focused.
+            let nothing_focused = ctx.memory(|memory| memory.focus().is_none());
+            if nothing_focused && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter)) {
+                self.try_communicate_main();
+            }
 
-                tracing::event!(Level::TRACE, "Succesfully updated history");
+            if ctx.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape)) {
+                self.clear_main_choice();
             }
-            // }}}
-            // {{{ Game finished
-            Ok(RequestPayload::GameFinished) => {
-                self.game_finished = true;
+        } else if self.can_make_sabotage_choice() {
+            if let Some(index) = Self::consume_number_key(ctx, Modifiers::NONE) {
+                if let Some(guess) = self.opponent_creature_possibilities().index(index) {
+                    self.communicate_sabotage(guess);
+                }
+            }
+        } else if self.can_make_seer_choice() {
+            if let Some(index) = Self::consume_number_key(ctx, Modifiers::NONE) {
+                let choices = self.my_creatures().unwrap_or_default();
+                if let Some(creature) = choices.index(index) {
+                    self.communicate_seer(creature);
+                }
             }
-            // }}}
-            _ => {}
         }
     }
     // }}}
@@ -504,35 +1891,62 @@ impl egui_dock::TabViewer for UIState {
     type Tab = UITab;
 
     fn title(&mut self, tab: &mut Self::Tab) -> egui::WidgetText {
-        format!("{tab:?}").into()
+        self.locale().variant("tab", *tab).into()
     }
 
     fn ui(&mut self, ui: &mut Ui, tab: &mut Self::Tab) {
         let span = tracing::span!(Level::INFO, "Rendering ui");
         let _guard = span.enter();
 
+        let locale = self.locale();
         let [me, you] = self.input.player.order_as(self.input.state.player_states);
         match tab {
             // {{{ Field state
             UITab::Field => {
+                self.draw_clocks(ui);
+                self.draw_session(ui);
+
                 if self.game_finished {
-                    let result = self
-                        .history
-                        .last()
-                        .unwrap()
-                        .score
-                        .unwrap()
-                        .from_perspective(self.input.player)
-                        .to_battle_result();
-
-                    ui.heading(format!("Game ended! Game result: {:?}", result));
+                    let result = self.final_score().unwrap_or_default().to_battle_result();
+
+                    ui.horizontal(|ui| {
+                        let result = locale.variant("result", result);
+                        ui.heading(locale.format("game-ended", &[("result", &result)]));
+                        self.draw_score_delta(ui);
+                    });
+
+                    ui.horizontal(|ui| {
+                        if ui.button(locale.get("rematch")).clicked() {
+                            self.menu_request = Some(MenuRequest::Rematch);
+                        }
+
+                        if ui.button(locale.get("back-to-start")).clicked() {
+                            self.menu_request = Some(MenuRequest::StartScreen);
+                        }
+                    });
+
+                    self.draw_review(ui);
                     return;
                 }
 
+                ui.horizontal(|ui| {
+                    if self.strategy_provider.is_some() {
+                        ui.checkbox(
+                            &mut self.show_strategy_hints,
+                            locale.get("show-strategy-hints"),
+                        );
+                    }
+
+                    // Only offered while the runner waits on us, such
+                    // that the game cannot end before it gets noticed.
+                    if !self.decision_sent && ui.button(locale.get("resign")).clicked() {
+                        self.resign();
+                    }
+                });
+
                 ui.vertical(|ui| {
                     // {{{ Prepare data
-                    let opponent_creature_possibilities =
-                        !(self.input.hidden.get_main() | self.input.state.graveyard);
+                    let opponent_creature_possibilities = self.opponent_creature_possibilities();
 
                     let [my_edict, your_edict] = self.played_edicts();
                     let [my_sabotage, your_sabotage] = self.sabotage_choices();
@@ -544,18 +1958,20 @@ impl egui_dock::TabViewer for UIState {
                     let show_your_sabotage =
                         !is_main && self.input.phase.sabotage_status(!self.input.player);
 
-                    // The following three vars are true when the player is expected to make a
-                    // choice for the respective phase.
-                    let can_make_main_choice = is_main && !self.decision_sent;
-                    let can_make_sabotage_choice = self.input.phase.tag() == PhaseTag::Sabotage
-                        && !self.decision_sent
-                        && show_my_sabotage;
-                    let can_make_seer_choice = self.input.phase.tag() == PhaseTag::Seer
-                        && !self.decision_sent
-                        && self.input.state.seer_status(self.input.player);
+                    let can_make_main_choice = self.can_make_main_choice();
+                    let can_make_sabotage_choice = self.can_make_sabotage_choice();
+                    let can_make_seer_choice = self.can_make_seer_choice();
+
+                    let columns = [
+                        opponent_creature_possibilities.len(),
+                        self.input.hidden.get_main().len(),
+                        me.edicts.len(),
+                        you.edicts.len(),
+                    ];
+                    self.fit_cards(ui, columns.into_iter().max().unwrap_or_default());
                     // }}}
                     // {{{ Opponent's board
-                    ui.heading("Opponent's board");
+                    ui.heading(locale.get("opponents-board"));
 
                     // {{{ Edicts
                     ui.horizontal(|ui| {
@@ -576,13 +1992,13 @@ impl egui_dock::TabViewer for UIState {
                     // {{{ Choices
                     Grid::new("Opponent's choices").show(ui, |ui| {
                         // {{{ Labels
-                        ui.label("Edict");
+                        ui.label(locale.get("edict"));
 
                         if show_your_sabotage {
-                            ui.label("Sabotage");
+                            ui.label(locale.get("sabotage"));
                         }
 
-                        ui.label("Creature");
+                        ui.label(locale.get("creature"));
 
                         ui.end_row();
                         // }}}
@@ -593,8 +2009,12 @@ impl egui_dock::TabViewer for UIState {
                             self.draw_opt_creature(ui, your_sabotage);
                         }
 
-                        let choices = self.history[self.input.state.battlefields.current].choices;
-                        self.draw_opt_creature(ui, (!self.input.player).select(choices).creature);
+                        let choices =
+                            self.history.turns[self.input.state.battlefields.current].choices;
+                        self.draw_revealed_creature(
+                            ui,
+                            (!self.input.player).select(choices).creature,
+                        );
 
                         ui.end_row();
                     });
@@ -602,25 +2022,27 @@ impl egui_dock::TabViewer for UIState {
                     // }}}
 
                     ui.separator();
-                    ui.label(format!(
-                        "Your score - opponent's score = {:?}",
-                        self.input.state.score.from_perspective(self.input.player)
-                    ));
+                    ui.horizontal(|ui| {
+                        let score = self.input.state.score.from_perspective(self.input.player);
+                        ui.label(locale.format("score-difference", &[("score", &score.0)]));
+                        self.draw_score_delta(ui);
+                    });
                     ui.separator();
 
                     // {{{ Player's board
-                    ui.heading("Your board");
+                    ui.heading(locale.get("your-board"));
 
                     // {{{ Choices
-                    Grid::new("Player's choices").show(ui, |ui| {
+                    // Doubles as the area cards can be dragged onto during the main phase.
+                    let play_area = Grid::new("Player's choices").show(ui, |ui| {
                         // {{{ Labels
-                        ui.label("Edict");
+                        ui.label(locale.get("edict"));
 
                         if show_my_sabotage {
-                            ui.label("Sabotage");
+                            ui.label(locale.get("sabotage"));
                         }
 
-                        ui.label("Creatures");
+                        ui.label(locale.get("creatures"));
                         ui.end_row();
                         // }}}
 
@@ -644,22 +2066,25 @@ impl egui_dock::TabViewer for UIState {
                             self.input.state.creature_choice_size(self.input.player);
 
                         for _ in creature_choices.len()..max_creature_choice_count {
-                            self.textures.card_back.show(ui);
+                            Self::draw_image(ui, self.textures.card_back(), self.card_size());
                         }
                         // }}}
 
                         ui.end_row();
                     });
+                    let play_area = play_area.response.rect;
                     // }}}
                     // {{{ Edicts
                     ui.horizontal(|ui| {
                         for edict in me.edicts {
                             let res = self.draw_edict(ui, edict, can_make_main_choice);
 
-                            if can_make_main_choice && res.clicked() {
-                                if let Some(choice) = &mut self.partial_main_choice {
-                                    choice.edict = Some(edict);
+                            if can_make_main_choice {
+                                if res.clicked() {
+                                    self.select_main_edict(edict);
                                 }
+
+                                self.drag_card(ui, &res, DraggedCard::Edict(edict), play_area);
                             }
                         }
                     });
@@ -669,27 +2094,38 @@ impl egui_dock::TabViewer for UIState {
                         for creature in self.input.hidden.get_main() {
                             let res = self.draw_creature(ui, creature, can_make_main_choice);
 
-                            if can_make_main_choice && res.clicked() {
-                                if let Some(choice) = &mut self.partial_main_choice {
-                                    if choice.creatures.has(creature) {
-                                        choice.creatures.remove(creature);
-                                    } else if choice.creatures.len()
-                                        == self.input.state.creature_choice_size(self.input.player)
-                                    {
-                                        choice.creatures.remove(choice.creatures.index(0).unwrap());
-                                        choice.creatures.insert(creature);
-                                    } else {
-                                        choice.creatures.insert(creature);
-                                    }
+                            if can_make_main_choice {
+                                if res.clicked() {
+                                    self.toggle_main_creature(creature);
                                 }
+
+                                let card = DraggedCard::Creature(creature);
+                                self.drag_card(ui, &res, card, play_area);
                             }
                         }
                     });
                     // }}}
                     // }}}
-                    // {{{ Communicate
-                    if self.input.phase.tag() == PhaseTag::Main {
+                    // {{{ Confirm
+                    // Unless disabled in the settings, nothing gets sent until the
+                    // user explicitly confirms their choice, which makes misclicks recoverable.
+                    if can_make_main_choice && !self.settings.confirm_main_choice {
                         self.try_communicate_main();
+                    } else if can_make_main_choice {
+                        ui.horizontal(|ui| {
+                            let complete = self.complete_main_choice().is_some();
+
+                            let confirm =
+                                egui::Button::new(locale.get("confirm")).shortcut_text("Enter");
+                            if ui.add_enabled(complete, confirm).clicked() {
+                                self.try_communicate_main();
+                            }
+
+                            let clear = egui::Button::new(locale.get("clear")).shortcut_text("Esc");
+                            if ui.add(clear).clicked() {
+                                self.clear_main_choice();
+                            }
+                        });
                     }
                     // }}}
                 });
@@ -698,53 +2134,63 @@ impl egui_dock::TabViewer for UIState {
             // {{{ Effects
             UITab::Effects => {
                 ui.vertical(|ui| {
-                    ui.heading("Your effects");
+                    ui.heading(locale.get("your-effects"));
                     self.draw_status_effect_set(ui, me.effects);
                 });
 
                 ui.vertical(|ui| {
-                    ui.heading("Opponent's effects");
+                    ui.heading(locale.get("opponents-effects"));
                     self.draw_status_effect_set(ui, you.effects);
                 });
             }
             // }}}
             // {{{ History
             UITab::History => {
+                // The battlefield, followed by the three choices of both players.
+                self.fit_cards(ui, 7);
+
                 ui.vertical(|ui| {
                     // {{{ Battlefields
                     Grid::new("battlefield & history grid").show(ui, |ui| {
-                        ui.label("Battlefields");
-                        ui.label("Your creature");
-                        ui.label("Your edict");
-                        ui.label("Your sabotage");
-                        ui.label("Opponent's sabotage");
-                        ui.label("Opponent's edict");
-                        ui.label("Opponent's creature");
+                        ui.label(locale.get("battlefields"));
+                        ui.label(locale.get("your-creature"));
+                        ui.label(locale.get("your-edict"));
+                        ui.label(locale.get("your-sabotage"));
+                        ui.label(locale.get("opponents-sabotage"));
+                        ui.label(locale.get("opponents-edict"));
+                        ui.label(locale.get("opponents-creature"));
                         ui.end_row();
 
-                        for index in 0..4 {
+                        for index in 0..Battlefields::COUNT {
                             let in_the_past =
                                 self.game_finished || index < self.input.state.battlefields.current;
 
-                            self.draw_battlefield(
-                                ui,
-                                self.input.state.battlefields.all[index],
-                                false,
-                            );
+                            let battlefield = self.input.state.battlefields.all[index];
+                            self.draw_battlefield(ui, battlefield, false);
 
-                            let entry = self.history[index];
+                            let entry = self.history.turns[index];
 
                             if in_the_past {
-                                let [me, you] = self.input.player.order_as(entry.choices);
-                                self.draw_opt_creature(ui, me.creature);
+                                let choices = self.input.player.order_as(entry.choices);
+                                let [my_badges, your_badges] =
+                                    Self::creature_badges(battlefield, choices);
+                                let [me, you] = choices;
+
+                                let res = self.draw_opt_creature(ui, me.creature);
+                                self.draw_badges(ui, &res, &my_badges);
                                 self.draw_opt_edict(ui, me.edict);
                                 self.draw_opt_creature(ui, me.sabotage);
                                 self.draw_opt_creature(ui, you.sabotage);
                                 self.draw_opt_edict(ui, you.edict);
-                                self.draw_opt_creature(ui, you.creature);
+                                let res = self.draw_revealed_creature(ui, you.creature);
+                                self.draw_badges(ui, &res, &your_badges);
                             } else {
                                 for _ in 0..6 {
-                                    Self::draw_gray_image(ui, &self.textures.card_back);
+                                    self.draw_gray_image(
+                                        ui,
+                                        self.textures.card_back(),
+                                        self.card_size(),
+                                    );
                                 }
                             }
 
@@ -752,16 +2198,27 @@ impl egui_dock::TabViewer for UIState {
                         }
                     });
                     // }}}
+                    // {{{ Export
+                    ui.horizontal(|ui| {
+                        if ui.button(locale.get("export")).clicked() {
+                            self.export_match(ui.ctx());
+                        }
+
+                        if let Some(status) = &self.export_status {
+                            ui.label(status);
+                        }
+                    });
+                    // }}}
                     // {{{ Score plot
                     ui.group(|ui| {
-                        ui.heading("Your score - Opponent's score");
+                        ui.heading(locale.get("score-chart"));
 
                         let plot = egui::plot::Plot::new("score plot")
                             .allow_scroll(false)
                             .allow_drag(false);
 
                         plot.show(ui, |plot_ui| {
-                            let scores = self.history.iter().filter_map(|e| e.score);
+                            let scores = self.history.turns.iter().filter_map(|e| e.score);
                             let min_score = scores.clone().min().unwrap_or(Score(-5));
                             let max_score = scores.clone().max().unwrap_or(Score(5));
 
@@ -775,6 +2232,7 @@ impl egui_dock::TabViewer for UIState {
 
                             let points: Vec<_> = self
                                 .history
+                                .turns
                                 .iter()
                                 .enumerate()
                                 .filter_map(|(i, entry)| entry.score.map(|score| (i, score)))
@@ -802,10 +2260,10 @@ impl egui_dock::TabViewer for UIState {
                 if let Some(hovered) = self.hovered_card {
                     // {{{ Card name
                     let name = match hovered {
-                        HoveredCard::Creature(inner) => format!("{inner:?}"),
-                        HoveredCard::Edict(inner) => format!("{inner:?}"),
-                        HoveredCard::Battlefield(inner) => format!("{inner:?}"),
-                        HoveredCard::StatusEffect(inner) => format!("{inner:?}"),
+                        HoveredCard::Creature(inner) => locale.creature(inner),
+                        HoveredCard::Edict(inner) => locale.edict(inner),
+                        HoveredCard::Battlefield(inner) => locale.battlefield(inner),
+                        HoveredCard::StatusEffect(inner) => locale.status_effect(inner),
                     };
 
                     ui.heading(name);
@@ -814,16 +2272,25 @@ impl egui_dock::TabViewer for UIState {
                     let max_width = ui.available_width();
                     match hovered {
                         HoveredCard::Creature(creature) => {
-                            self.textures.creatures[creature as usize]
-                                .show_size(ui, Vec2::new(max_width, max_width));
+                            Self::draw_image(
+                                ui,
+                                self.textures.creature(creature),
+                                Vec2::new(max_width, max_width),
+                            );
                         }
                         HoveredCard::Edict(edict) => {
-                            self.textures.edicts[edict as usize]
-                                .show_size(ui, Vec2::new(max_width, max_width));
+                            Self::draw_image(
+                                ui,
+                                self.textures.edict(edict),
+                                Vec2::new(max_width, max_width),
+                            );
                         }
                         HoveredCard::Battlefield(battlefield) => {
-                            self.textures.battlefields[battlefield as usize]
-                                .show_size(ui, Vec2::new(max_width, max_width));
+                            Self::draw_image(
+                                ui,
+                                self.textures.battlefield(battlefield),
+                                Vec2::new(max_width, max_width),
+                            );
                         }
                         HoveredCard::StatusEffect(status_effect) => {
                             self.draw_status_effect(ui, status_effect)
@@ -832,9 +2299,9 @@ impl egui_dock::TabViewer for UIState {
                     // }}}
                     // {{{ Description
                     let description = match hovered {
-                        HoveredCard::Creature(inner) => Creature::DESCRIPTIONS[inner as usize],
-                        HoveredCard::Edict(inner) => Edict::DESCRIPTIONS[inner as usize],
-                        _ => "unwritten",
+                        HoveredCard::Creature(inner) => locale.creature_description(inner),
+                        HoveredCard::Edict(inner) => locale.edict_description(inner),
+                        _ => locale.get("description-missing").to_string(),
                     };
 
                     ui.label(description);
@@ -844,10 +2311,12 @@ impl egui_dock::TabViewer for UIState {
                     match hovered {
                         // {{{ Creature
                         HoveredCard::Creature(creature) => {
-                            ui.label(format!(
-                                "Strength = {} + bonuses from:",
-                                creature.strength()
-                            ));
+                            ui.label(
+                                locale.format(
+                                    "strength-bonuses",
+                                    &[("strength", &creature.strength())],
+                                ),
+                            );
 
                             let bonus_image_size = ui.available_width() / 4.0;
 
@@ -860,7 +2329,7 @@ impl egui_dock::TabViewer for UIState {
 
                                         let res = Self::draw_clickable_image_size(
                                             ui,
-                                            &self.textures.battlefields[battlefield as usize],
+                                            self.textures.battlefield(battlefield),
                                             [bonus_image_size * size_multiplier; 2],
                                         );
 
@@ -886,12 +2355,12 @@ impl egui_dock::TabViewer for UIState {
                             if creatures != CreatureSet::empty() {
                                 let bonus_image_size = ui.available_width() / 5.0;
 
-                                ui.label("Battlefield bonuses");
+                                ui.label(locale.get("battlefield-bonuses"));
                                 ui.horizontal(|ui| {
                                     for creature in creatures {
                                         let res = Self::draw_clickable_image_size(
                                             ui,
-                                            &self.textures.creatures[creature as usize],
+                                            self.textures.creature(creature),
                                             [bonus_image_size; 2],
                                         );
 
@@ -904,7 +2373,10 @@ impl egui_dock::TabViewer for UIState {
                                 ui.separator();
                             }
 
-                            ui.label(format!("Reward: {}", battlefield.reward()));
+                            ui.label(locale.format(
+                                "reward",
+                                &[("reward", &self.input.state.rules.reward(battlefield))],
+                            ));
                         }
                         // }}}
                         _ => {}
@@ -914,17 +2386,171 @@ impl egui_dock::TabViewer for UIState {
             } // }}}
             // {{{ Debug info
             UITab::DebugInfo => {
-                ui.heading("Debug info");
+                ui.heading(locale.get("tab-debug-info"));
                 Grid::new("debug info").show(ui, |ui| {
-                    ui.label("Decision sent");
+                    ui.label(locale.get("decision-sent"));
                     ui.label(format!("{}", self.decision_sent));
                     ui.end_row();
-                    ui.label("Phase");
+                    ui.label(locale.get("phase"));
                     ui.label(format!("{:?}", self.input.phase.tag()));
                     ui.end_row();
-                    ui.label("Hovered");
+                    ui.label(locale.get("hovered"));
                     ui.label(format!("{:?}", self.hovered_card));
+                    ui.end_row();
+                    ui.label(locale.get("last-reveal"));
+                    ui.label(self.last_reveal.as_deref().unwrap_or("-"));
+                });
+            } // }}}
+            // {{{ Beliefs
+            UITab::Beliefs => {
+                ui.heading(locale.get("opponents-hand"));
+                ui.label(locale.get(if self.strategy_provider.is_some() {
+                    "beliefs-weighted"
+                } else {
+                    "beliefs-uniform"
+                }));
+
+                let Some(beliefs) = &self.beliefs else {
+                    ui.label(locale.get("beliefs-unavailable"));
+                    return;
+                };
+
+                let probabilities = beliefs.creature_probabilities();
+                let possibilities = self.opponent_creature_possibilities();
+
+                Grid::new("beliefs").show(ui, |ui| {
+                    for creature in possibilities {
+                        let probability = probabilities[creature as usize];
+
+                        if ui.label(locale.creature(creature)).hovered() {
+                            self.hovered_card = Some(HoveredCard::Creature(creature));
+                        }
+
+                        ui.add(
+                            egui::ProgressBar::new(probability)
+                                .text(format!("{:.0}%", probability * 100.0)),
+                        );
+                        ui.end_row();
+                    }
+                });
+            } // }}}
+            // {{{ Training
+            UITab::Training => {
+                ui.heading(locale.get("tab-training"));
+                self.draw_training(ui);
+            } // }}}
+            // {{{ Settings
+            UITab::Settings => {
+                ui.heading(locale.get("tab-settings"));
+
+                let old_settings = self.settings.clone();
+                let settings = &mut self.settings;
+
+                Grid::new("settings").show(ui, |ui| {
+                    ui.label(locale.get("card-size"));
+                    ui.add(egui::Slider::new(
+                        &mut settings.card_size,
+                        Settings::CARD_SIZE_RANGE,
+                    ));
+                    ui.end_row();
+
+                    ui.label(locale.get("zoom"));
+                    ui.add(egui::Slider::new(&mut settings.zoom, Settings::ZOOM_RANGE));
+                    ui.end_row();
+
+                    ui.label(locale.get("confirm-main-choices"));
+                    ui.checkbox(&mut settings.confirm_main_choice, "");
+                    ui.end_row();
+
+                    ui.label(locale.get("animations"));
+                    ui.checkbox(&mut settings.animations, "");
+                    ui.end_row();
+
+                    ui.label(locale.get("colorblind-cues"));
+                    ui.checkbox(&mut settings.colorblind_cues, "");
+                    ui.end_row();
+
+                    ui.label(locale.get("theme"));
+                    egui::ComboBox::from_id_source("theme")
+                        .selected_text(locale.variant("theme", settings.theme))
+                        .show_ui(ui, |ui| {
+                            for theme in Theme::THEMES {
+                                let name = locale.variant("theme", theme);
+                                ui.selectable_value(&mut settings.theme, theme, name);
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label(locale.get("language"));
+                    egui::ComboBox::from_id_source("language")
+                        .selected_text(settings.language.name())
+                        .show_ui(ui, |ui| {
+                            for language in Language::LANGUAGES {
+                                ui.selectable_value(
+                                    &mut settings.language,
+                                    language,
+                                    language.name(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label(locale.get("log-verbosity"));
+                    egui::ComboBox::from_id_source("log level")
+                        .selected_text(settings.log_level.as_str())
+                        .show_ui(ui, |ui| {
+                            for level in Settings::LOG_LEVELS {
+                                ui.selectable_value(&mut settings.log_level, level, level.as_str());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label(locale.get("clock"))
+                        .on_hover_text(locale.get("applies-next-game"));
+                    let clock_name = |clock: Option<TimeControl>| {
+                        clock.map_or_else(|| locale.get("no-clock").to_string(), |c| c.to_string())
+                    };
+                    egui::ComboBox::from_id_source("clock")
+                        .selected_text(clock_name(settings.clock))
+                        .show_ui(ui, |ui| {
+                            let presets = TimeControl::PRESETS.map(Some);
+                            for clock in [None].into_iter().chain(presets) {
+                                ui.selectable_value(&mut settings.clock, clock, clock_name(clock));
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label(locale.get("on-timeout"))
+                        .on_hover_text(locale.get("applies-next-game"));
+                    egui::ComboBox::from_id_source("on timeout")
+                        .selected_text(locale.variant("timeout", settings.on_timeout))
+                        .show_ui(ui, |ui| {
+                            for behaviour in TimeoutBehaviour::BEHAVIOURS {
+                                let name = locale.variant("timeout", behaviour);
+                                ui.selectable_value(&mut settings.on_timeout, behaviour, name);
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label(locale.get("custom-art"));
+                    let mut assets = settings
+                        .assets
+                        .as_ref()
+                        .map(|path| path.display().to_string())
+                        .unwrap_or_default();
+                    if ui.text_edit_singleline(&mut assets).changed() {
+                        settings.assets = Some(assets)
+                            .filter(|path| !path.is_empty())
+                            .map(PathBuf::from);
+                    }
+                    ui.end_row();
                 });
+
+                if *settings != old_settings {
+                    if let Err(error) = settings.save(Settings::DEFAULT_PATH) {
+                        tracing::event!(Level::ERROR, "Failed to save settings: {error}");
+                    }
+                }
             } // }}}
         }
     }
@@ -934,40 +2560,200 @@ impl egui_dock::TabViewer for UIState {
 // {{{ GUIApp stuff
 impl GUIApp {
     /// Called once before the first frame.
-    pub fn new(_cc: &eframe::CreationContext<'_>, communication: UIBus) -> Self {
-        let ui_state = UIState {
-            input: communication.receiver.recv().unwrap().get_input().unwrap(),
-            history: [HistoryEntry::default(); 4],
-            partial_main_choice: Some(PartialMainPhaseChoice::default()),
-            decision_sent: false,
-            textures: AppTextures::new(),
-            hovered_card: None,
-            game_finished: false,
-            communication,
-        };
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        launcher: GameLauncher,
+        settings: Settings,
+    ) -> Self {
+        cc.egui_ctx.set_visuals(settings.theme.visuals());
 
         // {{{ Tabs
-        let mut tab_tree = egui_dock::Tree::new(vec![UITab::Field, UITab::Effects, UITab::History]);
+        let mut tab_tree = egui_dock::Tree::new(vec![
+            UITab::Field,
+            UITab::Effects,
+            UITab::History,
+            UITab::Training,
+        ]);
         tab_tree.split_left(
             egui_dock::tree::node_index::NodeIndex::root(),
             0.33,
-            vec![UITab::CardPreview, UITab::DebugInfo],
+            vec![UITab::CardPreview, UITab::Beliefs, UITab::DebugInfo],
         );
         // }}}
 
         Self {
             tab_tree,
-            state: ui_state,
+            start_screen: StartScreen {
+                launcher,
+                opponent: OpponentKind::Random,
+                opponents: OpponentKind::BUILTIN.to_vec(),
+                error: None,
+            },
+            state: None,
+            textures: Some(AppTextures::new(&cc.egui_ctx, settings.assets.as_deref())),
+            strategy_provider: None,
+            settings,
+            session: SessionStats::default(),
+        }
+    }
+
+    /// Plugs in something which can recommend strategies to the human player.
+    /// The hints are shown in an optional overlay.
+    pub fn with_strategy_provider(mut self, provider: Box<dyn StrategyProvider>) -> Self {
+        self.strategy_provider = Some(provider);
+        self
+    }
+
+    /// Offers the given opponents on the start screen, instead of the builtin
+    /// ones. The launcher must be able to start games against all of them,
+    /// and there must be at least one.
+    pub fn with_opponents(mut self, opponents: &[OpponentKind]) -> Self {
+        self.start_screen.opponents = opponents.to_vec();
+        if !opponents.contains(&self.start_screen.opponent) {
+            self.start_screen.opponent = opponents[0];
+        }
+
+        self
+    }
+
+    /// Ends the current game (if any), and starts a new
+    /// one against the opponent selected on the start screen.
+    fn start_game(&mut self) {
+        self.stop_game();
+
+        let opponent = self.start_screen.opponent;
+        match (self.start_screen.launcher)(opponent, self.settings.time_control()) {
+            Some(bus) => {
+                tracing::event!(Level::INFO, "Starting game against {:?}", opponent);
+
+                let textures = self.textures.take().unwrap();
+                let provider = self.strategy_provider.take();
+
+                self.start_screen.error = None;
+                self.state = Some(UIState::new(
+                    bus,
+                    textures,
+                    self.settings.clone(),
+                    provider,
+                    self.session,
+                ));
+            }
+            None => {
+                let name = self.locale().variant("opponent", opponent);
+                self.start_screen.error = Some(
+                    self.locale()
+                        .format("opponent-unavailable", &[("opponent", &name)]),
+                );
+            }
+        }
+    }
+
+    /// The translations for the language picked in the settings.
+    fn locale(&self) -> &'static Locale {
+        Locale::of(self.settings.language)
+    }
+
+    /// Drops the current game, reclaiming the resources it was using.
+    fn stop_game(&mut self) {
+        if let Some(state) = self.state.take() {
+            self.textures = Some(state.textures);
+            self.strategy_provider = state.strategy_provider;
+            self.settings = state.settings;
+            self.session = state.session;
+        }
+    }
+
+    /// Renders the screen used to pick an opponent.
+    fn start_screen_ui(&mut self, ui: &mut Ui) {
+        let locale = self.locale();
+        ui.heading(locale.get("new-game"));
+
+        egui::ComboBox::from_label(locale.get("opponent"))
+            .selected_text(locale.variant("opponent", self.start_screen.opponent))
+            .show_ui(ui, |ui| {
+                for &opponent in &self.start_screen.opponents {
+                    let name = locale.variant("opponent", opponent);
+                    ui.selectable_value(&mut self.start_screen.opponent, opponent, name);
+                }
+            });
+
+        if ui.button(locale.get("start")).clicked() {
+            // Rematches keep the session going, new games start a new one.
+            self.session = SessionStats::default();
+            self.start_game();
+        }
+
+        if let Some(error) = &self.start_screen.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+
+        if let Some(textures) = &self.textures {
+            let progress = textures.progress();
+            if progress < 1.0 {
+                ui.add(egui::ProgressBar::new(progress).text(locale.get("loading-art")));
+            }
+        }
+    }
+
+    /// Focuses one of the tabs whenever the respective F-key gets pressed.
+    fn handle_tab_shortcuts(tab_tree: &mut egui_dock::Tree<UITab>, ctx: &egui::Context) {
+        for (tab, key) in UITab::SHORTCUT_ORDER.iter().zip(UITab::SHORTCUT_KEYS) {
+            if !ctx.input_mut(|i| i.consume_key(Modifiers::NONE, key)) {
+                continue;
+            }
+
+            if let Some((node, index)) = tab_tree.find_tab(tab) {
+                tab_tree.set_active_tab(node, index);
+                tab_tree.set_focused_node(node);
+            }
         }
     }
 
     /// Main rendering function
     fn ui(&mut self, ui: &mut Ui) {
-        self.state.try_accept_input();
+        // Card images get decoded one per frame, unless they are needed sooner.
+        let textures = match &self.state {
+            Some(state) => Some(&state.textures),
+            None => self.textures.as_ref(),
+        };
+
+        if textures.is_some_and(AppTextures::preload) {
+            ui.ctx().request_repaint();
+        }
+
+        let Some(state) = &mut self.state else {
+            self.start_screen_ui(ui);
+            return;
+        };
+
+        state.try_accept_input();
+        state.handle_timeout();
+        state.animations.update();
+        state.handle_shortcuts(ui.ctx());
+        Self::handle_tab_shortcuts(&mut self.tab_tree, ui.ctx());
 
         egui_dock::DockArea::new(&mut self.tab_tree)
             .style(egui_dock::Style::from_egui(ui.style().as_ref()))
-            .show_inside(ui, &mut self.state);
+            .show_inside(ui, state);
+
+        state.draw_strategy_hints(ui.ctx());
+
+        // Games driven by the gui only make progress while frames are being drawn.
+        let waiting_for_game =
+            state.communication.driver.is_some() && state.decision_sent && !state.game_finished;
+        if state.animations.is_running() || waiting_for_game {
+            ui.ctx().request_repaint();
+        }
+
+        if ui.visuals().dark_mode != (state.settings.theme == Theme::Dark) {
+            ui.ctx().set_visuals(state.settings.theme.visuals());
+        }
+
+        match state.menu_request {
+            Some(MenuRequest::Rematch) => self.start_game(),
+            Some(MenuRequest::StartScreen) => self.stop_game(),
+            None => {}
+        }
     }
 }
 
@@ -975,7 +2761,18 @@ impl GUIApp {
 impl eframe::App for GUIApp {
     /// Called each time the UI needs repainting, which may be many times per second.
     /// Put your widgets into a `SidePanel`, `TopPanel`, `CentralPanel`, `Window` or `Area`.
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Zoom on top of the scale the screen asks for, which keeps high dpi screens sharp.
+        let settings = self
+            .state
+            .as_ref()
+            .map_or(&self.settings, |state| &state.settings);
+        let native = frame.info().native_pixels_per_point.unwrap_or(1.0);
+        let pixels_per_point = native * settings.zoom;
+        if (ctx.pixels_per_point() - pixels_per_point).abs() > f32::EPSILON {
+            ctx.set_pixels_per_point(pixels_per_point);
+        }
+
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::ScrollArea::new([false; 2]).show(ui, |ui| self.ui(ui));
         });
@@ -0,0 +1,288 @@
+//! Translations of the text shown by the gui.
+//!
+//! Every language has a table of messages in `assets/locales/<code>.ftl`,
+//! written in a small subset of the fluent syntax: `key = value` lines,
+//! indented continuation lines for multiline values, `#` comments and
+//! `{ $variable }` placeables. Messages missing from some language fall
+//! back to english, and then to the key itself.
+use crate::game::battlefield::Battlefield;
+use crate::game::creature::Creature;
+use crate::game::edict::Edict;
+use crate::game::status_effect::StatusEffect;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Write};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tracing::Level;
+
+// {{{ Language
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Language {
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "fr")]
+    French,
+}
+
+impl Language {
+    pub const LANGUAGES: [Language; 2] = [Language::English, Language::French];
+
+    /// The code used for the locale file and in the settings.
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::French => "fr",
+        }
+    }
+
+    /// The name of the language, in said language.
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::French => "Français",
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Language::English => include_str!("../../assets/locales/en.ftl"),
+            Language::French => include_str!("../../assets/locales/fr.ftl"),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Language::LANGUAGES
+            .into_iter()
+            .find(|language| language.code() == s)
+            .ok_or(())
+    }
+}
+// }}}
+// {{{ Parsing
+/// Parses the `key = value` pairs of a locale file.
+fn parse(source: &str) -> HashMap<String, String> {
+    let mut messages = HashMap::new();
+    let mut current: Option<(String, String)> = None;
+
+    for line in source.lines() {
+        let is_continuation = line.starts_with(char::is_whitespace) && !line.trim().is_empty();
+
+        if is_continuation {
+            if let Some((_, value)) = &mut current {
+                if !value.is_empty() {
+                    value.push('\n');
+                }
+
+                value.push_str(line.trim());
+            }
+
+            continue;
+        }
+
+        messages.extend(current.take());
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) => {
+                current = Some((key.trim().to_string(), value.trim().to_string()))
+            }
+            None => tracing::event!(Level::WARN, "Invalid locale line {line:?}"),
+        }
+    }
+
+    messages.extend(current);
+    messages
+}
+
+/// Turns the name of some enum variant into the kebab-case form used in keys.
+fn kebab_case(value: impl Debug) -> String {
+    let mut result = String::new();
+
+    for (index, char) in format!("{value:?}").chars().enumerate() {
+        if char.is_uppercase() && index > 0 {
+            result.push('-');
+        }
+
+        result.push(char.to_ascii_lowercase());
+    }
+
+    result
+}
+// }}}
+// {{{ Locale
+/// The messages of a single language.
+pub struct Locale {
+    messages: HashMap<String, String>,
+    fallback: Option<&'static Locale>,
+}
+
+impl Locale {
+    /// The locale of some language, parsed the first time it gets requested.
+    pub fn of(language: Language) -> &'static Locale {
+        static LOCALES: [OnceLock<Locale>; 2] = [OnceLock::new(), OnceLock::new()];
+
+        LOCALES[language as usize].get_or_init(|| Locale {
+            messages: parse(language.source()),
+            fallback: (language != Language::English).then(|| Locale::of(Language::English)),
+        })
+    }
+
+    fn lookup(&self, key: &str) -> Option<&str> {
+        match self.messages.get(key) {
+            Some(message) => Some(message),
+            None => self.fallback?.lookup(key),
+        }
+    }
+
+    /// Looks up some message, returning the key itself if it is missing.
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.lookup(key).unwrap_or_else(|| {
+            tracing::event!(Level::DEBUG, "Missing translation for {key:?}");
+            key
+        })
+    }
+
+    /// Looks up some message, filling in the given variables.
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut result = String::new();
+        let mut rest = self.get(key);
+
+        while let Some(start) = rest.find('{') {
+            let Some(length) = rest[start..].find('}') else {
+                break;
+            };
+
+            result.push_str(&rest[..start]);
+
+            let placeable = &rest[start..=start + length];
+            let name = placeable[1..length].trim().trim_start_matches('$');
+            match args.iter().find(|(arg, _)| *arg == name) {
+                Some((_, value)) => write!(result, "{value}").unwrap(),
+                None => result.push_str(placeable),
+            }
+
+            rest = &rest[start + length + 1..];
+        }
+
+        result.push_str(rest);
+        result
+    }
+
+    // {{{ Cards
+    /// The message for some enum variant, with keys of the form `<prefix>-<variant>`.
+    pub fn variant(&self, prefix: &str, value: impl Debug) -> String {
+        let key = format!("{prefix}-{}", kebab_case(value));
+        self.get(&key).to_string()
+    }
+
+    pub fn creature(&self, creature: Creature) -> String {
+        self.variant("creature", creature)
+    }
+
+    pub fn creature_description(&self, creature: Creature) -> String {
+        self.get(&format!("creature-{}-description", kebab_case(creature)))
+            .to_string()
+    }
+
+    pub fn edict(&self, edict: Edict) -> String {
+        self.variant("edict", edict)
+    }
+
+    pub fn edict_description(&self, edict: Edict) -> String {
+        self.get(&format!("edict-{}-description", kebab_case(edict)))
+            .to_string()
+    }
+
+    pub fn battlefield(&self, battlefield: Battlefield) -> String {
+        self.variant("battlefield", battlefield)
+    }
+
+    pub fn status_effect(&self, status_effect: StatusEffect) -> String {
+        self.variant("effect", status_effect)
+    }
+    // }}}
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_files_are_parsed_correctly() {
+        let messages = parse(
+            "# comment\n\
+             single = Hello { $name }!\n\
+             multi =\n    - first\n    - second\n\n\
+             after = value",
+        );
+
+        assert_eq!(messages["single"], "Hello { $name }!");
+        assert_eq!(messages["multi"], "- first\n- second");
+        assert_eq!(messages["after"], "value");
+        assert_eq!(messages.len(), 3);
+    }
+
+    #[test]
+    fn variables_get_filled_in() {
+        let locale = Locale::of(Language::English);
+
+        assert_eq!(
+            locale.format("reward", &[("reward", &3)]),
+            "Reward: 3".to_string()
+        );
+        assert_eq!(locale.get("missing-key"), "missing-key");
+    }
+
+    #[test]
+    fn every_language_translates_every_message() {
+        let english = parse(Language::English.source());
+
+        for language in Language::LANGUAGES {
+            let messages = parse(language.source());
+
+            for key in english.keys() {
+                assert!(
+                    messages.contains_key(key),
+                    "{language:?} is missing a translation for {key:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_card_has_a_name_and_description() {
+        let locale = Locale::of(Language::English);
+        let has = |key: String| locale.lookup(&key).is_some();
+
+        for creature in Creature::CREATURES {
+            assert!(has(format!("creature-{}", kebab_case(creature))));
+            assert!(has(format!(
+                "creature-{}-description",
+                kebab_case(creature)
+            )));
+        }
+
+        for edict in Edict::EDICTS {
+            assert!(has(format!("edict-{}", kebab_case(edict))));
+            assert!(has(format!("edict-{}-description", kebab_case(edict))));
+        }
+
+        for battlefield in Battlefield::BATTLEFIELDS {
+            assert!(has(format!("battlefield-{}", kebab_case(battlefield))));
+        }
+
+        for status_effect in StatusEffect::STATUS_EFFECTS {
+            assert!(has(format!("effect-{}", kebab_case(status_effect))));
+        }
+    }
+}
@@ -1,5 +1,20 @@
+pub mod always_zero_agent;
+#[cfg(feature = "gui")]
+pub mod animations;
+pub mod async_agent;
+pub mod best_response_agent;
+pub mod clock;
 pub mod echo_ai;
-mod textures;
+#[cfg(feature = "gui")]
 pub mod human_player;
+pub mod locale;
+pub mod observer;
+pub mod opponent_model_agent;
 pub mod random_agent;
-pub mod always_zero_agent;
+pub mod session;
+pub mod settings;
+pub mod strategy_agent;
+pub mod strategy_hints;
+#[cfg(feature = "gui")]
+mod textures;
+pub mod transcript;
@@ -0,0 +1,81 @@
+//! Hooks for watching games played by an `EchoRunner`, without taking part in them.
+//!
+//! Observers see everything that happens during a game (including the hidden
+//! information of both players), which makes them a good fit for logging,
+//! replays, or streaming overlays. Agents must never be handed one.
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::position::GamePosition;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::types::{Player, Score};
+use tracing::Level;
+
+/// Gets notified by the runner whenever something happens during a game.
+/// Every method does nothing by default.
+pub trait GameObserver {
+    /// Called at the start of every phase, before any agent gets asked to decide.
+    #[inline(always)]
+    fn on_phase_start(&mut self, _position: &GamePosition) {}
+
+    /// Called once the (legal) decision of some player has been accepted.
+    #[inline(always)]
+    fn on_decision(&mut self, _position: &GamePosition, _player: Player, _decision: DecisionIndex) {
+    }
+
+    /// Called at the end of every phase, with the position the phase was
+    /// played in (the one the reveal index can be decoded against).
+    #[inline(always)]
+    fn on_reveal(&mut self, _position: &GamePosition, _reveal_index: RevealIndex, _score: Score) {}
+
+    /// Called at the end of every turn (including the last one),
+    /// with the index of the turn and the score after it.
+    #[inline(always)]
+    fn on_turn_end(&mut self, _turn: usize, _score: Score) {}
+
+    /// Called once the game is over (including games ending early by forfeit).
+    #[inline(always)]
+    fn on_game_end(&mut self, _score: Score) {}
+}
+
+/// Logs everything that happens during a game (at the info level).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingObserver;
+
+impl GameObserver for LoggingObserver {
+    fn on_phase_start(&mut self, position: &GamePosition) {
+        tracing::event!(
+            Level::INFO,
+            "Turn {}: {:?} phase started",
+            position.state.battlefields.current + 1,
+            position.phase.tag()
+        );
+    }
+
+    fn on_decision(&mut self, position: &GamePosition, player: Player, decision: DecisionIndex) {
+        let description = decision.describe(
+            &position.state,
+            &position.phase,
+            player,
+            player.select(position.hidden),
+        );
+
+        tracing::event!(Level::INFO, "{player:?} decided: {description}");
+    }
+
+    fn on_reveal(&mut self, position: &GamePosition, reveal_index: RevealIndex, _score: Score) {
+        let description = reveal_index.describe(&position.state, &position.phase);
+        tracing::event!(Level::INFO, "Revealed: {description}");
+    }
+
+    fn on_turn_end(&mut self, turn: usize, score: Score) {
+        tracing::event!(
+            Level::INFO,
+            "Turn {} ended with score {}",
+            turn + 1,
+            score.0
+        );
+    }
+
+    fn on_game_end(&mut self, score: Score) {
+        tracing::event!(Level::INFO, "Game ended with score {}", score.0);
+    }
+}
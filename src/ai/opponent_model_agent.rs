@@ -0,0 +1,511 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::decision::Probability;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::phase::PerPhase;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::choice::{FinalMainPhaseChoice, SabotagePhaseChoice};
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::{Edict, EdictSet};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::simulate::BattleContext;
+use crate::game::types::{Player, Score, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use std::cell::Cell;
+
+// {{{ Frequency model
+/// Counts how often the opponent has played each creature / edict.
+///
+/// The counts are kept across games, which means the model gets
+/// sharper the longer a session goes on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChoiceFrequencies {
+    creatures: [u32; Creature::CREATURES.len()],
+    edicts: [u32; Edict::EDICTS.len()],
+}
+
+impl ChoiceFrequencies {
+    #[inline(always)]
+    pub fn record_creature(&mut self, creature: Creature) {
+        self.creatures[creature as usize] += 1;
+    }
+
+    #[inline(always)]
+    pub fn record_edict(&mut self, edict: Edict) {
+        self.edicts[edict as usize] += 1;
+    }
+
+    /// Returns the number of times a creature has been played so far.
+    #[inline(always)]
+    pub fn creature_count(&self, creature: Creature) -> u32 {
+        self.creatures[creature as usize]
+    }
+
+    /// Returns the number of times an edict has been played so far.
+    #[inline(always)]
+    pub fn edict_count(&self, edict: Edict) -> u32 {
+        self.edicts[edict as usize]
+    }
+
+    /// Probability the opponent plays a given creature, assuming they can only
+    /// play one of `possibilities`. Uses laplace smoothing, such that creatures
+    /// we've never seen played still get some weight.
+    pub fn creature_probability(
+        &self,
+        creature: Creature,
+        possibilities: CreatureSet,
+    ) -> Probability {
+        let total: u32 = possibilities
+            .into_iter()
+            .map(|c| self.creature_count(c) + 1)
+            .sum();
+
+        (self.creature_count(creature) + 1) as Probability / total as Probability
+    }
+
+    /// Similar to `creature_probability`, except the opponent only holds `hand_size`
+    /// of the `possibilities`, the rest having been set aside at the start of the
+    /// game (see `Battlefields::SET_ASIDE`). Every hand is assumed to be equally likely.
+    pub fn creature_probability_in_hand(
+        &self,
+        creature: Creature,
+        possibilities: CreatureSet,
+        hand_size: usize,
+    ) -> Probability {
+        let mut hands = 0;
+        let mut total = 0.0;
+
+        for hand in possibilities.subsets_of_size(hand_size) {
+            hands += 1;
+
+            if hand.has(creature) {
+                total += self.creature_probability(creature, hand);
+            }
+        }
+
+        total / hands as Probability
+    }
+
+    /// Similar to `creature_probability`, but for edicts.
+    pub fn edict_probability(&self, edict: Edict, possibilities: EdictSet) -> Probability {
+        let total: u32 = possibilities
+            .into_iter()
+            .map(|e| self.edict_count(e) + 1)
+            .sum();
+
+        (self.edict_count(edict) + 1) as Probability / total as Probability
+    }
+
+    /// Returns the creature the opponent is most likely to play.
+    pub fn most_likely_creature(&self, possibilities: CreatureSet) -> Option<Creature> {
+        possibilities
+            .into_iter()
+            .max_by_key(|creature| self.creature_count(*creature))
+    }
+}
+// }}}
+// {{{ Agent
+/// An agent which does not assume the opponent plays optimally.
+///
+/// Instead, it keeps track of everything the opponent reveals, builds a
+/// frequency model over their choices, and best responds to said model.
+/// The response is depth-limited: the agent looks a fixed number of turns
+/// ahead (see `with_depth`), assuming the opponent keeps following the
+/// model, and ignores whatever happens past that.
+///
+/// The model persists across games, so the same instance should be used
+/// for every game in a session (see the `EchoAgent` impl for `&mut A`).
+#[derive(Debug, Clone)]
+pub struct OpponentModelAgent {
+    frequencies: ChoiceFrequencies,
+
+    /// How many turns (including the current one) to look ahead for.
+    depth: usize,
+
+    /// The input we received last time we were asked to make a choice.
+    /// Required in order to decode reveal indices.
+    last_input: Option<AgentInput>,
+
+    /// How many battles got simulated for the last decision.
+    searched: Cell<u64>,
+}
+
+impl Default for OpponentModelAgent {
+    fn default() -> Self {
+        Self {
+            frequencies: ChoiceFrequencies::default(),
+            depth: Self::DEFAULT_DEPTH,
+            last_input: None,
+            searched: Cell::new(0),
+        }
+    }
+}
+
+impl OpponentModelAgent {
+    /// Looking further ahead gets expensive quickly, as every turn
+    /// multiplies the number of simulated battles by over a hundred.
+    pub const DEFAULT_DEPTH: usize = 2;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of turns (at least one) the agent looks ahead for.
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        assert!(depth > 0, "The agent must look at least one turn ahead");
+        self.depth = depth;
+        self
+    }
+
+    /// Read-only access to the model built so far.
+    pub fn frequencies(&self) -> &ChoiceFrequencies {
+        &self.frequencies
+    }
+
+    // {{{ Evaluation helpers
+    /// Simulates a battle, returning the score change it causes from the
+    /// perspective of a given player, together with the state the next
+    /// turn starts in (or `None` if the battle ends the game).
+    fn battle(
+        &self,
+        state: &KnownState,
+        player: Player,
+        main_choices: [FinalMainPhaseChoice; 2],
+        sabotage_choices: [SabotagePhaseChoice; 2],
+    ) -> (Probability, Option<KnownState>) {
+        self.searched.set(self.searched.get() + 1);
+
+        let context = BattleContext::new(main_choices, sabotage_choices, *state, false);
+        let (score, next) = match context.advance_known_state().1 {
+            TurnResult::Finished(score) => (score, None),
+            TurnResult::Unfinished(next) => (next.score, Some(next)),
+        };
+
+        let delta = Score(score.0 - state.score.0).from_perspective(player).0;
+        (delta as Probability, next)
+    }
+
+    /// Expected score change from the start of some turn until `depth` turns
+    /// later, for a player holding `hand` who best responds to the model.
+    fn best_response_value(
+        &self,
+        state: &KnownState,
+        player: Player,
+        hand: CreatureSet,
+        depth: usize,
+    ) -> Probability {
+        if depth == 0 {
+            return 0.0;
+        }
+
+        let edicts = state.player_edicts(player);
+
+        hand.subsets_of_size(state.creature_choice_size(player))
+            .flat_map(|creatures| edicts.into_iter().map(move |edict| (creatures, edict)))
+            .map(|(creatures, edict)| {
+                self.expected_main_value(state, player, hand, creatures, edict, depth)
+            })
+            .fold(Probability::NEG_INFINITY, Probability::max)
+    }
+
+    /// Value of a battle for a given player, including the value of the
+    /// following turns (up to the depth limit) when best responding.
+    fn lookahead_value(
+        &self,
+        state: &KnownState,
+        player: Player,
+        hand: CreatureSet,
+        main_choices: [FinalMainPhaseChoice; 2],
+        sabotage_choices: [SabotagePhaseChoice; 2],
+        depth: usize,
+    ) -> Probability {
+        let (delta, next) = self.battle(state, player, main_choices, sabotage_choices);
+        let played = CreatureSet::singleton(player.select(main_choices).creature);
+
+        delta
+            + next.map_or(0.0, |next| {
+                self.best_response_value(&next, player, hand - played, depth - 1)
+            })
+    }
+
+    /// Expected value for playing a set of creatures (one, or two under
+    /// the seer effect) with some edict, weighted by the opponent model.
+    fn expected_main_value(
+        &self,
+        state: &KnownState,
+        player: Player,
+        hand: CreatureSet,
+        creatures: CreatureSet,
+        edict: Edict,
+        depth: usize,
+    ) -> Probability {
+        let opponent_creatures = state.overseer_candidates(hand);
+        let opponent_edicts = state.player_edicts(!player);
+        let guess = self.frequencies.most_likely_creature(opponent_creatures);
+        let my_sabotage = if edict == Edict::Sabotage {
+            guess
+        } else {
+            None
+        };
+
+        let mut total = 0.0;
+
+        for your_creature in opponent_creatures {
+            let creature_probability = self.frequencies.creature_probability_in_hand(
+                your_creature,
+                opponent_creatures,
+                state.hand_size(),
+            );
+
+            for your_edict in opponent_edicts {
+                let edict_probability = self
+                    .frequencies
+                    .edict_probability(your_edict, opponent_edicts);
+
+                // Under the seer effect we get to pick the best of our
+                // creatures after seeing the opponent's one.
+                let value = creatures
+                    .into_iter()
+                    .map(|my_creature| {
+                        self.lookahead_value(
+                            state,
+                            player,
+                            hand,
+                            player.order_as([
+                                FinalMainPhaseChoice::new(my_creature, edict),
+                                FinalMainPhaseChoice::new(your_creature, your_edict),
+                            ]),
+                            player.order_as([my_sabotage, None]),
+                            depth,
+                        )
+                    })
+                    .fold(Probability::NEG_INFINITY, Probability::max);
+
+                total += creature_probability * edict_probability * value;
+            }
+        }
+
+        total
+    }
+    // }}}
+    // {{{ Per phase choices
+    fn choose_main(&self, input: &AgentInput) -> DecisionIndex {
+        let hand = input.hidden.get_main();
+
+        DecisionIndex::enumerate(&input.state, &input.phase, input.player, input.hidden)
+            .filter_map(|(index, decoded)| {
+                let PerPhase::Main((creatures, edict)) = decoded else {
+                    return None;
+                };
+
+                let value = self.expected_main_value(
+                    &input.state,
+                    input.player,
+                    hand,
+                    creatures,
+                    edict,
+                    self.depth,
+                );
+
+                Some((index, value))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or_else(DecisionIndex::default, |(index, _)| index)
+    }
+
+    /// The best guess against the model is simply
+    /// the creature the opponent plays most often.
+    fn choose_sabotage(&self, input: &AgentInput) -> DecisionIndex {
+        let hand = input.hidden.get_main();
+        let possibilities = !(hand | input.state.graveyard);
+        let guess = self.frequencies.most_likely_creature(possibilities);
+
+        DecisionIndex::encode_sabotage_index(&input.state, hand, guess)
+    }
+
+    fn choose_seer(&self, input: &AgentInput) -> Option<DecisionIndex> {
+        let PerPhase::Seer(phase) = input.phase else {
+            return None;
+        };
+
+        let player = input.player;
+        let your_choice = FinalMainPhaseChoice::new(
+            phase.revealed_creature,
+            (!player).select(phase.edict_choices),
+        );
+
+        DecisionIndex::enumerate(&input.state, &input.phase, player, input.hidden)
+            .filter_map(|(index, decoded)| {
+                let PerPhase::Seer(creature) = decoded else {
+                    return None;
+                };
+
+                let my_choice =
+                    FinalMainPhaseChoice::new(creature, player.select(phase.edict_choices));
+
+                let value = self.lookahead_value(
+                    &input.state,
+                    player,
+                    input.hidden.get_main(),
+                    player.order_as([my_choice, your_choice]),
+                    phase.sabotage_choices,
+                    self.depth,
+                );
+
+                Some((index, value))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+    // }}}
+}
+
+impl EchoAgent for OpponentModelAgent {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        self.last_input = Some(agent_input);
+        self.searched.set(0);
+
+        let count = agent_input
+            .player
+            .select(agent_input.phase.decision_counts(&agent_input.state));
+
+        if count == 1 {
+            return DecisionIndex::default();
+        }
+
+        match agent_input.phase {
+            PerPhase::Main(_) => self.choose_main(&agent_input),
+            PerPhase::Sabotage(_) => self.choose_sabotage(&agent_input),
+            PerPhase::Seer(_) => self.choose_seer(&agent_input).unwrap_or_default(),
+        }
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, _updated_score: Score) {
+        let Some(input) = self.last_input else {
+            return;
+        };
+
+        let opponent = !input.player;
+        let last_revealer = input.state.last_creature_revealer();
+
+        match reveal_index.decode(&input.phase, &input.state).ok() {
+            Some(PerPhase::Main(edict_choices)) => {
+                self.frequencies
+                    .record_edict(opponent.select(edict_choices));
+            }
+            Some(PerPhase::Sabotage((_, creature))) if opponent != last_revealer => {
+                self.frequencies.record_creature(creature);
+            }
+            Some(PerPhase::Seer(creature)) if opponent == last_revealer => {
+                self.frequencies.record_creature(creature);
+            }
+            _ => {}
+        }
+    }
+
+    fn search_effort(&mut self) -> Option<u64> {
+        Some(self.searched.get())
+    }
+
+    fn game_finished(&mut self) {
+        self.last_input = None;
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::cfr::fixtures::{last_turn_state, turn_state};
+    use crate::cfr::phase::{MainPhase, Phase};
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// A hand made out of the first few creatures outside the graveyard.
+    fn some_hand(state: &KnownState) -> CreatureSet {
+        let mut hand = CreatureSet::default();
+        for creature in (!state.graveyard).into_iter().take(state.hand_size()) {
+            hand.insert(creature);
+        }
+
+        hand
+    }
+
+    #[test]
+    fn looking_past_the_end_of_the_game_changes_nothing() {
+        let state = last_turn_state();
+        let hand = some_hand(&state);
+        let agent = OpponentModelAgent::new();
+
+        let shallow = agent.best_response_value(&state, Player::Me, hand, 1);
+        let deep = agent.best_response_value(&state, Player::Me, hand, 3);
+
+        assert_eq!(shallow, deep);
+    }
+
+    #[test]
+    fn deeper_searches_simulate_more_battles() {
+        let state = turn_state(Battlefields::COUNT - 2);
+        let hand = some_hand(&state);
+        let agent = OpponentModelAgent::new();
+
+        agent.best_response_value(&state, Player::Me, hand, 1);
+        let shallow = agent.searched.replace(0);
+        agent.best_response_value(&state, Player::Me, hand, 2);
+        let deep = agent.searched.get();
+
+        assert!(shallow > 0);
+        assert!(deep > shallow, "{deep} battles is not more than {shallow}");
+    }
+
+    #[test]
+    fn plays_full_games() {
+        let state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+        let phase = MainPhase::new();
+        let hidden = phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        let mut agent = OpponentModelAgent::new().with_depth(1);
+        for seed in 0..3 {
+            let opponent = RandomAgent::new(StdRng::seed_from_u64(seed));
+            let runner =
+                EchoRunner::new(state, PerPhase::Main(phase), (opponent, &mut agent), hidden);
+
+            assert!(runner.run_game().is_ok());
+        }
+
+        let played: u32 = Creature::CREATURES
+            .iter()
+            .map(|creature| agent.frequencies().creature_count(*creature))
+            .sum();
+        assert!(played > 0);
+    }
+
+    #[test]
+    fn creatures_in_hand_are_played_with_total_probability_one() {
+        let state = turn_state(Battlefields::COUNT - 2);
+        let possibilities = state.overseer_candidates(some_hand(&state));
+        let mut frequencies = ChoiceFrequencies::default();
+        frequencies.record_creature(possibilities.into_iter().next().unwrap());
+
+        let total: Probability = possibilities
+            .into_iter()
+            .map(|creature| {
+                frequencies.creature_probability_in_hand(creature, possibilities, state.hand_size())
+            })
+            .sum();
+
+        assert!((total - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn agents_must_look_ahead() {
+        OpponentModelAgent::new().with_depth(0);
+    }
+}
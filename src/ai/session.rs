@@ -0,0 +1,377 @@
+//! Sessions of several games played between the same two agents.
+//!
+//! The agents take turns being `Player::Me`. Every pair of games gets played
+//! from the same deal, such that both agents get to play both hands, from both
+//! seats. Results are always reported from the perspective of the first agent.
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use super::clock::TimeControl;
+use super::echo_ai::{EchoAgent, EchoRunner};
+use super::transcript::AgentStats;
+use crate::cfr::hidden_index::PerPhaseInfo;
+use crate::cfr::phase::{MainPhase, PerPhase};
+use crate::error::EchoResult;
+use crate::game::creature::CreatureSet;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::{BattleResult, Player, Score};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+
+// {{{ Stats
+/// Aggregated results of the games played so far,
+/// from the perspective of the first agent.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionStats {
+    /// How many games ended in each result, indexed by `BattleResult`.
+    pub results: [usize; 3],
+
+    /// The sum of all the final scores.
+    pub total_score: i64,
+
+    /// The Elo rating of both agents.
+    pub ratings: Pair<f32>,
+}
+
+impl Default for SessionStats {
+    fn default() -> Self {
+        Self {
+            results: [0; 3],
+            total_score: 0,
+            ratings: [Self::INITIAL_RATING; 2],
+        }
+    }
+}
+
+impl SessionStats {
+    /// The rating both agents start the session with.
+    pub const INITIAL_RATING: f32 = 1500.0;
+
+    /// The most a rating can change after a single game.
+    pub const K_FACTOR: f32 = 32.0;
+
+    #[inline(always)]
+    pub fn games(&self) -> usize {
+        self.results.iter().sum()
+    }
+
+    #[inline(always)]
+    pub fn wins(&self) -> usize {
+        self.results[BattleResult::Won as usize]
+    }
+
+    #[inline(always)]
+    pub fn losses(&self) -> usize {
+        self.results[BattleResult::Lost as usize]
+    }
+
+    #[inline(always)]
+    pub fn ties(&self) -> usize {
+        self.results[BattleResult::Tied as usize]
+    }
+
+    /// The average final score, or zero before any game got played.
+    pub fn average_score(&self) -> f32 {
+        self.total_score as f32 / self.games().max(1) as f32
+    }
+
+    /// The probability of the first agent winning the next
+    /// game (counting ties as half a win), according to Elo.
+    pub fn expected_result(&self) -> f32 {
+        let [mine, yours] = self.ratings;
+        1.0 / (1.0 + 10f32.powf((yours - mine) / 400.0))
+    }
+
+    /// Takes the final score of another game into account.
+    pub fn record(&mut self, score: Score) {
+        let result = score.to_battle_result();
+        let actual = match result {
+            BattleResult::Lost => 0.0,
+            BattleResult::Tied => 0.5,
+            BattleResult::Won => 1.0,
+        };
+
+        let delta = Self::K_FACTOR * (actual - self.expected_result());
+        self.ratings[0] += delta;
+        self.ratings[1] -= delta;
+
+        self.results[result as usize] += 1;
+        self.total_score += score.0 as i64;
+    }
+}
+// }}}
+// {{{ Session
+/// Plays a fixed number of games between two agents (see the module docs).
+pub struct Session<A, B> {
+    agents: (A, B),
+    state: KnownState,
+    games: usize,
+    played: usize,
+    clock: Option<TimeControl>,
+    stats: SessionStats,
+
+    /// How both agents went about their decisions, in the order the agents were given.
+    timings: Pair<AgentStats>,
+
+    /// The hands of the current pair of games, indexed by seat.
+    deal: Pair<CreatureSet>,
+
+    seed: u64,
+    rng: StdRng,
+}
+
+impl<A: EchoAgent, B: EchoAgent> Session<A, B> {
+    /// Creates a session of the given number of games,
+    /// all of which start from the given state.
+    pub fn new(state: KnownState, agents: (A, B), games: usize) -> Self {
+        let seed = rand::random();
+
+        Self {
+            agents,
+            state,
+            games,
+            played: 0,
+            clock: None,
+            stats: SessionStats::default(),
+            timings: Default::default(),
+            deal: [CreatureSet::empty(); 2],
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Seeds the deals, together with the runner of every game.
+    /// Random otherwise, see `seed`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Plays every game using the given time control.
+    pub fn with_clock(mut self, control: TimeControl) -> Self {
+        self.clock = Some(control);
+        self
+    }
+
+    /// Carries over the results (and ratings) of a previous session.
+    pub fn with_stats(mut self, stats: SessionStats) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    #[inline(always)]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    #[inline(always)]
+    pub fn stats(&self) -> &SessionStats {
+        &self.stats
+    }
+
+    /// The decision stats of both agents (see `transcript::DecisionStats`).
+    #[inline(always)]
+    pub fn timings(&self) -> &Pair<AgentStats> {
+        &self.timings
+    }
+
+    /// How many games of this session have been played so far.
+    #[inline(always)]
+    pub fn played(&self) -> usize {
+        self.played
+    }
+
+    #[inline(always)]
+    pub fn is_finished(&self) -> bool {
+        self.played() >= self.games
+    }
+
+    /// The seat the first agent plays the next game from.
+    #[inline(always)]
+    pub fn seat(&self) -> Player {
+        match self.played() % 2 {
+            0 => Player::Me,
+            _ => Player::You,
+        }
+    }
+
+    pub fn into_agents(self) -> (A, B) {
+        self.agents
+    }
+
+    /// Splits the creatures outside the graveyard between the seats at random.
+    fn deal(&mut self) -> Pair<CreatureSet> {
+        let hand_size = self.state.hand_size();
+        let mut creatures: Vec<_> = (!self.state.graveyard).into_iter().collect();
+        creatures.shuffle(&mut self.rng);
+
+        let mut hands = [CreatureSet::empty(); 2];
+        for (index, creature) in creatures.into_iter().take(2 * hand_size).enumerate() {
+            hands[index / hand_size].insert(creature);
+        }
+
+        hands
+    }
+
+    /// Plays the next game, returning its final score
+    /// from the perspective of the first agent.
+    pub fn play_game(&mut self) -> EchoResult<Score> {
+        let seat = self.seat();
+        if seat == Player::Me {
+            self.deal = self.deal();
+        }
+
+        let hidden = self.deal.map(PerPhaseInfo::Main);
+        let phase = PerPhase::Main(MainPhase::new());
+        let seed = self.rng.gen();
+        let clock = self.clock;
+
+        let transcript = match seat {
+            Player::Me => {
+                let agents = (&mut self.agents.0, &mut self.agents.1);
+                let runner = EchoRunner::new(self.state, phase, agents, hidden).with_seed(seed);
+
+                match clock {
+                    Some(control) => runner.with_clock(control),
+                    None => runner,
+                }
+                .run_game_with_transcript()?
+            }
+            Player::You => {
+                let agents = (&mut self.agents.1, &mut self.agents.0);
+                let runner = EchoRunner::new(self.state, phase, agents, hidden).with_seed(seed);
+
+                match clock {
+                    Some(control) => runner.with_clock(control),
+                    None => runner,
+                }
+                .run_game_with_transcript()?
+            }
+        };
+
+        let timings = seat.order_as(transcript.agent_stats());
+        for (total, timings) in self.timings.iter_mut().zip(&timings) {
+            total.merge(timings);
+        }
+
+        let score = transcript
+            .result
+            .expect("Finished games always have a result")
+            .from_perspective(seat);
+        self.stats.record(score);
+        self.played += 1;
+
+        Ok(score)
+    }
+
+    /// Plays every remaining game, returning the final results.
+    pub fn run(mut self) -> EchoResult<SessionStats> {
+        while !self.is_finished() {
+            self.play_game()?;
+        }
+
+        Ok(self.stats)
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::always_zero_agent::AlwaysZeroAgent;
+    use crate::ai::echo_ai::AgentInput;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::cfr::decision_index::DecisionIndex;
+    use crate::game::battlefield::{Battlefield, Battlefields};
+
+    /// Remembers the seat it got asked to decide from,
+    /// pretending to search a single node for every decision.
+    #[derive(Default)]
+    struct SeatAgent {
+        seats: Vec<Player>,
+    }
+
+    impl EchoAgent for SeatAgent {
+        fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+            if self.seats.last() != Some(&agent_input.player) {
+                self.seats.push(agent_input.player);
+            }
+
+            DecisionIndex(0)
+        }
+
+        fn search_effort(&mut self) -> Option<u64> {
+            Some(1)
+        }
+    }
+
+    fn state() -> KnownState {
+        KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT])
+    }
+
+    #[test]
+    fn agents_swap_seats() {
+        let agents = (SeatAgent::default(), AlwaysZeroAgent::default());
+        let mut session = Session::new(state(), agents, 4);
+
+        while !session.is_finished() {
+            session.play_game().unwrap();
+        }
+
+        // Stats follow the agents around, instead of the seats.
+        let [searching, other] = *session.timings();
+        assert_eq!(searching.nodes_per_decision(), Some(1.0));
+        assert_eq!(other.nodes_per_decision(), None);
+
+        let (agent, _) = session.into_agents();
+        assert_eq!(
+            agent.seats,
+            [Player::Me, Player::You, Player::Me, Player::You]
+        );
+    }
+
+    #[test]
+    fn mirrored_games_cancel_out() {
+        // Both agents decide the same way, hence only the deal can matter.
+        let agents = (AlwaysZeroAgent::default(), AlwaysZeroAgent::default());
+        let stats = Session::new(state(), agents, 6).run().unwrap();
+
+        assert_eq!(stats.games(), 6);
+        assert_eq!(stats.total_score, 0);
+        assert_eq!(stats.wins(), stats.losses());
+    }
+
+    #[test]
+    fn ratings_move_in_opposite_directions() {
+        let mut stats = SessionStats::default();
+        stats.record(Score(2));
+        assert!(stats.ratings[0] > SessionStats::INITIAL_RATING);
+        assert_eq!(
+            stats.ratings[0] + stats.ratings[1],
+            2.0 * SessionStats::INITIAL_RATING
+        );
+
+        stats.record(Score(0));
+        stats.record(Score(-1));
+        assert_eq!(stats.results, [1, 1, 1]);
+        assert_eq!(stats.total_score, 1);
+    }
+
+    #[test]
+    fn seeded_sessions_can_be_reproduced() {
+        let run = || {
+            let agents = (
+                RandomAgent::new(StdRng::seed_from_u64(1)),
+                RandomAgent::new(StdRng::seed_from_u64(2)),
+            );
+
+            Session::new(state(), agents, 4).with_seed(7).run().unwrap()
+        };
+
+        assert_eq!(run(), run());
+    }
+}
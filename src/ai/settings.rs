@@ -0,0 +1,264 @@
+use super::clock::{TimeControl, TimeoutBehaviour};
+use super::locale::Language;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::Level;
+
+// {{{ Theme
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const THEMES: [Theme; 2] = [Theme::Dark, Theme::Light];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn visuals(self) -> egui::Visuals {
+        match self {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+        }
+    }
+}
+
+impl FromStr for Theme {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Theme::THEMES
+            .into_iter()
+            .find(|theme| theme.name() == s)
+            .ok_or(())
+    }
+}
+// }}}
+// {{{ Settings
+/// User preferences for the gui, persisted in between runs as toml.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Width & height cards get rendered at on the field. Cards
+    /// shrink below this when the window is too narrow to fit them.
+    pub card_size: f32,
+
+    /// Scale applied to the whole ui, on top of the scale of the screen.
+    pub zoom: f32,
+
+    /// Whether main phase choices require pressing the confirm button,
+    /// or get sent as soon as they are complete.
+    pub confirm_main_choice: bool,
+    pub animations: bool,
+
+    /// Whether cues otherwise conveyed by color alone (like grayed out cards)
+    /// get backed by patterns and badges, such that they can be told apart
+    /// without perceiving colors.
+    pub colorblind_cues: bool,
+    pub theme: Theme,
+    pub language: Language,
+
+    /// Verbosity of the logs emitted by this crate.
+    /// Only read at startup.
+    #[serde(with = "log_level")]
+    pub log_level: Level,
+
+    /// Folder containing custom card art, laid out like the `assets` folder
+    /// of this repo (`creatures/wall.png`, `battlefields/night.jpeg`, ...).
+    /// Missing images fall back to the builtin art. Only read at startup.
+    #[serde(with = "assets", skip_serializing_if = "Option::is_none")]
+    pub assets: Option<PathBuf>,
+
+    /// The time control games started from the gui get played with, if any.
+    #[serde(with = "clock", skip_serializing_if = "Option::is_none")]
+    pub clock: Option<TimeControl>,
+    pub on_timeout: TimeoutBehaviour,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            card_size: 130.0,
+            zoom: 1.0,
+            confirm_main_choice: true,
+            animations: true,
+            colorblind_cues: false,
+            theme: Theme::Dark,
+            language: Language::English,
+            log_level: Level::INFO,
+            assets: None,
+            clock: None,
+            on_timeout: TimeoutBehaviour::Forfeit,
+        }
+    }
+}
+
+impl Settings {
+    pub const DEFAULT_PATH: &'static str = "echo_settings.toml";
+    pub const CARD_SIZE_RANGE: std::ops::RangeInclusive<f32> = 50.0..=200.0;
+    pub const ZOOM_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+    pub const LOG_LEVELS: [Level; 5] = [
+        Level::ERROR,
+        Level::WARN,
+        Level::INFO,
+        Level::DEBUG,
+        Level::TRACE,
+    ];
+
+    // {{{ Parsing
+    /// Parses a settings file.
+    ///
+    /// Unknown keys and invalid values are reported and skipped,
+    /// such that a broken config never prevents the gui from starting.
+    pub fn parse(source: &str) -> Self {
+        let mut settings = Self::default();
+
+        match toml::from_str(source) {
+            Ok(table) => settings.apply(&table),
+            Err(error) => tracing::event!(Level::WARN, "Ignoring invalid settings: {error}"),
+        }
+
+        settings
+    }
+
+    /// Similar to `parse`, but only overwrites the settings present in the table.
+    pub fn apply(&mut self, overrides: &toml::Table) {
+        for (key, value) in overrides {
+            let mut table = self.to_table();
+            table.insert(key.clone(), value.clone());
+
+            match toml::Value::Table(table).try_into::<Self>() {
+                Ok(settings) => *self = settings.clamped(),
+                Err(error) => {
+                    tracing::event!(
+                        Level::WARN,
+                        "Ignoring invalid setting {key} = {value}: {error}"
+                    )
+                }
+            }
+        }
+    }
+
+    pub fn serialize(&self) -> String {
+        toml::to_string(self).expect("Settings always serialize to toml")
+    }
+
+    fn to_table(&self) -> toml::Table {
+        match toml::Value::try_from(self) {
+            Ok(toml::Value::Table(table)) => table,
+            _ => unreachable!("Settings always serialize to a table"),
+        }
+    }
+
+    /// Brings the sizes back inside the ranges the gui supports.
+    fn clamped(mut self) -> Self {
+        let (sizes, zooms) = (Self::CARD_SIZE_RANGE, Self::ZOOM_RANGE);
+        self.card_size = self.card_size.clamp(*sizes.start(), *sizes.end());
+        self.zoom = self.zoom.clamp(*zooms.start(), *zooms.end());
+        self
+    }
+
+    /// The time control games should get played with, if any.
+    pub fn time_control(&self) -> Option<TimeControl> {
+        self.clock
+            .map(|clock| clock.with_timeout_behaviour(self.on_timeout))
+    }
+    // }}}
+    // {{{ Persistence
+    /// Loads the settings stored at some path,
+    /// falling back to the defaults if the file does not exist.
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        match fs::read_to_string(path) {
+            Ok(source) => Self::parse(&source),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+    // }}}
+}
+// }}}
+// {{{ Field formats
+/// Log levels are stored by name (e.g. `"INFO"`).
+mod log_level {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use tracing::Level;
+
+    pub fn serialize<S: Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(level.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Level, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| D::Error::custom(format!("unknown log level {name:?}")))
+    }
+}
+
+/// Paths are stored as strings, where the empty string means no path.
+mod assets {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::path::PathBuf;
+
+    pub fn serialize<S: Serializer>(
+        path: &Option<PathBuf>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let path = path.as_ref().map(|path| path.to_string_lossy());
+        serializer.serialize_str(path.as_deref().unwrap_or_default())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<PathBuf>, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        Ok(Some(path)
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from))
+    }
+}
+
+/// Time controls are stored the way they get displayed (e.g. `"3+2"`),
+/// where the empty string means no time control.
+mod clock {
+    use super::TimeControl;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        clock: &Option<TimeControl>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let clock = clock.map(|clock| clock.to_string());
+        serializer.serialize_str(clock.as_deref().unwrap_or_default())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<TimeControl>, D::Error> {
+        let clock = String::deserialize(deserializer)?;
+
+        if clock.is_empty() {
+            return Ok(None);
+        }
+
+        clock
+            .parse()
+            .map(Some)
+            .map_err(|_| D::Error::custom(format!("invalid time control {clock:?}")))
+    }
+}
+// }}}
@@ -0,0 +1,45 @@
+use super::echo_ai::{AgentInput, EchoAgent};
+use super::strategy_hints::StrategyProvider;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::types::Score;
+use crate::helpers::sampling::sample;
+use rand::Rng;
+
+/// An agent which samples its decisions from the strategy some provider
+/// recommends (usually a trained blueprint).
+///
+/// Falls back to picking uniformly at random whenever
+/// the provider has no recommendation to offer.
+pub struct StrategyAgent<P, R> {
+    provider: P,
+    rng: R,
+}
+
+impl<P: StrategyProvider, R: Rng> StrategyAgent<P, R> {
+    pub fn new(provider: P, rng: R) -> Self {
+        Self { provider, rng }
+    }
+}
+
+impl<P: StrategyProvider, R: Rng> EchoAgent for StrategyAgent<P, R> {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        let counts = agent_input.phase.decision_counts(&agent_input.state);
+        let count = agent_input.player.select(counts);
+
+        match self.provider.strategy(&agent_input) {
+            Some(strategy) if strategy.len() == count => {
+                DecisionIndex(sample(&strategy, &mut self.rng))
+            }
+            _ => DecisionIndex(self.rng.gen_range(0..count)),
+        }
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, _updated_score: Score) {
+        self.provider.reveal_info(reveal_index);
+    }
+
+    fn game_finished(&mut self) {
+        self.provider.game_finished();
+    }
+}
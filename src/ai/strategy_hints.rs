@@ -0,0 +1,166 @@
+use super::echo_ai::AgentInput;
+use crate::cfr::blueprint::{BlockId, BlueprintReader, PublicStrategy};
+use crate::cfr::decision::{Probability, Scope};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::{HiddenIndex, PerPhaseInfo};
+use crate::cfr::opening_book::OpeningBook;
+use crate::cfr::reveal_index::RevealIndex;
+use std::io::{self, Read, Seek};
+
+// {{{ Strategy provider trait
+/// Something which can recommend a mixed strategy for some decision.
+///
+/// Used by the gui to display hints for the human player. Providers receive
+/// the same reveal info agents do, such that they can keep track of where
+/// in the game tree we currently are.
+pub trait StrategyProvider {
+    /// Returns the probability of taking each decision, indexed by `DecisionIndex`.
+    /// Returns `None` if no recommendation is available for the given input.
+    fn strategy(&mut self, input: &AgentInput) -> Option<Vec<Probability>>;
+
+    #[inline(always)]
+    fn reveal_info(&mut self, _reveal_index: RevealIndex) {}
+
+    #[inline(always)]
+    fn game_finished(&mut self) {}
+}
+
+/// Allows providers to be lent out (for instance, to a `StrategyAgent`).
+impl<P: StrategyProvider + ?Sized> StrategyProvider for &mut P {
+    #[inline(always)]
+    fn strategy(&mut self, input: &AgentInput) -> Option<Vec<Probability>> {
+        (**self).strategy(input)
+    }
+
+    #[inline(always)]
+    fn reveal_info(&mut self, reveal_index: RevealIndex) {
+        (**self).reveal_info(reveal_index)
+    }
+
+    #[inline(always)]
+    fn game_finished(&mut self) {
+        (**self).game_finished()
+    }
+}
+// }}}
+// {{{ Scope backed provider
+/// Provides strategies by looking them up inside a trained blueprint.
+pub struct ScopeStrategyProvider<'a> {
+    root: &'a Scope<'a>,
+
+    /// The scope corresponding to the current phase, if the blueprint
+    /// reaches this deep into the game.
+    current: Option<&'a Scope<'a>>,
+}
+
+impl<'a> ScopeStrategyProvider<'a> {
+    pub fn new(root: &'a Scope<'a>) -> Self {
+        Self {
+            root,
+            current: Some(root),
+        }
+    }
+}
+
+impl<'a> StrategyProvider for ScopeStrategyProvider<'a> {
+    fn strategy(&mut self, input: &AgentInput) -> Option<Vec<Probability>> {
+        let index = HiddenIndex::encode(&input.state, input.player, input.hidden).ok()?;
+        self.current?
+            .get_explored()?
+            .strategy_for(input.player, index)
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex) {
+        self.current = self.current.and_then(|scope| scope.descend(reveal_index));
+    }
+
+    fn game_finished(&mut self) {
+        self.current = Some(self.root);
+    }
+}
+// }}}
+// {{{ Blueprint file backed provider
+/// Provides strategies by looking them up inside a blueprint file.
+///
+/// Public states are only decompressed once the game actually reaches them,
+/// so the entire blueprint never has to fit in memory.
+pub struct BlueprintStrategyProvider<R> {
+    reader: BlueprintReader<R>,
+
+    /// The public state we are currently in, if the blueprint reaches this far.
+    current: Option<PublicStrategy>,
+}
+
+impl<R: Read + Seek> BlueprintStrategyProvider<R> {
+    pub fn new(mut reader: BlueprintReader<R>) -> io::Result<Self> {
+        let current = Self::load_root(&mut reader)?;
+        Ok(Self { reader, current })
+    }
+
+    /// The blueprint the strategies come from.
+    #[inline(always)]
+    pub fn blueprint(&self) -> &BlueprintReader<R> {
+        &self.reader
+    }
+
+    fn load_root(reader: &mut BlueprintReader<R>) -> io::Result<Option<PublicStrategy>> {
+        if reader.is_empty() {
+            Ok(None)
+        } else {
+            reader.load(BlockId::ROOT).map(Some)
+        }
+    }
+}
+
+impl<R: Read + Seek> StrategyProvider for BlueprintStrategyProvider<R> {
+    fn strategy(&mut self, input: &AgentInput) -> Option<Vec<Probability>> {
+        let matrix = self.current.as_ref()?.get_matrix(input.player);
+
+        match matrix.get(HiddenIndex::encode(&input.state, input.player, input.hidden).ok()?) {
+            Some(strategy) => Some(strategy.to_vec()),
+            None if matrix.decision_count() == 1 => Some(vec![1.0]),
+            None => None,
+        }
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex) {
+        let next = self.current.as_ref().and_then(|c| c.next(reveal_index));
+
+        self.current = next.and_then(|id| match self.reader.load(id) {
+            Ok(block) => Some(block),
+            Err(error) => {
+                tracing::warn!("Failed to load blueprint block {}: {error}", id.0);
+                None
+            }
+        });
+    }
+
+    fn game_finished(&mut self) {
+        self.current = Self::load_root(&mut self.reader).unwrap_or_else(|error| {
+            tracing::warn!("Failed to load the root of the blueprint: {error}");
+            None
+        });
+    }
+}
+// }}}
+// {{{ Opening book provider
+/// Only recommends strategies for the first decision of the game,
+/// which is all an opening book knows about.
+impl StrategyProvider for OpeningBook {
+    fn strategy(&mut self, input: &AgentInput) -> Option<Vec<Probability>> {
+        match input.hidden {
+            PerPhaseInfo::Main(hand) => self.lookup(&input.state, input.player, hand),
+            _ => None,
+        }
+    }
+}
+// }}}
+// {{{ Decision descriptions
+/// Returns a short description of what taking some decision means.
+/// See `DecisionIndex::try_describe` for more details.
+pub fn describe_decision(input: &AgentInput, index: DecisionIndex) -> Option<String> {
+    index
+        .try_describe(&input.state, &input.phase, input.player, input.hidden)
+        .ok()
+}
+// }}}
@@ -1,15 +1,35 @@
 use crate::game::battlefield::Battlefield;
 use crate::game::creature::Creature;
 use crate::game::edict::Edict;
-use crate::helpers::try_from_iter::TryCollect;
-use egui_extras::RetainedImage;
-use std::fmt::Debug;
+use egui::{Color32, ColorImage, Pos2, Rect, TextureHandle, TextureId, TextureOptions};
+use image::imageops::FilterType;
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::Level;
 
+/// Every card image, packed into a single texture atlas.
+///
+/// Images get decoded lazily, either when first drawn or while preloading
+/// (see `preload`), such that startup does not wait for every image to be
+/// decoded. Drawing from a single texture also saves on texture binds.
+///
+/// Art can be replaced by dropping images into a custom assets folder
+/// (see `Settings::assets`), which is looked up before the builtin art.
+/// Images which cannot be decoded are replaced by a flat placeholder.
 pub struct AppTextures {
-    pub edicts: [RetainedImage; 5],
-    pub battlefields: [RetainedImage; 6],
-    pub creatures: [RetainedImage; 11],
-    pub card_back: RetainedImage,
+    atlas: RefCell<TextureHandle>,
+    assets: Option<PathBuf>,
+
+    /// Bitmask of the slots which have been decoded and uploaded.
+    loaded: Cell<u32>,
+}
+
+/// Some image inside the atlas, which can be passed to `egui::Image`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CardTexture {
+    pub id: TextureId,
+    pub uv: Rect,
 }
 
 // {{{ Included bytes
@@ -46,33 +66,185 @@ const CREATURE_TEXTURES: [&[u8]; 11] = [
 
 const CARD_BACK: &[u8] = include_bytes!("../../assets/cardback.png");
 // }}}
+// {{{ Atlas layout
+/// Paths (relative to the assets folder, without extension) of every image.
+/// Creatures come first, followed by the edicts, battlefields and card back.
+const FILES: [&str; SLOTS] = [
+    "creatures/wall",
+    "creatures/seer",
+    "creatures/rogue",
+    "creatures/bard",
+    "creatures/diplomat",
+    "creatures/ranger",
+    "creatures/steward",
+    "creatures/barbarian",
+    "creatures/witch",
+    "creatures/mercenary",
+    "creatures/monarch",
+    "edicts/rilethepublic",
+    "edicts/divertattention",
+    "edicts/sabotage",
+    "edicts/gambit",
+    "edicts/ambush",
+    "battlefields/mountain",
+    "battlefields/glade",
+    "battlefields/urban",
+    "battlefields/laststrand",
+    "battlefields/night",
+    "battlefields/plains",
+    "cardback",
+];
+
+const EDICT_OFFSET: usize = 11;
+const BATTLEFIELD_OFFSET: usize = 16;
+const CARD_BACK_SLOT: usize = 22;
+const SLOTS: usize = 23;
+
+/// Images get resized to squares of this size inside the atlas.
+const CELL_SIZE: usize = 512;
+const COLUMNS: usize = 5;
+const ROWS: usize = SLOTS.div_ceil(COLUMNS);
+
+/// Extensions custom art gets looked up with, in order.
+const EXTENSIONS: [&str; 3] = ["png", "jpeg", "jpg"];
+
+/// The builtin bytes of the image in some slot.
+fn embedded(slot: usize) -> &'static [u8] {
+    match slot {
+        CARD_BACK_SLOT => CARD_BACK,
+        slot if slot >= BATTLEFIELD_OFFSET => BATTLEFIELD_TEXTURES[slot - BATTLEFIELD_OFFSET],
+        slot if slot >= EDICT_OFFSET => EDICT_TEXTURES[slot - EDICT_OFFSET],
+        slot => CREATURE_TEXTURES[slot],
+    }
+}
+// }}}
 // {{{ Texture loading code
 impl AppTextures {
-    fn load_array<const N: usize, T: Debug>(images: [&[u8]; N], all: [T; N]) -> [RetainedImage; N] {
-        images
-            .iter()
-            .zip(all)
-            .map(|(bytes, value)| {
-                let name = format!("{:?}", value);
-                RetainedImage::from_image_bytes(name, bytes).unwrap()
-            })
-            .attempt_collect()
-            .unwrap()
+    /// Creates the (still empty) atlas. No image gets decoded yet.
+    pub fn new(ctx: &egui::Context, assets: Option<&Path>) -> Self {
+        if let Some(assets) = assets {
+            tracing::event!(Level::INFO, "Loading custom art from {assets:?}");
+        }
+
+        let blank = ColorImage::new(
+            [COLUMNS * CELL_SIZE, ROWS * CELL_SIZE],
+            Color32::TRANSPARENT,
+        );
+
+        Self {
+            atlas: RefCell::new(ctx.load_texture("cards", blank, TextureOptions::default())),
+            assets: assets.map(Path::to_path_buf),
+            loaded: Cell::new(0),
+        }
     }
 
-    pub fn new() -> Self {
-        let edicts = Self::load_array(EDICT_TEXTURES, Edict::EDICTS);
-        let creatures = Self::load_array(CREATURE_TEXTURES, Creature::CREATURES);
-        let battlefields = Self::load_array(BATTLEFIELD_TEXTURES, Battlefield::BATTLEFIELDS);
+    // {{{ Decoding
+    /// Decodes some image, resizing it to fit a cell of the atlas.
+    fn decode(bytes: &[u8]) -> Result<ColorImage, String> {
+        let image = image::load_from_memory(bytes).map_err(|error| error.to_string())?;
+        let image = image
+            .resize_exact(CELL_SIZE as u32, CELL_SIZE as u32, FilterType::Triangle)
+            .to_rgba8();
 
-        let card_back = RetainedImage::from_image_bytes("card_back", CARD_BACK).unwrap();
+        Ok(ColorImage::from_rgba_unmultiplied(
+            [CELL_SIZE; 2],
+            image.as_flat_samples().as_slice(),
+        ))
+    }
 
-        Self {
-            edicts,
-            card_back,
-            creatures,
-            battlefields,
+    /// Reads the custom version of some image, if one exists.
+    fn load_custom(assets: &Path, file: &str) -> Option<ColorImage> {
+        let (path, bytes) = EXTENSIONS.iter().find_map(|extension| {
+            let path = assets.join(format!("{file}.{extension}"));
+            let bytes = fs::read(&path).ok()?;
+            Some((path, bytes))
+        })?;
+
+        match Self::decode(&bytes) {
+            Ok(image) => Some(image),
+            Err(error) => {
+                tracing::event!(Level::WARN, "Failed to decode {path:?}: {error}");
+                None
+            }
+        }
+    }
+
+    /// Decodes the image in some slot, and copies it into the atlas.
+    fn load_slot(&self, slot: usize) {
+        let file = FILES[slot];
+        let image = self
+            .assets
+            .as_deref()
+            .and_then(|assets| Self::load_custom(assets, file))
+            .or_else(|| Self::decode(embedded(slot)).ok())
+            .unwrap_or_else(|| {
+                tracing::event!(Level::ERROR, "Failed to decode the builtin {file:?} art");
+                ColorImage::new([CELL_SIZE; 2], Color32::DARK_GRAY)
+            });
+
+        let position = [(slot % COLUMNS) * CELL_SIZE, (slot / COLUMNS) * CELL_SIZE];
+        self.atlas
+            .borrow_mut()
+            .set_partial(position, image, TextureOptions::default());
+
+        self.loaded.set(self.loaded.get() | (1 << slot));
+    }
+    // }}}
+    // {{{ Preloading
+    /// Decodes the next image which has not been loaded yet.
+    /// Meant to be called once a frame, returning `false` once everything is loaded.
+    pub fn preload(&self) -> bool {
+        let loaded = self.loaded.get();
+        let Some(slot) = (0..SLOTS).find(|slot| loaded & (1 << slot) == 0) else {
+            return false;
+        };
+
+        self.load_slot(slot);
+        true
+    }
+
+    /// The fraction of the images which have been loaded so far.
+    pub fn progress(&self) -> f32 {
+        self.loaded.get().count_ones() as f32 / SLOTS as f32
+    }
+    // }}}
+    // {{{ Lookup
+    /// Looks up the texture for some slot, loading it on the spot if needed.
+    fn slot(&self, slot: usize) -> CardTexture {
+        if self.loaded.get() & (1 << slot) == 0 {
+            self.load_slot(slot);
+        }
+
+        // Shrink the rect by half a texel, such that
+        // filtering does not bleed into the neighbouring cells.
+        let texel = egui::vec2(1.0 / COLUMNS as f32, 1.0 / ROWS as f32) / CELL_SIZE as f32;
+        let min = Pos2::new(
+            (slot % COLUMNS) as f32 / COLUMNS as f32,
+            (slot / COLUMNS) as f32 / ROWS as f32,
+        );
+        let max = min + egui::vec2(1.0 / COLUMNS as f32, 1.0 / ROWS as f32);
+
+        CardTexture {
+            id: self.atlas.borrow().id(),
+            uv: Rect::from_min_max(min + texel / 2.0, max - texel / 2.0),
         }
     }
+
+    pub fn creature(&self, creature: Creature) -> CardTexture {
+        self.slot(creature as usize)
+    }
+
+    pub fn edict(&self, edict: Edict) -> CardTexture {
+        self.slot(EDICT_OFFSET + edict as usize)
+    }
+
+    pub fn battlefield(&self, battlefield: Battlefield) -> CardTexture {
+        self.slot(BATTLEFIELD_OFFSET + battlefield as usize)
+    }
+
+    pub fn card_back(&self) -> CardTexture {
+        self.slot(CARD_BACK_SLOT)
+    }
+    // }}}
 }
 // }}}
@@ -0,0 +1,157 @@
+//! Typed transcripts of entire games, built by the `EchoRunner`.
+//!
+//! Unlike records (see `game::record`), transcripts keep the exact position
+//! every phase was played in, which means they can be replayed or analyzed
+//! without simulating anything. They are not meant to be written by hand.
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::position::GamePosition;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::game::types::{Player, Score};
+use crate::helpers::pair::Pair;
+use std::time::Duration;
+
+// {{{ Stats
+/// How long some agent took to make a decision, and how much searching it did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecisionStats {
+    /// The wall time between the agent getting asked
+    /// to decide and the decision getting accepted.
+    pub time: Duration,
+
+    /// Nodes (or rollouts) searched, for agents which
+    /// search (see `EchoAgent::search_effort`).
+    pub nodes: Option<u64>,
+}
+
+/// The `DecisionStats` of some agent, summed across one or more games.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AgentStats {
+    pub decisions: usize,
+    pub total_time: Duration,
+    pub max_time: Duration,
+
+    /// The total number of nodes searched, together with the
+    /// number of decisions the agent reported it for.
+    pub nodes: u64,
+    pub searched_decisions: usize,
+}
+
+impl AgentStats {
+    pub fn record(&mut self, stats: DecisionStats) {
+        self.decisions += 1;
+        self.total_time += stats.time;
+        self.max_time = self.max_time.max(stats.time);
+
+        if let Some(nodes) = stats.nodes {
+            self.nodes += nodes;
+            self.searched_decisions += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &AgentStats) {
+        self.decisions += other.decisions;
+        self.total_time += other.total_time;
+        self.max_time = self.max_time.max(other.max_time);
+        self.nodes += other.nodes;
+        self.searched_decisions += other.searched_decisions;
+    }
+
+    pub fn average_time(&self) -> Duration {
+        self.total_time / self.decisions.max(1) as u32
+    }
+
+    /// `None` for agents which never reported searching anything.
+    pub fn nodes_per_decision(&self) -> Option<f64> {
+        (self.searched_decisions > 0).then(|| self.nodes as f64 / self.searched_decisions as f64)
+    }
+}
+// }}}
+// {{{ Transcripts
+
+/// Everything that happened during a single phase.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaseTranscript {
+    /// The position the phase was played in.
+    pub position: GamePosition,
+    pub decisions: Pair<DecisionIndex>,
+    pub reveal: RevealIndex,
+
+    /// The score once the phase was over.
+    pub score: Score,
+
+    /// How both players went about making their decisions.
+    pub stats: Pair<DecisionStats>,
+}
+
+/// Everything that happened during a game, in order.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transcript {
+    /// The position the game started in.
+    pub initial: GamePosition,
+    pub phases: Vec<PhaseTranscript>,
+
+    /// The final score, once the game is over.
+    pub result: Option<Score>,
+
+    /// The player which forfeited the game (by running out of
+    /// time or retries), if the game did not get played out.
+    pub forfeited_by: Option<Player>,
+
+    /// The seed the runner was using (see `EchoRunner::with_seed`).
+    pub seed: u64,
+}
+
+impl Transcript {
+    pub fn new(initial: GamePosition, seed: u64) -> Self {
+        Self {
+            initial,
+            phases: Vec::new(),
+            result: None,
+            forfeited_by: None,
+            seed,
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_finished(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// The score at the end of every turn played so far, in order. The
+    /// last entry belongs to a turn which is still going, if any.
+    pub fn score_trajectory(&self) -> Vec<Score> {
+        let mut scores: Vec<Score> = Vec::new();
+        let mut last_turn = None;
+
+        for phase in &self.phases {
+            let turn = phase.position.state.battlefields.current;
+
+            if last_turn == Some(turn) {
+                *scores.last_mut().unwrap() = phase.score;
+            } else {
+                scores.push(phase.score);
+                last_turn = Some(turn);
+            }
+        }
+
+        scores
+    }
+
+    /// The stats of both players, summed across every phase.
+    pub fn agent_stats(&self) -> Pair<AgentStats> {
+        let mut result = [AgentStats::default(); 2];
+
+        for phase in &self.phases {
+            for (stats, decision) in result.iter_mut().zip(phase.stats) {
+                stats.record(decision);
+            }
+        }
+
+        result
+    }
+}
+// }}}
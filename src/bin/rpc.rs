@@ -0,0 +1,39 @@
+//! Serves the JSON-RPC solver service (see `echo::rpc`) over http.
+//!
+//! Usage: `echo-rpc <blueprint> [address] [samples]`
+use echo::rpc::{serve, SolverService};
+use tracing::Level;
+use tracing_subscriber::prelude::*;
+
+/// Reports some error and exits the program.
+fn exit_with(error: String) -> ! {
+    eprintln!("{error}");
+    std::process::exit(1)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        exit_with("Usage: echo-rpc <blueprint> [address] [samples]".to_string());
+    };
+
+    let address = args.get(1).map_or("127.0.0.1:8080", String::as_str);
+    let samples = match args.get(2) {
+        Some(samples) => samples
+            .parse()
+            .unwrap_or_else(|_| exit_with(format!("Invalid sample count {samples:?}"))),
+        None => 1000,
+    };
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().compact())
+        .with(tracing_subscriber::filter::Targets::new().with_target("echo", Level::INFO))
+        .init();
+
+    let service = SolverService::open(path, samples)
+        .unwrap_or_else(|error| exit_with(format!("Failed to load blueprint {path:?}: {error}")));
+
+    if let Err(error) = serve(service, address) {
+        exit_with(error.to_string());
+    }
+}
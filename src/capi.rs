@@ -0,0 +1,514 @@
+//! C bindings for the core engine, such that games can be hosted by front ends
+//! written in other languages (eg: Unity or Godot). The matching header lives
+//! at `include/echo.h`, and can be regenerated using `cbindgen`.
+//!
+//! Games are represented by opaque `EchoGame` handles, which hold the hidden
+//! information of both players. Decisions are plain indices, ranging from zero
+//! to the count returned by `echo_game_decision_count` (exclusive).
+//!
+//! Functions which can fail return `NULL` or a negative `EchoStatus`, in which
+//! case `echo_last_error` returns a description of what went wrong.
+//! Strings are returned by writing them into caller provided buffers.
+use crate::ai::echo_ai::AgentInput;
+use crate::ai::strategy_hints::{describe_decision, BlueprintStrategyProvider, StrategyProvider};
+use crate::cfr::blueprint::BlueprintReader;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::phase::{MainPhase, PerPhase, Phase};
+use crate::cfr::position::GamePosition;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::{Player, Score, TurnResult};
+use crate::helpers::pair::Pair;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::fs::File;
+use std::io::BufReader;
+use std::ptr;
+
+// {{{ Types
+/// Opaque handle to a game in progress.
+pub struct EchoGame {
+    position: GamePosition,
+    score: Score,
+    finished: bool,
+
+    /// Every reveal so far, used to look the position up inside blueprints.
+    reveals: Vec<RevealIndex>,
+}
+
+/// Opaque handle to a blueprint file.
+pub struct EchoBlueprint(BlueprintStrategyProvider<BufReader<File>>);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoPlayer {
+    EchoMe = 0,
+    EchoYou = 1,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoPhase {
+    EchoMain = 0,
+    EchoSabotage = 1,
+    EchoSeer = 2,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoStatus {
+    /// The game moved on to the next phase.
+    EchoOk = 0,
+    /// The game is over. The final score is available via `echo_game_score`.
+    EchoFinished = 1,
+    /// Some argument was null or otherwise malformed.
+    EchoInvalidArgument = -1,
+    /// The decisions were rejected by the engine.
+    EchoInvalidDecision = -2,
+}
+
+impl From<EchoPlayer> for Player {
+    fn from(player: EchoPlayer) -> Self {
+        match player {
+            EchoPlayer::EchoMe => Player::Me,
+            EchoPlayer::EchoYou => Player::You,
+        }
+    }
+}
+// }}}
+// {{{ Error handling
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', ""))
+        .expect("Nul bytes have just been removed");
+
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Returns a description of the last error which occurred on the current
+/// thread, or `NULL` if no error occurred yet. The string stays valid until
+/// the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn echo_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+// }}}
+// {{{ Helpers
+/// Reads a nul terminated utf8 string.
+///
+/// # Safety
+///
+/// The pointer must be null, or point to a nul terminated string.
+unsafe fn read_str<'a>(source: *const c_char) -> EchoResult<&'a str> {
+    if source.is_null() {
+        return Err(EchoError::InvalidState(
+            "Unexpected null string".to_string(),
+        ));
+    }
+
+    CStr::from_ptr(source)
+        .to_str()
+        .map_err(|_| EchoError::InvalidState("Strings must be valid utf8".to_string()))
+}
+
+/// Writes a string into a buffer, truncating it if necessary. The result is
+/// always nul terminated (unless the capacity is zero).
+///
+/// Returns the length of the full string, similarly to `snprintf`.
+///
+/// # Safety
+///
+/// The buffer must be valid for writes of `capacity` bytes.
+unsafe fn write_str(source: &str, buffer: *mut c_char, capacity: usize) -> usize {
+    if !buffer.is_null() && capacity > 0 {
+        let count = source.len().min(capacity - 1);
+        ptr::copy_nonoverlapping(source.as_ptr().cast(), buffer, count);
+        *buffer.add(count) = 0;
+    }
+
+    source.len()
+}
+
+impl EchoGame {
+    fn input_for(&self, player: Player) -> AgentInput {
+        AgentInput::from_position(&self.position, player)
+    }
+
+    fn advance(&mut self, decisions: Pair<DecisionIndex>) -> EchoResult<EchoStatus> {
+        if self.finished {
+            return Err(EchoError::InvalidState(
+                "The game is already over".to_string(),
+            ));
+        }
+
+        let (reveal_index, result) = self.position.advance(decisions)?;
+
+        self.reveals.push(reveal_index);
+
+        match result {
+            TurnResult::Finished(score) => {
+                self.score = score;
+                self.finished = true;
+                Ok(EchoStatus::EchoFinished)
+            }
+            TurnResult::Unfinished(position) => {
+                self.score = position.state.score;
+                self.position = position;
+                Ok(EchoStatus::EchoOk)
+            }
+        }
+    }
+}
+// }}}
+// {{{ Games
+/// Starts a new game on the given comma separated battlefields
+/// (eg: `"Night,Glade,Urban,LastStrand"`), dealing random hands
+/// based on the given seed.
+///
+/// Returns `NULL` on failure. The game must be freed using `echo_game_free`.
+///
+/// # Safety
+///
+/// The battlefields must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_new(battlefields: *const c_char, seed: u64) -> *mut EchoGame {
+    let battlefields = read_str(battlefields).and_then(|battlefields| {
+        let battlefields: [Battlefield; Battlefields::COUNT] = battlefields
+            .split(',')
+            .map(|name| name.trim().parse())
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .map_err(|_| {
+                EchoError::InvalidState("Expected exactly four battlefields".to_string())
+            })?;
+
+        Ok(battlefields)
+    });
+
+    match battlefields {
+        Ok(battlefields) => {
+            let state = KnownState::new_starting(battlefields);
+            let main_phase = MainPhase::new();
+            let deals: Vec<_> = main_phase.valid_hidden_states(state.to_summary()).collect();
+            let mut rng = StdRng::seed_from_u64(seed);
+
+            Box::into_raw(Box::new(EchoGame {
+                position: GamePosition::new(
+                    state,
+                    PerPhase::Main(main_phase),
+                    deals[rng.gen_range(0..deals.len())],
+                ),
+                score: state.score,
+                finished: false,
+                reveals: Vec::new(),
+            }))
+        }
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a game created by `echo_game_new`. Passing `NULL` is a no-op.
+///
+/// # Safety
+///
+/// The game must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_free(game: *mut EchoGame) {
+    if !game.is_null() {
+        drop(Box::from_raw(game));
+    }
+}
+
+/// # Safety
+///
+/// The game must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_phase(game: *const EchoGame) -> EchoPhase {
+    match (*game).position.phase {
+        PerPhase::Main(_) => EchoPhase::EchoMain,
+        PerPhase::Sabotage(_) => EchoPhase::EchoSabotage,
+        PerPhase::Seer(_) => EchoPhase::EchoSeer,
+    }
+}
+
+/// # Safety
+///
+/// The game must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_turn(game: *const EchoGame) -> usize {
+    (*game).position.state.battlefields.current
+}
+
+/// The score, from the perspective of the first player.
+///
+/// # Safety
+///
+/// The game must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_score(game: *const EchoGame) -> i8 {
+    (*game).score.0
+}
+
+/// # Safety
+///
+/// The game must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_is_finished(game: *const EchoGame) -> bool {
+    (*game).finished
+}
+
+/// Returns the number of legal decisions the given player can currently make.
+/// Returns zero once the game is over.
+///
+/// # Safety
+///
+/// The game must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_decision_count(
+    game: *const EchoGame,
+    player: EchoPlayer,
+) -> usize {
+    let game = &*game;
+    if game.finished {
+        0
+    } else {
+        Player::from(player).select(game.position.decision_counts())
+    }
+}
+
+/// Writes a short description of what taking some decision means
+/// (eg: `"Play Wall + Gambit"`) into the given buffer.
+///
+/// Returns the length of the full description (see `snprintf`),
+/// or a negative value if the decision is out of range.
+///
+/// # Safety
+///
+/// The game must be a valid handle, and the buffer must be
+/// valid for writes of `capacity` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_describe_decision(
+    game: *const EchoGame,
+    player: EchoPlayer,
+    decision: usize,
+    buffer: *mut c_char,
+    capacity: usize,
+) -> isize {
+    let input = (*game).input_for(player.into());
+    match describe_decision(&input, DecisionIndex(decision)) {
+        Some(description) => write_str(&description, buffer, capacity) as isize,
+        None => {
+            set_last_error(EchoError::Decode("decision index"));
+            EchoStatus::EchoInvalidArgument as isize
+        }
+    }
+}
+
+/// Writes the position from the perspective of the given player
+/// into the given buffer, using the notation from `game::notation`.
+///
+/// Returns the length of the full position (see `snprintf`).
+///
+/// # Safety
+///
+/// The game must be a valid handle, and the buffer must be
+/// valid for writes of `capacity` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_notation(
+    game: *const EchoGame,
+    player: EchoPlayer,
+    buffer: *mut c_char,
+    capacity: usize,
+) -> usize {
+    let game = &*game;
+    let player = Player::from(player);
+    let notation = game.position.to_notation(player);
+
+    write_str(&notation, buffer, capacity)
+}
+
+/// Advances the game to the next phase, given the decisions of both players.
+///
+/// # Safety
+///
+/// The game must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn echo_game_advance(
+    game: *mut EchoGame,
+    my_decision: usize,
+    your_decision: usize,
+) -> EchoStatus {
+    match (*game).advance([DecisionIndex(my_decision), DecisionIndex(your_decision)]) {
+        Ok(status) => status,
+        Err(error) => {
+            set_last_error(&error);
+            match error {
+                EchoError::InvalidDecision(_) => EchoStatus::EchoInvalidDecision,
+                _ => EchoStatus::EchoInvalidArgument,
+            }
+        }
+    }
+}
+// }}}
+// {{{ Blueprints
+/// Opens a blueprint file, returning `NULL` on failure.
+/// The blueprint must be freed using `echo_blueprint_free`.
+///
+/// # Safety
+///
+/// The path must be a nul terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn echo_blueprint_open(path: *const c_char) -> *mut EchoBlueprint {
+    let provider = read_str(path).and_then(|path| {
+        File::open(path)
+            .and_then(|file| BlueprintReader::new(BufReader::new(file)))
+            .and_then(BlueprintStrategyProvider::new)
+            .map_err(|error| {
+                EchoError::InvalidState(format!("Failed to load blueprint {path:?}: {error}"))
+            })
+    });
+
+    match provider {
+        Ok(provider) => Box::into_raw(Box::new(EchoBlueprint(provider))),
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Frees a blueprint opened by `echo_blueprint_open`. Passing `NULL` is a no-op.
+///
+/// # Safety
+///
+/// The blueprint must not be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn echo_blueprint_free(blueprint: *mut EchoBlueprint) {
+    if !blueprint.is_null() {
+        drop(Box::from_raw(blueprint));
+    }
+}
+
+/// Writes the probability of the given player taking every decision into
+/// the output buffer, which must have room for `echo_game_decision_count` values.
+///
+/// Returns the number of decisions, or a negative value if the blueprint
+/// does not cover the current position (including blueprints trained
+/// for positions the game never went through).
+///
+/// # Safety
+///
+/// The blueprint and game must be valid handles, and the output
+/// must be valid for writes of `capacity` floats.
+#[no_mangle]
+pub unsafe extern "C" fn echo_blueprint_strategy(
+    blueprint: *mut EchoBlueprint,
+    game: *const EchoGame,
+    player: EchoPlayer,
+    output: *mut f32,
+    capacity: usize,
+) -> isize {
+    let (provider, game) = (&mut (*blueprint).0, &*game);
+
+    if game.finished {
+        set_last_error("The game is already over");
+        return EchoStatus::EchoInvalidArgument as isize;
+    }
+
+    if let Err(error) = provider.blueprint().check_position(
+        &game.position.state,
+        &game.position.phase,
+        &game.reveals,
+    ) {
+        set_last_error(error);
+        return EchoStatus::EchoInvalidArgument as isize;
+    }
+
+    provider.game_finished();
+    for reveal_index in &game.reveals {
+        provider.reveal_info(*reveal_index);
+    }
+
+    let strategy = match provider.strategy(&game.input_for(player.into())) {
+        Some(strategy) => strategy,
+        None => {
+            set_last_error("The blueprint does not cover the current position");
+            return EchoStatus::EchoInvalidArgument as isize;
+        }
+    };
+
+    if output.is_null() || capacity < strategy.len() {
+        set_last_error(format!(
+            "Expected room for {} probabilities",
+            strategy.len()
+        ));
+        return EchoStatus::EchoInvalidArgument as isize;
+    }
+
+    ptr::copy_nonoverlapping(strategy.as_ptr(), output, strategy.len());
+    strategy.len() as isize
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battlefields(names: &str) -> CString {
+        CString::new(names).unwrap()
+    }
+
+    #[test]
+    fn games_run_to_completion() {
+        unsafe {
+            let game = echo_game_new(battlefields("Night,Glade,Urban,LastStrand").as_ptr(), 0);
+            assert!(!game.is_null());
+
+            let mut status = EchoStatus::EchoOk;
+            while status == EchoStatus::EchoOk {
+                assert!(!echo_game_is_finished(game));
+
+                let mut buffer = [0; 256];
+                let length = echo_game_notation(game, EchoPlayer::EchoMe, buffer.as_mut_ptr(), 256);
+                assert_eq!(CStr::from_ptr(buffer.as_ptr()).to_bytes().len(), length);
+
+                let [mine, yours] = [EchoPlayer::EchoMe, EchoPlayer::EchoYou]
+                    .map(|player| echo_game_decision_count(game, player) - 1);
+
+                status = echo_game_advance(game, mine, yours);
+            }
+
+            assert_eq!(status, EchoStatus::EchoFinished);
+            assert_eq!(echo_game_decision_count(game, EchoPlayer::EchoMe), 0);
+            echo_game_free(game);
+        }
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        unsafe {
+            assert!(echo_game_new(battlefields("Night,Glade").as_ptr(), 0).is_null());
+            assert!(!echo_last_error().is_null());
+
+            let game = echo_game_new(battlefields("Night,Glade,Urban,LastStrand").as_ptr(), 0);
+            let count = echo_game_decision_count(game, EchoPlayer::EchoMe);
+            assert_eq!(
+                echo_game_advance(game, count, 0),
+                EchoStatus::EchoInvalidDecision
+            );
+            assert_eq!(echo_game_phase(game), EchoPhase::EchoMain);
+            echo_game_free(game);
+        }
+    }
+}
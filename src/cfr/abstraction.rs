@@ -0,0 +1,196 @@
+//! Hidden state abstraction.
+//!
+//! Trees store a decision vector for every hidden index of every player, which
+//! is what makes deep trees run out of memory. An abstraction groups the hidden
+//! states of a player into buckets of similar hand strength, with every hidden
+//! state inside a bucket sharing the same weights (and thus the same strategy).
+//!
+//! Decision indices are relative to the hidden state of the player, so sharing
+//! a strategy means taking decisions with the same indices. This is only an
+//! approximation of the real game, which is the price paid for the memory.
+use super::hidden_index::{HiddenIndex, HiddenState};
+use super::phase::{Phase, PhaseTag};
+use crate::game::creature::Creature;
+use crate::game::known_state::KnownState;
+use crate::game::types::Player;
+
+/// The maximum number of buckets for the hidden states of every phase.
+/// Phases without a bucket count are left exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HiddenAbstraction {
+    pub main: Option<usize>,
+    pub sabotage: Option<usize>,
+    pub seer: Option<usize>,
+}
+
+/// Maps the hidden indices of a player (in some state and phase) to buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Buckets {
+    /// The bucket of every hidden index.
+    pub of_index: Vec<usize>,
+
+    /// The number of buckets. Every bucket contains at least one hidden index.
+    pub count: usize,
+}
+
+impl HiddenAbstraction {
+    pub fn bucket_count(&self, phase: PhaseTag) -> Option<usize> {
+        match phase {
+            PhaseTag::Main => self.main,
+            PhaseTag::Sabotage => self.sabotage,
+            PhaseTag::Seer => self.seer,
+        }
+    }
+
+    /// Splits the hidden states of a player into equally sized buckets ranked
+    /// by their strength. Returns `None` if there are no more hidden states
+    /// than buckets, in which case the abstraction would not save anything.
+    pub fn buckets<P: Phase>(
+        &self,
+        phase: &P,
+        state: &KnownState,
+        player: Player,
+    ) -> Option<Buckets> {
+        let hidden_count = player.select(phase.hidden_counts(state));
+        let count = self.bucket_count(P::TAG)?.max(1);
+
+        if hidden_count <= count {
+            return None;
+        }
+
+        let mut ranked: Vec<_> = (0..hidden_count)
+            .map(|index| {
+                let hidden = HiddenIndex(index)
+                    .decode(state, player, phase.hidden_index_decoding_info())
+                    .expect("Every hidden index below the count is valid");
+
+                (hand_strength(state, hidden), index)
+            })
+            .collect();
+
+        ranked.sort_unstable();
+
+        let mut of_index = vec![0; hidden_count];
+        for (rank, (_, index)) in ranked.into_iter().enumerate() {
+            of_index[index] = rank * count / hidden_count;
+        }
+
+        Some(Buckets { of_index, count })
+    }
+}
+
+/// Rough measure of how good a hidden state is on the upcoming battlefields.
+///
+/// Every creature in hand is worth its strength (plus one on battlefields
+/// granting it a bonus), summed over the battlefields still to be fought on.
+/// Creatures chosen this turn are fought with right away, so they additionally
+/// count for their strength on the current battlefield, weighted such that
+/// the choice always matters more than the rest of the hand.
+pub fn hand_strength(state: &KnownState, hidden: HiddenState) -> u32 {
+    let battlefields = state.battlefields.active();
+    let strength = |creature: Creature| -> u32 {
+        battlefields
+            .iter()
+            .map(|battlefield| creature.strength() as u32 + battlefield.bonus(creature) as u32)
+            .sum()
+    };
+
+    let hand: u32 = hidden.hand.into_iter().map(strength).sum();
+    let choice: u32 = hidden.choice.map_or(0, |choice| {
+        let current = state.battlefields.current();
+        choice
+            .into_iter()
+            .map(|creature| creature.strength() as u32 + current.bonus(creature) as u32)
+            .sum()
+    });
+
+    // Hands are at most 11 creatures of strength at most 6 (with bonus) on at
+    // most 4 battlefields, so scaling by 512 keeps the two parts apart.
+    choice * 512 + hand
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::decision::DecisionMatrix;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::phase::MainPhase;
+    use crate::cfr::train::TrainingContext;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use bumpalo::Bump;
+    use std::collections::HashSet;
+
+    #[test]
+    fn buckets_are_non_empty_and_ranked_by_strength() {
+        let state = last_turn_state();
+        let phase = MainPhase::new();
+        let abstraction = HiddenAbstraction {
+            main: Some(3),
+            ..Default::default()
+        };
+
+        let buckets = abstraction.buckets(&phase, &state, Player::Me).unwrap();
+        assert_eq!(buckets.count, 3);
+        assert_eq!(buckets.of_index.len(), phase.hidden_counts(&state)[0]);
+
+        let strengths: Vec<_> = (0..buckets.of_index.len())
+            .map(|index| {
+                let hidden = HiddenIndex(index)
+                    .decode(&state, Player::Me, phase.hidden_index_decoding_info())
+                    .unwrap();
+                hand_strength(&state, hidden)
+            })
+            .collect();
+
+        for bucket in 0..buckets.count {
+            assert!(buckets.of_index.contains(&bucket));
+        }
+
+        for (i, a) in buckets.of_index.iter().enumerate() {
+            for (j, b) in buckets.of_index.iter().enumerate() {
+                if a < b {
+                    assert!(strengths[i] <= strengths[j]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bucketed_trees_share_weights_and_train() {
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let abstraction = HiddenAbstraction {
+            main: Some(2),
+            ..Default::default()
+        };
+        let mut scope = GenerationContext::new(1, state, &allocator)
+            .with_abstraction(abstraction)
+            .generate();
+
+        let explored = scope.get_explored().unwrap();
+        let DecisionMatrix::Expanded(vectors) = explored.matrices.get_matrix(Player::Me) else {
+            panic!("The root matrix should not be trivial");
+        };
+
+        let weights: HashSet<_> = vectors
+            .iter()
+            .map(|vector| vector.regret_sum.as_ptr())
+            .collect();
+        assert!(vectors.len() > 2);
+        assert_eq!(weights.len(), 2);
+
+        TrainingContext::new(false).cfr(&mut scope, state.to_summary(), 20);
+
+        let explored = scope.get_explored().unwrap();
+        let DecisionMatrix::Expanded(vectors) = explored.matrices.get_matrix(Player::Me) else {
+            unreachable!()
+        };
+
+        let strategies: HashSet<_> = vectors
+            .iter()
+            .map(|vector| format!("{:?}", vector.get_average_strategy()))
+            .collect();
+        assert_eq!(strategies.len(), 2);
+    }
+}
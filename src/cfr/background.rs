@@ -0,0 +1,245 @@
+//! Training running on a thread of its own.
+//!
+//! Generates the tree for the next couple of turns starting at some state,
+//! then runs iterations of `cfr` until told to stop. Generation is limited by
+//! a memory budget, as the first turns of a game take tens of gigabytes. Progress (including the
+//! average strategy of a single hand at the root) gets published every now
+//! and then, such that it can be watched from elsewhere (like the gui).
+use super::decision::{Probability, Scope};
+use super::generate::{GenerationContext, MemoryBudget};
+use super::hidden_index::{EncodingInfo, HiddenIndex};
+use super::leaves::HeuristicLeaves;
+use super::train::TrainingContext;
+use crate::game::creature::CreatureSet;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::Player;
+use bumpalo::Bump;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// The state of some background training run at some point in time.
+#[derive(Debug, Clone, Default)]
+pub struct TrainingSnapshot {
+    /// Whether the tree has been generated (training only starts afterwards).
+    pub generated: bool,
+
+    /// Whether the training thread has exited.
+    pub finished: bool,
+    pub iterations: usize,
+
+    /// Time spent training (excluding generation).
+    pub elapsed: Duration,
+
+    /// Bytes allocated for the tree.
+    pub memory: usize,
+
+    /// Subtrees left unexplored because they did not fit in the memory budget.
+    pub unexplored: usize,
+
+    /// Why the training thread crashed, if it did.
+    pub error: Option<String>,
+
+    /// The average strategy of the watched hand, indexed by `DecisionIndex`.
+    pub strategy: Option<Vec<Probability>>,
+}
+
+impl TrainingSnapshot {
+    pub fn iterations_per_second(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+
+        if elapsed == 0.0 {
+            0.0
+        } else {
+            self.iterations as f64 / elapsed
+        }
+    }
+}
+
+/// Handle to a training run. Dropping it stops training.
+pub struct BackgroundTraining {
+    snapshot: Arc<Mutex<TrainingSnapshot>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl BackgroundTraining {
+    /// Minimum time between two published snapshots.
+    pub const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(200);
+
+    /// The most memory the generated tree can take up.
+    pub const MEMORY_BUDGET: usize = 1 << 30;
+
+    /// Starts training the given number of turns, starting at the main phase
+    /// of the given state. Turns past the limit are valued heuristically
+    /// (see `leaves`). The strategy of the given player holding the given hand
+    /// gets published together with the rest of the progress.
+    pub fn start(state: KnownState, turns: usize, player: Player, hand: CreatureSet) -> Self {
+        let snapshot = Arc::new(Mutex::new(TrainingSnapshot::default()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let snapshot = snapshot.clone();
+            let stop = stop.clone();
+
+            thread::spawn(move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    train(state, turns, player, hand, &snapshot, &stop)
+                }));
+
+                let mut snapshot = lock(&snapshot);
+                snapshot.finished = true;
+
+                if let Err(payload) = result {
+                    let message = payload
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| payload.downcast_ref::<String>().cloned())
+                        .unwrap_or_else(|| "The training thread panicked".to_string());
+
+                    snapshot.error = Some(message);
+                }
+            })
+        };
+
+        Self {
+            snapshot,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    #[inline(always)]
+    pub fn snapshot(&self) -> TrainingSnapshot {
+        lock(&self.snapshot).clone()
+    }
+
+    /// Asks the training thread to stop after the current iteration, without waiting for it.
+    #[inline(always)]
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    #[inline(always)]
+    pub fn is_stopping(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Stops training, waiting for the training thread to exit.
+    pub fn wait(mut self) -> TrainingSnapshot {
+        self.stop();
+
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                tracing::event!(Level::ERROR, "The training thread panicked");
+            }
+        }
+
+        self.snapshot()
+    }
+}
+
+impl Drop for BackgroundTraining {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Locks the snapshot, even if the training thread panicked while holding it.
+fn lock(snapshot: &Mutex<TrainingSnapshot>) -> MutexGuard<'_, TrainingSnapshot> {
+    snapshot.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn train(
+    state: KnownState,
+    turns: usize,
+    player: Player,
+    hand: CreatureSet,
+    snapshot: &Mutex<TrainingSnapshot>,
+    stop: &AtomicBool,
+) {
+    let allocator = Bump::new();
+    let budget = MemoryBudget::new(BackgroundTraining::MEMORY_BUDGET);
+    let mut scope = GenerationContext::new(turns, state, &allocator)
+        .with_memory_budget(&budget)
+        .generate();
+    let trainer = TrainingContext::new(false).with_leaf_evaluator(HeuristicLeaves::default());
+
+    let summary = state.to_summary();
+    let index = match HiddenIndex::encode(&state, player, EncodingInfo::Main(hand)) {
+        Ok(index) => index,
+        Err(error) => {
+            lock(snapshot).error = Some(error.to_string());
+            return;
+        }
+    };
+    let strategy = |scope: &Scope| scope.get_explored()?.strategy_for(player, index);
+
+    {
+        let mut snapshot = lock(snapshot);
+        snapshot.generated = true;
+        snapshot.memory = allocator.allocated_bytes();
+        snapshot.unexplored = budget.skipped_scopes();
+        snapshot.strategy = strategy(&scope);
+    }
+
+    let start = Instant::now();
+    let mut last_published = start;
+    let mut iterations = 0;
+
+    while !stop.load(Ordering::Relaxed) {
+        trainer.cfr_iteration(&mut scope, summary);
+        iterations += 1;
+
+        if last_published.elapsed() >= BackgroundTraining::SNAPSHOT_INTERVAL {
+            last_published = Instant::now();
+
+            let mut snapshot = lock(snapshot);
+            snapshot.iterations = iterations;
+            snapshot.elapsed = start.elapsed();
+            snapshot.strategy = strategy(&scope);
+        }
+    }
+
+    let mut snapshot = lock(snapshot);
+    snapshot.iterations = iterations;
+    snapshot.elapsed = start.elapsed();
+    snapshot.strategy = strategy(&scope);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::helpers::bitfield::Bitfield;
+
+    #[test]
+    fn training_can_be_watched_and_stopped() {
+        let state = last_turn_state();
+
+        let mut hand = CreatureSet::empty();
+        for creature in (!state.graveyard).into_iter().take(state.hand_size()) {
+            hand.insert(creature);
+        }
+
+        let training = BackgroundTraining::start(state, 1, Player::Me, hand);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while training.snapshot().iterations == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let snapshot = training.wait();
+        assert!(snapshot.generated && snapshot.finished);
+        assert_eq!(snapshot.error, None);
+        assert_eq!(snapshot.unexplored, 0);
+        assert!(snapshot.iterations > 0);
+        assert!(snapshot.memory > 0);
+
+        let total: Probability = snapshot.strategy.unwrap().iter().sum();
+        assert!((total - 1.0).abs() < 0.001);
+    }
+}
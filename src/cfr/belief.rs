@@ -0,0 +1,287 @@
+//! Beliefs about the hidden information of the opponent.
+//!
+//! A player keeps track of every deal consistent with what it has seen so far,
+//! weighted by the reach probability of the opponent (how likely the opponent
+//! was to play the way it did with the respective hidden information). Without
+//! a strategy for the opponent, every decision counts as equally likely, so the
+//! beliefs merely rule out deals contradicting the revealed information.
+use super::best_response::Deal;
+use super::decision::Probability;
+use super::decision_index::DecisionIndex;
+use super::hidden_index::{EncodingInfo, HiddenState};
+use super::phase::{PerPhase, Phase, SomePhase};
+use super::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::creature::Creature;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use crate::game::types::Player;
+use crate::helpers::pair::Pair;
+use std::collections::HashMap;
+
+/// Deals consistent with everything some player has seen so far.
+#[derive(Debug, Clone)]
+pub struct Beliefs {
+    player: Player,
+
+    /// Weighted by the probability of getting dealt, and of the opponent
+    /// playing along. The weights are not normalized.
+    deals: Vec<Deal>,
+}
+
+impl Beliefs {
+    /// Every deal consistent with the hidden information of the player
+    /// (which is all the player knows at the start of a game).
+    pub fn new(
+        state: &KnownState,
+        phase: &SomePhase,
+        player: Player,
+        hidden: EncodingInfo,
+    ) -> Self {
+        let summary = state.to_summary();
+        let deals = match phase {
+            PerPhase::Main(inner) => consistent_deals(inner, summary, player, hidden),
+            PerPhase::Sabotage(inner) => consistent_deals(inner, summary, player, hidden),
+            PerPhase::Seer(inner) => consistent_deals(inner, summary, player, hidden),
+        };
+
+        Self { player, deals }
+    }
+
+    #[inline(always)]
+    pub fn deals(&self) -> &[Deal] {
+        &self.deals
+    }
+
+    /// Follows the deals into the next phase, once the player has taken some
+    /// decision and the given information got revealed. The strategy of the
+    /// opponent for each of its hidden states gets looked up using the given
+    /// function (returning `None` counts every decision as equally likely).
+    ///
+    /// If the strategy says the opponent would never have played this way, the
+    /// reach probabilities get ignored. Fails if no deal is consistent with the
+    /// revealed information, which means the beliefs were out of sync with the game.
+    pub fn update(
+        &mut self,
+        state: &KnownState,
+        phase: &SomePhase,
+        decision: DecisionIndex,
+        reveal_index: RevealIndex,
+        mut strategy: impl FnMut(EncodingInfo) -> Option<Vec<Probability>>,
+    ) -> EchoResult<()> {
+        let opponent = !self.player;
+        let decision_count = opponent.select(phase.decision_counts(state));
+        let strategies: Vec<_> = self
+            .deals
+            .iter()
+            .map(|(hidden, _)| {
+                strategy(opponent.select(*hidden))
+                    .filter(|strategy| strategy.len() == decision_count)
+                    .unwrap_or_else(|| vec![1.0; decision_count])
+            })
+            .collect();
+
+        let mut deals = self.advance(state, phase, decision, reveal_index, &strategies)?;
+
+        if deals.iter().all(|(_, probability)| *probability == 0.0) {
+            tracing::debug!("The opponent strategy contradicts the revealed information");
+            let uniform = vec![vec![1.0; decision_count]; strategies.len()];
+            deals = self.advance(state, phase, decision, reveal_index, &uniform)?;
+        }
+
+        deals.retain(|(_, probability)| *probability > 0.0);
+
+        if deals.is_empty() {
+            return Err(EchoError::InvalidState(
+                "No deal is consistent with the revealed information".to_string(),
+            ));
+        }
+
+        self.deals = deals;
+        Ok(())
+    }
+
+    /// Splits every deal over the decisions of the opponent, keeping
+    /// the ones leading to the given reveal index.
+    fn advance(
+        &self,
+        state: &KnownState,
+        phase: &SomePhase,
+        decision: DecisionIndex,
+        reveal_index: RevealIndex,
+        strategies: &[Vec<Probability>],
+    ) -> EchoResult<Vec<Deal>> {
+        let summary = state.to_summary();
+        let mut merged: HashMap<Pair<EncodingInfo>, Probability> = HashMap::new();
+
+        for ((hidden, probability), strategy) in self.deals.iter().zip(strategies) {
+            for (opponent_decision, opponent_probability) in strategy.iter().enumerate() {
+                let decisions = self
+                    .player
+                    .order_as([decision, DecisionIndex(opponent_decision)]);
+                let hidden = hidden.map(HiddenState::from);
+
+                let (_, next_hidden, revealed) = match phase {
+                    PerPhase::Main(inner) => {
+                        inner.advance_hidden_indices(summary, hidden, decisions)
+                    }
+                    PerPhase::Sabotage(inner) => {
+                        inner.advance_hidden_indices(summary, hidden, decisions)
+                    }
+                    PerPhase::Seer(inner) => {
+                        inner.advance_hidden_indices(summary, hidden, decisions)
+                    }
+                }?;
+
+                if revealed == reveal_index {
+                    *merged.entry(next_hidden).or_default() += probability * opponent_probability;
+                }
+            }
+        }
+
+        Ok(merged.into_iter().collect())
+    }
+
+    /// The probability of the opponent holding each creature
+    /// (indexed by the creature), according to these beliefs.
+    pub fn creature_probabilities(&self) -> [Probability; Creature::CREATURES.len()] {
+        let mut result = [0.0; Creature::CREATURES.len()];
+        let total: Probability = self.deals.iter().map(|(_, probability)| probability).sum();
+
+        if total == 0.0 {
+            return result;
+        }
+
+        for (hidden, probability) in &self.deals {
+            for creature in (!self.player).select(*hidden).get_main() {
+                result[creature as usize] += probability / total;
+            }
+        }
+
+        result
+    }
+
+    #[inline(always)]
+    pub fn creature_probability(&self, creature: Creature) -> Probability {
+        self.creature_probabilities()[creature as usize]
+    }
+}
+
+fn consistent_deals<P: Phase>(
+    phase: &P,
+    state: KnownStateSummary,
+    player: Player,
+    hidden: EncodingInfo,
+) -> Vec<Deal> {
+    phase
+        .valid_hidden_states(state)
+        .filter(|deal| player.select(*deal) == hidden)
+        .map(|deal| (deal, 1.0))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::MainPhase;
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::creature::CreatureSet;
+    use crate::game::edict::Edict;
+    use crate::helpers::bitfield::Bitfield;
+
+    fn starting_state() -> KnownState {
+        KnownState::new_starting(Battlefields::last_of([
+            Battlefield::Mountain,
+            Battlefield::Glade,
+            Battlefield::Urban,
+            Battlefield::Night,
+        ]))
+    }
+
+    fn my_hand(state: &KnownState) -> CreatureSet {
+        let mut hand = CreatureSet::empty();
+        for creature in Creature::CREATURES.into_iter().take(state.hand_size()) {
+            hand.insert(creature);
+        }
+
+        hand
+    }
+
+    #[test]
+    fn own_creatures_are_never_believed_to_be_held() {
+        let state = starting_state();
+        let phase = MainPhase::new().to_some_phase();
+        let hand = my_hand(&state);
+        let beliefs = Beliefs::new(&state, &phase, Player::Me, EncodingInfo::Main(hand));
+
+        let probabilities = beliefs.creature_probabilities();
+        let total: Probability = probabilities.iter().sum();
+
+        for creature in hand {
+            assert_eq!(probabilities[creature as usize], 0.0);
+        }
+
+        assert!((total - state.hand_size() as Probability).abs() < 0.001);
+    }
+
+    #[test]
+    fn reach_probabilities_shift_the_beliefs() {
+        let state = starting_state();
+        let phase = MainPhase::new().to_some_phase();
+        let hand = my_hand(&state);
+        let mut beliefs = Beliefs::new(&state, &phase, Player::Me, EncodingInfo::Main(hand));
+        let before = beliefs.creature_probabilities();
+
+        // Play the first edict, and reveal the opponent played the first edict as well.
+        let decision = DecisionIndex(0);
+        let (_, decoded) =
+            DecisionIndex::enumerate(&state, &phase, Player::Me, EncodingInfo::Main(hand))
+                .next()
+                .unwrap();
+        let PerPhase::Main((_, edict)) = decoded else {
+            unreachable!()
+        };
+        assert_eq!(edict, Edict::EDICTS[0]);
+
+        let opponent_hand = beliefs.deals()[0].0[1];
+        let (_, _, reveal_index) = MainPhase::new()
+            .advance_hidden_indices(
+                state.to_summary(),
+                [EncodingInfo::Main(hand), opponent_hand].map(HiddenState::from),
+                [decision, DecisionIndex(0)],
+            )
+            .unwrap();
+
+        // An opponent which only ever plays the edict when holding the last creature.
+        let last = Creature::CREATURES[10];
+        beliefs
+            .update(&state, &phase, decision, reveal_index, |hidden| {
+                let count = Player::You.select(phase.decision_counts(&state));
+                let holds_last = hidden.get_main().has(last);
+
+                Some(
+                    (0..count)
+                        .map(|index| {
+                            let decoded = DecisionIndex(index)
+                                .decode(&state, &phase, Player::You, hidden)
+                                .unwrap();
+                            let PerPhase::Main((_, edict)) = decoded else {
+                                unreachable!()
+                            };
+
+                            if edict == Edict::EDICTS[0] && !holds_last {
+                                0.0
+                            } else {
+                                1.0
+                            }
+                        })
+                        .collect(),
+                )
+            })
+            .unwrap();
+
+        let after = beliefs.creature_probabilities();
+        assert!(before[last as usize] < 1.0);
+        assert!((after[last as usize] - 1.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,386 @@
+//! Best responses against the average strategies learned during training.
+//!
+//! The value of a best response measures how much a player could win by
+//! exploiting the strategy of the opponent. Summing these values over both
+//! players gives the nash gap (sometimes called "nash conv"), which is zero
+//! exactly when the average strategies form a nash equilibrium. Unlike the
+//! regrets accumulated during training, this is an actual measure of how good
+//! the learned strategies are.
+//!
+//! The opponent can also follow any other frozen strategy (see `evaluate`),
+//! which is what `BestResponseAgent` uses to exploit blueprints in actual games.
+//!
+//! The responding player remembers everything it has seen during the game,
+//! while the trained strategies only get to look at hidden indices,
+//! so the values computed here are an upper bound for the abstraction.
+use super::decision::{ExploredScope, Probability, Scope, UnexploredScope, Utility};
+use super::decision_index::DecisionIndex;
+use super::evaluate::{Cursor, FrozenStrategy};
+use super::hidden_index::{EncodingInfo, HiddenState};
+use super::phase::{MainPhase, Phase};
+use super::reveal_index::RevealIndex;
+use crate::error::EchoResult;
+use crate::game::known_state_summary::KnownStateSummary;
+use crate::game::types::{Player, Score};
+use crate::helpers::pair::Pair;
+use std::collections::HashMap;
+
+/// Hidden information of both players, together with the probability
+/// of dealing it and of the opponent playing the way it did so far.
+pub type Deal = (Pair<EncodingInfo>, Probability);
+
+/// The deals some decision leads to, grouped by the scope they end up in.
+pub type Children = Vec<(RevealIndex, KnownStateSummary, Vec<Deal>)>;
+
+/// Computes the expected utility a player gets by best responding
+/// to the average strategy of the opponent, starting from the main phase.
+pub fn best_response_value(scope: &Scope, state: KnownStateSummary, player: Player) -> Utility {
+    best_response_value_against(scope, state, player, FrozenStrategy::Trained)
+        .expect("The opponent must have a strategy for every deal")
+}
+
+/// Similar to `best_response_value`, except the opponent
+/// sticks to the given strategy instead of the trained one.
+pub fn best_response_value_against(
+    scope: &Scope,
+    state: KnownStateSummary,
+    player: Player,
+    mut opponent: FrozenStrategy,
+) -> EchoResult<Utility> {
+    let phase = MainPhase::new();
+    let hidden_states: Vec<_> = phase.valid_hidden_states(state).collect();
+    let probability = 1.0 / hidden_states.len() as Probability;
+
+    // The player knows its own hand, and can respond differently to each one.
+    let mut hands: Vec<Vec<Deal>> = Vec::new();
+    let mut hand_positions: HashMap<EncodingInfo, usize> = HashMap::new();
+
+    for hidden in hidden_states {
+        let position = *hand_positions
+            .entry(player.select(hidden))
+            .or_insert_with(|| {
+                hands.push(Vec::new());
+                hands.len() - 1
+            });
+
+        hands[position].push((hidden, probability));
+    }
+
+    let cursor = opponent.root()?;
+    let mut total = 0.0;
+
+    for deals in &hands {
+        total += respond(scope, phase, state, player, &mut opponent, &cursor, deals)?;
+    }
+
+    Ok(total)
+}
+
+/// Sums the best response values of both players. This is never
+/// negative, and gets closer to zero as training converges.
+pub fn nash_gap(scope: &Scope, state: KnownStateSummary) -> Utility {
+    Player::PLAYERS
+        .into_iter()
+        .map(|player| best_response_value(scope, state, player))
+        .sum()
+}
+
+/// Computes the utility of a score from the perspective of some player.
+#[inline(always)]
+pub(crate) fn utility_for(player: Player, score: Score) -> Utility {
+    player.select([score.to_utility(), -score.to_utility()])
+}
+
+/// Picks the best decision for the player in the given scope, returning the sum
+/// of the utilities of the given deals (weighted by their probabilities).
+///
+/// Every deal must contain the same hidden information for the player.
+fn respond<P: Phase>(
+    scope: &Scope,
+    phase: P,
+    state: KnownStateSummary,
+    player: Player,
+    opponent: &mut FrozenStrategy,
+    cursor: &Cursor,
+    deals: &[Deal],
+) -> EchoResult<Utility> {
+    let total_probability: Probability = deals.iter().map(|(_, probability)| probability).sum();
+
+    let value = match scope {
+        Scope::Completed(score) => total_probability * utility_for(player, *score),
+        // Left out because of the memory budget (or the turn limit). Unless it uses
+        // a leaf evaluator, training pretends the game ends right away.
+        Scope::Unexplored(UnexploredScope {
+            state: Some(state), ..
+        }) => total_probability * utility_for(player, state.score),
+        // Never reached during training, so the strategies leading here are never played.
+        Scope::Unexplored(_) => 0.0,
+        Scope::Explored(scope) => {
+            decision_values(scope, phase, state, player, opponent, cursor, deals)?
+                .into_iter()
+                .fold(Utility::NEG_INFINITY, Utility::max)
+        }
+    };
+
+    Ok(value)
+}
+
+/// Computes the utility of every decision the player can take in the given scope,
+/// assuming it keeps best responding afterwards. The utilities are the sums over
+/// the given deals, weighted by their probabilities.
+///
+/// Every deal must contain the same hidden information for the player.
+pub(crate) fn decision_values<P: Phase>(
+    scope: &ExploredScope,
+    phase: P,
+    state: KnownStateSummary,
+    player: Player,
+    opponent: &mut FrozenStrategy,
+    cursor: &Cursor,
+    deals: &[Deal],
+) -> EchoResult<Vec<Utility>> {
+    let decision_count = player.select(scope.matrices.decision_counts());
+    let strategies = opponent_strategies(scope, state, player, opponent, cursor, deals)?;
+    let mut values = Vec::with_capacity(decision_count);
+
+    for decision in 0..decision_count {
+        let children = split_deals(
+            phase,
+            state,
+            player,
+            DecisionIndex(decision),
+            &strategies,
+            deals,
+        );
+
+        let mut value = 0.0;
+
+        for (reveal_index, new_state, deals) in children {
+            let next_phase = phase.advance_phase(&state, reveal_index).unwrap();
+            let cursor = opponent.descend(cursor, reveal_index)?;
+
+            value += respond::<P::Next>(
+                &scope.next[reveal_index.0],
+                next_phase,
+                new_state,
+                player,
+                opponent,
+                &cursor,
+                &deals,
+            )?;
+        }
+
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+/// Computes the deals the player ends up with after taking some decision, grouped
+/// by the information revealed afterwards. The probability of every deal gets
+/// multiplied by the probability of the opponent playing along.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn decision_children<P: Phase>(
+    scope: &ExploredScope,
+    phase: P,
+    state: KnownStateSummary,
+    player: Player,
+    decision: DecisionIndex,
+    opponent: &FrozenStrategy,
+    cursor: &Cursor,
+    deals: &[Deal],
+) -> EchoResult<Children> {
+    let strategies = opponent_strategies(scope, state, player, opponent, cursor, deals)?;
+    Ok(split_deals(
+        phase,
+        state,
+        player,
+        decision,
+        &strategies,
+        deals,
+    ))
+}
+
+/// Looks up the strategy the opponent follows for each deal.
+fn opponent_strategies(
+    scope: &ExploredScope,
+    state: KnownStateSummary,
+    player: Player,
+    opponent: &FrozenStrategy,
+    cursor: &Cursor,
+    deals: &[Deal],
+) -> EchoResult<Vec<Vec<Probability>>> {
+    deals
+        .iter()
+        .map(|(hidden, _)| {
+            opponent.strategy(cursor, scope, &state, !player, (!player).select(*hidden))
+        })
+        .collect()
+}
+
+fn split_deals<P: Phase>(
+    phase: P,
+    state: KnownStateSummary,
+    player: Player,
+    decision: DecisionIndex,
+    strategies: &[Vec<Probability>],
+    deals: &[Deal],
+) -> Children {
+    // Deals get split between the scopes they lead to.
+    let mut children: Children = vec![];
+
+    for ((hidden, probability), strategy) in deals.iter().zip(strategies) {
+        for (opponent_decision, opponent_probability) in strategy.iter().enumerate() {
+            if *opponent_probability == 0.0 {
+                continue;
+            }
+
+            let decisions = player.order_as([decision, DecisionIndex(opponent_decision)]);
+
+            let (new_state, new_hidden, reveal_index) = phase
+                .advance_hidden_indices(state, hidden.map(HiddenState::from), decisions)
+                .unwrap();
+
+            let deal = (new_hidden, probability * opponent_probability);
+
+            match children.iter_mut().find(|child| child.0 == reveal_index) {
+                Some((_, _, deals)) => deals.push(deal),
+                None => children.push((reveal_index, new_state, vec![deal])),
+            }
+        }
+    }
+
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::best_response_agent::BestResponseAgent;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::ai::strategy_agent::StrategyAgent;
+    use crate::ai::strategy_hints::BlueprintStrategyProvider;
+    use crate::cfr::blueprint::{write_blueprint, BlueprintReader};
+    use crate::cfr::evaluate::expected_values;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::phase::PerPhase;
+    use crate::cfr::train::TrainingContext;
+    use crate::game::battlefield::Battlefields;
+    use crate::game::edict::Edict;
+    use crate::game::known_state::KnownState;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::helpers::bitfield::Bitfield;
+    use bumpalo::Bump;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::io::Cursor as IoCursor;
+
+    #[test]
+    fn nash_gap_decreases_during_training() {
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        let mut trainer = TrainingContext::new(false);
+
+        trainer.cfr(&mut scope, state.to_summary(), 1);
+        let initial = nash_gap(&scope, state.to_summary());
+
+        trainer.cfr(&mut scope, state.to_summary(), 100);
+        let trained = nash_gap(&scope, state.to_summary());
+
+        assert!(
+            trained >= -1e-4,
+            "The nash gap {trained} cannot be negative"
+        );
+        assert!(
+            trained < initial / 4.0,
+            "The nash gap went from {initial} to {trained}"
+        );
+    }
+
+    /// The last turn of a game, which (unlike the fixture) can actually be played out.
+    fn playable_state() -> KnownState {
+        // Games only get validated when actually played out.
+        let mut state = last_turn_state();
+        for player_state in &mut state.player_states {
+            for edict in &Edict::EDICTS[..Battlefields::COUNT - 1] {
+                player_state.edicts.remove(*edict);
+            }
+        }
+
+        state
+    }
+
+    #[test]
+    fn best_responses_exploit_fixed_strategies() {
+        let state = playable_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, summary, 20);
+
+        let trained = best_response_value(&scope, summary, Player::Me);
+        let against_trained =
+            best_response_value_against(&scope, summary, Player::Me, FrozenStrategy::Trained)
+                .unwrap();
+        assert!((trained - against_trained).abs() < 1e-4);
+
+        // No strategy does better against uniform play than the best response.
+        let response =
+            best_response_value_against(&scope, summary, Player::Me, FrozenStrategy::Uniform)
+                .unwrap();
+        let [trained_against_uniform, _] = expected_values(
+            &scope,
+            summary,
+            [FrozenStrategy::Trained, FrozenStrategy::Uniform],
+        )
+        .unwrap();
+        assert!(response >= trained_against_uniform - 1e-4);
+
+        // The agent can actually play out the response.
+        let mut agent = BestResponseAgent::new(&scope, FrozenStrategy::Uniform).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        for hidden in MainPhase::new().valid_hidden_states(summary).take(10) {
+            let agents = (&mut agent, RandomAgent::new(&mut rng));
+            let runner = EchoRunner::new(state, PerPhase::Main(MainPhase::new()), agents, hidden);
+            runner.run_game().unwrap();
+        }
+    }
+
+    #[test]
+    fn best_response_agents_play_against_blueprints() {
+        let state = playable_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, summary, 20);
+
+        let mut file = vec![];
+        write_blueprint(&scope, &state, 0, &mut file).unwrap();
+        let mut reader = BlueprintReader::new(IoCursor::new(file.clone())).unwrap();
+        let provider =
+            BlueprintStrategyProvider::new(BlueprintReader::new(IoCursor::new(file)).unwrap())
+                .unwrap();
+
+        // Plays the way simulations do, with the response
+        // generating its own tree from the blueprint.
+        let mut agent = BestResponseAgent::from_blueprint(&mut reader, 1, &allocator).unwrap();
+        let mut opponent = StrategyAgent::new(provider, StdRng::seed_from_u64(0));
+        let mut rng = StdRng::seed_from_u64(1);
+
+        for hidden in MainPhase::new().valid_hidden_states(summary).take(10) {
+            let agents = (&mut agent, &mut opponent);
+            let transcript =
+                EchoRunner::new(state, PerPhase::Main(MainPhase::new()), agents, hidden)
+                    .with_seed(rng.gen())
+                    .run_game_with_transcript()
+                    .unwrap();
+
+            // The agent responds to the blueprint instead
+            // of falling back to the first decision.
+            assert!(transcript.agent_stats()[0].nodes > 0);
+        }
+    }
+}
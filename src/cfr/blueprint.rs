@@ -0,0 +1,875 @@
+//! Persisting trained blueprints as zstd-compressed containers.
+//!
+//! Every explored scope (i.e. every public state) gets compressed into its
+//! own block, such that strategies can be loaded lazily at play time by only
+//! decompressing the blocks the game actually reaches.
+//!
+//! Layout (all integers are little endian):
+//! ```text
+//! magic: b"ECHOBP02"
+//! root state: length: u32, followed by the known state at the root (see `encode_state`)
+//! blocks: compressed blocks, one after the other
+//! index: (offset: u64, length: u32) for each block
+//! footer: (index offset: u64, block count: u32)
+//! ```
+//!
+//! The root scope is always stored in the first block. Once decompressed,
+//! a block contains:
+//! ```text
+//! child count: u32, followed by the block id of each child (u32::MAX if missing)
+//! symmetrical: u8
+//! one matrix if symmetrical, two otherwise, each made out of
+//!     hidden count: u32, decision count: u32, and the average strategies (f32)
+//! ```
+use super::decision::{DecisionMatrices, DecisionMatrix, Probability, Scope};
+use super::hidden_index::HiddenIndex;
+use super::phase::{MainPhase, PerPhase, Phase, SomePhase};
+use super::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::creature::CreatureSet;
+use crate::game::edict::EdictSet;
+use crate::game::known_state::KnownState;
+use crate::game::rules::{Ruleset, ScoringMode};
+use crate::game::status_effect::StatusEffectSet;
+use crate::game::types::{Player, Score, TurnResult};
+use crate::helpers::pair::Pair;
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+const MAGIC: &[u8; 8] = b"ECHOBP02";
+const FOOTER_SIZE: usize = 12;
+const MISSING: u32 = u32::MAX;
+
+/// Identifies a block inside a blueprint file.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct BlockId(pub u32);
+
+impl BlockId {
+    pub const ROOT: Self = Self(0);
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// {{{ Root state
+/// Encodes the state at the root of a blueprint, such that readers can
+/// make sure they are looking at the game the blueprint was trained for.
+fn encode_state(state: &KnownState) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    bytes.extend(state.battlefields.all.map(|battlefield| battlefield as u8));
+    bytes.push(state.battlefields.current as u8);
+    bytes.extend(state.graveyard.0.to_le_bytes());
+    bytes.push(state.score.0 as u8);
+
+    for player_state in &state.player_states {
+        bytes.push(player_state.edicts.0);
+        bytes.push(player_state.effects.0);
+        bytes.extend(player_state.lingering);
+    }
+
+    let rules = state.rules;
+    bytes.extend([
+        rules.turns,
+        rules.last_strand_reward,
+        rules.battlefield_reward,
+        rules.monarch_bonus,
+        rules.gambit_loses_ties as u8,
+        match rules.scoring {
+            ScoringMode::Points => 0,
+            ScoringMode::BattlesWon => 1,
+        },
+    ]);
+
+    bytes
+}
+
+/// Inverse of `encode_state`.
+fn decode_state(mut cursor: &[u8]) -> io::Result<KnownState> {
+    let mut byte = || -> io::Result<u8> {
+        let mut bytes = [0];
+        cursor.read_exact(&mut bytes)?;
+        Ok(bytes[0])
+    };
+
+    let mut battlefields = [Battlefield::Mountain; Battlefields::COUNT];
+    for battlefield in &mut battlefields {
+        *battlefield = *Battlefield::BATTLEFIELDS
+            .get(byte()? as usize)
+            .ok_or_else(|| invalid_data("Invalid battlefield in the root state"))?;
+    }
+
+    let mut state = KnownState::new_starting(battlefields);
+    state.battlefields.current = byte()? as usize;
+    state.graveyard = CreatureSet(u16::from_le_bytes([byte()?, byte()?]));
+    state.score = Score(byte()? as i8);
+
+    for player_state in &mut state.player_states {
+        player_state.edicts = EdictSet(byte()?);
+        player_state.effects = StatusEffectSet(byte()?);
+
+        for lingering in &mut player_state.lingering {
+            *lingering = byte()?;
+        }
+    }
+
+    state.rules = Ruleset {
+        turns: byte()?,
+        last_strand_reward: byte()?,
+        battlefield_reward: byte()?,
+        monarch_bonus: byte()?,
+        gambit_loses_ties: byte()? != 0,
+        scoring: match byte()? {
+            0 => ScoringMode::Points,
+            1 => ScoringMode::BattlesWon,
+            _ => return Err(invalid_data("Invalid scoring mode in the root state")),
+        },
+    };
+
+    if !cursor.is_empty() {
+        return Err(invalid_data("Trailing data at the end of the root state"));
+    }
+
+    Ok(state)
+}
+// }}}
+// {{{ Writing
+/// Compresses blocks one after the other, keeping track of the index.
+struct BlueprintWriter<W> {
+    writer: W,
+    level: i32,
+    offset: u64,
+    index: Vec<(u64, u32)>,
+}
+
+impl<W: Write> BlueprintWriter<W> {
+    fn new(mut writer: W, state: &KnownState, level: i32) -> io::Result<Self> {
+        let state = encode_state(state);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(state.len() as u32).to_le_bytes())?;
+        writer.write_all(&state)?;
+
+        Ok(Self {
+            writer,
+            level,
+            offset: (MAGIC.len() + 4 + state.len()) as u64,
+            index: vec![],
+        })
+    }
+
+    fn write_block(&mut self, block: &[u8]) -> io::Result<()> {
+        let compressed = zstd::bulk::compress(block, self.level)?;
+        self.writer.write_all(&compressed)?;
+        self.index.push((self.offset, compressed.len() as u32));
+        self.offset += compressed.len() as u64;
+
+        Ok(())
+    }
+
+    /// Writes the index and the footer, returning the number of blocks written.
+    fn finish(mut self) -> io::Result<usize> {
+        for (block_offset, length) in &self.index {
+            self.writer.write_all(&block_offset.to_le_bytes())?;
+            self.writer.write_all(&length.to_le_bytes())?;
+        }
+
+        self.writer.write_all(&self.offset.to_le_bytes())?;
+        self.writer
+            .write_all(&(self.index.len() as u32).to_le_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.index.len())
+    }
+}
+
+/// Writes a trained scope (together with everything reachable from it) to disk.
+/// The scope must have been generated starting at the main phase of `state`.
+///
+/// The compression level is passed straight to zstd (`0` selects the default).
+pub fn write_blueprint(
+    scope: &Scope,
+    state: &KnownState,
+    level: i32,
+    writer: impl Write,
+) -> io::Result<()> {
+    let mut output = BlueprintWriter::new(writer, state, level)?;
+
+    // Blocks are written in breadth first order. Ids are handed out when the
+    // scopes get pushed onto the queue, which matches the order they get
+    // popped (and thus written) in.
+    let mut queue = VecDeque::new();
+    let mut next_id = 1;
+
+    if scope.get_explored().is_some() {
+        queue.push_back(scope);
+    }
+
+    while let Some(scope) = queue.pop_front() {
+        let explored = scope.get_explored().unwrap();
+        let mut block = vec![];
+
+        block.extend((explored.next.len() as u32).to_le_bytes());
+        for child in explored.next.iter() {
+            let id = if child.get_explored().is_some() {
+                queue.push_back(child);
+                next_id += 1;
+                next_id - 1
+            } else {
+                MISSING
+            };
+
+            block.extend(id.to_le_bytes());
+        }
+
+        match &explored.matrices {
+            DecisionMatrices::Symmetrical(matrix) => {
+                block.push(1);
+                write_matrix(matrix, &mut block);
+            }
+            DecisionMatrices::Asymmetrical(matrices) => {
+                block.push(0);
+                for matrix in matrices {
+                    write_matrix(matrix, &mut block);
+                }
+            }
+        }
+
+        output.write_block(&block)?;
+    }
+
+    output.finish().map(|_| ())
+}
+
+fn write_matrix(matrix: &DecisionMatrix, block: &mut Vec<u8>) {
+    match matrix {
+        DecisionMatrix::Trivial => {
+            block.extend(0u32.to_le_bytes());
+            block.extend(1u32.to_le_bytes());
+        }
+        DecisionMatrix::Expanded(vectors) => {
+            block.extend((vectors.len() as u32).to_le_bytes());
+            block.extend((matrix.len() as u32).to_le_bytes());
+
+            for vector in vectors.iter() {
+                for probability in vector.get_average_strategy() {
+                    block.extend(probability.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+// }}}
+// {{{ Loaded blocks
+/// The average strategies of a single player in some public state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyMatrix {
+    decision_count: usize,
+    /// Empty if the matrix is trivial.
+    probabilities: Vec<Probability>,
+}
+
+impl StrategyMatrix {
+    #[inline(always)]
+    pub fn decision_count(&self) -> usize {
+        self.decision_count
+    }
+
+    /// The number of hidden indices the matrix holds strategies for
+    /// (zero if the matrix is trivial).
+    #[inline(always)]
+    pub fn hidden_count(&self) -> usize {
+        self.probabilities.len() / self.decision_count.max(1)
+    }
+
+    /// Returns the strategy to use for some hidden state, or `None`
+    /// if the player only has a single decision to take.
+    pub fn get(&self, index: HiddenIndex) -> Option<&[Probability]> {
+        if self.probabilities.is_empty() {
+            return None;
+        }
+
+        let start = index.0 * self.decision_count;
+        self.probabilities.get(start..start + self.decision_count)
+    }
+
+    /// Turns every strategy into a pure one, always taking
+    /// the decision the strategy deemed most likely.
+    pub fn purify(&mut self) {
+        if self.decision_count == 0 {
+            return;
+        }
+
+        for strategy in self.probabilities.chunks_mut(self.decision_count) {
+            let best = strategy
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map_or(0, |(index, _)| index);
+
+            for (index, probability) in strategy.iter_mut().enumerate() {
+                *probability = if index == best { 1.0 } else { 0.0 };
+            }
+        }
+    }
+
+    fn encode(&self, block: &mut Vec<u8>) {
+        block.extend((self.hidden_count() as u32).to_le_bytes());
+        block.extend((self.decision_count as u32).to_le_bytes());
+
+        for probability in &self.probabilities {
+            block.extend(probability.to_le_bytes());
+        }
+    }
+
+    /// Computes the weighted average of the strategies in a few matrices.
+    ///
+    /// Trivial matrices do not contribute anything, as they hold no strategies.
+    fn average(matrices: &[(&StrategyMatrix, f32)]) -> io::Result<Self> {
+        let decision_count = matrices[0].0.decision_count;
+        let expanded: Vec<_> = matrices
+            .iter()
+            .filter(|(matrix, _)| !matrix.probabilities.is_empty())
+            .collect();
+        let length = expanded
+            .first()
+            .map_or(0, |(matrix, _)| matrix.probabilities.len());
+
+        if matrices
+            .iter()
+            .any(|(matrix, _)| matrix.decision_count != decision_count)
+            || expanded
+                .iter()
+                .any(|(matrix, _)| matrix.probabilities.len() != length)
+        {
+            return Err(invalid_data(
+                "The blueprints describe different strategy matrices",
+            ));
+        }
+
+        let total_weight: f32 = expanded.iter().map(|(_, weight)| weight).sum();
+        let mut probabilities = vec![0.0; length];
+
+        for (matrix, weight) in expanded {
+            for (average, probability) in probabilities.iter_mut().zip(&matrix.probabilities) {
+                *average += probability * weight / total_weight;
+            }
+        }
+
+        Ok(Self {
+            decision_count,
+            probabilities,
+        })
+    }
+}
+
+/// A decompressed block, holding the strategies for a single public state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicStrategy {
+    children: Vec<Option<BlockId>>,
+    matrices: Pair<StrategyMatrix>,
+}
+
+impl PublicStrategy {
+    /// Returns the block holding the public state we end up in after
+    /// some information gets revealed, if the blueprint reaches that far.
+    pub fn next(&self, reveal_index: RevealIndex) -> Option<BlockId> {
+        self.children.get(reveal_index.0).copied().flatten()
+    }
+
+    #[inline(always)]
+    pub fn get_matrix(&self, player: Player) -> &StrategyMatrix {
+        player.select_ref(&self.matrices)
+    }
+
+    /// Purifies the strategies of both players (see `StrategyMatrix::purify`).
+    pub fn purify(&mut self) {
+        for matrix in &mut self.matrices {
+            matrix.purify();
+        }
+    }
+
+    fn decode(block: &[u8]) -> io::Result<Self> {
+        let mut cursor = block;
+
+        let child_count = read_u32(&mut cursor)? as usize;
+        let children = (0..child_count)
+            .map(|_| read_u32(&mut cursor).map(|id| (id != MISSING).then_some(BlockId(id))))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let mut symmetrical = [0];
+        cursor.read_exact(&mut symmetrical)?;
+
+        let first = read_matrix(&mut cursor)?;
+        let matrices = if symmetrical[0] == 1 {
+            [first.clone(), first]
+        } else {
+            [first, read_matrix(&mut cursor)?]
+        };
+
+        if !cursor.is_empty() {
+            return Err(invalid_data(
+                "Trailing data at the end of a blueprint block",
+            ));
+        }
+
+        Ok(Self { children, matrices })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut block = vec![];
+
+        block.extend((self.children.len() as u32).to_le_bytes());
+        for child in &self.children {
+            block.extend(child.map_or(MISSING, |id| id.0).to_le_bytes());
+        }
+
+        let [first, second] = &self.matrices;
+        if first == second {
+            block.push(1);
+            first.encode(&mut block);
+        } else {
+            block.push(0);
+            first.encode(&mut block);
+            second.encode(&mut block);
+        }
+
+        block
+    }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_matrix(cursor: &mut &[u8]) -> io::Result<StrategyMatrix> {
+    let hidden_count = read_u32(cursor)? as usize;
+    let decision_count = read_u32(cursor)? as usize;
+
+    let probabilities = (0..hidden_count * decision_count)
+        .map(|_| read_u32(cursor).map(f32::from_bits))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(StrategyMatrix {
+        decision_count,
+        probabilities,
+    })
+}
+// }}}
+// {{{ Reading
+/// Provides random access to the blocks of a blueprint file.
+///
+/// Only the index gets read upfront. Blocks are decompressed on demand.
+pub struct BlueprintReader<R> {
+    reader: R,
+    index: Vec<(u64, u32)>,
+
+    /// The state the blueprint was trained for.
+    state: KnownState,
+}
+
+impl<R: Read + Seek> BlueprintReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(invalid_data("Not a blueprint file"));
+        }
+
+        let mut state = vec![0; read_u32(&mut reader)? as usize];
+        reader.read_exact(&mut state)?;
+        let state = decode_state(&state)?;
+
+        reader.seek(SeekFrom::End(-(FOOTER_SIZE as i64)))?;
+        let index_offset = read_u64(&mut reader)?;
+        let block_count = read_u32(&mut reader)? as usize;
+
+        reader.seek(SeekFrom::Start(index_offset))?;
+        let index = (0..block_count)
+            .map(|_| Ok((read_u64(&mut reader)?, read_u32(&mut reader)?)))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            reader,
+            index,
+            state,
+        })
+    }
+
+    /// The state at the root of the blueprint (during the main phase).
+    #[inline(always)]
+    pub fn root_state(&self) -> KnownState {
+        self.state
+    }
+
+    /// Computes the public state the given reveals lead to from the root,
+    /// such that positions can be checked against the blueprint before
+    /// looking up their strategies.
+    pub fn public_state(&self, reveals: &[RevealIndex]) -> EchoResult<(KnownState, SomePhase)> {
+        let mut state = self.state;
+        let mut phase = PerPhase::Main(MainPhase::new());
+
+        for reveal_index in reveals {
+            if reveal_index.0 >= phase.reveal_count(&state) {
+                return Err(EchoError::Decode("reveal index"));
+            }
+
+            let next_phase = phase.advance_phase(&state, *reveal_index)?;
+            let next_state = match phase {
+                PerPhase::Main(inner) => inner.advance_state(&state, *reveal_index, true),
+                PerPhase::Sabotage(inner) => inner.advance_state(&state, *reveal_index, true),
+                PerPhase::Seer(inner) => inner.advance_state(&state, *reveal_index, true),
+            };
+
+            state = match next_state {
+                TurnResult::Unfinished(state) => state,
+                TurnResult::Finished(_) => {
+                    return Err(EchoError::InvalidPosition(
+                        "The reveals lead past the end of the game".to_string(),
+                    ))
+                }
+            };
+            phase = next_phase;
+        }
+
+        Ok((state, phase))
+    }
+
+    /// Makes sure the given reveals lead to the given position. The notation
+    /// used to describe positions knows nothing about the rules or the duration
+    /// of status effects, so those get ignored (and taken from the blueprint).
+    ///
+    /// Returns the full state the blueprint has for the position.
+    pub fn check_position(
+        &self,
+        state: &KnownState,
+        phase: &SomePhase,
+        reveals: &[RevealIndex],
+    ) -> EchoResult<KnownState> {
+        let (expected, expected_phase) = self.public_state(reveals)?;
+
+        let mut comparable = *state;
+        comparable.rules = expected.rules;
+        let player_states = comparable.player_states.iter_mut();
+        for (player_state, original) in player_states.zip(expected.player_states) {
+            player_state.lingering = original.lingering;
+        }
+
+        if comparable != expected || *phase != expected_phase {
+            return Err(EchoError::InvalidPosition(
+                "The reveals lead to a different position in the blueprint".to_string(),
+            ));
+        }
+
+        Ok(expected)
+    }
+
+    /// The number of public states stored in the file.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Seeks to some block and decompresses it.
+    pub fn load(&mut self, id: BlockId) -> io::Result<PublicStrategy> {
+        let (offset, length) = *self
+            .index
+            .get(id.0 as usize)
+            .ok_or_else(|| invalid_data(format!("Block {} does not exist", id.0)))?;
+
+        let mut compressed = vec![0; length as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut compressed)?;
+
+        PublicStrategy::decode(&zstd::decode_all(compressed.as_slice())?)
+    }
+}
+// }}}
+// {{{ Merging
+/// Combines blueprints trained on the same game (usually with different
+/// seeds) into a single one, which averages out some of the sampling noise.
+///
+/// Public states are matched by following the same reveal indices from the
+/// root, and strategies by their hidden index. Every strategy is the weighted
+/// average of the strategies found in the blueprints reaching its public state.
+///
+/// Returns the number of public states written.
+pub fn merge_blueprints<R: Read + Seek>(
+    inputs: &mut [(BlueprintReader<R>, f32)],
+    level: i32,
+    writer: impl Write,
+) -> io::Result<usize> {
+    if inputs
+        .iter()
+        .any(|(_, weight)| !weight.is_finite() || *weight <= 0.0)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Blueprint weights must be positive",
+        ));
+    }
+
+    let Some(state) = inputs.first().map(|(reader, _)| reader.root_state()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "At least one blueprint is required",
+        ));
+    };
+
+    if inputs.iter().any(|(reader, _)| reader.root_state() != state) {
+        return Err(invalid_data(
+            "The blueprints were trained for different states",
+        ));
+    }
+
+    let mut output = BlueprintWriter::new(writer, &state, level)?;
+
+    // Just like in `write_blueprint`, ids are handed out in breadth first
+    // order. Every entry holds the block each input uses for the public state.
+    let mut queue = VecDeque::new();
+    let mut next_id = 1;
+
+    let roots: Vec<_> = inputs
+        .iter()
+        .map(|(reader, _)| (!reader.is_empty()).then_some(BlockId::ROOT))
+        .collect();
+
+    if roots.iter().any(Option::is_some) {
+        queue.push_back(roots);
+    }
+
+    while let Some(ids) = queue.pop_front() {
+        let mut strategies = Vec::with_capacity(inputs.len());
+        for ((reader, weight), id) in inputs.iter_mut().zip(ids) {
+            strategies.push(match id {
+                Some(id) => Some((reader.load(id)?, *weight)),
+                None => None,
+            });
+        }
+
+        let loaded: Vec<_> = strategies.iter().flatten().collect();
+        let child_count = loaded[0].0.children.len();
+        if loaded
+            .iter()
+            .any(|(strategy, _)| strategy.children.len() != child_count)
+        {
+            return Err(invalid_data(
+                "The blueprints describe different public states",
+            ));
+        }
+
+        let mut children = Vec::with_capacity(child_count);
+        for reveal_index in 0..child_count {
+            // Inputs which do not reach this far simply stop contributing.
+            let child_ids: Vec<_> = strategies
+                .iter()
+                .map(|strategy| {
+                    strategy
+                        .as_ref()
+                        .and_then(|(strategy, _)| strategy.next(RevealIndex(reveal_index)))
+                })
+                .collect();
+
+            if child_ids.iter().any(Option::is_some) {
+                queue.push_back(child_ids);
+                children.push(Some(BlockId(next_id)));
+                next_id += 1;
+            } else {
+                children.push(None);
+            }
+        }
+
+        let average = |player: Player| {
+            let matrices: Vec<_> = loaded
+                .iter()
+                .map(|(strategy, weight)| (strategy.get_matrix(player), *weight))
+                .collect();
+
+            StrategyMatrix::average(&matrices)
+        };
+
+        let merged = PublicStrategy {
+            children,
+            matrices: [average(Player::Me)?, average(Player::You)?],
+        };
+
+        output.write_block(&merged.encode())?;
+    }
+
+    output.finish()
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::train::TrainingContext;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use bumpalo::Bump;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::io::Cursor;
+
+    #[test]
+    fn blueprints_roundtrip() {
+        let state = last_turn_state();
+
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, state.to_summary(), 20);
+
+        let mut file = vec![];
+        write_blueprint(&scope, &state, 0, &mut file).unwrap();
+
+        let mut reader = BlueprintReader::new(Cursor::new(file)).unwrap();
+        let root = reader.load(BlockId::ROOT).unwrap();
+        let explored = scope.get_explored().unwrap();
+
+        for player in Player::PLAYERS {
+            let matrix = explored.matrices.get_matrix(player);
+            let loaded = root.get_matrix(player);
+
+            assert_eq!(loaded.decision_count(), matrix.len());
+
+            if let DecisionMatrix::Expanded(vectors) = matrix {
+                for (index, vector) in vectors.iter().enumerate() {
+                    assert_eq!(
+                        loaded.get(HiddenIndex(index)).unwrap(),
+                        vector.get_average_strategy().as_slice()
+                    );
+                }
+            }
+        }
+
+        // Every block should be reachable from the root
+        let mut visited = 0;
+        let mut stack = vec![root];
+
+        while let Some(block) = stack.pop() {
+            visited += 1;
+
+            for index in 0..block.children.len() {
+                if let Some(id) = block.next(RevealIndex(index)) {
+                    stack.push(reader.load(id).unwrap());
+                }
+            }
+        }
+
+        assert_eq!(visited, reader.len());
+    }
+
+    #[test]
+    fn blueprints_know_their_public_states() {
+        let state = last_turn_state();
+
+        let allocator = Bump::new();
+        let scope = GenerationContext::new(1, state, &allocator).generate();
+
+        let mut file = vec![];
+        write_blueprint(&scope, &state, 0, &mut file).unwrap();
+        let reader = BlueprintReader::new(Cursor::new(file)).unwrap();
+        assert_eq!(reader.root_state(), state);
+
+        let main = PerPhase::Main(MainPhase::new());
+        assert_eq!(reader.public_state(&[]).unwrap(), (state, main));
+        assert_eq!(reader.check_position(&state, &main, &[]), Ok(state));
+
+        let reveal_index = RevealIndex(0);
+        let next = main.advance_phase(&state, reveal_index).unwrap();
+        assert_eq!(reader.public_state(&[reveal_index]).unwrap(), (state, next));
+        assert!(reader.check_position(&state, &main, &[reveal_index]).is_err());
+
+        let mut other = state;
+        other.score.0 += 1;
+        assert!(reader.check_position(&other, &main, &[]).is_err());
+
+        let too_far = RevealIndex(main.reveal_count(&state));
+        assert!(reader.public_state(&[too_far]).is_err());
+    }
+
+    #[test]
+    fn blueprints_can_be_merged() {
+        let state = last_turn_state();
+
+        let allocator = Bump::new();
+        let files: Vec<_> = [3, 4]
+            .into_iter()
+            .map(|seed| {
+                let mut scope = GenerationContext::new(1, state, &allocator).generate();
+                let mut rng = StdRng::seed_from_u64(seed);
+                TrainingContext::new(false).cs_cfr(&mut rng, &mut scope, state.to_summary(), 50);
+
+                let mut file = vec![];
+                write_blueprint(&scope, &state, 0, &mut file).unwrap();
+                file
+            })
+            .collect();
+
+        let open = |file: &Vec<u8>| BlueprintReader::new(Cursor::new(file.clone())).unwrap();
+        let mut inputs = vec![(open(&files[0]), 1.0), (open(&files[1]), 3.0)];
+        let mut merged = vec![];
+        let written = merge_blueprints(&mut inputs, 0, &mut merged).unwrap();
+
+        let mut merged = BlueprintReader::new(Cursor::new(merged)).unwrap();
+        assert_eq!(written, merged.len());
+        assert_eq!(merged.len(), inputs[0].0.len());
+
+        let roots = [&files[0], &files[1]].map(|file| open(file).load(BlockId::ROOT).unwrap());
+        let root = merged.load(BlockId::ROOT).unwrap();
+
+        for player in Player::PLAYERS {
+            let matrix = root.get_matrix(player);
+
+            for index in 0..matrix.probabilities.len() / matrix.decision_count() {
+                let [first, second] = roots
+                    .each_ref()
+                    .map(|root| root.get_matrix(player).get(HiddenIndex(index)));
+
+                for (decision, probability) in
+                    matrix.get(HiddenIndex(index)).unwrap().iter().enumerate()
+                {
+                    let expected =
+                        (first.unwrap()[decision] + 3.0 * second.unwrap()[decision]) / 4.0;
+                    assert!((probability - expected).abs() < 1e-5);
+                }
+            }
+        }
+
+        // Merging a single blueprint leaves it unchanged
+        let mut single = vec![];
+        merge_blueprints(&mut [(open(&files[0]), 2.0)], 0, &mut single).unwrap();
+        let mut single = BlueprintReader::new(Cursor::new(single)).unwrap();
+        let mut original = open(&files[0]);
+
+        for id in 0..original.len() {
+            let id = BlockId(id as u32);
+            assert_eq!(single.load(id).unwrap(), original.load(id).unwrap());
+        }
+
+        assert!(merge_blueprints(&mut [(open(&files[0]), 0.0)], 0, vec![]).is_err());
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!(BlueprintReader::new(Cursor::new(b"definitely not a blueprint".to_vec())).is_err());
+    }
+}
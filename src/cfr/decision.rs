@@ -2,13 +2,26 @@ use crate::game::known_state::KnownState;
 use crate::game::known_state_summary::KnownStateSummary;
 use crate::game::simulate::BattleContext;
 use crate::game::types::{Player, Score};
-use crate::helpers::pair::{are_equal, Pair};
-use crate::helpers::{normalize_vec, roulette};
+use crate::helpers::pair::{are_equal, Pair, PairExt};
+use crate::helpers::sampling::{sample, AliasTable};
+use crate::helpers::{lane_sum, normalize_vec};
 use bumpalo::Bump;
 use rand::Rng;
+#[cfg(feature = "half-weights")]
+use std::cell::RefCell;
+use std::cell::{Cell, OnceCell};
+#[cfg(feature = "half-weights")]
+use std::collections::HashMap;
+use std::fmt::Write;
+#[cfg(not(feature = "half-weights"))]
+use std::marker::PhantomData;
 use std::mem::size_of;
 
+use super::abstraction::Buckets;
+use super::generate::GenerationContext;
 use super::hidden_index::HiddenIndex;
+use super::reveal_index::RevealIndex;
+use super::storage::WeightStorage;
 
 // {{{ Helper types
 /// Utility is the quantity players attempt to maximize.
@@ -16,6 +29,94 @@ pub type Utility = f32;
 
 /// Float between 0 and 1.
 pub type Probability = f32;
+
+/// The type the weights inside decision vectors are stored as.
+///
+/// The `half-weights` feature stores weights as half precision floats, which
+/// roughly halves the memory taken up by trees. Arithmetic always happens on
+/// `f32`s, with results only getting rounded once they are written back.
+/// Training accumulates the updates of every iteration in full precision
+/// before rounding them (see `WeightUpdates`), and the sums of every vector
+/// get scaled by a power of two such that they never overflow.
+#[cfg(not(feature = "half-weights"))]
+pub type Weight = f32;
+
+#[cfg(feature = "half-weights")]
+pub type Weight = half::f16;
+
+#[inline(always)]
+pub fn load_weight(weight: Weight) -> f32 {
+    #[cfg(feature = "half-weights")]
+    return weight.to_f32();
+
+    #[cfg(not(feature = "half-weights"))]
+    weight
+}
+
+#[inline(always)]
+pub fn store_weight(value: f32) -> Weight {
+    #[cfg(feature = "half-weights")]
+    return half::f16::from_f32(value);
+
+    #[cfg(not(feature = "half-weights"))]
+    value
+}
+
+/// Half precision floats cannot hold anything larger than this.
+#[cfg(feature = "half-weights")]
+const MAX_WEIGHT: f32 = 65504.0;
+
+/// Rounds up or down with probability proportional to the distance to each
+/// of the two nearest half precision floats, which keeps the expected value
+/// of the result equal to `value`. Rounding to the nearest float instead would
+/// throw away every update smaller than half the gap between such floats.
+///
+/// `noise` should be uniformly distributed between 0 and 1.
+#[cfg(feature = "half-weights")]
+fn store_weight_stochastically(value: f32, noise: f32) -> Weight {
+    let nearest = half::f16::from_f32(value);
+    let rounded = nearest.to_f32();
+
+    if rounded == value || !rounded.is_finite() {
+        return nearest;
+    }
+
+    // The float on the other side of `value`. The sign of the nearest
+    // float always matches the sign of `value` (even for zeros).
+    let other = if value.abs() > rounded.abs() {
+        half::f16::from_bits(nearest.to_bits() + 1)
+    } else {
+        half::f16::from_bits(nearest.to_bits() - 1)
+    };
+
+    let distance = (value - rounded).abs() / (other.to_f32() - rounded).abs();
+    if noise < distance {
+        other
+    } else {
+        nearest
+    }
+}
+
+/// Deterministically hashes some bits to a number between 0 and 1 (using splitmix64).
+#[cfg(feature = "half-weights")]
+fn rounding_noise(bits: u64) -> f32 {
+    let mut z = bits.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^= z >> 31;
+
+    (z >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Keeps track of a decision pruned by regret based pruning (see `train::RegretPruning`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SkippedVisits {
+    /// How many more visits to skip the decision for.
+    pub remaining: usize,
+
+    /// How many visits have been skipped since the decision was last traversed.
+    pub skipped: usize,
+}
 // }}}
 // {{{ Decision vector
 /// A decision a player takes in the game.
@@ -23,36 +124,85 @@ pub type Probability = f32;
 /// For efficiency, all the values are tightly packed into vectors indexed
 /// by so called "decision indices", which are encoded/decoded differently
 /// depending on the phase of the game we are currently in.
+///
+/// Weights are stored inside cells, such that identical scopes reached
+/// through different reveal sequences can share the same vectors.
 #[derive(Debug)]
 pub struct DecisionVector<'a> {
     /// Sum of every strategy devised so far during training.
     /// Unintuitively, the current strategy doesn't approach
     /// optimal play, but the sum of devised strategies does!
-    pub strategy_sum: &'a mut [f32],
+    pub strategy_sum: &'a [Cell<Weight>],
 
     /// Regret accumulated during training (so far).
-    pub regret_sum: &'a mut [f32],
-
-    /// Cached value of the positive elements in the regret_sum vector.
-    regret_positive_magnitude: f32,
+    ///
+    /// When storing half precision weights, both sums hold the actual sums
+    /// divided by some power of two (see `regrets` and `strategy_sums`).
+    pub regret_sum: &'a [Cell<Weight>],
+
+    /// The exponents of the powers of two the regret and strategy sums
+    /// (in that order) are stored divided by. These grow whenever one of
+    /// the sums would not fit inside a half precision float otherwise.
+    #[cfg(feature = "half-weights")]
+    exponents: &'a [Cell<Weight>],
+
+    /// Cached inverse of the sum of the positive elements in the regret_sum
+    /// vector (or `0` if there are no such elements). Storing the inverse
+    /// turns the division performed by `strategy` into a multiplication.
+    regret_scale: Cell<f32>,
+
+    /// Regret based pruning state of every decision. Only gets allocated
+    /// (in the same arena as the rest of the tree) for trees generated
+    /// with regret based pruning in mind (see `init_skipped_visits`).
+    skipped_visits: OnceCell<&'a [Cell<SkippedVisits>]>,
 }
 
 impl<'a> DecisionVector<'a> {
     // {{{ Helpers
-    pub fn new(size: usize, allocator: &'a Bump) -> Self {
-        let regret_sum = allocator.alloc_slice_fill_copy(size, 0.0);
-        let strategy_sum = allocator.alloc_slice_fill_copy(size, 0.0);
+    pub fn new(size: usize, weights: WeightStorage<'a>) -> Self {
+        let regret_sum = weights.alloc_weights(size);
+        let strategy_sum = weights.alloc_weights(size);
 
-        Self {
+        let result = Self {
             regret_sum,
-            regret_positive_magnitude: 0.0,
+            #[cfg(feature = "half-weights")]
+            exponents: weights.alloc_weights(Self::EXPONENTS),
+            regret_scale: Cell::new(0.0),
             strategy_sum,
+            skipped_visits: OnceCell::new(),
+        };
+
+        // Weights loaded from disk might already contain some regret.
+        result.recompute_regret_magnitude();
+        result
+    }
+
+    /// Creates a vector sharing the weights of this one (see `abstraction`).
+    ///
+    /// The cached regret scale is not shared, which is fine since training
+    /// recomputes it before every use of the current strategy. Pruning state
+    /// is not shared either, as every copy gets visited on its own.
+    pub fn share(&self) -> Self {
+        Self {
+            regret_sum: self.regret_sum,
+            #[cfg(feature = "half-weights")]
+            exponents: self.exponents,
+            regret_scale: Cell::new(self.regret_scale.get()),
+            strategy_sum: self.strategy_sum,
+            skipped_visits: OnceCell::new(),
         }
     }
 
+    /// The number of weights storing exponents (see `exponents`).
+    pub const EXPONENTS: usize = if cfg!(feature = "half-weights") { 2 } else { 0 };
+
     /// Estimates how much memory an instance of this type will take.
-    pub fn estimate_alloc(size: usize) -> usize {
-        size_of::<f32>() * size * 2 + size_of::<Self>()
+    pub fn estimate_alloc(size: usize, regret_pruning: bool) -> usize {
+        let skipped_visits = if regret_pruning { size } else { 0 };
+
+        size_of::<Weight>() * (size * 2 + Self::EXPONENTS)
+            + size_of::<Cell<SkippedVisits>>() * skipped_visits
+            + size_of::<Self>()
     }
 
     /// Returns the number of actions we can take at this node.
@@ -69,8 +219,10 @@ impl<'a> DecisionVector<'a> {
     /// * `index` - The index of the strategy to compute
     #[inline(always)]
     pub fn strategy(&self, index: usize) -> Probability {
-        if self.regret_positive_magnitude > 0.0 {
-            f32::max(self.regret_sum[index], 0.0) / self.regret_positive_magnitude
+        let scale = self.regret_scale.get();
+
+        if scale > 0.0 {
+            f32::max(load_weight(self.regret_sum[index].get()), 0.0) * scale
         } else {
             1.0 / (self.len() as Probability)
         }
@@ -93,87 +245,382 @@ impl<'a> DecisionVector<'a> {
     }
 
     /// Update the strategy sum with the current strategy.
+    ///
+    /// Rounds the sum right away when storing half precision weights.
+    /// Training goes through `WeightUpdates` instead.
     #[inline(always)]
-    pub fn update_strategy_sum(&mut self, probability: Probability) {
-        for i in 0..self.len() {
-            self.strategy_sum[i] += (probability * self.strategy(i)) as f32;
+    pub fn update_strategy_sum(&self, probability: Probability) {
+        #[cfg(feature = "half-weights")]
+        {
+            let mut sums = self.strategy_sums();
+            for (index, sum) in sums.iter_mut().enumerate() {
+                *sum += probability * self.strategy(index);
+            }
+
+            self.set_strategy_sums(&sums);
+        }
+
+        // Branching once (instead of calling `strategy` for every element)
+        // leaves a loop the compiler can vectorize.
+        #[cfg(not(feature = "half-weights"))]
+        if self.regret_scale.get() > 0.0 {
+            let scale = probability * self.regret_scale.get();
+
+            for (sum, regret) in self.strategy_sum.iter().zip(self.regret_sum) {
+                let regret = f32::max(regret.get(), 0.0);
+                sum.set(sum.get() + regret * scale);
+            }
+        } else {
+            let amount = probability / (self.len() as Probability);
+
+            for sum in self.strategy_sum {
+                sum.set(sum.get() + amount);
+            }
         }
     }
 
     /// Accumulates some regret for a given decision.
+    /// Like `update_strategy_sum`, this rounds right away.
+    #[inline(always)]
+    pub fn accumulate_regret(&self, index: usize, amount: Utility) {
+        #[cfg(feature = "half-weights")]
+        {
+            let mut regrets = self.regrets();
+            regrets[index] += amount;
+            self.set_regrets(&regrets);
+        }
+
+        #[cfg(not(feature = "half-weights"))]
+        {
+            let regret = &self.regret_sum[index];
+            regret.set(regret.get() + amount);
+        }
+    }
+
+    /// The regret accumulated for some decision.
     #[inline(always)]
-    pub fn accumulate_regret(&mut self, index: usize, amount: Utility) {
-        self.regret_sum[index] += amount as f32;
+    pub fn regret(&self, index: usize) -> Utility {
+        load_weight(self.regret_sum[index].get()) * self.sum_scale(0)
     }
 
-    /// Updates the cached regret magnitude once the regret sum has been changed.
-    pub fn recompute_regret_magnitude(&mut self) {
-        let mut sum = 0.0;
-        for i in 0..self.len() {
-            sum += f32::max(self.regret_sum[i], 0.0);
+    /// The regret accumulated for every decision.
+    pub fn regrets(&self) -> Vec<Utility> {
+        self.load_sums(self.regret_sum, 0)
+    }
+
+    /// The strategy sum of every decision.
+    pub fn strategy_sums(&self) -> Vec<f32> {
+        self.load_sums(self.strategy_sum, 1)
+    }
+
+    /// Overwrites the regret accumulated for every decision.
+    /// The cached regret magnitude needs to be recomputed afterwards.
+    pub fn set_regrets(&self, regrets: &[Utility]) {
+        self.store_sums(self.regret_sum, 0, regrets, None);
+    }
+
+    /// Overwrites the strategy sum of every decision.
+    pub fn set_strategy_sums(&self, sums: &[f32]) {
+        self.store_sums(self.strategy_sum, 1, sums, None);
+    }
+
+    /// The amount the weights of one of the sums (see `exponents`) get scaled by when loaded.
+    #[inline(always)]
+    fn sum_scale(&self, sum: usize) -> f32 {
+        #[cfg(feature = "half-weights")]
+        return 2.0f32.powi(load_weight(self.exponents[sum].get()) as i32);
+
+        #[cfg(not(feature = "half-weights"))]
+        {
+            let _ = sum;
+            1.0
+        }
+    }
+
+    fn load_sums(&self, weights: &[Cell<Weight>], sum: usize) -> Vec<f32> {
+        let scale = self.sum_scale(sum);
+        weights
+            .iter()
+            .map(|weight| load_weight(weight.get()) * scale)
+            .collect()
+    }
+
+    /// Stores some sums, picking the smallest exponent all of them fit with.
+    /// Rounds stochastically (see `store_weight_stochastically`) when given a seed.
+    fn store_sums(&self, weights: &[Cell<Weight>], sum: usize, values: &[f32], seed: Option<u64>) {
+        assert_eq!(weights.len(), values.len());
+
+        #[cfg(feature = "half-weights")]
+        {
+            let largest = values.iter().fold(0.0, |a: f32, b| a.max(b.abs()));
+            let mut exponent = 0;
+            while largest > MAX_WEIGHT * 2.0f32.powi(exponent) {
+                exponent += 1;
+            }
+
+            self.exponents[sum].set(store_weight(exponent as f32));
+            let scale = 2.0f32.powi(-exponent);
+
+            for (index, (weight, value)) in weights.iter().zip(values).enumerate() {
+                let value = value * scale;
+                weight.set(match seed {
+                    None => store_weight(value),
+                    Some(seed) => {
+                        let bits = value.to_bits() as u64 ^ (index as u64).rotate_left(32);
+                        store_weight_stochastically(value, rounding_noise(bits ^ seed))
+                    }
+                });
+            }
+        }
+
+        #[cfg(not(feature = "half-weights"))]
+        {
+            let _ = (sum, seed);
+            for (weight, value) in weights.iter().zip(values) {
+                weight.set(*value);
+            }
         }
-        self.regret_positive_magnitude = sum;
+    }
+
+    /// The regret based pruning state of every decision, or `None`
+    /// if the state has never been allocated (see `init_skipped_visits`).
+    #[inline(always)]
+    pub fn skipped_visits(&self) -> Option<&'a [Cell<SkippedVisits>]> {
+        self.skipped_visits.get().copied()
+    }
+
+    /// Allocates the regret based pruning state, unless already present.
+    ///
+    /// Arenas never run destructors, so the state gets allocated inside
+    /// the arena itself, which also lets memory budgets account for it.
+    pub fn init_skipped_visits(&self, allocator: &'a Bump) {
+        self.skipped_visits
+            .get_or_init(|| allocator.alloc_slice_fill_default(self.len()));
+    }
+
+    /// Updates the cached regret magnitude once the regret sum has been changed.
+    pub fn recompute_regret_magnitude(&self) {
+        let sum = lane_sum(self.regret_sum, |regret| {
+            f32::max(load_weight(regret.get()), 0.0)
+        });
+
+        self.regret_scale
+            .set(if sum > 0.0 { 1.0 / sum } else { 0.0 });
     }
 
     /// Returns the strategy one should take in an actual game.
     /// Do not use this during training! (Performs a clone)
     pub fn get_average_strategy(&self) -> Vec<f32> {
-        let mut average_strategy = self.strategy_sum.to_vec();
+        let mut average_strategy: Vec<_> = self
+            .strategy_sum
+            .iter()
+            .map(|sum| load_weight(sum.get()))
+            .collect();
 
         normalize_vec(&mut average_strategy);
 
         average_strategy
     }
 
+    /// Returns the (unnormalized) weights of the average strategy.
+    fn average_strategy_weights(&self) -> Vec<f32> {
+        self.strategy_sum
+            .iter()
+            .map(|sum| load_weight(sum.get()))
+            .collect()
+    }
+
     /// Returns a random action based on the probability distribution
     /// in self.strategy_sum.
-    ///
-    /// TODO: perform normalization on-the-fly to avoid a .clone
-    ///       (not very urgent, as this is never called during training)
     pub fn random_action<R: Rng>(&self, rng: &mut R) -> usize {
-        let average = self.get_average_strategy();
+        sample(&self.average_strategy_weights(), rng)
+    }
 
-        roulette(&average, rng)
+    /// Returns a sampler for the average strategy, which is cheaper
+    /// than `random_action` when sampling many actions at once.
+    pub fn action_sampler(&self) -> AliasTable {
+        AliasTable::new(&self.average_strategy_weights())
     }
     // }}}
 }
 // }}}
+// {{{ Weight updates
+/// The updates some training iteration performs on a decision vector,
+/// accumulated in full precision (see `WeightUpdates`).
+#[cfg(feature = "half-weights")]
+struct PendingUpdates<'a> {
+    vector: &'a DecisionVector<'a>,
+    regret_sum: Vec<f32>,
+    strategy_sum: Vec<f32>,
+}
+
+/// Collects the updates training performs on decision vectors during a single iteration.
+///
+/// Full precision weights get updated right away. Half precision weights would stop
+/// changing once they grow large compared to the individual updates, so updates get
+/// accumulated as `f32`s instead, only getting rounded once the iteration is over
+/// (see `flush`). Buffers only get allocated for the vectors visited this iteration.
+///
+/// Even the updates of an entire iteration end up being small compared to the sums
+/// after enough iterations, so sums get rounded stochastically. The randomness is
+/// derived from the weights and the given seed, which keeps training deterministic.
+#[derive(Default)]
+pub struct WeightUpdates<'a> {
+    /// Should be different for every iteration. Otherwise, sums receiving the same
+    /// updates every iteration would keep getting rounded in the same direction.
+    #[cfg(feature = "half-weights")]
+    seed: u64,
+
+    /// Keyed by the address of the regret sum, such that vectors sharing
+    /// weights (see `DecisionVector::share`) share the buffers as well.
+    #[cfg(feature = "half-weights")]
+    pending: RefCell<HashMap<*const Cell<Weight>, PendingUpdates<'a>>>,
+
+    #[cfg(not(feature = "half-weights"))]
+    vectors: PhantomData<&'a DecisionVector<'a>>,
+}
+
+impl<'a> WeightUpdates<'a> {
+    pub fn new(seed: u64) -> Self {
+        #[cfg(not(feature = "half-weights"))]
+        let _ = seed;
+
+        Self {
+            #[cfg(feature = "half-weights")]
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Like `DecisionVector::accumulate_regret`.
+    #[inline(always)]
+    pub fn accumulate_regret(&self, vector: &'a DecisionVector<'a>, index: usize, amount: Utility) {
+        #[cfg(feature = "half-weights")]
+        self.with_pending(vector, |pending| pending.regret_sum[index] += amount);
+
+        #[cfg(not(feature = "half-weights"))]
+        vector.accumulate_regret(index, amount);
+    }
+
+    /// Like `DecisionVector::update_strategy_sum`.
+    #[inline(always)]
+    pub fn update_strategy_sum(&self, vector: &'a DecisionVector<'a>, probability: Probability) {
+        #[cfg(feature = "half-weights")]
+        self.with_pending(vector, |pending| {
+            for (index, sum) in pending.strategy_sum.iter_mut().enumerate() {
+                *sum += probability * vector.strategy(index);
+            }
+        });
+
+        #[cfg(not(feature = "half-weights"))]
+        vector.update_strategy_sum(probability);
+    }
+
+    /// Adds the given amounts to the strategy sum of some vector.
+    #[inline(always)]
+    pub fn add_strategy_sums(&self, vector: &'a DecisionVector<'a>, amounts: &[Probability]) {
+        #[cfg(feature = "half-weights")]
+        self.with_pending(vector, |pending| {
+            for (sum, amount) in pending.strategy_sum.iter_mut().zip(amounts) {
+                *sum += amount;
+            }
+        });
+
+        #[cfg(not(feature = "half-weights"))]
+        for (sum, amount) in vector.strategy_sum.iter().zip(amounts) {
+            sum.set(sum.get() + amount);
+        }
+    }
+
+    /// Like `DecisionVector::regret`, except updates which
+    /// have not been flushed yet are taken into account.
+    #[inline(always)]
+    pub fn regret(&self, vector: &'a DecisionVector<'a>, index: usize) -> Utility {
+        #[cfg(feature = "half-weights")]
+        if let Some(pending) = self.pending.borrow().get(&vector.regret_sum.as_ptr()) {
+            return vector.regret(index) + pending.regret_sum[index];
+        }
+
+        vector.regret(index)
+    }
+
+    #[cfg(feature = "half-weights")]
+    fn with_pending<R>(
+        &self,
+        vector: &'a DecisionVector<'a>,
+        f: impl FnOnce(&mut PendingUpdates<'a>) -> R,
+    ) -> R {
+        let mut pending = self.pending.borrow_mut();
+        let pending = pending
+            .entry(vector.regret_sum.as_ptr())
+            .or_insert_with(|| PendingUpdates {
+                vector,
+                regret_sum: vec![0.0; vector.len()],
+                strategy_sum: vec![0.0; vector.len()],
+            });
+
+        f(pending)
+    }
+
+    /// Writes the updates collected so far into the decision vectors.
+    /// The order vectors get written in does not matter.
+    pub fn flush(self) {
+        #[cfg(feature = "half-weights")]
+        for pending in self.pending.into_inner().into_values() {
+            let vector = pending.vector;
+            let add = |sums: Vec<f32>, updates: &[f32]| -> Vec<f32> {
+                sums.into_iter()
+                    .zip(updates)
+                    .map(|(sum, update)| sum + update)
+                    .collect()
+            };
+
+            let regrets = add(vector.regrets(), &pending.regret_sum);
+            let strategy_sums = add(vector.strategy_sums(), &pending.strategy_sum);
+            vector.store_sums(vector.regret_sum, 0, &regrets, Some(self.seed));
+            vector.store_sums(vector.strategy_sum, 1, &strategy_sums, Some(!self.seed));
+        }
+    }
+}
+// }}}
 // {{{ Decision matrix
 /// A decision matrix holds all the decision weights for a certain player
 /// (in a certain known game state).
 ///
 /// We don't have to expand this mapping out if the player can make a single decision.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum DecisionMatrix<'a> {
     Trivial,
-    Expanded(&'a mut [DecisionVector<'a>]),
+    Expanded(&'a [DecisionVector<'a>]),
 }
 
 impl<'a> DecisionMatrix<'a> {
     /// Indexes the matrix, returning `None` if it is trivial. That is, it returns
     /// `None` when the player should be treated as having a single decision they can
     /// (and will) take with probability `1`.
-    pub fn get_node_mut(&mut self, index: HiddenIndex) -> Option<&mut DecisionVector<'a>> {
+    pub fn get_node(&self, index: HiddenIndex) -> Option<&'a DecisionVector<'a>> {
         match self {
             Self::Trivial => None,
-            Self::Expanded(vec) => Some(&mut vec[index.0]),
+            Self::Expanded(vec) => Some(&vec[index.0]),
         }
     }
 
-    /// Similar to `get_node_mut`, but immutable.
-    pub fn get_node(&self, index: HiddenIndex) -> Option<&DecisionVector<'a>> {
-        match self {
-            Self::Trivial => None,
-            Self::Expanded(vec) => Some(&vec[index.0]),
+    /// Allocates the regret based pruning state of every vector
+    /// (see `DecisionVector::init_skipped_visits`).
+    pub fn init_skipped_visits(&self, allocator: &'a Bump) {
+        if let Self::Expanded(vectors) = self {
+            for vector in vectors.iter() {
+                vector.init_skipped_visits(allocator);
+            }
         }
     }
 
-    pub fn estimate_alloc(matrix_size: usize, vector_size: usize) -> usize {
+    pub fn estimate_alloc(matrix_size: usize, vector_size: usize, regret_pruning: bool) -> usize {
         size_of::<Self>()
             + if vector_size == 1 {
                 1
             } else {
-                matrix_size * DecisionVector::estimate_alloc(vector_size)
+                matrix_size * DecisionVector::estimate_alloc(vector_size, regret_pruning)
             }
     }
 
@@ -181,11 +628,16 @@ impl<'a> DecisionMatrix<'a> {
         if vector_size == 1 {
             1
         } else {
-            matrix_size * vector_size * 2
+            matrix_size * (vector_size * 2 + DecisionVector::EXPONENTS)
         }
     }
 
-    pub fn new(matrix_size: usize, vector_size: usize, allocator: &'a Bump) -> DecisionMatrix<'a> {
+    pub fn new(
+        matrix_size: usize,
+        vector_size: usize,
+        allocator: &'a Bump,
+        weights: WeightStorage<'a>,
+    ) -> DecisionMatrix<'a> {
         assert!(
             vector_size >= 1,
             "Players always have at least one valid decision"
@@ -198,9 +650,41 @@ impl<'a> DecisionMatrix<'a> {
         if vector_size == 1 {
             Self::Trivial
         } else {
-            Self::Expanded(allocator.alloc_slice_fill_with(matrix_size, |_| {
-                DecisionVector::new(vector_size, allocator)
-            }))
+            Self::Expanded(
+                allocator.alloc_slice_fill_with(matrix_size, |_| {
+                    DecisionVector::new(vector_size, weights)
+                }),
+            )
+        }
+    }
+
+    /// Like `new`, except hidden indices inside the same bucket share weights.
+    pub fn new_bucketed(
+        buckets: &Buckets,
+        vector_size: usize,
+        allocator: &'a Bump,
+        weights: WeightStorage<'a>,
+    ) -> DecisionMatrix<'a> {
+        assert!(
+            vector_size >= 1,
+            "Players always have at least one valid decision"
+        );
+
+        if vector_size == 1 {
+            Self::Trivial
+        } else {
+            let shared: Vec<_> = (0..buckets.count)
+                .map(|_| DecisionVector::new(vector_size, weights))
+                .collect();
+
+            Self::Expanded(
+                allocator.alloc_slice_fill_iter(
+                    buckets
+                        .of_index
+                        .iter()
+                        .map(|bucket| shared[*bucket].share()),
+                ),
+            )
         }
     }
 
@@ -214,10 +698,19 @@ impl<'a> DecisionMatrix<'a> {
             Self::Expanded(vectors) => vectors[0].len(),
         }
     }
+
+    /// Short description of the size of the matrix (eg: `120x8`).
+    fn describe_size(&self) -> String {
+        match self {
+            Self::Trivial => "trivial".to_string(),
+            Self::Expanded(vectors) => format!("{}x{}", vectors.len(), self.len()),
+        }
+    }
 }
 // }}}
 // {{{ Decision matrices
 /// A pair of decision matrices
+#[derive(Clone, Copy)]
 pub enum DecisionMatrices<'a> {
     Symmetrical(DecisionMatrix<'a>),
     Asymmetrical(Pair<DecisionMatrix<'a>>),
@@ -229,6 +722,7 @@ impl<'a> DecisionMatrices<'a> {
         hidden_counts: Pair<usize>,
         decision_counts: Pair<usize>,
         allocator: &'a Bump,
+        weights: WeightStorage<'a>,
     ) -> Self {
         if is_symmetrical {
             assert!(are_equal(decision_counts));
@@ -238,34 +732,65 @@ impl<'a> DecisionMatrices<'a> {
                 hidden_counts[0],
                 decision_counts[0],
                 allocator,
+                weights,
             ))
         } else {
-            let matrices = hidden_counts
-                .into_iter()
-                .zip(decision_counts)
-                .map(|(hidden, decision)| DecisionMatrix::new(hidden, decision, allocator))
-                .next_chunk()
-                .unwrap();
+            let matrices = hidden_counts.zip_with(decision_counts, |hidden, decision| {
+                DecisionMatrix::new(hidden, decision, allocator, weights)
+            });
 
             Self::Asymmetrical(matrices)
         }
     }
 
+    /// Like `new`, except hidden indices inside the same bucket share weights.
+    /// Players without buckets get their usual matrix.
+    pub fn new_bucketed(
+        is_symmetrical: bool,
+        hidden_counts: Pair<usize>,
+        decision_counts: Pair<usize>,
+        buckets: Pair<Option<Buckets>>,
+        allocator: &'a Bump,
+        weights: WeightStorage<'a>,
+    ) -> Self {
+        let matrix = |hidden, decision, buckets: &Option<Buckets>| match buckets {
+            Some(buckets) => DecisionMatrix::new_bucketed(buckets, decision, allocator, weights),
+            None => DecisionMatrix::new(hidden, decision, allocator, weights),
+        };
+
+        if is_symmetrical {
+            assert!(are_equal(decision_counts));
+            assert!(are_equal(hidden_counts));
+
+            Self::Symmetrical(matrix(hidden_counts[0], decision_counts[0], &buckets[0]))
+        } else {
+            Self::Asymmetrical(Player::PLAYERS.map(|player| {
+                matrix(
+                    player.select(hidden_counts),
+                    player.select(decision_counts),
+                    player.select_ref(&buckets),
+                )
+            }))
+        }
+    }
+
     pub fn estimate_alloc(
         is_symmetrical: bool,
         hidden_counts: Pair<usize>,
         decision_counts: Pair<usize>,
+        regret_pruning: bool,
     ) -> usize {
         if is_symmetrical {
             assert!(are_equal(decision_counts));
             assert!(are_equal(hidden_counts));
 
-            DecisionMatrix::estimate_alloc(hidden_counts[0], decision_counts[0])
+            DecisionMatrix::estimate_alloc(hidden_counts[0], decision_counts[0], regret_pruning)
         } else {
             hidden_counts
+                .zip_with(decision_counts, |hidden, decisions| {
+                    DecisionMatrix::estimate_alloc(hidden, decisions, regret_pruning)
+                })
                 .into_iter()
-                .zip(decision_counts)
-                .map(|(hidden, decision)| DecisionMatrix::estimate_alloc(hidden, decision))
                 .sum()
         }
     }
@@ -282,9 +807,8 @@ impl<'a> DecisionMatrices<'a> {
             DecisionMatrix::estimate_weight_storage(hidden_counts[0], decision_counts[0])
         } else {
             hidden_counts
+                .zip_with(decision_counts, DecisionMatrix::estimate_weight_storage)
                 .into_iter()
-                .zip(decision_counts)
-                .map(|(hidden, decision)| DecisionMatrix::estimate_weight_storage(hidden, decision))
                 .sum()
         }
     }
@@ -297,21 +821,18 @@ impl<'a> DecisionMatrices<'a> {
         }
     }
 
-    /// Gets the respective decision vectors for both players, mutably.
+    /// Gets the respective decision vectors for both players.
     ///
-    /// Conceptually, this is like calling `.get_node_mut` on the individual
+    /// Conceptually, this is like calling `.get_node` on the individual
     /// matrices (although the matrices might not be "individual" if the game
     /// state is symmetric).
-    pub fn get_nodes_mut(
-        &mut self,
-        [li, ri]: Pair<HiddenIndex>,
-    ) -> Pair<Option<&mut DecisionVector<'a>>> {
+    pub fn get_nodes(&self, [li, ri]: Pair<HiddenIndex>) -> Pair<Option<&'a DecisionVector<'a>>> {
         match self {
-            Self::Asymmetrical([left, right]) => [left.get_node_mut(li), right.get_node_mut(ri)],
-            Self::Symmetrical(matrix) => match matrix {
-                DecisionMatrix::Trivial => [None, None],
-                DecisionMatrix::Expanded(vec) => vec.get_many_mut([li.0, ri.0]).unwrap().map(Some),
-            },
+            Self::Asymmetrical([left, right]) => [left.get_node(li), right.get_node(ri)],
+            Self::Symmetrical(matrix) => {
+                debug_assert_ne!(li, ri, "Players cannot share the same hidden state");
+                [matrix.get_node(li), matrix.get_node(ri)]
+            }
         }
     }
 
@@ -322,6 +843,19 @@ impl<'a> DecisionMatrices<'a> {
             Self::Asymmetrical(matrices) => player.select_ref(matrices),
         }
     }
+
+    /// Allocates the regret based pruning state of every vector
+    /// (see `DecisionVector::init_skipped_visits`).
+    pub fn init_skipped_visits(&self, allocator: &'a Bump) {
+        match self {
+            Self::Symmetrical(matrix) => matrix.init_skipped_visits(allocator),
+            Self::Asymmetrical(matrices) => {
+                for matrix in matrices {
+                    matrix.init_skipped_visits(allocator);
+                }
+            }
+        }
+    }
 }
 // }}}
 // {{{ Explored scope
@@ -342,6 +876,28 @@ pub struct ExploredScope<'a> {
     /// Vector of possible future states.
     pub next: &'a mut [Scope<'a>],
 }
+
+impl<'a> ExploredScope<'a> {
+    /// Looks up the decision vector of a player in a given hidden state.
+    /// Returns `None` if the matrix is trivial, or the index is out of range.
+    pub fn node(&self, player: Player, index: HiddenIndex) -> Option<&'a DecisionVector<'a>> {
+        match self.matrices.get_matrix(player) {
+            DecisionMatrix::Trivial => None,
+            DecisionMatrix::Expanded(vectors) => vectors.get(index.0),
+        }
+    }
+
+    /// Computes the average strategy learned by some player in a given hidden state.
+    ///
+    /// Players with a single decision always take it, so this returns `[1.0]`
+    /// for trivial matrices. Returns `None` if the index is out of range.
+    pub fn strategy_for(&self, player: Player, index: HiddenIndex) -> Option<Vec<f32>> {
+        match self.matrices.get_matrix(player) {
+            DecisionMatrix::Trivial => Some(vec![1.0]),
+            DecisionMatrix::Expanded(_) => Some(self.node(player, index)?.get_average_strategy()),
+        }
+    }
+}
 // }}}
 // {{{ Unexplored scope
 /// An explored scope is a scope where all the game rules have
@@ -349,6 +905,10 @@ pub struct ExploredScope<'a> {
 // TODO: add utility tables
 pub struct UnexploredScope<'a> {
     pub state: Option<&'a KnownState>,
+
+    /// Context able to expand this scope the first time training reaches it.
+    /// Only present for trees generated with lazy expansion enabled.
+    pub expansion: Option<GenerationContext<'a>>,
 }
 // }}}
 // {{{ Scope
@@ -365,5 +925,70 @@ impl<'a> Scope<'a> {
             _ => None,
         }
     }
+
+    /// Moves on to the scope reached after revealing some information.
+    /// Returns `None` if this scope has not been explored.
+    pub fn descend(&self, reveal_index: RevealIndex) -> Option<&Scope<'a>> {
+        self.get_explored()?.next.get(reveal_index.0)
+    }
+
+    /// Repeatedly calls `descend`, following the given reveals in order.
+    pub fn descend_path(&self, reveals: &[RevealIndex]) -> Option<&Scope<'a>> {
+        reveals
+            .iter()
+            .try_fold(self, |scope, reveal_index| scope.descend(*reveal_index))
+    }
+
+    // {{{ Graphviz export
+    /// Renders the tree rooted at this scope using the graphviz DOT language.
+    ///
+    /// Nodes show the sizes of the decision matrices (`hidden states x decisions`)
+    /// or the final score, while edges are labeled by reveal indices.
+    /// The output grows with the size of the tree, so this is only
+    /// meant to be used on small subgames.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("digraph scope {\n    node [shape=box];\n");
+        let mut next_id = 0;
+
+        self.write_dot(&mut result, &mut next_id);
+        result.push_str("}\n");
+
+        result
+    }
+
+    /// Writes the node for this scope (and all its children), returning its id.
+    fn write_dot(&self, out: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        match self {
+            Self::Completed(score) => {
+                writeln!(out, "    n{id} [label=\"{:+}\", shape=ellipse];", score.0).unwrap();
+            }
+            Self::Unexplored(_) => {
+                writeln!(out, "    n{id} [label=\"unexplored\", style=dashed];").unwrap();
+            }
+            Self::Explored(explored) => {
+                let label = match &explored.matrices {
+                    DecisionMatrices::Symmetrical(matrix) => {
+                        format!("symmetrical {}", matrix.describe_size())
+                    }
+                    DecisionMatrices::Asymmetrical([left, right]) => {
+                        format!("{} / {}", left.describe_size(), right.describe_size())
+                    }
+                };
+
+                writeln!(out, "    n{id} [label=\"{label}\"];").unwrap();
+
+                for (reveal_index, child) in explored.next.iter().enumerate() {
+                    let child_id = child.write_dot(out, next_id);
+                    writeln!(out, "    n{id} -> n{child_id} [label=\"{reveal_index}\"];").unwrap();
+                }
+            }
+        }
+
+        id
+    }
+    // }}}
 }
 // }}}
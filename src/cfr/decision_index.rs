@@ -1,15 +1,26 @@
+use super::hidden_index::EncodingInfo;
+use super::phase::{PerPhase, SomePhase};
+use crate::error::{EchoError, EchoResult};
 use crate::game::creature::{Creature, CreatureSet};
 use crate::game::edict::Edict;
+use crate::game::known_state::KnownState;
 use crate::game::known_state_summary::KnownStateEssentials;
 use crate::game::types::Player;
 use crate::helpers::bitfield::const_size_codec::ConstSizeCodec;
 use crate::helpers::bitfield::Bitfield;
-use crate::helpers::choose::choose;
+use crate::helpers::choose::choose_multi;
 use crate::helpers::ranged::MixRanged;
 use itertools::Itertools;
 
+/// The action a decision encodes during each phase:
+/// - the creatures and edict played during the main phase
+/// - the sabotage guess (if any) during the sabotage phase
+/// - the creature played during the seer phase
+pub type DecodedDecision = PerPhase<(CreatureSet, Edict), Option<Creature>, Creature>;
+
 /// Used to index decision vectors.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DecisionIndex(pub usize);
 
 impl DecisionIndex {
@@ -21,12 +32,13 @@ impl DecisionIndex {
         hand: CreatureSet,
         creatures: CreatureSet,
         edict: Edict,
-    ) -> Option<DecisionIndex> {
+    ) -> EchoResult<DecisionIndex> {
         let creature_choice = creatures.encode_ones_relative_to(hand);
 
-        Some(DecisionIndex(
-            creature_choice.mix_indexof(edict, state.player_edicts(player))?,
-        ))
+        creature_choice
+            .mix_indexof(edict, state.player_edicts(player))
+            .map(DecisionIndex)
+            .ok_or(EchoError::Encode("main phase decision"))
     }
 
     /// Decodes a main phase user choice into a decision index.
@@ -35,26 +47,89 @@ impl DecisionIndex {
         state: &S,
         player: Player,
         hand: CreatureSet,
-    ) -> Option<(CreatureSet, Edict)> {
+    ) -> EchoResult<(CreatureSet, Edict)> {
         assert_eq!(hand.len(), state.hand_size());
 
-        let (encoded_creatures, edict) = self.0.unmix_indexof(state.player_edicts(player))?;
+        let error = EchoError::Decode("main phase decision");
+        let (encoded_creatures, edict) = self
+            .0
+            .unmix_indexof(state.player_edicts(player))
+            .ok_or(error.clone())?;
         let creature_choice = CreatureSet::decode_ones_relative_to(
             encoded_creatures,
             state.creature_choice_size(player),
             hand,
-        )?;
+        )
+        .ok_or(error)?;
+
+        Ok((creature_choice, edict))
+    }
+
+    /// Batch version of `encode_main_phase_index`, writing the results into `out`.
+    /// Stops at the first choice which cannot be encoded.
+    pub fn encode_main_phase_all<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+        hand: CreatureSet,
+        choices: &[(CreatureSet, Edict)],
+        out: &mut [DecisionIndex],
+    ) -> EchoResult<()> {
+        assert_eq!(choices.len(), out.len());
+        let edicts = state.player_edicts(player);
+
+        for ((creatures, edict), result) in choices.iter().zip(out.iter_mut()) {
+            *result = creatures
+                .encode_ones_relative_to(hand)
+                .mix_indexof(*edict, edicts)
+                .map(DecisionIndex)
+                .ok_or(EchoError::Encode("main phase decision"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Batch version of `decode_main_phase_index`, writing the results into `out`.
+    /// Stops at the first index which cannot be decoded.
+    pub fn decode_main_phase_all<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+        hand: CreatureSet,
+        indices: &[DecisionIndex],
+        out: &mut [(CreatureSet, Edict)],
+    ) -> EchoResult<()> {
+        assert_eq!(hand.len(), state.hand_size());
+        assert_eq!(indices.len(), out.len());
+
+        let edicts = state.player_edicts(player);
+        let choice_size = state.creature_choice_size(player);
+        let error = EchoError::Decode("main phase decision");
+
+        for (index, result) in indices.iter().zip(out.iter_mut()) {
+            let (encoded_creatures, edict) = index.0.unmix_indexof(edicts).ok_or(error.clone())?;
+            let creatures =
+                CreatureSet::decode_ones_relative_to(encoded_creatures, choice_size, hand)
+                    .ok_or(error.clone())?;
 
-        Some((creature_choice, edict))
+            *result = (creatures, edict);
+        }
+
+        Ok(())
     }
 
     /// One more than the maximum value of `encode_main_phase_index`.
+    /// Fails if the count does not fit inside an `usize`.
     #[inline(always)]
-    pub fn main_phase_index_count<S: KnownStateEssentials>(state: &S, player: Player) -> usize {
-        let choice_count = choose(state.hand_size(), state.creature_choice_size(player));
+    pub fn main_phase_index_count<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+    ) -> EchoResult<usize> {
+        let choice_size = state.creature_choice_size(player);
         let edict_count = state.player_edicts(player).len();
 
-        choice_count * edict_count
+        // Split the hand into the creatures we play, and the ones we keep.
+        choose_multi(&[choice_size, state.hand_size() - choice_size])
+            .and_then(|choice_count| choice_count.checked_mul(edict_count))
+            .ok_or(EchoError::Overflow("main phase decisions"))
     }
     // }}}
     // {{{ Sabotage phase
@@ -89,22 +164,69 @@ impl DecisionIndex {
         state: &S,
         hand: CreatureSet,
         sabotage_status: bool,
-    ) -> Option<Option<Creature>> {
+    ) -> EchoResult<Option<Creature>> {
         let result = if sabotage_status {
             let possibilities = Self::sabotage_decision_possibilities(hand, state.graveyard());
 
-            let creature = CreatureSet::decode_ones_relative_to(self.0, 1, possibilities)?
-                .into_iter()
-                .exactly_one()
-                .ok()?;
+            let creature = CreatureSet::decode_ones_relative_to(self.0, 1, possibilities)
+                .and_then(|creatures| creatures.into_iter().exactly_one().ok())
+                .ok_or(EchoError::Decode("sabotage phase decision"))?;
 
             Some(creature)
-        } else {
-            assert_eq!(self.0, 0);
+        } else if self.0 == 0 {
             None
+        } else {
+            return Err(EchoError::Decode("sabotage phase decision"));
         };
 
-        Some(result)
+        Ok(result)
+    }
+
+    /// Batch version of `encode_sabotage_index`, writing the results into `out`.
+    pub fn encode_sabotage_all<S: KnownStateEssentials>(
+        state: &S,
+        hand: CreatureSet,
+        guesses: &[Option<Creature>],
+        out: &mut [Self],
+    ) {
+        assert_eq!(guesses.len(), out.len());
+        let possibilities = Self::sabotage_decision_possibilities(hand, state.graveyard());
+
+        for (guess, result) in guesses.iter().zip(out.iter_mut()) {
+            *result = match guess {
+                Some(guess) => {
+                    Self(CreatureSet::singleton(*guess).encode_ones_relative_to(possibilities))
+                }
+                None => Self(0),
+            };
+        }
+    }
+
+    /// Batch version of `decode_sabotage_index`, writing the results into `out`.
+    /// Stops at the first index which cannot be decoded.
+    pub fn decode_sabotage_all<S: KnownStateEssentials>(
+        state: &S,
+        hand: CreatureSet,
+        sabotage_status: bool,
+        indices: &[Self],
+        out: &mut [Option<Creature>],
+    ) -> EchoResult<()> {
+        assert_eq!(indices.len(), out.len());
+        let possibilities = Self::sabotage_decision_possibilities(hand, state.graveyard());
+        let error = EchoError::Decode("sabotage phase decision");
+
+        for (index, result) in indices.iter().zip(out.iter_mut()) {
+            *result = if sabotage_status {
+                let creature = possibilities.index(index.0).ok_or(error.clone())?;
+                Some(creature)
+            } else if index.0 == 0 {
+                None
+            } else {
+                return Err(error);
+            };
+        }
+
+        Ok(())
     }
 
     /// One more than the maximum value of `encode_sabotage_phase_index`.
@@ -123,13 +245,18 @@ impl DecisionIndex {
     // {{{ Seer phase
     /// Encodes a decision we can take during the seer phase.
     /// Assumes we know the hidden information of the current player.
-    pub fn encode_seer_index(creatures: CreatureSet, choice: Creature) -> Option<Self> {
-        creatures.indexof(choice).map(Self)
+    pub fn encode_seer_index(creatures: CreatureSet, choice: Creature) -> EchoResult<Self> {
+        creatures
+            .indexof(choice)
+            .map(Self)
+            .ok_or(EchoError::Encode("seer phase decision"))
     }
 
     /// Inverse of `encode_seer_index`.
-    pub fn decode_seer_index(self, creatures: CreatureSet) -> Option<Creature> {
-        creatures.index(self.0)
+    pub fn decode_seer_index(self, creatures: CreatureSet) -> EchoResult<Creature> {
+        creatures
+            .index(self.0)
+            .ok_or(EchoError::Decode("seer phase decision"))
     }
 
     /// One more than the maximum value of `encode-seer_index`
@@ -137,6 +264,87 @@ impl DecisionIndex {
         creatures.len()
     }
     // }}}
+    // {{{ Enumeration
+    /// Decodes the decision a player took during the given phase.
+    pub fn decode(
+        self,
+        state: &KnownState,
+        phase: &SomePhase,
+        player: Player,
+        hidden: EncodingInfo,
+    ) -> EchoResult<DecodedDecision> {
+        let hand = hidden.get_main();
+
+        let result = match phase {
+            PerPhase::Main(_) => PerPhase::Main(self.decode_main_phase_index(state, player, hand)?),
+            PerPhase::Sabotage(_) => {
+                let status = phase.sabotage_status(player);
+                PerPhase::Sabotage(self.decode_sabotage_index(state, hand, status)?)
+            }
+            PerPhase::Seer(_) => {
+                let choices = hidden.get_sabotage().ok_or(EchoError::InvalidState(
+                    "The creature choices must be known during the seer phase".to_string(),
+                ))?;
+
+                PerPhase::Seer(self.decode_seer_index(choices)?)
+            }
+        };
+
+        Ok(result)
+    }
+
+    /// Enumerates every decision the given player can take during the given phase,
+    /// together with the action it encodes.
+    pub fn enumerate(
+        state: &KnownState,
+        phase: &SomePhase,
+        player: Player,
+        hidden: EncodingInfo,
+    ) -> impl Iterator<Item = (Self, DecodedDecision)> {
+        let (state, phase) = (*state, *phase);
+        let count = player.select(phase.decision_counts(&state));
+
+        (0..count).filter_map(move |index| {
+            let index = Self(index);
+            let decoded = index.decode(&state, &phase, player, hidden).ok()?;
+            Some((index, decoded))
+        })
+    }
+    // }}}
+    // {{{ Descriptions
+    /// Describes what taking this decision means, in a human readable way
+    /// (e.g. `Play Monarch + Gambit` or `Sabotage guess: Witch`).
+    pub fn try_describe(
+        self,
+        state: &KnownState,
+        phase: &SomePhase,
+        player: Player,
+        hidden: EncodingInfo,
+    ) -> EchoResult<String> {
+        let result = match self.decode(state, phase, player, hidden)? {
+            PerPhase::Main((creatures, edict)) => {
+                format!("Play {} + {edict}", creatures.into_iter().join(" & "))
+            }
+            PerPhase::Sabotage(Some(guess)) => format!("Sabotage guess: {guess}"),
+            PerPhase::Sabotage(None) => "No sabotage guess".to_string(),
+            PerPhase::Seer(creature) => format!("Play {creature}"),
+        };
+
+        Ok(result)
+    }
+
+    /// Same as `try_describe`, except invalid decisions are described as such.
+    pub fn describe(
+        self,
+        state: &KnownState,
+        phase: &SomePhase,
+        player: Player,
+        hidden: EncodingInfo,
+    ) -> String {
+        self.try_describe(state, phase, player, hidden)
+            .unwrap_or_else(|_| format!("Invalid decision #{}", self.0))
+    }
+    // }}}
 }
 
 impl From<usize> for DecisionIndex {
@@ -149,6 +357,8 @@ impl From<usize> for DecisionIndex {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cfr::fixtures::graveyards;
+    use crate::game::creature::Creature::*;
     use crate::game::edict::EdictSet;
     use crate::game::known_state_summary::KnownStateSummary;
     use std::{assert_eq, iter};
@@ -161,7 +371,7 @@ mod tests {
                 continue;
             }
 
-            for graveyard in CreatureSet::members() {
+            for graveyard in graveyards() {
                 let player = Player::Me;
 
                 for seer_player in [None, Some(player)] {
@@ -186,9 +396,10 @@ mod tests {
                             .unwrap();
 
                             let decoded = encoded.decode_main_phase_index(&state, player, hand);
-                            let count = DecisionIndex::main_phase_index_count(&state, player);
+                            let count =
+                                DecisionIndex::main_phase_index_count(&state, player).unwrap();
 
-                            assert_eq!(decoded, Some((creatures, edict)));
+                            assert_eq!(decoded, Ok((creatures, edict)));
                             assert!(encoded.0 < count);
 
                             if encoded.0 + 1 == count {
@@ -206,7 +417,7 @@ mod tests {
     // {{{ Sabotage phase
     #[test]
     fn encode_decode_sabotage_inverses() {
-        for graveyard in CreatureSet::members() {
+        for graveyard in graveyards() {
             let player = Player::Me;
             let state = KnownStateSummary::new(Default::default(), graveyard, None);
             let choice_size = state.creature_choice_size(player);
@@ -228,7 +439,7 @@ mod tests {
                     let decoded = encoded.decode_sabotage_index(&state, hand, guess.is_some());
                     let count = DecisionIndex::sabotage_phase_index_count(&state, guess.is_some());
 
-                    assert_eq!(decoded, Some(guess));
+                    assert_eq!(decoded, Ok(guess));
                     assert!(encoded.0 < count);
 
                     if encoded.0 + 1 == count {
@@ -258,16 +469,189 @@ mod tests {
                 let encoded = DecisionIndex::encode_seer_index(creatures, result);
 
                 assert_eq!(
-                    encoded.and_then(|e| e.decode_seer_index(creatures)),
+                    encoded
+                        .clone()
+                        .and_then(|e| e.decode_seer_index(creatures))
+                        .ok(),
                     expected
                 );
 
-                if let Some(encoded) = encoded {
+                if let Ok(encoded) = encoded {
                     assert!(encoded.0 < DecisionIndex::seer_index_count(creatures));
                 }
             }
         }
     }
     // }}}
+    // {{{ Batch codec
+    #[test]
+    fn batch_codec_matches_single_codec() {
+        let player = Player::Me;
+
+        for graveyard in graveyards().step_by(37) {
+            let state = KnownStateSummary::new([EdictSet::all(); 2], graveyard, Some(player));
+            let choice_size = state.creature_choice_size(player);
+
+            if state.hand_size() < choice_size {
+                continue;
+            }
+
+            for hand in (!graveyard).subsets_of_size(state.hand_size()).take(3) {
+                // {{{ Main phase
+                let choices: Vec<_> = hand
+                    .subsets_of_size(choice_size)
+                    .cartesian_product(EdictSet::all())
+                    .collect();
+
+                let mut encoded = vec![DecisionIndex(0); choices.len()];
+                let mut decoded = vec![(CreatureSet::empty(), Edict::Gambit); choices.len()];
+
+                DecisionIndex::encode_main_phase_all(&state, player, hand, &choices, &mut encoded)
+                    .unwrap();
+                DecisionIndex::decode_main_phase_all(&state, player, hand, &encoded, &mut decoded)
+                    .unwrap();
+
+                for ((creatures, edict), index) in choices.iter().zip(&encoded) {
+                    assert_eq!(
+                        Ok(*index),
+                        DecisionIndex::encode_main_phase_index(
+                            &state, player, hand, *creatures, *edict
+                        )
+                    );
+                }
+
+                assert_eq!(choices, decoded);
+                // }}}
+                // {{{ Sabotage phase
+                let guesses: Vec<_> =
+                    DecisionIndex::sabotage_decision_possibilities(hand, graveyard)
+                        .into_iter()
+                        .map(Some)
+                        .collect();
+
+                let mut encoded = vec![DecisionIndex(0); guesses.len()];
+                let mut decoded = vec![None; guesses.len()];
+
+                DecisionIndex::encode_sabotage_all(&state, hand, &guesses, &mut encoded);
+                DecisionIndex::decode_sabotage_all(&state, hand, true, &encoded, &mut decoded)
+                    .unwrap();
+
+                for (guess, index) in guesses.iter().zip(&encoded) {
+                    assert_eq!(
+                        *index,
+                        DecisionIndex::encode_sabotage_index(&state, hand, *guess)
+                    );
+                }
+
+                assert_eq!(guesses, decoded);
+                // }}}
+            }
+        }
+    }
+    // }}}
+    // {{{ Enumeration
+    #[test]
+    fn enumeration_covers_every_decision() {
+        use crate::cfr::phase::{MainPhase, SabotagePhase};
+        use crate::game::battlefield::{Battlefield, Battlefields};
+
+        let state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        let player = Player::Me;
+        let hand = CreatureSet::all()
+            .subsets_of_size(state.hand_size())
+            .next()
+            .unwrap();
+
+        let phase = PerPhase::Main(MainPhase::new());
+        let hidden = EncodingInfo::Main(hand);
+        let decisions: Vec<_> = DecisionIndex::enumerate(&state, &phase, player, hidden).collect();
+
+        assert_eq!(
+            decisions.len(),
+            DecisionIndex::main_phase_index_count(&state, player).unwrap()
+        );
+
+        for (index, decoded) in decisions {
+            let PerPhase::Main((creatures, edict)) = decoded else {
+                panic!("Decoded a decision from the wrong phase");
+            };
+
+            assert_eq!(
+                DecisionIndex::encode_main_phase_index(&state, player, hand, creatures, edict),
+                Ok(index)
+            );
+        }
+
+        let phase = PerPhase::Sabotage(SabotagePhase::new([Edict::Sabotage, Edict::Gambit]));
+        let choice = hand.subsets_of_size(1).next().unwrap();
+        let guesses: Vec<_> =
+            DecisionIndex::enumerate(&state, &phase, player, EncodingInfo::Sabotage(hand, choice))
+                .map(|(_, decoded)| decoded)
+                .collect();
+
+        assert_eq!(
+            guesses.len(),
+            DecisionIndex::sabotage_phase_index_count(&state, true)
+        );
+        assert!(guesses.iter().all(
+            |guess| matches!(guess, PerPhase::Sabotage(Some(creature)) if !hand.has(*creature))
+        ));
+    }
+    // }}}
+    // {{{ Descriptions
+    #[test]
+    fn decisions_get_described() {
+        use crate::cfr::phase::{MainPhase, SabotagePhase};
+        use crate::game::battlefield::{Battlefield, Battlefields};
+
+        let battlefields = [Battlefield::Night; Battlefields::COUNT];
+        let state = KnownState::new_starting(battlefields);
+        let player = Player::Me;
+        let mut hand = CreatureSet::default();
+
+        for creature in [Monarch, Wall, Seer, Rogue, Witch]
+            .into_iter()
+            .take(state.hand_size())
+        {
+            hand.insert(creature);
+        }
+
+        // {{{ Main phase
+        let phase = PerPhase::Main(MainPhase::new());
+        let hidden = EncodingInfo::Main(hand);
+        let decision = DecisionIndex::encode_main_phase_index(
+            &state,
+            player,
+            hand,
+            CreatureSet::singleton(Monarch),
+            Edict::Gambit,
+        )
+        .unwrap();
+
+        assert_eq!(
+            decision.describe(&state, &phase, player, hidden),
+            "Play Monarch + Gambit"
+        );
+        assert_eq!(
+            DecisionIndex(1000).describe(&state, &phase, player, hidden),
+            "Invalid decision #1000"
+        );
+        // }}}
+        // {{{ Sabotage phase
+        let phase = PerPhase::Sabotage(SabotagePhase::new([Edict::Sabotage, Edict::Gambit]));
+        let hidden = EncodingInfo::Sabotage(hand, CreatureSet::singleton(Monarch));
+        let decision = DecisionIndex::encode_sabotage_index(&state, hand, Some(Creature::Bard));
+
+        assert_eq!(
+            decision.describe(&state, &phase, player, hidden),
+            "Sabotage guess: Bard"
+        );
+        assert_eq!(
+            DecisionIndex(0).describe(&state, &phase, !player, hidden),
+            "No sabotage guess"
+        );
+        // }}}
+    }
+    // }}}
 }
 // }}}
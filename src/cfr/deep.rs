@@ -0,0 +1,670 @@
+//! Deep counterfactual regret minimization (see "Deep Counterfactual Regret
+//! Minimization" by Brown et al).
+//!
+//! Tabular training stores the regrets of every hidden index of every public
+//! state, which stops fitting in memory long before the full game does. Here,
+//! the regrets (or rather, the advantages) get approximated by a small neural
+//! network per player instead, which generalizes between similar situations.
+//!
+//! The tree never gets generated: every iteration samples games directly from
+//! `GamePosition`s (using external sampling), collecting training samples into
+//! fixed size reservoirs. Memory usage thus only depends on the size of the
+//! reservoirs, and not on how many turns the game lasts. Once training is over,
+//! a last network gets fit to the average strategy, which is what agents play.
+use super::decision::{Probability, Utility};
+use super::decision_index::DecisionIndex;
+use super::hidden_index::{EncodingInfo, PerPhaseInfo};
+use super::phase::{MainPhase, PerPhase, Phase, SomePhase};
+use super::position::GamePosition;
+use crate::ai::echo_ai::AgentInput;
+use crate::ai::strategy_hints::StrategyProvider;
+use crate::error::{EchoError, EchoResult};
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::Edict;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::status_effect::StatusEffect;
+use crate::game::types::{Player, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::choose::checked_choose;
+use crate::helpers::pair::Pair;
+use crate::helpers::sampling::sample;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::{linear, AdamW, Linear, Module, Optimizer, ParamsAdamW, VarBuilder, VarMap};
+use rand::seq::index;
+use rand::Rng;
+use std::path::Path;
+use std::time::Instant;
+use tracing::Level;
+
+impl From<candle_core::Error> for EchoError {
+    fn from(error: candle_core::Error) -> Self {
+        Self::Model(error.to_string())
+    }
+}
+
+// {{{ Features
+const CREATURES: usize = Creature::CREATURES.len();
+const EDICTS: usize = Edict::EDICTS.len();
+const STATUS_EFFECTS: usize = StatusEffect::STATUS_EFFECTS.len();
+
+/// The number of values the position of a player gets turned into
+/// (see `encode_features` for the meaning of every value).
+pub const FEATURE_COUNT: usize = 3
+    + 1
+    + Battlefield::BATTLEFIELDS.len()
+    + 1
+    + CREATURES
+    + 2 * EDICTS
+    + 2 * STATUS_EFFECTS
+    + 3 * CREATURES
+    + 2 * EDICTS
+    + 2 * CREATURES;
+
+/// The most decisions any player can ever choose between. Networks
+/// have one output per decision, the extra ones being ignored.
+pub const MAX_DECISIONS: usize = match checked_choose(Battlefields::COUNT + 1, 2) {
+    Some(choices) => choices * EDICTS,
+    None => panic!("Too many battlefields"),
+};
+
+/// Describes the position of a player as seen by the player, such that
+/// it can be fed to a network. Every value is either a flag or a fraction.
+pub fn encode_features(
+    state: &KnownState,
+    phase: &SomePhase,
+    player: Player,
+    hidden: EncodingInfo,
+) -> Vec<f32> {
+    fn flag(features: &mut Vec<f32>, value: bool) {
+        features.push(if value { 1.0 } else { 0.0 });
+    }
+
+    fn bits(features: &mut Vec<f32>, bitfield: impl Bitfield, count: usize) {
+        for bit in 0..count {
+            flag(features, bitfield.has_raw(bit));
+        }
+    }
+
+    fn one_hot(features: &mut Vec<f32>, value: Option<usize>, count: usize) {
+        for index in 0..count {
+            flag(features, value == Some(index));
+        }
+    }
+
+    let mut features = Vec::with_capacity(FEATURE_COUNT);
+    let players = [player, !player];
+
+    // {{{ Public information
+    one_hot(&mut features, Some(phase.tag() as usize), 3);
+    features.push(state.battlefields.current as f32 / Battlefields::COUNT as f32);
+    one_hot(
+        &mut features,
+        Battlefield::BATTLEFIELDS
+            .iter()
+            .position(|battlefield| *battlefield == state.battlefields.current()),
+        Battlefield::BATTLEFIELDS.len(),
+    );
+    features.push(state.score(player).0 as f32 / 10.0);
+    bits(&mut features, state.graveyard, CREATURES);
+
+    for player in players {
+        bits(&mut features, state.player_edicts(player), EDICTS);
+    }
+
+    for player in players {
+        let effects = player.select(state.player_states).effects;
+        bits(&mut features, effects, STATUS_EFFECTS);
+    }
+    // }}}
+    // {{{ Hidden information
+    let (choice, revealed) = match hidden {
+        PerPhaseInfo::Main(_) => (None, None),
+        PerPhaseInfo::Sabotage(_, choice) => (Some(choice), None),
+        PerPhaseInfo::Seer(_, choice, revealed) => (Some(choice), Some(revealed)),
+    };
+
+    bits(&mut features, hidden.get_main(), CREATURES);
+    bits(
+        &mut features,
+        choice.unwrap_or_else(CreatureSet::empty),
+        CREATURES,
+    );
+    one_hot(&mut features, revealed.map(|c| c as usize), CREATURES);
+    // }}}
+    // {{{ Choices made earlier this turn
+    let (edicts, guesses) = match phase {
+        PerPhase::Main(_) => (None, None),
+        PerPhase::Sabotage(phase) => (Some(phase.edict_choices), None),
+        PerPhase::Seer(phase) => (Some(phase.edict_choices), Some(phase.sabotage_choices)),
+    };
+
+    for player in players {
+        let edict = edicts.map(|edicts| player.select(edicts) as usize);
+        one_hot(&mut features, edict, EDICTS);
+    }
+
+    for player in players {
+        let guess = guesses.and_then(|guesses| player.select(guesses));
+        one_hot(&mut features, guess.map(|c| c as usize), CREATURES);
+    }
+    // }}}
+
+    debug_assert_eq!(features.len(), FEATURE_COUNT);
+    features
+}
+// }}}
+// {{{ Samples
+/// A position together with the values the network should learn to output.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub features: Vec<f32>,
+
+    /// One value per decision. Decisions without a value
+    /// (for instance, ones left out by sampling) are ignored.
+    pub values: Vec<Option<f32>>,
+
+    /// How much the sample matters compared to the others. Samples
+    /// from later iterations count more (as in linear cfr).
+    pub weight: f32,
+}
+
+/// Keeps a uniformly random subset of all the samples ever pushed to it,
+/// never holding more than some fixed number of them (reservoir sampling).
+#[derive(Debug, Clone)]
+pub struct Reservoir {
+    samples: Vec<Sample>,
+    capacity: usize,
+
+    /// Number of samples pushed so far.
+    seen: usize,
+}
+
+impl Reservoir {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: vec![],
+            capacity,
+            seen: 0,
+        }
+    }
+
+    pub fn push<R: Rng>(&mut self, sample: Sample, rng: &mut R) {
+        self.seen += 1;
+
+        if self.samples.len() < self.capacity {
+            self.samples.push(sample);
+        } else {
+            let index = rng.gen_range(0..self.seen);
+            if index < self.capacity {
+                self.samples[index] = sample;
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+}
+// }}}
+// {{{ Networks
+/// A network mapping features to one value per decision.
+pub struct DecisionNetwork {
+    variables: VarMap,
+    layers: Vec<Linear>,
+}
+
+impl DecisionNetwork {
+    /// Creates a randomly initialized network.
+    pub fn new(hidden_size: usize) -> EchoResult<Self> {
+        let variables = VarMap::new();
+        let builder = VarBuilder::from_varmap(&variables, DType::F32, &Device::Cpu);
+        let layers = vec![
+            linear(FEATURE_COUNT, hidden_size, builder.pp("input"))?,
+            linear(hidden_size, hidden_size, builder.pp("hidden"))?,
+            linear(hidden_size, MAX_DECISIONS, builder.pp("output"))?,
+        ];
+
+        Ok(Self { variables, layers })
+    }
+
+    /// Loads a network saved using `save`.
+    pub fn load(path: impl AsRef<Path>) -> EchoResult<Self> {
+        let tensors = candle_core::safetensors::load(path.as_ref(), &Device::Cpu)?;
+        let hidden_size = match tensors.get("input.weight") {
+            Some(weights) => weights.dims()[0],
+            None => return Err(EchoError::Model("Missing the input layer".to_string())),
+        };
+
+        let mut network = Self::new(hidden_size)?;
+        network.variables.load(path)?;
+        Ok(network)
+    }
+
+    /// Saves the weights of the network as safetensors.
+    pub fn save(&self, path: impl AsRef<Path>) -> EchoResult<()> {
+        self.variables.save(path)?;
+        Ok(())
+    }
+
+    fn forward(&self, features: &Tensor) -> candle_core::Result<Tensor> {
+        let (last, hidden) = self.layers.split_last().unwrap();
+        let mut values = features.clone();
+
+        for layer in hidden {
+            values = layer.forward(&values)?.relu()?;
+        }
+
+        last.forward(&values)
+    }
+
+    /// Computes the values of the first `decision_count` decisions.
+    pub fn predict(&self, features: &[f32], decision_count: usize) -> EchoResult<Vec<f32>> {
+        let features = Tensor::from_slice(features, (1, FEATURE_COUNT), &Device::Cpu)?;
+        let mut values = self.forward(&features)?.squeeze(0)?.to_vec1::<f32>()?;
+        values.truncate(decision_count);
+        Ok(values)
+    }
+
+    /// Minimizes the weighted mean squared error over the given samples,
+    /// returning the loss of the last batch.
+    pub fn fit<R: Rng>(
+        &mut self,
+        samples: &[Sample],
+        config: &DeepCfrConfig,
+        rng: &mut R,
+    ) -> EchoResult<f32> {
+        if samples.is_empty() {
+            return Ok(0.0);
+        }
+
+        let params = ParamsAdamW {
+            lr: config.learning_rate,
+            ..Default::default()
+        };
+        let mut optimizer = AdamW::new(self.variables.all_vars(), params)?;
+        let mut loss = 0.0;
+
+        for _ in 0..config.training_steps {
+            let size = config.batch_size;
+            let mut features = Vec::with_capacity(size * FEATURE_COUNT);
+            let mut targets = vec![0.0; size * MAX_DECISIONS];
+            let mut weights = vec![0.0; size * MAX_DECISIONS];
+
+            for row in 0..size {
+                let sample = &samples[rng.gen_range(0..samples.len())];
+                features.extend_from_slice(&sample.features);
+
+                for (decision, value) in sample.values.iter().enumerate() {
+                    if let Some(value) = value {
+                        targets[row * MAX_DECISIONS + decision] = *value;
+                        weights[row * MAX_DECISIONS + decision] = sample.weight;
+                    }
+                }
+            }
+
+            let device = Device::Cpu;
+            let features = Tensor::from_vec(features, (size, FEATURE_COUNT), &device)?;
+            let targets = Tensor::from_vec(targets, (size, MAX_DECISIONS), &device)?;
+            let weights = Tensor::from_vec(weights, (size, MAX_DECISIONS), &device)?;
+
+            let errors = (self.forward(&features)? - targets)?.sqr()?;
+            let total_weight = weights.sum_all()?.to_scalar::<f32>()?.max(f32::EPSILON);
+            let batch_loss = ((errors * weights)?.sum_all()? / total_weight as f64)?;
+
+            optimizer.backward_step(&batch_loss)?;
+            loss = batch_loss.to_scalar::<f32>()?;
+        }
+
+        Ok(loss)
+    }
+}
+// }}}
+// {{{ Training
+/// Options controlling deep cfr training.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeepCfrConfig {
+    pub iterations: usize,
+
+    /// Games sampled for each player every iteration.
+    pub traversals: usize,
+
+    /// How many decisions the traversing player tries at every position.
+    /// Trying all of them gets expensive quickly, as the main phase
+    /// can have dozens of decisions. All of them are tried if missing.
+    pub exploration_width: Option<usize>,
+
+    /// The maximum number of samples kept around for each network.
+    pub reservoir_capacity: usize,
+
+    pub hidden_size: usize,
+    pub training_steps: usize,
+    pub batch_size: usize,
+    pub learning_rate: f64,
+}
+
+impl Default for DeepCfrConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 100,
+            traversals: 1000,
+            exploration_width: Some(4),
+            reservoir_capacity: 1_000_000,
+            hidden_size: 128,
+            training_steps: 1000,
+            batch_size: 512,
+            learning_rate: 0.001,
+        }
+    }
+}
+
+pub struct DeepCfrContext {
+    config: DeepCfrConfig,
+
+    /// Approximates the advantages of each player. `None`
+    /// before the first iteration, in which case play is uniform.
+    advantages: Pair<Option<DecisionNetwork>>,
+    advantage_samples: Pair<Reservoir>,
+    strategy_samples: Reservoir,
+}
+
+impl DeepCfrContext {
+    pub fn new(config: DeepCfrConfig) -> Self {
+        Self {
+            config,
+            advantages: [None, None],
+            advantage_samples: [
+                Reservoir::new(config.reservoir_capacity),
+                Reservoir::new(config.reservoir_capacity),
+            ],
+            strategy_samples: Reservoir::new(config.reservoir_capacity),
+        }
+    }
+
+    /// Trains both players starting from the given state (during the main phase),
+    /// returning a network approximating the average strategy.
+    pub fn train<R: Rng>(&mut self, state: KnownState, rng: &mut R) -> EchoResult<DecisionNetwork> {
+        let phase = MainPhase::new();
+        let deals: Vec<_> = phase.valid_hidden_states(state.to_summary()).collect();
+        let start = Instant::now();
+
+        for iteration in 1..=self.config.iterations {
+            for traverser in Player::PLAYERS {
+                for _ in 0..self.config.traversals {
+                    let deal = deals[rng.gen_range(0..deals.len())];
+                    let position = GamePosition::new(state, PerPhase::Main(phase), deal);
+                    self.traverse(&position, traverser, iteration, rng)?;
+                }
+
+                // Networks get retrained from scratch, as suggested by the paper.
+                let mut network = DecisionNetwork::new(self.config.hidden_size)?;
+                let samples = traverser.select_ref(&self.advantage_samples).samples();
+                let loss = network.fit(samples, &self.config, rng)?;
+                tracing::event!(Level::DEBUG, iteration, ?traverser, loss, "Fit advantages");
+
+                *traverser.select_mut(&mut self.advantages) = Some(network);
+            }
+
+            let elapsed = start.elapsed();
+            tracing::event!(Level::INFO, iteration, ?elapsed, "Finished iteration");
+        }
+
+        let mut strategy = DecisionNetwork::new(self.config.hidden_size)?;
+        let loss = strategy.fit(self.strategy_samples.samples(), &self.config, rng)?;
+        tracing::event!(Level::DEBUG, loss, "Fit average strategy");
+
+        Ok(strategy)
+    }
+
+    /// The strategy a player follows during the current iteration,
+    /// obtained by regret matching on the predicted advantages.
+    fn current_strategy(
+        &self,
+        player: Player,
+        features: &[f32],
+        decision_count: usize,
+    ) -> EchoResult<Vec<Probability>> {
+        let Some(network) = player.select_ref(&self.advantages) else {
+            return Ok(vec![1.0 / decision_count as Probability; decision_count]);
+        };
+
+        let advantages = network.predict(features, decision_count)?;
+        let total: f32 = advantages.iter().map(|a| a.max(0.0)).sum();
+
+        if total > 0.0 {
+            return Ok(advantages.iter().map(|a| a.max(0.0) / total).collect());
+        }
+
+        // Picks the decision which is the least bad when none look good.
+        let best = advantages
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(0, |(index, _)| index);
+
+        let mut strategy = vec![0.0; decision_count];
+        strategy[best] = 1.0;
+        Ok(strategy)
+    }
+
+    /// External sampling: tries (some of) the decisions of the traverser,
+    /// sampling a single decision for the opponent. Returns the
+    /// expected utility of the traverser.
+    fn traverse<R: Rng>(
+        &mut self,
+        position: &GamePosition,
+        traverser: Player,
+        iteration: usize,
+        rng: &mut R,
+    ) -> EchoResult<Utility> {
+        let opponent = !traverser;
+        let counts = position.decision_counts();
+        let [features, opponent_features] = [traverser, opponent].map(|player| {
+            encode_features(
+                &position.state,
+                &position.phase,
+                player,
+                player.select(position.hidden),
+            )
+        });
+
+        if counts.iter().any(|count| *count > MAX_DECISIONS) {
+            return Err(EchoError::InvalidState(format!(
+                "Cannot handle more than {MAX_DECISIONS} decisions, got {counts:?}"
+            )));
+        }
+
+        // {{{ Opponent decision
+        let opponent_count = opponent.select(counts);
+        let opponent_strategy =
+            self.current_strategy(opponent, &opponent_features, opponent_count)?;
+
+        if opponent_count > 1 {
+            let sample = Sample {
+                features: opponent_features,
+                values: opponent_strategy.iter().copied().map(Some).collect(),
+                weight: iteration as f32,
+            };
+
+            self.strategy_samples.push(sample, rng);
+        }
+
+        let opponent_decision = DecisionIndex(sample(&opponent_strategy, rng));
+        // }}}
+        // {{{ Traverser decisions
+        let count = traverser.select(counts);
+        let strategy = self.current_strategy(traverser, &features, count)?;
+        let width = self
+            .config
+            .exploration_width
+            .unwrap_or(count)
+            .clamp(1, count);
+        let mut values: Vec<Option<Utility>> = vec![None; count];
+
+        for decision in index::sample(rng, count, width).into_vec() {
+            let decisions = traverser.order_as([DecisionIndex(decision), opponent_decision]);
+
+            let value = match position.advance(decisions)?.1 {
+                TurnResult::Finished(score) => {
+                    traverser.select([score.to_utility(), -score.to_utility()])
+                }
+                TurnResult::Unfinished(next) => self.traverse(&next, traverser, iteration, rng)?,
+            };
+
+            values[decision] = Some(value);
+        }
+        // }}}
+        // {{{ Advantages
+        // Decisions which were not tried are left out of the expected value.
+        let (mass, total) = values
+            .iter()
+            .zip(&strategy)
+            .filter_map(|(value, probability)| Some((*probability, value.as_ref()? * probability)))
+            .fold((0.0, 0.0), |(m, t), (p, v)| (m + p, t + v));
+
+        let value = if mass > 0.0 {
+            total / mass
+        } else {
+            let tried = values.iter().flatten();
+            tried.clone().sum::<Utility>() / tried.count() as Utility
+        };
+
+        if count > 1 {
+            let sample = Sample {
+                features,
+                values: values.iter().map(|v| v.map(|v| v - value)).collect(),
+                weight: iteration as f32,
+            };
+
+            traverser
+                .select_mut(&mut self.advantage_samples)
+                .push(sample, rng);
+        }
+        // }}}
+
+        Ok(value)
+    }
+}
+// }}}
+// {{{ Strategy provider
+/// Recommends the strategies approximated by an average strategy network.
+impl StrategyProvider for DecisionNetwork {
+    fn strategy(&mut self, input: &AgentInput) -> Option<Vec<Probability>> {
+        let count = input
+            .player
+            .select(input.phase.decision_counts(&input.state));
+        if count > MAX_DECISIONS {
+            return None;
+        }
+
+        let features = encode_features(&input.state, &input.phase, input.player, input.hidden);
+        let values = match self.predict(&features, count) {
+            Ok(values) => values,
+            Err(error) => {
+                tracing::warn!("Failed to evaluate the strategy network: {error}");
+                return None;
+            }
+        };
+
+        let total: f32 = values.iter().map(|v| v.max(0.0)).sum();
+        if total > 0.0 {
+            Some(values.iter().map(|v| v.max(0.0) / total).collect())
+        } else {
+            Some(vec![1.0 / count as Probability; count])
+        }
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::battlefield::Battlefield;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn features_have_a_fixed_size() {
+        let state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        let phase = MainPhase::new();
+        let hidden = phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+        let mut position = GamePosition::new(state, PerPhase::Main(phase), hidden);
+
+        loop {
+            for player in Player::PLAYERS {
+                let features = encode_features(
+                    &position.state,
+                    &position.phase,
+                    player,
+                    player.select(position.hidden),
+                );
+
+                assert_eq!(features.len(), FEATURE_COUNT);
+                assert!(player.select(position.decision_counts()) <= MAX_DECISIONS);
+            }
+
+            match position.advance([DecisionIndex(0); 2]).unwrap().1 {
+                TurnResult::Finished(_) => break,
+                TurnResult::Unfinished(next) => position = next,
+            }
+        }
+    }
+
+    #[test]
+    fn reservoirs_stay_within_capacity() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut reservoir = Reservoir::new(10);
+
+        for index in 0..100 {
+            let sample = Sample {
+                features: vec![index as f32],
+                values: vec![],
+                weight: 1.0,
+            };
+
+            reservoir.push(sample, &mut rng);
+        }
+
+        assert_eq!(reservoir.samples().len(), 10);
+        assert!(reservoir.samples().iter().any(|s| s.features[0] >= 10.0));
+    }
+
+    #[test]
+    fn deep_cfr_produces_a_strategy() {
+        let state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        let config = DeepCfrConfig {
+            iterations: 1,
+            traversals: 2,
+            exploration_width: Some(1),
+            reservoir_capacity: 100,
+            hidden_size: 8,
+            training_steps: 5,
+            batch_size: 4,
+            ..Default::default()
+        };
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = DeepCfrContext::new(config).train(state, &mut rng).unwrap();
+
+        let hidden = MainPhase::new()
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+        let input = AgentInput::new(
+            PerPhase::Main(MainPhase::new()),
+            state,
+            Player::Me.select(hidden),
+            Player::Me,
+        );
+
+        let strategy = network.strategy(&input).unwrap();
+        let count = Player::Me.select(input.phase.decision_counts(&state));
+        assert_eq!(strategy.len(), count);
+        assert!((strategy.iter().sum::<f32>() - 1.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,472 @@
+//! Splitting training between machines.
+//!
+//! A `Coordinator` waits for a number of workers to connect over TCP, and hands
+//! each of them a disjoint set of subtrees (identified by the reveal indices of
+//! the root). Every worker generates the same tree, then repeatedly runs a number
+//! of chance-sampled iterations which only update the root and the subtrees it
+//! owns (see `TrainingContext::cs_cfr_branches`), before shipping the changes it
+//! made to the weights (the regret and strategy deltas) to the coordinator.
+//!
+//! The coordinator adds up the deltas of every worker, and sends the combined
+//! deltas back, such that every machine starts the next round from the same
+//! weights. Once training is over, the tree of the coordinator holds the result.
+//!
+//! Messages are sent as lines of json. Weights are referred to by their position
+//! in the tree (see `WeightTable`), so trees must have the same shape on every
+//! machine. This rules out lazy expansion, which changes the shape during training.
+use super::decision::{DecisionMatrix, DecisionVector, Scope};
+use super::train::TrainingContext;
+use crate::error::{EchoError, EchoResult};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::Player;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use tracing::Level;
+
+/// Changes made to some weights, as `(index, amount)` pairs.
+/// Indices refer to the weights of a `WeightTable`.
+pub type WeightDeltas = Vec<(usize, f32)>;
+
+// {{{ Weight table
+/// Every decision vector of a tree, in a fixed order, such
+/// that weights can be referred to by index across machines.
+pub struct WeightTable<'a> {
+    vectors: Vec<&'a DecisionVector<'a>>,
+
+    /// The index of the first weight of every vector.
+    offsets: Vec<usize>,
+    len: usize,
+}
+
+impl<'a> WeightTable<'a> {
+    /// Collects the vectors in breadth first order. Matrices shared between
+    /// scopes (see `with_transpositions`) and weights shared between vectors
+    /// (see `with_abstraction`) only get collected once.
+    pub fn new(scope: &Scope<'a>) -> Self {
+        let mut vectors = vec![];
+        let mut offsets = vec![];
+        let mut len = 0;
+
+        let mut seen = HashSet::new();
+        let mut seen_weights = HashSet::new();
+        let mut queue = VecDeque::from([scope]);
+
+        while let Some(scope) = queue.pop_front() {
+            let Some(explored) = scope.get_explored() else {
+                continue;
+            };
+
+            let matrices = explored.matrices;
+            for player in Player::PLAYERS {
+                let DecisionMatrix::Expanded(matrix) = *matrices.get_matrix(player) else {
+                    continue;
+                };
+
+                if !seen.insert(matrix.as_ptr()) {
+                    continue;
+                }
+
+                for vector in matrix {
+                    if !seen_weights.insert(vector.regret_sum.as_ptr()) {
+                        continue;
+                    }
+
+                    vectors.push(vector);
+                    offsets.push(len);
+                    len += 2 * vector.len();
+                }
+            }
+
+            queue.extend(explored.next.iter());
+        }
+
+        Self {
+            vectors,
+            offsets,
+            len,
+        }
+    }
+
+    /// The total number of weights in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copies every weight (the regret sums, followed by the strategy sums of every vector).
+    pub fn snapshot(&self) -> Vec<f32> {
+        let mut result = Vec::with_capacity(self.len);
+
+        for vector in &self.vectors {
+            result.extend(vector.regrets());
+            result.extend(vector.strategy_sums());
+        }
+
+        result
+    }
+
+    /// Computes the changes made to the weights since the given snapshot was taken.
+    pub fn deltas(&self, snapshot: &[f32]) -> WeightDeltas {
+        self.snapshot()
+            .into_iter()
+            .zip(snapshot)
+            .enumerate()
+            .filter(|(_, (current, previous))| current != *previous)
+            .map(|(index, (current, previous))| (index, current - previous))
+            .collect()
+    }
+
+    /// Overwrites every weight with the ones in the given snapshot.
+    pub fn restore(&self, snapshot: &[f32]) {
+        for (vector, offset) in self.vectors.iter().zip(&self.offsets) {
+            let weights = &snapshot[*offset..*offset + 2 * vector.len()];
+            let (regrets, strategy_sums) = weights.split_at(vector.len());
+
+            vector.set_regrets(regrets);
+            vector.set_strategy_sums(strategy_sums);
+            vector.recompute_regret_magnitude();
+        }
+    }
+
+    /// Adds the given deltas to the weights.
+    pub fn apply(&self, deltas: &WeightDeltas) -> EchoResult<()> {
+        // Half precision sums of a vector share an exponent
+        // (see `DecisionVector::regrets`), so vectors get stored as a whole.
+        let mut weights = self.snapshot();
+
+        for (index, amount) in deltas {
+            let Some(weight) = weights.get_mut(*index) else {
+                return Err(EchoError::InvalidState(format!(
+                    "Weight {index} is out of range (the tree only has {} weights)",
+                    self.len
+                )));
+            };
+
+            *weight += amount;
+        }
+
+        self.restore(&weights);
+
+        Ok(())
+    }
+}
+// }}}
+// {{{ Messages
+/// The work handed to a worker once it connects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    /// The state the tree gets generated from.
+    pub state: KnownState,
+
+    /// The reveal indices of the root whose subtrees the worker trains.
+    pub branches: Vec<usize>,
+
+    /// The number of weights in the tree, used to make sure the trees match.
+    pub weights: usize,
+
+    /// How many times to sync with the coordinator.
+    pub rounds: usize,
+
+    /// How many iterations to run between syncs.
+    pub iterations: usize,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum CoordinatorMessage {
+    Assign(Assignment),
+
+    /// The combined deltas of every worker during the last round.
+    Sync(WeightDeltas),
+}
+
+/// A connection sending messages as lines of json.
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> EchoResult<Self> {
+        let writer = stream.try_clone().map_err(network_error)?;
+
+        Ok(Self {
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+
+    fn send<T: Serialize>(&mut self, message: &T) -> EchoResult<()> {
+        let mut line = serde_json::to_vec(message)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).map_err(network_error)
+    }
+
+    fn receive<T: DeserializeOwned>(&mut self) -> EchoResult<T> {
+        let mut line = String::new();
+
+        if self.reader.read_line(&mut line).map_err(network_error)? == 0 {
+            return Err(EchoError::Network(
+                "The connection was closed mid-training".to_string(),
+            ));
+        }
+
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+fn network_error(error: io::Error) -> EchoError {
+    EchoError::Network(error.to_string())
+}
+// }}}
+// {{{ Coordinator
+/// Options for `Coordinator::train`.
+#[derive(Debug, Clone, Copy)]
+pub struct DistributedTraining {
+    /// How many workers to wait for before starting.
+    pub workers: usize,
+
+    /// How many times the workers sync with the coordinator.
+    pub rounds: usize,
+
+    /// How many iterations every worker runs between syncs.
+    pub iterations: usize,
+
+    /// Every worker gets seeded with this plus its index.
+    pub seed: u64,
+}
+
+/// Splits training between the workers connecting to it.
+pub struct Coordinator {
+    listener: TcpListener,
+}
+
+impl Coordinator {
+    pub fn bind(address: impl ToSocketAddrs) -> EchoResult<Self> {
+        let listener = TcpListener::bind(address).map_err(|error| {
+            EchoError::Network(format!("Failed to start the coordinator: {error}"))
+        })?;
+
+        Ok(Self { listener })
+    }
+
+    /// The address the coordinator is listening on. Useful when binding to port 0.
+    pub fn local_addr(&self) -> EchoResult<SocketAddr> {
+        self.listener.local_addr().map_err(|error| {
+            EchoError::Network(format!("Failed to read the coordinator address: {error}"))
+        })
+    }
+
+    /// Waits for every worker to connect, then keeps combining their
+    /// deltas into the given tree (generated from the given state).
+    pub fn train(
+        &self,
+        scope: &Scope,
+        state: KnownState,
+        options: DistributedTraining,
+    ) -> EchoResult<()> {
+        let table = WeightTable::new(scope);
+        let reveals = scope
+            .get_explored()
+            .ok_or_else(|| {
+                EchoError::InvalidState("The root of the tree must be explored".to_string())
+            })?
+            .next
+            .len();
+
+        let mut workers = Vec::with_capacity(options.workers);
+
+        for index in 0..options.workers {
+            let (stream, address) = self.listener.accept().map_err(|error| {
+                EchoError::Network(format!("Failed to accept a connection: {error}"))
+            })?;
+
+            tracing::event!(Level::INFO, "Worker {index} connected from {address}");
+
+            let mut connection = Connection::new(stream)?;
+            connection.send(&CoordinatorMessage::Assign(Assignment {
+                state,
+                branches: (index..reveals).step_by(options.workers).collect(),
+                weights: table.len(),
+                rounds: options.rounds,
+                iterations: options.iterations,
+                seed: options.seed.wrapping_add(index as u64),
+            }))?;
+
+            workers.push(connection);
+        }
+
+        for round in 0..options.rounds {
+            let mut combined = vec![];
+
+            for worker in &mut workers {
+                let deltas: WeightDeltas = worker.receive()?;
+                combined.extend(deltas);
+            }
+
+            table.apply(&combined)?;
+
+            let message = CoordinatorMessage::Sync(combined);
+            for worker in &mut workers {
+                worker.send(&message)?;
+            }
+
+            tracing::event!(Level::INFO, "Finished round {round}");
+        }
+
+        Ok(())
+    }
+}
+// }}}
+// {{{ Worker
+/// Trains some of the subtrees handed out by a coordinator.
+pub struct Worker {
+    connection: Connection,
+    assignment: Assignment,
+}
+
+impl Worker {
+    /// Connects to the coordinator, waiting for it to hand out some work.
+    pub fn connect(address: impl ToSocketAddrs) -> EchoResult<Self> {
+        let stream = TcpStream::connect(address).map_err(network_error)?;
+        let mut connection = Connection::new(stream)?;
+
+        let CoordinatorMessage::Assign(assignment) = connection.receive()? else {
+            return Err(EchoError::Network(
+                "Expected the coordinator to assign some work".to_string(),
+            ));
+        };
+
+        Ok(Self {
+            connection,
+            assignment,
+        })
+    }
+
+    /// The tree passed to `train` must be generated from the assigned state.
+    pub fn assignment(&self) -> &Assignment {
+        &self.assignment
+    }
+
+    /// Runs every round of training, syncing with the coordinator in-between.
+    pub fn train(mut self, trainer: &mut TrainingContext, scope: &mut Scope) -> EchoResult<()> {
+        let table = WeightTable::new(scope);
+        if table.len() != self.assignment.weights {
+            return Err(EchoError::InvalidState(format!(
+                "The tree has {} weights, while the one of the coordinator has {}",
+                table.len(),
+                self.assignment.weights
+            )));
+        }
+
+        let reveals = scope
+            .get_explored()
+            .map_or(0, |explored| explored.next.len());
+        let mut owned = vec![false; reveals];
+        for branch in &self.assignment.branches {
+            if let Some(owned) = owned.get_mut(*branch) {
+                *owned = true;
+            }
+        }
+
+        let state = self.assignment.state.to_summary();
+        let mut rng = StdRng::seed_from_u64(self.assignment.seed);
+
+        for _ in 0..self.assignment.rounds {
+            let snapshot = table.snapshot();
+            trainer.cs_cfr_branches(&mut rng, scope, state, self.assignment.iterations, &owned);
+
+            self.connection.send(&table.deltas(&snapshot))?;
+
+            let CoordinatorMessage::Sync(combined) = self.connection.receive()? else {
+                return Err(EchoError::Network(
+                    "Expected the coordinator to sync the weights".to_string(),
+                ));
+            };
+
+            // Our own deltas are part of the combined ones.
+            table.restore(&snapshot);
+            table.apply(&combined)?;
+        }
+
+        Ok(())
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use bumpalo::Bump;
+
+    #[test]
+    fn workers_end_up_with_the_weights_of_the_coordinator() {
+        let coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+        let address = coordinator.local_addr().unwrap();
+        let options = DistributedTraining {
+            workers: 2,
+            rounds: 3,
+            iterations: 5,
+            seed: 0,
+        };
+
+        let workers: Vec<_> = (0..options.workers)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    let worker = Worker::connect(address).unwrap();
+                    let allocator = Bump::new();
+                    let mut scope =
+                        GenerationContext::new(1, worker.assignment().state, &allocator).generate();
+
+                    worker
+                        .train(&mut TrainingContext::new(false), &mut scope)
+                        .unwrap();
+
+                    WeightTable::new(&scope).snapshot()
+                })
+            })
+            .collect();
+
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let scope = GenerationContext::new(1, state, &allocator).generate();
+        coordinator.train(&scope, state, options).unwrap();
+
+        let expected = WeightTable::new(&scope).snapshot();
+        assert!(expected.iter().any(|weight| *weight != 0.0));
+
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn deltas_can_be_applied_to_other_trees() {
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        let other = GenerationContext::new(1, state, &allocator).generate();
+
+        let table = WeightTable::new(&scope);
+        let snapshot = table.snapshot();
+        TrainingContext::new(false).cfr(&mut scope, state.to_summary(), 3);
+
+        let other_table = WeightTable::new(&other);
+        assert_eq!(other_table.len(), table.len());
+        other_table.apply(&table.deltas(&snapshot)).unwrap();
+        assert_eq!(other_table.snapshot(), table.snapshot());
+
+        assert!(other_table.apply(&vec![(table.len(), 1.0)]).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! Solving the final turn of the game.
+//!
+//! Once a single battlefield is left, the rest of the game is small enough to
+//! be generated in full and solved (almost) exactly. Training keeps going
+//! until the nash gap (see `best_response`) drops below some tolerance, which
+//! certifies the average strategies form an equilibrium up to that tolerance.
+//!
+//! Solutions serve as ground truth for tests, and as leaf values when training
+//! trees which stop right before the final turn (see `EndgameTable`).
+use super::best_response;
+use super::decision::{Probability, Scope, UnexploredScope, Utility};
+use super::decision_index::DecisionIndex;
+use super::generate::GenerationContext;
+use super::hidden_index::{EncodingInfo, HiddenIndex, HiddenState};
+use super::phase::{MainPhase, Phase};
+use super::train::TrainingContext;
+use crate::error::{EchoError, EchoResult};
+use crate::game::creature::CreatureSet;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use crate::game::types::Player;
+use crate::helpers::pair::Pair;
+use bumpalo::Bump;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+// {{{ Solutions
+/// The equilibrium of a final turn, summarized by the value of every deal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndgameSolution {
+    /// Expected utility of the first player for every pair of starting hands,
+    /// assuming both players follow the equilibrium strategies.
+    values: HashMap<Pair<CreatureSet>, Utility>,
+
+    /// Expected utility of the first player, averaged over every deal.
+    pub value: Utility,
+
+    /// The nash gap of the strategies the values were computed from.
+    pub nash_gap: Utility,
+
+    /// How many iterations of cfr it took to get here.
+    pub iterations: usize,
+}
+
+impl EndgameSolution {
+    /// Returns the value of a deal (from the perspective of the first player),
+    /// or `None` if the hands cannot be dealt in the solved state.
+    pub fn deal_value(&self, hands: Pair<CreatureSet>) -> Option<Utility> {
+        self.values.get(&hands).copied()
+    }
+}
+// }}}
+// {{{ Solver
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EndgameSolver {
+    /// Training stops once the nash gap is at most this large.
+    tolerance: Utility,
+
+    /// Every how many iterations to compute the nash gap.
+    check_interval: usize,
+
+    /// Training gives up after this many iterations, even if the
+    /// solution is not within the tolerance yet.
+    max_iterations: usize,
+}
+
+impl Default for EndgameSolver {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TOLERANCE)
+    }
+}
+
+impl EndgameSolver {
+    /// Plain cfr struggles to push the gap much lower in reasonable time.
+    pub const DEFAULT_TOLERANCE: Utility = 0.01;
+
+    pub fn new(tolerance: Utility) -> Self {
+        Self {
+            tolerance,
+            check_interval: 50,
+            max_iterations: 10_000,
+        }
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Solves the final turn starting at the given state.
+    ///
+    /// Fails if the state is not at the start of the final turn. Hitting the
+    /// iteration limit is not an error, so the nash gap of the solution should
+    /// be checked if the tolerance matters.
+    pub fn solve(&self, state: &KnownState) -> EchoResult<EndgameSolution> {
+        if !state.battlefields.is_last() {
+            return Err(EchoError::InvalidState(
+                "Only the final turn can be solved exactly".to_string(),
+            ));
+        }
+
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, *state, &allocator).generate();
+        let trainer = TrainingContext::new(false);
+
+        let mut iterations = 0;
+        let mut nash_gap = best_response::nash_gap(&scope, summary);
+
+        while nash_gap > self.tolerance && iterations < self.max_iterations {
+            for _ in 0..self.check_interval {
+                trainer.cfr_iteration(&mut scope, summary);
+            }
+
+            iterations += self.check_interval;
+            nash_gap = best_response::nash_gap(&scope, summary);
+        }
+
+        let phase = MainPhase::new();
+        let mut values = HashMap::new();
+        let mut total = 0.0;
+
+        for hidden in phase.valid_hidden_states(summary) {
+            let value = deal_value(&scope, phase, summary, hidden);
+            values.insert(hidden.map(EncodingInfo::get_main), value);
+            total += value;
+        }
+
+        Ok(EndgameSolution {
+            value: total / values.len() as Utility,
+            values,
+            nash_gap,
+            iterations,
+        })
+    }
+}
+
+/// Computes the expected utility of the first player for a single deal,
+/// assuming both players follow the average strategies stored in the scope.
+fn deal_value<P: Phase>(
+    scope: &Scope,
+    phase: P,
+    state: KnownStateSummary,
+    hidden: Pair<EncodingInfo>,
+) -> Utility {
+    match scope {
+        Scope::Completed(score) => score.to_utility(),
+        Scope::Unexplored(UnexploredScope {
+            state: Some(state), ..
+        }) => state.score.to_utility(),
+        Scope::Unexplored(_) => unreachable!("Final turns are always generated in full"),
+        Scope::Explored(scope) => {
+            let [mine, yours]: Pair<Vec<Probability>> = Player::PLAYERS.map(|player| {
+                HiddenIndex::encode(&state, player, player.select(hidden))
+                    .ok()
+                    .and_then(|index| scope.strategy_for(player, index))
+                    .expect("Every deal must have a strategy")
+            });
+
+            let mut total = 0.0;
+
+            for (my_decision, my_probability) in mine.iter().enumerate() {
+                for (your_decision, your_probability) in yours.iter().enumerate() {
+                    let probability = my_probability * your_probability;
+                    if probability == 0.0 {
+                        continue;
+                    }
+
+                    let (new_state, new_hidden, reveal_index) = phase
+                        .advance_hidden_indices(
+                            state,
+                            hidden.map(HiddenState::from),
+                            [DecisionIndex(my_decision), DecisionIndex(your_decision)],
+                        )
+                        .unwrap();
+                    let next_phase = phase.advance_phase(&state, reveal_index).unwrap();
+
+                    total += probability
+                        * deal_value::<P::Next>(
+                            &scope.next[reveal_index.0],
+                            next_phase,
+                            new_state,
+                            new_hidden,
+                        );
+                }
+            }
+
+            total
+        }
+    }
+}
+// }}}
+// {{{ Leaf values
+/// Remembers the solution of every final turn training runs into, such that
+/// trees stopping right before the final turn can use exact leaf values.
+#[derive(Debug, Default)]
+pub struct EndgameTable {
+    solver: EndgameSolver,
+    solutions: RefCell<HashMap<KnownState, EndgameSolution>>,
+}
+
+impl EndgameTable {
+    pub fn new(solver: EndgameSolver) -> Self {
+        Self {
+            solver,
+            solutions: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The number of final turns solved so far.
+    pub fn len(&self) -> usize {
+        self.solutions.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solutions.borrow().is_empty()
+    }
+
+    /// Returns the value (from the perspective of the first player) of dealing
+    /// the given hands at the start of the final turn, solving it if needed.
+    ///
+    /// Returns `None` for states which are not at the start of the final turn.
+    pub fn leaf_value(&self, state: &KnownState, hands: Pair<CreatureSet>) -> Option<Utility> {
+        if !state.battlefields.is_last() {
+            return None;
+        }
+
+        if let Some(solution) = self.solutions.borrow().get(state) {
+            return solution.deal_value(hands);
+        }
+
+        let solution = self.solver.solve(state).ok()?;
+        if solution.nash_gap > self.solver.tolerance {
+            tracing::warn!(
+                "Gave up solving a final turn with a nash gap of {}",
+                solution.nash_gap
+            );
+        }
+
+        let value = solution.deal_value(hands);
+        self.solutions.borrow_mut().insert(*state, solution);
+
+        value
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::game::battlefield::Battlefields;
+    use crate::helpers::bitfield::Bitfield;
+
+    #[test]
+    fn final_turns_get_solved_within_the_tolerance() {
+        let state = last_turn_state();
+        let solution = EndgameSolver::default().solve(&state).unwrap();
+
+        assert!(solution.nash_gap <= EndgameSolver::DEFAULT_TOLERANCE);
+        assert!(solution.iterations > 0);
+
+        let hands = MainPhase::new()
+            .valid_hidden_states(state.to_summary())
+            .map(|hidden| hidden.map(EncodingInfo::get_main))
+            .collect::<Vec<_>>();
+
+        let average: Utility = hands
+            .iter()
+            .map(|hands| solution.deal_value(*hands).unwrap())
+            .sum::<Utility>()
+            / hands.len() as Utility;
+
+        assert!((average - solution.value).abs() < 1e-4);
+        assert_eq!(solution.deal_value([CreatureSet::empty(); 2]), None);
+    }
+
+    #[test]
+    fn leaf_values_only_exist_for_final_turns() {
+        let state = last_turn_state();
+        let table = EndgameTable::default();
+
+        let mut earlier = state;
+        earlier.battlefields.current = Battlefields::COUNT - 2;
+        assert_eq!(table.leaf_value(&earlier, [CreatureSet::empty(); 2]), None);
+        assert!(table.is_empty());
+
+        let hands = MainPhase::new()
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap()
+            .map(EncodingInfo::get_main);
+
+        let value = table.leaf_value(&state, hands).unwrap();
+        assert_eq!(table.leaf_value(&state, hands), Some(value));
+        assert_eq!(table.len(), 1);
+    }
+}
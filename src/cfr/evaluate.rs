@@ -0,0 +1,349 @@
+//! Exact expected values for a pair of fixed strategies.
+//!
+//! Comparing agents by playing sampled matches requires lots of games before
+//! the noise averages out. When both agents follow a strategy which does not
+//! change during the game, we can instead walk the tree once, weighing every
+//! outcome by the probability of reaching it.
+use super::blueprint::{BlockId, BlueprintReader, PublicStrategy};
+use super::decision::{ExploredScope, Probability, Scope, UnexploredScope, Utility};
+use super::decision_index::DecisionIndex;
+use super::hidden_index::{EncodingInfo, HiddenIndex, HiddenState};
+use super::phase::{MainPhase, Phase};
+use super::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::known_state_summary::KnownStateSummary;
+use crate::game::types::Player;
+use crate::helpers::pair::Pair;
+use std::io::{self, Read, Seek};
+
+/// Hidden information of both players, together with the
+/// probability of dealing it and of playing the way they did.
+type Deal = (Pair<EncodingInfo>, Probability);
+
+/// Looks up the strategy of a player for some hidden information.
+/// Receives the number of decisions the player can take.
+pub type CustomStrategy<'a> =
+    &'a dyn Fn(&KnownStateSummary, Player, EncodingInfo, usize) -> Option<Vec<Probability>>;
+
+// {{{ Frozen strategies
+/// Object safe access to the blocks of a blueprint, such
+/// that strategies are not generic over the underlying reader.
+pub trait BlockSource {
+    fn load_block(&mut self, id: BlockId) -> io::Result<PublicStrategy>;
+}
+
+impl<R: Read + Seek> BlockSource for BlueprintReader<R> {
+    #[inline(always)]
+    fn load_block(&mut self, id: BlockId) -> io::Result<PublicStrategy> {
+        self.load(id)
+    }
+}
+
+/// A strategy which stays the same for the whole evaluation.
+pub enum FrozenStrategy<'a> {
+    /// The average strategy learned by the tree being walked.
+    Trained,
+    /// Takes every decision with the same probability.
+    Uniform,
+    /// The strategy stored inside a blueprint of the same tree.
+    Blueprint(&'a mut dyn BlockSource),
+    /// Any other strategy.
+    Custom(CustomStrategy<'a>),
+}
+
+/// Where in the strategy of a player we currently are.
+pub(crate) enum Cursor {
+    /// The strategy does not depend on the public state.
+    Stateless,
+    /// The blueprint block holding the current public state.
+    Block(PublicStrategy),
+    /// The blueprint does not reach the current public state.
+    Missing,
+}
+
+impl<'a> FrozenStrategy<'a> {
+    pub(crate) fn root(&mut self) -> EchoResult<Cursor> {
+        match self {
+            Self::Blueprint(source) => Self::load(*source, Some(BlockId::ROOT)),
+            _ => Ok(Cursor::Stateless),
+        }
+    }
+
+    pub(crate) fn descend(
+        &mut self,
+        cursor: &Cursor,
+        reveal_index: RevealIndex,
+    ) -> EchoResult<Cursor> {
+        match (self, cursor) {
+            (Self::Blueprint(source), Cursor::Block(block)) => {
+                Self::load(*source, block.next(reveal_index))
+            }
+            _ => Ok(Cursor::Stateless),
+        }
+    }
+
+    fn load(source: &mut dyn BlockSource, id: Option<BlockId>) -> EchoResult<Cursor> {
+        let Some(id) = id else {
+            return Ok(Cursor::Missing);
+        };
+
+        source.load_block(id).map(Cursor::Block).map_err(|error| {
+            EchoError::InvalidState(format!("Failed to load blueprint block {}: {error}", id.0))
+        })
+    }
+
+    pub(crate) fn strategy(
+        &self,
+        cursor: &Cursor,
+        scope: &ExploredScope,
+        state: &KnownStateSummary,
+        player: Player,
+        hidden: EncodingInfo,
+    ) -> EchoResult<Vec<Probability>> {
+        let decision_count = player.select(scope.matrices.decision_counts());
+        let index = || HiddenIndex::encode(state, player, hidden);
+
+        let strategy = match (self, cursor) {
+            (Self::Trained, _) => scope.strategy_for(player, index()?),
+            (Self::Uniform, _) => Some(vec![1.0 / decision_count as Probability; decision_count]),
+            (Self::Blueprint(_), Cursor::Block(block)) => {
+                let matrix = block.get_matrix(player);
+                match matrix.get(index()?) {
+                    Some(strategy) => Some(strategy.to_vec()),
+                    None if matrix.decision_count() == 1 => Some(vec![1.0]),
+                    None => None,
+                }
+            }
+            (Self::Blueprint(_), _) => {
+                return Err(EchoError::InvalidState(
+                    "The blueprint does not reach every public state of the tree".to_string(),
+                ))
+            }
+            (Self::Custom(strategy), _) => strategy(state, player, hidden, decision_count),
+        };
+
+        strategy
+            .filter(|strategy| strategy.len() == decision_count)
+            .ok_or_else(|| {
+                EchoError::InvalidState(format!(
+                    "No strategy with {decision_count} decisions for {player:?}"
+                ))
+            })
+    }
+}
+// }}}
+// {{{ Evaluation
+/// Computes the expected utility of both players when each one sticks to the
+/// given strategy, starting from the main phase with every deal equally likely.
+///
+/// Public states left unexplored count as if the game ended right away
+/// (see `best_response`). Fails if some public state reached with a non-zero
+/// probability has not been generated, or if some strategy is missing.
+pub fn expected_values(
+    scope: &Scope,
+    state: KnownStateSummary,
+    mut strategies: Pair<FrozenStrategy>,
+) -> EchoResult<Pair<Utility>> {
+    let phase = MainPhase::new();
+    let hidden_states: Vec<_> = phase.valid_hidden_states(state).collect();
+    let probability = 1.0 / hidden_states.len() as Probability;
+    let deals: Vec<Deal> = hidden_states
+        .into_iter()
+        .map(|hidden| (hidden, probability))
+        .collect();
+
+    let [mine, yours] = &mut strategies;
+    let cursors = [mine.root()?, yours.root()?];
+    let value = evaluate(scope, phase, state, &mut strategies, &cursors, &deals)?;
+
+    Ok([value, -value])
+}
+
+/// Sums the utilities (from the perspective of the first player)
+/// of the given deals, weighted by their probabilities.
+fn evaluate<P: Phase>(
+    scope: &Scope,
+    phase: P,
+    state: KnownStateSummary,
+    strategies: &mut Pair<FrozenStrategy>,
+    cursors: &Pair<Cursor>,
+    deals: &[Deal],
+) -> EchoResult<Utility> {
+    let total_probability: Probability = deals.iter().map(|(_, probability)| probability).sum();
+
+    match scope {
+        Scope::Completed(score) => Ok(total_probability * score.to_utility()),
+        Scope::Unexplored(UnexploredScope {
+            state: Some(state), ..
+        }) => Ok(total_probability * state.score.to_utility()),
+        Scope::Unexplored(_) => Err(EchoError::InvalidState(
+            "Cannot evaluate strategies on scopes which have not been generated".to_string(),
+        )),
+        Scope::Explored(scope) => {
+            // Deals get split between the scopes they lead to.
+            let mut children: Vec<(RevealIndex, KnownStateSummary, Vec<Deal>)> = vec![];
+
+            for (hidden, probability) in deals {
+                let [mine, yours] = Player::PLAYERS.map(|player| {
+                    player.select_ref(strategies).strategy(
+                        player.select_ref(cursors),
+                        scope,
+                        &state,
+                        player,
+                        player.select(*hidden),
+                    )
+                });
+                let (mine, yours) = (mine?, yours?);
+
+                for (my_decision, my_probability) in mine.iter().enumerate() {
+                    for (your_decision, your_probability) in yours.iter().enumerate() {
+                        let probability = probability * my_probability * your_probability;
+                        if probability == 0.0 {
+                            continue;
+                        }
+
+                        let (new_state, new_hidden, reveal_index) = phase
+                            .advance_hidden_indices(
+                                state,
+                                hidden.map(HiddenState::from),
+                                [DecisionIndex(my_decision), DecisionIndex(your_decision)],
+                            )
+                            .unwrap();
+
+                        let deal = (new_hidden, probability);
+
+                        match children.iter_mut().find(|child| child.0 == reveal_index) {
+                            Some((_, _, deals)) => deals.push(deal),
+                            None => children.push((reveal_index, new_state, vec![deal])),
+                        }
+                    }
+                }
+            }
+
+            let mut total = 0.0;
+
+            for (reveal_index, new_state, deals) in children {
+                let next_phase = phase.advance_phase(&state, reveal_index).unwrap();
+                let [mine, yours] = strategies;
+                let cursors = [
+                    mine.descend(&cursors[0], reveal_index)?,
+                    yours.descend(&cursors[1], reveal_index)?,
+                ];
+
+                total += evaluate::<P::Next>(
+                    &scope.next[reveal_index.0],
+                    next_phase,
+                    new_state,
+                    strategies,
+                    &cursors,
+                    &deals,
+                )?;
+            }
+
+            Ok(total)
+        }
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::blueprint::write_blueprint;
+    use crate::cfr::endgame::EndgameSolver;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::train::TrainingContext;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use bumpalo::Bump;
+    use std::io::Cursor as IoCursor;
+
+    #[test]
+    fn strategies_can_be_evaluated_exactly() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+
+        // Both players are in the same situation, so uniform play is a draw.
+        let [mine, yours] = expected_values(
+            &scope,
+            summary,
+            [FrozenStrategy::Uniform, FrozenStrategy::Uniform],
+        )
+        .unwrap();
+        assert!(mine.abs() < 1e-4);
+        assert_eq!(mine, -yours);
+
+        TrainingContext::new(false).cfr(&mut scope, summary, 100);
+
+        let mut file = vec![];
+        write_blueprint(&scope, &state, 0, &mut file).unwrap();
+        let mut blueprint = BlueprintReader::new(IoCursor::new(file)).unwrap();
+
+        // Blueprints store the trained strategies.
+        let [trained, _] = expected_values(
+            &scope,
+            summary,
+            [FrozenStrategy::Trained, FrozenStrategy::Uniform],
+        )
+        .unwrap();
+        let [loaded, _] = expected_values(
+            &scope,
+            summary,
+            [
+                FrozenStrategy::Blueprint(&mut blueprint),
+                FrozenStrategy::Uniform,
+            ],
+        )
+        .unwrap();
+        assert!((trained - loaded).abs() < 1e-4);
+
+        // The trained strategy should not lose against uniform play.
+        assert!(trained > -1e-4);
+
+        // Custom strategies can replicate the uniform one.
+        let uniform: CustomStrategy =
+            &|_, _, _, count| Some(vec![1.0 / count as Probability; count]);
+        let [custom, _] = expected_values(
+            &scope,
+            summary,
+            [FrozenStrategy::Custom(uniform), FrozenStrategy::Trained],
+        )
+        .unwrap();
+        let [expected, _] = expected_values(
+            &scope,
+            summary,
+            [FrozenStrategy::Uniform, FrozenStrategy::Trained],
+        )
+        .unwrap();
+        assert!((custom - expected).abs() < 1e-4);
+
+        // Strategies of the wrong size get rejected.
+        let broken: CustomStrategy = &|_, _, _, _| Some(vec![1.0]);
+        assert!(expected_values(
+            &scope,
+            summary,
+            [FrozenStrategy::Custom(broken), FrozenStrategy::Trained],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn equilibria_are_worth_the_solved_value() {
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, state.to_summary(), 200);
+
+        let [value, _] = expected_values(
+            &scope,
+            state.to_summary(),
+            [FrozenStrategy::Trained, FrozenStrategy::Trained],
+        )
+        .unwrap();
+
+        let solution = EndgameSolver::new(0.05).solve(&state).unwrap();
+        assert!((value - solution.value).abs() < 0.05);
+    }
+}
@@ -0,0 +1,322 @@
+//! Sampled exploitability estimates using local best responses.
+//!
+//! Computing the nash gap (see `best_response`) walks the entire tree, which
+//! gets too slow to do after every couple of iterations once trees grow large.
+//! A local best response instead plays a number of sampled games against the
+//! average strategy of the opponent. Like `BestResponseAgent`, it keeps track of
+//! the deals consistent with everything it has seen so far. At every decision,
+//! it picks the one with the highest value assuming both players follow their
+//! average strategies afterwards, where values are estimated using rollouts.
+//!
+//! Local best responses only look a single decision ahead, so (up to sampling
+//! noise) the estimates are lower bounds on the nash gap. They are reported
+//! together with the half width of a 95% confidence interval.
+use super::best_response::{decision_children, utility_for, Deal};
+use super::decision::{ExploredScope, Scope, UnexploredScope, Utility};
+use super::decision_index::DecisionIndex;
+use super::evaluate::{Cursor, FrozenStrategy};
+use super::hidden_index::{EncodingInfo, HiddenIndex, HiddenState};
+use super::phase::{MainPhase, Phase};
+use crate::game::known_state_summary::KnownStateSummary;
+use crate::game::types::Player;
+use crate::helpers::pair::Pair;
+use crate::helpers::sampling::sample;
+use rand::Rng;
+
+/// Controls how much effort goes into an estimate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalBestResponse {
+    /// Number of games played by each player.
+    pub games: usize,
+
+    /// Number of rollouts used to estimate the value of every decision.
+    pub rollouts: usize,
+}
+
+impl Default for LocalBestResponse {
+    fn default() -> Self {
+        Self {
+            games: 100,
+            rollouts: 8,
+        }
+    }
+}
+
+/// The sum over both players of the average utility of the local best responses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExploitabilityEstimate {
+    pub value: Utility,
+
+    /// Half the width of the 95% confidence interval around the value.
+    pub margin: Utility,
+}
+
+/// Plays games using local best responses for both players,
+/// starting from the main phase with every deal equally likely.
+pub fn estimate_exploitability<R: Rng>(
+    scope: &Scope,
+    state: KnownStateSummary,
+    options: LocalBestResponse,
+    rng: &mut R,
+) -> ExploitabilityEstimate {
+    assert!(options.games > 0, "Estimates require at least one game");
+
+    let phase = MainPhase::new();
+    let deals: Vec<_> = phase.valid_hidden_states(state).collect();
+
+    let mut value = 0.0;
+    let mut variance = 0.0;
+
+    for player in Player::PLAYERS {
+        let mut game = Game {
+            player,
+            rollouts: options.rollouts.max(1),
+            rng: &mut *rng,
+        };
+
+        let utilities: Vec<_> = (0..options.games)
+            .map(|_| {
+                let hidden = deals[game.rng.gen_range(0..deals.len())];
+                let beliefs = deals
+                    .iter()
+                    .filter(|deal| player.select(**deal) == player.select(hidden))
+                    .map(|deal| (*deal, 1.0))
+                    .collect();
+
+                game.play(scope, phase, state, hidden, beliefs)
+            })
+            .collect();
+
+        let count = utilities.len() as Utility;
+        let mean = utilities.iter().sum::<Utility>() / count;
+        let sample_variance = utilities
+            .iter()
+            .map(|utility| (utility - mean).powi(2))
+            .sum::<Utility>()
+            / (count - 1.0).max(1.0);
+
+        value += mean;
+        variance += sample_variance / count;
+    }
+
+    ExploitabilityEstimate {
+        value,
+        margin: 1.96 * variance.sqrt(),
+    }
+}
+
+/// A single game played by a local best response.
+struct Game<'a, R> {
+    /// The player using the local best response.
+    player: Player,
+    rollouts: usize,
+    rng: &'a mut R,
+}
+
+impl<'a, R: Rng> Game<'a, R> {
+    /// Plays the rest of the game, returning the utility of the player.
+    fn play<P: Phase>(
+        &mut self,
+        scope: &Scope,
+        phase: P,
+        state: KnownStateSummary,
+        hidden: Pair<EncodingInfo>,
+        beliefs: Vec<Deal>,
+    ) -> Utility {
+        let scope = match scope {
+            Scope::Explored(scope) => scope,
+            _ => return self.leaf_value(scope),
+        };
+
+        let player = self.player;
+        let decisions = player.order_as([
+            self.decide(scope, phase, state, &beliefs),
+            self.sample_decision(scope, &state, !player, (!player).select(hidden)),
+        ]);
+
+        let (new_state, new_hidden, reveal_index) = phase
+            .advance_hidden_indices(state, hidden.map(HiddenState::from), decisions)
+            .unwrap();
+
+        // The actual deal always stays consistent with what got revealed.
+        let beliefs = decision_children(
+            scope,
+            phase,
+            state,
+            player,
+            player.select(decisions),
+            &FrozenStrategy::Trained,
+            &Cursor::Stateless,
+            &beliefs,
+        )
+        .expect("The trained strategy covers every deal")
+        .into_iter()
+        .find(|(index, _, _)| *index == reveal_index)
+        .map_or(vec![], |(_, _, deals)| deals);
+
+        let next_phase = phase.advance_phase(&state, reveal_index).unwrap();
+        self.play::<P::Next>(
+            &scope.next[reveal_index.0],
+            next_phase,
+            new_state,
+            new_hidden,
+            beliefs,
+        )
+    }
+
+    /// Picks the decision with the highest estimated value. Every decision gets
+    /// evaluated using the same sampled deals (and opponent decisions), which
+    /// makes comparing them less noisy.
+    fn decide<P: Phase>(
+        &mut self,
+        scope: &ExploredScope,
+        phase: P,
+        state: KnownStateSummary,
+        beliefs: &[Deal],
+    ) -> DecisionIndex {
+        let player = self.player;
+        let decision_count = player.select(scope.matrices.decision_counts());
+
+        if decision_count == 1 || beliefs.is_empty() {
+            return DecisionIndex::default();
+        }
+
+        let weights: Vec<_> = beliefs
+            .iter()
+            .map(|(_, probability)| *probability)
+            .collect();
+        let samples: Vec<_> = (0..self.rollouts)
+            .map(|_| {
+                let hidden = beliefs[sample(&weights, self.rng)].0;
+                let decision =
+                    self.sample_decision(scope, &state, !player, (!player).select(hidden));
+
+                (hidden, decision)
+            })
+            .collect();
+
+        let mut best = (DecisionIndex::default(), Utility::NEG_INFINITY);
+
+        for decision in 0..decision_count {
+            let mut value = 0.0;
+
+            for (hidden, opponent_decision) in &samples {
+                let decisions = player.order_as([DecisionIndex(decision), *opponent_decision]);
+                let (new_state, new_hidden, reveal_index) = phase
+                    .advance_hidden_indices(state, hidden.map(HiddenState::from), decisions)
+                    .unwrap();
+
+                let next_phase = phase.advance_phase(&state, reveal_index).unwrap();
+                value += self.rollout::<P::Next>(
+                    &scope.next[reveal_index.0],
+                    next_phase,
+                    new_state,
+                    new_hidden,
+                );
+            }
+
+            if value > best.1 {
+                best = (DecisionIndex(decision), value);
+            }
+        }
+
+        best.0
+    }
+
+    /// Plays the rest of the game using the average strategies of both players.
+    fn rollout<P: Phase>(
+        &mut self,
+        scope: &Scope,
+        phase: P,
+        state: KnownStateSummary,
+        hidden: Pair<EncodingInfo>,
+    ) -> Utility {
+        let scope = match scope {
+            Scope::Explored(scope) => scope,
+            _ => return self.leaf_value(scope),
+        };
+
+        let decisions = Player::PLAYERS
+            .map(|player| self.sample_decision(scope, &state, player, player.select(hidden)));
+
+        let (new_state, new_hidden, reveal_index) = phase
+            .advance_hidden_indices(state, hidden.map(HiddenState::from), decisions)
+            .unwrap();
+
+        let next_phase = phase.advance_phase(&state, reveal_index).unwrap();
+        self.rollout::<P::Next>(
+            &scope.next[reveal_index.0],
+            next_phase,
+            new_state,
+            new_hidden,
+        )
+    }
+
+    /// Samples a decision from the average strategy of some player.
+    fn sample_decision(
+        &mut self,
+        scope: &ExploredScope,
+        state: &KnownStateSummary,
+        player: Player,
+        hidden: EncodingInfo,
+    ) -> DecisionIndex {
+        let strategy = HiddenIndex::encode(state, player, hidden)
+            .ok()
+            .and_then(|index| scope.strategy_for(player, index))
+            .expect("The trained strategy covers every hidden index");
+
+        DecisionIndex(sample(&strategy, self.rng))
+    }
+
+    /// Values scopes the same way `best_response` does.
+    fn leaf_value(&self, scope: &Scope) -> Utility {
+        match scope {
+            Scope::Completed(score) => utility_for(self.player, *score),
+            Scope::Unexplored(UnexploredScope {
+                state: Some(state), ..
+            }) => utility_for(self.player, state.score),
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::best_response::nash_gap;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::train::TrainingContext;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use bumpalo::Bump;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn estimates_shrink_during_training_without_exceeding_the_nash_gap() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        let mut rng = StdRng::seed_from_u64(0);
+        let options = LocalBestResponse {
+            games: 400,
+            rollouts: 16,
+        };
+
+        let untrained = estimate_exploitability(&scope, summary, options, &mut rng);
+        TrainingContext::new(false).cfr(&mut scope, summary, 100);
+        let trained = estimate_exploitability(&scope, summary, options, &mut rng);
+
+        assert!(
+            untrained.value - untrained.margin > trained.value + trained.margin,
+            "Expected {untrained:?} to be larger than {trained:?}"
+        );
+
+        let gap = nash_gap(&scope, summary);
+        assert!(
+            trained.value - trained.margin <= gap,
+            "The estimate {trained:?} exceeds the nash gap {gap}"
+        );
+    }
+}
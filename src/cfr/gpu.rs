@@ -0,0 +1,652 @@
+//! Regret matching and strategy sum accumulation on the gpu.
+//!
+//! Both operations are independent for every decision vector, which makes them
+//! a great fit for compute shaders once enough vectors get processed at once.
+//! Vectors get collected into a `StrategyBatch` (for instance, every matrix of
+//! a public state), which then gets handed to some `StrategyBackend`. The same
+//! goes for computing the regrets of every deal reaching a public state (see
+//! `RegretBatch`). Vectorized training performs both through the backend of
+//! the training context (see `TrainingContext::with_backend`).
+//!
+//! The cpu backend performs the exact same computations, and is used whenever
+//! no gpu is available. The gpu backend requires the gpu feature.
+use super::decision::{DecisionMatrix, DecisionVector, Probability, Utility};
+use crate::error::EchoResult;
+use crate::helpers::pair::Pair;
+
+#[cfg(feature = "gpu")]
+use crate::error::EchoError;
+#[cfg(feature = "gpu")]
+use wgpu::util::DeviceExt;
+
+// {{{ Batches
+/// Rows of decision weights (of possibly different lengths), laid out contiguously.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrategyBatch {
+    regrets: Vec<f32>,
+    strategy_sums: Vec<f32>,
+
+    /// The index the weights of each row start at, followed by the total length.
+    offsets: Vec<u32>,
+
+    /// The probability the strategy of each row gets weighted by.
+    reach: Vec<Probability>,
+}
+
+impl StrategyBatch {
+    pub fn new() -> Self {
+        Self {
+            offsets: vec![0],
+            ..Default::default()
+        }
+    }
+
+    #[inline(always)]
+    pub fn rows(&self) -> usize {
+        self.reach.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.rows() == 0
+    }
+
+    /// The range of weights some row takes up.
+    #[inline(always)]
+    pub fn range(&self, row: usize) -> std::ops::Range<usize> {
+        self.offsets[row] as usize..self.offsets[row + 1] as usize
+    }
+
+    /// The strategy sum of some row.
+    #[inline(always)]
+    pub fn strategy_sum(&self, row: usize) -> &[f32] {
+        &self.strategy_sums[self.range(row)]
+    }
+
+    pub fn push_row(&mut self, regrets: &[f32], strategy_sum: &[f32], reach: Probability) {
+        assert_eq!(regrets.len(), strategy_sum.len());
+
+        self.regrets.extend_from_slice(regrets);
+        self.strategy_sums.extend_from_slice(strategy_sum);
+        self.offsets.push(self.regrets.len() as u32);
+        self.reach.push(reach);
+    }
+
+    /// Pushes a row whose strategy sum starts out empty, such that it ends up
+    /// holding the amount the actual strategy sum should be increased by.
+    pub fn push_regrets(&mut self, regrets: &[f32], reach: Probability) {
+        self.push_row(regrets, &vec![0.0; regrets.len()], reach);
+    }
+
+    pub fn push_vector(&mut self, vector: &DecisionVector, reach: Probability) {
+        self.push_row(&vector.regrets(), &vector.strategy_sums(), reach);
+    }
+
+    /// Pushes every vector of a matrix, using the reach probability of the
+    /// respective hidden index. Trivial matrices have no weights to update.
+    pub fn push_matrix(&mut self, matrix: &DecisionMatrix, reach: &[Probability]) {
+        if let DecisionMatrix::Expanded(vectors) = matrix {
+            for (vector, reach) in vectors.iter().zip(reach) {
+                self.push_vector(vector, *reach);
+            }
+        }
+    }
+
+    /// Writes the strategy sums back into the vectors pushed to the batch
+    /// (in the order they got pushed in).
+    pub fn write_back<'a, 'b: 'a>(
+        &self,
+        vectors: impl IntoIterator<Item = &'a DecisionVector<'b>>,
+    ) {
+        for (row, vector) in vectors.into_iter().enumerate() {
+            vector.set_strategy_sums(self.strategy_sum(row));
+        }
+    }
+}
+
+/// Accumulates the current strategies of every vector of a matrix
+/// (weighted by the reach probability of the respective hidden index).
+/// Equivalent to calling `DecisionVector::update_strategy_sum` on every vector.
+pub fn accumulate_matrix(
+    backend: &mut dyn StrategyBackend,
+    matrix: &DecisionMatrix,
+    reach: &[Probability],
+) -> EchoResult<()> {
+    let DecisionMatrix::Expanded(vectors) = matrix else {
+        return Ok(());
+    };
+
+    let mut batch = StrategyBatch::new();
+    batch.push_matrix(matrix, reach);
+    backend.accumulate(&mut batch)?;
+    batch.write_back(vectors.iter());
+
+    Ok(())
+}
+
+/// The matrix games played by a batch of deals reaching the same public state
+/// (see `TrainingContext::vectorized_cfr`). Every array is indexed by the deal first.
+#[derive(Debug, Clone, Copy)]
+pub struct RegretBatch<'a> {
+    /// The number of decisions of each player.
+    pub counts: Pair<usize>,
+
+    /// The utility (for the first player) of every deal, for every pair of decisions.
+    pub values: &'a [Utility],
+
+    /// The current strategy of each player, for every deal.
+    pub strategies: Pair<&'a [Probability]>,
+
+    /// The reach probability of each player, for every deal.
+    pub reach: Pair<&'a [Probability]>,
+}
+
+impl<'a> RegretBatch<'a> {
+    #[inline(always)]
+    pub fn deals(&self) -> usize {
+        self.reach[0].len()
+    }
+}
+
+/// The results of `StrategyBackend::regrets`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchRegrets {
+    /// The expected utility (for the first player) of every deal.
+    pub utilities: Vec<Utility>,
+
+    /// The regret of each player for every deal and decision,
+    /// weighted by the reach probability of the other player.
+    pub regrets: Pair<Vec<Utility>>,
+}
+// }}}
+// {{{ Backends
+pub trait StrategyBackend {
+    /// Performs regret matching on every row of the batch, adding the resulting
+    /// strategies (weighted by the reach probability of the row) to the strategy
+    /// sums. Returns the strategies, laid out the same way the weights are.
+    fn accumulate(&mut self, batch: &mut StrategyBatch) -> EchoResult<Vec<Probability>>;
+
+    /// Computes the utility of every deal of the batch,
+    /// together with the regrets of both players.
+    fn regrets(&mut self, batch: &RegretBatch) -> EchoResult<BatchRegrets>;
+}
+
+/// Performs the updates one row at a time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuBackend;
+
+impl StrategyBackend for CpuBackend {
+    fn accumulate(&mut self, batch: &mut StrategyBatch) -> EchoResult<Vec<Probability>> {
+        let mut strategies = vec![0.0; batch.regrets.len()];
+
+        for row in 0..batch.rows() {
+            let range = batch.range(row);
+            let regrets = &batch.regrets[range.clone()];
+            let total: f32 = regrets.iter().map(|regret| regret.max(0.0)).sum();
+
+            for index in range {
+                let strategy = if total > 0.0 {
+                    batch.regrets[index].max(0.0) / total
+                } else {
+                    1.0 / batch.range(row).len() as Probability
+                };
+
+                strategies[index] = strategy;
+                batch.strategy_sums[index] += batch.reach[row] * strategy;
+            }
+        }
+
+        Ok(strategies)
+    }
+
+    fn regrets(&mut self, batch: &RegretBatch) -> EchoResult<BatchRegrets> {
+        let [my_count, your_count] = batch.counts;
+        let mut result = BatchRegrets {
+            utilities: vec![0.0; batch.deals()],
+            regrets: batch.counts.map(|count| vec![0.0; batch.deals() * count]),
+        };
+
+        for deal in 0..batch.deals() {
+            let my_strategy = &batch.strategies[0][deal * my_count..(deal + 1) * my_count];
+            let your_strategy = &batch.strategies[1][deal * your_count..(deal + 1) * your_count];
+            let values = &batch.values[deal * my_count * your_count..][..my_count * your_count];
+            let [my_values, your_values] = &mut result.regrets;
+            let my_values = &mut my_values[deal * my_count..(deal + 1) * my_count];
+            let your_values = &mut your_values[deal * your_count..(deal + 1) * your_count];
+
+            for (my_index, row) in values.chunks_exact(your_count).enumerate() {
+                for (your_index, value) in row.iter().enumerate() {
+                    my_values[my_index] += your_strategy[your_index] * value;
+                    your_values[your_index] += my_strategy[my_index] * value;
+                }
+            }
+
+            let utility: Utility = my_strategy
+                .iter()
+                .zip(my_values.iter())
+                .map(|(probability, value)| probability * value)
+                .sum();
+            result.utilities[deal] = utility;
+
+            // The utility of the second player is the opposite of ours
+            for value in my_values {
+                *value = batch.reach[1][deal] * (*value - utility);
+            }
+
+            for value in your_values {
+                *value = batch.reach[0][deal] * (utility - *value);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Performs the updates using compute shaders, with one invocation per row (or deal).
+#[cfg(feature = "gpu")]
+pub struct GpuBackend {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    strategy_pipeline: wgpu::ComputePipeline,
+    regret_pipeline: wgpu::ComputePipeline,
+}
+
+#[cfg(feature = "gpu")]
+impl GpuBackend {
+    const STRATEGY_SHADER: &'static str = include_str!("gpu.wgsl");
+    const REGRET_SHADER: &'static str = include_str!("gpu_regrets.wgsl");
+    const WORKGROUP_SIZE: usize = 64;
+
+    /// Invocations handled by a single dispatch (the maximum number of workgroups is limited).
+    const INVOCATIONS_PER_DISPATCH: usize = 65535 * Self::WORKGROUP_SIZE;
+
+    /// Fails if no gpu is available.
+    pub fn new() -> EchoResult<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter =
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))
+                .ok_or_else(|| EchoError::Gpu("No adapter available".to_string()))?;
+
+        let (device, queue) =
+            pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+                .map_err(|error| EchoError::Gpu(error.to_string()))?;
+
+        let pipeline = |label, shader: &str| {
+            let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu::ShaderSource::Wgsl(shader.into()),
+            });
+
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: None,
+                module: &module,
+                entry_point: "main",
+            })
+        };
+
+        let strategy_pipeline = pipeline("Regret matching", Self::STRATEGY_SHADER);
+        let regret_pipeline = pipeline("Regret computation", Self::REGRET_SHADER);
+
+        Ok(Self {
+            device,
+            queue,
+            strategy_pipeline,
+            regret_pipeline,
+        })
+    }
+
+    fn storage_buffer(&self, label: &str, contents: &[u8]) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            })
+    }
+
+    /// Runs a pipeline once for every one of `invocations` items. The shader
+    /// receives the first item handled by the current dispatch, the total
+    /// number of items, and the two given parameters as a uniform.
+    fn dispatch(
+        &self,
+        label: &str,
+        pipeline: &wgpu::ComputePipeline,
+        buffers: &[&wgpu::Buffer],
+        invocations: usize,
+        params: [u32; 2],
+    ) {
+        let uniform = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Params"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = pipeline.get_bind_group_layout(0);
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(label),
+            layout: &layout,
+            entries: &buffers
+                .iter()
+                .chain([&&uniform])
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect::<Vec<_>>(),
+        });
+
+        for first in (0..invocations).step_by(Self::INVOCATIONS_PER_DISPATCH) {
+            let count = (invocations - first).min(Self::INVOCATIONS_PER_DISPATCH);
+            let contents = [first as u32, invocations as u32, params[0], params[1]];
+            self.queue
+                .write_buffer(&uniform, 0, bytemuck::cast_slice(&contents));
+
+            let mut encoder = self.device.create_command_encoder(&Default::default());
+
+            {
+                let mut pass =
+                    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some(label) });
+
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(count.div_ceil(Self::WORKGROUP_SIZE) as u32, 1, 1);
+            }
+
+            self.queue.submit(Some(encoder.finish()));
+        }
+    }
+
+    /// Copies the contents of a buffer back to the cpu.
+    fn read_buffer(&self, buffer: &wgpu::Buffer) -> EchoResult<Vec<f32>> {
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Staging"),
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+        self.queue.submit(Some(encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|error| EchoError::Gpu(error.to_string()))?
+            .map_err(|error| EchoError::Gpu(error.to_string()))?;
+
+        let result = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "gpu")]
+impl StrategyBackend for GpuBackend {
+    fn accumulate(&mut self, batch: &mut StrategyBatch) -> EchoResult<Vec<Probability>> {
+        if batch.regrets.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let regrets = self.storage_buffer("Regrets", bytemuck::cast_slice(&batch.regrets));
+        let offsets = self.storage_buffer("Offsets", bytemuck::cast_slice(&batch.offsets));
+        let reach = self.storage_buffer("Reach", bytemuck::cast_slice(&batch.reach));
+        let sums = self.storage_buffer("Strategy sums", bytemuck::cast_slice(&batch.strategy_sums));
+        let strategies = self.storage_buffer(
+            "Strategies",
+            bytemuck::cast_slice(&vec![0.0f32; batch.regrets.len()]),
+        );
+
+        self.dispatch(
+            "Regret matching",
+            &self.strategy_pipeline,
+            &[&regrets, &offsets, &reach, &sums, &strategies],
+            batch.rows(),
+            [0, 0],
+        );
+
+        // The batch is left untouched if anything fails.
+        let strategy_sums = self.read_buffer(&sums)?;
+        let strategies = self.read_buffer(&strategies)?;
+        batch.strategy_sums = strategy_sums;
+
+        Ok(strategies)
+    }
+
+    fn regrets(&mut self, batch: &RegretBatch) -> EchoResult<BatchRegrets> {
+        if batch.deals() == 0 {
+            return Ok(BatchRegrets::default());
+        }
+
+        // The reach probabilities of both players get interleaved,
+        // in order to stay below the limit on storage buffers.
+        let reach: Vec<Probability> = (0..batch.deals())
+            .flat_map(|deal| batch.reach.map(|reach| reach[deal]))
+            .collect();
+
+        let values = self.storage_buffer("Values", bytemuck::cast_slice(batch.values));
+        let reach = self.storage_buffer("Reach", bytemuck::cast_slice(&reach));
+        let [my_strategies, your_strategies] = batch
+            .strategies
+            .map(|strategies| self.storage_buffer("Strategies", bytemuck::cast_slice(strategies)));
+        let utilities = self.storage_buffer(
+            "Utilities",
+            bytemuck::cast_slice(&vec![0.0f32; batch.deals()]),
+        );
+        let [my_regrets, your_regrets] = batch.counts.map(|count| {
+            self.storage_buffer(
+                "Regrets",
+                bytemuck::cast_slice(&vec![0.0f32; batch.deals() * count]),
+            )
+        });
+
+        self.dispatch(
+            "Regret computation",
+            &self.regret_pipeline,
+            &[
+                &values,
+                &my_strategies,
+                &your_strategies,
+                &reach,
+                &utilities,
+                &my_regrets,
+                &your_regrets,
+            ],
+            batch.deals(),
+            batch.counts.map(|count| count as u32),
+        );
+
+        Ok(BatchRegrets {
+            utilities: self.read_buffer(&utilities)?,
+            regrets: [
+                self.read_buffer(&my_regrets)?,
+                self.read_buffer(&your_regrets)?,
+            ],
+        })
+    }
+}
+
+/// Uses the gpu if there is one, falling back to the cpu otherwise.
+pub fn best_backend() -> Box<dyn StrategyBackend> {
+    #[cfg(feature = "gpu")]
+    match GpuBackend::new() {
+        Ok(backend) => return Box::new(backend),
+        Err(error) => tracing::warn!("Falling back to cpu strategy updates: {error}"),
+    }
+
+    Box::new(CpuBackend)
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::storage::WeightStorage;
+    use bumpalo::Bump;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_batch(rows: usize) -> StrategyBatch {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut batch = StrategyBatch::new();
+
+        for row in 0..rows {
+            let size = rng.gen_range(1..20);
+            let regrets: Vec<f32> = (0..size).map(|_| rng.gen_range(-1.0..1.0)).collect();
+            let sums: Vec<f32> = (0..size).map(|_| rng.gen_range(0.0..1.0)).collect();
+
+            // Rows without positive regrets get played uniformly.
+            let regrets = if row % 5 == 0 {
+                vec![-1.0; size]
+            } else {
+                regrets
+            };
+            batch.push_row(&regrets, &sums, rng.gen());
+        }
+
+        batch
+    }
+
+    #[test]
+    fn cpu_batches_match_decision_vectors() {
+        let allocator = Bump::new();
+        let matrix = DecisionMatrix::new(8, 5, &allocator, WeightStorage::Arena(&allocator));
+        let DecisionMatrix::Expanded(vectors) = matrix else {
+            unreachable!()
+        };
+
+        for (index, vector) in vectors.iter().enumerate() {
+            vector.accumulate_regret(index % 5, 1.0);
+            vector.accumulate_regret((index + 2) % 5, -0.5);
+            vector.recompute_regret_magnitude();
+        }
+
+        let reach: Vec<_> = (0..8).map(|index| index as f32 / 8.0).collect();
+        let expected: Vec<_> = vectors
+            .iter()
+            .zip(&reach)
+            .map(|(vector, reach)| {
+                let copy = vector.strategy_sums();
+                vector.update_strategy_sum(*reach);
+                let result = vector.get_average_strategy();
+                vector.set_strategy_sums(&copy);
+
+                result
+            })
+            .collect();
+
+        accumulate_matrix(&mut CpuBackend, &matrix, &reach).unwrap();
+
+        for (vector, expected) in vectors.iter().zip(expected) {
+            for (actual, expected) in vector.get_average_strategy().iter().zip(expected) {
+                assert!((actual - expected).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "gpu")]
+    fn gpu_matches_cpu() {
+        let Ok(mut gpu) = GpuBackend::new() else {
+            eprintln!("No gpu available, skipping the parity test");
+            return;
+        };
+
+        let mut cpu_batch = random_batch(1000);
+        let mut gpu_batch = cpu_batch.clone();
+
+        let cpu_strategies = CpuBackend.accumulate(&mut cpu_batch).unwrap();
+        let gpu_strategies = gpu.accumulate(&mut gpu_batch).unwrap();
+
+        let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        assert!(close(&cpu_strategies, &gpu_strategies));
+        assert!(close(&cpu_batch.strategy_sums, &gpu_batch.strategy_sums));
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (deals, counts) = (1000, [4, 3]);
+        let values: Vec<f32> = (0..deals * 12).map(|_| rng.gen_range(-1.0..1.0)).collect();
+        let strategies = counts.map(|count| {
+            let batch = random_batch(deals);
+            let strategies = CpuBackend.accumulate(&mut batch.clone()).unwrap();
+            strategies[..deals * count].to_vec()
+        });
+        let reach = [(); 2].map(|_| (0..deals).map(|_| rng.gen()).collect::<Vec<f32>>());
+        let batch = RegretBatch {
+            counts,
+            values: &values,
+            strategies: [&strategies[0], &strategies[1]],
+            reach: [&reach[0], &reach[1]],
+        };
+
+        let cpu = CpuBackend.regrets(&batch).unwrap();
+        let gpu = gpu.regrets(&batch).unwrap();
+        assert!(close(&cpu.utilities, &gpu.utilities));
+        assert!(close(&cpu.regrets[0], &gpu.regrets[0]));
+        assert!(close(&cpu.regrets[1], &gpu.regrets[1]));
+    }
+
+    #[test]
+    #[cfg(feature = "gpu")]
+    fn gpu_training_matches_cpu() {
+        use crate::cfr::best_response;
+        use crate::cfr::decision::{DecisionMatrices, Scope};
+        use crate::cfr::fixtures::last_turn_state;
+        use crate::cfr::generate::GenerationContext;
+        use crate::cfr::train::TrainingContext;
+        use crate::game::known_state_summary::KnownStateEssentials;
+
+        let Ok(gpu) = GpuBackend::new() else {
+            eprintln!("No gpu available, skipping the parity test");
+            return;
+        };
+
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut cpu_scope = GenerationContext::new(1, state, &allocator).generate();
+        let mut gpu_scope = GenerationContext::new(1, state, &allocator).generate();
+
+        TrainingContext::new(false)
+            .with_backend(Box::new(CpuBackend))
+            .vectorized_cfr(&mut cpu_scope, summary, 50);
+        TrainingContext::new(false)
+            .with_backend(Box::new(gpu))
+            .vectorized_cfr(&mut gpu_scope, summary, 50);
+
+        let expected = best_response::nash_gap(&cpu_scope, summary);
+        let actual = best_response::nash_gap(&gpu_scope, summary);
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "Gpu nash gap {actual} differs from the cpu one ({expected})"
+        );
+
+        // Compares the average strategies at the root as well
+        fn matrices<'a>(scope: &Scope<'a>) -> Vec<DecisionMatrix<'a>> {
+            match scope.get_explored().unwrap().matrices {
+                DecisionMatrices::Symmetrical(matrix) => vec![matrix],
+                DecisionMatrices::Asymmetrical(matrices) => matrices.to_vec(),
+            }
+        }
+
+        for (cpu, gpu) in matrices(&cpu_scope).into_iter().zip(matrices(&gpu_scope)) {
+            let (DecisionMatrix::Expanded(cpu), DecisionMatrix::Expanded(gpu)) = (cpu, gpu) else {
+                continue;
+            };
+
+            for (cpu, gpu) in cpu.iter().zip(gpu) {
+                let (cpu, gpu) = (cpu.get_average_strategy(), gpu.get_average_strategy());
+                assert!(cpu.iter().zip(&gpu).all(|(a, b)| (a - b).abs() < 1e-3));
+            }
+        }
+    }
+}
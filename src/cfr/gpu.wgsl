@@ -0,0 +1,42 @@
+// Regret matching and strategy sum accumulation (see gpu.rs).
+// Every invocation handles a single row of the batch.
+
+struct Params {
+    first_row: u32,
+    rows: u32,
+    _padding: vec2<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> regrets: array<f32>;
+@group(0) @binding(1) var<storage, read> offsets: array<u32>;
+@group(0) @binding(2) var<storage, read> reach: array<f32>;
+@group(0) @binding(3) var<storage, read_write> strategy_sums: array<f32>;
+@group(0) @binding(4) var<storage, read_write> strategies: array<f32>;
+@group(0) @binding(5) var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let row = params.first_row + id.x;
+    if (row >= params.rows) {
+        return;
+    }
+
+    let start = offsets[row];
+    let end = offsets[row + 1u];
+
+    var total = 0.0;
+    for (var i = start; i < end; i++) {
+        total += max(regrets[i], 0.0);
+    }
+
+    let uniform_strategy = 1.0 / f32(end - start);
+    for (var i = start; i < end; i++) {
+        var strategy = uniform_strategy;
+        if (total > 0.0) {
+            strategy = max(regrets[i], 0.0) / total;
+        }
+
+        strategies[i] = strategy;
+        strategy_sums[i] += reach[row] * strategy;
+    }
+}
@@ -0,0 +1,56 @@
+// Computes the utility and regrets of every deal (see gpu.rs).
+// Every invocation handles a single deal of the batch.
+
+struct Params {
+    first_deal: u32,
+    deals: u32,
+    my_count: u32,
+    your_count: u32,
+}
+
+@group(0) @binding(0) var<storage, read> values: array<f32>;
+@group(0) @binding(1) var<storage, read> my_strategies: array<f32>;
+@group(0) @binding(2) var<storage, read> your_strategies: array<f32>;
+// The reach probabilities of both players, interleaved.
+@group(0) @binding(3) var<storage, read> reach: array<f32>;
+@group(0) @binding(4) var<storage, read_write> utilities: array<f32>;
+@group(0) @binding(5) var<storage, read_write> my_regrets: array<f32>;
+@group(0) @binding(6) var<storage, read_write> your_regrets: array<f32>;
+@group(0) @binding(7) var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let deal = params.first_deal + id.x;
+    if (deal >= params.deals) {
+        return;
+    }
+
+    let my_start = deal * params.my_count;
+    let your_start = deal * params.your_count;
+    let value_start = deal * params.my_count * params.your_count;
+
+    // Accumulate the value of every decision into the regret arrays first.
+    for (var i = 0u; i < params.my_count; i++) {
+        for (var j = 0u; j < params.your_count; j++) {
+            let value = values[value_start + i * params.your_count + j];
+            my_regrets[my_start + i] += your_strategies[your_start + j] * value;
+            your_regrets[your_start + j] += my_strategies[my_start + i] * value;
+        }
+    }
+
+    var utility = 0.0;
+    for (var i = 0u; i < params.my_count; i++) {
+        utility += my_strategies[my_start + i] * my_regrets[my_start + i];
+    }
+
+    utilities[deal] = utility;
+
+    // The utility of the second player is the opposite of ours
+    for (var i = 0u; i < params.my_count; i++) {
+        my_regrets[my_start + i] = reach[2u * deal + 1u] * (my_regrets[my_start + i] - utility);
+    }
+
+    for (var j = 0u; j < params.your_count; j++) {
+        your_regrets[your_start + j] = reach[2u * deal] * (utility - your_regrets[your_start + j]);
+    }
+}
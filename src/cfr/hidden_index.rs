@@ -1,10 +1,11 @@
 use super::phase::PhaseTag;
+use crate::error::{EchoError, EchoResult};
 use crate::game::creature::{Creature, CreatureSet};
 use crate::game::known_state_summary::KnownStateEssentials;
 use crate::game::types::Player;
 use crate::helpers::bitfield::const_size_codec::ConstSizeCodec;
 use crate::helpers::bitfield::Bitfield;
-use crate::helpers::choose::choose;
+use crate::helpers::choose::{choose, choose_multi};
 use crate::helpers::ranged::MixRanged;
 use std::assert_eq;
 
@@ -13,7 +14,8 @@ use std::assert_eq;
 /// - a `A` if `phase >= main`
 /// - a `B` if `phase >= sabotage`
 /// - a `C` if `phase >= seer`
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PerPhaseInfo<A, B, C> {
     Main(A),
     Sabotage(A, B),
@@ -30,24 +32,52 @@ impl<A, B, C> PerPhaseInfo<A, B, C> {
         }
     }
 
+    /// Returns true if both values hold info about the same phase,
+    /// regardless of what the info actually is.
     #[inline(always)]
-    pub fn forget_main(self) -> PerPhaseInfo<(), B, C> {
+    pub fn same_phase_as<D, E, F>(self, other: PerPhaseInfo<D, E, F>) -> bool {
+        self.tag() == other.tag()
+    }
+
+    // {{{ Mapping
+    #[inline(always)]
+    pub fn map_main<D>(self, f: impl FnOnce(A) -> D) -> PerPhaseInfo<D, B, C> {
         match self {
-            Self::Main(_) => PerPhaseInfo::Main(()),
-            Self::Sabotage(_, b) => PerPhaseInfo::Sabotage((), b),
-            Self::Seer(_, b, c) => PerPhaseInfo::Seer((), b, c),
+            Self::Main(a) => PerPhaseInfo::Main(f(a)),
+            Self::Sabotage(a, b) => PerPhaseInfo::Sabotage(f(a), b),
+            Self::Seer(a, b, c) => PerPhaseInfo::Seer(f(a), b, c),
         }
     }
 
     #[inline(always)]
-    pub fn forget_sabotage(self) -> PerPhaseInfo<A, (), C> {
+    pub fn map_sabotage<D>(self, f: impl FnOnce(B) -> D) -> PerPhaseInfo<A, D, C> {
         match self {
             Self::Main(a) => PerPhaseInfo::Main(a),
-            Self::Sabotage(a, _) => PerPhaseInfo::Sabotage(a, ()),
-            Self::Seer(a, _, c) => PerPhaseInfo::Seer(a, (), c),
+            Self::Sabotage(a, b) => PerPhaseInfo::Sabotage(a, f(b)),
+            Self::Seer(a, b, c) => PerPhaseInfo::Seer(a, f(b), c),
         }
     }
 
+    #[inline(always)]
+    pub fn map_seer<D>(self, f: impl FnOnce(C) -> D) -> PerPhaseInfo<A, B, D> {
+        match self {
+            Self::Main(a) => PerPhaseInfo::Main(a),
+            Self::Sabotage(a, b) => PerPhaseInfo::Sabotage(a, b),
+            Self::Seer(a, b, c) => PerPhaseInfo::Seer(a, b, f(c)),
+        }
+    }
+
+    #[inline(always)]
+    pub fn forget_main(self) -> PerPhaseInfo<(), B, C> {
+        self.map_main(|_| ())
+    }
+
+    #[inline(always)]
+    pub fn forget_sabotage(self) -> PerPhaseInfo<A, (), C> {
+        self.map_sabotage(|_| ())
+    }
+    // }}}
+
     #[inline(always)]
     pub fn is_post_main(self) -> bool {
         self.tag() != PhaseTag::Main
@@ -149,15 +179,54 @@ impl HiddenState {
             (None, None) => EncodingInfo::Main(self.hand),
             (Some(choice), None) => EncodingInfo::Sabotage(self.hand, choice),
             (Some(choice), Some(revealed)) => EncodingInfo::Seer(self.hand, choice, revealed),
-            (_, _) => panic!("Impossible state")
+            (_, _) => panic!("Impossible state"),
         }
     }
 }
 
+// {{{ Conversions
+impl From<EncodingInfo> for HiddenState {
+    #[inline(always)]
+    fn from(info: EncodingInfo) -> Self {
+        Self::from_encoding_info(info)
+    }
+}
+
+impl From<EncodingInfo> for DecodingInfo {
+    /// Throws away everything which can be recovered by decoding the hidden index.
+    #[inline(always)]
+    fn from(info: EncodingInfo) -> Self {
+        info.forget_main().forget_sabotage()
+    }
+}
+
+impl TryFrom<(HiddenState, DecodingInfo)> for EncodingInfo {
+    type Error = EchoError;
+
+    /// Inverse of converting an `EncodingInfo` into both a `HiddenState` and a `DecodingInfo`.
+    /// Fails if the creature choice is present when it shouldn't be, or vice-versa.
+    fn try_from((hidden, info): (HiddenState, DecodingInfo)) -> EchoResult<Self> {
+        match (info, hidden.choice) {
+            (PerPhaseInfo::Main(()), None) => Ok(Self::Main(hidden.hand)),
+            (PerPhaseInfo::Sabotage((), ()), Some(choice)) => {
+                Ok(Self::Sabotage(hidden.hand, choice))
+            }
+            (PerPhaseInfo::Seer((), (), revealed), Some(choice)) => {
+                Ok(Self::Seer(hidden.hand, choice, revealed))
+            }
+            (info, _) => Err(EchoError::InvalidState(format!(
+                "Hidden state {hidden:?} does not match the {:?} phase",
+                info.tag()
+            ))),
+        }
+    }
+}
+// }}}
 // }}}
 // {{{ HiddenIndex
 /// Encodes all hidden information known by a player.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HiddenIndex(pub(super) usize);
 
 impl HiddenIndex {
@@ -177,9 +246,117 @@ impl HiddenIndex {
         }
     }
 
-    pub fn encode<S: KnownStateEssentials>(state: &S, player: Player, info: EncodingInfo) -> Self {
+    /// Fails if the index does not fit inside an `usize`.
+    pub fn encode<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+        info: EncodingInfo,
+    ) -> EchoResult<Self> {
+        Encoder::new(state, player).encode(info)
+    }
+
+    pub fn decode<S: KnownStateEssentials>(
+        self,
+        state: &S,
+        player: Player,
+        info: DecodingInfo,
+    ) -> EchoResult<HiddenState> {
+        Decoder::new(state, player, info).decode(self)
+    }
+
+    /// Fails if the count does not fit inside an `usize`.
+    pub fn count<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+        phase: PhaseTag,
+    ) -> EchoResult<usize> {
+        let mut hand_possibility_count = (!state.graveyard()).len();
+
+        if phase == PhaseTag::Seer {
+            hand_possibility_count -= 1;
+        }
+
+        let hand_size = state.hand_size_during(player, phase);
+        let choice_len = if Self::index_contains_choice(state, player, phase) {
+            state.creature_choice_size(player)
+        } else {
+            0
+        };
+
+        // Split the possibilities into the hand, the choice, and everything else
+        choose_multi(&[
+            hand_size,
+            choice_len,
+            hand_possibility_count - hand_size - choice_len,
+        ])
+        .ok_or(EchoError::Overflow("hidden states"))
+    }
+    // }}}
+    // {{{ Batch codec
+    /// Encodes many hidden infos at once, writing the results into `out`.
+    /// The parts of the computation which only depend on the state are only done once.
+    /// Stops at the first info which cannot be encoded.
+    pub fn encode_all<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+        infos: &[EncodingInfo],
+        out: &mut [Self],
+    ) -> EchoResult<()> {
+        assert_eq!(infos.len(), out.len());
+        let encoder = Encoder::new(state, player);
+
+        for (info, result) in infos.iter().zip(out.iter_mut()) {
+            *result = encoder.encode(*info)?;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes many hidden indices (all sharing the same decoding info) at once,
+    /// writing the results into `out`. Stops at the first invalid index.
+    pub fn decode_all<S: KnownStateEssentials>(
+        state: &S,
+        player: Player,
+        info: DecodingInfo,
+        indices: &[Self],
+        out: &mut [HiddenState],
+    ) -> EchoResult<()> {
+        assert_eq!(indices.len(), out.len());
+        let decoder = Decoder::new(state, player, info);
+
+        for (index, result) in indices.iter().zip(out.iter_mut()) {
+            *result = decoder.decode(*index)?;
+        }
+
+        Ok(())
+    }
+    // }}}
+}
+
+// {{{ Encoder
+/// Everything `HiddenIndex::encode` needs to know about the state.
+struct Encoder {
+    player: Player,
+    alive: CreatureSet,
+    last_creature_revealer: Player,
+    choice_size: usize,
+}
+
+impl Encoder {
+    #[inline(always)]
+    fn new<S: KnownStateEssentials>(state: &S, player: Player) -> Self {
+        Self {
+            player,
+            alive: !state.graveyard(),
+            last_creature_revealer: state.last_creature_revealer(),
+            choice_size: state.creature_choice_size(player),
+        }
+    }
+
+    #[inline(always)]
+    fn encode(&self, info: EncodingInfo) -> EchoResult<HiddenIndex> {
         let hand = info.get_main();
-        let hand_possibilites = !state.graveyard() - CreatureSet::opt_singleton(info.get_seer());
+        let hand_possibilites = self.alive - CreatureSet::opt_singleton(info.get_seer());
         let irl_hand = hand - info.get_sabotage().unwrap_or_default();
         let encoded_hand = irl_hand.encode_ones_relative_to(hand_possibilites);
 
@@ -187,91 +364,105 @@ impl HiddenIndex {
             assert!(choice.is_subset_of(hand));
 
             match revealed {
-                Some(revealed) if player != state.last_creature_revealer() => {
+                Some(revealed) if self.player != self.last_creature_revealer => {
                     assert_eq!(choice, CreatureSet::singleton(revealed));
 
-                    encoded_hand.into()
+                    Ok(encoded_hand.into())
                 }
                 _ => {
-                    assert_eq!(choice.len(), state.creature_choice_size(player));
+                    assert_eq!(choice.len(), self.choice_size);
 
                     encoded_hand
                         .mix_subset(choice, hand_possibilites - irl_hand)
-                        .into()
+                        .map(HiddenIndex)
+                        .ok_or(EchoError::Encode("hidden index"))
                 }
             }
         } else {
-            encoded_hand.into()
+            Ok(encoded_hand.into())
         }
     }
+}
+// }}}
+// {{{ Decoder
+/// Everything `HiddenIndex::decode` needs to know about the state and the decoding info.
+struct Decoder {
+    hand_possibilites: CreatureSet,
+    irl_hand_size: usize,
+    choice_size: usize,
+    seer: Option<Creature>,
+
+    /// Present if and only if the index contains the creature choice of the player.
+    max_choice_value: Option<usize>,
+}
 
-    pub fn decode<S: KnownStateEssentials>(
-        self,
-        state: &S,
-        player: Player,
-        info: DecodingInfo,
-    ) -> Option<HiddenState> {
+impl Decoder {
+    #[inline(always)]
+    fn new<S: KnownStateEssentials>(state: &S, player: Player, info: DecodingInfo) -> Self {
         let hand_possibilites = !state.graveyard() - CreatureSet::opt_singleton(info.get_seer());
-        let self_contains_choice = Self::index_contains_choice(state, player, info.tag());
-
         let irl_hand_size = state.hand_size_during(player, info.tag());
         let choice_size = state.creature_choice_size(player);
 
-        let (encoded_hand, remaining) = if self_contains_choice {
-            let max_choice_value = choose(
-                hand_possibilites.len() - irl_hand_size, // length of `choice_possibilities`
-                choice_size,
-            );
+        let max_choice_value =
+            HiddenIndex::index_contains_choice(state, player, info.tag()).then(|| {
+                choose(
+                    hand_possibilites.len() - irl_hand_size, // length of `choice_possibilities`
+                    choice_size,
+                )
+            });
+
+        Self {
+            hand_possibilites,
+            irl_hand_size,
+            choice_size,
+            seer: info.get_seer(),
+            max_choice_value,
+        }
+    }
 
-            let (encoded_hand, remaining) = self.0.unmix_ranged(max_choice_value)?;
+    #[inline(always)]
+    fn decode(&self, index: HiddenIndex) -> EchoResult<HiddenState> {
+        let error = EchoError::Decode("hidden index");
+
+        let (encoded_hand, remaining) = if let Some(max_choice_value) = self.max_choice_value {
+            let (encoded_hand, remaining) = index
+                .0
+                .unmix_ranged(max_choice_value)
+                .ok_or(error.clone())?;
 
             (encoded_hand, Some(remaining))
         } else {
-            (self.0, None)
+            (index.0, None)
         };
 
-        let irl_hand =
-            CreatureSet::decode_ones_relative_to(encoded_hand, irl_hand_size, hand_possibilites)?;
+        let irl_hand = CreatureSet::decode_ones_relative_to(
+            encoded_hand,
+            self.irl_hand_size,
+            self.hand_possibilites,
+        )
+        .ok_or(error.clone())?;
 
         let choice = if let Some(remaining) = remaining {
-            let choice_possibilities = hand_possibilites - irl_hand;
-            let decoded =
-                CreatureSet::decode_ones_relative_to(remaining, choice_size, choice_possibilities)?;
+            let choice_possibilities = self.hand_possibilites - irl_hand;
+            let decoded = CreatureSet::decode_ones_relative_to(
+                remaining,
+                self.choice_size,
+                choice_possibilities,
+            )
+            .ok_or(error)?;
 
             Some(decoded)
         } else {
-            info.get_seer().map(|c| CreatureSet::singleton(c))
+            self.seer.map(CreatureSet::singleton)
         };
 
-        Some(HiddenState::new(
+        Ok(HiddenState::new(
             irl_hand | choice.unwrap_or_default(),
             choice,
         ))
     }
-
-    pub fn count<S: KnownStateEssentials>(state: &S, player: Player, phase: PhaseTag) -> usize {
-        let mut hand_possibility_count = (!state.graveyard()).len();
-
-        if phase == PhaseTag::Seer {
-            hand_possibility_count -= 1;
-        }
-
-        let hand_size = state.hand_size_during(player, phase);
-        let hand_count = choose(hand_possibility_count, hand_size);
-
-        let choice_count = if Self::index_contains_choice(state, player, phase) {
-            let choice_len = state.creature_choice_size(player);
-            let choice_count = choose(hand_possibility_count - hand_size, choice_len);
-
-            choice_count
-        } else {
-            1
-        };
-
-        hand_count * choice_count
-    }
-    // }}}
 }
+// }}}
 
 impl From<usize> for HiddenIndex {
     fn from(value: usize) -> Self {
@@ -279,33 +470,41 @@ impl From<usize> for HiddenIndex {
     }
 }
 
+impl From<HiddenIndex> for usize {
+    fn from(value: HiddenIndex) -> Self {
+        value.0
+    }
+}
+
 // }}}
 // {{{ Tests
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cfr::fixtures::graveyards;
+    use crate::game::battlefield::Battlefields;
     use crate::game::known_state_summary::KnownStateSummary;
     use std::assert_eq;
 
     // {{{ Main phase
     #[test]
     fn hidden_encode_decode_main_inverses() {
-        for graveyard in Bitfield::members() {
+        for graveyard in graveyards() {
             let player = Player::Me;
             let state = KnownStateSummary::new_all_edicts(graveyard, Some(player));
             let mut found_max = false;
 
             for hand in (!graveyard).subsets_of_size(state.hand_size()) {
                 let info = PerPhaseInfo::Main(hand);
-                let decoding_info = info.forget_main().forget_sabotage();
-                let encoded = HiddenIndex::encode(&state, player, info);
+                let decoding_info = DecodingInfo::from(info);
+                let encoded = HiddenIndex::encode(&state, player, info).unwrap();
 
                 assert_eq!(
                     encoded.decode(&state, player, decoding_info),
-                    Some(HiddenState::from_encoding_info(info))
+                    Ok(HiddenState::from_encoding_info(info))
                 );
 
-                let count = HiddenIndex::count(&state, player, info.tag());
+                let count = HiddenIndex::count(&state, player, info.tag()).unwrap();
 
                 assert!(
                     encoded.0 < count,
@@ -323,10 +522,38 @@ mod tests {
         }
     }
     // }}}
+    // {{{ Overseer
+    #[test]
+    fn opponent_hands_exclude_set_aside_creatures() {
+        for graveyard in graveyards() {
+            let state = KnownStateSummary::new_all_edicts(graveyard, None);
+
+            // Both players discard one creature per turn
+            if graveyard.len() % 2 == 1 {
+                continue;
+            }
+
+            for hand in (!graveyard).subsets_of_size(state.hand_size()).take(5) {
+                let mut overseers = CreatureSet::empty();
+
+                for (set_aside, opponent_hand) in state.opponent_hands(hand) {
+                    assert_eq!(opponent_hand.len(), state.hand_size());
+                    assert_eq!(set_aside.len(), Battlefields::SET_ASIDE);
+                    assert_eq!(opponent_hand & (hand | graveyard), CreatureSet::empty());
+                    assert_eq!(opponent_hand & set_aside, CreatureSet::empty());
+
+                    overseers |= set_aside;
+                }
+
+                assert_eq!(overseers, state.overseer_candidates(hand));
+            }
+        }
+    }
+    // }}}
     // {{{ Sabotage phase
     #[test]
     fn hidden_encode_decode_sabotage_inverses() {
-        for graveyard in Bitfield::members() {
+        for graveyard in graveyards() {
             for seer_player in [None, Some(Player::Me), Some(Player::You)] {
                 let player = Player::Me;
                 let state = KnownStateSummary::new_all_edicts(graveyard, seer_player);
@@ -340,15 +567,15 @@ mod tests {
                 for hand in (!graveyard).subsets_of_size(state.hand_size()) {
                     for choice in hand.subsets_of_size(choice_size) {
                         let info = PerPhaseInfo::Sabotage(hand, choice);
-                        let decoding_info = info.forget_main().forget_sabotage();
-                        let encoded = HiddenIndex::encode(&state, player, info);
+                        let decoding_info = DecodingInfo::from(info);
+                        let encoded = HiddenIndex::encode(&state, player, info).unwrap();
 
                         assert_eq!(
                             encoded.decode(&state, player, decoding_info),
-                            Some(HiddenState::from_encoding_info(info))
+                            Ok(HiddenState::from_encoding_info(info))
                         );
 
-                        let count = HiddenIndex::count(&state, player, info.tag());
+                        let count = HiddenIndex::count(&state, player, info.tag()).unwrap();
 
                         assert!(encoded.0 < count);
 
@@ -366,7 +593,7 @@ mod tests {
     // {{{ Seer phase
     #[test]
     fn hidden_encode_decode_seer_inverses() {
-        for graveyard in Bitfield::members() {
+        for graveyard in graveyards() {
             for seer_player in [None, Some(Player::Me), Some(Player::You)] {
                 let player = Player::Me;
                 let state = KnownStateSummary::new_all_edicts(graveyard, seer_player);
@@ -391,15 +618,15 @@ mod tests {
 
                         for revealed in revealed_iter {
                             let info = PerPhaseInfo::Seer(hand, choice, revealed);
-                            let decoding_info = info.forget_main().forget_sabotage();
-                            let encoded = HiddenIndex::encode(&state, player, info);
+                            let decoding_info = DecodingInfo::from(info);
+                            let encoded = HiddenIndex::encode(&state, player, info).unwrap();
 
                             assert_eq!(
                                 encoded.decode(&state, player, decoding_info),
-                                Some(HiddenState::from_encoding_info(info))
+                                Ok(HiddenState::from_encoding_info(info))
                             );
 
-                            let count = HiddenIndex::count(&state, player, info.tag());
+                            let count = HiddenIndex::count(&state, player, info.tag()).unwrap();
 
                             assert!(encoded.0 < count);
 
@@ -415,5 +642,77 @@ mod tests {
         }
     }
     // }}}
+    // {{{ Batch codec
+    #[test]
+    fn batch_codec_matches_single_codec() {
+        for graveyard in graveyards().step_by(37) {
+            let player = Player::Me;
+            let state = KnownStateSummary::new_all_edicts(graveyard, Some(player));
+            let choice_size = state.creature_choice_size(player);
+
+            if state.hand_size() < choice_size {
+                continue;
+            };
+
+            let infos: Vec<EncodingInfo> = (!graveyard)
+                .subsets_of_size(state.hand_size())
+                .flat_map(|hand| {
+                    hand.subsets_of_size(choice_size)
+                        .map(move |choice| PerPhaseInfo::Sabotage(hand, choice))
+                })
+                .collect();
+
+            let mut encoded = vec![HiddenIndex(0); infos.len()];
+            HiddenIndex::encode_all(&state, player, &infos, &mut encoded).unwrap();
+
+            for (info, index) in infos.iter().zip(&encoded) {
+                assert_eq!(Ok(*index), HiddenIndex::encode(&state, player, *info));
+            }
+
+            let decoding_info = PerPhaseInfo::Sabotage((), ());
+            let count = HiddenIndex::count(&state, player, PhaseTag::Sabotage).unwrap();
+            let indices: Vec<_> = (0..count).map(HiddenIndex).collect();
+            let mut decoded = vec![HiddenState::new(CreatureSet::empty(), None); count];
+
+            assert_eq!(
+                HiddenIndex::decode_all(&state, player, decoding_info, &indices, &mut decoded),
+                Ok(())
+            );
+
+            for (index, hidden) in indices.iter().zip(&decoded) {
+                assert_eq!(Ok(*hidden), index.decode(&state, player, decoding_info));
+            }
+        }
+    }
+    // }}}
+    // {{{ Conversions
+    #[test]
+    fn conversions_roundtrip() {
+        let hand = CreatureSet::all() - CreatureSet::singleton(Creature::Wall);
+        let choice = CreatureSet::singleton(Creature::Seer);
+
+        for info in [
+            EncodingInfo::Main(hand),
+            EncodingInfo::Sabotage(hand, choice),
+            EncodingInfo::Seer(hand, choice, Creature::Witch),
+        ] {
+            let hidden = HiddenState::from(info);
+            let decoding_info = DecodingInfo::from(info);
+
+            assert!(info.same_phase_as(decoding_info));
+            assert_eq!(EncodingInfo::try_from((hidden, decoding_info)), Ok(info));
+        }
+
+        let missing_choice = (HiddenState::new(hand, None), DecodingInfo::Sabotage((), ()));
+        assert!(EncodingInfo::try_from(missing_choice).is_err());
+
+        let info = EncodingInfo::Seer(hand, choice, Creature::Witch);
+        assert_eq!(
+            info.map_main(CreatureSet::len)
+                .map_seer(CreatureSet::singleton),
+            PerPhaseInfo::Seer(hand.len(), choice, CreatureSet::singleton(Creature::Witch))
+        );
+    }
+    // }}}
 }
 // }}}
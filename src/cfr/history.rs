@@ -0,0 +1,150 @@
+//! Reconstructs what happened during a game from the point of view of a player,
+//! using nothing but the reveal indices the player receives at the end of every phase.
+use super::phase::{PerPhase, SomePhase};
+use super::reveal_index::RevealIndex;
+use crate::error::EchoResult;
+use crate::game::battlefield::Battlefields;
+use crate::game::creature::Creature;
+use crate::game::edict::Edict;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::{Player, Score};
+use crate::helpers::pair::Pair;
+
+/// A value summarizing all the choices a player made during an entire turn.
+///
+/// Some of the data might not yet be available, so we use options everywhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerTurnHistory {
+    pub creature: Option<Creature>,
+    pub edict: Option<Edict>,
+    pub sabotage: Option<Creature>,
+}
+
+/// A value summarizing data about a given turn, be it in the past or the future.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TurnHistory {
+    /// The score at the end of the turn.
+    pub score: Option<Score>,
+    pub choices: Pair<PlayerTurnHistory>,
+}
+
+/// Everything revealed so far during a game, one entry per battlefield.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct History {
+    pub turns: [TurnHistory; Battlefields::COUNT],
+}
+
+impl History {
+    /// Records the information revealed at the end of some phase.
+    ///
+    /// The score is the one sent together with the reveal index,
+    /// and only gets recorded at the end of the turn.
+    ///
+    /// Returns the creature flipped by this reveal (if any), together with its owner.
+    pub fn record(
+        &mut self,
+        state: &KnownState,
+        phase: &SomePhase,
+        reveal_index: RevealIndex,
+        score: Score,
+    ) -> EchoResult<Option<(Player, Creature)>> {
+        let last_revealer = state.last_creature_revealer();
+        let turn = &mut self.turns[state.battlefields.current];
+
+        let flipped = match reveal_index.decode(phase, state)? {
+            PerPhase::Main(edict_choices) => {
+                for player in Player::PLAYERS {
+                    player.select_mut(&mut turn.choices).edict = Some(player.select(edict_choices));
+                }
+
+                None
+            }
+            PerPhase::Sabotage((sabotage_choices, revealed_creature)) => {
+                for player in Player::PLAYERS {
+                    player.select_mut(&mut turn.choices).sabotage = player.select(sabotage_choices);
+                }
+
+                (!last_revealer).select_mut(&mut turn.choices).creature = Some(revealed_creature);
+
+                Some((!last_revealer, revealed_creature))
+            }
+            PerPhase::Seer(revealed_creature) => {
+                last_revealer.select_mut(&mut turn.choices).creature = Some(revealed_creature);
+                turn.score = Some(score);
+
+                Some((last_revealer, revealed_creature))
+            }
+        };
+
+        Ok(flipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, SabotagePhase, SeerPhase};
+    use crate::game::battlefield::Battlefield;
+
+    #[test]
+    fn reveals_get_accumulated() {
+        let state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        let revealer = state.last_creature_revealer();
+        let mut history = History::default();
+
+        let main = PerPhase::Main(MainPhase::new());
+        let edicts = [Edict::Sabotage, Edict::Gambit];
+        let reveal = RevealIndex::encode_main_phase_reveal(edicts, state.edict_sets()).unwrap();
+        assert_eq!(history.record(&state, &main, reveal, state.score), Ok(None));
+
+        let sabotage = PerPhase::Sabotage(SabotagePhase::new(edicts));
+        let guesses = [Some(Creature::Witch), None];
+        let reveal = RevealIndex::encode_sabotage_phase_reveal(
+            guesses,
+            revealer,
+            Creature::Wall,
+            state.graveyard,
+        )
+        .unwrap();
+
+        assert_eq!(
+            history.record(&state, &sabotage, reveal, state.score),
+            Ok(Some((!revealer, Creature::Wall)))
+        );
+
+        let seer = PerPhase::Seer(SeerPhase::new(edicts, guesses, Creature::Wall));
+        let reveal = RevealIndex::encode_seer_phase_reveal(
+            Creature::Monarch,
+            state.graveyard,
+            Creature::Wall,
+        )
+        .unwrap();
+
+        assert_eq!(
+            history.record(&state, &seer, reveal, Score(2)),
+            Ok(Some((revealer, Creature::Monarch)))
+        );
+
+        let turn = history.turns[0];
+        assert_eq!(turn.score, Some(Score(2)));
+
+        for player in Player::PLAYERS {
+            let choices = player.select(turn.choices);
+            assert_eq!(choices.edict, Some(player.select(edicts)));
+            assert_eq!(choices.sabotage, player.select(guesses));
+        }
+
+        assert_eq!(
+            revealer.select(turn.choices).creature,
+            Some(Creature::Monarch)
+        );
+        assert_eq!(
+            (!revealer).select(turn.choices).creature,
+            Some(Creature::Wall)
+        );
+    }
+}
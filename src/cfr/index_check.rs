@@ -0,0 +1,211 @@
+//! Runtime self-check for the index codecs.
+//!
+//! For a given state summary, every hidden, decision and reveal index
+//! gets decoded and re-encoded, making sure the codecs are bijections
+//! between `0..count` and the values they describe. The unit tests do
+//! this for a handful of states, while this module allows checking
+//! arbitrary states (useful when experimenting with new rules).
+use super::decision_index::DecisionIndex;
+use super::hidden_index::{HiddenIndex, PerPhaseInfo};
+use super::phase::PhaseTag;
+use super::reveal_index::RevealIndex;
+use crate::error::EchoResult;
+use crate::game::edict::Edict;
+use crate::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use crate::game::types::Player;
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+
+/// The results of running `check_indices`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IndexCheckReport {
+    /// The number of indices which got decoded and re-encoded.
+    pub checked: usize,
+    /// Human readable descriptions of everything that went wrong.
+    pub errors: Vec<String>,
+}
+
+impl IndexCheckReport {
+    /// Decodes and re-encodes every index in `0..count`.
+    ///
+    /// The `roundtrip` function returns the re-encoded index, or an
+    /// error if either the decoding or the encoding failed. Counts which
+    /// could not be computed get reported as errors.
+    fn check(
+        &mut self,
+        label: &str,
+        count: EchoResult<usize>,
+        mut roundtrip: impl FnMut(usize) -> EchoResult<usize>,
+    ) {
+        let count = match count {
+            Ok(count) => count,
+            Err(error) => return self.errors.push(format!("{label}: {error}")),
+        };
+
+        for index in 0..count {
+            self.checked += 1;
+
+            match roundtrip(index) {
+                Ok(encoded) if encoded == index => {}
+                Ok(encoded) => self
+                    .errors
+                    .push(format!("{label}: index {index} re-encoded to {encoded}")),
+                Err(error) => self.errors.push(format!("{label}: index {index}: {error}")),
+            }
+        }
+    }
+}
+
+/// Checks every index which can occur in the given state.
+pub fn check_indices(state: &KnownStateSummary) -> IndexCheckReport {
+    let mut report = IndexCheckReport::default();
+    let alive = !state.graveyard;
+    let hands: Vec<_> = alive.subsets_of_size(state.hand_size()).collect();
+
+    for player in Player::PLAYERS {
+        // {{{ Hidden indices
+        for phase in PhaseTag::PHASES {
+            let label = format!("Hidden index ({phase:?} phase, {player:?})");
+            let count = HiddenIndex::count(state, player, phase);
+
+            // The decoding info for the seer phase contains the creature
+            // revealed by the opponent, so we check every possibility.
+            let revealed: Vec<_> = match phase {
+                PhaseTag::Seer => alive.into_iter().map(Some).collect(),
+                _ => vec![None],
+            };
+
+            for revealed in revealed {
+                let info = match (phase, revealed) {
+                    (PhaseTag::Main, _) => PerPhaseInfo::Main(()),
+                    (PhaseTag::Sabotage, _) => PerPhaseInfo::Sabotage((), ()),
+                    (PhaseTag::Seer, revealed) => PerPhaseInfo::Seer((), (), revealed.unwrap()),
+                };
+
+                report.check(&label, count.clone(), |index| {
+                    let hidden = HiddenIndex::from(index).decode(state, player, info)?;
+                    let encoded =
+                        HiddenIndex::encode(state, player, hidden.to_encoding_info(revealed))?;
+
+                    Ok(encoded.into())
+                });
+            }
+        }
+        // }}}
+        // {{{ Decision indices
+        for hand in &hands {
+            let hand = *hand;
+            let label = format!("Main phase decision ({player:?}, hand {hand:?})");
+            let count = DecisionIndex::main_phase_index_count(state, player);
+
+            report.check(&label, count, |index| {
+                let (creatures, edict) =
+                    DecisionIndex(index).decode_main_phase_index(state, player, hand)?;
+
+                DecisionIndex::encode_main_phase_index(state, player, hand, creatures, edict)
+                    .map(|index| index.0)
+            });
+
+            for status in [false, true] {
+                let label =
+                    format!("Sabotage phase decision ({player:?}, hand {hand:?}, status {status})");
+                let count = DecisionIndex::sabotage_phase_index_count(state, status);
+
+                report.check(&label, Ok(count), |index| {
+                    let guess = DecisionIndex(index).decode_sabotage_index(state, hand, status)?;
+
+                    Ok(DecisionIndex::encode_sabotage_index(state, hand, guess).0)
+                });
+            }
+        }
+
+        for size in 1..=2 {
+            for choices in alive.subsets_of_size(size) {
+                let label = format!("Seer phase decision ({player:?}, choices {choices:?})");
+
+                let count = Ok(DecisionIndex::seer_index_count(choices));
+
+                report.check(&label, count, |index| {
+                    let creature = DecisionIndex(index).decode_seer_index(choices)?;
+
+                    DecisionIndex::encode_seer_index(choices, creature).map(|index| index.0)
+                });
+            }
+        }
+        // }}}
+    }
+
+    // {{{ Reveal indices
+    let edict_sets = state.edict_sets();
+    report.check(
+        "Main phase reveal",
+        RevealIndex::main_phase_count(edict_sets),
+        |index| {
+            let edicts: Pair<Edict> = RevealIndex(index).decode_main_phase_reveal(edict_sets)?;
+
+            RevealIndex::encode_main_phase_reveal(edicts, edict_sets).map(|index| index.0)
+        },
+    );
+
+    let seer_player = state.last_creature_revealer();
+    for statuses in [[false, false], [false, true], [true, false], [true, true]] {
+        let label = format!("Sabotage phase reveal (statuses {statuses:?})");
+        let count = RevealIndex::sabotage_phase_count(statuses, seer_player, state.graveyard);
+
+        report.check(&label, count, |index| {
+            let (choices, revealed) = RevealIndex(index).decode_sabotage_phase_reveal(
+                statuses,
+                seer_player,
+                state.graveyard,
+            )?;
+
+            RevealIndex::encode_sabotage_phase_reveal(
+                choices,
+                seer_player,
+                revealed,
+                state.graveyard,
+            )
+            .map(|index| index.0)
+        });
+    }
+
+    for revealed in alive {
+        let label = format!("Seer phase reveal (revealed {revealed:?})");
+        let count = Ok(RevealIndex::seer_phase_count(state.graveyard));
+
+        report.check(&label, count, |index| {
+            let creature =
+                RevealIndex(index).decode_seer_phase_reveal(state.graveyard, revealed)?;
+
+            RevealIndex::encode_seer_phase_reveal(creature, state.graveyard, revealed)
+                .map(|index| index.0)
+        });
+    }
+    // }}}
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::creature::{Creature, CreatureSet};
+    use crate::game::edict::EdictSet;
+
+    #[test]
+    fn indices_roundtrip() {
+        let mut graveyard = CreatureSet::empty();
+        graveyard.insert(Creature::Wall);
+        graveyard.insert(Creature::Monarch);
+
+        for (graveyard, seer_player) in
+            [(CreatureSet::empty(), None), (graveyard, Some(Player::You))]
+        {
+            let state = KnownStateSummary::new([EdictSet::all(); 2], graveyard, seer_player);
+            let report = check_indices(&state);
+
+            assert!(report.checked > 0);
+            assert_eq!(report.errors, Vec::<String>::new());
+        }
+    }
+}
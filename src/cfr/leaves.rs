@@ -0,0 +1,159 @@
+//! Values for the leaves of depth-limited trees.
+//!
+//! Trees generated for a limited number of turns end in unexplored scopes at
+//! the start of some later turn, where training needs to know how the game
+//! would continue. By default, it pretends the game ends right away, which is
+//! only accurate once the score decides the game. Leaf evaluators provide
+//! better estimates: exact solutions of the final turn (see `endgame`),
+//! rollouts (see `montecarlo`), or a quick heuristic.
+use super::abstraction::hand_strength;
+use super::decision::Utility;
+use super::endgame::EndgameTable;
+use super::hidden_index::{HiddenState, PerPhaseInfo};
+use super::montecarlo::RolloutEvaluator;
+use super::phase::{MainPhase, Phase};
+use super::position::GamePosition;
+use crate::game::creature::CreatureSet;
+use crate::game::known_state::KnownState;
+use crate::helpers::pair::Pair;
+use std::cell::RefCell;
+
+/// Values the leaves of depth-limited trees during training
+/// (see `TrainingContext::with_leaf_evaluator`).
+pub trait LeafEvaluator {
+    /// The utility of the first player once the main phase of the given state
+    /// gets reached with the given hands. Returning `None` falls back to the score.
+    fn leaf_value(&self, state: &KnownState, hands: Pair<CreatureSet>) -> Option<Utility>;
+}
+
+/// Only values the final turn, which gets solved exactly.
+impl LeafEvaluator for EndgameTable {
+    fn leaf_value(&self, state: &KnownState, hands: Pair<CreatureSet>) -> Option<Utility> {
+        EndgameTable::leaf_value(self, state, hands)
+    }
+}
+
+/// Plays the rest of the game a number of times for every visit of a leaf.
+/// Rollouts are random, so training sees slightly different values every time.
+pub struct RolloutLeaves<'a> {
+    evaluator: RefCell<RolloutEvaluator<'a>>,
+}
+
+impl<'a> RolloutLeaves<'a> {
+    pub fn new(evaluator: RolloutEvaluator<'a>) -> Self {
+        Self {
+            evaluator: RefCell::new(evaluator),
+        }
+    }
+}
+
+impl<'a> LeafEvaluator for RolloutLeaves<'a> {
+    fn leaf_value(&self, state: &KnownState, hands: Pair<CreatureSet>) -> Option<Utility> {
+        let position = GamePosition::new(
+            *state,
+            MainPhase::new().to_some_phase(),
+            hands.map(PerPhaseInfo::Main),
+        );
+
+        self.evaluator.borrow_mut().position_value(position).ok()
+    }
+}
+
+/// Guesses the outcome from the score and the strength of both hands on the
+/// battlefields left (see `abstraction::hand_strength`), squashed into `(-1, 1)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeuristicLeaves {
+    /// How many points of score a point of hand strength is worth.
+    pub weight: Utility,
+}
+
+impl Default for HeuristicLeaves {
+    fn default() -> Self {
+        Self { weight: 0.1 }
+    }
+}
+
+impl LeafEvaluator for HeuristicLeaves {
+    fn leaf_value(&self, state: &KnownState, hands: Pair<CreatureSet>) -> Option<Utility> {
+        let [mine, yours] =
+            hands.map(|hand| hand_strength(state, HiddenState { hand, choice: None }) as Utility);
+
+        Some((state.score.0 as Utility + self.weight * (mine - yours)).tanh())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::fixtures::turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::train::TrainingContext;
+    use crate::game::battlefield::Battlefields;
+    use crate::game::creature::Creature;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::types::Score;
+    use crate::helpers::bitfield::Bitfield;
+    use bumpalo::Bump;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Values every leaf the same, counting the visits.
+    struct ConstantLeaves {
+        value: Utility,
+        visits: Rc<Cell<usize>>,
+    }
+
+    impl LeafEvaluator for ConstantLeaves {
+        fn leaf_value(&self, _state: &KnownState, _hands: Pair<CreatureSet>) -> Option<Utility> {
+            self.visits.set(self.visits.get() + 1);
+            Some(self.value)
+        }
+    }
+
+    #[test]
+    fn depth_limited_training_uses_the_evaluator() {
+        let state = turn_state(Battlefields::COUNT - 2);
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+
+        let visits = Rc::new(Cell::new(0));
+        let trainer = TrainingContext::new(false).with_leaf_evaluator(ConstantLeaves {
+            value: 1.0,
+            visits: visits.clone(),
+        });
+
+        let utility = trainer.cfr_iteration(&mut scope, state.to_summary());
+        assert!(visits.get() > 0);
+        assert!(
+            utility > 0.5,
+            "Expected the leaves to be won, got {utility}"
+        );
+    }
+
+    #[test]
+    fn heuristic_values_follow_the_score_and_the_hands() {
+        let mut state = turn_state(Battlefields::COUNT - 2);
+        let heuristic = HeuristicLeaves::default();
+        let hand = |creatures: [Creature; 3]| {
+            let mut hand = CreatureSet::empty();
+            for creature in creatures {
+                hand.insert(creature);
+            }
+
+            hand
+        };
+
+        let weak = hand([Creature::Diplomat, Creature::Ranger, Creature::Steward]);
+        let strong = hand([Creature::Witch, Creature::Mercenary, Creature::Monarch]);
+        let value = |state: &KnownState, hands| heuristic.leaf_value(state, hands).unwrap();
+
+        assert!(value(&state, [strong, weak]) > 0.0);
+        assert_eq!(
+            value(&state, [strong, weak]),
+            -value(&state, [weak, strong])
+        );
+
+        state.score = Score(-5);
+        assert!(value(&state, [strong, weak]) < -0.9);
+    }
+}
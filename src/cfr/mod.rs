@@ -1,7 +1,61 @@
 pub mod hidden_index;
 pub mod decision_index;
 pub mod reveal_index;
+pub mod history;
 pub mod decision;
+pub mod abstraction;
 pub mod phase;
 pub mod generate;
 pub mod train;
+#[cfg(feature = "net")]
+pub mod distributed;
+#[cfg(feature = "deep-cfr")]
+pub mod deep;
+pub mod gpu;
+pub mod background;
+pub mod best_response;
+pub mod belief;
+pub mod exploitability;
+pub mod montecarlo;
+pub mod endgame;
+pub mod leaves;
+pub mod evaluate;
+pub mod storage;
+pub mod blueprint;
+pub mod index_check;
+pub mod position;
+pub mod opening_book;
+pub mod review;
+
+/// Fixtures shared by the tests of the solver.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::creature::{Creature, CreatureSet};
+    use crate::game::known_state::KnownState;
+    use crate::helpers::bitfield::Bitfield;
+
+    /// The start of some turn (on plains only), where the first few
+    /// creatures have been played during the previous turns.
+    pub fn turn_state(turn: usize) -> KnownState {
+        let mut state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+        state.battlefields.current = turn;
+        for creature in &Creature::CREATURES[..2 * turn] {
+            state.graveyard.insert(*creature);
+        }
+
+        state
+    }
+
+    /// The last turn of a game. Its tree is small enough to get trained during tests.
+    pub fn last_turn_state() -> KnownState {
+        turn_state(Battlefields::COUNT - 1)
+    }
+
+    /// Every graveyard which can occur during a game (both players
+    /// discard one creature per turn, for as many turns as there are
+    /// battlefields), for tests going through all of them.
+    pub fn graveyards() -> impl Iterator<Item = CreatureSet> {
+        CreatureSet::members().filter(|graveyard| graveyard.len() <= 2 * Battlefields::COUNT)
+    }
+}
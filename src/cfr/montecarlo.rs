@@ -0,0 +1,294 @@
+//! Monte carlo estimates of the value of positions.
+//!
+//! Every rollout deals random hands consistent with the public state (unless
+//! the hands are already known), then plays the game until the end using some
+//! cheap policy. Averaging the final scores is much cheaper than solving the
+//! position, which makes rollouts useful as the value of leaves (in searches
+//! like mcts, or in depth-limited trees) and for quickly estimating who is
+//! ahead. The estimates are only as good as the policies though.
+use super::best_response::Deal;
+use super::decision::Utility;
+use super::decision_index::DecisionIndex;
+use super::hidden_index::PerPhaseInfo;
+use super::phase::{MainPhase, PerPhase, Phase, SomePhase};
+use super::position::GamePosition;
+use crate::error::{EchoError, EchoResult};
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::types::{Player, Score, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use crate::helpers::sampling::sample;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+
+// {{{ Policies
+/// Decides how players behave during rollouts.
+pub trait RolloutPolicy {
+    fn decide(
+        &mut self,
+        position: &GamePosition,
+        player: Player,
+        rng: &mut dyn RngCore,
+    ) -> DecisionIndex;
+}
+
+/// Takes every decision with the same probability.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UniformPolicy;
+
+impl RolloutPolicy for UniformPolicy {
+    fn decide(
+        &mut self,
+        position: &GamePosition,
+        player: Player,
+        rng: &mut dyn RngCore,
+    ) -> DecisionIndex {
+        let count = player.select(position.decision_counts());
+        DecisionIndex(rng.gen_range(0..count))
+    }
+}
+
+/// Plays the creatures which are the strongest on the current battlefield,
+/// breaking ties (and picking edicts and sabotage guesses) at random.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyPolicy;
+
+impl RolloutPolicy for GreedyPolicy {
+    fn decide(
+        &mut self,
+        position: &GamePosition,
+        player: Player,
+        rng: &mut dyn RngCore,
+    ) -> DecisionIndex {
+        let battlefield = position.state.battlefields.current();
+        let strength =
+            |creature: Creature| creature.strength() as i32 + battlefield.bonus(creature) as i32;
+
+        let decisions: Vec<_> = position
+            .legal_decisions(player)
+            .map(|(index, decision)| {
+                let value = match decision {
+                    PerPhase::Main((choice, _)) => choice.into_iter().map(strength).sum(),
+                    PerPhase::Sabotage(_) => 0,
+                    PerPhase::Seer(creature) => strength(creature),
+                };
+
+                (index, value)
+            })
+            .collect();
+
+        let best = decisions.iter().map(|(_, value)| *value).max();
+        let candidates: Vec<_> = decisions
+            .into_iter()
+            .filter(|(_, value)| Some(*value) == best)
+            .map(|(index, _)| index)
+            .collect();
+
+        candidates.choose(rng).copied().unwrap_or_default()
+    }
+}
+// }}}
+// {{{ Evaluator
+/// Estimates the value of positions by averaging the results of rollouts.
+/// Estimates are deterministic for a given seed.
+pub struct RolloutEvaluator<'a> {
+    policies: Pair<Box<dyn RolloutPolicy + 'a>>,
+
+    /// The number of games played for every estimate.
+    rollouts: usize,
+    rng: StdRng,
+}
+
+impl<'a> RolloutEvaluator<'a> {
+    /// Both players follow the uniform policy by default.
+    pub fn new(rollouts: usize, seed: u64) -> Self {
+        Self {
+            policies: [Box::new(UniformPolicy), Box::new(UniformPolicy)],
+            rollouts: rollouts.max(1),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Makes both players follow the given policy.
+    pub fn with_policy(self, policy: impl RolloutPolicy + Clone + 'a) -> Self {
+        self.with_policies(policy.clone(), policy)
+    }
+
+    pub fn with_policies(
+        mut self,
+        mine: impl RolloutPolicy + 'a,
+        yours: impl RolloutPolicy + 'a,
+    ) -> Self {
+        self.policies = [Box::new(mine), Box::new(yours)];
+        self
+    }
+
+    /// Estimates the utility of the first player in a position where
+    /// the hidden information of both players is known.
+    pub fn position_value(&mut self, position: GamePosition) -> EchoResult<Utility> {
+        let mut total = 0.0;
+
+        for _ in 0..self.rollouts {
+            total += self.rollout(position)?.to_utility();
+        }
+
+        Ok(total / self.rollouts as Utility)
+    }
+
+    /// Estimates the utility of the first player at the start of the main
+    /// phase of the given state, dealing random hands for every rollout.
+    pub fn state_value(&mut self, state: &KnownState) -> EchoResult<Utility> {
+        let mut total = 0.0;
+
+        for _ in 0..self.rollouts {
+            let hands = self.deal(state);
+            let position = GamePosition::new(
+                *state,
+                MainPhase::new().to_some_phase(),
+                hands.map(PerPhaseInfo::Main),
+            );
+
+            total += self.rollout(position)?.to_utility();
+        }
+
+        Ok(total / self.rollouts as Utility)
+    }
+
+    /// Estimates the utility of every decision the given player can take in
+    /// some phase. Every rollout deals the hidden information of both players
+    /// from the given (weighted) deals, with the opponent replying according
+    /// to its rollout policy.
+    pub fn decision_values(
+        &mut self,
+        state: &KnownState,
+        phase: &SomePhase,
+        player: Player,
+        deals: &[Deal],
+    ) -> EchoResult<Vec<Utility>> {
+        if deals.is_empty() {
+            return Err(EchoError::InvalidState(
+                "Cannot evaluate decisions without any deal".to_string(),
+            ));
+        }
+
+        let weights: Vec<_> = deals.iter().map(|(_, probability)| *probability).collect();
+        let count = player.select(phase.decision_counts(state));
+        let mut values = Vec::with_capacity(count);
+
+        for decision in 0..count {
+            let mut total = 0.0;
+
+            for _ in 0..self.rollouts {
+                let (hidden, _) = deals[sample(&weights, &mut self.rng)];
+                let position = GamePosition::new(*state, *phase, hidden);
+                let reply = (!player).select_mut(&mut self.policies).decide(
+                    &position,
+                    !player,
+                    &mut self.rng,
+                );
+
+                let decisions = player.order_as([DecisionIndex(decision), reply]);
+                let score = match position.advance(decisions)?.1 {
+                    TurnResult::Finished(score) => score,
+                    TurnResult::Unfinished(next) => self.rollout(next)?,
+                };
+
+                total += score.from_perspective(player).to_utility();
+            }
+
+            values.push(total / self.rollouts as Utility);
+        }
+
+        Ok(values)
+    }
+
+    /// Splits the creatures outside the graveyard between the players at random.
+    fn deal(&mut self, state: &KnownState) -> Pair<CreatureSet> {
+        let hand_size = state.hand_size();
+        let mut creatures: Vec<_> = (!state.graveyard).into_iter().collect();
+        creatures.shuffle(&mut self.rng);
+
+        let mut hands = [CreatureSet::empty(); 2];
+        for (index, creature) in creatures.into_iter().take(2 * hand_size).enumerate() {
+            hands[index / hand_size].insert(creature);
+        }
+
+        hands
+    }
+
+    /// Plays a single game until the end, returning the final score.
+    fn rollout(&mut self, mut position: GamePosition) -> EchoResult<Score> {
+        loop {
+            let decisions = Player::PLAYERS.map(|player| {
+                player
+                    .select_mut(&mut self.policies)
+                    .decide(&position, player, &mut self.rng)
+            });
+
+            match position.advance(decisions)?.1 {
+                TurnResult::Finished(score) => return Ok(score),
+                TurnResult::Unfinished(next) => position = next,
+            }
+        }
+    }
+}
+
+/// Estimates the utility of the first player at the start of the
+/// main phase of the given state, using uniform rollouts.
+pub fn estimate_utility(state: &KnownState, rollouts: usize, seed: u64) -> EchoResult<Utility> {
+    RolloutEvaluator::new(rollouts, seed).state_value(state)
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::battlefield::{Battlefield, Battlefields};
+
+    fn starting_state() -> KnownState {
+        KnownState::new_starting(Battlefields::last_of([
+            Battlefield::Mountain,
+            Battlefield::Glade,
+            Battlefield::Urban,
+            Battlefield::Night,
+        ]))
+    }
+
+    #[test]
+    fn estimates_are_deterministic() {
+        let state = starting_state();
+
+        assert_eq!(
+            estimate_utility(&state, 50, 7).unwrap(),
+            estimate_utility(&state, 50, 7).unwrap()
+        );
+    }
+
+    #[test]
+    fn decided_games_are_valued_exactly() {
+        let mut state = starting_state();
+        state.battlefields.current = Battlefields::COUNT - 1;
+        for creature in &Creature::CREATURES[..2 * state.battlefields.current] {
+            state.graveyard.insert(*creature);
+        }
+
+        state.score = Score(20);
+        assert_eq!(estimate_utility(&state, 20, 0).unwrap(), 1.0);
+
+        state.score = Score(-20);
+        assert_eq!(estimate_utility(&state, 20, 0).unwrap(), -1.0);
+    }
+
+    #[test]
+    fn greedy_rollouts_beat_uniform_ones() {
+        let value = RolloutEvaluator::new(400, 0)
+            .with_policies(GreedyPolicy, UniformPolicy)
+            .state_value(&starting_state())
+            .unwrap();
+
+        assert!(value > 0.1, "Greedy rollouts only reached {value}");
+    }
+}
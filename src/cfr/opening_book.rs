@@ -0,0 +1,329 @@
+//! Opening books: the strategies a blueprint recommends for the very first
+//! decision of the game, extracted into a small text file.
+//!
+//! Blueprints trained from the start of the game are huge, while lightweight
+//! agents (or the hints shown in the gui) often only care about how to open.
+//! A book is made out of a header, followed by an empty line and one line
+//! for every player and starting hand:
+//! ```text
+//! [Battlefields "NGUL"]
+//!
+//! m WSRB W:G=0.2500 R:A=0.7500
+//! y WSRB S:S=1.0000
+//! ```
+//! Every line contains the player (`m` for me, `y` for you), the starting hand,
+//! and every move with a non-negligible probability of getting played, written
+//! as `<creatures>:<edict>=<probability>`. Cards are denoted by the same letters
+//! as in `game::notation`.
+use super::blueprint::PublicStrategy;
+use super::decision::Probability;
+use super::decision_index::DecisionIndex;
+use super::hidden_index::{HiddenIndex, PerPhaseInfo};
+use super::phase::PhaseTag;
+use crate::error::{EchoError, EchoResult};
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::creature::CreatureSet;
+use crate::game::edict::Edict;
+use crate::game::known_state::KnownState;
+use crate::game::notation::{decode_set, encode_set, Code};
+use crate::game::types::Player;
+use crate::helpers::pair::Pair;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Moves played with a smaller probability are left out of the book.
+const PRECISION: Probability = 0.00005;
+
+/// A single main phase decision, together with the probability of taking it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OpeningMove {
+    pub creatures: CreatureSet,
+    pub edict: Edict,
+    pub probability: Probability,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpeningBook {
+    /// The battlefields the blueprint got trained on.
+    pub battlefields: [Battlefield; Battlefields::COUNT],
+
+    /// The moves recommended for every starting hand of each player.
+    pub lines: Pair<BTreeMap<CreatureSet, Vec<OpeningMove>>>,
+}
+
+impl OpeningBook {
+    /// Extracts the strategies stored at the root of a blueprint.
+    /// The blueprint must have been trained from the start of the game.
+    pub fn from_blueprint(
+        root: &PublicStrategy,
+        battlefields: [Battlefield; Battlefields::COUNT],
+    ) -> EchoResult<Self> {
+        let state = KnownState::new_starting(battlefields);
+        let mut lines: Pair<BTreeMap<_, _>> = Default::default();
+
+        for player in Player::PLAYERS {
+            let matrix = root.get_matrix(player);
+            let hidden_count = HiddenIndex::count(&state, player, PhaseTag::Main)?;
+            let decision_count = DecisionIndex::main_phase_index_count(&state, player)?;
+
+            if matrix.hidden_count() != hidden_count || matrix.decision_count() != decision_count {
+                return Err(EchoError::InvalidState(
+                    "The blueprint does not start at the beginning of the game".to_string(),
+                ));
+            }
+
+            for index in 0..hidden_count {
+                let hand = HiddenIndex(index)
+                    .decode(&state, player, PerPhaseInfo::Main(()))?
+                    .hand;
+                let strategy = matrix.get(HiddenIndex(index)).unwrap();
+                let mut moves = vec![];
+
+                for (decision, probability) in strategy.iter().enumerate() {
+                    if *probability < PRECISION {
+                        continue;
+                    }
+
+                    let (creatures, edict) =
+                        DecisionIndex(decision).decode_main_phase_index(&state, player, hand)?;
+
+                    moves.push(OpeningMove {
+                        creatures,
+                        edict,
+                        probability: *probability,
+                    });
+                }
+
+                player.select_mut(&mut lines).insert(hand, moves);
+            }
+        }
+
+        Ok(Self {
+            battlefields,
+            lines,
+        })
+    }
+
+    /// Returns the probability of taking each decision (indexed by `DecisionIndex`)
+    /// for the first decision of the game, or `None` if the position is not covered.
+    pub fn lookup(
+        &self,
+        state: &KnownState,
+        player: Player,
+        hand: CreatureSet,
+    ) -> Option<Vec<Probability>> {
+        if state.battlefields.all != self.battlefields || state.battlefields.current != 0 {
+            return None;
+        }
+
+        let moves = player.select_ref(&self.lines).get(&hand)?;
+        let mut strategy = vec![0.0; DecisionIndex::main_phase_index_count(state, player).ok()?];
+
+        for opening_move in moves {
+            let index = DecisionIndex::encode_main_phase_index(
+                state,
+                player,
+                hand,
+                opening_move.creatures,
+                opening_move.edict,
+            )
+            .ok()?;
+
+            *strategy.get_mut(index.0)? += opening_move.probability;
+        }
+
+        // Probabilities are rounded when saving the book.
+        let total: Probability = strategy.iter().sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        for probability in &mut strategy {
+            *probability /= total;
+        }
+
+        Some(strategy)
+    }
+}
+
+// {{{ Text format
+impl Display for OpeningMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}={:.4}",
+            encode_set(self.creatures),
+            self.edict.code(),
+            self.probability
+        )
+    }
+}
+
+impl FromStr for OpeningMove {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (creatures, rest) = s.split_once(':').ok_or_else(|| {
+            EchoError::Notation(format!(
+                "Expected a move of the form creatures:edict=p, got {s:?}"
+            ))
+        })?;
+        let (edict, probability) = rest.split_once('=').ok_or_else(|| {
+            EchoError::Notation(format!(
+                "Expected a move of the form creatures:edict=p, got {s:?}"
+            ))
+        })?;
+
+        let edict = match edict.chars().collect::<Vec<_>>()[..] {
+            [code] => Edict::from_code(code)?,
+            _ => {
+                return Err(EchoError::Notation(format!(
+                    "Expected a single edict, got {edict:?}"
+                )))
+            }
+        };
+
+        Ok(Self {
+            creatures: decode_set(creatures)?,
+            edict,
+            probability: probability
+                .parse()
+                .map_err(|_| EchoError::Notation(format!("Invalid probability {probability:?}")))?,
+        })
+    }
+}
+
+impl Display for OpeningBook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let battlefields: String = self.battlefields.iter().map(|b| b.code()).collect();
+
+        writeln!(f, "[Battlefields {battlefields:?}]")?;
+        writeln!(f)?;
+
+        for (player, code) in Player::PLAYERS.into_iter().zip(['m', 'y']) {
+            for (hand, moves) in player.select_ref(&self.lines) {
+                write!(f, "{code} {}", encode_set(*hand))?;
+
+                for opening_move in moves {
+                    write!(f, " {opening_move}")?;
+                }
+
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for OpeningBook {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim);
+
+        let header = lines.next().unwrap_or_default();
+        let battlefields = header
+            .strip_prefix("[Battlefields \"")
+            .and_then(|header| header.strip_suffix("\"]"))
+            .ok_or_else(|| EchoError::Notation(format!("Invalid header line {header:?}")))?
+            .chars()
+            .map(Battlefield::from_code)
+            .collect::<Result<Vec<_>, _>>()?
+            .try_into()
+            .map_err(|_| {
+                EchoError::Notation(format!(
+                    "Expected exactly {} battlefields",
+                    Battlefields::COUNT
+                ))
+            })?;
+
+        let mut book = Self {
+            battlefields,
+            lines: Default::default(),
+        };
+
+        for line in lines.filter(|line| !line.is_empty()) {
+            let mut fields = line.split_whitespace();
+
+            let player = match fields.next() {
+                Some("m") => Player::Me,
+                Some("y") => Player::You,
+                _ => return Err(EchoError::Notation(format!("Invalid line {line:?}"))),
+            };
+
+            let hand = decode_set(fields.next().unwrap_or_default())?;
+            let moves = fields
+                .map(OpeningMove::from_str)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            player.select_mut(&mut book.lines).insert(hand, moves);
+        }
+
+        Ok(book)
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::blueprint::{write_blueprint, BlockId, BlueprintReader};
+    use crate::cfr::generate::GenerationContext;
+    use bumpalo::Bump;
+    use std::io::Cursor;
+
+    #[test]
+    fn books_can_be_extracted_and_parsed() {
+        let battlefields = Battlefields::last_of([
+            Battlefield::Night,
+            Battlefield::Glade,
+            Battlefield::Urban,
+            Battlefield::LastStrand,
+        ]);
+        let state = KnownState::new_starting(battlefields);
+        let allocator = Bump::new();
+
+        // Only the root gets written to the blueprint, so nothing else needs generating.
+        let scope = GenerationContext::new(1, state, &allocator)
+            .with_lazy_expansion()
+            .generate();
+
+        let mut file = vec![];
+        write_blueprint(&scope, &state, 0, &mut file).unwrap();
+        let mut reader = BlueprintReader::new(Cursor::new(file)).unwrap();
+        let root = reader.load(BlockId::ROOT).unwrap();
+
+        let book = OpeningBook::from_blueprint(&root, battlefields).unwrap();
+        let parsed: OpeningBook = book.to_string().parse().unwrap();
+        assert_eq!(parsed.battlefields, battlefields);
+
+        for player in Player::PLAYERS {
+            let matrix = root.get_matrix(player);
+            assert_eq!(
+                player.select_ref(&parsed.lines).len(),
+                matrix.hidden_count()
+            );
+
+            for index in 0..matrix.hidden_count() {
+                let hand = HiddenIndex(index)
+                    .decode(&state, player, PerPhaseInfo::Main(()))
+                    .unwrap()
+                    .hand;
+                let expected = matrix.get(HiddenIndex(index)).unwrap();
+                let strategy = parsed.lookup(&state, player, hand).unwrap();
+
+                for (probability, expected) in strategy.iter().zip(expected) {
+                    assert!((probability - expected).abs() < 1e-3);
+                }
+            }
+        }
+
+        let mut later = state;
+        later.battlefields.current = 1;
+        let hand = *parsed.lines[0].keys().next().unwrap();
+        assert_eq!(parsed.lookup(&later, Player::Me, hand), None);
+        assert!("[Battlefields \"NG\"]".parse::<OpeningBook>().is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use super::decision_index::DecisionIndex;
 use super::hidden_index::{self, HiddenIndex, PerPhaseInfo};
 use super::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
 use crate::game::choice::{FinalMainPhaseChoice, SabotagePhaseChoice};
 use crate::game::creature::{Creature, CreatureSet};
 use crate::game::edict::Edict;
@@ -10,7 +11,7 @@ use crate::game::simulate::BattleContext;
 use crate::game::types::{Player, TurnResult};
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::itertools::{ArrayUnzip, Itercools};
-use crate::helpers::pair::{are_equal, Pair};
+use crate::helpers::pair::{are_equal, for_player, try_for_player, Pair, PairExt};
 use crate::helpers::try_from_iter::TryCollect;
 use derive_more::{Add, AddAssign, Sum};
 use indicatif::HumanBytes;
@@ -80,7 +81,7 @@ impl Debug for PhaseStats {
 }
 // }}}
 // {{{ The Phase trait
-pub trait Phase: Sync + Sized {
+pub trait Phase: Sync + Sized + Copy {
     type Next: Phase;
 
     const TAG: PhaseTag;
@@ -91,7 +92,7 @@ pub trait Phase: Sync + Sized {
         &self,
         state: &S,
         reveal_index: RevealIndex,
-    ) -> Option<Self::Next>;
+    ) -> EchoResult<Self::Next>;
 
     /// Computes the following state given the revealed information.
     ///
@@ -126,7 +127,10 @@ pub trait Phase: Sync + Sized {
     fn decision_counts(&self, state: &KnownState) -> Pair<usize>;
     fn reveal_count(&self, state: &KnownState) -> usize;
     fn hidden_counts<S: KnownStateEssentials>(&self, state: &S) -> Pair<usize> {
-        Player::PLAYERS.map(|player| HiddenIndex::count(state, player, Self::TAG))
+        for_player(|player| {
+            HiddenIndex::count(state, player, Self::TAG)
+                .expect("Reachable states have a representable number of hidden states")
+        })
     }
 
     fn valid_hidden_states(
@@ -139,7 +143,7 @@ pub trait Phase: Sync + Sized {
         state: KnownStateSummary,
         hidden: Pair<hidden_index::HiddenState>,
         decisions: Pair<DecisionIndex>,
-    ) -> Option<(
+    ) -> EchoResult<(
         KnownStateSummary,
         Pair<hidden_index::EncodingInfo>,
         RevealIndex,
@@ -152,11 +156,15 @@ pub trait Phase: Sync + Sized {
     fn pass_to<P>(self, f: impl FnOnce(Self) -> P) -> P {
         f(self)
     }
+
+    /// Wraps the phase inside the respective `SomePhase` variant.
+    fn to_some_phase(self) -> SomePhase;
 }
 // }}}
 // {{{ Phase instances
 // {{{ Main phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MainPhase;
 
 impl MainPhase {
@@ -175,30 +183,35 @@ impl Phase for MainPhase {
     }
 
     fn decision_counts(&self, state: &KnownState) -> Pair<usize> {
-        Player::PLAYERS.map(|player| DecisionIndex::main_phase_index_count(state, player))
+        for_player(|player| {
+            DecisionIndex::main_phase_index_count(state, player)
+                .expect("Reachable states have a representable number of decisions")
+        })
     }
 
     // We offer a more performant implementation than the default one,
     // which makes use of the fact that during the main phase,
     // both players have the same number of possible hidden states!
     fn hidden_counts<S: KnownStateEssentials>(&self, state: &S) -> Pair<usize> {
-        let count = HiddenIndex::count(state, Player::Me, Self::TAG);
+        let count = HiddenIndex::count(state, Player::Me, Self::TAG)
+            .expect("Reachable states have a representable number of hidden states");
 
         [count; 2]
     }
 
     fn reveal_count(&self, state: &KnownState) -> usize {
         RevealIndex::main_phase_count(state.edict_sets())
+            .expect("Reachable states have a representable number of reveals")
     }
 
     fn advance_phase<S: KnownStateEssentials>(
         &self,
         state: &S,
         reveal_index: RevealIndex,
-    ) -> Option<Self::Next> {
+    ) -> EchoResult<Self::Next> {
         let edict_choices = reveal_index.decode_main_phase_reveal(state.edict_sets())?;
 
-        Some(SabotagePhase::new(edict_choices))
+        Ok(SabotagePhase::new(edict_choices))
     }
 
     fn advance_state(
@@ -209,7 +222,10 @@ impl Phase for MainPhase {
     ) -> TurnResult<KnownState> {
         // Sanity check
         for player in Player::PLAYERS {
-            debug_assert!(state.player_edicts(player).len() >= 5 - state.battlefields.current);
+            debug_assert!(
+                state.player_edicts(player).len()
+                    >= Edict::EDICTS.len() - state.battlefields.current
+            );
         }
 
         TurnResult::Unfinished(*state)
@@ -235,43 +251,48 @@ impl Phase for MainPhase {
         state: KnownStateSummary,
         hidden: Pair<hidden_index::HiddenState>,
         decisions: Pair<DecisionIndex>,
-    ) -> Option<(
+    ) -> EchoResult<(
         KnownStateSummary,
         Pair<hidden_index::EncodingInfo>,
         RevealIndex,
     )> {
-        let (creature_choices, edicts) = Player::PLAYERS
-            .try_map(|player| {
-                player.select(decisions).decode_main_phase_index(
-                    &state,
-                    player,
-                    player.select(hidden).hand,
-                )
-            })?
-            .unzip();
-
-        let hidden_info = Player::PLAYERS.map(|player| {
-            PerPhaseInfo::Sabotage(player.select(hidden).hand, player.select(creature_choices))
+        let (creature_choices, edicts) = try_for_player(|player| {
+            player.select(decisions).decode_main_phase_index(
+                &state,
+                player,
+                player.select(hidden).hand,
+            )
+        })?
+        .unzip();
+
+        let hidden_info = hidden.zip_with(creature_choices, |hidden, choice| {
+            PerPhaseInfo::Sabotage(hidden.hand, choice)
         });
 
         let reveal_index = RevealIndex::encode_main_phase_reveal(edicts, state.edict_sets())?;
 
-        Some((state, hidden_info, reveal_index))
+        Ok((state, hidden_info, reveal_index))
     }
 
     fn hidden_index_decoding_info(&self) -> hidden_index::DecodingInfo {
         PerPhaseInfo::Main(())
     }
+
+    #[inline(always)]
+    fn to_some_phase(self) -> SomePhase {
+        PerPhase::Main(self)
+    }
 }
 // }}}
 // {{{ Sabotage phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SabotagePhase {
     pub edict_choices: Pair<Edict>,
 }
 
 impl SabotagePhase {
-    fn new(edict_choices: Pair<Edict>) -> Self {
+    pub fn new(edict_choices: Pair<Edict>) -> Self {
         Self { edict_choices }
     }
 
@@ -284,9 +305,18 @@ impl SabotagePhase {
     /// Returns a pair where the element coresponding to some player is true
     /// if and only if `self.sabotage_status(player)`;
     #[inline(always)]
-    fn sabotage_statuses(&self) -> Pair<bool> {
+    pub fn sabotage_statuses(&self) -> Pair<bool> {
         self.edict_choices.map(|e| e == Edict::Sabotage)
     }
+
+    /// Extracts the creatures both players have chosen during the main phase.
+    fn creature_choices(hidden: Pair<hidden_index::HiddenState>) -> EchoResult<Pair<CreatureSet>> {
+        hidden.try_map(|h| {
+            h.choice.ok_or(EchoError::InvalidState(
+                "The creature choices must be known after the main phase".to_string(),
+            ))
+        })
+    }
 }
 
 impl Phase for SabotagePhase {
@@ -299,7 +329,7 @@ impl Phase for SabotagePhase {
     }
 
     fn decision_counts(&self, state: &KnownState) -> Pair<usize> {
-        Player::PLAYERS.map(|player| {
+        for_player(|player| {
             let status = self.sabotage_status(player);
             DecisionIndex::sabotage_phase_index_count(state, status)
         })
@@ -311,13 +341,14 @@ impl Phase for SabotagePhase {
             state.last_creature_revealer(),
             state.graveyard,
         )
+        .expect("Reachable states have a representable number of reveals")
     }
 
     fn advance_phase<S: KnownStateEssentials>(
         &self,
         state: &S,
         reveal_index: RevealIndex,
-    ) -> Option<Self::Next> {
+    ) -> EchoResult<Self::Next> {
         let (sabotage_choices, revealed_creature) = reveal_index.decode_sabotage_phase_reveal(
             self.sabotage_statuses(),
             state.last_creature_revealer(),
@@ -326,7 +357,7 @@ impl Phase for SabotagePhase {
 
         let next = SeerPhase::new(self.edict_choices, sabotage_choices, revealed_creature);
 
-        Some(next)
+        Ok(next)
     }
 
     fn advance_state(
@@ -345,8 +376,8 @@ impl Phase for SabotagePhase {
         MainPhase::new()
             .valid_hidden_states(state)
             .flat_map(move |info_pairs| {
-                let [a, b] = Player::PLAYERS.map(|player| {
-                    let hand = player.select(info_pairs).get_main();
+                let [a, b] = info_pairs.map_per_player(|player, info| {
+                    let hand = info.get_main();
 
                     hand.subsets_of_size(state.creature_choice_size(player))
                         .map(move |choice| PerPhaseInfo::Sabotage(hand, choice))
@@ -361,12 +392,12 @@ impl Phase for SabotagePhase {
         state: KnownStateSummary,
         hidden: Pair<hidden_index::HiddenState>,
         decisions: Pair<DecisionIndex>,
-    ) -> Option<(
+    ) -> EchoResult<(
         KnownStateSummary,
         Pair<hidden_index::EncodingInfo>,
         RevealIndex,
     )> {
-        let guesses = Player::PLAYERS.try_map(|player| {
+        let guesses = try_for_player(|player| {
             player.select(decisions).decode_sabotage_index(
                 &state,
                 player.select(hidden).hand,
@@ -374,19 +405,18 @@ impl Phase for SabotagePhase {
             )
         })?;
 
-        let choices = hidden.try_map(|h| h.choice)?;
+        let choices = Self::creature_choices(hidden)?;
         let revealed = (!state.last_creature_revealer())
             .select(choices)
             .into_iter()
             .exactly_one()
-            .ok()?;
-
-        let hidden_info = Player::PLAYERS.try_map(|player| {
-            let hand = player.select(hidden).hand;
-            let choice = player.select(choices);
+            .map_err(|_| {
+                EchoError::InvalidState("Exactly one creature must get revealed".to_string())
+            })?;
 
-            Some(PerPhaseInfo::Seer(hand, choice, revealed))
-        })?;
+        let hidden_info = hidden.zip_with(choices, |hidden, choice| {
+            PerPhaseInfo::Seer(hidden.hand, choice, revealed)
+        });
 
         let reveal_index = RevealIndex::encode_sabotage_phase_reveal(
             guesses,
@@ -395,16 +425,22 @@ impl Phase for SabotagePhase {
             state.graveyard(),
         )?;
 
-        Some((state, hidden_info, reveal_index))
+        Ok((state, hidden_info, reveal_index))
     }
 
     fn hidden_index_decoding_info(&self) -> hidden_index::DecodingInfo {
         PerPhaseInfo::Sabotage((), ())
     }
+
+    #[inline(always)]
+    fn to_some_phase(self) -> SomePhase {
+        PerPhase::Sabotage(self)
+    }
 }
 // }}}
 // {{{ Seer phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SeerPhase {
     pub edict_choices: Pair<Edict>,
     pub sabotage_choices: Pair<SabotagePhaseChoice>,
@@ -423,6 +459,15 @@ impl SeerPhase {
             revealed_creature,
         }
     }
+
+    /// The same phase with the sabotage guesses of both players forgotten
+    /// (see `GenerationContext::with_imperfect_recall`).
+    pub fn without_sabotage_choices(self) -> Self {
+        Self {
+            sabotage_choices: [None; 2],
+            ..self
+        }
+    }
 }
 
 impl Phase for SeerPhase {
@@ -445,8 +490,12 @@ impl Phase for SeerPhase {
         RevealIndex::seer_phase_count(state.graveyard)
     }
 
-    fn advance_phase<S: KnownStateEssentials>(&self, _: &S, _: RevealIndex) -> Option<Self::Next> {
-        Some(MainPhase::new())
+    fn advance_phase<S: KnownStateEssentials>(
+        &self,
+        _: &S,
+        _: RevealIndex,
+    ) -> EchoResult<Self::Next> {
+        Ok(MainPhase::new())
     }
 
     fn battle_context(
@@ -531,24 +580,21 @@ impl Phase for SeerPhase {
         state: KnownStateSummary,
         hidden: Pair<hidden_index::HiddenState>,
         decisions: Pair<DecisionIndex>,
-    ) -> Option<(
+    ) -> EchoResult<(
         KnownStateSummary,
         Pair<hidden_index::EncodingInfo>,
         RevealIndex,
     )> {
-        let choices = hidden.try_map(|h| h.choice)?;
-        let final_choices = Player::PLAYERS.try_map(|player| {
+        let choices = SabotagePhase::creature_choices(hidden)?;
+        let final_choices = try_for_player(|player| {
             player
                 .select(decisions)
                 .decode_seer_index(player.select(choices))
         })?;
 
-        let hidden_info = Player::PLAYERS.try_map(|player| {
-            let hand = player.select(hidden).hand;
-            let final_choice = player.select(final_choices);
-
-            Some(PerPhaseInfo::Main(hand - final_choice))
-        })?;
+        let hidden_info = hidden.zip_with(final_choices, |hidden, final_choice| {
+            PerPhaseInfo::Main(hidden.hand - final_choice)
+        });
 
         let reveal_index = RevealIndex::encode_seer_phase_reveal(
             state.last_creature_revealer().select(final_choices),
@@ -566,10 +612,8 @@ impl Phase for SeerPhase {
             result
         };
 
-        let edicts = Player::PLAYERS.map(|player| {
-            let mut result = state.player_edicts(player);
-
-            let [my_creature, your_creature] = player.order_as(final_choices);
+        let edicts = state.edict_sets().map_per_player(|player, mut result| {
+            let [my_creature, your_creature] = final_choices.into_perspective(player);
 
             if my_creature == Creature::Steward && your_creature != Creature::Witch {
                 result.fill();
@@ -583,7 +627,7 @@ impl Phase for SeerPhase {
         let seer_player = Player::PLAYERS
             .into_iter()
             .filter(|player| {
-                let [my_creature, your_creature] = player.order_as(final_choices);
+                let [my_creature, your_creature] = final_choices.into_perspective(*player);
 
                 my_creature == Creature::Seer
                     && your_creature != Creature::Witch
@@ -594,12 +638,17 @@ impl Phase for SeerPhase {
 
         let new_state = KnownStateSummary::new(edicts, graveyard, seer_player);
 
-        Some((new_state, hidden_info, reveal_index))
+        Ok((new_state, hidden_info, reveal_index))
     }
 
     fn hidden_index_decoding_info(&self) -> hidden_index::DecodingInfo {
         PerPhaseInfo::Seer((), (), self.revealed_creature)
     }
+
+    #[inline(always)]
+    fn to_some_phase(self) -> SomePhase {
+        PerPhase::Seer(self)
+    }
 }
 // }}}
 // }}}
@@ -614,7 +663,7 @@ mod tests {
     use crate::game::types::Player;
     use crate::helpers::bitfield::Bitfield;
     use crate::helpers::itertools::Itercools;
-    use crate::helpers::pair::Pair;
+    use crate::helpers::pair::{Pair, PairExt};
     use bumpalo::Bump;
     use itertools::Itertools;
 
@@ -638,11 +687,11 @@ mod tests {
         ];
 
         for infos in phase.valid_hidden_states(state) {
-            let [left, right] = Player::PLAYERS
-                .map(|player| HiddenIndex::encode(&state, player, player.select(infos)));
+            let [left, right] = infos
+                .map_per_player(|player, info| HiddenIndex::encode(&state, player, info).unwrap());
 
-            hidden_index_trackers[0][left.0] = (true, infos[0].forget_main().forget_sabotage());
-            hidden_index_trackers[1][right.0] = (true, infos[1].forget_main().forget_sabotage());
+            hidden_index_trackers[0][left.0] = (true, infos[0].into());
+            hidden_index_trackers[1][right.0] = (true, infos[1].into());
         }
 
         for player in Player::PLAYERS {
@@ -716,7 +765,8 @@ mod tests {
 }
 // }}}
 // {{{ Some phase
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PerPhase<Main, Sabotage, Seer> {
     Main(Main),
     Sabotage(Sabotage),
@@ -725,6 +775,13 @@ pub enum PerPhase<Main, Sabotage, Seer> {
 
 pub type SomePhase = PerPhase<MainPhase, SabotagePhase, SeerPhase>;
 
+/// The info revealed by `SomePhase::advance`, together with the next state,
+/// hidden indices and phase (unless the game is over).
+pub type Advanced = (
+    RevealIndex,
+    TurnResult<(KnownState, Pair<hidden_index::EncodingInfo>, SomePhase)>,
+);
+
 impl<A, B, C> PerPhase<A, B, C> {
     #[inline(always)]
     pub fn tag(&self) -> PhaseTag {
@@ -759,14 +816,14 @@ impl SomePhase {
         &self,
         state: &S,
         reveal_index: RevealIndex,
-    ) -> Option<Self> {
+    ) -> EchoResult<Self> {
         let res = match self {
             Self::Main(inner) => Self::Sabotage(inner.advance_phase(state, reveal_index)?),
             Self::Sabotage(inner) => Self::Seer(inner.advance_phase(state, reveal_index)?),
             Self::Seer(inner) => Self::Main(inner.advance_phase(state, reveal_index)?),
         };
 
-        Some(res)
+        Ok(res)
     }
 
     /// The biggest advane-function so far. Advances some state to the value it takes
@@ -777,10 +834,7 @@ impl SomePhase {
         hidden: Pair<hidden_index::HiddenState>,
         decisions: Pair<DecisionIndex>,
         hopeless_surrenders: bool,
-    ) -> Option<(
-        RevealIndex,
-        TurnResult<(KnownState, Pair<hidden_index::EncodingInfo>, Self)>,
-    )> {
+    ) -> EchoResult<Advanced> {
         let summary = state.to_summary();
         let (next_summary, next_hidden, reveal_index) = per_phase!(self, |inner| inner
             .advance_hidden_indices(summary, hidden, decisions))?;
@@ -798,7 +852,7 @@ impl SomePhase {
             (next_state, next_hidden, next_phase)
         });
 
-        Some((reveal_index, result))
+        Ok((reveal_index, result))
     }
 
     /// Similar to calling the method with the same name on the inner phase object.
@@ -807,6 +861,12 @@ impl SomePhase {
         per_phase!(self, |inner| inner.decision_counts(state))
     }
 
+    /// Similar to calling the method with the same name on the inner phase object.
+    #[inline(always)]
+    pub fn reveal_count(&self, state: &KnownState) -> usize {
+        per_phase!(self, |inner| inner.reveal_count(state))
+    }
+
     /// Returns `true` if the given player has played the sabotage edict
     /// this turn.
     ///
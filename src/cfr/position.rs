@@ -0,0 +1,171 @@
+//! A single value describing everything there is to know about a position:
+//! the public state, the current phase, and the hidden information of both players.
+use super::decision_index::{DecisionIndex, DecodedDecision};
+use super::hidden_index::{EncodingInfo, HiddenState};
+use super::phase::SomePhase;
+use super::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::creature::CreatureSet;
+use crate::game::known_state::KnownState;
+use crate::game::notation::to_notation;
+use crate::game::types::{Player, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamePosition {
+    pub state: KnownState,
+    pub phase: SomePhase,
+    pub hidden: Pair<EncodingInfo>,
+}
+
+impl GamePosition {
+    pub fn new(state: KnownState, phase: SomePhase, hidden: Pair<EncodingInfo>) -> Self {
+        Self {
+            state,
+            phase,
+            hidden,
+        }
+    }
+
+    /// The number of decisions each player can take.
+    #[inline(always)]
+    pub fn decision_counts(&self) -> Pair<usize> {
+        self.phase.decision_counts(&self.state)
+    }
+
+    /// Enumerates every decision the given player can take,
+    /// together with the action it encodes.
+    pub fn legal_decisions(
+        &self,
+        player: Player,
+    ) -> impl Iterator<Item = (DecisionIndex, DecodedDecision)> {
+        DecisionIndex::enumerate(&self.state, &self.phase, player, player.select(self.hidden))
+    }
+
+    /// Moves on to the next phase, once both players have made their decisions.
+    ///
+    /// Returns the information revealed to both players along the way, together
+    /// with the final score (if the game is over) or the next position.
+    pub fn advance(
+        &self,
+        decisions: Pair<DecisionIndex>,
+    ) -> EchoResult<(RevealIndex, TurnResult<GamePosition>)> {
+        let counts = self.decision_counts();
+        if decisions.iter().zip(counts).any(|(d, count)| d.0 >= count) {
+            return Err(EchoError::InvalidDecision(self.phase.tag()));
+        }
+
+        let (reveal_index, result) = self.phase.advance(
+            self.state,
+            self.hidden.map(HiddenState::from_encoding_info),
+            decisions,
+            false,
+        )?;
+
+        let result = match result {
+            TurnResult::Finished(score) => TurnResult::Finished(score),
+            TurnResult::Unfinished((state, hidden, phase)) => {
+                TurnResult::Unfinished(Self::new(state, phase, hidden))
+            }
+        };
+
+        Ok((reveal_index, result))
+    }
+
+    /// Encodes the position as seen by the given player. See `game::notation`.
+    pub fn to_notation(&self, player: Player) -> String {
+        to_notation(&self.state, &self.phase, player, player.select(self.hidden))
+    }
+
+    /// Makes sure the state and the hidden information are consistent.
+    pub fn validate(&self) -> EchoResult<()> {
+        self.state.validate()?;
+
+        for hidden in self.hidden {
+            HiddenState::from_encoding_info(hidden).validate_against(&self.state)?;
+
+            if hidden.tag() != self.phase.tag() {
+                return Err(EchoError::InvalidPosition(format!(
+                    "Expected hidden information for the {:?} phase, found {:?}",
+                    self.phase.tag(),
+                    hidden.tag()
+                )));
+            }
+        }
+
+        let [mine, yours] = self.hidden.map(|hidden| hidden.get_main());
+        if (mine & yours) != CreatureSet::empty() {
+            return Err(EchoError::InvalidPosition(
+                "Both players cannot hold the same creature".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::phase::{MainPhase, PerPhase, Phase};
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::game::notation::from_notation;
+
+    #[test]
+    fn games_can_be_played_through_positions() {
+        let state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        let main_phase = MainPhase::new();
+        let hidden = main_phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        let mut position = GamePosition::new(state, PerPhase::Main(main_phase), hidden);
+        let (parsed_state, parsed_phase, player, _) =
+            from_notation(&position.to_notation(Player::You)).unwrap();
+
+        assert_eq!(position.validate(), Ok(()));
+        assert_eq!((parsed_state, parsed_phase), (state, position.phase));
+        assert_eq!(player, Player::You);
+        assert!(position
+            .advance([DecisionIndex(usize::MAX), DecisionIndex(0)])
+            .is_err());
+
+        loop {
+            let decisions = Player::PLAYERS.map(|player| {
+                let count = player.select(position.decision_counts());
+                let legal: Vec<_> = position.legal_decisions(player).collect();
+
+                assert_eq!(legal.len(), count);
+                legal[count - 1].0
+            });
+
+            match position.advance(decisions).unwrap().1 {
+                TurnResult::Finished(_) => break,
+                TurnResult::Unfinished(next) => position = next,
+            }
+        }
+    }
+
+    #[test]
+    fn inconsistent_positions_are_rejected() {
+        let state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        let main_phase = MainPhase::new();
+        let [mine, yours] = main_phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        let position = GamePosition::new(state, PerPhase::Main(main_phase), [mine, mine]);
+        assert!(position.validate().is_err());
+
+        let hand = yours.get_main();
+        let choice = CreatureSet::singleton(hand.index(0).unwrap());
+        let hidden = [mine, EncodingInfo::Sabotage(hand, choice)];
+        let position = GamePosition::new(state, PerPhase::Main(main_phase), hidden);
+        assert!(position.validate().is_err());
+    }
+}
@@ -1,38 +1,56 @@
-use std::unreachable;
-
+use super::phase::{PerPhase, SomePhase};
+use crate::error::{EchoError, EchoResult};
 use crate::game::choice::SabotagePhaseChoice;
 use crate::game::creature::{Creature, CreatureSet};
 use crate::game::edict::{Edict, EdictSet};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
 use crate::game::types::Player;
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::pair::Pair;
 use crate::helpers::ranged::MixRanged;
 
+/// The information revealed at the end of each phase:
+/// - the edicts played during the main phase
+/// - the sabotage guesses and the creature revealed by
+///   the non seer player during the sabotage phase
+/// - the creature played by the seer player during the seer phase
+pub type DecodedReveal = PerPhase<Pair<Edict>, (Pair<SabotagePhaseChoice>, Creature), Creature>;
+
 /// Encodes all the information revealed at the end of a phase.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RevealIndex(pub usize);
 
 impl RevealIndex {
     // {{{ Main phase
     #[inline(always)]
-    pub fn encode_main_phase_reveal(choices: Pair<Edict>, edicts: Pair<EdictSet>) -> Option<Self> {
-        let index = edicts[1]
-            .indexof(choices[1])?
-            .mix_indexof(choices[0], edicts[0])?;
-
-        Some(Self(index))
+    pub fn encode_main_phase_reveal(
+        choices: Pair<Edict>,
+        edicts: Pair<EdictSet>,
+    ) -> EchoResult<Self> {
+        edicts[1]
+            .indexof(choices[1])
+            .and_then(|index| index.mix_indexof(choices[0], edicts[0]))
+            .map(Self)
+            .ok_or(EchoError::Encode("main phase reveal"))
     }
 
     #[inline(always)]
-    pub fn decode_main_phase_reveal(self, edict_sets: Pair<EdictSet>) -> Option<Pair<Edict>> {
-        let (p2_index, p1_choice) = self.0.unmix_indexof(edict_sets[0])?;
-
-        Some([p1_choice, edict_sets[1].index(p2_index)?])
+    pub fn decode_main_phase_reveal(self, edict_sets: Pair<EdictSet>) -> EchoResult<Pair<Edict>> {
+        self.0
+            .unmix_indexof(edict_sets[0])
+            .and_then(|(p2_index, p1_choice)| Some([p1_choice, edict_sets[1].index(p2_index)?]))
+            .ok_or(EchoError::Decode("main phase reveal"))
     }
 
+    /// Fails if the count does not fit inside an `usize`.
     #[inline(always)]
-    pub fn main_phase_count(player_edicts: Pair<EdictSet>) -> usize {
-        player_edicts[0].len() * player_edicts[1].len()
+    pub fn main_phase_count(player_edicts: Pair<EdictSet>) -> EchoResult<usize> {
+        player_edicts[0]
+            .len()
+            .checked_mul(player_edicts[1].len())
+            .ok_or(EchoError::Overflow("main phase reveals"))
     }
     // }}}
     // {{{ Sabotage phase
@@ -45,32 +63,43 @@ impl RevealIndex {
         seer_player: Player,
         revealed_creature: Creature,
         graveyard: CreatureSet,
-    ) -> Option<Self> {
+    ) -> EchoResult<Self> {
         let possibilities = !graveyard; // Pool of choices for sabotage guesses
         let mut revealed_creature_possibilities = possibilities;
+        let error = EchoError::Encode("sabotage phase reveal");
 
-        assert!(
-            !graveyard.has(revealed_creature),
-            "Revealed creature cannot be in the graveyard"
-        );
+        if graveyard.has(revealed_creature) {
+            return Err(EchoError::InvalidState(
+                "Revealed creature cannot be in the graveyard".to_string(),
+            ));
+        }
 
         // If we are the non seer player, then we revealed
         // `revealed_creature` this turn, which means we would've
         // had no reason to try and sabotage it.
         if let Some(sabotaged_by_non_seer) = (!seer_player).select(sabotage_choices) {
-            revealed_creature_possibilities.remove(sabotaged_by_non_seer);
+            revealed_creature_possibilities.try_remove(sabotaged_by_non_seer)?;
         };
 
-        let mut result = revealed_creature_possibilities.indexof(revealed_creature)?;
+        let mut result = revealed_creature_possibilities
+            .indexof(revealed_creature)
+            .ok_or(error.clone())?;
 
         for player in Player::PLAYERS {
             if let Some(sabotaged) = player.select(sabotage_choices) {
-                assert!(!graveyard.has(sabotaged), "Cannot sabotage a dead creature");
-                result = result.mix_indexof(sabotaged, possibilities)?;
+                if graveyard.has(sabotaged) {
+                    return Err(EchoError::InvalidState(
+                        "Cannot sabotage a dead creature".to_string(),
+                    ));
+                }
+
+                result = result
+                    .mix_indexof(sabotaged, possibilities)
+                    .ok_or(error.clone())?;
             }
         }
 
-        Some(Self(result))
+        Ok(Self(result))
     }
 
     /// Inverse of `encode_sabotage_phase_reveal`.
@@ -79,14 +108,16 @@ impl RevealIndex {
         sabotage_statuses: Pair<bool>,
         seer_player: Player,
         graveyard: CreatureSet,
-    ) -> Option<(Pair<SabotagePhaseChoice>, Creature)> {
+    ) -> EchoResult<(Pair<SabotagePhaseChoice>, Creature)> {
         let possibilities = !graveyard; // Pool of choices for sabotage guesses
         let mut encoded = self.0;
         let mut sabotage_choices = [None; 2];
+        let error = EchoError::Decode("sabotage phase reveal");
 
         for player in Player::PLAYERS.into_iter().rev() {
             if player.select(sabotage_statuses) {
-                let (remaining, sabotaged) = encoded.unmix_indexof(possibilities)?;
+                let (remaining, sabotaged) =
+                    encoded.unmix_indexof(possibilities).ok_or(error.clone())?;
                 encoded = remaining;
                 player.set_selection(&mut sabotage_choices, Some(sabotaged));
             }
@@ -98,19 +129,22 @@ impl RevealIndex {
         // `revealed_creature` this turn, which means we would've
         // had no reason to try and sabotage it.
         if let Some(sabotaged_by_non_seer) = (!seer_player).select(sabotage_choices) {
-            revealed_creature_possibilities.remove(sabotaged_by_non_seer);
+            revealed_creature_possibilities.try_remove(sabotaged_by_non_seer)?;
         };
 
-        let revealed_creature = revealed_creature_possibilities.index(encoded)?;
+        let revealed_creature = revealed_creature_possibilities
+            .index(encoded)
+            .ok_or(error)?;
 
-        Some((sabotage_choices, revealed_creature))
+        Ok((sabotage_choices, revealed_creature))
     }
 
+    /// Fails if the count does not fit inside an `usize`.
     pub fn sabotage_phase_count(
         sabotage_statuses: Pair<bool>,
         forced_seer_player: Player,
         graveyard: CreatureSet,
-    ) -> usize {
+    ) -> EchoResult<usize> {
         // How many times the sabotage card was played this turn
         let mut sabotage_play_count = 0;
 
@@ -126,44 +160,117 @@ impl RevealIndex {
             reveal_possibilities -= 1;
         };
 
+        // Every sabotage guess is made independently of the other one.
         let sabotage_possibilities = (!graveyard).len();
 
-        let sabotage_count = match sabotage_play_count {
-            0 => 1,
-            1 => sabotage_possibilities,
-            2 => sabotage_possibilities * sabotage_possibilities,
-            _ => unreachable!(),
-        };
-
-        reveal_possibilities * sabotage_count
+        sabotage_possibilities
+            .checked_pow(sabotage_play_count)
+            .and_then(|sabotage_count| reveal_possibilities.checked_mul(sabotage_count))
+            .ok_or(EchoError::Overflow("sabotage phase reveals"))
     }
     // }}}
     // {{{ Seer phase
+    /// Encodes the creature played by the seer player during the seer phase.
+    ///
+    /// The `revealed_creature` is the one the non seer player revealed at the
+    /// end of the sabotage phase (see `SeerPhase::revealed_creature`), which
+    /// the seer player cannot possibly have in hand.
     #[inline(always)]
     pub fn encode_seer_phase_reveal(
         creature: Creature,
         graveyard: CreatureSet,
         revealed_creature: Creature,
-    ) -> Option<Self> {
+    ) -> EchoResult<Self> {
         let possibilities = !graveyard - revealed_creature;
-        Some(Self(possibilities.indexof(creature)?))
+        possibilities
+            .indexof(creature)
+            .map(Self)
+            .ok_or(EchoError::Encode("seer phase reveal"))
     }
 
+    /// Inverse of `encode_seer_phase_reveal`.
     #[inline(always)]
     pub fn decode_seer_phase_reveal(
         self,
         graveyard: CreatureSet,
         revealed_creature: Creature,
-    ) -> Option<Creature> {
+    ) -> EchoResult<Creature> {
         let possibilities = !graveyard - revealed_creature;
-        possibilities.index(self.0)
+        possibilities
+            .index(self.0)
+            .ok_or(EchoError::Decode("seer phase reveal"))
     }
 
+    /// One more than the maximum value of `encode_seer_phase_reveal`.
     #[inline(always)]
     pub fn seer_phase_count(graveyard: CreatureSet) -> usize {
         (!graveyard).len() - 1
     }
     // }}}
+    // {{{ Decoding
+    /// Decodes the information revealed at the end of the given phase.
+    pub fn decode<S: KnownStateEssentials>(
+        self,
+        phase: &SomePhase,
+        state: &S,
+    ) -> EchoResult<DecodedReveal> {
+        let result = match phase {
+            PerPhase::Main(_) => PerPhase::Main(self.decode_main_phase_reveal(state.edict_sets())?),
+            PerPhase::Sabotage(inner) => PerPhase::Sabotage(self.decode_sabotage_phase_reveal(
+                inner.sabotage_statuses(),
+                state.last_creature_revealer(),
+                state.graveyard(),
+            )?),
+            PerPhase::Seer(inner) => PerPhase::Seer(
+                self.decode_seer_phase_reveal(state.graveyard(), inner.revealed_creature)?,
+            ),
+        };
+
+        Ok(result)
+    }
+
+    /// Enumerates every valid reveal index at the end of the given phase,
+    /// together with the information it encodes.
+    pub fn iter_for_phase(
+        phase: SomePhase,
+        state: KnownState,
+    ) -> impl Iterator<Item = (Self, DecodedReveal)> {
+        (0..phase.reveal_count(&state)).filter_map(move |index| {
+            let index = Self(index);
+            let decoded = index.decode(&phase, &state).ok()?;
+            Some((index, decoded))
+        })
+    }
+    // }}}
+    // {{{ Descriptions
+    /// Describes the information revealed at the end of the given phase
+    /// in a human readable way (e.g. `Edicts: Gambit / Ambush`).
+    pub fn try_describe(self, state: &KnownState, phase: &SomePhase) -> EchoResult<String> {
+        let result = match self.decode(phase, state)? {
+            PerPhase::Main([mine, yours]) => format!("Edicts: {mine} / {yours}"),
+            PerPhase::Sabotage(([mine, yours], revealed)) => {
+                let guess = |choice: SabotagePhaseChoice| {
+                    choice.map_or("-".to_string(), |creature| creature.to_string())
+                };
+
+                format!(
+                    "Revealed: {revealed}, sabotage guesses: {} / {}",
+                    guess(mine),
+                    guess(yours)
+                )
+            }
+            PerPhase::Seer(creature) => format!("Seer player revealed: {creature}"),
+        };
+
+        Ok(result)
+    }
+
+    /// Same as `try_describe`, except invalid reveals are described as such.
+    pub fn describe(self, state: &KnownState, phase: &SomePhase) -> String {
+        self.try_describe(state, phase)
+            .unwrap_or_else(|_| format!("Invalid reveal #{}", self.0))
+    }
+    // }}}
 }
 
 // {{{ Tests
@@ -227,7 +334,8 @@ mod tests {
                                         [first_sabotage_status, second_sabotage_status],
                                         seer_player,
                                         graveyard,
-                                    );
+                                    )
+                                    .unwrap();
 
                                     assert!(encoded.0 < count, "Encoded value was {}, even though the total count was supposed to be {}", encoded.0, count);
 
@@ -237,10 +345,7 @@ mod tests {
                                         graveyard,
                                     );
 
-                                    assert_eq!(
-                                        decoded,
-                                        Some((sabotage_choices, revealed_creature))
-                                    );
+                                    assert_eq!(decoded, Ok((sabotage_choices, revealed_creature)));
                                 }
                             }
                         }
@@ -249,6 +354,136 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn sabotage_encode_rejects_bad_input() {
+        let graveyard = CreatureSet::singleton(Creature::Wall);
+
+        for (sabotage_choices, revealed_creature) in [
+            ([None, None], Creature::Wall),
+            ([Some(Creature::Wall), None], Creature::Seer),
+        ] {
+            let encoded = RevealIndex::encode_sabotage_phase_reveal(
+                sabotage_choices,
+                Player::Me,
+                revealed_creature,
+                graveyard,
+            );
+
+            assert!(matches!(
+                encoded,
+                Err(EchoError::InvalidState(_) | EchoError::BitNotPresent(_))
+            ));
+        }
+    }
+    // }}}
+    // {{{ Seer
+    #[test]
+    fn seer_decode_encode_inverses() {
+        for graveyard in CreatureSet::members().step_by(7) {
+            if (!graveyard).len() < 2 {
+                continue;
+            }
+
+            for revealed_creature in !graveyard {
+                let count = RevealIndex::seer_phase_count(graveyard);
+                let mut found_max = false;
+
+                for creature in !graveyard - revealed_creature {
+                    let encoded = RevealIndex::encode_seer_phase_reveal(
+                        creature,
+                        graveyard,
+                        revealed_creature,
+                    )
+                    .unwrap();
+
+                    assert!(encoded.0 < count);
+                    assert_eq!(
+                        encoded.decode_seer_phase_reveal(graveyard, revealed_creature),
+                        Ok(creature)
+                    );
+
+                    if encoded.0 + 1 == count {
+                        found_max = true;
+                    }
+                }
+
+                assert!(found_max);
+                assert!(RevealIndex::encode_seer_phase_reveal(
+                    revealed_creature,
+                    graveyard,
+                    revealed_creature
+                )
+                .is_err());
+            }
+        }
+    }
+    // }}}
+    // {{{ Enumeration
+    #[test]
+    fn enumeration_matches_advancing_the_phase() {
+        use crate::cfr::phase::{MainPhase, SabotagePhase, SeerPhase};
+        use crate::game::battlefield::{Battlefield, Battlefields};
+
+        let mut state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        state.graveyard = CreatureSet::singleton(Creature::Wall);
+
+        for phase in [
+            PerPhase::Main(MainPhase::new()),
+            PerPhase::Sabotage(SabotagePhase::new([Edict::Sabotage, Edict::Sabotage])),
+            PerPhase::Seer(SeerPhase::new(
+                [Edict::Gambit, Edict::Ambush],
+                [None, None],
+                Creature::Seer,
+            )),
+        ] {
+            let reveals: Vec<_> = RevealIndex::iter_for_phase(phase, state).collect();
+
+            assert_eq!(reveals.len(), phase.reveal_count(&state));
+
+            for (index, decoded) in reveals {
+                let next = phase.advance_phase(&state, index).unwrap();
+
+                match (decoded, next) {
+                    (PerPhase::Main(edicts), PerPhase::Sabotage(next)) => {
+                        assert_eq!(edicts, next.edict_choices);
+                    }
+                    (PerPhase::Sabotage((choices, revealed)), PerPhase::Seer(next)) => {
+                        assert_eq!(choices, next.sabotage_choices);
+                        assert_eq!(revealed, next.revealed_creature);
+                    }
+                    (PerPhase::Seer(creature), PerPhase::Main(_)) => {
+                        assert!(!state.graveyard.has(creature));
+                        assert_ne!(creature, Creature::Seer);
+                    }
+                    _ => panic!("Decoded the reveal of the wrong phase"),
+                }
+            }
+        }
+    }
+    // }}}
+    // {{{ Descriptions
+    #[test]
+    fn main_phase_reveals_get_described() {
+        use crate::cfr::phase::MainPhase;
+        use crate::game::battlefield::{Battlefield, Battlefields};
+
+        let battlefields = [Battlefield::Night; Battlefields::COUNT];
+        let state = KnownState::new_starting(battlefields);
+        let phase = PerPhase::Main(MainPhase::new());
+
+        let reveal = RevealIndex::encode_main_phase_reveal(
+            [Edict::Gambit, Edict::Sabotage],
+            state.edict_sets(),
+        )
+        .unwrap();
+
+        assert_eq!(reveal.describe(&state, &phase), "Edicts: Gambit / Sabotage");
+        assert_eq!(
+            RevealIndex(1000).describe(&state, &phase),
+            "Invalid reveal #1000"
+        );
+    }
     // }}}
 }
 // }}}
@@ -0,0 +1,195 @@
+//! Post-game review of the decisions some player took.
+//!
+//! Every decision gets re-evaluated using rollouts (see `montecarlo`) over the
+//! deals the player considered possible at the time (see `belief`), estimating
+//! how much utility the player gave up compared to the best decision. When a
+//! strategy (like a blueprint) was available during the game, decisions it
+//! would (almost) never take get flagged as deviations as well.
+use super::best_response::Deal;
+use super::decision::{Probability, Utility};
+use super::decision_index::DecisionIndex;
+use super::hidden_index::EncodingInfo;
+use super::montecarlo::{GreedyPolicy, RolloutEvaluator};
+use super::phase::SomePhase;
+use crate::error::EchoResult;
+use crate::game::known_state::KnownState;
+use crate::game::types::Player;
+
+/// Decisions the strategy takes with a smaller probability count as deviations.
+pub const DEVIATION_THRESHOLD: Probability = 0.05;
+
+/// Everything needed to review some decision after the game.
+#[derive(Debug, Clone)]
+pub struct DecisionRecord {
+    pub state: KnownState,
+    pub phase: SomePhase,
+    pub player: Player,
+    pub hidden: EncodingInfo,
+    pub decision: DecisionIndex,
+
+    /// The strategy recommended at the time, if any.
+    pub strategy: Option<Vec<Probability>>,
+
+    /// The deals the player considered possible at the time.
+    pub deals: Vec<Deal>,
+}
+
+// {{{ Verdicts
+/// How bad a decision was, based on the utility given up (utilities range from -1 to 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verdict {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Verdict {
+    pub fn from_utility_lost(lost: Utility) -> Self {
+        match lost {
+            lost if lost <= 0.001 => Verdict::Best,
+            lost if lost < 0.05 => Verdict::Good,
+            lost if lost < 0.15 => Verdict::Inaccuracy,
+            lost if lost < 0.35 => Verdict::Mistake,
+            _ => Verdict::Blunder,
+        }
+    }
+}
+// }}}
+// {{{ Reviewed decisions
+#[derive(Debug, Clone)]
+pub struct ReviewedDecision {
+    pub record: DecisionRecord,
+
+    /// The estimated utility of every decision available at the time.
+    pub values: Vec<Utility>,
+}
+
+impl ReviewedDecision {
+    pub fn best_decision(&self) -> DecisionIndex {
+        DecisionIndex(
+            self.values
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map_or(0, |(index, _)| index),
+        )
+    }
+
+    /// How much worse the decision taken was compared to the best one.
+    pub fn utility_lost(&self) -> Utility {
+        let best = self.values[self.best_decision().0];
+        (best - self.values[self.record.decision.0]).max(0.0)
+    }
+
+    #[inline(always)]
+    pub fn verdict(&self) -> Verdict {
+        Verdict::from_utility_lost(self.utility_lost())
+    }
+
+    /// The probability the strategy available at the time took the same decision with.
+    pub fn strategy_probability(&self) -> Option<Probability> {
+        let strategy = self.record.strategy.as_ref()?;
+        strategy.get(self.record.decision.0).copied()
+    }
+
+    /// Whether the strategy available at the time would (almost) never take this decision.
+    pub fn is_deviation(&self) -> bool {
+        self.strategy_probability()
+            .is_some_and(|probability| probability < DEVIATION_THRESHOLD)
+    }
+}
+
+/// Reviews the given decisions, using a number of greedy rollouts
+/// for every decision available at every point.
+pub fn review(
+    records: Vec<DecisionRecord>,
+    rollouts: usize,
+    seed: u64,
+) -> EchoResult<Vec<ReviewedDecision>> {
+    let mut evaluator = RolloutEvaluator::new(rollouts, seed).with_policy(GreedyPolicy);
+
+    records
+        .into_iter()
+        .map(|record| {
+            let values = evaluator.decision_values(
+                &record.state,
+                &record.phase,
+                record.player,
+                &record.deals,
+            )?;
+
+            Ok(ReviewedDecision { record, values })
+        })
+        .collect()
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::belief::Beliefs;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::phase::{MainPhase, Phase};
+    use crate::game::creature::CreatureSet;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::helpers::bitfield::Bitfield;
+
+    fn last_turn_record(decision: DecisionIndex) -> DecisionRecord {
+        let state = last_turn_state();
+
+        let phase = MainPhase::new().to_some_phase();
+        let mut hand = CreatureSet::empty();
+        for creature in (!state.graveyard).into_iter().take(state.hand_size()) {
+            hand.insert(creature);
+        }
+
+        let hidden = EncodingInfo::Main(hand);
+        let deals = Beliefs::new(&state, &phase, Player::Me, hidden)
+            .deals()
+            .to_vec();
+
+        DecisionRecord {
+            state,
+            phase,
+            player: Player::Me,
+            hidden,
+            decision,
+            strategy: Some(vec![0.0; Player::Me.select(phase.decision_counts(&state))]),
+            deals,
+        }
+    }
+
+    #[test]
+    fn best_decisions_lose_nothing() {
+        let record = last_turn_record(DecisionIndex(0));
+        let count = record.strategy.as_ref().unwrap().len();
+        let reviewed = review(vec![record], 20, 0).unwrap().remove(0);
+
+        assert_eq!(reviewed.values.len(), count);
+        assert!(reviewed.utility_lost() >= 0.0);
+        assert!(reviewed.is_deviation());
+
+        let best = review(vec![last_turn_record(reviewed.best_decision())], 20, 0)
+            .unwrap()
+            .remove(0);
+        assert_eq!(best.verdict(), Verdict::Best);
+    }
+
+    #[test]
+    fn verdicts_get_worse_with_the_utility_lost() {
+        let verdicts = [0.0, 0.01, 0.1, 0.2, 1.0].map(Verdict::from_utility_lost);
+
+        assert_eq!(
+            verdicts,
+            [
+                Verdict::Best,
+                Verdict::Good,
+                Verdict::Inaccuracy,
+                Verdict::Mistake,
+                Verdict::Blunder
+            ]
+        );
+    }
+}
@@ -0,0 +1,348 @@
+use super::decision::{store_weight, Weight};
+use crate::error::{EchoError, EchoResult};
+use crate::game::known_state::KnownState;
+use bumpalo::Bump;
+use memmap2::MmapMut;
+use std::cell::{Cell, UnsafeCell};
+use std::collections::hash_map::DefaultHasher;
+use std::fs::OpenOptions;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::mem::size_of;
+use std::path::Path;
+
+// {{{ Header
+/// Identifies the tree some weight file belongs to, such that files
+/// trained for other positions (or builds) do not get silently reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    /// The number of bytes taken up by each weight.
+    weight_size: u64,
+
+    /// Hash of the state at the root of the tree.
+    state_hash: u64,
+
+    /// The number of weights the file has room for.
+    weight_count: u64,
+}
+
+impl Header {
+    const MAGIC: [u8; 8] = *b"ECHOWGHT";
+
+    /// The header is padded such that the weights after it stay aligned.
+    const SIZE: usize = 32;
+
+    fn new(state: &KnownState, weight_count: usize) -> Self {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+
+        Self {
+            weight_size: size_of::<Weight>() as u64,
+            state_hash: hasher.finish(),
+            weight_count: weight_count as u64,
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8]) {
+        bytes[..8].copy_from_slice(&Self::MAGIC);
+        bytes[8..16].copy_from_slice(&self.weight_size.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.state_hash.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.weight_count.to_le_bytes());
+    }
+
+    fn read(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::SIZE || bytes[..8] != Self::MAGIC {
+            return None;
+        }
+
+        let field = |index: usize| {
+            let start = 8 * index;
+            u64::from_le_bytes(bytes[start..start + 8].try_into().unwrap())
+        };
+
+        Some(Self {
+            weight_size: field(1),
+            state_hash: field(2),
+            weight_count: field(3),
+        })
+    }
+
+    /// The size of a file holding the header and all the weights.
+    fn file_size(&self) -> usize {
+        Self::SIZE + (self.weight_size * self.weight_count) as usize
+    }
+}
+// }}}
+
+// {{{ Mapped storage
+/// Storage for decision weights backed by a memory-mapped file.
+///
+/// Trees deeper than a couple of turns do not fit in memory, so this lets the
+/// operating system page weights in and out of disk as needed. Weights are laid
+/// out in the order they get allocated in, and generation is deterministic, so
+/// generating the same tree on top of an existing file gives access to the
+/// weights trained in a previous run.
+///
+/// Files start with a header recording the root state and the number of weights
+/// (see `EstimationContext`), which gets checked when reopening them.
+pub struct MappedStorage {
+    mmap: UnsafeCell<MmapMut>,
+
+    /// Offset (in bytes) of the first unused byte in the mapping.
+    used: Cell<usize>,
+
+    /// Holds the weights which did not fit in the file, such that generation
+    /// can finish before the problem gets reported (see `check`).
+    overflow: Bump,
+}
+
+impl MappedStorage {
+    /// Creates (or overwrites) a file able to hold `weight_count` weights
+    /// for the tree starting at the given state.
+    pub fn create(
+        path: impl AsRef<Path>,
+        state: &KnownState,
+        weight_count: usize,
+    ) -> EchoResult<Self> {
+        let path = path.as_ref();
+        let header = Header::new(state, weight_count);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(|error| io_error(path, error))?;
+
+        file.set_len(header.file_size() as u64)
+            .map_err(|error| io_error(path, error))?;
+
+        let storage = Self::from_file(&file).map_err(|error| io_error(path, error))?;
+
+        // Safety: nothing has been handed out yet.
+        header.write(unsafe { &mut (*storage.mmap.get())[..Header::SIZE] });
+
+        Ok(storage)
+    }
+
+    /// Maps an existing file (usually one trained in a previous run), making
+    /// sure it was created for the same tree (see `create`).
+    pub fn open(
+        path: impl AsRef<Path>,
+        state: &KnownState,
+        weight_count: usize,
+    ) -> EchoResult<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(|error| io_error(path, error))?;
+
+        let storage = Self::from_file(&file).map_err(|error| io_error(path, error))?;
+        let expected = Header::new(state, weight_count);
+
+        // Safety: nothing has been handed out yet.
+        let found = Header::read(unsafe { &*storage.mmap.get() })
+            .ok_or_else(|| EchoError::Weights(format!("{path:?} is not a weight file")))?;
+
+        let mismatch = if found.weight_size != expected.weight_size {
+            Some(format!(
+                "stores weights of {} bytes instead of {}",
+                found.weight_size, expected.weight_size
+            ))
+        } else if found.state_hash != expected.state_hash {
+            Some("was created for a different position".to_string())
+        } else if found.weight_count != expected.weight_count {
+            Some(format!(
+                "holds {} weights, while the tree needs {}",
+                found.weight_count, expected.weight_count
+            ))
+        } else if storage.capacity() != found.file_size() {
+            Some("is truncated".to_string())
+        } else {
+            None
+        };
+
+        match mismatch {
+            Some(reason) => Err(EchoError::Weights(format!("{path:?} {reason}"))),
+            None => Ok(storage),
+        }
+    }
+
+    fn from_file(file: &std::fs::File) -> io::Result<Self> {
+        // Safety: the file is not supposed to be modified by other processes
+        // while training is taking place.
+        let mmap = unsafe { MmapMut::map_mut(file)? };
+
+        Ok(Self {
+            mmap: UnsafeCell::new(mmap),
+            used: Cell::new(Header::SIZE),
+            overflow: Bump::new(),
+        })
+    }
+
+    /// The number of bytes handed out so far.
+    #[inline(always)]
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    #[inline(always)]
+    pub fn capacity(&self) -> usize {
+        // Safety: the mapping itself is never moved or resized.
+        unsafe { (&*self.mmap.get()).len() }
+    }
+
+    /// Whether every weight handed out so far fit in the file.
+    pub fn check(&self) -> EchoResult<()> {
+        if self.overflow.allocated_bytes() > 0 {
+            return Err(EchoError::Weights(
+                "The file backing the weights is too small for this tree".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Hands out a slice of `len` weights which have not been handed out before.
+    /// If the file is not large enough, the weights get allocated in memory
+    /// instead, and `check` starts reporting an error.
+    // The slices handed out never overlap, the same way arena allocations do not.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_weights(&self, len: usize) -> &mut [Weight] {
+        // Everything handed out is made of weights, and the header
+        // is padded accordingly, so this is always aligned.
+        let start = self.used();
+        let end = start + len * size_of::<Weight>();
+
+        if end > self.capacity() {
+            return self.overflow.alloc_slice_fill_copy(len, store_weight(0.0));
+        }
+
+        self.used.set(end);
+
+        // Safety:
+        // - the range is in bounds, properly aligned (mappings are page aligned),
+        //   and never handed out twice, so no two slices can alias
+        // - any bit pattern is a valid weight
+        // - the mapping lives (and stays in place) for as long as `self` does
+        unsafe {
+            let base = (*self.mmap.get()).as_mut_ptr();
+            std::slice::from_raw_parts_mut(base.add(start) as *mut Weight, len)
+        }
+    }
+
+    /// Writes all the changes made so far to disk.
+    pub fn flush(&self) -> io::Result<()> {
+        // Safety: flushing does not touch the contents of the mapping.
+        unsafe { (&*self.mmap.get()).flush() }
+    }
+}
+
+fn io_error(path: &Path, error: io::Error) -> EchoError {
+    EchoError::Weights(format!("Failed to map {path:?}: {error}"))
+}
+// }}}
+// {{{ Weight storage
+/// Where the weights inside decision vectors get allocated.
+#[derive(Clone, Copy)]
+pub enum WeightStorage<'a> {
+    Arena(&'a Bump),
+    Mapped(&'a MappedStorage),
+}
+
+impl<'a> WeightStorage<'a> {
+    /// Allocates a slice of `len` weights.
+    ///
+    /// Weights start out as zeroes, unless they come from a file opened using
+    /// `MappedStorage::open`, in which case they keep their previous contents
+    /// (such that training can be resumed, or strategies queried, across runs).
+    pub fn alloc_weights(self, len: usize) -> &'a [Cell<Weight>] {
+        let weights = match self {
+            Self::Arena(allocator) => allocator.alloc_slice_fill_copy(len, store_weight(0.0)),
+            Self::Mapped(storage) => storage.alloc_weights(len),
+        };
+
+        Cell::from_mut(weights).as_slice_of_cells()
+    }
+
+    /// Whether every weight allocated so far ended up in the intended place.
+    pub fn check(self) -> EchoResult<()> {
+        match self {
+            Self::Arena(_) => Ok(()),
+            Self::Mapped(storage) => storage.check(),
+        }
+    }
+}
+
+impl<'a> From<&'a Bump> for WeightStorage<'a> {
+    fn from(allocator: &'a Bump) -> Self {
+        Self::Arena(allocator)
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::{EstimationContext, GenerationContext};
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("echo-{}-{name}.weights", std::process::id()))
+    }
+
+    #[test]
+    fn weight_files_only_open_for_the_same_tree() {
+        let path = temp_path("reopen");
+        let state = last_turn_state();
+        let count = EstimationContext::new(1, state)
+            .estimate()
+            .total()
+            .total_weights;
+
+        {
+            let allocator = Bump::new();
+            let storage = MappedStorage::create(&path, &state, count).unwrap();
+            let context = GenerationContext::new(1, state, &allocator)
+                .with_weight_storage(WeightStorage::Mapped(&storage));
+            context.generate();
+
+            assert_eq!(context.check_weights(), Ok(()));
+            storage.flush().unwrap();
+        }
+
+        assert!(MappedStorage::open(&path, &state, count).is_ok());
+        assert!(MappedStorage::open(&path, &state, count + 1).is_err());
+
+        let mut other = state;
+        other.score.0 += 1;
+        assert!(MappedStorage::open(&path, &other, count).is_err());
+
+        std::fs::write(&path, b"not a weight file").unwrap();
+        assert!(MappedStorage::open(&path, &state, count).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn small_weight_files_report_an_error() {
+        let path = temp_path("small");
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let storage = MappedStorage::create(&path, &state, 16).unwrap();
+        let context = GenerationContext::new(1, state, &allocator)
+            .with_weight_storage(WeightStorage::Mapped(&storage));
+        context.generate();
+
+        assert!(matches!(
+            context.check_weights(),
+            Err(EchoError::Weights(_))
+        ));
+
+        drop(storage);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
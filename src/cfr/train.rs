@@ -1,77 +1,788 @@
 use rand::distributions::Uniform;
 use rand::prelude::Distribution;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
 
-use super::decision::{DecisionVector, Probability, Scope, Utility};
+use super::best_response;
+use super::blueprint::{BlockId, BlueprintReader};
+use super::decision::{
+    DecisionMatrices, DecisionMatrix, DecisionVector, Probability, Scope, SkippedVisits,
+    UnexploredScope, Utility, WeightUpdates,
+};
+use super::endgame::EndgameTable;
+use super::exploitability::{estimate_exploitability, ExploitabilityEstimate, LocalBestResponse};
+use super::gpu::{CpuBackend, RegretBatch, StrategyBackend, StrategyBatch};
 use super::hidden_index::{self, HiddenIndex, HiddenState};
-use super::phase::{MainPhase, Phase};
+use super::leaves::LeafEvaluator;
+use super::phase::{MainPhase, Phase, PhaseTag};
+use super::reveal_index::RevealIndex;
 use crate::cfr::decision_index::DecisionIndex;
+use crate::error::EchoResult;
+use crate::game::known_state::KnownState;
 use crate::game::known_state_summary::KnownStateSummary;
 use crate::game::types::Player;
 use crate::helpers::pair::Pair;
+use crate::helpers::sampling::sample;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, Read, Seek};
+use std::time::{Duration, Instant};
 use std::{debug_assert_eq, println, unreachable};
+use tracing::Level;
+
+// {{{ Telemetry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryFormat {
+    Csv,
+    /// One json object per line.
+    JsonLines,
+}
+
+/// Statistics collected during a single training iteration.
+#[derive(Debug, Clone, Copy)]
+pub struct IterationStats {
+    pub iteration: usize,
+
+    /// Average utility (from the perspective of the first player)
+    /// over the hidden states visited this iteration.
+    pub average_utility: Utility,
+
+    /// Sum over both players of the average (positive) regret of the best
+    /// decision at the root, per visit of the respective node.
+    /// CFR bounds the exploitability in terms of these regrets,
+    /// so this should approach 0 as training converges.
+    pub exploitability_estimate: Utility,
+
+    /// Sum of the best response values of both players against the average
+    /// strategies (see `best_response::nash_gap`). Only computed every
+    /// couple of iterations, as doing so is about as expensive as training.
+    pub nash_gap: Option<Utility>,
+
+    /// Cheaper estimate of the nash gap using local best responses (see
+    /// `exploitability`). Only computed every couple of iterations as well.
+    pub sampled_exploitability: Option<ExploitabilityEstimate>,
+
+    /// Number of explored scopes visited this iteration.
+    pub node_touches: usize,
+
+    /// Time elapsed since training started.
+    pub elapsed: Duration,
+}
+
+/// Writes per-iteration statistics somewhere, such that convergence
+/// can be plotted using external tools.
+pub struct Telemetry {
+    format: TelemetryFormat,
+    writer: Box<dyn io::Write>,
+    wrote_header: bool,
+}
+
+impl Telemetry {
+    pub fn new(format: TelemetryFormat, writer: impl io::Write + 'static) -> Self {
+        Self {
+            format,
+            writer: Box::new(writer),
+            wrote_header: false,
+        }
+    }
+
+    fn write(&mut self, stats: IterationStats) -> io::Result<()> {
+        let IterationStats {
+            iteration,
+            average_utility,
+            exploitability_estimate,
+            nash_gap,
+            sampled_exploitability,
+            node_touches,
+            elapsed,
+        } = stats;
+        let elapsed = elapsed.as_secs_f64();
+        let (exploitability, margin) = match sampled_exploitability {
+            Some(estimate) => (Some(estimate.value), Some(estimate.margin)),
+            None => (None, None),
+        };
+
+        match self.format {
+            TelemetryFormat::Csv => {
+                if !self.wrote_header {
+                    self.wrote_header = true;
+                    writeln!(
+                        self.writer,
+                        "iteration,average_utility,exploitability_estimate,nash_gap,sampled_exploitability,sampled_exploitability_margin,node_touches,elapsed"
+                    )?;
+                }
+
+                let optional =
+                    |value: Option<Utility>| value.map_or(String::new(), |value| value.to_string());
+                let (nash_gap, exploitability, margin) = (
+                    optional(nash_gap),
+                    optional(exploitability),
+                    optional(margin),
+                );
+                writeln!(
+                    self.writer,
+                    "{iteration},{average_utility},{exploitability_estimate},{nash_gap},{exploitability},{margin},{node_touches},{elapsed}"
+                )
+            }
+            TelemetryFormat::JsonLines => {
+                let (average_utility, exploitability_estimate) = (
+                    json_number(Some(average_utility)),
+                    json_number(Some(exploitability_estimate)),
+                );
+                let (nash_gap, exploitability, margin) = (
+                    json_number(nash_gap),
+                    json_number(exploitability),
+                    json_number(margin),
+                );
+                writeln!(
+                    self.writer,
+                    "{{\"iteration\":{iteration},\"average_utility\":{average_utility},\"exploitability_estimate\":{exploitability_estimate},\"nash_gap\":{nash_gap},\"sampled_exploitability\":{exploitability},\"sampled_exploitability_margin\":{margin},\"node_touches\":{node_touches},\"elapsed\":{elapsed}}}"
+                )
+            }
+        }
+    }
+}
+
+/// Formats a value as a json number. Json has no way of representing
+/// `NaN` or infinities, so those get written as `null`, just like missing values.
+fn json_number(value: Option<Utility>) -> String {
+    match value {
+        Some(value) if value.is_finite() => value.to_string(),
+        _ => "null".to_string(),
+    }
+}
+// }}}
+
+// {{{ Warm starting
+/// Controls how a saved strategy gets loaded into a fresh tree (see `warm_start`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarmStart {
+    /// The saved strategy is worth roughly this many iterations at the root.
+    pub weight: f32,
+
+    /// Always take the most likely decision of the saved strategy.
+    pub purify: bool,
+}
+
+impl WarmStart {
+    pub const DEFAULT_WEIGHT: f32 = 100.0;
+}
+
+impl Default for WarmStart {
+    fn default() -> Self {
+        Self {
+            weight: Self::DEFAULT_WEIGHT,
+            purify: false,
+        }
+    }
+}
+// }}}
+
+// {{{ Regret based pruning
+/// Controls which decisions get skipped by regret based pruning (see `with_regret_pruning`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegretPruning {
+    /// Decisions get pruned once their regret drops below this (negative) amount.
+    pub threshold: Utility,
+
+    /// Upper bound on the amount of regret a decision can gain every time its
+    /// node gets visited. Decisions get skipped for as many visits as it would
+    /// take them to make up for their regret at this rate, such that they cannot
+    /// start being played before they get revisited. Utilities range from `-1`
+    /// to `1`, so `2` is safe as long as reach probabilities do not exceed `1`.
+    /// Chance sampling scales both this and the threshold by its importance
+    /// weight (see `scaled`), which keeps the bound intact.
+    pub recovery: Utility,
+
+    /// Decisions get revisited after being skipped at most this many times.
+    pub max_skip: usize,
+}
+
+impl RegretPruning {
+    /// Adapts the options to reach probabilities which go up to `weight`
+    /// instead of `1`, as is the case when importance sampling deals.
+    pub fn scaled(self, weight: Probability) -> Self {
+        Self {
+            threshold: self.threshold * weight,
+            recovery: self.recovery * weight,
+            ..self
+        }
+    }
+}
+
+impl Default for RegretPruning {
+    fn default() -> Self {
+        Self {
+            threshold: -10.0,
+            recovery: 2.0,
+            max_skip: 1000,
+        }
+    }
+}
+// }}}
+// {{{ Branches
+/// Restricts training to some of the subtrees of the root (see `cs_cfr_branches`).
+struct Branches<'a> {
+    /// Whether we train the subtree behind each reveal index of the root.
+    owned: &'a [bool],
+
+    /// Samples lines of play through the subtrees we do not train.
+    rng: RefCell<&'a mut dyn RngCore>,
+}
+
+impl<'a> Branches<'a> {
+    fn owns(&self, reveal_index: RevealIndex) -> bool {
+        self.owned.get(reveal_index.0).copied().unwrap_or(false)
+    }
+}
+// }}}
+// {{{ Deal batches
+/// Deals sharing the same public state (see `vectorized_cfr`). The reach
+/// probabilities of every player are stored as a single contiguous array,
+/// indexed the same way as the deals.
+#[derive(Default)]
+struct DealBatch {
+    hidden: Vec<Pair<hidden_index::EncodingInfo>>,
+    reach: Pair<Vec<Probability>>,
+}
+
+impl DealBatch {
+    fn push(&mut self, hidden: Pair<hidden_index::EncodingInfo>, reach: Pair<Probability>) {
+        self.hidden.push(hidden);
+        self.reach[0].push(reach[0]);
+        self.reach[1].push(reach[1]);
+    }
+
+    fn len(&self) -> usize {
+        self.hidden.len()
+    }
+}
+// }}}
 
 // TODO: implement resetting of weights halfway through training.
 pub struct TrainingContext {
     enable_pruning: bool,
+
+    /// Reach probabilities below this get pruned (when pruning is enabled).
+    pruning_threshold: Probability,
+    telemetry: Option<Telemetry>,
+
+    /// Every how many iterations to compute the nash gap (if at all).
+    nash_gap_interval: Option<usize>,
+
+    /// Every how many iterations to estimate the exploitability (if at all), and how.
+    sampled_exploitability: Option<(usize, LocalBestResponse)>,
+
+    /// Samples the games played when estimating the exploitability.
+    exploitability_rng: StdRng,
+
+    /// Values the scopes left unexplored (see `with_leaf_evaluator`).
+    leaves: Option<Box<dyn LeafEvaluator>>,
+
+    /// Number of explored scopes visited since the last telemetry row got written.
+    node_touches: Cell<usize>,
+
+    /// Skips decisions with very negative regret (see `with_regret_pruning`).
+    regret_pruning: Option<RegretPruning>,
+
+    /// Number of iterations whose weight updates have been collected so far.
+    /// Seeds the rounding of half precision weights (see `WeightUpdates`).
+    weight_update_count: Cell<u64>,
+
+    /// Performs the weight updates of `vectorized_cfr` (see `with_backend`).
+    backend: RefCell<Box<dyn StrategyBackend>>,
 }
 
 impl TrainingContext {
+    pub const DEFAULT_PRUNING_THRESHOLD: Probability = 0.00000001;
+
+    /// Nodes with at most this many decisions keep their temporary regrets on the stack.
+    const STACK_REGRETS: usize = 128;
+
     pub fn new(enable_pruning: bool) -> Self {
-        Self { enable_pruning }
+        Self {
+            enable_pruning,
+            pruning_threshold: Self::DEFAULT_PRUNING_THRESHOLD,
+            telemetry: None,
+            nash_gap_interval: None,
+            sampled_exploitability: None,
+            exploitability_rng: StdRng::seed_from_u64(0),
+            leaves: None,
+            node_touches: Cell::new(0),
+            regret_pruning: None,
+            weight_update_count: Cell::new(0),
+            backend: RefCell::new(Box::new(CpuBackend)),
+        }
+    }
+
+    pub fn with_pruning_threshold(mut self, pruning_threshold: Probability) -> Self {
+        self.pruning_threshold = pruning_threshold;
+        self
+    }
+
+    /// Writes statistics about every iteration using the given telemetry.
+    pub fn with_telemetry(mut self, telemetry: Telemetry) -> Self {
+        self.telemetry = Some(telemetry);
+        self
+    }
+
+    /// Computes and logs the nash gap every `interval` iterations.
+    pub fn with_nash_gap_interval(mut self, interval: usize) -> Self {
+        self.nash_gap_interval = Some(interval).filter(|interval| *interval > 0);
+        self
+    }
+
+    /// Estimates and logs the exploitability every `interval` iterations by playing
+    /// games using local best responses (see `exploitability`). On large trees,
+    /// this is much cheaper than computing the nash gap.
+    pub fn with_sampled_exploitability(
+        mut self,
+        interval: usize,
+        options: LocalBestResponse,
+        seed: u64,
+    ) -> Self {
+        self.sampled_exploitability =
+            Some((interval, options)).filter(|(interval, _)| *interval > 0);
+        self.exploitability_rng = StdRng::seed_from_u64(seed);
+        self
     }
 
-    pub fn cfr(&self, scope: &mut Scope, state: KnownStateSummary, iterations: usize) {
+    /// Uses exact solutions (see `endgame`) as the values of final turns left
+    /// unexplored, instead of pretending the game ends before they get played.
+    /// Useful for trees which only get generated up to the final turn.
+    pub fn with_endgame_leaves(self, endgame: EndgameTable) -> Self {
+        self.with_leaf_evaluator(endgame)
+    }
+
+    /// Values the leaves of depth-limited trees (the main phases of the turns
+    /// past the turn limit) using the given evaluator, instead of pretending
+    /// the game ends before they get played (see `leaves`).
+    pub fn with_leaf_evaluator(mut self, evaluator: impl LeafEvaluator + 'static) -> Self {
+        self.leaves = Some(Box::new(evaluator));
+        self
+    }
+
+    /// Skips decisions whose regret is deeply negative for a number of visits
+    /// proportional to how negative it is, revisiting them afterwards. Unlike
+    /// pruning based on reach probabilities, this skips entire subtrees in the
+    /// later stages of training, when most decisions are never taken anymore.
+    ///
+    /// Only prunes trees generated using `GenerationContext::with_regret_pruning`.
+    pub fn with_regret_pruning(mut self, options: RegretPruning) -> Self {
+        self.regret_pruning = Some(options);
+        self
+    }
+
+    /// Performs the regret matching and regret computations of `vectorized_cfr`
+    /// using the given backend (see `gpu`). Falls back to the cpu if the backend
+    /// ever fails. The other variants always update weights one node at a time.
+    pub fn with_backend(mut self, backend: Box<dyn StrategyBackend>) -> Self {
+        self.backend = RefCell::new(backend);
+        self
+    }
+
+    pub fn cfr(&mut self, scope: &mut Scope, state: KnownStateSummary, iterations: usize) {
+        let start = Instant::now();
+
+        for i in 0..iterations {
+            println!("Iteration {i}");
+
+            let utility = self.cfr_iteration(scope, state);
+            self.record_iteration(scope, state, i, utility, start);
+        }
+    }
+
+    /// Runs a single iteration of `cfr`, returning the average utility over every deal.
+    pub(super) fn cfr_iteration(&self, scope: &mut Scope, state: KnownStateSummary) -> Utility {
         let probabilities: Pair<Probability> = [1.0; 2];
         let phase = MainPhase::new();
+        let updates = self.weight_updates();
+
+        let mut utility = 0.0;
+        let mut samples = 0;
+
+        for hidden in phase.valid_hidden_states(state) {
+            utility += self
+                .train_phase(scope, phase, state, hidden, probabilities, None, &updates)
+                .unwrap_or_default();
+            samples += 1;
+        }
+
+        updates.flush();
+        utility / samples as Utility
+    }
+
+    /// Same as `cfr`, except every public state gets visited once per iteration,
+    /// together with all the deals reaching it (instead of once for every deal).
+    ///
+    /// Strategies, reach probabilities and utilities are processed as
+    /// contiguous arrays over the deals, which is much friendlier to the
+    /// cache. Regrets only get updated once every deal has been processed,
+    /// so (unlike with `cfr`) the deals visited first do not influence the
+    /// strategy used for the rest of the iteration. Regret based pruning
+    /// and restricting training to some branches are not supported.
+    ///
+    /// Regret matching and the regrets of every deal are computed
+    /// using the backend of the context (see `with_backend`).
+    pub fn vectorized_cfr(
+        &mut self,
+        scope: &mut Scope,
+        state: KnownStateSummary,
+        iterations: usize,
+    ) {
+        let start = Instant::now();
+
         for i in 0..iterations {
             println!("Iteration {i}");
 
-            for hidden in phase.valid_hidden_states(state) {
-                self.train_phase(scope, phase, state, hidden, probabilities);
-            }
+            let utility = self.vectorized_cfr_iteration(scope, state);
+            self.record_iteration(scope, state, i, utility, start);
         }
     }
 
+    /// Runs a single iteration of `vectorized_cfr`, returning the average utility over every deal.
+    fn vectorized_cfr_iteration(&self, scope: &mut Scope, state: KnownStateSummary) -> Utility {
+        let phase = MainPhase::new();
+        let updates = self.weight_updates();
+        let mut deals = DealBatch::default();
+
+        for hidden in phase.valid_hidden_states(state) {
+            deals.push(hidden, [1.0; 2]);
+        }
+
+        let utilities = self.train_batch(scope, phase, state, &deals, &updates);
+        updates.flush();
+        utilities.iter().sum::<Utility>() / utilities.len() as Utility
+    }
+
     /// Chance-sampling counterfactual regret minimization.
     ///
     /// Similar to `cfr`, but focuses on a single (random) initial set of hidden indices.
+    /// Every iteration is much cheaper, but more iterations are required in order
+    /// to converge. Training is deterministic for a given (seeded) random generator.
     pub fn cs_cfr<R: Rng>(
-        &self,
+        &mut self,
         rng: &mut R,
         scope: &mut Scope,
         state: KnownStateSummary,
         iterations: usize,
     ) {
-        let probabilities: Pair<Probability> = [1.0; 2];
+        self.sampled_iterations(rng, scope, state, iterations, None)
+    }
+
+    /// Like `cs_cfr`, except only the root and the subtrees behind the owned
+    /// reveal indices of the root get updated. The values of the other subtrees
+    /// are estimated by sampling a single line of play using the current
+    /// strategies, which is unbiased, but much cheaper than traversing them.
+    /// Used for splitting training between machines (see `distributed`).
+    pub(super) fn cs_cfr_branches<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        scope: &mut Scope,
+        state: KnownStateSummary,
+        iterations: usize,
+        owned: &[bool],
+    ) {
+        self.sampled_iterations(rng, scope, state, iterations, Some(owned))
+    }
+
+    fn sampled_iterations<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        scope: &mut Scope,
+        state: KnownStateSummary,
+        iterations: usize,
+        owned: Option<&[bool]>,
+    ) {
         let phase = MainPhase::new();
 
         // TODO: consider not allocating?
         let hidden_vec: Vec<_> = phase.valid_hidden_states(state).collect();
         let distribution = Uniform::new(0, hidden_vec.len());
 
+        // Deals are sampled uniformly, which `cfr` weighs equally. Every update
+        // gets multiplied by exactly one of the reach probabilities, so scaling
+        // both by the inverse of the sampling probability makes the expected
+        // updates match those performed by `cfr`, keeping regrets comparable.
+        let importance_weight = hidden_vec.len() as Probability;
+        let probabilities: Pair<Probability> = [importance_weight; 2];
+        let start = Instant::now();
+
+        // The regrets get scaled the same way, so the pruning options must follow.
+        let regret_pruning = self.regret_pruning;
+        self.regret_pruning = regret_pruning.map(|options| options.scaled(importance_weight));
+
         for i in 0..iterations {
             if i % 10 == 0 {
                 println!("Iteration {i}");
             }
 
             let index = distribution.sample(rng);
-            self.train_phase(scope, phase, state, hidden_vec[index], probabilities);
+            let branches = owned.map(|owned| Branches {
+                owned,
+                rng: RefCell::new(&mut *rng),
+            });
+
+            let updates = self.weight_updates();
+            let utility = self
+                .train_phase(
+                    scope,
+                    phase,
+                    state,
+                    hidden_vec[index],
+                    probabilities,
+                    branches.as_ref(),
+                    &updates,
+                )
+                .unwrap_or_default();
+            updates.flush();
+
+            self.record_iteration(scope, state, i, utility, start);
         }
+
+        self.regret_pruning = regret_pruning;
+    }
+
+    /// Collects the weight updates performed by a new iteration.
+    /// The updates need to be flushed once the iteration is over.
+    fn weight_updates<'a>(&self) -> WeightUpdates<'a> {
+        let count = self.weight_update_count.get();
+        self.weight_update_count.set(count + 1);
+        WeightUpdates::new(count)
     }
 
-    fn train_phase<P: Phase>(
+    // {{{ Warm starting
+    /// Initializes the weights of a freshly generated tree from a saved blueprint,
+    /// such that training can pick up where some earlier run left off (e.g. after
+    /// tweaking the rules, or when increasing the iteration budget).
+    ///
+    /// Both the regret and strategy sums are set to the saved strategy times the
+    /// weight, which makes it both the current and the average strategy. Reach
+    /// probabilities are not known at this point, so deeper nodes get the same
+    /// weight as the root. Scopes the blueprint does not reach keep their weights,
+    /// while scopes with a different shape are treated as an error.
+    ///
+    /// Returns the number of scopes which got initialized.
+    pub fn warm_start<R: Read + Seek>(
         &self,
-        scope: &mut Scope,
+        scope: &Scope,
+        blueprint: &mut BlueprintReader<R>,
+        options: WarmStart,
+    ) -> io::Result<usize> {
+        if options.weight <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The warm start weight must be positive",
+            ));
+        }
+
+        if blueprint.is_empty() {
+            return Ok(0);
+        }
+
+        let shape_error = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The blueprint does not match the shape of the tree",
+            )
+        };
+
+        let mut queue = VecDeque::from([(scope, BlockId::ROOT)]);
+        let mut initialized = 0;
+
+        while let Some((scope, id)) = queue.pop_front() {
+            let Some(explored) = scope.get_explored() else {
+                continue;
+            };
+
+            let mut strategy = blueprint.load(id)?;
+            if options.purify {
+                strategy.purify();
+            }
+
+            for player in Player::PLAYERS {
+                let saved = strategy.get_matrix(player);
+                let matrix = explored.matrices.get_matrix(player);
+
+                if saved.decision_count() != matrix.len() {
+                    return Err(shape_error());
+                }
+
+                let DecisionMatrix::Expanded(vectors) = matrix else {
+                    continue;
+                };
+
+                if saved.hidden_count() != vectors.len() {
+                    return Err(shape_error());
+                }
+
+                for (index, vector) in vectors.iter().enumerate() {
+                    let weights: Vec<_> = saved
+                        .get(HiddenIndex(index))
+                        .unwrap()
+                        .iter()
+                        .map(|probability| probability * options.weight)
+                        .collect();
+
+                    vector.set_regrets(&weights);
+                    vector.set_strategy_sums(&weights);
+                    vector.recompute_regret_magnitude();
+                }
+            }
+
+            initialized += 1;
+
+            for (index, child) in explored.next.iter().enumerate() {
+                if let Some(id) = strategy.next(RevealIndex(index)) {
+                    queue.push_back((child, id));
+                }
+            }
+        }
+
+        Ok(initialized)
+    }
+    // }}}
+    // {{{ Telemetry
+    /// Writes a telemetry row for the given iteration (if telemetry is enabled),
+    /// logging the nash gap as well if it is due this iteration.
+    fn record_iteration(
+        &mut self,
+        scope: &Scope,
+        state: KnownStateSummary,
+        iteration: usize,
+        average_utility: Utility,
+        start: Instant,
+    ) {
+        let node_touches = self.node_touches.replace(0);
+        let nash_gap = self
+            .nash_gap_interval
+            .filter(|interval| iteration % interval == interval - 1)
+            .map(|_| best_response::nash_gap(scope, state));
+
+        if let Some(nash_gap) = nash_gap {
+            println!("Nash gap after {} iterations: {nash_gap}", iteration + 1);
+            tracing::event!(Level::DEBUG, iteration, nash_gap, "Computed nash gap");
+        }
+
+        let sampled_exploitability = self
+            .sampled_exploitability
+            .filter(|(interval, _)| iteration % interval == interval - 1)
+            .map(|(_, options)| {
+                estimate_exploitability(scope, state, options, &mut self.exploitability_rng)
+            });
+
+        if let Some(ExploitabilityEstimate { value, margin }) = sampled_exploitability {
+            println!(
+                "Sampled exploitability after {} iterations: {value} ± {margin}",
+                iteration + 1
+            );
+            tracing::event!(
+                Level::DEBUG,
+                iteration,
+                value,
+                margin,
+                "Estimated exploitability"
+            );
+        }
+
+        let Some(telemetry) = &mut self.telemetry else {
+            return;
+        };
+
+        let stats = IterationStats {
+            iteration,
+            average_utility,
+            exploitability_estimate: Self::root_regret(scope),
+            nash_gap,
+            sampled_exploitability,
+            node_touches,
+            elapsed: start.elapsed(),
+        };
+
+        if let Err(error) = telemetry.write(stats) {
+            tracing::event!(
+                Level::WARN,
+                "Failed to write telemetry, disabling it: {error}"
+            );
+            self.telemetry = None;
+        }
+    }
+
+    /// Sums (over both players) the average regret of the best decision at the root.
+    ///
+    /// Regrets are divided by the number of times each node was visited
+    /// (which at the root is the same as the sum of its strategy sums).
+    fn root_regret(scope: &Scope) -> Utility {
+        let Some(scope) = scope.get_explored() else {
+            return 0.0;
+        };
+
+        let average_max_regret = |matrix: &DecisionMatrix| match matrix {
+            DecisionMatrix::Trivial => 0.0,
+            DecisionMatrix::Expanded(vectors) => {
+                let total: Utility = vectors
+                    .iter()
+                    .map(|vector| {
+                        let regret = vector.regrets().into_iter().fold(0.0, Utility::max);
+                        let visits: f32 = vector.strategy_sums().into_iter().sum();
+
+                        if visits > 0.0 {
+                            regret / visits
+                        } else {
+                            0.0
+                        }
+                    })
+                    .sum();
+
+                total / vectors.len() as Utility
+            }
+        };
+
+        match &scope.matrices {
+            DecisionMatrices::Symmetrical(matrix) => 2.0 * average_max_regret(matrix),
+            DecisionMatrices::Asymmetrical(matrices) => {
+                matrices.iter().map(average_max_regret).sum()
+            }
+        }
+    }
+    // }}}
+
+    #[allow(clippy::too_many_arguments)]
+    fn train_phase<'a, P: Phase>(
+        &self,
+        scope: &mut Scope<'a>,
         phase: P,
         state: KnownStateSummary,
         hidden: Pair<hidden_index::EncodingInfo>,
         probabilities: Pair<Probability>,
+        branches: Option<&Branches>,
+        updates: &WeightUpdates<'a>,
     ) -> Option<Utility> {
         match scope {
             Scope::Completed(score) => Some(score.to_utility()),
+            Scope::Unexplored(UnexploredScope {
+                expansion: Some(context),
+                ..
+            }) => {
+                let context = *context;
+                *scope = context.expand(phase);
+                self.train_phase(
+                    scope,
+                    phase,
+                    state,
+                    hidden,
+                    probabilities,
+                    branches,
+                    updates,
+                )
+            }
+            Scope::Unexplored(UnexploredScope {
+                state: Some(state), ..
+            }) => Some(self.leaf_value::<P>(state, hidden)),
             Scope::Unexplored(_) => unreachable!("Oops, cannot handle unexplored scopes"),
             Scope::Explored(scope) => {
+                self.node_touches.set(self.node_touches.get() + 1);
+
                 #[cfg(debug_assertions)]
                 debug_assert_eq!(
                     scope.summary, state,
@@ -82,41 +793,70 @@ impl TrainingContext {
                 // {{{ Prepare data
                 let counts = scope.matrices.decision_counts();
                 let hidden_states = hidden.map(HiddenState::from_encoding_info);
-                let indices = Player::PLAYERS
-                    .map(|player| HiddenIndex::encode(&state, player, player.select(hidden)));
+                let indices = Player::PLAYERS.map(|player| {
+                    HiddenIndex::encode(&state, player, player.select(hidden))
+                        .expect("Generated scopes only hold encodable hidden states")
+                });
 
-                let mut nodes = scope.matrices.get_nodes_mut(indices);
+                let nodes = scope.matrices.get_nodes(indices);
                 let mut total_utility: Utility = 0.0;
+
+                // The regrets of the second player only get added to the node at the end,
+                // such that its strategy stays the same for every decision of the first player.
+                // Decision counts are small, so we avoid allocating in the common case.
+                let mut stack_regrets = [0.0; Self::STACK_REGRETS];
+                let mut heap_regrets = Vec::new();
+                let your_regrets: &mut [Utility] = if counts[1] <= Self::STACK_REGRETS {
+                    &mut stack_regrets[..counts[1]]
+                } else {
+                    heap_regrets.resize(counts[1], 0.0);
+                    &mut heap_regrets
+                };
                 // }}}
                 // {{{ Compute strategies
-                for (i, node) in nodes.iter_mut().enumerate() {
+                for (i, node) in nodes.iter().enumerate() {
                     if let Some(node) = node {
                         node.recompute_regret_magnitude();
-                        node.update_strategy_sum(probabilities[i]);
+                        updates.update_strategy_sum(node, probabilities[i]);
                     }
                 }
                 // }}}
 
-                if self.enable_pruning && Self::is_almost_zero(probabilities[1]) {
+                if self.enable_pruning && self.is_almost_zero(probabilities[1]) {
                     return Some(0.0);
                 };
 
+                let regret_weights = nodes.map(|node| self.regret_weights(node));
+                let regret_weight = |player: usize, index: usize| {
+                    regret_weights[player].get(index).copied().unwrap_or(1.0)
+                };
+
                 // {{{ First player
                 for index in 0..(counts[0]) {
+                    // Pruned decisions are never taken, so skipping them only
+                    // leaves out the updates to their own regret.
+                    if regret_weight(0, index) == 0.0 {
+                        continue;
+                    }
+
                     let my_decision = DecisionIndex(index);
-                    let my_probability = DecisionVector::try_strategy(nodes[0].as_deref(), index);
+                    let my_probability = DecisionVector::try_strategy(nodes[0], index);
 
                     // {{{ Second player
                     let future_utility = {
-                        if self.enable_pruning && Self::is_almost_zero(probabilities[0]) {
+                        if self.enable_pruning && self.is_almost_zero(probabilities[0]) {
                             0.0
                         } else {
                             let mut total_utility: Utility = 0.0;
 
-                            for index in 0..(counts[1]) {
+                            for (index, your_regret) in your_regrets.iter_mut().enumerate() {
+                                if regret_weight(1, index) == 0.0 {
+                                    continue;
+                                }
+
                                 let your_decision = DecisionIndex(index);
                                 let your_probability =
-                                    DecisionVector::try_strategy(nodes[1].as_deref(), index);
+                                    DecisionVector::try_strategy(nodes[1], index);
 
                                 // {{{ Recursive call
                                 let new_probabilities = [
@@ -131,26 +871,34 @@ impl TrainingContext {
                                     .unwrap();
 
                                 let new_scope = &mut scope.next[reveal_index.0];
-                                let next_phase = phase.advance_phase(&state, reveal_index)?;
-
-                                let future_utility = -self.train_phase::<P::Next>(
-                                    new_scope,
-                                    next_phase,
-                                    new_state,
-                                    new_hidden,
-                                    new_probabilities,
-                                )?;
+                                let next_phase = phase.advance_phase(&state, reveal_index).ok()?;
+
+                                let future_utility = match branches {
+                                    Some(branches) if !branches.owns(reveal_index) => self
+                                        .sample_value::<P::Next>(
+                                            new_scope,
+                                            next_phase,
+                                            new_state,
+                                            new_hidden,
+                                            &mut *branches.rng.borrow_mut(),
+                                        ),
+                                    _ => self.train_phase::<P::Next>(
+                                        new_scope,
+                                        next_phase,
+                                        new_state,
+                                        new_hidden,
+                                        new_probabilities,
+                                        None,
+                                        updates,
+                                    ),
+                                };
+                                let future_utility = -future_utility?;
                                 // }}}
 
                                 total_utility += your_probability * future_utility;
 
                                 // {{{ Add utility to your regret
-                                if let Some(node) = &mut nodes[1] {
-                                    node.accumulate_regret(
-                                        index,
-                                        my_probability * probabilities[0] * future_utility,
-                                    );
-                                }
+                                *your_regret += my_probability * probabilities[0] * future_utility;
                                 // }}}
                             }
 
@@ -162,35 +910,774 @@ impl TrainingContext {
                     total_utility += my_probability * future_utility;
 
                     // {{{ Add utility to my regret
-                    if let Some(node) = &mut nodes[0] {
-                        node.accumulate_regret(index, probabilities[1] * future_utility);
+                    if let Some(node) = nodes[0] {
+                        updates.accumulate_regret(
+                            node,
+                            index,
+                            regret_weight(0, index) * probabilities[1] * future_utility,
+                        );
                     }
                     // }}}
                 }
                 // }}}
                 // {{{ Subtract total utility from regrets
-                if let Some(node) = &mut nodes[0] {
+                if let Some(node) = nodes[0] {
                     for index in 0..counts[0] {
-                        node.accumulate_regret(index, -probabilities[1] * total_utility);
+                        updates.accumulate_regret(
+                            node,
+                            index,
+                            -regret_weight(0, index) * probabilities[1] * total_utility,
+                        );
                     }
                 }
 
-                if let Some(node) = &mut nodes[1] {
-                    for index in 0..counts[1] {
-                        node.accumulate_regret(index, -probabilities[0] * total_utility);
+                // The utility of the second player is the opposite of ours
+                if let Some(node) = nodes[1] {
+                    for (index, regret) in your_regrets.iter().enumerate() {
+                        updates.accumulate_regret(
+                            node,
+                            index,
+                            regret_weight(1, index) * (regret + probabilities[0] * total_utility),
+                        );
                     }
                 }
                 // }}}
 
+                for (node, weights) in nodes.into_iter().zip(&regret_weights) {
+                    self.schedule_regret_pruning(node, weights, updates);
+                }
+
                 Some(total_utility)
             }
         }
     }
 
+    /// Vectorized version of `train_phase`, returning the utility of every deal in the batch.
+    fn train_batch<'a, P: Phase>(
+        &self,
+        scope: &mut Scope<'a>,
+        phase: P,
+        state: KnownStateSummary,
+        deals: &DealBatch,
+        updates: &WeightUpdates<'a>,
+    ) -> Vec<Utility> {
+        match scope {
+            Scope::Completed(score) => vec![score.to_utility(); deals.len()],
+            Scope::Unexplored(UnexploredScope {
+                expansion: Some(context),
+                ..
+            }) => {
+                let context = *context;
+                *scope = context.expand(phase);
+                self.train_batch(scope, phase, state, deals, updates)
+            }
+            Scope::Unexplored(UnexploredScope {
+                state: Some(state), ..
+            }) => deals
+                .hidden
+                .iter()
+                .map(|hidden| self.leaf_value::<P>(state, *hidden))
+                .collect(),
+            Scope::Unexplored(_) => unreachable!("Oops, cannot handle unexplored scopes"),
+            Scope::Explored(scope) => {
+                self.node_touches.set(self.node_touches.get() + 1);
+
+                #[cfg(debug_assertions)]
+                debug_assert_eq!(
+                    scope.summary, state,
+                    "Something went wrong with simulating {:?}",
+                    scope.context
+                );
+
+                // {{{ Prepare data
+                let deal_count = deals.len();
+                let counts = scope.matrices.decision_counts();
+                let hidden_states: Vec<_> = deals
+                    .hidden
+                    .iter()
+                    .map(|hidden| hidden.map(HiddenState::from_encoding_info))
+                    .collect();
+                let nodes: Vec<_> = deals
+                    .hidden
+                    .iter()
+                    .map(|hidden| {
+                        scope.matrices.get_nodes(Player::PLAYERS.map(|player| {
+                            HiddenIndex::encode(&state, player, player.select(*hidden))
+                                .expect("Generated scopes only hold encodable hidden states")
+                        }))
+                    })
+                    .collect();
+
+                // Deals one of the players (almost) never reaches contribute nothing.
+                let active: Vec<_> = (0..deal_count)
+                    .map(|deal| {
+                        !self.enable_pruning
+                            || deals
+                                .reach
+                                .iter()
+                                .all(|reach| !self.is_almost_zero(reach[deal]))
+                    })
+                    .collect();
+                // }}}
+                // {{{ Compute strategies
+                // Every player gets a single `deals × decisions` array.
+                let strategies = [0, 1].map(|player| {
+                    let mut batch = StrategyBatch::new();
+
+                    for (deal, nodes) in nodes.iter().enumerate() {
+                        if let Some(node) = nodes[player] {
+                            batch.push_regrets(&node.regrets(), deals.reach[player][deal]);
+                        }
+                    }
+
+                    let computed = self.run_backend(|backend| backend.accumulate(&mut batch));
+                    let mut strategies = Vec::with_capacity(deal_count * counts[player]);
+                    let mut row = 0;
+
+                    for nodes in &nodes {
+                        match nodes[player] {
+                            Some(node) => {
+                                updates.add_strategy_sums(node, batch.strategy_sum(row));
+                                strategies.extend_from_slice(&computed[batch.range(row)]);
+                                row += 1;
+                            }
+                            None => strategies.push(1.0),
+                        }
+                    }
+
+                    strategies
+                });
+                // }}}
+                // {{{ Recursive calls
+                // The utility of every deal, for every pair of decisions.
+                let pair_count = counts[0] * counts[1];
+                let mut values = vec![0.0; deal_count * pair_count];
+
+                // Deals get grouped by the info revealed once the decisions are taken,
+                // such that every child gets visited once (different decisions often
+                // reveal the same info). Ordered maps keep training deterministic.
+                let mut children = BTreeMap::new();
+
+                for my_index in 0..counts[0] {
+                    for your_index in 0..counts[1] {
+                        let decisions = [DecisionIndex(my_index), DecisionIndex(your_index)];
+
+                        for deal in (0..deal_count).filter(|deal| active[*deal]) {
+                            let (new_state, new_hidden, reveal_index) = phase
+                                .advance_hidden_indices(state, hidden_states[deal], decisions)
+                                .unwrap();
+
+                            let (_, positions, batch) = children
+                                .entry(reveal_index.0)
+                                .or_insert_with(|| (new_state, vec![], DealBatch::default()));
+
+                            positions.push(deal * pair_count + my_index * counts[1] + your_index);
+                            batch.push(
+                                new_hidden,
+                                [
+                                    deals.reach[0][deal]
+                                        * strategies[0][deal * counts[0] + my_index],
+                                    deals.reach[1][deal]
+                                        * strategies[1][deal * counts[1] + your_index],
+                                ],
+                            );
+                        }
+                    }
+                }
+
+                for (reveal_index, (new_state, positions, batch)) in children {
+                    let next_phase = phase
+                        .advance_phase(&state, RevealIndex(reveal_index))
+                        .unwrap();
+                    let utilities = self.train_batch::<P::Next>(
+                        &mut scope.next[reveal_index],
+                        next_phase,
+                        new_state,
+                        &batch,
+                        updates,
+                    );
+
+                    for (position, utility) in positions.into_iter().zip(utilities) {
+                        values[position] = utility;
+                    }
+                }
+                // }}}
+                // {{{ Accumulate regrets
+                let batch = RegretBatch {
+                    counts,
+                    values: &values,
+                    strategies: [&strategies[0], &strategies[1]],
+                    reach: [&deals.reach[0], &deals.reach[1]],
+                };
+                let computed = self.run_backend(|backend| backend.regrets(&batch));
+
+                for deal in (0..deal_count).filter(|deal| active[*deal]) {
+                    for (player, node) in nodes[deal].into_iter().enumerate() {
+                        if let Some(node) = node {
+                            let regrets = &computed.regrets[player][deal * counts[player]..];
+
+                            for (index, regret) in regrets[..counts[player]].iter().enumerate() {
+                                updates.accumulate_regret(node, index, *regret);
+                            }
+                        }
+                    }
+                }
+                // }}}
+
+                // Inactive deals never get their values filled in, so their utility is zero
+                computed.utilities
+            }
+        }
+    }
+
+    /// Runs some computation on the backend, switching to the cpu backend
+    /// (and running the computation again) if it fails.
+    fn run_backend<R>(&self, mut f: impl FnMut(&mut dyn StrategyBackend) -> EchoResult<R>) -> R {
+        let mut backend = self.backend.borrow_mut();
+
+        match f(backend.as_mut()) {
+            Ok(result) => result,
+            Err(error) => {
+                tracing::warn!("Falling back to the cpu backend: {error}");
+                *backend = Box::new(CpuBackend);
+                f(backend.as_mut()).expect("The cpu backend cannot fail")
+            }
+        }
+    }
+
+    /// Estimates the value of a scope by sampling a single line of play
+    /// using the current strategies, without updating any weights.
+    fn sample_value<P: Phase>(
+        &self,
+        scope: &mut Scope,
+        phase: P,
+        state: KnownStateSummary,
+        hidden: Pair<hidden_index::EncodingInfo>,
+        mut rng: &mut dyn RngCore,
+    ) -> Option<Utility> {
+        match scope {
+            Scope::Completed(score) => Some(score.to_utility()),
+            Scope::Unexplored(UnexploredScope {
+                expansion: Some(context),
+                ..
+            }) => {
+                let context = *context;
+                *scope = context.expand(phase);
+                self.sample_value(scope, phase, state, hidden, rng)
+            }
+            Scope::Unexplored(UnexploredScope {
+                state: Some(state), ..
+            }) => Some(self.leaf_value::<P>(state, hidden)),
+            Scope::Unexplored(_) => unreachable!("Oops, cannot handle unexplored scopes"),
+            Scope::Explored(scope) => {
+                let hidden_states = hidden.map(HiddenState::from_encoding_info);
+                let indices = Player::PLAYERS.map(|player| {
+                    HiddenIndex::encode(&state, player, player.select(hidden))
+                        .expect("Generated scopes only hold encodable hidden states")
+                });
+
+                let decisions = scope.matrices.get_nodes(indices).map(|node| {
+                    DecisionIndex(node.map_or(0, |node| {
+                        node.recompute_regret_magnitude();
+                        let strategy: Vec<_> = (0..node.len()).map(|i| node.strategy(i)).collect();
+                        sample(&strategy, &mut rng)
+                    }))
+                });
+
+                let (new_state, new_hidden, reveal_index) = phase
+                    .advance_hidden_indices(state, hidden_states, decisions)
+                    .unwrap();
+                let next_phase = phase.advance_phase(&state, reveal_index).ok()?;
+
+                self.sample_value::<P::Next>(
+                    &mut scope.next[reveal_index.0],
+                    next_phase,
+                    new_state,
+                    new_hidden,
+                    rng,
+                )
+            }
+        }
+    }
+
+    /// The value of a scope left out because of the memory budget (or the turn limit).
+    /// Unless the leaf evaluator knows better, we pretend the game ends right away.
+    fn leaf_value<P: Phase>(
+        &self,
+        state: &KnownState,
+        hidden: Pair<hidden_index::EncodingInfo>,
+    ) -> Utility {
+        self.leaves
+            .as_ref()
+            .filter(|_| P::TAG == PhaseTag::Main)
+            .and_then(|leaves| leaves.leaf_value(state, hidden.map(|info| info.get_main())))
+            .unwrap_or_else(|| state.score.to_utility())
+    }
+
+    // {{{ Regret based pruning
+    /// Computes what to multiply the regret updates of every decision by during
+    /// the current visit of a node, where `0` means the decision gets skipped.
+    /// Only decisions the current strategy never takes get skipped. Once revisited,
+    /// updates get multiplied by the number of visits since the last traversal,
+    /// making up for the skipped updates (assuming they would have been similar).
+    ///
+    /// Returns an empty vector (without allocating) unless regret based pruning is enabled.
+    fn regret_weights(&self, node: Option<&DecisionVector>) -> Vec<Utility> {
+        let (Some(_), Some(node)) = (self.regret_pruning, node) else {
+            return vec![];
+        };
+
+        let Some(skipped_visits) = node.skipped_visits() else {
+            return vec![1.0; node.len()];
+        };
+
+        skipped_visits
+            .iter()
+            .enumerate()
+            .map(|(index, skip)| {
+                let mut visits = skip.get();
+
+                if visits.remaining > 0 && node.strategy(index) == 0.0 {
+                    visits.remaining -= 1;
+                    visits.skipped += 1;
+                    skip.set(visits);
+                    0.0
+                } else {
+                    (visits.skipped + 1) as Utility
+                }
+            })
+            .collect()
+    }
+
+    /// Decides how many visits to skip the traversed decisions whose regret
+    /// (still) lies below the threshold for.
+    fn schedule_regret_pruning<'a>(
+        &self,
+        node: Option<&'a DecisionVector<'a>>,
+        weights: &[Utility],
+        updates: &WeightUpdates<'a>,
+    ) {
+        let (Some(options), Some(node)) = (self.regret_pruning, node) else {
+            return;
+        };
+
+        let Some(skipped_visits) = node.skipped_visits() else {
+            return;
+        };
+
+        let decisions = weights.iter().zip(skipped_visits).enumerate();
+
+        for (index, (weight, skip)) in decisions {
+            if *weight == 0.0 {
+                continue;
+            }
+
+            let amount = updates.regret(node, index);
+            let remaining = if amount < options.threshold {
+                let remaining = (-amount / options.recovery).ceil() as usize;
+                remaining.clamp(1, options.max_skip)
+            } else {
+                0
+            };
+
+            skip.set(SkippedVisits {
+                remaining,
+                skipped: 0,
+            });
+        }
+    }
+    // }}}
+
     /// With the goal of trying to avoid floating point arithmetic weirdness,
     /// we declare things to be equal to 0 if they are "close enough"
     #[inline(always)]
-    fn is_almost_zero(num: Probability) -> bool {
-        num.abs() < 0.00000001
+    fn is_almost_zero(&self, num: Probability) -> bool {
+        num.abs() < self.pruning_threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::blueprint::write_blueprint;
+    use crate::cfr::evaluate::{expected_values, FrozenStrategy};
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::storage::WeightStorage;
+    use crate::game::battlefield::Battlefields;
+    use crate::game::creature::{Creature, CreatureSet};
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use crate::helpers::bitfield::Bitfield;
+    use bumpalo::Bump;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::io::Cursor;
+
+    #[test]
+    fn json_telemetry_writes_non_finite_values_as_null() {
+        #[derive(Clone, Default)]
+        struct SharedBuffer(std::rc::Rc<RefCell<Vec<u8>>>);
+
+        impl io::Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                io::Write::write(&mut *self.0.borrow_mut(), buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = SharedBuffer::default();
+        let mut telemetry = Telemetry::new(TelemetryFormat::JsonLines, buffer.clone());
+        telemetry
+            .write(IterationStats {
+                iteration: 3,
+                average_utility: Utility::NAN,
+                exploitability_estimate: 0.5,
+                nash_gap: Some(Utility::INFINITY),
+                sampled_exploitability: None,
+                node_touches: 7,
+                elapsed: Duration::from_secs(2),
+            })
+            .unwrap();
+
+        let line = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        assert_eq!(
+            line,
+            "{\"iteration\":3,\"average_utility\":null,\"exploitability_estimate\":0.5,\"nash_gap\":null,\"sampled_exploitability\":null,\"sampled_exploitability_margin\":null,\"node_touches\":7,\"elapsed\":2}\n"
+        );
+    }
+
+    #[test]
+    fn symmetric_positions_are_worth_nothing() {
+        // Neither player has an edge in a symmetric position, hence the utility
+        // averaged over every deal should stay close to zero while training.
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        let context = TrainingContext::new(false);
+        let phase = MainPhase::new();
+        let summary = state.to_summary();
+
+        let mut total = 0.0;
+        let mut samples = 0;
+        for _ in 0..50 {
+            let updates = context.weight_updates();
+            for hidden in phase.valid_hidden_states(summary) {
+                total += context
+                    .train_phase(&mut scope, phase, summary, hidden, [1.0; 2], None, &updates)
+                    .unwrap();
+                samples += 1;
+            }
+            updates.flush();
+        }
+
+        let value = total / samples as Utility;
+        assert!(
+            value.abs() < 0.01,
+            "The first player is worth {value} on average"
+        );
+    }
+
+    fn train_chance_sampled(seed: u64, iterations: usize) -> Utility {
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        TrainingContext::new(false).cs_cfr(&mut rng, &mut scope, state.to_summary(), iterations);
+
+        TrainingContext::root_regret(&scope)
+    }
+
+    #[test]
+    fn training_can_be_warm_started_from_a_blueprint() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, summary, 20);
+
+        let mut file = vec![];
+        write_blueprint(&scope, &state, 0, &mut file).unwrap();
+        let mut blueprint = BlueprintReader::new(Cursor::new(file)).unwrap();
+
+        let trainer = TrainingContext::new(false);
+        let warm = GenerationContext::new(1, state, &allocator).generate();
+        let initialized = trainer
+            .warm_start(&warm, &mut blueprint, WarmStart::default())
+            .unwrap();
+        assert_eq!(initialized, blueprint.len());
+
+        let (expected, actual) = (scope.get_explored().unwrap(), warm.get_explored().unwrap());
+        for player in Player::PLAYERS {
+            for index in 0..HiddenIndex::count(&state, player, PhaseTag::Main).unwrap() {
+                let index = HiddenIndex(index);
+                let expected = expected.strategy_for(player, index).unwrap();
+                let actual = actual.strategy_for(player, index).unwrap();
+
+                for (expected, actual) in expected.iter().zip(&actual) {
+                    assert!((expected - actual).abs() < 1e-3);
+                }
+            }
+        }
+
+        let gap = best_response::nash_gap(&scope, summary);
+        assert!((best_response::nash_gap(&warm, summary) - gap).abs() < 1e-2);
+
+        // Purified strategies always take a single decision.
+        let pure = GenerationContext::new(1, state, &allocator).generate();
+        let options = WarmStart {
+            purify: true,
+            ..WarmStart::default()
+        };
+        trainer.warm_start(&pure, &mut blueprint, options).unwrap();
+        let strategy = pure
+            .get_explored()
+            .unwrap()
+            .strategy_for(Player::Me, HiddenIndex(0))
+            .unwrap();
+        assert_eq!(strategy.iter().filter(|p| **p > 0.0).count(), 1);
+
+        // Blueprints for some other state cannot be loaded.
+        let mut other = state;
+        other.battlefields.current = Battlefields::COUNT - 2;
+        other.graveyard = CreatureSet::empty();
+        for creature in &Creature::CREATURES[..3] {
+            other.graveyard.insert(*creature);
+        }
+        let other = GenerationContext::new(1, other, &allocator)
+            .with_lazy_expansion()
+            .generate();
+        assert!(trainer
+            .warm_start(&other, &mut blueprint, WarmStart::default())
+            .is_err());
+    }
+
+    /// Whether some node of the tree is currently skipping a decision.
+    fn has_pruned_nodes(scope: &Scope) -> bool {
+        let Some(explored) = scope.get_explored() else {
+            return false;
+        };
+
+        let pruned = [Player::Me, Player::You]
+            .into_iter()
+            .filter_map(|player| match explored.matrices.get_matrix(player) {
+                DecisionMatrix::Trivial => None,
+                DecisionMatrix::Expanded(vectors) => Some(*vectors),
+            })
+            .flatten()
+            .filter_map(|node| node.skipped_visits())
+            .any(|visits| visits.iter().any(|skip| skip.get().remaining > 0));
+
+        pruned || explored.next.iter().any(has_pruned_nodes)
+    }
+
+    #[test]
+    fn regret_pruning_skips_decisions_without_hurting_convergence() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, summary, 300);
+        let expected = best_response::nash_gap(&scope, summary);
+        let trained = [FrozenStrategy::Trained, FrozenStrategy::Trained];
+        let [expected_value, _] = expected_values(&scope, summary, trained).unwrap();
+
+        let mut pruned = GenerationContext::new(1, state, &allocator)
+            .with_regret_pruning()
+            .generate();
+        let mut trainer = TrainingContext::new(false).with_regret_pruning(RegretPruning::default());
+        trainer.cfr(&mut pruned, summary, 300);
+
+        assert!(has_pruned_nodes(&pruned));
+
+        let gap = best_response::nash_gap(&pruned, summary);
+        assert!(
+            gap < 1.05 * expected,
+            "Pruning increased the nash gap from {expected} to {gap}"
+        );
+
+        // Equilibria are not unique, so the strategies themselves might differ.
+        let trained = [FrozenStrategy::Trained, FrozenStrategy::Trained];
+        let [value, _] = expected_values(&pruned, summary, trained).unwrap();
+        assert!(
+            (value - expected_value).abs() < 0.005,
+            "{value} vs {expected_value}"
+        );
+    }
+
+    #[test]
+    fn regret_pruning_does_not_hurt_chance_sampling() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let deals = MainPhase::new().valid_hidden_states(summary).count();
+        let allocator = Bump::new();
+
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        let mut rng = StdRng::seed_from_u64(3);
+        TrainingContext::new(false).cs_cfr(&mut rng, &mut scope, summary, 500 * deals);
+        let expected = best_response::nash_gap(&scope, summary);
+
+        // A single unlucky deal must not get decisions pruned for long.
+        let mut pruned = GenerationContext::new(1, state, &allocator)
+            .with_regret_pruning()
+            .generate();
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut trainer = TrainingContext::new(false).with_regret_pruning(RegretPruning::default());
+        trainer.cs_cfr(&mut rng, &mut pruned, summary, 500 * deals);
+        let gap = best_response::nash_gap(&pruned, summary);
+
+        assert_eq!(trainer.regret_pruning, Some(RegretPruning::default()));
+        assert!(
+            gap < 1.5 * expected,
+            "Pruning increased the sampled nash gap from {expected} to {gap}"
+        );
+    }
+
+    #[test]
+    fn chance_sampling_is_deterministic() {
+        assert_eq!(train_chance_sampled(7, 50), train_chance_sampled(7, 50));
+    }
+
+    #[test]
+    fn chance_sampling_converges_like_full_cfr() {
+        let state = last_turn_state();
+        let deals = MainPhase::new()
+            .valid_hidden_states(state.to_summary())
+            .count();
+
+        // Both variants get to visit the same number of deals. Equilibria are
+        // not unique, so we compare the regrets instead of the strategies.
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, state.to_summary(), 10000 / deals);
+
+        let expected = TrainingContext::root_regret(&scope);
+        let sampled = train_chance_sampled(0, 10000);
+
+        assert!(train_chance_sampled(0, 100) > 2.0 * sampled);
+        assert!(
+            sampled < 1.2 * expected,
+            "Sampled regret {sampled} is much larger than {expected}"
+        );
+    }
+
+    #[test]
+    fn chance_sampling_approaches_the_nash_gap_of_full_cfr() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let deals = MainPhase::new().valid_hidden_states(summary).count();
+        let allocator = Bump::new();
+
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, summary, 500);
+        let expected = best_response::nash_gap(&scope, summary);
+
+        // Both variants get to visit the same number of deals.
+        let mut sampled = GenerationContext::new(1, state, &allocator).generate();
+        let mut rng = StdRng::seed_from_u64(2);
+        TrainingContext::new(false).cs_cfr(&mut rng, &mut sampled, summary, 500 * deals);
+        let actual = best_response::nash_gap(&sampled, summary);
+
+        assert!(
+            actual < 1.5 * expected,
+            "Sampled nash gap {actual} is much larger than {expected}"
+        );
+    }
+
+    #[test]
+    fn vectorized_cfr_converges_like_full_cfr() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+
+        // Every deal of the first iteration sees the same (uniform) strategies.
+        let mut vectorized = GenerationContext::new(1, state, &allocator).generate();
+        let utility =
+            TrainingContext::new(false).vectorized_cfr_iteration(&mut vectorized, summary);
+        let [expected, _] = expected_values(
+            &vectorized,
+            summary,
+            [FrozenStrategy::Uniform, FrozenStrategy::Uniform],
+        )
+        .unwrap();
+        assert!((utility - expected).abs() < 1e-5);
+
+        // Updating every regret at once converges a bit slower per iteration.
+        let mut scalar = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scalar, summary, 100);
+        TrainingContext::new(false).vectorized_cfr(&mut vectorized, summary, 99);
+
+        let expected = best_response::nash_gap(&scalar, summary);
+        let actual = best_response::nash_gap(&vectorized, summary);
+        assert!(
+            actual < 2.0 * expected,
+            "Vectorized nash gap {actual} is much larger than {expected}"
+        );
+    }
+
+    #[test]
+    fn vectorized_cfr_visits_every_public_state_once() {
+        fn explored(scope: &Scope) -> usize {
+            match scope {
+                Scope::Explored(scope) => 1 + scope.next.iter().map(explored).sum::<usize>(),
+                _ => 0,
+            }
+        }
+
+        let state = last_turn_state();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+
+        // Otherwise, revisited scopes would see the regrets of the earlier visits.
+        let context = TrainingContext::new(false);
+        context.vectorized_cfr_iteration(&mut scope, state.to_summary());
+        assert_eq!(context.node_touches.get(), explored(&scope));
+    }
+
+    // Should pass with and without the `half-weights` feature.
+    #[test]
+    fn weight_sums_keep_growing_past_the_range_of_half_precision_floats() {
+        let allocator = Bump::new();
+        let vector = DecisionVector::new(3, WeightStorage::Arena(&allocator));
+        let context = TrainingContext::new(false);
+
+        // Importance sampling makes the individual updates quite large.
+        for _ in 0..100_000 {
+            let updates = context.weight_updates();
+            updates.accumulate_regret(&vector, 0, 30.0);
+            updates.accumulate_regret(&vector, 1, 0.3);
+            updates.update_strategy_sum(&vector, 30.0);
+            updates.flush();
+        }
+
+        // Rounding half precision weights stochastically adds a bit of noise,
+        // especially to sums much smaller than the others in their vector.
+        let close = |actual: f32, expected: f32| (actual / expected - 1.0).abs() < 0.05;
+
+        let regrets = vector.regrets();
+        assert!(close(regrets[0], 3_000_000.0), "{regrets:?}");
+        assert!(close(regrets[1], 30_000.0), "{regrets:?}");
+
+        let strategy_sums = vector.strategy_sums();
+        assert!(close(strategy_sums[0], 1_000_000.0), "{strategy_sums:?}");
+    }
+
+    // Should pass with and without the `half-weights` feature.
+    #[test]
+    fn weight_precision_does_not_hurt_convergence() {
+        let state = last_turn_state();
+        let summary = state.to_summary();
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+
+        TrainingContext::new(false).cfr(&mut scope, summary, 500);
+
+        // Full precision weights end up with a nash gap of about 0.0355.
+        let nash_gap = best_response::nash_gap(&scope, summary);
+        assert!(nash_gap < 0.04, "Nash gap {nash_gap} is too large");
     }
 }
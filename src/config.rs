@@ -0,0 +1,606 @@
+//! Options for the solver, the gui and the available agents, loaded from a toml file:
+//! ```toml
+//! database = "matches.sqlite"
+//! seed = 7
+//!
+//! [solver]
+//! turns = 2
+//! iterations = 1000
+//! allocator_capacity = 4096
+//! variant = "chance_sampling"
+//! backend = "auto"
+//!
+//! [rules]
+//! turns = 3
+//! gambit_loses_ties = false
+//! scoring = "battles_won"
+//!
+//! [gui]
+//! card_size = 100
+//!
+//! [[agents]]
+//! name = "bot"
+//! kind = "random"
+//! seed = 42
+//!
+//! [[agents]]
+//! name = "solver"
+//! kind = "blueprint"
+//! blueprint = "blueprint.bin"
+//! ```
+//! Every field is optional. Individual values can be overridden from the command line
+//! using assignments of the form `solver.turns=3`.
+use crate::ai::settings::Settings;
+use crate::cfr::abstraction::HiddenAbstraction;
+use crate::cfr::decision::{Probability, Scope, Utility};
+use crate::cfr::endgame::EndgameTable;
+use crate::cfr::exploitability::LocalBestResponse;
+use crate::cfr::generate::{GenerationContext, MemoryBudget, TranspositionTable};
+#[cfg(feature = "gpu")]
+use crate::cfr::gpu::GpuBackend;
+use crate::cfr::gpu::{best_backend, CpuBackend, StrategyBackend};
+use crate::cfr::leaves::{HeuristicLeaves, RolloutLeaves};
+use crate::cfr::montecarlo::{GreedyPolicy, RolloutEvaluator};
+use crate::cfr::train::{RegretPruning, TrainingContext};
+use crate::error::{EchoError, EchoResult};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateSummary;
+use crate::game::rules::Ruleset;
+use bumpalo::Bump;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// {{{ Solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CfrVariant {
+    /// Goes through every possible deal each iteration.
+    Vanilla,
+    /// Samples a single deal each iteration.
+    ChanceSampling,
+    /// Goes through every possible deal each iteration, visiting
+    /// every public state once with all of its deals at the same time.
+    Vectorized,
+}
+
+/// Where vectorized training performs its weight updates (see `cfr::gpu`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    Cpu,
+    /// Requires the gpu feature. Fails if no gpu is available.
+    Gpu,
+    /// Uses the gpu if there is one, falling back to the cpu otherwise.
+    Auto,
+}
+
+/// How training values the leaves of trees cut short by the turn limit (see `cfr::leaves`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeafKind {
+    /// Pretends the game ends right away.
+    Score,
+    /// Solves the final turn exactly (see `cfr::endgame`). Earlier turns fall back to the score.
+    Endgame,
+    /// Plays the rest of the game using greedy rollouts (see `cfr::montecarlo`).
+    Rollouts,
+    /// Guesses the outcome from the score and the strength of both hands.
+    Heuristic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SolverConfig {
+    /// How many turns to generate the tree for.
+    pub turns: usize,
+    pub iterations: usize,
+
+    /// The maximum amount of memory the tree can take up (in megabytes).
+    pub allocator_capacity: usize,
+    pub variant: CfrVariant,
+
+    /// Where to perform the weight updates of vectorized training.
+    pub backend: BackendKind,
+    pub pruning: bool,
+    pub pruning_threshold: Probability,
+
+    /// Skip decisions whose regret drops below this (negative) amount for a
+    /// while (see `TrainingContext::with_regret_pruning`). Disabled if not present.
+    pub regret_pruning_threshold: Option<Utility>,
+
+    /// Expand scopes the first time training reaches them,
+    /// instead of generating the entire tree upfront.
+    pub lazy_expansion: bool,
+
+    /// How many threads to split tree generation between. Every thread
+    /// gets its own arena, which shares the allocator capacity.
+    pub generation_threads: usize,
+
+    /// Share decision matrices between scopes starting in the same state,
+    /// regardless of the order things got revealed in. Disables parallel generation.
+    pub transpositions: bool,
+
+    /// Forget the exact sabotage guesses once the seer phase begins, sharing
+    /// decision matrices between seer phases only differing in them. Saves
+    /// memory, but can cost a lot of expected value (see `cfr::generate`).
+    pub imperfect_recall: bool,
+
+    /// How much memory generation is allowed to use up (in megabytes). Subtrees
+    /// which do not fit are left unexplored instead of running out of memory.
+    /// Sizes are only estimated, so this should be somewhat below the capacity.
+    pub memory_budget: Option<usize>,
+
+    /// Group the hidden states of each player into at most this many buckets
+    /// of similar strength, sharing a single strategy (see `cfr::abstraction`).
+    /// One count per phase. Hidden states are kept exact if not present.
+    pub main_buckets: Option<usize>,
+    pub sabotage_buckets: Option<usize>,
+    pub seer_buckets: Option<usize>,
+
+    /// Seed for the random number generator used by chance sampling.
+    /// Training gets seeded from entropy if this is not present.
+    pub seed: Option<u64>,
+
+    /// Every how many iterations to compute and log the nash gap (the sum of
+    /// the best response values of both players). Each computation costs
+    /// about as much as an iteration of vanilla cfr. Disabled if not present.
+    pub nash_gap_interval: Option<usize>,
+
+    /// Every how many iterations to estimate the nash gap by playing games using
+    /// local best responses (see `cfr::exploitability`). Much cheaper than
+    /// computing the actual nash gap on large trees. Disabled if not present.
+    pub exploitability_interval: Option<usize>,
+
+    /// How many games each player plays for every exploitability estimate.
+    pub exploitability_games: usize,
+
+    /// How to value the turns left out by the turn limit.
+    pub leaves: LeafKind,
+
+    /// How many games to play from every leaf when valuing leaves using rollouts.
+    pub leaf_rollouts: usize,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            turns: 1,
+            iterations: 1000,
+            allocator_capacity: 4096,
+            variant: CfrVariant::Vanilla,
+            backend: BackendKind::Cpu,
+            pruning: false,
+            pruning_threshold: TrainingContext::DEFAULT_PRUNING_THRESHOLD,
+            regret_pruning_threshold: None,
+            lazy_expansion: false,
+            generation_threads: 1,
+            transpositions: false,
+            imperfect_recall: false,
+            memory_budget: None,
+            main_buckets: None,
+            sabotage_buckets: None,
+            seer_buckets: None,
+            seed: None,
+            nash_gap_interval: None,
+            exploitability_interval: None,
+            exploitability_games: LocalBestResponse::default().games,
+            leaves: LeafKind::Score,
+            leaf_rollouts: 16,
+        }
+    }
+}
+
+impl SolverConfig {
+    /// Creates an allocator limited to the configured capacity.
+    pub fn allocator(&self) -> Bump {
+        let allocator = Bump::new();
+        allocator.set_allocation_limit(Some(self.allocator_capacity * 1024 * 1024));
+        allocator
+    }
+
+    /// Creates the arenas used by parallel generation (none if
+    /// generation is single threaded), splitting the capacity between them.
+    pub fn arenas(&self) -> Vec<Bump> {
+        if self.generation_threads <= 1 {
+            return vec![];
+        }
+
+        let capacity = self.allocator_capacity * 1024 * 1024 / self.generation_threads;
+
+        (0..self.generation_threads)
+            .map(|_| {
+                let arena = Bump::new();
+                arena.set_allocation_limit(Some(capacity));
+                arena
+            })
+            .collect()
+    }
+
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.memory_budget
+            .map(|megabytes| MemoryBudget::new(megabytes * 1024 * 1024))
+    }
+
+    /// Creates a generation context using the configured options. The transposition
+    /// table only gets used if transpositions or imperfect recall are enabled.
+    pub fn generation_context<'a>(
+        &self,
+        state: KnownState,
+        allocator: &'a Bump,
+        transpositions: &'a TranspositionTable<'a>,
+        budget: Option<&'a MemoryBudget>,
+    ) -> GenerationContext<'a> {
+        let mut context = GenerationContext::new(self.turns, state, allocator);
+
+        if self.lazy_expansion {
+            context = context.with_lazy_expansion();
+        }
+
+        if self.transpositions {
+            context = context.with_transpositions(transpositions);
+        }
+
+        if self.imperfect_recall {
+            context = context.with_imperfect_recall(transpositions);
+        }
+
+        if let Some(budget) = budget {
+            context = context.with_memory_budget(budget);
+        }
+
+        let abstraction = HiddenAbstraction {
+            main: self.main_buckets,
+            sabotage: self.sabotage_buckets,
+            seer: self.seer_buckets,
+        };
+
+        if abstraction != HiddenAbstraction::default() {
+            context = context.with_abstraction(abstraction);
+        }
+
+        if self.regret_pruning_threshold.is_some() {
+            context = context.with_regret_pruning();
+        }
+
+        context
+    }
+
+    /// Creates the configured backend. Fails if a gpu was
+    /// requested, but none is available.
+    pub fn strategy_backend(&self) -> EchoResult<Box<dyn StrategyBackend>> {
+        Ok(match self.backend {
+            BackendKind::Cpu => Box::new(CpuBackend),
+            BackendKind::Auto => best_backend(),
+            #[cfg(feature = "gpu")]
+            BackendKind::Gpu => Box::new(GpuBackend::new()?),
+            #[cfg(not(feature = "gpu"))]
+            BackendKind::Gpu => {
+                return Err(EchoError::Gpu(
+                    "Compiled without the gpu feature".to_string(),
+                ))
+            }
+        })
+    }
+
+    pub fn training_context(&self) -> EchoResult<TrainingContext> {
+        let mut context = TrainingContext::new(self.pruning)
+            .with_pruning_threshold(self.pruning_threshold)
+            .with_backend(self.strategy_backend()?);
+
+        match self.leaves {
+            LeafKind::Score => {}
+            LeafKind::Endgame => context = context.with_endgame_leaves(EndgameTable::default()),
+            LeafKind::Rollouts => {
+                let evaluator = RolloutEvaluator::new(self.leaf_rollouts, self.seed.unwrap_or(0))
+                    .with_policy(GreedyPolicy);
+                context = context.with_leaf_evaluator(RolloutLeaves::new(evaluator));
+            }
+            LeafKind::Heuristic => {
+                context = context.with_leaf_evaluator(HeuristicLeaves::default());
+            }
+        }
+
+        if let Some(interval) = self.exploitability_interval {
+            let options = LocalBestResponse {
+                games: self.exploitability_games,
+                ..LocalBestResponse::default()
+            };
+
+            context =
+                context.with_sampled_exploitability(interval, options, self.seed.unwrap_or(0));
+        }
+
+        if let Some(threshold) = self.regret_pruning_threshold {
+            context = context.with_regret_pruning(RegretPruning {
+                threshold,
+                ..RegretPruning::default()
+            });
+        }
+
+        Ok(match self.nash_gap_interval {
+            Some(interval) => context.with_nash_gap_interval(interval),
+            None => context,
+        })
+    }
+
+    /// Trains the given scope using the configured variant of cfr.
+    pub fn train(
+        &self,
+        trainer: &mut TrainingContext,
+        scope: &mut Scope,
+        state: KnownStateSummary,
+    ) {
+        match self.variant {
+            CfrVariant::Vanilla => trainer.cfr(scope, state, self.iterations),
+            CfrVariant::Vectorized => trainer.vectorized_cfr(scope, state, self.iterations),
+            CfrVariant::ChanceSampling => {
+                let mut rng = match self.seed {
+                    Some(seed) => StdRng::seed_from_u64(seed),
+                    None => StdRng::from_entropy(),
+                };
+
+                trainer.cs_cfr(&mut rng, scope, state, self.iterations)
+            }
+        }
+    }
+}
+// }}}
+// {{{ Agents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentKind {
+    Random,
+    AlwaysZero,
+    /// Models the opponent, best responding to it a few turns deep.
+    OpponentModel,
+    /// Samples decisions from a blueprint trained using the solver config.
+    Blueprint,
+    /// Best responds to a blueprint (trained from the start of the game),
+    /// using a tree spanning as many turns as the solver config does.
+    BestResponse,
+    /// Opens using an opening book, playing randomly afterwards.
+    OpeningBook,
+    /// Samples decisions from a network trained using deep cfr.
+    /// Requires the deep-cfr feature.
+    Deep,
+}
+
+/// An agent which can be referred to by name.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentConfig {
+    pub name: String,
+    pub kind: AgentKind,
+
+    /// Seed for the random number generator of the agent.
+    /// Agents are seeded from entropy if this is not present.
+    #[serde(default)]
+    pub seed: Option<u64>,
+
+    /// The blueprint file blueprint agents sample their decisions
+    /// from (and best response agents respond to).
+    #[serde(default)]
+    pub blueprint: Option<PathBuf>,
+
+    /// The file opening book agents sample their first decision from.
+    #[serde(default)]
+    pub opening_book: Option<PathBuf>,
+
+    /// The strategy network deep agents sample their decisions from.
+    #[serde(default)]
+    pub network: Option<PathBuf>,
+
+    /// How many turns opponent model agents look ahead for.
+    #[serde(default)]
+    pub depth: Option<usize>,
+}
+// }}}
+// {{{ Config
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub solver: SolverConfig,
+
+    /// The rules games get simulated and solved by.
+    pub rules: Ruleset,
+
+    /// Overrides for the persisted gui settings.
+    /// Uses the same keys as the settings file.
+    pub gui: toml::Table,
+    pub agents: Vec<AgentConfig>,
+
+    /// SQLite database every finished game gets saved to (see the `database`
+    /// module). Only used when built with the `database` feature.
+    pub database: Option<PathBuf>,
+
+    /// Seeds the deals of the games played in the gui.
+    /// Games get seeded from entropy if this is not present.
+    pub seed: Option<u64>,
+}
+
+impl Config {
+    pub const DEFAULT_PATH: &'static str = "echo.toml";
+
+    /// Parses a config, applying the given `path.to.key=value` overrides on top.
+    pub fn parse(source: &str, overrides: &[String]) -> EchoResult<Self> {
+        let mut table: toml::Table =
+            toml::from_str(source).map_err(|error| EchoError::Config(error.to_string()))?;
+
+        for assignment in overrides {
+            apply_override(&mut table, assignment)?;
+        }
+
+        let config =
+            Self::deserialize(table).map_err(|error| EchoError::Config(error.to_string()))?;
+        config.rules.validate()?;
+
+        Ok(config)
+    }
+
+    /// Loads the config stored at some path, using the
+    /// defaults (plus overrides) if the file does not exist.
+    pub fn load(path: impl AsRef<Path>, overrides: &[String]) -> EchoResult<Self> {
+        let path = path.as_ref();
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(error) => {
+                return Err(EchoError::Config(format!(
+                    "Failed to read {path:?}: {error}"
+                )))
+            }
+        };
+
+        Self::parse(&source, overrides)
+            .map_err(|error| EchoError::Config(format!("{path:?}: {error}")))
+    }
+
+    pub fn agent(&self, name: &str) -> Option<&AgentConfig> {
+        self.agents.iter().find(|agent| agent.name == name)
+    }
+
+    /// Overwrites the settings specified in the `gui` table.
+    pub fn apply_gui_settings(&self, settings: &mut Settings) {
+        settings.apply(&self.gui);
+    }
+}
+
+/// Sets a value inside a table, creating intermediate tables as needed.
+/// Values which are not valid toml are treated as strings.
+fn apply_override(table: &mut toml::Table, assignment: &str) -> EchoResult<()> {
+    let (path, value) = assignment.split_once('=').ok_or_else(|| {
+        EchoError::Config(format!(
+            "Expected an override of the form key=value, got {assignment:?}"
+        ))
+    })?;
+
+    let value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+        .ok()
+        .and_then(|mut wrapper| wrapper.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()));
+
+    let mut segments: Vec<_> = path.trim().split('.').collect();
+    let last = segments.pop().unwrap();
+    let mut current = table;
+
+    for segment in segments {
+        current = current
+            .entry(segment)
+            .or_insert_with(|| toml::Value::Table(Default::default()))
+            .as_table_mut()
+            .ok_or_else(|| {
+                EchoError::Config(format!(
+                    "Cannot override {path:?}, {segment:?} is not a table"
+                ))
+            })?;
+    }
+
+    current.insert(last.to_string(), value);
+
+    Ok(())
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let config = Config::parse("", &[]).unwrap();
+
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn configs_are_parsed_correctly() {
+        let source = r#"
+            database = "matches.sqlite"
+            seed = 11
+
+            [solver]
+            turns = 3
+            variant = "chance_sampling"
+            backend = "auto"
+
+            [gui]
+            card_size = 100
+            theme = "light"
+            assets = "skins/hd"
+
+            [[agents]]
+            name = "bot"
+            kind = "random"
+            seed = 7
+        "#;
+
+        let config = Config::parse(source, &[]).unwrap();
+
+        assert_eq!(config.solver.turns, 3);
+        assert_eq!(config.solver.variant, CfrVariant::ChanceSampling);
+        assert_eq!(config.solver.backend, BackendKind::Auto);
+        assert_eq!(config.solver.iterations, SolverConfig::default().iterations);
+        assert_eq!(config.agent("bot").unwrap().seed, Some(7));
+        assert_eq!(config.database, Some(PathBuf::from("matches.sqlite")));
+        assert_eq!(config.seed, Some(11));
+
+        let mut settings = Settings::default();
+        config.apply_gui_settings(&mut settings);
+
+        assert_eq!(settings.card_size, 100.0);
+        assert_eq!(settings.theme.name(), "light");
+        assert_eq!(settings.assets, Some(PathBuf::from("skins/hd")));
+    }
+
+    #[test]
+    fn gui_settings_keep_their_quoting() {
+        let source = r#"
+            [gui]
+            assets = 'C:\Users\echo\"skins"'
+            clock = "3+2"
+            zoom = "huge"
+            volume = 11
+        "#;
+
+        let config = Config::parse(source, &[]).unwrap();
+        let mut settings = Settings::default();
+        config.apply_gui_settings(&mut settings);
+
+        // Invalid and unknown keys get skipped without affecting the rest.
+        assert_eq!(
+            settings.assets,
+            Some(PathBuf::from(r#"C:\Users\echo\"skins""#))
+        );
+        assert_eq!(settings.clock, Some("3+2".parse().unwrap()));
+        assert_eq!(settings.zoom, Settings::default().zoom);
+
+        // Saved settings load back the same.
+        assert_eq!(Settings::parse(&settings.serialize()), settings);
+    }
+
+    #[test]
+    fn overrides_take_precedence() {
+        let overrides = [
+            "solver.turns=4".to_string(),
+            "solver.variant=vanilla".to_string(),
+        ];
+
+        let config = Config::parse("[solver]\nturns = 3", &overrides).unwrap();
+
+        assert_eq!(config.solver.turns, 4);
+        assert_eq!(config.solver.variant, CfrVariant::Vanilla);
+    }
+
+    #[test]
+    fn invalid_configs_are_rejected() {
+        assert!(Config::parse("[solver]\nturns = \"many\"", &[]).is_err());
+        assert!(Config::parse("[solver]\nunknown = 3", &[]).is_err());
+        assert!(Config::parse("[solver]\nbackend = \"tpu\"", &[]).is_err());
+        assert!(Config::parse("", &["solver".to_string()]).is_err());
+        assert!(Config::parse("[rules]\nturns = 5", &[]).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+//! SQLite database of finished games, such that the strength
+//! of agents can be tracked over time.
+//!
+//! Every game is stored together with the names of the agents,
+//! the seed, the full record (see `game::record`) and the final score.
+use crate::error::{EchoError, EchoResult};
+use crate::game::record::GameRecord;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::path::Path;
+
+impl From<rusqlite::Error> for EchoError {
+    fn from(error: rusqlite::Error) -> Self {
+        Self::Database(error.to_string())
+    }
+}
+
+// {{{ Win rates
+/// Results of some agent across a number of games.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WinRate {
+    pub wins: usize,
+    pub ties: usize,
+    pub losses: usize,
+}
+
+impl WinRate {
+    pub fn games(&self) -> usize {
+        self.wins + self.ties + self.losses
+    }
+
+    /// The fraction of games won. Zero if no games were played.
+    pub fn rate(&self) -> f32 {
+        self.wins as f32 / self.games().max(1) as f32
+    }
+
+    /// Reads the `wins`, `ties` and `losses` columns returned by `WIN_RATE_COLUMNS`.
+    fn from_row(row: &Row, offset: usize) -> rusqlite::Result<Self> {
+        let count = |index| {
+            row.get::<_, i64>(offset + index)
+                .map(|count| count as usize)
+        };
+
+        Ok(Self {
+            wins: count(0)?,
+            ties: count(1)?,
+            losses: count(2)?,
+        })
+    }
+}
+// }}}
+// {{{ Database
+/// Counts the results of games from the perspective of the agent `?1`.
+/// Scores are stored from the perspective of the first agent.
+const WIN_RATE_COLUMNS: &str = "
+    COALESCE(SUM(CASE WHEN (agent_a = ?1 AND score > 0) OR (agent_a <> ?1 AND score < 0) THEN 1 ELSE 0 END), 0),
+    COALESCE(SUM(CASE WHEN score = 0 THEN 1 ELSE 0 END), 0),
+    COALESCE(SUM(CASE WHEN (agent_a = ?1 AND score < 0) OR (agent_a <> ?1 AND score > 0) THEN 1 ELSE 0 END), 0)";
+
+/// Restricts a query to the games of the agent `?1`,
+/// optionally against the opponent `?2` (ignored when null).
+const AGENT_FILTER: &str = "
+    (agent_a = ?1 AND (?2 IS NULL OR agent_b = ?2))
+    OR (agent_b = ?1 AND (?2 IS NULL OR agent_a = ?2))";
+
+pub struct MatchDatabase {
+    connection: Connection,
+}
+
+impl MatchDatabase {
+    /// Opens the database at the given path, creating it if it does not exist.
+    pub fn open(path: impl AsRef<Path>) -> EchoResult<Self> {
+        Self::new(Connection::open(path)?)
+    }
+
+    /// Creates a database which only lives as long as the returned value.
+    pub fn in_memory() -> EchoResult<Self> {
+        Self::new(Connection::open_in_memory()?)
+    }
+
+    fn new(connection: Connection) -> EchoResult<Self> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS games (
+                id INTEGER PRIMARY KEY,
+                played_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                agent_a TEXT NOT NULL,
+                agent_b TEXT NOT NULL,
+                seed INTEGER,
+                record TEXT NOT NULL,
+                score INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS games_by_agent_a ON games (agent_a);
+            CREATE INDEX IF NOT EXISTS games_by_agent_b ON games (agent_b);",
+        )?;
+
+        Ok(Self { connection })
+    }
+
+    /// Saves a finished game, returning its id.
+    pub fn insert(&self, record: &GameRecord) -> EchoResult<i64> {
+        let score = record.result.ok_or(EchoError::InvalidState(
+            "Only finished games can be saved".to_string(),
+        ))?;
+
+        let [agent_a, agent_b] = &record.agents;
+
+        // Sqlite has no unsigned integers, so seeds get stored as their bit pattern.
+        let seed = record.seed.map(|seed| seed as i64);
+
+        self.connection.execute(
+            "INSERT INTO games (agent_a, agent_b, seed, record, score) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![agent_a, agent_b, seed, record.to_string(), score.0],
+        )?;
+
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// Looks up the record of some game.
+    pub fn get(&self, id: i64) -> EchoResult<Option<GameRecord>> {
+        let source: Option<String> = self
+            .connection
+            .query_row("SELECT record FROM games WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+            .optional()?;
+
+        match source {
+            Some(source) => Ok(Some(source.parse().map_err(|error| {
+                EchoError::Database(format!("Invalid record for game {id}: {error}"))
+            })?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Computes the results of some agent across every recorded game,
+    /// optionally only counting games against some specific opponent.
+    pub fn win_rate(&self, agent: &str, opponent: Option<&str>) -> EchoResult<WinRate> {
+        let query = format!("SELECT {WIN_RATE_COLUMNS} FROM games WHERE {AGENT_FILTER}");
+        let result = self
+            .connection
+            .query_row(&query, params![agent, opponent], |row| {
+                WinRate::from_row(row, 0)
+            })?;
+
+        Ok(result)
+    }
+
+    /// Similar to `win_rate`, except the results are grouped by the (UTC)
+    /// day the games were played on. Days are formatted as `YYYY-MM-DD`.
+    pub fn win_rate_over_time(
+        &self,
+        agent: &str,
+        opponent: Option<&str>,
+    ) -> EchoResult<Vec<(String, WinRate)>> {
+        let query = format!(
+            "SELECT date(played_at) AS day, {WIN_RATE_COLUMNS} FROM games
+            WHERE {AGENT_FILTER} GROUP BY day ORDER BY day"
+        );
+
+        let mut statement = self.connection.prepare(&query)?;
+        let rows = statement.query_map(params![agent, opponent], |row| {
+            Ok((row.get(0)?, WinRate::from_row(row, 1)?))
+        })?;
+
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::types::Score;
+
+    const BATTLEFIELDS: [Battlefield; Battlefields::COUNT] = Battlefields::last_of([
+        Battlefield::Night,
+        Battlefield::Glade,
+        Battlefield::Urban,
+        Battlefield::LastStrand,
+    ]);
+
+    fn finished(agents: [&str; 2], score: i8) -> GameRecord {
+        let mut record = GameRecord::new(BATTLEFIELDS, Some(u64::MAX), agents.map(str::to_string));
+
+        record.result = Some(Score(score));
+        record
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let database = MatchDatabase::in_memory().unwrap();
+        let record = finished(["random", "greedy"], 2);
+        let id = database.insert(&record).unwrap();
+
+        assert_eq!(database.get(id), Ok(Some(record)));
+        assert_eq!(database.get(id + 1), Ok(None));
+
+        let unfinished = GameRecord::new(BATTLEFIELDS, None, ["a", "b"].map(str::to_string));
+        assert!(database.insert(&unfinished).is_err());
+    }
+
+    #[test]
+    fn win_rates_are_counted_from_the_perspective_of_the_agent() {
+        let database = MatchDatabase::in_memory().unwrap();
+
+        for (agents, score) in [
+            (["random", "greedy"], 3),
+            (["greedy", "random"], 1),
+            (["greedy", "random"], -2),
+            (["random", "bot"], 0),
+        ] {
+            database.insert(&finished(agents, score)).unwrap();
+        }
+
+        let overall = WinRate {
+            wins: 2,
+            ties: 1,
+            losses: 1,
+        };
+
+        assert_eq!(database.win_rate("random", None), Ok(overall));
+        assert_eq!(
+            database.win_rate("random", Some("greedy")),
+            Ok(WinRate {
+                wins: 2,
+                ties: 0,
+                losses: 1,
+            })
+        );
+        assert_eq!(database.win_rate("nobody", None), Ok(WinRate::default()));
+
+        let over_time = database.win_rate_over_time("random", None).unwrap();
+        assert_eq!(over_time.len(), 1);
+        assert_eq!(over_time[0].1, overall);
+    }
+}
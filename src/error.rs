@@ -0,0 +1,58 @@
+//! Errors surfaced by the public APIs of the library.
+use crate::cfr::phase::PhaseTag;
+use crate::game::types::Player;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EchoError {
+    #[error("Bit {0} is already present in the bitfield")]
+    BitAlreadyPresent(usize),
+    #[error("Bit {0} is not present in the bitfield")]
+    BitNotPresent(usize),
+    #[error("Cannot encode {0} — the value is not among the possibilities")]
+    Encode(&'static str),
+    #[error("Cannot decode {0} — the index is out of range")]
+    Decode(&'static str),
+    #[error("The number of {0} does not fit inside an usize")]
+    Overflow(&'static str),
+    #[error("Unknown {what} {input:?}")]
+    Unknown { what: &'static str, input: String },
+    #[error("Invalid notation: {0}")]
+    Notation(String),
+    #[error("Invalid rules: {0}")]
+    InvalidRules(String),
+    #[error("Invalid position: {0}")]
+    InvalidPosition(String),
+    #[error("Invalid record: {0}")]
+    InvalidRecord(String),
+    #[error("Turn {turn} of the record is invalid: {reason}")]
+    InvalidTurn { turn: usize, reason: String },
+    #[error("Invalid weight file: {0}")]
+    Weights(String),
+    #[error("Invalid config: {0}")]
+    Config(String),
+    #[error("Invalid state: {0}")]
+    InvalidState(String),
+    #[error("Invalid decisions during the {0:?} phase")]
+    InvalidDecision(PhaseTag),
+    #[error(
+        "{player:?} kept making illegal decisions (the last one was {decision}, out of {count})"
+    )]
+    IllegalDecision {
+        player: Player,
+        decision: usize,
+        count: usize,
+    },
+    #[error("The game got cancelled")]
+    Cancelled,
+    #[error("Network error: {0}")]
+    Network(String),
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("Neural network error: {0}")]
+    Model(String),
+    #[error("Gpu error: {0}")]
+    Gpu(String),
+}
+
+pub type EchoResult<T> = Result<T, EchoError>;
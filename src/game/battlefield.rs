@@ -1,9 +1,13 @@
 use super::creature::Creature;
+use super::edict::Edict;
+use crate::error::EchoError;
 use std::fmt::{self, Display};
+use std::str::FromStr;
 use Battlefield::*;
 
 // {{{ Battlefield
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Battlefield {
     Mountain,
     Glade,
@@ -18,6 +22,7 @@ impl Battlefield {
 
     // Amount of points rewarded for winning a battle
     // in this location (top-left of card)
+    // Games played by different rules should use `Ruleset::reward` instead.
     pub fn reward(self) -> u8 {
         match self {
             LastStrand => 5,
@@ -43,23 +48,76 @@ impl Display for Battlefield {
         write!(f, "{:?}", self)
     }
 }
+
+impl FromStr for Battlefield {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Battlefield::BATTLEFIELDS
+            .into_iter()
+            .find(|battlefield| battlefield.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| EchoError::Unknown {
+                what: "battlefield",
+                input: s.to_string(),
+            })
+    }
+}
 // }}}
 // {{{ Battlefields
 /// List of battlefields used in a battle.
 // TODO: consider sharing battlefields.all
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Battlefields {
-    pub all: [Battlefield; 4],
+    pub all: [Battlefield; Battlefields::COUNT],
     pub current: usize,
 }
 
+// Every turn, each player discards a creature, and players must still be able
+// to choose between two creatures on the last turn (for the seer to work).
+// Hands are thus made out of `COUNT + 1` creatures, which must all be distinct,
+// with at least one creature (the overseer) left out of both hands.
+const _: () = assert!(2 * (Battlefields::COUNT + 1) < Creature::CREATURES.len());
+
+// Every turn, each player plays one of their edicts.
+const _: () = assert!(Battlefields::COUNT <= Edict::EDICTS.len());
+
 impl Battlefields {
-    pub const fn new(all: [Battlefield; 4]) -> Self {
+    /// The number of battlefields in a match (i.e. the maximum number of turns).
+    ///
+    /// Hand sizes, and the history kept by the gui, are derived from this.
+    /// Matches can be made shorter by lowering it, in which case more creatures
+    /// get set aside. The game only has 11 creatures, so the assertions above
+    /// cap this at 4: longer matches require a larger creature pool first.
+    pub const COUNT: usize = 4;
+
+    /// The number of creatures put away at the start of a match, which neither
+    /// player ever holds (the overseer, together with any creature the hands
+    /// have no room for).
+    pub const SET_ASIDE: usize = Creature::CREATURES.len() - 2 * (Self::COUNT + 1);
+
+    pub const fn new(all: [Battlefield; Battlefields::COUNT]) -> Self {
         Battlefields { all, current: 0 }
     }
 
+    /// Keeps the last `COUNT` battlefields of some list, such that lists written
+    /// out for full length matches keep working when `COUNT` is lowered.
+    pub const fn last_of<const N: usize>(list: [Battlefield; N]) -> [Battlefield; Self::COUNT] {
+        assert!(N >= Self::COUNT);
+
+        let mut result = [Plains; Self::COUNT];
+        let mut index = 0;
+
+        while index < Self::COUNT {
+            result[index] = list[N - Self::COUNT + index];
+            index += 1;
+        }
+
+        result
+    }
+
     pub fn is_last(&self) -> bool {
-        self.current == 3
+        self.current == Self::COUNT - 1
     }
 
     pub fn next(&self) -> Option<Self> {
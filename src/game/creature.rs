@@ -1,11 +1,14 @@
+use crate::error::EchoError;
 use crate::helpers::bitfield::{Bitfield, Bitfield16};
 use crate::make_bitfield;
 use std::convert::TryFrom;
 use std::debug_assert;
 use std::fmt::{self, Display};
+use std::str::FromStr;
 
 // {{{ Creature
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Creature {
     Wall,
     Seer,
@@ -64,6 +67,20 @@ impl Display for Creature {
     }
 }
 
+impl FromStr for Creature {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Creature::CREATURES
+            .into_iter()
+            .find(|creature| creature.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| EchoError::Unknown {
+                what: "creature",
+                input: s.to_string(),
+            })
+    }
+}
+
 impl TryFrom<usize> for Creature {
     type Error = ();
     fn try_from(value: usize) -> Result<Self, Self::Error> {
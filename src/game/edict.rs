@@ -1,14 +1,17 @@
 use crate::{
+    error::EchoError,
     helpers::bitfield::{Bitfield, Bitfield16},
     make_bitfield,
 };
 use std::{
     debug_assert,
     fmt::{self, Display},
+    str::FromStr,
 };
 
 // {{{ Edict
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Edict {
     // Victory point edicts
     RileThePublic,
@@ -26,6 +29,20 @@ impl Display for Edict {
     }
 }
 
+impl FromStr for Edict {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Edict::EDICTS
+            .into_iter()
+            .find(|edict| edict.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| EchoError::Unknown {
+                what: "edict",
+                input: s.to_string(),
+            })
+    }
+}
+
 impl Edict {
     pub const EDICTS: [Edict; 5] = [
         Edict::RileThePublic,
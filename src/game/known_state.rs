@@ -2,25 +2,66 @@ use super::battlefield::{Battlefield, Battlefields};
 use super::creature::{Creature, CreatureSet};
 use super::edict::{Edict, EdictSet};
 use super::known_state_summary::KnownStateEssentials;
-use super::status_effect::{StatusEffect, StatusEffectSet};
+use super::rules::{Ruleset, ScoringMode};
+use super::status_effect::{Stacking, StatusEffect, StatusEffectSet};
 use super::types::{Player, Score};
+use crate::cfr::hidden_index::{EncodingInfo, HiddenState, PerPhaseInfo};
+use crate::error::{EchoError, EchoResult};
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::pair::{are_equal, Pair};
 
 /// State of a player known by both players.
-#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KnownPlayerState {
     pub edicts: EdictSet,
     pub effects: StatusEffectSet,
+
+    /// The number of battles each active effect lingers
+    /// for after the current one, indexed by effect.
+    pub lingering: [u8; StatusEffect::STATUS_EFFECTS.len()],
+}
+
+impl KnownPlayerState {
+    /// Applies an effect to the player, according to its duration and stacking rules.
+    pub fn apply_effect(&mut self, effect: StatusEffect) {
+        let lingering = &mut self.lingering[effect as usize];
+
+        if !self.effects.has(effect) {
+            *lingering = effect.duration() - 1;
+        } else {
+            *lingering = match effect.stacking() {
+                Stacking::Refresh => u8::max(*lingering, effect.duration() - 1),
+                Stacking::Extend => *lingering + effect.duration(),
+            };
+        }
+
+        self.effects.insert(effect);
+    }
+
+    /// Moves on to the next battle, getting rid of the effects which ran out.
+    pub fn expire_effects(&mut self) {
+        for effect in self.effects {
+            let lingering = &mut self.lingering[effect as usize];
+
+            if *lingering == 0 {
+                self.effects.remove(effect);
+            } else {
+                *lingering -= 1;
+            }
+        }
+    }
 }
 
 /// State known by both players at some point in time.
-#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KnownState {
     pub player_states: Pair<KnownPlayerState>,
     pub battlefields: Battlefields,
     pub graveyard: CreatureSet,
     pub score: Score,
+    pub rules: Ruleset,
 }
 
 impl KnownStateEssentials for KnownState {
@@ -52,13 +93,66 @@ impl KnownStateEssentials for KnownState {
 }
 
 impl KnownState {
-    pub fn new_starting(battlefields: [Battlefield; 4]) -> Self {
+    pub fn new_starting(battlefields: [Battlefield; Battlefields::COUNT]) -> Self {
+        Self::new_with_rules(battlefields, Ruleset::default())
+    }
+
+    pub fn new_with_rules(
+        battlefields: [Battlefield; Battlefields::COUNT],
+        rules: Ruleset,
+    ) -> Self {
         Self {
             player_states: Default::default(),
             graveyard: Default::default(),
             score: Default::default(),
             battlefields: Battlefields::new(battlefields),
+            rules,
+        }
+    }
+
+    /// Creates the starting state of a game in which the hands of both
+    /// players are fully specified. Useful for studying specific matchups,
+    /// or for testing without relying on randomness.
+    ///
+    /// Returns the state, together with the hidden information of both players.
+    pub fn new_dealt(
+        battlefields: [Battlefield; Battlefields::COUNT],
+        rules: Ruleset,
+        hands: Pair<CreatureSet>,
+    ) -> EchoResult<(Self, Pair<EncodingInfo>)> {
+        let state = Self::new_with_rules(battlefields, rules);
+        state.validate()?;
+
+        if hands[0] & hands[1] != CreatureSet::empty() {
+            return Err(EchoError::InvalidPosition(
+                "The hands of the two players must be disjoint".to_string(),
+            ));
+        }
+
+        for hand in hands {
+            HiddenState::new(hand, None).validate_against(&state)?;
         }
+
+        Ok((state, hands.map(PerPhaseInfo::Main)))
+    }
+
+    /// Similar to `new_dealt`, except two deals get returned: the given one,
+    /// and the one where the players swap hands. Playing both games (mirror
+    /// matches) makes sure neither player gets an advantage from the deal.
+    pub fn new_mirrored(
+        battlefields: [Battlefield; Battlefields::COUNT],
+        rules: Ruleset,
+        hands: Pair<CreatureSet>,
+    ) -> EchoResult<(Self, [Pair<EncodingInfo>; 2])> {
+        let (state, [mine, yours]) = Self::new_dealt(battlefields, rules, hands)?;
+
+        Ok((state, [[mine, yours], [yours, mine]]))
+    }
+
+    /// Returns whether the game ends after the current battle.
+    #[inline(always)]
+    pub fn is_last_turn(&self) -> bool {
+        self.rules.is_last_turn(&self.battlefields)
     }
 
     /// Returns whether the current known game state is symmetrical.
@@ -86,12 +180,16 @@ impl KnownState {
     /// no matter what the opponent can pull off.
     // TODO: add stalling with wall?
     pub fn guaranteed_win(&self, player: Player) -> bool {
+        let turns_left = self.rules.turns_left(&self.battlefields);
+
+        if self.rules.scoring == ScoringMode::BattlesWon {
+            return self.score(player) > Score(turns_left as i8);
+        }
+
         // {{{ Rile the public spam
         let has_rtp = self.player_edicts(!player).has(Edict::RileThePublic);
         let has_steward = !self.graveyard.has(Creature::Steward);
         let has_urban = self.battlefields.will_be_active(Battlefield::Urban);
-
-        let turns_left = 4 - self.battlefields.current;
         let mut rtp_usages = 0;
 
         if has_rtp {
@@ -115,7 +213,8 @@ impl KnownState {
             .battlefields
             .active()
             .into_iter()
-            .map(|battlefield| battlefield.reward())
+            .take(turns_left)
+            .map(|battlefield| self.rules.reward(*battlefield))
             .sum::<u8>() as i8
             + rtp_usages;
 
@@ -136,3 +235,19 @@ impl KnownState {
         self.score(player) > Score(max_opponent_gain)
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_roundtrip() {
+        let mut state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        state.graveyard.insert(Creature::Wall);
+        state.player_states[1].apply_effect(StatusEffect::Seer);
+        state.score = Score(-3);
+
+        let encoded = toml::to_string(&state).unwrap();
+        assert_eq!(toml::from_str::<KnownState>(&encoded).unwrap(), state);
+    }
+}
@@ -1,5 +1,5 @@
 use super::{
-    creature::CreatureSet, creature_choice::UserCreatureChoice, edict::EdictSet, types::Player,
+    battlefield::Battlefields, creature::CreatureSet, creature_choice::UserCreatureChoice, edict::EdictSet, types::Player,
 };
 use crate::{
     cfr::phase::PhaseTag,
@@ -45,7 +45,7 @@ pub trait KnownStateEssentials {
     /// Computes the size of both players' hands at the start of the turn.
     #[inline(always)]
     fn hand_size(&self) -> usize {
-        5 - self.graveyard().len() / 2
+        Battlefields::COUNT + 1 - self.graveyard().len() / 2
     }
 
     /// Computes the size of the hand in a non-main phase.
@@ -77,6 +77,33 @@ pub trait KnownStateEssentials {
         self.seer_player().is_some()
     }
 
+    /// Returns the creatures which might have been set aside at the start of
+    /// the game (see `Battlefields::SET_ASIDE`), which neither player ever holds,
+    /// from the perspective of a player holding a given hand.
+    #[inline(always)]
+    fn overseer_candidates(&self, hand: CreatureSet) -> CreatureSet {
+        !(self.graveyard() | hand)
+    }
+
+    /// Enumerates every possible set of creatures put aside at the start of the
+    /// game, together with the hand the opponent of a player holding a given hand
+    /// would have in each case. Every pair is equally likely from the perspective
+    /// of said player.
+    #[inline(always)]
+    fn opponent_hands(
+        &self,
+        hand: CreatureSet,
+    ) -> impl Iterator<Item = (CreatureSet, CreatureSet)> {
+        let candidates = self.overseer_candidates(hand);
+
+        debug_assert_eq!(hand.len(), self.hand_size());
+        debug_assert_eq!(candidates.len(), self.hand_size() + Battlefields::SET_ASIDE);
+
+        candidates
+            .subsets_of_size(self.hand_size())
+            .map(move |opponent_hand| (candidates - opponent_hand, opponent_hand))
+    }
+
     /// Returns the edicts a player has in hand.
     #[inline(always)]
     fn player_edicts(&self, player: Player) -> EdictSet {
@@ -97,6 +124,7 @@ pub trait KnownStateEssentials {
 /// Furthermore, this struct holds the minimal information required
 /// to implement `KnownStateEssentials`.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KnownStateSummary {
     pub edict_sets: Pair<EdictSet>,
     pub graveyard: CreatureSet,
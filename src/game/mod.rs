@@ -8,4 +8,8 @@ pub mod creature_choice;
 pub mod known_state;
 pub mod known_state_summary;
 pub mod simulate;
+pub mod notation;
+pub mod record;
+pub mod rules;
+pub mod validate;
 
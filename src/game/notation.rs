@@ -0,0 +1,403 @@
+//! Compact, human-writable notation for positions, similar to FEN in chess.
+//!
+//! A position is made out of 8 fields separated by slashes:
+//! ```text
+//! NGUL/1/WS/RDSG,RDGA/-,S/+2/m/m:TAHM
+//! ```
+//! 1. The four battlefields.
+//! 2. The index of the current battlefield.
+//! 3. The graveyard (or `-` if empty).
+//! 4. The edicts in each player's hand (`mine,yours`).
+//! 5. The status effects affecting each player (`mine,yours`).
+//! 6. The score (from the perspective of the first player).
+//! 7. The phase:
+//!     - `m` for the main phase
+//!     - `s:<edicts>` for the sabotage phase (eg: `s:SG`)
+//!     - `e:<edicts>:<sabotage guesses>:<revealed creature>` for the seer phase,
+//!       where players who did not sabotage are represented by `-` (eg: `e:SG:W-:R`)
+//! 8. The hidden information known by some player: `<player>:<hand>[:<choice>]`,
+//!    where the player is either `m` (me) or `y` (you). The creature choice
+//!    must be present for every phase but the main one.
+//!
+//! Every card is denoted by a single letter:
+//! - creatures: `W`all, `S`eer, `R`ogue, `B`ard, `D`iplomat, ra`N`ger,
+//!   s`T`eward, b`A`rbarian, wit`H`, m`E`rcenary, `M`onarch
+//! - edicts: `R`ile the public, `D`ivert attention, `S`abotage, `G`ambit, `A`mbush
+//! - battlefields: `M`ountain, `G`lade, `U`rban, `N`ight, `L`ast strand, `P`lains
+//! - status effects: `M`ountain, `G`lade, `N`ight, `S`eer, `B`ard, m`E`rcenary, b`A`rbarian
+use super::battlefield::{Battlefield, Battlefields};
+use super::creature::{Creature, CreatureSet};
+use super::edict::{Edict, EdictSet};
+use super::known_state::KnownState;
+use super::status_effect::{StatusEffect, StatusEffectSet};
+use super::types::{Player, Score};
+use crate::cfr::hidden_index::{EncodingInfo, PerPhaseInfo};
+use crate::cfr::phase::{MainPhase, PerPhase, SabotagePhase, SeerPhase, SomePhase};
+use crate::error::{EchoError, EchoResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+
+// {{{ Card codes
+/// Types which can be represented by a single character.
+pub(crate) trait Code: Copy + PartialEq + 'static {
+    const NAME: &'static str;
+    const ALL: &'static [Self];
+
+    /// One character for every element of `ALL`, in the same order.
+    const CODES: &'static str;
+
+    fn code(self) -> char {
+        let index = Self::ALL.iter().position(|x| *x == self).unwrap();
+        Self::CODES.chars().nth(index).unwrap()
+    }
+
+    fn from_code(code: char) -> EchoResult<Self> {
+        Self::CODES
+            .chars()
+            .position(|c| c == code)
+            .map(|index| Self::ALL[index])
+            .ok_or_else(|| EchoError::Unknown {
+                what: Self::NAME,
+                input: code.to_string(),
+            })
+    }
+}
+
+impl Code for Creature {
+    const NAME: &'static str = "creature";
+    const ALL: &'static [Self] = &Creature::CREATURES;
+    const CODES: &'static str = "WSRBDNTAHEM";
+}
+
+impl Code for Edict {
+    const NAME: &'static str = "edict";
+    const ALL: &'static [Self] = &Edict::EDICTS;
+    const CODES: &'static str = "RDSGA";
+}
+
+impl Code for Battlefield {
+    const NAME: &'static str = "battlefield";
+    const ALL: &'static [Self] = &Battlefield::BATTLEFIELDS;
+    const CODES: &'static str = "MGUNLP";
+}
+
+impl Code for StatusEffect {
+    const NAME: &'static str = "status effect";
+    const ALL: &'static [Self] = &StatusEffect::STATUS_EFFECTS;
+    const CODES: &'static str = "MGNSBEA";
+}
+
+pub(crate) fn encode_set<B: Bitfield>(set: B) -> String
+where
+    B::Element: Code,
+{
+    if set == B::empty() {
+        "-".to_string()
+    } else {
+        set.into_iter().map(Code::code).collect()
+    }
+}
+
+pub(crate) fn decode_set<B: Bitfield>(source: &str) -> EchoResult<B>
+where
+    B::Element: Code,
+{
+    let mut result = B::empty();
+
+    if source == "-" {
+        return Ok(result);
+    }
+
+    for code in source.chars() {
+        let element = B::Element::from_code(code)?;
+
+        if result.has(element) {
+            return Err(EchoError::Notation(format!(
+                "Duplicate {} {code:?}",
+                B::Element::NAME
+            )));
+        }
+
+        result.insert(element);
+    }
+
+    Ok(result)
+}
+
+pub(super) fn decode_pair(source: &str) -> EchoResult<Pair<&str>> {
+    source
+        .split_once(',')
+        .map(|(mine, yours)| [mine, yours])
+        .ok_or_else(|| {
+            EchoError::Notation(format!(
+                "Expected a pair of the form mine,yours, got {source:?}"
+            ))
+        })
+}
+
+fn decode_edict_pair(source: &str) -> EchoResult<Pair<Edict>> {
+    match source.chars().collect::<Vec<_>>()[..] {
+        [mine, yours] => Ok([Edict::from_code(mine)?, Edict::from_code(yours)?]),
+        _ => Err(EchoError::Notation(format!(
+            "Expected exactly two edicts, got {source:?}"
+        ))),
+    }
+}
+// }}}
+// {{{ Encoding
+/// Encodes a position as a string. See the module level docs for the format.
+pub fn to_notation(
+    state: &KnownState,
+    phase: &SomePhase,
+    player: Player,
+    hidden: EncodingInfo,
+) -> String {
+    let battlefields: String = state.battlefields.all.iter().map(|b| b.code()).collect();
+    let [my_state, your_state] = state.player_states;
+
+    let phase = match phase {
+        PerPhase::Main(_) => "m".to_string(),
+        PerPhase::Sabotage(phase) => {
+            let [mine, yours] = phase.edict_choices;
+            format!("s:{}{}", mine.code(), yours.code())
+        }
+        PerPhase::Seer(phase) => {
+            let [mine, yours] = phase.edict_choices;
+            let guesses: String = phase
+                .sabotage_choices
+                .iter()
+                .map(|guess| guess.map_or('-', Creature::code))
+                .collect();
+
+            format!(
+                "e:{}{}:{guesses}:{}",
+                mine.code(),
+                yours.code(),
+                phase.revealed_creature.code()
+            )
+        }
+    };
+
+    let player = match player {
+        Player::Me => 'm',
+        Player::You => 'y',
+    };
+
+    let hidden = match hidden {
+        PerPhaseInfo::Main(hand) => format!("{player}:{}", encode_set(hand)),
+        PerPhaseInfo::Sabotage(hand, choice) | PerPhaseInfo::Seer(hand, choice, _) => {
+            format!("{player}:{}:{}", encode_set(hand), encode_set(choice))
+        }
+    };
+
+    format!(
+        "{battlefields}/{}/{}/{},{}/{},{}/{:+}/{phase}/{hidden}",
+        state.battlefields.current,
+        encode_set(state.graveyard),
+        encode_set(my_state.edicts),
+        encode_set(your_state.edicts),
+        encode_set(my_state.effects),
+        encode_set(your_state.effects),
+        state.score.0,
+    )
+}
+// }}}
+// {{{ Decoding
+/// Inverse of `to_notation`.
+pub fn from_notation(notation: &str) -> EchoResult<(KnownState, SomePhase, Player, EncodingInfo)> {
+    let fields: Vec<_> = notation.trim().split('/').collect();
+    let [battlefields, current, graveyard, edicts, effects, score, phase, hidden] = fields[..]
+    else {
+        return Err(EchoError::Notation(format!(
+            "Expected 8 fields, got {}",
+            fields.len()
+        )));
+    };
+
+    // {{{ Known state
+    let battlefields = battlefields
+        .chars()
+        .map(Battlefield::from_code)
+        .collect::<Result<Vec<_>, _>>()?
+        .try_into()
+        .map_err(|_| {
+            EchoError::Notation(format!(
+                "Expected exactly {} battlefields",
+                Battlefields::COUNT
+            ))
+        })?;
+
+    let mut state = KnownState::new_starting(battlefields);
+
+    state.battlefields.current = match current.parse() {
+        Ok(current) if current < Battlefields::COUNT => current,
+        _ => {
+            return Err(EchoError::Notation(format!(
+                "Invalid battlefield index {current:?}"
+            )))
+        }
+    };
+
+    state.graveyard = decode_set::<CreatureSet>(graveyard)?;
+    state.score = Score(
+        score
+            .parse()
+            .map_err(|_| EchoError::Notation(format!("Invalid score {score:?}")))?,
+    );
+
+    for (player_state, edicts) in state.player_states.iter_mut().zip(decode_pair(edicts)?) {
+        player_state.edicts = decode_set::<EdictSet>(edicts)?;
+    }
+
+    for (player_state, effects) in state.player_states.iter_mut().zip(decode_pair(effects)?) {
+        player_state.effects = decode_set::<StatusEffectSet>(effects)?;
+    }
+    // }}}
+    // {{{ Phase
+    let phase_fields: Vec<_> = phase.split(':').collect();
+    let phase = match phase_fields[..] {
+        ["m"] => PerPhase::Main(MainPhase::new()),
+        ["s", edicts] => PerPhase::Sabotage(SabotagePhase::new(decode_edict_pair(edicts)?)),
+        ["e", edicts, guesses, revealed] => {
+            let guesses: Pair<Option<Creature>> = guesses
+                .chars()
+                .map(|code| match code {
+                    '-' => Ok(None),
+                    code => Creature::from_code(code).map(Some),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+                .try_into()
+                .map_err(|_| {
+                    EchoError::Notation(format!(
+                        "Expected exactly two sabotage guesses, got {guesses:?}"
+                    ))
+                })?;
+
+            let revealed = match revealed.chars().collect::<Vec<_>>()[..] {
+                [code] => Creature::from_code(code)?,
+                _ => {
+                    return Err(EchoError::Notation(format!(
+                        "Expected a single revealed creature, got {revealed:?}"
+                    )))
+                }
+            };
+
+            let mut guessed = guesses.into_iter().flatten().chain([revealed]);
+            if guessed.any(|creature| state.graveyard.has(creature)) {
+                return Err(EchoError::Notation(
+                    "Creatures in the graveyard cannot be guessed or revealed".to_string(),
+                ));
+            }
+
+            PerPhase::Seer(SeerPhase::new(
+                decode_edict_pair(edicts)?,
+                guesses,
+                revealed,
+            ))
+        }
+        _ => return Err(EchoError::Notation(format!("Invalid phase {phase:?}"))),
+    };
+    // }}}
+    // {{{ Hidden info
+    let hidden_fields: Vec<_> = hidden.split(':').collect();
+    let (player, hand, choice) = match hidden_fields[..] {
+        [player, hand] => (player, hand, None),
+        [player, hand, choice] => (player, hand, Some(choice)),
+        _ => {
+            return Err(EchoError::Notation(format!(
+                "Invalid hidden info {hidden:?}"
+            )))
+        }
+    };
+
+    let player = match player {
+        "m" => Player::Me,
+        "y" => Player::You,
+        _ => return Err(EchoError::Notation(format!("Invalid player {player:?}"))),
+    };
+
+    let hand = decode_set::<CreatureSet>(hand)?;
+    if hand & state.graveyard != CreatureSet::empty() {
+        return Err(EchoError::Notation(
+            "The hand cannot contain creatures from the graveyard".to_string(),
+        ));
+    }
+
+    let choice = choice.map(decode_set::<CreatureSet>).transpose()?;
+    let hidden = match (&phase, choice) {
+        (PerPhase::Main(_), None) => PerPhaseInfo::Main(hand),
+        (PerPhase::Sabotage(_), Some(choice)) => PerPhaseInfo::Sabotage(hand, choice),
+        (PerPhase::Seer(phase), Some(choice)) => {
+            PerPhaseInfo::Seer(hand, choice, phase.revealed_creature)
+        }
+        (PerPhase::Main(_), Some(_)) => {
+            return Err(EchoError::Notation(
+                "No creatures can be chosen during the main phase".to_string(),
+            ))
+        }
+        (_, None) => {
+            return Err(EchoError::Notation(
+                "The creature choice is required after the main phase".to_string(),
+            ))
+        }
+    };
+    // }}}
+
+    Ok((state, phase, player, hidden))
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: [&str; 4] = [
+        "NGUL/0/-/RDSGA,RDSGA/-,-/+0/m/m:WSRBDNTAHEM",
+        "MGUP/1/WS/RDSG,RDGA/-,S/+2/m/y:RBDN",
+        "NGUL/2/WSRB/SG,GA/-,-/-3/s:SG/m:DNT:DN",
+        "PPPL/2/WSRB/SG,GA/S,-/-3/e:SG:T-:H/m:DNT:DN",
+    ];
+
+    #[test]
+    fn notation_roundtrips() {
+        for example in EXAMPLES {
+            let (state, phase, player, hidden) = from_notation(example).unwrap();
+            assert_eq!(to_notation(&state, &phase, player, hidden), example);
+        }
+    }
+
+    #[test]
+    fn starting_state_roundtrips() {
+        let state = KnownState::new_starting([Battlefield::Night; Battlefields::COUNT]);
+        let phase = PerPhase::Main(MainPhase::new());
+        let hidden = PerPhaseInfo::Main(CreatureSet::empty());
+        let notation = to_notation(&state, &phase, Player::Me, hidden);
+        let (decoded, _, _, _) = from_notation(&notation).unwrap();
+
+        assert_eq!(decoded, state);
+    }
+
+    #[test]
+    fn invalid_notation_is_rejected() {
+        let invalid = [
+            "",
+            "NGUL/0/-/RDSGA,RDSGA/-,-/+0/m",
+            "NGU/0/-/RDSGA,RDSGA/-,-/+0/m/m:W",
+            "NGUL/4/-/RDSGA,RDSGA/-,-/+0/m/m:W",
+            "NGUL/0/WW/RDSGA,RDSGA/-,-/+0/m/m:S",
+            "NGUL/0/W/RDSGA,RDSGA/-,-/+0/m/m:W",
+            "NGUL/0/-/RDSGA,RDSGA/-,-/+0/s:SG/m:WS",
+            "NGUL/0/-/RDSGA,RDSGA/-,-/+0/x/m:WS",
+            "NGUL/0/-/RDSGA/-,-/+0/m/m:WS",
+            "NGUL/0/W/RDSGA,RDSGA/-,-/+0/e:SS:W-:R/m:SR:S",
+        ];
+
+        for notation in invalid {
+            assert!(
+                from_notation(notation).is_err(),
+                "{notation:?} should be invalid"
+            );
+        }
+    }
+}
+// }}}
@@ -0,0 +1,527 @@
+//! Text format for recording entire games, similar to PGN in chess.
+//!
+//! A record is made out of a header of `[Tag "value"]` lines,
+//! followed by an empty line and one line for every turn:
+//! ```text
+//! [Battlefields "NGUL"]
+//! [Seed "42"]
+//! [Me "human"]
+//! [You "random"]
+//! [Result "+2"]
+//!
+//! 1. WS,R G,D -,- W
+//! 2. T,B S,A -,T -
+//! ```
+//! Every turn consists of four space separated fields:
+//! 1. The creatures chosen by each player (`mine,yours`).
+//! 2. The edicts played by each player (`mine,yours`).
+//! 3. The sabotage guesses of each player (`-` for no guess).
+//! 4. The creature picked during the seer phase (`-` if nobody had the seer effect).
+//!
+//! Cards are denoted by the same letters as in `game::notation`.
+//! The seed and result tags are optional, and unknown tags are ignored.
+use super::battlefield::{Battlefield, Battlefields};
+use super::choice::FinalMainPhaseChoice;
+use super::creature::{Creature, CreatureSet};
+use super::edict::Edict;
+use super::known_state::KnownState;
+use super::known_state_summary::KnownStateEssentials;
+use super::notation::{decode_pair, decode_set, encode_set, Code};
+use super::simulate::{BattleContext, BattleEvent};
+use super::types::{Player, Score, TurnResult};
+use crate::error::{EchoError, EchoResult};
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+// {{{ Helpers
+fn decode_single<T: Code>(source: &str) -> EchoResult<T> {
+    match source.chars().collect::<Vec<_>>()[..] {
+        [code] => T::from_code(code),
+        _ => Err(EchoError::Notation(format!(
+            "Expected a single {}, got {source:?}",
+            T::NAME
+        ))),
+    }
+}
+
+fn decode_optional<T: Code>(source: &str) -> EchoResult<Option<T>> {
+    if source == "-" {
+        Ok(None)
+    } else {
+        decode_single(source).map(Some)
+    }
+}
+// }}}
+
+// {{{ Turn records
+/// All the choices made by both players during one turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurnRecord {
+    /// The creatures chosen in the main phase. Contains two
+    /// creatures for players under the effect of the seer.
+    pub creatures: Pair<CreatureSet>,
+    pub edicts: Pair<Edict>,
+    pub sabotage_guesses: Pair<Option<Creature>>,
+
+    /// The creature the seer player decided to play.
+    pub seer_pick: Option<Creature>,
+}
+
+impl TurnRecord {
+    /// Returns the creature each player ended up playing.
+    pub fn played_creatures(&self) -> Option<Pair<Creature>> {
+        let [mine, yours] = self.creatures.map(|creatures| match creatures.len() {
+            1 => creatures.into_iter().next(),
+            2 => self.seer_pick.filter(|pick| creatures.has(*pick)),
+            _ => None,
+        });
+
+        Some([mine?, yours?])
+    }
+
+    /// Makes sure the choices in this turn are legal in the given state.
+    fn validate(&self, state: &KnownState, turn: usize) -> EchoResult<()> {
+        let invalid = |reason: String| EchoError::InvalidTurn { turn, reason };
+
+        for player in Player::PLAYERS {
+            let creatures = player.select(self.creatures);
+            let edict = player.select(self.edicts);
+
+            if creatures.len() != state.creature_choice_size(player) {
+                return Err(invalid(format!(
+                    "{player:?} must choose {} creature(s)",
+                    state.creature_choice_size(player)
+                )));
+            }
+
+            if (creatures & state.graveyard) != CreatureSet::empty() {
+                return Err(invalid(format!(
+                    "{player:?} chose creatures from the graveyard"
+                )));
+            }
+
+            if !state.player_edicts(player).has(edict) {
+                return Err(invalid(format!(
+                    "{player:?} does not have the {edict} edict"
+                )));
+            }
+
+            if let Some(guess) = player.select(self.sabotage_guesses) {
+                if edict != Edict::Sabotage || state.graveyard.has(guess) {
+                    return Err(invalid(format!("{player:?} cannot guess {guess}")));
+                }
+            }
+        }
+
+        let [mine, yours] = self.creatures;
+        if (mine & yours) != CreatureSet::empty() {
+            return Err(invalid("Both players chose the same creature".to_string()));
+        }
+
+        let needs_pick = self.creatures.iter().any(|creatures| creatures.len() == 2);
+        if needs_pick != self.seer_pick.is_some() {
+            return Err(invalid(
+                "The seer pick must be present iff someone chose two creatures".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for TurnRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [my_creatures, your_creatures] = self.creatures.map(encode_set);
+        let [my_edict, your_edict] = self.edicts.map(Edict::code);
+        let [my_guess, your_guess] = self
+            .sabotage_guesses
+            .map(|guess| guess.map_or('-', Creature::code));
+        let seer_pick = self.seer_pick.map_or('-', Creature::code);
+
+        write!(
+            f,
+            "{my_creatures},{your_creatures} {my_edict},{your_edict} {my_guess},{your_guess} {seer_pick}"
+        )
+    }
+}
+
+impl FromStr for TurnRecord {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = s.split_whitespace().collect();
+        let [creatures, edicts, guesses, seer_pick] = fields[..] else {
+            return Err(EchoError::Notation(format!(
+                "Expected 4 fields, got {}",
+                fields.len()
+            )));
+        };
+
+        let [my_creatures, your_creatures] = decode_pair(creatures)?;
+        let [my_edict, your_edict] = decode_pair(edicts)?;
+        let [my_guess, your_guess] = decode_pair(guesses)?;
+
+        Ok(Self {
+            creatures: [decode_set(my_creatures)?, decode_set(your_creatures)?],
+            edicts: [decode_single(my_edict)?, decode_single(your_edict)?],
+            sabotage_guesses: [decode_optional(my_guess)?, decode_optional(your_guess)?],
+            seer_pick: decode_optional(seer_pick)?,
+        })
+    }
+}
+// }}}
+// {{{ Game records
+/// A full game, together with some metadata about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameRecord {
+    pub battlefields: [Battlefield; Battlefields::COUNT],
+
+    /// The seed used for the random number generators of the agents, if any.
+    pub seed: Option<u64>,
+
+    /// Names of the agents playing each side.
+    pub agents: Pair<String>,
+    pub turns: Vec<TurnRecord>,
+
+    /// The final score. Not present for unfinished games.
+    pub result: Option<Score>,
+}
+
+impl GameRecord {
+    pub fn new(
+        battlefields: [Battlefield; Battlefields::COUNT],
+        seed: Option<u64>,
+        agents: Pair<String>,
+    ) -> Self {
+        Self {
+            battlefields,
+            seed,
+            agents,
+            turns: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Re-simulates the recorded game, making sure every turn is legal
+    /// and that the final score matches the recorded result (if any).
+    pub fn replay(&self) -> EchoResult<Replay> {
+        let mut state = KnownState::new_starting(self.battlefields);
+        let mut positions = Vec::with_capacity(self.turns.len());
+        let mut events = Vec::with_capacity(self.turns.len());
+        let mut final_score = None;
+
+        for (index, turn) in self.turns.iter().enumerate() {
+            let turn_number = index + 1;
+
+            if final_score.is_some() {
+                return Err(EchoError::InvalidTurn {
+                    turn: turn_number,
+                    reason: "The game is already over".to_string(),
+                });
+            }
+
+            turn.validate(&state, turn_number)?;
+
+            let [my_creature, your_creature] =
+                turn.played_creatures()
+                    .ok_or_else(|| EchoError::InvalidTurn {
+                        turn: turn_number,
+                        reason: "The seer pick must be one of the chosen creatures".to_string(),
+                    })?;
+
+            let main_choices = [
+                FinalMainPhaseChoice::new(my_creature, turn.edicts[0]),
+                FinalMainPhaseChoice::new(your_creature, turn.edicts[1]),
+            ];
+
+            positions.push(state);
+
+            let context = BattleContext::new(main_choices, turn.sabotage_guesses, state, false);
+            let (_, turn_result, battle_events) = context.advance_known_state_with_events();
+            events.push(battle_events);
+
+            match turn_result {
+                TurnResult::Finished(score) => final_score = Some(score),
+                TurnResult::Unfinished(next) => state = next,
+            }
+        }
+
+        if let (Some(recorded), Some(simulated)) = (self.result, final_score) {
+            if recorded != simulated {
+                return Err(EchoError::InvalidRecord(format!(
+                    "The recorded result ({:+}) does not match the simulated one ({:+})",
+                    recorded.0, simulated.0
+                )));
+            }
+        } else if self.result.is_some() {
+            return Err(EchoError::InvalidRecord(
+                "The game has a result but is not over".to_string(),
+            ));
+        }
+
+        Ok(Replay {
+            positions,
+            events,
+            final_score,
+        })
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let battlefields: String = self.battlefields.iter().map(|b| b.code()).collect();
+        let [me, you] = &self.agents;
+
+        writeln!(f, "[Battlefields {battlefields:?}]")?;
+
+        if let Some(seed) = self.seed {
+            writeln!(f, "[Seed \"{seed}\"]")?;
+        }
+
+        writeln!(f, "[Me {me:?}]")?;
+        writeln!(f, "[You {you:?}]")?;
+
+        if let Some(result) = self.result {
+            writeln!(f, "[Result \"{:+}\"]", result.0)?;
+        }
+
+        writeln!(f)?;
+
+        for (index, turn) in self.turns.iter().enumerate() {
+            writeln!(f, "{}. {turn}", index + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().map(str::trim);
+
+        // {{{ Header
+        let mut battlefields = None;
+        let mut seed = None;
+        let mut agents: Pair<Option<String>> = [None, None];
+        let mut result = None;
+
+        for line in lines.by_ref().take_while(|line| !line.is_empty()) {
+            let tag = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+                .and_then(|line| line.split_once(' '))
+                .and_then(|(name, value)| {
+                    let value = value.strip_prefix('"')?.strip_suffix('"')?;
+                    Some((name, value))
+                });
+
+            let Some((name, value)) = tag else {
+                return Err(EchoError::Notation(format!("Invalid header line {line:?}")));
+            };
+
+            match name {
+                "Battlefields" => {
+                    let parsed = value
+                        .chars()
+                        .map(Battlefield::from_code)
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    battlefields = Some(parsed.try_into().map_err(|_| {
+                        EchoError::Notation(format!(
+                            "Expected exactly {} battlefields",
+                            Battlefields::COUNT
+                        ))
+                    })?);
+                }
+                "Seed" => {
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| EchoError::Notation(format!("Invalid seed {value:?}")))?,
+                    )
+                }
+                "Me" => agents[0] = Some(value.to_string()),
+                "You" => agents[1] = Some(value.to_string()),
+                "Result" => {
+                    result = Some(Score(value.parse().map_err(|_| {
+                        EchoError::Notation(format!("Invalid result {value:?}"))
+                    })?))
+                }
+                _ => {}
+            }
+        }
+        // }}}
+        // {{{ Turns
+        let mut turns = Vec::new();
+
+        for line in lines.filter(|line| !line.is_empty()) {
+            let expected = format!("{}.", turns.len() + 1);
+            let turn = line.strip_prefix(&expected).ok_or_else(|| {
+                EchoError::Notation(format!("Expected line {line:?} to start with {expected:?}"))
+            })?;
+
+            turns.push(turn.parse().map_err(|error| {
+                EchoError::Notation(format!("Turn {}: {error}", turns.len() + 1))
+            })?);
+        }
+        // }}}
+
+        let [Some(me), Some(you)] = agents else {
+            return Err(EchoError::Notation(
+                "Both the Me and You tags are required".to_string(),
+            ));
+        };
+
+        Ok(Self {
+            battlefields: battlefields.ok_or_else(|| {
+                EchoError::Notation("The Battlefields tag is required".to_string())
+            })?,
+            seed,
+            agents: [me, you],
+            turns,
+            result,
+        })
+    }
+}
+// }}}
+// {{{ Replays
+/// The outcome of re-simulating a recorded game.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    /// The known state at the start of every turn, which can be
+    /// combined with a hand in order to analyze the position.
+    pub positions: Vec<KnownState>,
+
+    /// Explanations for how the battle of every turn got resolved.
+    pub events: Vec<Vec<BattleEvent>>,
+
+    /// The final score. Not present for unfinished games.
+    pub final_score: Option<Score>,
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::creature::Creature::*;
+    use crate::game::edict::Edict::*;
+    use crate::game::notation::decode_set;
+
+    fn creatures(source: &str) -> CreatureSet {
+        decode_set(source).unwrap()
+    }
+
+    fn example_record() -> GameRecord {
+        let mut record = GameRecord::new(
+            Battlefields::last_of([
+                Battlefield::Night,
+                Battlefield::Glade,
+                Battlefield::Urban,
+                Battlefield::LastStrand,
+            ]),
+            Some(42),
+            ["human".to_string(), "random".to_string()],
+        );
+
+        record.turns.push(TurnRecord {
+            creatures: [creatures("WS"), creatures("R")],
+            edicts: [Gambit, DivertAttention],
+            sabotage_guesses: [None, None],
+            seer_pick: Some(Wall),
+        });
+
+        record.turns.push(TurnRecord {
+            creatures: [creatures("T"), creatures("B")],
+            edicts: [Sabotage, Ambush],
+            sabotage_guesses: [None, Some(Steward)],
+            seer_pick: None,
+        });
+
+        record.result = Some(Score(2));
+        record
+    }
+
+    #[test]
+    fn records_are_written_correctly() {
+        let expected = "[Battlefields \"NGUL\"]
+[Seed \"42\"]
+[Me \"human\"]
+[You \"random\"]
+[Result \"+2\"]
+
+1. WS,R G,D -,- W
+2. T,B S,A -,T -
+";
+
+        assert_eq!(example_record().to_string(), expected);
+    }
+
+    #[test]
+    fn records_roundtrip() {
+        let record = example_record();
+        let parsed: GameRecord = record.to_string().parse().unwrap();
+
+        assert_eq!(parsed, record);
+    }
+
+    /// Game played by two random agents.
+    const RECORDED_GAME: &str = "[Battlefields \"NGUL\"]
+[Seed \"1\"]
+[Me \"random\"]
+[You \"random\"]
+[Result \"-10\"]
+
+1. T,E A,S -,B -
+2. S,D R,A -,- -
+3. WB,M G,G -,- W
+4. N,H A,D -,- -
+";
+
+    #[test]
+    fn recorded_games_can_be_replayed() {
+        let record: GameRecord = RECORDED_GAME.parse().unwrap();
+        let replay = record.replay().unwrap();
+
+        assert_eq!(replay.positions.len(), 4);
+        assert_eq!(replay.events.len(), 4);
+        assert_eq!(replay.final_score, Some(Score(-10)));
+        assert!(replay.positions[3].graveyard.has(Wall));
+    }
+
+    #[test]
+    fn invalid_records_are_rejected() {
+        let wrong_result = RECORDED_GAME.replace("-10", "+3");
+        let illegal_edict = RECORDED_GAME.replace("S,D R,A", "S,D A,A");
+        let missing_pick = RECORDED_GAME.replace("G,G -,- W", "G,G -,- -");
+        let missing_tag = RECORDED_GAME.replace("[Me \"random\"]\n", "");
+
+        assert!(wrong_result
+            .parse::<GameRecord>()
+            .unwrap()
+            .replay()
+            .is_err());
+        assert!(illegal_edict
+            .parse::<GameRecord>()
+            .unwrap()
+            .replay()
+            .is_err());
+        assert!(missing_pick
+            .parse::<GameRecord>()
+            .unwrap()
+            .replay()
+            .is_err());
+        assert!(missing_tag.parse::<GameRecord>().is_err());
+    }
+
+    #[test]
+    fn played_creatures_take_the_seer_pick_into_account() {
+        let record = example_record();
+
+        assert_eq!(record.turns[0].played_creatures(), Some([Wall, Rogue]));
+        assert_eq!(record.turns[1].played_creatures(), Some([Steward, Bard]));
+    }
+}
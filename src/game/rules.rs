@@ -0,0 +1,91 @@
+//! Rule variants, such that alternative versions of the game
+//! can be simulated (and solved) without forking the code.
+use super::battlefield::{Battlefield, Battlefields};
+use crate::error::{EchoError, EchoResult};
+use serde::Deserialize;
+
+/// What players compete over.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringMode {
+    /// Battles award victory points, and the player with the most points wins.
+    Points,
+    /// Every battle is worth a single point, regardless of the battlefield
+    /// or effects, such that the player winning the most battles wins.
+    BattlesWon,
+}
+
+/// The rules a game gets played by. The default ruleset matches the base game.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, Deserialize)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[serde(default, deny_unknown_fields)]
+pub struct Ruleset {
+    /// How many battles the game lasts for (at most one per battlefield).
+    pub turns: u8,
+
+    /// Points awarded for winning a battle on the Last Strand.
+    pub last_strand_reward: u8,
+
+    /// Points awarded for winning a battle on any other battlefield.
+    pub battlefield_reward: u8,
+
+    /// Points awarded for defeating (or tying with) the monarch.
+    pub monarch_bonus: u8,
+
+    /// Whether a player who played the gambit loses ties
+    /// (unless both players did). Ties stay ties otherwise.
+    pub gambit_loses_ties: bool,
+
+    /// What the score keeps track of.
+    pub scoring: ScoringMode,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Self {
+            turns: Battlefields::COUNT as u8,
+            last_strand_reward: 5,
+            battlefield_reward: 3,
+            monarch_bonus: 2,
+            gambit_loses_ties: true,
+            scoring: ScoringMode::Points,
+        }
+    }
+}
+
+impl Ruleset {
+    /// Amount of points rewarded for winning a battle in some location.
+    #[inline(always)]
+    pub fn reward(&self, battlefield: Battlefield) -> u8 {
+        match battlefield {
+            Battlefield::LastStrand => self.last_strand_reward,
+            _ => self.battlefield_reward,
+        }
+    }
+
+    /// Returns whether the game ends after the current battle.
+    #[inline(always)]
+    pub fn is_last_turn(&self, battlefields: &Battlefields) -> bool {
+        battlefields.is_last() || battlefields.current + 1 >= self.turns as usize
+    }
+
+    /// Returns the number of battles left to play (including the current one).
+    #[inline(always)]
+    pub fn turns_left(&self, battlefields: &Battlefields) -> usize {
+        (self.turns as usize).saturating_sub(battlefields.current)
+    }
+
+    /// Makes sure the game can actually be played by these rules.
+    pub fn validate(&self) -> EchoResult<()> {
+        if !(1..=Battlefields::COUNT).contains(&(self.turns as usize)) {
+            return Err(EchoError::InvalidRules(format!(
+                "Games must last between 1 and {} turns, not {}",
+                Battlefields::COUNT,
+                self.turns
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -3,13 +3,225 @@ use super::choice::{FinalMainPhaseChoice, SabotagePhaseChoice};
 use super::creature::Creature;
 use super::edict::Edict;
 use super::known_state::KnownState;
+use super::rules::ScoringMode;
 use super::status_effect::{StatusEffect, StatusEffectSet};
 use super::types::{BattleResult, Player, TurnResult};
 use crate::game::edict::EdictSet;
 use crate::helpers::bitfield::Bitfield;
 use crate::helpers::pair::Pair;
+use std::fmt::{self, Display};
 use std::{debug_assert, debug_assert_eq};
 
+// {{{ Events
+/// Something which can trigger effects during a battle.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Cause {
+    Creature(Creature),
+    Edict(Edict),
+    Battlefield(Battlefield),
+    Effect(StatusEffect),
+}
+
+impl Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cause::Creature(creature) => write!(f, "{creature}"),
+            Cause::Edict(edict) => write!(f, "{edict}"),
+            Cause::Battlefield(battlefield) => write!(f, "{battlefield}"),
+            Cause::Effect(effect) => write!(f, "{effect} effect"),
+        }
+    }
+}
+
+/// A single step taken while resolving a battle. Useful
+/// for explaining to humans why a battle ended the way it did.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum BattleEvent {
+    /// The effect of the creature played by some player got negated.
+    EffectNegated { player: Player, by: Creature },
+    /// An effect decided the outcome of the battle for some player.
+    EffectTriggered {
+        player: Player,
+        cause: Cause,
+        outcome: BattleResult,
+    },
+    /// The strength of the creature played by some player changed.
+    StrengthModified {
+        player: Player,
+        modifier: i8,
+        cause: Cause,
+    },
+    /// The amount of points some player earns by winning the battle changed.
+    RewardAdjusted {
+        player: Player,
+        modifier: i8,
+        cause: Cause,
+    },
+    /// The final strengths of the creatures played by the two players.
+    StrengthsCompared { strengths: Pair<i8> },
+    /// The battle ended, changing the score by the given amount.
+    Resolved {
+        result: BattleResult,
+        score_delta: i8,
+    },
+}
+
+impl Display for BattleEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BattleEvent::EffectNegated { player, by } => {
+                write!(f, "[{player:?}] Creature negated by the {by}")
+            }
+            BattleEvent::EffectTriggered {
+                player,
+                cause,
+                outcome,
+            } => {
+                let outcome = match outcome {
+                    BattleResult::Won => "won",
+                    BattleResult::Tied => "tied",
+                    BattleResult::Lost => "lost",
+                };
+
+                write!(
+                    f,
+                    "[{player:?}] {outcome} the battle because of the {cause}"
+                )
+            }
+            BattleEvent::StrengthModified {
+                player,
+                modifier,
+                cause,
+            } => write!(f, "[{player:?}] {modifier:+} strength ({cause})"),
+            BattleEvent::RewardAdjusted {
+                player,
+                modifier,
+                cause,
+            } => write!(f, "[{player:?}] {modifier:+} points ({cause})"),
+            BattleEvent::StrengthsCompared {
+                strengths: [mine, yours],
+            } => write!(f, "Strengths: {mine} vs {yours}"),
+            BattleEvent::Resolved {
+                result,
+                score_delta,
+            } => write!(f, "Resolved as {result:?} (score {score_delta:+})"),
+        }
+    }
+}
+
+/// A change to some quantity (strength or reward) taking place during a battle.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Modifier {
+    pub amount: i8,
+    pub cause: Cause,
+    /// The tag marking the implementation of the rule in the source code
+    /// (for instance, `MOUNTAIN EFFECT 1`).
+    pub source: &'static str,
+}
+
+impl Modifier {
+    #[inline(always)]
+    fn new(amount: i8, cause: Cause, source: &'static str) -> Self {
+        Self {
+            amount,
+            cause,
+            source,
+        }
+    }
+}
+
+impl Display for Modifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:+} ({}) [[{}]]", self.amount, self.cause, self.source)
+    }
+}
+
+/// A summary of how a battle gets resolved, relative to some player.
+/// Pairs are ordered such that the first element always belongs to
+/// the player the breakdown was created for. Meant to be shown to humans.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct BattleBreakdown {
+    pub player: Player,
+    /// The strengths the two creatures have before any modifiers are applied.
+    pub base_strengths: Pair<i8>,
+    /// Every modifier applied to the strengths of the two creatures.
+    pub strength_modifiers: Pair<Vec<Modifier>>,
+    /// The strengths the two creatures end up with.
+    pub strengths: Pair<i8>,
+    /// The base amount of points winning the battle is worth.
+    pub base_reward: u8,
+    /// Every modifier applied to the rewards each player would
+    /// earn by winning the battle (the total is clamped at zero).
+    pub reward_modifiers: Pair<Vec<Modifier>>,
+    pub result: BattleResult,
+    /// The amount the score changes by, from the perspective of `player`.
+    pub score_delta: i8,
+}
+
+impl Display for BattleBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, label) in ["Mine", "Theirs"].into_iter().enumerate() {
+            writeln!(f, "{label}: {} base strength", self.base_strengths[index])?;
+
+            for modifier in &self.strength_modifiers[index] {
+                writeln!(f, "    {modifier}")?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Strengths: {} vs {}",
+            self.strengths[0], self.strengths[1]
+        )?;
+        writeln!(f, "Reward: {} base points", self.base_reward)?;
+
+        for (index, label) in ["Mine", "Theirs"].into_iter().enumerate() {
+            for modifier in &self.reward_modifiers[index] {
+                writeln!(f, "    [{label}] {modifier}")?;
+            }
+        }
+
+        write!(
+            f,
+            "Result: {:?} (score {:+})",
+            self.result, self.score_delta
+        )
+    }
+}
+
+/// Somewhere to send battle events to. Events are built lazily,
+/// such that resolving battles without logging costs nothing extra.
+trait EventLog {
+    fn log(&mut self, event: impl FnOnce() -> BattleEvent);
+}
+
+impl EventLog for () {
+    #[inline(always)]
+    fn log(&mut self, _event: impl FnOnce() -> BattleEvent) {}
+}
+
+impl EventLog for Vec<BattleEvent> {
+    fn log(&mut self, event: impl FnOnce() -> BattleEvent) {
+        self.push(event());
+    }
+}
+// }}}
+
+/// Returns the creature negating the effect of some creature
+/// played against a given opposing creature, if any.
+#[inline(always)]
+pub fn negated_by(creature: Creature, opponent: Creature) -> Option<Creature> {
+    // [[[WITCH EFFECT 1]]]
+    if opponent == Creature::Witch {
+        Some(Creature::Witch)
+    // [[[ROGUE EFFECT 1]]]
+    } else if creature == Creature::Seer && opponent == Creature::Rogue {
+        Some(Creature::Rogue)
+    } else {
+        None
+    }
+}
+
 // Context required resolving a battle
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct BattleContext {
@@ -77,16 +289,16 @@ impl BattleContext {
         self.state.battlefields.current()
     }
 
+    /// Returns the creature negating the creature a player has played, if any.
+    #[inline(always)]
+    fn negated_by(&self, player: Player) -> Option<Creature> {
+        negated_by(self.creature(player), self.creature(!player))
+    }
+
     /// Checks if the creature a player has played is negated.
     #[inline(always)]
     fn creature_is_negated(&self, player: Player) -> bool {
-        // [[[WITCH EFFECT 1]]]
-        let witch = self.creature(!player) == Creature::Witch;
-        // [[[ROGUE EFFECT 1]]]
-        let rogue =
-            self.creature(player) == Creature::Seer && self.creature(!player) == Creature::Rogue;
-
-        witch || rogue
+        self.negated_by(player).is_some()
     }
 
     /// Returns true if the given creature is the one a given player
@@ -123,13 +335,18 @@ impl BattleContext {
         self.battlefield().bonus(self.creature(player))
     }
 
-    /// Calculates the strength modifier for the creature the current player has played
-    fn strength_modifier(&self, player: Player) -> i8 {
+    /// Calls `f` with every strength modifier affecting
+    /// the creature the current player has played.
+    #[inline(always)]
+    fn for_each_strength_modifier(&self, player: Player, mut f: impl FnMut(Modifier)) {
         let effects = self.player_effects(player);
-        let mut result: i8 = 0;
 
         if self.battlefield_bonus(player) {
-            result += 2;
+            f(Modifier::new(
+                2,
+                Cause::Battlefield(self.battlefield()),
+                "BATTLEFIELD BONUS",
+            ));
         }
 
         // Creature strength bonuses:
@@ -139,11 +356,19 @@ impl BattleContext {
                 Creature::Ranger
                     if self.battlefield_bonus(player) && !(self.battlefield_bonus(!player)) =>
                 {
-                    result += 2;
+                    f(Modifier::new(
+                        2,
+                        Cause::Creature(Creature::Ranger),
+                        "RANGER EFFECT 1",
+                    ));
                 }
                 // [[[BARBARIAN EFFECT 1]]]
                 Creature::Barbarian if effects.has(StatusEffect::Barbarian) => {
-                    result += 2;
+                    f(Modifier::new(
+                        2,
+                        Cause::Effect(StatusEffect::Barbarian),
+                        "BARBARIAN EFFECT 1",
+                    ));
                 }
                 _ => {}
             }
@@ -153,7 +378,7 @@ impl BattleContext {
         // (the witch cannot get strength bonuses from edicts)
         // [[[WITCH EFFECT 2]]]
         if self.creature(player) != Creature::Witch {
-            result += self.edict_multiplier(player) as i8
+            let bonus = self.edict_multiplier(player)
                 * match self.edict(player) {
                     // [[[SABOTAGE EFFECT 1]]]
                     Edict::Sabotage
@@ -166,74 +391,103 @@ impl BattleContext {
                     // [[[GAMBIT EFFECT 1]]]
                     Edict::Gambit => 1,
                     _ => 0,
-                }
+                };
+
+            if bonus != 0 {
+                let source = match self.edict(player) {
+                    Edict::Sabotage => "SABOTAGE EFFECT 1",
+                    Edict::Ambush => "AMBUSH EFFECT 1",
+                    _ => "GAMBIT EFFECT 1",
+                };
+
+                f(Modifier::new(
+                    bonus,
+                    Cause::Edict(self.edict(player)),
+                    source,
+                ));
+            }
         }
 
         // Lingering effects which modify strength:
         // Effects caused by the previously played creature
         // [[[BARD EFFECT 1]]]
         if effects.has(StatusEffect::Bard) {
-            result += 1;
+            f(Modifier::new(
+                1,
+                Cause::Effect(StatusEffect::Bard),
+                "BARD EFFECT 1",
+            ));
         // [[[MERCENARY EFFECT 1]]]
         } else if effects.has(StatusEffect::Mercenary) {
-            result -= 1;
+            f(Modifier::new(
+                -1,
+                Cause::Effect(StatusEffect::Mercenary),
+                "MERCENARY EFFECT 1",
+            ));
         }
 
         // Effects caused by previous battlefields
         // [[[MOUNTAIN EFFECT 1]]]
         if effects.has(StatusEffect::Mountain) {
-            result += 1;
+            f(Modifier::new(
+                1,
+                Cause::Effect(StatusEffect::Mountain),
+                "MOUNTAIN EFFECT 1",
+            ));
         }
-
-        result
     }
 
-    /// Calculate strength modifiers for a player and it's opponent.
-    fn strength_modifiers(&self, player: Player) -> (i8, i8) {
-        (
-            self.strength_modifier(player),
-            self.strength_modifier(!player),
-        )
+    /// Calculates the strength modifier for the creature the current player has played
+    fn strength_modifier(&self, player: Player) -> i8 {
+        let mut result = 0;
+        self.for_each_strength_modifier(player, |modifier| result += modifier.amount);
+        result
     }
 
-    /// Check if some player wins because of an effect
-    fn wins_by_effect(&self, player: Player) -> bool {
+    /// Returns the creature whose effect makes some player win, if any.
+    fn effect_win(&self, player: Player) -> Option<Creature> {
         if self.creature_is_negated(player) {
-            return false;
+            return None;
         }
 
+        let creature = self.creature(player);
+
         // The wall gets negated by the witch and rogue characters
         // [[[ROGUE EFFECT 2]]]
         // [[[WITCH EFFECT 3]]]
         if self.creature(!player) == Creature::Wall
-            && (self.creature(player) == Creature::Witch
-                || self.creature(player) == Creature::Rogue)
+            && (creature == Creature::Witch || creature == Creature::Rogue)
         {
-            return true;
+            return Some(creature);
         }
 
         // The rogue wins against the monarch
         // [[[ROGUE EFFECT 2]]]
-        if self.creature(player) == Creature::Rogue && self.creature(!player) == Creature::Monarch {
-            return true;
+        if creature == Creature::Rogue && self.creature(!player) == Creature::Monarch {
+            return Some(creature);
         }
 
         // The diplomat wins against any creature
         // if the two edicts are identical
         // [[[DIPLOMAT EFFECT 1]]]
-        if self.creature(player) == Creature::Diplomat && self.edict(player) == self.edict(!player)
-        {
-            return true;
+        if creature == Creature::Diplomat && self.edict(player) == self.edict(!player) {
+            return Some(creature);
         }
 
-        return false;
+        None
+    }
+
+    /// Check if some player wins because of an effect
+    fn wins_by_effect(&self, player: Player) -> bool {
+        self.effect_win(player).is_some()
     }
 
     /// Resolves the gambit effects on a tie, relative to a given player.
     /// [[[GAMBIT EFFECT 2]]]
     fn resolve_gambits(&self, player: Player) -> BattleResult {
-        // If both players played gambits, nothing happens
-        if self.edict(player) == self.edict(!player) {
+        // If both players played gambits (or the rules
+        // say gambits do not matter), nothing happens
+        if self.edict(player) == self.edict(!player) || !self.state.rules.gambit_loses_ties {
             return BattleResult::Tied;
         }
 
@@ -251,40 +505,94 @@ impl BattleContext {
         BattleResult::Tied
     }
 
+    /// Resolves a tie relative to some player, logging the effect of gambits.
+    fn resolve_tie(&self, player: Player, log: &mut impl EventLog) -> BattleResult {
+        let result = self.resolve_gambits(player);
+
+        if result != BattleResult::Tied {
+            let gambit_player = if result == BattleResult::Lost {
+                player
+            } else {
+                !player
+            };
+
+            log.log(|| BattleEvent::EffectTriggered {
+                player: gambit_player,
+                cause: Cause::Edict(Edict::Gambit),
+                outcome: BattleResult::Lost,
+            });
+        }
+
+        result
+    }
+
     /// Resolves a battle relative to some player
     fn battle_result(&self, player: Player) -> BattleResult {
-        if self.wins_by_effect(player) {
-            return BattleResult::Won;
-        } else if self.wins_by_effect(!player) {
-            return BattleResult::Lost;
+        self.battle_result_with(player, &mut ())
+    }
+
+    fn battle_result_with(&self, player: Player, log: &mut impl EventLog) -> BattleResult {
+        for player in [player, !player] {
+            if let Some(by) = self.negated_by(player) {
+                log.log(|| BattleEvent::EffectNegated { player, by });
+            }
         }
+
+        for (player, result) in [(player, BattleResult::Won), (!player, BattleResult::Lost)] {
+            if let Some(creature) = self.effect_win(player) {
+                log.log(|| BattleEvent::EffectTriggered {
+                    player,
+                    cause: Cause::Creature(creature),
+                    outcome: BattleResult::Won,
+                });
+
+                return result;
+            }
+        }
+
         // The wall can force ties.
         // We don't have to check for the wall being negated here,
         // as that would trigger a win by effect.
         // [[[WALL EFFECT 1]]]
-        else if self.creature(player) == Creature::Wall
-            || self.creature(!player) == Creature::Wall
-        {
-            return self.resolve_gambits(player);
+        for player in [player, !player] {
+            if self.creature(player) == Creature::Wall {
+                log.log(|| BattleEvent::EffectTriggered {
+                    player,
+                    cause: Cause::Creature(Creature::Wall),
+                    outcome: BattleResult::Tied,
+                });
+            }
         }
 
-        let base_strengths = (
-            self.creature(player).strength() as i8,
-            self.creature(!player).strength() as i8,
-        );
+        if self.creature(player) == Creature::Wall || self.creature(!player) == Creature::Wall {
+            return self.resolve_tie(player, log);
+        }
 
-        let strength_modifiers = self.strength_modifiers(player);
-        let strengths = (
-            base_strengths.0 + strength_modifiers.0,
-            base_strengths.1 + strength_modifiers.1,
-        );
+        let strengths = [player, !player].map(|player| {
+            let mut strength = self.creature(player).strength() as i8;
+
+            self.for_each_strength_modifier(player, |modifier| {
+                strength += modifier.amount;
+                log.log(|| BattleEvent::StrengthModified {
+                    player,
+                    modifier: modifier.amount,
+                    cause: modifier.cause,
+                });
+            });
+
+            strength
+        });
+
+        log.log(|| BattleEvent::StrengthsCompared {
+            strengths: player.order_as(strengths),
+        });
 
-        if strengths.0 < strengths.1 {
+        if strengths[0] < strengths[1] {
             BattleResult::Lost
-        } else if strengths.0 > strengths.1 {
+        } else if strengths[0] > strengths[1] {
             BattleResult::Won
         } else {
-            self.resolve_gambits(player)
+            self.resolve_tie(player, log)
         }
     }
 
@@ -303,36 +611,79 @@ impl BattleContext {
             }
     }
 
-    /// Calculates the amount of victory points
-    /// earned by winning this partidcular battle
-    /// as a given player.
-    fn battle_reward(&self, player: Player) -> u8 {
+    /// Calls `f` with every change to the amount of victory points
+    /// earned by winning this battle as a given player.
+    #[inline(always)]
+    fn for_each_reward_modifier(&self, player: Player, mut f: impl FnMut(Modifier)) {
         let effects = self.player_effects(player);
-        let mut total = self.battlefield().reward();
 
         // Lingering effects:
         // [[[NIGHT EFFECT 1]]]
         if effects.has(StatusEffect::Night) {
-            total += 1;
+            f(Modifier::new(
+                1,
+                Cause::Effect(StatusEffect::Night),
+                "NIGHT EFFECT 1",
+            ));
         // [[[GLADE EFFECT 1]]]
         } else if effects.has(StatusEffect::Glade) {
-            total += 2;
+            f(Modifier::new(
+                2,
+                Cause::Effect(StatusEffect::Glade),
+                "GLADE EFFECT 1",
+            ));
         }
 
         // [[[BARD EFFECT 2]]]
         if effects.has(StatusEffect::Bard) {
-            total += 1;
+            f(Modifier::new(
+                1,
+                Cause::Effect(StatusEffect::Bard),
+                "BARD EFFECT 2",
+            ));
         }
 
         // Apply the "rile the public" and "divert attention" edicts.
-        // This is the only place where the total can decrease,
-        // which is why we must be careful for it not to become negative.
-        total = i8::max(
-            0,
-            total as i8 + self.edict_reward(player) + self.edict_reward(!player),
-        ) as u8;
+        for player in [player, !player] {
+            let reward = self.edict_reward(player);
+
+            if reward != 0 {
+                let source = match self.edict(player) {
+                    Edict::RileThePublic => "RILETHEPUBLIC EFFECT 1",
+                    _ => "DIVERTATTENTION EFFECT 1",
+                };
+
+                f(Modifier::new(
+                    reward,
+                    Cause::Edict(self.edict(player)),
+                    source,
+                ));
+            }
+        }
+    }
 
-        total
+    /// Calculates the amount of victory points
+    /// earned by winning this partidcular battle
+    /// as a given player.
+    fn battle_reward(&self, player: Player) -> u8 {
+        self.battle_reward_with(player, &mut ())
+    }
+
+    fn battle_reward_with(&self, player: Player, log: &mut impl EventLog) -> u8 {
+        let mut total = self.state.rules.reward(self.battlefield()) as i8;
+
+        self.for_each_reward_modifier(player, |modifier| {
+            total += modifier.amount;
+            log.log(|| BattleEvent::RewardAdjusted {
+                player,
+                modifier: modifier.amount,
+                cause: modifier.cause,
+            });
+        });
+
+        // Edicts are the only thing which can decrease the total,
+        // which is why we must be careful for it not to become negative.
+        i8::max(0, total) as u8
     }
 
     /// The reward for a player killing the monarch
@@ -342,7 +693,7 @@ impl BattleContext {
             BattleResult::Won | BattleResult::Tied
                 if self.is_active_creature(!player, Creature::Monarch) =>
             {
-                2
+                self.state.rules.monarch_bonus
             }
             _ => 0,
         }
@@ -352,34 +703,130 @@ impl BattleContext {
     /// - positive values mean we've earned points
     /// - negative values mean the opponent has gained points
     fn battle_score_delta(&self, result: BattleResult, player: Player) -> i8 {
+        self.battle_score_delta_with(result, player, &mut ())
+    }
+
+    fn battle_score_delta_with(
+        &self,
+        result: BattleResult,
+        player: Player,
+        log: &mut impl EventLog,
+    ) -> i8 {
+        // Rewards don't matter when only the number of battles won does
+        if self.state.rules.scoring == ScoringMode::BattlesWon {
+            return match result {
+                BattleResult::Won => 1,
+                BattleResult::Tied => 0,
+                BattleResult::Lost => -1,
+            };
+        }
+
         let mut delta = match result {
             BattleResult::Tied => 0,
-            BattleResult::Won => self.battle_reward(player) as i8,
-            BattleResult::Lost => -(self.battle_reward(!player) as i8),
+            BattleResult::Won => self.battle_reward_with(player, log) as i8,
+            BattleResult::Lost => -(self.battle_reward_with(!player, log) as i8),
         };
 
         // Trigger monarch's effect
-        delta += self.monarch_reward(player, result) as i8;
-        delta -= self.monarch_reward(!player, !result) as i8;
+        for (player, result, sign) in [(player, result, 1), (!player, !result, -1)] {
+            let reward = self.monarch_reward(player, result);
+
+            if reward > 0 {
+                log.log(|| BattleEvent::RewardAdjusted {
+                    player,
+                    modifier: reward as i8,
+                    cause: Cause::Creature(Creature::Monarch),
+                });
+            }
+
+            delta += sign * reward as i8;
+        }
 
         delta
     }
 
+    /// Similar to `advance_known_state`, but additionally returns
+    /// every event which took place while resolving the battle, in order.
+    pub fn advance_known_state_with_events(
+        &self,
+    ) -> (BattleResult, TurnResult<KnownState>, Vec<BattleEvent>) {
+        let mut events = vec![];
+        let (result, turn_result) = self.advance_known_state_with(&mut events);
+
+        (result, turn_result, events)
+    }
+
+    /// Explains how the battle gets resolved, relative to a given player.
+    pub fn explain(&self, player: Player) -> BattleBreakdown {
+        let players = [player, !player];
+        let result = self.battle_result(player);
+
+        let strength_modifiers = players.map(|player| {
+            let mut modifiers = vec![];
+            self.for_each_strength_modifier(player, |modifier| modifiers.push(modifier));
+            modifiers
+        });
+
+        let reward_modifiers = players.map(|player| {
+            let mut modifiers = vec![];
+            self.for_each_reward_modifier(player, |modifier| modifiers.push(modifier));
+            modifiers
+        });
+
+        let base_strengths = players.map(|player| self.creature(player).strength() as i8);
+        let strengths = [0, 1].map(|index| {
+            base_strengths[index]
+                + strength_modifiers[index]
+                    .iter()
+                    .map(|modifier| modifier.amount)
+                    .sum::<i8>()
+        });
+
+        BattleBreakdown {
+            player,
+            base_strengths,
+            strength_modifiers,
+            strengths,
+            base_reward: self.state.rules.reward(self.battlefield()),
+            reward_modifiers,
+            result,
+            score_delta: self.battle_score_delta(result, player),
+        }
+    }
+
     pub fn advance_known_state(&self) -> (BattleResult, TurnResult<KnownState>) {
+        self.advance_known_state_with(&mut ())
+    }
+
+    fn advance_known_state_with(
+        &self,
+        log: &mut impl EventLog,
+    ) -> (BattleResult, TurnResult<KnownState>) {
         let player = Player::Me;
-        let battle_result = self.battle_result(player);
+        let battle_result = self.battle_result_with(player, log);
 
         debug_assert_eq!(battle_result, !self.battle_result(!player));
 
-        let score_delta = self.battle_score_delta(battle_result, player);
+        let score_delta = self.battle_score_delta_with(battle_result, player, log);
         let score = self.state.score + score_delta;
 
+        log.log(|| BattleEvent::Resolved {
+            result: battle_result,
+            score_delta,
+        });
+
         debug_assert_eq!(
             score_delta,
             -self.battle_score_delta(!battle_result, !player)
         );
 
-        let turn_result = match self.state.battlefields.next() {
+        let next_battlefields = if self.state.is_last_turn() {
+            None
+        } else {
+            self.state.battlefields.next()
+        };
+
+        let turn_result = match next_battlefields {
             // Continue game
             Some(battlefields) => {
                 let mut new_state = KnownState {
@@ -398,9 +845,9 @@ impl BattleContext {
                 new_state.graveyard.insert(self.creature(player));
                 new_state.graveyard.insert(self.creature(!player));
 
-                // Clear status effects
-                p1.effects.clear();
-                p2.effects.clear();
+                // Get rid of the status effects which ran out
+                p1.expire_effects();
+                p2.expire_effects();
 
                 // Resolve the Steward effect
                 // [[[STEWARD EFFECT 2]]]
@@ -413,8 +860,8 @@ impl BattleContext {
                 // Set up global lingering effects
                 if self.battlefield() == Battlefield::Night {
                     // [[[NIGHT SETUP]]]
-                    p1.effects.insert(StatusEffect::Night);
-                    p2.effects.insert(StatusEffect::Night);
+                    p1.apply_effect(StatusEffect::Night);
+                    p2.apply_effect(StatusEffect::Night);
                 }
 
                 // first is winner, second is loser
@@ -428,11 +875,11 @@ impl BattleContext {
                     match self.battlefield() {
                         // [[[GLADE SETUP]]]
                         Battlefield::Glade => {
-                            winner.effects.insert(StatusEffect::Glade);
+                            winner.apply_effect(StatusEffect::Glade);
                         }
                         // [[[MOUNTAIN SETUP]]]
                         Battlefield::Mountain => {
-                            winner.effects.insert(StatusEffect::Mountain);
+                            winner.apply_effect(StatusEffect::Mountain);
                         }
                         _ => {}
                     }
@@ -441,12 +888,12 @@ impl BattleContext {
                     // in adding the status effect anymore
                     // [[[BARBARIAN SETUP]]]
                     if !new_state.graveyard.has(Creature::Barbarian) {
-                        loser.effects.insert(StatusEffect::Barbarian)
+                        loser.apply_effect(StatusEffect::Barbarian)
                     }
                 }
 
                 for player in Player::PLAYERS {
-                    let effects = &mut player.select_mut(&mut new_state.player_states).effects;
+                    let player_state = player.select_mut(&mut new_state.player_states);
 
                     if self.creature_is_negated(player) {
                         continue;
@@ -454,11 +901,11 @@ impl BattleContext {
 
                     match self.creature(player) {
                         // [[[MERCENARY SETUP]]]
-                        Creature::Mercenary => effects.insert(StatusEffect::Mercenary),
+                        Creature::Mercenary => player_state.apply_effect(StatusEffect::Mercenary),
                         // [[[SEER SETUP]]]
-                        Creature::Seer => effects.insert(StatusEffect::Seer),
+                        Creature::Seer => player_state.apply_effect(StatusEffect::Seer),
                         // [[[BARD SETUP]]]
-                        Creature::Bard => effects.insert(StatusEffect::Bard),
+                        Creature::Bard => player_state.apply_effect(StatusEffect::Bard),
                         _ => {}
                     }
                 }
@@ -523,16 +970,19 @@ impl BattleContext {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::game::{battlefield::Battlefields, creature::CreatureSet, types::Score};
+    use crate::game::{
+        battlefield::Battlefields, creature::CreatureSet, rules::Ruleset, types::Score,
+    };
     use once_cell::sync::Lazy;
     use std::assert_eq;
 
     // {{{ Common setup
     static BASIC_STATE: Lazy<KnownState> = Lazy::new(|| KnownState {
-        battlefields: Battlefields::new([Battlefield::Plains; 4]),
+        battlefields: Battlefields::new([Battlefield::Plains; Battlefields::COUNT]),
         graveyard: CreatureSet::default(),
         score: Score::default(),
         player_states: Default::default(),
+        rules: Ruleset::default(),
     });
 
     static BASIC_BATTLE_CONTEXT: Lazy<BattleContext> = Lazy::new(|| {
@@ -900,5 +1350,193 @@ mod tests {
         );
     }
     // }}}
+    // }}}
+    // {{{ Rule variants
+    #[test]
+    fn gambit_ties_rule() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.set_edict(Player::You, Edict::RileThePublic);
+
+        assert_eq!(ctx.resolve_gambits(Player::Me), BattleResult::Lost);
+
+        ctx.state.rules.gambit_loses_ties = false;
+        assert_eq!(ctx.resolve_gambits(Player::Me), BattleResult::Tied);
+    }
+
+    #[test]
+    fn custom_rewards() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.set_creature(Player::You, Creature::Monarch);
+        ctx.state.rules.battlefield_reward = 4;
+        ctx.state.rules.monarch_bonus = 1;
+
+        assert_eq!(ctx.battle_reward(Player::Me), 4);
+        assert_eq!(ctx.monarch_reward(Player::Me, BattleResult::Won), 1);
+    }
+
+    #[test]
+    fn shorter_games_end_early() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.state.rules.turns = 1;
+
+        assert!(matches!(
+            ctx.advance_known_state().1,
+            TurnResult::Finished(_)
+        ));
+    }
+
+    #[test]
+    fn battles_won_scoring() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.state.rules.scoring = ScoringMode::BattlesWon;
+        ctx.set_creature(Player::You, Creature::Monarch);
+        ctx.set_battlefield(Battlefield::LastStrand);
+
+        for result in [BattleResult::Won, BattleResult::Tied, BattleResult::Lost] {
+            let expected = match result {
+                BattleResult::Won => 1,
+                BattleResult::Tied => 0,
+                BattleResult::Lost => -1,
+            };
+
+            assert_eq!(ctx.battle_score_delta(result, Player::Me), expected);
+        }
+
+        // A single battle left cannot make up for a two battle deficit
+        ctx.state.score = Score(2);
+        ctx.state.battlefields.current = Battlefields::COUNT - 1;
+        assert!(ctx.state.guaranteed_win(Player::Me));
+        assert!(!ctx.state.guaranteed_win(Player::You));
+
+        ctx.state.score = Score(1);
+        assert!(!ctx.state.guaranteed_win(Player::Me));
+    }
+    // }}}
+    // {{{ Durations
+    #[test]
+    fn lingering_effects_outlast_battles() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.add_effect(Player::Me, StatusEffect::Mountain);
+        ctx.add_effect(Player::You, StatusEffect::Night);
+        ctx.state.player_states[0].lingering[StatusEffect::Mountain as usize] = 1;
+
+        let [mine, yours] = ctx
+            .advance_known_state()
+            .1
+            .get_unfinished()
+            .unwrap()
+            .player_states;
+
+        assert!(mine.effects.has(StatusEffect::Mountain));
+        assert_eq!(mine.lingering[StatusEffect::Mountain as usize], 0);
+        assert!(!yours.effects.has(StatusEffect::Night));
+
+        // Effects applied at the end of the battle only last for their duration
+        assert!(mine.effects.has(StatusEffect::Mercenary));
+        assert_eq!(
+            mine.lingering[StatusEffect::Mercenary as usize],
+            StatusEffect::Mercenary.duration() - 1
+        );
+    }
+    // }}}
+    // {{{ Events
+    #[test]
+    fn events_explain_results() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.set_battlefield(Battlefield::Urban);
+        ctx.add_effect(Player::Me, StatusEffect::Bard);
+
+        for mine in Creature::CREATURES {
+            for yours in Creature::CREATURES {
+                if mine == yours {
+                    continue;
+                }
+
+                ctx.set_creature(Player::Me, mine);
+                ctx.set_creature(Player::You, yours);
+
+                let (result, turn_result, events) = ctx.advance_known_state_with_events();
+                assert_eq!((result, turn_result), ctx.advance_known_state());
+
+                let mut strengths = [mine.strength() as i8, yours.strength() as i8];
+                for event in &events {
+                    if let BattleEvent::StrengthModified {
+                        player, modifier, ..
+                    } = event
+                    {
+                        *player.select_mut(&mut strengths) += modifier;
+                    }
+
+                    if let BattleEvent::StrengthsCompared {
+                        strengths: compared,
+                    } = event
+                    {
+                        assert_eq!(*compared, strengths);
+                    }
+                }
+
+                assert!(matches!(
+                    events.last(),
+                    Some(BattleEvent::Resolved { result: resolved, .. }) if *resolved == result
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn breakdowns_agree_with_resolution() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.set_battlefield(Battlefield::Mountain);
+        ctx.add_effect(Player::Me, StatusEffect::Mountain);
+        ctx.add_effect(Player::You, StatusEffect::Night);
+
+        for player in Player::PLAYERS {
+            for mine in Creature::CREATURES {
+                ctx.set_creature(Player::Me, mine);
+
+                let breakdown = ctx.explain(player);
+                assert_eq!(breakdown.result, ctx.battle_result(player));
+                assert_eq!(
+                    breakdown.strengths[0] - breakdown.base_strengths[0],
+                    ctx.strength_modifier(player)
+                );
+
+                // Only the first player is affected by the mountain effect
+                let mine = &breakdown.strength_modifiers[(player == Player::You) as usize];
+                assert!(mine
+                    .iter()
+                    .any(|modifier| modifier.source == "MOUNTAIN EFFECT 1"));
+            }
+        }
+    }
+
+    #[test]
+    fn wall_events() {
+        let mut ctx = *BASIC_BATTLE_CONTEXT;
+        ctx.set_creature(Player::Me, Creature::Wall);
+        ctx.set_edict(Player::Me, Edict::DivertAttention);
+        ctx.set_edict(Player::You, Edict::RileThePublic);
+
+        let events = ctx.advance_known_state_with_events().2;
+        assert!(events.contains(&BattleEvent::EffectTriggered {
+            player: Player::Me,
+            cause: Cause::Creature(Creature::Wall),
+            outcome: BattleResult::Tied,
+        }));
+
+        ctx.set_creature(Player::You, Creature::Witch);
+
+        let events = ctx.advance_known_state_with_events().2;
+        assert!(events.contains(&BattleEvent::EffectNegated {
+            player: Player::Me,
+            by: Creature::Witch,
+        }));
+        assert!(events.contains(&BattleEvent::EffectTriggered {
+            player: Player::You,
+            cause: Cause::Creature(Creature::Witch),
+            outcome: BattleResult::Won,
+        }));
+    }
+    // }}}
 }
 // }}
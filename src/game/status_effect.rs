@@ -1,5 +1,6 @@
-use crate::{helpers::bitfield::{Bitfield16, Bitfield}, make_bitfield};
+use crate::{error::EchoError, helpers::bitfield::{Bitfield16, Bitfield}, make_bitfield};
 use std::fmt::{self, Display};
+use std::str::FromStr;
 
 /// Different kind of lingering effects affecting a given player
 #[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
@@ -26,9 +27,12 @@ pub enum StatusEffect {
 }
 
 impl StatusEffect {
-    pub const STATUS_EFFECTS: [StatusEffect; 6] = [
+    // Must list the effects in declaration order, as
+    // bitfields use this to map indices back to effects.
+    pub const STATUS_EFFECTS: [StatusEffect; 7] = [
         StatusEffect::Mountain,
         StatusEffect::Glade,
+        StatusEffect::Night,
         StatusEffect::Seer,
         StatusEffect::Bard,
         StatusEffect::Mercenary,
@@ -36,12 +40,58 @@ impl StatusEffect {
     ];
 }
 
+/// What happens when an effect gets applied to a player already under it.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum Stacking {
+    /// The effect lasts for its full duration again.
+    Refresh,
+    /// The duration of the new application gets added to the battles left.
+    Extend,
+}
+
+impl StatusEffect {
+    /// The number of battles an effect stays active for once applied.
+    /// Every effect in the base game only lasts for the next battle.
+    #[inline(always)]
+    pub const fn duration(self) -> u8 {
+        match self {
+            StatusEffect::Mountain => 1,
+            StatusEffect::Glade => 1,
+            StatusEffect::Night => 1,
+            StatusEffect::Seer => 1,
+            StatusEffect::Bard => 1,
+            StatusEffect::Mercenary => 1,
+            StatusEffect::Barbarian => 1,
+        }
+    }
+
+    /// How re-applying an already active effect behaves.
+    #[inline(always)]
+    pub const fn stacking(self) -> Stacking {
+        Stacking::Refresh
+    }
+}
+
 impl Display for StatusEffect {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
+impl FromStr for StatusEffect {
+    type Err = EchoError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        StatusEffect::STATUS_EFFECTS
+            .into_iter()
+            .find(|effect| effect.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| EchoError::Unknown {
+                what: "status effect",
+                input: s.to_string(),
+            })
+    }
+}
+
 impl From<usize> for StatusEffect {
    fn from(value: usize) -> Self {
        StatusEffect::STATUS_EFFECTS[value]
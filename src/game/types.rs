@@ -7,6 +7,7 @@ use std::ops::Sub;
 
 // {{{ Players
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Player {
     Me,  // Current player
     You, // Opponent
@@ -108,7 +109,8 @@ mod player_tests {
 // - Negative => player 2 won
 // - Positive => player 1 won
 // - 0 => draw
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Default)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Score(pub i8);
 
 impl Score {
@@ -122,7 +124,9 @@ impl Score {
         }
     }
 
-    /// Returns the result of a game ending with this score.
+    /// Returns the result of a game ending with this score. Depending on the
+    /// scoring mode, scores hold either a point or a battle differential,
+    /// so in both cases the sign decides the winner.
     #[inline(always)]
     pub fn to_battle_result(self) -> BattleResult {
         if self.0 > 0 {
@@ -142,6 +146,14 @@ impl Score {
             Player::You => -self,
         }
     }
+
+    /// The final score once the given player forfeits the game. The player loses
+    /// by at least a point, keeping the current score if they were losing already.
+    #[inline(always)]
+    pub fn forfeited_by(self, player: Player) -> Score {
+        let score = self.from_perspective(player);
+        Score(score.0.min(-1)).from_perspective(player)
+    }
 }
 
 impl Add<i8> for Score {
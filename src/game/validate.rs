@@ -0,0 +1,251 @@
+//! Sanity checks for game states. Every state reachable by actually playing
+//! the game passes these, but states put together by hand (in tests,
+//! or from the command line) might not.
+use super::creature::{Creature, CreatureSet};
+use super::edict::Edict;
+use super::known_state::KnownState;
+use super::known_state_summary::KnownStateEssentials;
+use super::status_effect::StatusEffect;
+use super::types::Player;
+use crate::cfr::hidden_index::HiddenState;
+use crate::error::{EchoError, EchoResult};
+use crate::helpers::bitfield::Bitfield;
+
+impl KnownState {
+    /// Makes sure the state could have been reached by playing by its rules.
+    pub fn validate(&self) -> EchoResult<()> {
+        self.rules.validate()?;
+
+        let turn = self.battlefields.current;
+        if turn >= self.rules.turns as usize {
+            return Err(EchoError::InvalidPosition(format!(
+                "Turn {} is out of bounds for a game lasting {} turns",
+                turn + 1,
+                self.rules.turns
+            )));
+        }
+
+        // Both players discard a creature every turn
+        if self.graveyard.len() != 2 * turn {
+            return Err(EchoError::InvalidPosition(format!(
+                "Expected {} creatures in the graveyard on turn {}, found {}",
+                2 * turn,
+                turn + 1,
+                self.graveyard.len()
+            )));
+        }
+
+        for player in Player::PLAYERS {
+            let edicts = self.player_edicts(player).len();
+
+            // The steward can return every edict to its owner's hand,
+            // in which case we only know an upper bound on the edicts used.
+            // [[[STEWARD EFFECT 2]]]
+            let used = Edict::EDICTS.len() - edicts;
+            let valid = if self.graveyard.has(Creature::Steward) {
+                used <= turn
+            } else {
+                used == turn
+            };
+
+            if !valid {
+                return Err(EchoError::InvalidPosition(format!(
+                    "{player:?} cannot hold {edicts} edicts on turn {}",
+                    turn + 1
+                )));
+            }
+        }
+
+        // Lingering effects are only set up at the end of a turn
+        if turn == 0 && self.player_states.iter().any(|s| s.effects.len() > 0) {
+            return Err(EchoError::InvalidPosition(
+                "No status effects can be active on the first turn".to_string(),
+            ));
+        }
+
+        // Only one copy of the seer exists,
+        // and it must have been played to provide its effect.
+        // [[[SEER SETUP]]]
+        let seers = Player::PLAYERS
+            .into_iter()
+            .filter(|player| {
+                player
+                    .select(self.player_states)
+                    .effects
+                    .has(StatusEffect::Seer)
+            })
+            .count();
+
+        if seers > 1 {
+            return Err(EchoError::InvalidPosition(
+                "The seer effect cannot be active on both players".to_string(),
+            ));
+        } else if seers == 1 && !self.graveyard.has(Creature::Seer) {
+            return Err(EchoError::InvalidPosition(
+                "The seer effect cannot be active before the seer is played".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl HiddenState {
+    /// Makes sure the hidden information held by some player
+    /// is consistent with a given (valid) known state.
+    pub fn validate_against(&self, state: &KnownState) -> EchoResult<()> {
+        if self.hand & state.graveyard != CreatureSet::empty() {
+            return Err(EchoError::InvalidPosition(
+                "The hand cannot contain creatures from the graveyard".to_string(),
+            ));
+        }
+
+        if self.hand.len() != state.hand_size() {
+            return Err(EchoError::InvalidPosition(format!(
+                "Expected a hand of {} creatures, found {}",
+                state.hand_size(),
+                self.hand.len()
+            )));
+        }
+
+        if let Some(choice) = self.choice {
+            if !choice.is_subset_of(self.hand) {
+                return Err(EchoError::InvalidPosition(
+                    "The chosen creatures must come from the hand".to_string(),
+                ));
+            }
+
+            let max_choice = if state.seer_is_active() { 2 } else { 1 };
+            if !(1..=max_choice).contains(&choice.len()) {
+                return Err(EchoError::InvalidPosition(format!(
+                    "Cannot choose {} creatures when the seer effect is {}",
+                    choice.len(),
+                    if state.seer_is_active() {
+                        "active"
+                    } else {
+                        "inactive"
+                    }
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::choice::FinalMainPhaseChoice;
+    use crate::game::simulate::BattleContext;
+    use crate::game::types::TurnResult;
+
+    const BATTLEFIELDS: [Battlefield; Battlefields::COUNT] = Battlefields::last_of([
+        Battlefield::Plains,
+        Battlefield::Night,
+        Battlefield::Mountain,
+        Battlefield::LastStrand,
+    ]);
+
+    #[test]
+    fn played_states_are_valid() {
+        use Creature::*;
+        use Edict::*;
+
+        let mut state = KnownState::new_starting(BATTLEFIELDS);
+        let turns = [
+            (Seer, Wall, Gambit, Sabotage),
+            (Steward, Bard, Ambush, Gambit),
+            (Rogue, Mercenary, Gambit, Ambush),
+        ];
+
+        for (mine, yours, my_edict, your_edict) in turns {
+            assert_eq!(state.validate(), Ok(()));
+
+            let context = BattleContext::new(
+                [
+                    FinalMainPhaseChoice::new(mine, my_edict),
+                    FinalMainPhaseChoice::new(yours, your_edict),
+                ],
+                [None; 2],
+                state,
+                false,
+            );
+
+            match context.advance_known_state().1 {
+                TurnResult::Unfinished(next) => state = next,
+                TurnResult::Finished(_) => panic!("The game ended too early"),
+            }
+        }
+
+        assert_eq!(state.validate(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_states_are_rejected() {
+        let state = KnownState::new_starting(BATTLEFIELDS);
+
+        let mut bad_graveyard = state;
+        bad_graveyard.graveyard.insert(Creature::Wall);
+        assert!(bad_graveyard.validate().is_err());
+
+        let mut bad_edicts = state;
+        bad_edicts.player_states[1].edicts.remove(Edict::Gambit);
+        assert!(bad_edicts.validate().is_err());
+
+        let mut bad_seer = state;
+        bad_seer.player_states[0].effects.insert(StatusEffect::Seer);
+        assert!(bad_seer.validate().is_err());
+
+        let mut hand = CreatureSet::empty();
+        for creature in Creature::CREATURES.into_iter().take(state.hand_size()) {
+            hand.insert(creature);
+        }
+
+        assert_eq!(
+            HiddenState::new(hand, None).validate_against(&state),
+            Ok(())
+        );
+
+        let too_many = hand | CreatureSet::singleton(Creature::CREATURES[10]);
+        assert!(HiddenState::new(too_many, None)
+            .validate_against(&state)
+            .is_err());
+
+        let mut graveyard_state = state;
+        graveyard_state.graveyard = CreatureSet::singleton(hand.into_iter().next().unwrap());
+        assert!(HiddenState::new(hand, None)
+            .validate_against(&graveyard_state)
+            .is_err());
+
+        assert!(HiddenState::new(hand, Some(hand))
+            .validate_against(&state)
+            .is_err());
+    }
+
+    #[test]
+    fn dealt_hands_are_validated() {
+        let rules = Default::default();
+        let mut hands = [CreatureSet::empty(); 2];
+
+        for (index, creature) in Creature::CREATURES
+            .into_iter()
+            .take(2 * (Battlefields::COUNT + 1))
+            .enumerate()
+        {
+            hands[index % 2].insert(creature);
+        }
+
+        let (state, [deal, mirrored]) =
+            KnownState::new_mirrored(BATTLEFIELDS, rules, hands).unwrap();
+        assert_eq!(state, KnownState::new_starting(BATTLEFIELDS));
+        assert_eq!(deal.map(|info| info.get_main()), hands);
+        assert_eq!(mirrored.map(|info| info.get_main()), [hands[1], hands[0]]);
+
+        assert!(KnownState::new_dealt(BATTLEFIELDS, rules, [hands[0]; 2]).is_err());
+        assert!(
+            KnownState::new_dealt(BATTLEFIELDS, rules, [hands[0], CreatureSet::empty()]).is_err()
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! `Arbitrary` instances for the game types, such that codecs and the
+//! simulation can be tested on random inputs instead of nested loops.
+//!
+//! States generated here are always reachable by actually playing the game
+//! (see `KnownState::validate`), unless stated otherwise.
+use super::bitfield::Bitfield;
+use super::pair::Pair;
+use crate::cfr::phase::{MainPhase, PerPhase, PhaseTag, SabotagePhase, SeerPhase};
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::choice::FinalMainPhaseChoice;
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::{Edict, EdictSet};
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use crate::game::simulate::BattleContext;
+use crate::game::status_effect::{StatusEffect, StatusEffectSet};
+use crate::game::types::{Player, Score};
+use proptest::prelude::*;
+use proptest::sample::{select, subsequence};
+
+// {{{ Helpers
+/// Collects a list of elements into a bitfield.
+fn to_set<B: Bitfield>(elements: Vec<B::Element>) -> B {
+    let mut result = B::empty();
+
+    for element in elements {
+        result.insert(element);
+    }
+
+    result
+}
+
+/// Generates a subset of a given size.
+fn subset_of_size<B>(set: B, size: usize) -> impl Strategy<Value = B>
+where
+    B: Bitfield + std::fmt::Debug + 'static,
+    B::Element: std::fmt::Debug,
+{
+    subsequence(set.into_iter().collect::<Vec<_>>(), size).prop_map(to_set)
+}
+// }}}
+// {{{ Simple types
+macro_rules! arbitrary_enum {
+    ($type: ty, $values: expr) => {
+        impl Arbitrary for $type {
+            type Parameters = ();
+            type Strategy = BoxedStrategy<Self>;
+
+            fn arbitrary_with(_: ()) -> Self::Strategy {
+                select($values.to_vec()).boxed()
+            }
+        }
+    };
+}
+
+arbitrary_enum!(Creature, Creature::CREATURES);
+arbitrary_enum!(Edict, Edict::EDICTS);
+arbitrary_enum!(Battlefield, Battlefield::BATTLEFIELDS);
+arbitrary_enum!(StatusEffect, StatusEffect::STATUS_EFFECTS);
+arbitrary_enum!(Player, Player::PLAYERS);
+arbitrary_enum!(
+    PhaseTag,
+    [PhaseTag::Main, PhaseTag::Sabotage, PhaseTag::Seer]
+);
+
+macro_rules! arbitrary_bitfield {
+    ($type: ty) => {
+        impl Arbitrary for $type {
+            type Parameters = ();
+            type Strategy = BoxedStrategy<Self>;
+
+            fn arbitrary_with(_: ()) -> Self::Strategy {
+                (0..=<$type>::MAX).prop_map(<$type>::new).boxed()
+            }
+        }
+    };
+}
+
+arbitrary_bitfield!(CreatureSet);
+arbitrary_bitfield!(EdictSet);
+arbitrary_bitfield!(StatusEffectSet);
+// }}}
+// {{{ States
+impl Arbitrary for KnownStateSummary {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<KnownState>()
+            .prop_map(|state| state.to_summary())
+            .boxed()
+    }
+}
+
+impl Arbitrary for KnownState {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (0..Battlefields::COUNT)
+            .prop_flat_map(|turn| {
+                (
+                    Just(turn),
+                    any::<[Battlefield; Battlefields::COUNT]>(),
+                    subset_of_size(CreatureSet::all(), 2 * turn),
+                    -20..=20i8,
+                )
+            })
+            .prop_flat_map(|(turn, battlefields, graveyard, score)| {
+                // The steward can return used edicts to the hand
+                let max_edicts = if graveyard.has(Creature::Steward) {
+                    Edict::EDICTS.len()
+                } else {
+                    Edict::EDICTS.len() - turn
+                };
+
+                let edicts = (Edict::EDICTS.len() - turn..=max_edicts)
+                    .prop_flat_map(|size| subset_of_size(EdictSet::all(), size));
+
+                // Lingering effects only get set up after the first turn,
+                // and the seer effect requires the seer to have been played.
+                let effects = any::<StatusEffectSet>().prop_map(move |effects| {
+                    if turn == 0 {
+                        StatusEffectSet::empty()
+                    } else {
+                        effects - StatusEffectSet::singleton(StatusEffect::Seer)
+                    }
+                });
+
+                let seer_player = if graveyard.has(Creature::Seer) {
+                    any::<Option<Player>>().boxed()
+                } else {
+                    Just(None).boxed()
+                };
+
+                (
+                    Just((turn, battlefields, graveyard, score)),
+                    [edicts.clone(), edicts],
+                    [effects.clone(), effects],
+                    seer_player,
+                )
+            })
+            .prop_map(
+                |((turn, battlefields, graveyard, score), edicts, effects, seer_player)| {
+                    let mut state = KnownState::new_starting(battlefields);
+                    state.battlefields.current = turn;
+                    state.graveyard = graveyard;
+                    state.score = Score(score);
+
+                    for player in Player::PLAYERS {
+                        let player_state = player.select_mut(&mut state.player_states);
+                        player_state.edicts = player.select(edicts);
+                        player_state.effects = player.select(effects);
+
+                        if seer_player == Some(player) {
+                            player_state.effects.insert(StatusEffect::Seer);
+                        }
+                    }
+
+                    state
+                },
+            )
+            .boxed()
+    }
+}
+
+/// Generates the hands both players could be holding
+/// at the start of the current turn of some state.
+pub fn hands<S: KnownStateEssentials>(state: &S) -> impl Strategy<Value = Pair<CreatureSet>> {
+    let possibilities = !state.graveyard();
+    let hand_size = state.hand_size();
+
+    subset_of_size(possibilities, hand_size).prop_flat_map(move |mine| {
+        (Just(mine), subset_of_size(possibilities - mine, hand_size))
+            .prop_map(|(mine, yours)| [mine, yours])
+    })
+}
+// }}}
+// {{{ Phases
+impl Arbitrary for PerPhase<MainPhase, SabotagePhase, SeerPhase> {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// The generated phases are not necessarily consistent with any given state.
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        prop_oneof![
+            Just(PerPhase::Main(MainPhase::new())),
+            any::<Pair<Edict>>().prop_map(|edicts| PerPhase::Sabotage(SabotagePhase::new(edicts))),
+            any::<(Pair<Edict>, Pair<Option<Creature>>, Creature)>().prop_map(
+                |(edicts, guesses, revealed)| {
+                    PerPhase::Seer(SeerPhase::new(edicts, guesses, revealed))
+                }
+            ),
+        ]
+        .boxed()
+    }
+}
+// }}}
+// {{{ Battles
+impl Arbitrary for BattleContext {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        any::<KnownState>()
+            .prop_flat_map(|state| {
+                let creatures = subset_of_size(!state.graveyard, 2)
+                    .prop_map(|creatures| creatures.into_iter().collect::<Vec<_>>())
+                    .prop_shuffle();
+
+                let edicts = Player::PLAYERS.map(|player| {
+                    select(state.player_edicts(player).into_iter().collect::<Vec<_>>())
+                });
+
+                (
+                    Just(state),
+                    creatures,
+                    edicts,
+                    any::<Pair<Option<Creature>>>(),
+                )
+            })
+            .prop_map(|(state, creatures, edicts, guesses)| {
+                let main_choices = Player::PLAYERS.map(|player| {
+                    FinalMainPhaseChoice::new(creatures[player as usize], player.select(edicts))
+                });
+
+                // Only players who played the sabotage edict get to guess
+                let sabotage_choices = Player::PLAYERS.map(|player| {
+                    player
+                        .select(guesses)
+                        .filter(|_| player.select(edicts) == Edict::Sabotage)
+                });
+
+                BattleContext::new(main_choices, sabotage_choices, state, false)
+            })
+            .boxed()
+    }
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::hidden_index::{HiddenIndex, HiddenState, PerPhaseInfo};
+    use crate::game::types::TurnResult;
+
+    proptest! {
+        #[test]
+        fn generated_states_are_valid(state in any::<KnownState>()) {
+            prop_assert_eq!(state.validate(), Ok(()));
+        }
+
+        #[test]
+        fn battles_preserve_validity(context in any::<BattleContext>()) {
+            if let TurnResult::Unfinished(next) = context.advance_known_state().1 {
+                prop_assert_eq!(next.validate(), Ok(()));
+            }
+        }
+
+        #[test]
+        fn battles_are_symmetric(context in any::<BattleContext>()) {
+            let [mine, yours] = Player::PLAYERS.map(|player| context.explain(player));
+
+            prop_assert_eq!(mine.result, !yours.result);
+            prop_assert_eq!(mine.score_delta, -yours.score_delta);
+        }
+
+        #[test]
+        fn main_phase_hidden_indices_roundtrip(
+            (state, hands) in any::<KnownStateSummary>()
+                .prop_flat_map(|state| (Just(state), hands(&state)))
+        ) {
+            for player in Player::PLAYERS {
+                let info = PerPhaseInfo::Main(player.select(hands));
+                let encoded = HiddenIndex::encode(&state, player, info).unwrap();
+
+                prop_assert_eq!(
+                    encoded.decode(&state, player, PerPhaseInfo::Main(())),
+                    Ok(HiddenState::from_encoding_info(info))
+                );
+            }
+        }
+    }
+}
+// }}}
@@ -1,17 +1,19 @@
-use std::{fmt::{Binary, Debug}, convert::{TryFrom, TryInto}, ops::BitAnd};
+use std::{fmt::{Binary, Debug}, convert::{TryFrom, TryInto}, iter::FusedIterator, ops::BitAnd};
 
 use super::{choose::choose, bitops::snoob};
+use crate::error::EchoResult;
 
 // {{{ Trait definition
 /// A (non exhuasive) list of laws:
 /// - `Self::Representation::try_from` shouldn't be able to fail
 ///   for values in the range `0..Self::MAX.into()`
+/// - bitfields can hold at most 64 bits
 pub trait Bitfield: Sized + Copy + Binary + Into<Self::Representation> 
   + IntoIterator<Item = Self::Element> + BitAnd<Output = Self> + Eq 
 {
     type Element: TryFrom<usize> + Copy;
     type IndexBitfield: Bitfield<Element = usize>;
-    type Representation: Into<usize> + TryFrom<usize>;
+    type Representation: Into<u64> + TryFrom<usize>;
 
     /// The maximum valid value this bitfield can take.
     const MAX: Self::Representation;
@@ -93,6 +95,12 @@ pub trait Bitfield: Sized + Copy + Binary + Into<Self::Representation>
     /// ```
     fn remove(&mut self, index: Self::Element);
 
+    /// Similar to `insert`, but returns an error instead of panicking.
+    fn try_insert(&mut self, index: Self::Element) -> EchoResult<()>;
+
+    /// Similar to `remove`, but returns an error instead of panicking.
+    fn try_remove(&mut self, index: Self::Element) -> EchoResult<()>;
+
     /// Moves an element from `self` to some other bitfield.
     fn move_one(&mut self, to: &mut Self, bit: Self::Element) {
         self.remove(bit);
@@ -268,6 +276,8 @@ macro_rules! make_bitfield {
         $default_is_empty: literal
     ) => {
         #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[cfg_attr(feature = "serde", serde(transparent))]
         pub struct $name(pub $repr);
 
         impl Into<$repr> for $name {
@@ -349,6 +359,24 @@ macro_rules! make_bitfield {
                 self.0 ^= 1 << (index as $repr)
             }
 
+            fn try_insert(&mut self, index: $element) -> crate::error::EchoResult<()> {
+                if self.has(index) {
+                    return Err(crate::error::EchoError::BitAlreadyPresent(index as usize));
+                }
+
+                self.0 |= 1 << (index as $repr);
+                Ok(())
+            }
+
+            fn try_remove(&mut self, index: $element) -> crate::error::EchoResult<()> {
+                if !self.has(index) {
+                    return Err(crate::error::EchoError::BitNotPresent(index as usize));
+                }
+
+                self.0 ^= 1 << (index as $repr);
+                Ok(())
+            }
+
             #[inline(always)]
             fn len(self) -> usize {
                 self.0.count_ones() as usize
@@ -486,13 +514,19 @@ macro_rules! make_bitfield {
 pub struct BitfieldIterator<B> {
     index: usize,
     index_end: usize,
+    remaining: usize,
     bitfield: B,
 }
 
 impl<B: Bitfield> BitfieldIterator<B> {
     #[inline(always)]
     pub fn new(bitfield: B) -> Self {
-        Self { index: 0, index_end: B::BITS - 1, bitfield }
+        Self {
+            index: 0,
+            index_end: B::BITS - 1,
+            remaining: bitfield.len(),
+            bitfield,
+        }
     }
 }
 
@@ -500,35 +534,51 @@ impl<B: Bitfield> Iterator for BitfieldIterator<B> {
     type Item = B::Element;
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.index <= self.index_end {
-            if self.bitfield.has_raw(self.index) {
-                let result = B::Element::try_from(self.index).ok();
-                self.index += 1;
-                return result;
-            } else {
-                self.index += 1;
+        // Keeping track of the number of ones left guarantees
+        // there's always at least one more to find inside the loop.
+        if self.remaining == 0 {
+            return None;
+        }
+
+        loop {
+            let index = self.index;
+            self.index += 1;
+
+            if self.bitfield.has_raw(index) {
+                self.remaining -= 1;
+                return B::Element::try_from(index).ok();
             }
         }
+    }
 
-        None
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
     }
 }
 
 impl<B: Bitfield> DoubleEndedIterator for BitfieldIterator<B> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        while self.index <= self.index_end {
-            if self.bitfield.has_raw(self.index_end) {
-                let result = B::Element::try_from(self.index_end).ok();
-                self.index_end -= 1;
-                return result;
-            } else {
-                self.index_end -= 1;
-            }
+        if self.remaining == 0 {
+            return None;
         }
 
-        None
+        loop {
+            let index = self.index_end;
+            // The index might underflow after yielding the bit at index 0,
+            // but `remaining` will be 0 by then, so the value is never used.
+            self.index_end = self.index_end.wrapping_sub(1);
+
+            if self.bitfield.has_raw(index) {
+                self.remaining -= 1;
+                return B::Element::try_from(index).ok();
+            }
+        }
     }
 }
+
+impl<B: Bitfield> ExactSizeIterator for BitfieldIterator<B> {}
+impl<B: Bitfield> FusedIterator for BitfieldIterator<B> {}
 // }}}
 // {{{ Fixed size subset iterator
 #[derive(Debug, Clone, Copy)]
@@ -543,7 +593,8 @@ impl<B: Bitfield> BitfieldFixedSizeSubsetIterator<B> {
     pub fn new(possibilities: B, ones: usize) -> Self {
         Self {
             remaining: choose(possibilities.len(), ones),
-            current: (1 << ones) - 1,
+            // Written this way to support subsets containing all 64 bits
+            current: 1usize.checked_shl(ones as u32).unwrap_or(0).wrapping_sub(1),
             possibilities,
         }
     }
@@ -557,19 +608,34 @@ impl<B: Bitfield> Iterator for BitfieldFixedSizeSubsetIterator<B> {
             let result = B::IndexBitfield::new_unchecked(self.current);
 
             self.remaining -= 1;
-            self.current = snoob(self.current);
+
+            // The subset following the last one might not fit in an `usize`
+            if 0 < self.remaining {
+                self.current = snoob(self.current);
+            }
 
             Bitfield::decode_relative_to(result, self.possibilities)
         } else {
             None
         }
     }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
 }
+
+impl<B: Bitfield> ExactSizeIterator for BitfieldFixedSizeSubsetIterator<B> {}
+impl<B: Bitfield> FusedIterator for BitfieldFixedSizeSubsetIterator<B> {}
 // }}}
 // {{{ Subset iterator
+/// Iterates over every subset of a bitfield. The subsets get counted using an
+/// `usize`, so the bitfield must have less than `usize::BITS` members.
 #[derive(Debug, Clone, Copy)]
 pub struct BitfieldSubsetIterator<B> {
     index: usize,
+    /// Exclusive upper bound for the indices left to yield.
     index_end: usize,
     possibilities: B,
 }
@@ -577,11 +643,15 @@ pub struct BitfieldSubsetIterator<B> {
 impl<B: Bitfield> BitfieldSubsetIterator<B> {
     #[inline(always)]
     pub fn new(possibilities: B) -> Self {
+        let len = possibilities.len() as u32;
+        assert!(
+            len < usize::BITS,
+            "Cannot count the 2^{len} subsets of a bitfield"
+        );
+
         Self {
             index: 0,
-            // NOTE: this could fail a bit if the representation is `usize`,
-            // but we never use bitfields that large in practice.
-            index_end: 2usize.pow(possibilities.len() as u32) - 1,
+            index_end: 1 << len,
             possibilities,
         }
     }
@@ -591,7 +661,7 @@ impl<B: Bitfield> Iterator for BitfieldSubsetIterator<B> {
     type Item = B;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index <= self.index_end {
+        if self.index < self.index_end {
             let result = B::IndexBitfield::new_unchecked(self.index);
             self.index += 1;
             Bitfield::decode_relative_to(result, self.possibilities)
@@ -599,19 +669,28 @@ impl<B: Bitfield> Iterator for BitfieldSubsetIterator<B> {
             None
         }
     }
+
+    #[inline(always)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.index_end - self.index;
+        (len, Some(len))
+    }
 }
 
 impl<B: Bitfield> DoubleEndedIterator for BitfieldSubsetIterator<B> {
     fn next_back(&mut self) -> Option<Self::Item> {
-        if self.index <= self.index_end {
-            let result = B::IndexBitfield::new(self.index_end.try_into().ok()?);
+        if self.index < self.index_end {
             self.index_end -= 1;
+            let result = B::IndexBitfield::new(self.index_end.try_into().ok()?);
             Bitfield::decode_relative_to(result, self.possibilities)
         } else {
             None
         }
     }
 }
+
+impl<B: Bitfield> ExactSizeIterator for BitfieldSubsetIterator<B> {}
+impl<B: Bitfield> FusedIterator for BitfieldSubsetIterator<B> {}
 // }}}
 // {{{ Main definition
 make_bitfield!(
@@ -623,6 +702,24 @@ make_bitfield!(
     true
 );
 
+make_bitfield!(
+    Bitfield32,
+    usize,
+    u32,
+    32,
+    Bitfield32,
+    true
+);
+
+make_bitfield!(
+    Bitfield64,
+    usize,
+    u64,
+    64,
+    Bitfield64,
+    true
+);
+
 impl Bitfield16 {
     /// A nicer form of `decode_relative_to`.
     // NOTE: only used in testing now. Is it worth keeping?
@@ -635,10 +732,9 @@ impl Bitfield16 {
 // {{{ Ones encoding
 pub mod const_size_codec {
     use std::convert::TryInto;
-    use once_cell::sync::Lazy;
 
     // {{{ Implementation
-    use crate::helpers::choose::choose;
+    use crate::helpers::choose::{checked_choose, choose};
 
     use super::*;
 
@@ -653,15 +749,17 @@ pub mod const_size_codec {
     ///
     /// An index is simply equal to the previous one, plus the number
     /// of spots required by the previous table (in this case, `16 choose i - 1`).
-    static MAGIC_INDICES: Lazy<[usize; BIT_CASES]> = Lazy::new(|| {
+    const MAGIC_INDICES: [usize; BIT_CASES] = {
         let mut results = [0; BIT_CASES];
 
-        for i in 1..BIT_CASES {
-            results[i] = results[i - 1] + choose(16, i - 1);
-        };
+        let mut i = 1;
+        while i < BIT_CASES {
+            results[i] = results[i - 1] + checked_choose(16, i - 1).unwrap();
+            i += 1;
+        }
 
         results
-    });
+    };
 
     /// The lookup tables required for encoding!
     /// - the first table maps raw values to encoded values.
@@ -670,25 +768,30 @@ pub mod const_size_codec {
     ///   to respective raw values with n-ones inside.
     /// - the third table contains the number of entries in each table
     ///   contained in the second array (used for testing / asserts).
-    static LOOKUP_TABLES: Lazy<(
+    ///
+    /// The tables are computed at compile time, so lookups
+    /// don't have to check whether they've been initialized yet.
+    static LOOKUP_TABLES: (
         [u16; DECODED_COUNT],
         [u16; DECODED_COUNT],
         [usize; BIT_CASES],
-    )> = Lazy::new(|| {
+    ) = {
         let mut encode = [0 as u16; DECODED_COUNT];
         let mut decode = [0 as u16; DECODED_COUNT];
         let mut lengths = [0 as usize; BIT_CASES];
 
-        for decoded in 0..DECODED_COUNT {
-            let count = Bitfield16::new(decoded as u16).len() as usize;
+        let mut decoded = 0;
+        while decoded < DECODED_COUNT {
+            let count = (decoded as u16).count_ones() as usize;
             let encoded = lengths[count];
             decode[MAGIC_INDICES[count] + encoded] = decoded as u16;
             encode[decoded] = encoded as u16;
             lengths[count] += 1;
-        };
+            decoded += 1;
+        }
 
         (encode, decode, lengths)
-    });
+    };
 
     /// Returns the number of possible bitfields containing a given number of ones.
     #[inline(always)]
@@ -697,6 +800,55 @@ pub mod const_size_codec {
         LOOKUP_TABLES.2[ones]
     }
 
+    /// Computes the same encoding as the lookup tables, without any lookup tables.
+    ///
+    /// Uses the combinatorial number system: the ones at positions
+    /// `c_1 < c_2 < ... < c_k` get encoded as the sum of `c_i choose i`.
+    /// This is slower, but works for bitfields of any size.
+    fn encode_ones_combinatorial(decoded: u64) -> Encoded {
+        let mut result = 0;
+        let mut ones = 0;
+
+        for position in 0..(u64::BITS as usize) {
+            if decoded & (1 << position) != 0 {
+                ones += 1;
+
+                if position >= ones {
+                    result += choose(position, ones);
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Inverse of `encode_ones_combinatorial`.
+    fn decode_ones_combinatorial(mut encoded: Encoded, ones: usize, bits: usize) -> Option<u64> {
+        if ones > bits || encoded >= choose(bits, ones) {
+            return None;
+        }
+
+        let mut result = 0;
+        let mut position = bits;
+
+        // Greedily pick the largest position `c` with `c choose i <= encoded`
+        for i in (1..=ones).rev() {
+            position -= 1;
+
+            while position >= i && choose(position, i) > encoded {
+                position -= 1;
+            }
+
+            if position >= i {
+                encoded -= choose(position, i);
+            }
+
+            result |= 1 << position;
+        }
+
+        Some(result)
+    }
+
     /// Represents a bitfield, after all information about the number of ones
     /// has been removed.
     pub type Encoded = usize;
@@ -706,18 +858,26 @@ pub mod const_size_codec {
         /// representation of a number is known, removing such
         /// useless information.
         ///
-        /// The result fits inside an u16,
-        /// but we pass around an `usize` for convenience.
+        /// Bitfields of at most 16 bits use lookup tables,
+        /// while larger ones compute the encoding on the fly.
         #[inline(always)]
         fn encode_ones(self) -> Encoded {
-            assert!(Self::BITS <= 16);
+            let decoded: u64 = self.into().into();
 
-            LOOKUP_TABLES.0[self.into().into()] as usize
+            if Self::BITS <= 16 {
+                LOOKUP_TABLES.0[decoded as usize] as usize
+            } else {
+                encode_ones_combinatorial(decoded)
+            }
         }
 
         /// Inverse of `encode_ones`.
         fn decode_ones(encoded: Encoded, ones: usize) -> Option<Self> {
-            assert!(Self::BITS <= 16);
+            if Self::BITS > 16 {
+                let decoded = decode_ones_combinatorial(encoded, ones, Self::BITS)?;
+
+                return Some(Self::new((decoded as usize).try_into().ok()?));
+            }
 
             if encoded >= count_with_n_ones(ones) {
                 None
@@ -778,6 +938,44 @@ pub mod const_size_codec {
             }
         }
 
+        #[test]
+        fn combinatorial_encoding_matches_lookup_tables() {
+            for i in 0..=u16::MAX {
+                let bitfield = Bitfield16::new(i);
+                let encoded = bitfield.encode_ones();
+
+                assert_eq!(encode_ones_combinatorial(i as u64), encoded);
+                assert_eq!(
+                    decode_ones_combinatorial(encoded, bitfield.len(), 16),
+                    Some(i as u64)
+                );
+            }
+        }
+
+        #[test]
+        fn wide_encode_decode_identity() {
+            let examples = [0, 1, 0b1011, 0xF0F0_0000_0001, u64::MAX >> 1, u64::MAX];
+
+            for example in examples {
+                let bitfield = Bitfield64::new(example);
+
+                assert_eq!(
+                    Some(bitfield),
+                    ConstSizeCodec::decode_ones(bitfield.encode_ones(), bitfield.len())
+                );
+
+                let bitfield = Bitfield32::new(example as u32);
+
+                assert_eq!(
+                    Some(bitfield),
+                    ConstSizeCodec::decode_ones(bitfield.encode_ones(), bitfield.len())
+                );
+            }
+
+            assert_eq!(Bitfield64::decode_ones(1, 64), None);
+            assert_eq!(Bitfield32::decode_ones(choose(32, 3), 3), None);
+        }
+
         #[test]
         fn decode_encode_identity() {
             for ones in 0..=16 {
@@ -801,6 +999,7 @@ pub mod const_size_codec {
 mod tests {
     use std::assert_eq;
     use super::*;
+    use crate::error::EchoError;
 
     #[test]
     fn all_examples() {
@@ -1027,6 +1226,23 @@ mod tests {
         }
     }
 
+    #[test]
+    fn wide_examples() {
+        assert_eq!(Bitfield32::all().len(), 32);
+        assert_eq!(Bitfield64::all().len(), 64);
+        assert_eq!(Bitfield64::singleton(63).indexof(63), Some(0));
+        assert_eq!(Bitfield64::all().into_iter().next_back(), Some(63));
+        assert_eq!(Bitfield64::all().subsets_of_size(64).count(), 1);
+        assert_eq!(Bitfield64::all().subsets_of_size(2).count(), choose(64, 2));
+        assert_eq!(Bitfield64::new(u64::MAX >> 1).subsets().len(), 1 << 63);
+    }
+
+    #[test]
+    #[should_panic]
+    fn subsets_of_full_wide_bitfields_cannot_be_counted() {
+        Bitfield64::all().subsets();
+    }
+
     #[test]
     fn subsets_of_size_correct_count() {
         for i in 0..Bitfield16::MAX {
@@ -1037,5 +1253,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn fallible_insert_remove() {
+        let mut bitfield = Bitfield16::singleton(3);
+
+        assert_eq!(bitfield.try_insert(3), Err(EchoError::BitAlreadyPresent(3)));
+        assert_eq!(bitfield.try_remove(4), Err(EchoError::BitNotPresent(4)));
+        assert_eq!(bitfield.try_insert(4), Ok(()));
+        assert_eq!(bitfield.try_remove(3), Ok(()));
+        assert_eq!(bitfield, Bitfield16::singleton(4));
+    }
+
+    #[test]
+    fn iterators_report_exact_sizes() {
+        for i in (0..Bitfield16::MAX).step_by(97) {
+            let b = Bitfield16::new(i);
+
+            let mut members = b.into_iter();
+            let mut subsets = b.subsets();
+            for remaining in (0..=b.len()).rev() {
+                assert_eq!(members.len(), remaining);
+                members.next();
+            }
+
+            for remaining in (0..=2usize.pow(b.len() as u32)).rev() {
+                assert_eq!(subsets.len(), remaining);
+                subsets.next_back();
+            }
+
+            for ones in 0..=b.len() {
+                let subsets = b.subsets_of_size(ones);
+                assert_eq!(subsets.len(), subsets.count());
+            }
+        }
+    }
+
+    #[test]
+    fn iterating_from_both_ends_meets_in_the_middle() {
+        let b = Bitfield16::new(0b1000_0000_0010_0101);
+        let mut members = b.into_iter();
+
+        assert_eq!(members.next_back(), Some(15));
+        assert_eq!(members.next(), Some(0));
+        assert_eq!(members.next_back(), Some(5));
+        assert_eq!(members.next_back(), Some(2));
+        assert_eq!(members.next_back(), None);
+        assert_eq!(members.next(), None);
+
+        let singleton = Bitfield16::singleton(0);
+        assert_eq!(singleton.into_iter().rev().collect::<Vec<_>>(), vec![0]);
+        assert_eq!(singleton.subsets().rev().count(), 2);
+    }
 }
 // }}}
@@ -1,26 +1,101 @@
-use std::convert::TryInto;
+/// Values of `n` for which `n choose k` gets looked up in a table.
+const TABLE_SIZE: usize = 65;
 
-/// Const `n choose k` function.
-/// - tested for values smaller than 17.
-/// - fails when n=0 or n>=k.
-pub fn choose(n: usize, k: usize) -> usize {
-    assert!(n >= k);
+/// Pascal's triangle, precomputed at compile time.
+/// Entries where `k > n` are left as zero.
+///
+/// Entries are stored as `u64`s, since the larger ones do not
+/// fit inside an `usize` on 32-bit targets (eg: wasm).
+static PASCAL_TRIANGLE: [[u64; TABLE_SIZE]; TABLE_SIZE] = {
+    let mut table = [[0; TABLE_SIZE]; TABLE_SIZE];
+
+    let mut n = 0;
+    while n < TABLE_SIZE {
+        table[n][0] = 1;
+
+        let mut k = 1;
+        while k <= n {
+            table[n][k] = table[n - 1][k - 1] + table[n - 1][k];
+            k += 1;
+        }
 
-    if n == 0 {
-        return 1;
+        n += 1;
     }
 
-    let mut result: u64 = 1;
+    table
+};
 
-    for i in (n - k + 1)..(n + 1) {
-        result *= i as u64;
+/// Const `n choose k` function, returning `None` if the result
+/// does not fit inside an `usize`.
+/// - fails when n<k.
+pub const fn checked_choose(n: usize, k: usize) -> Option<usize> {
+    assert!(n >= k);
+
+    let k = if k < n - k { k } else { n - k };
+    let mut result: u128 = 1;
+
+    // After the ith step, `result` is equal to `(n - k + i) choose i`,
+    // so the division is always exact.
+    let mut i = 1;
+    while i <= k {
+        result = match result.checked_mul((n - k + i) as u128) {
+            Some(product) => product / i as u128,
+            None => return None,
+        };
+
+        i += 1;
     }
 
-    for i in 2..(k + 1) {
-        result /= i as u64;
+    if result <= usize::MAX as u128 {
+        Some(result as usize)
+    } else {
+        None
     }
+}
 
-    result.try_into().unwrap()
+/// `n choose k` function.
+/// - uses a lookup table for values smaller than 65.
+/// - fails when n<k, or when the result does not fit inside an `usize`.
+#[inline(always)]
+pub fn choose(n: usize, k: usize) -> usize {
+    assert!(n >= k);
+
+    let result = if n < TABLE_SIZE {
+        usize::try_from(PASCAL_TRIANGLE[n][k]).ok()
+    } else {
+        checked_choose(n, k)
+    };
+
+    result.unwrap_or_else(|| panic!("{n} choose {k} overflows an usize"))
+}
+
+/// Multinomial coefficient — the number of ways to split a set with
+/// `sum(groups)` elements into subsets with the given sizes.
+/// Returns `None` if the result does not fit inside an `usize`.
+///
+/// # Examples
+///
+/// ```ignore
+/// choose_multi(&[2, 1]) // Some(3) (same as `3 choose 2`)
+/// choose_multi(&[1, 1, 1]) // Some(6)
+/// ```
+pub fn choose_multi(groups: &[usize]) -> Option<usize> {
+    let mut total: usize = 0;
+    let mut result: usize = 1;
+
+    for group in groups {
+        total = total.checked_add(*group)?;
+
+        let choices = if total < TABLE_SIZE {
+            usize::try_from(PASCAL_TRIANGLE[total][*group]).ok()?
+        } else {
+            checked_choose(total, *group)?
+        };
+
+        result = result.checked_mul(choices)?;
+    }
+
+    Some(result)
 }
 
 #[cfg(test)]
@@ -42,10 +117,56 @@ mod tests {
                 result += choose(i, j);
             }
 
-            assert_eq!(result, (2 as usize).pow(i as u32), "Failed for {}", i);
+            assert_eq!(result, 2_usize.pow(i as u32), "Failed for {}", i);
+        }
+    }
+
+    /// Makes sure large values don't overflow along the way.
+    #[test]
+    fn large_examples() {
+        assert_eq!(choose(32, 16), 601080390);
+        assert_eq!(choose(64, 32), 1832624140942590534);
+        assert_eq!(choose(64, 63), 64);
+    }
+
+    #[test]
+    fn table_agrees_with_computation() {
+        for n in 0..TABLE_SIZE {
+            for k in 0..=n {
+                assert_eq!(
+                    Some(choose(n, k)),
+                    checked_choose(n, k),
+                    "Failed for {n} choose {k}"
+                );
+            }
         }
     }
 
+    #[test]
+    fn overflows_are_detected() {
+        assert_eq!(checked_choose(100, 2), Some(4950));
+        assert_eq!(checked_choose(100, 50), None);
+        assert_eq!(choose(100, 98), 4950);
+    }
+
+    #[test]
+    fn multinomial_examples() {
+        assert_eq!(choose_multi(&[]), Some(1));
+        assert_eq!(choose_multi(&[2, 1]), Some(3));
+        assert_eq!(choose_multi(&[1, 1, 1]), Some(6));
+        assert_eq!(choose_multi(&[5, 1, 5]), Some(choose(11, 5) * choose(6, 1)));
+    }
+
+    #[test]
+    fn multinomial_overflows_are_detected() {
+        assert_eq!(choose_multi(&[32, 32]), Some(choose(64, 32)));
+        assert_eq!(choose_multi(&[32, 32, 32]), None);
+
+        // Both factors fit inside an usize, but their product does not.
+        assert_eq!(choose_multi(&[32, 32, 1]), None);
+        assert_eq!(choose_multi(&[usize::MAX, 1]), None);
+    }
+
     /// Tests that `n choose k` is equal to `n choose n - k`.
     #[test]
     fn choice_complements() {
@@ -1,5 +1,3 @@
-use rand::Rng;
-
 pub mod bitops;
 pub mod try_from_iter;
 pub mod choose;
@@ -7,47 +5,76 @@ pub mod pair;
 pub mod bitfield;
 pub mod ranged;
 pub mod itertools;
+pub mod sampling;
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+
+/// Number of independent accumulators used by `lane_sum`.
+const LANES: usize = 8;
+
+/// Sums `f(value)` over an entire slice.
+///
+/// Elements are processed `LANES` at a time using independent accumulators,
+/// which lets the compiler keep the accumulators inside a single simd register
+/// (a plain loop has to respect the order floating point additions happen in).
+#[inline(always)]
+pub fn lane_sum<T>(values: &[T], f: impl Fn(&T) -> f32) -> f32 {
+    let mut lanes = [0.0; LANES];
+    let chunks = values.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    for chunk in chunks {
+        for (lane, value) in lanes.iter_mut().zip(chunk) {
+            *lane += f(value);
+        }
+    }
+
+    let mut sum: f32 = lanes.iter().sum();
+    for value in remainder {
+        sum += f(value);
+    }
+
+    sum
+}
 
 /// Normalize a vector. If all the values are zero,
 /// all the entries will be set to 1/size.
 pub fn normalize_vec(vec: &mut [f32]) {
-    let mut sum = 0.0;
-    let size = vec.len();
-
-    for value in &mut *vec {
-        sum += *value;
-    }
+    let sum = lane_sum(vec, |value| *value);
 
-    for value in vec {
-        if sum > 0.0 {
-            *value /= sum;
-        } else {
-            // TODO: maybe extract this in the outer scope?
-            // I doubt this really impact performance.
-            *value = 1.0 / (size as f32);
+    if sum > 0.0 {
+        let inverse = 1.0 / sum;
+        for value in vec {
+            *value *= inverse;
         }
+    } else {
+        let size = vec.len();
+        vec.fill(1.0 / (size as f32));
     }
 }
 
-/// Pick a random number using a probability distribution.
-pub fn roulette<R>(probabilities: &[f32], rng: &mut R) -> usize
-where
-    R: Rng,
-{
-    let upper = 100000;
-    let num: f32 = rng.gen_range(0..upper) as f32 / (upper as f32);
-    let mut total = 0.0;
-
-    for (index, length) in probabilities.into_iter().enumerate() {
-        if num >= total && num < total + length {
-            return index;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        total += *length;
+    #[test]
+    fn lane_sum_handles_remainders() {
+        for len in [0, 3, 8, 13, 64] {
+            let values: Vec<_> = (0..len).map(|i| i as f32).collect();
+            let expected: f32 = values.iter().sum();
+
+            assert_eq!(lane_sum(&values, |value| *value), expected);
+        }
     }
 
-    panic!(
-        "Degenerate probability distribution {:?} — value {:?} does not fit anywhere.",
-        probabilities, num
-    )
+    #[test]
+    fn normalize_vec_sums_to_one() {
+        let mut values = vec![1.0, 3.0, 0.0, 4.0];
+        normalize_vec(&mut values);
+        assert_eq!(values, vec![0.125, 0.375, 0.0, 0.5]);
+
+        let mut zeroes = vec![0.0; 4];
+        normalize_vec(&mut zeroes);
+        assert_eq!(zeroes, vec![0.25; 4]);
+    }
 }
@@ -1,3 +1,5 @@
+use crate::game::types::Player;
+
 pub type Pair<T> = [T; 2];
 
 // {{{ Swap
@@ -23,9 +25,96 @@ pub fn conditional_swap<T: Swap>(pair: T, should_swap: bool) -> T {
     }
 }
 // }}}
+// {{{ Combinators
+/// Helpers for working with pairs holding data for each player.
+pub trait PairExt<T> {
+    /// Combines the elements of two pairs one by one.
+    fn zip_with<U, V>(self, other: Pair<U>, f: impl FnMut(T, U) -> V) -> Pair<V>;
+
+    /// Similar to `map`, but also passes along the player each element belongs to.
+    fn map_per_player<U>(self, f: impl FnMut(Player, T) -> U) -> Pair<U>;
+
+    /// Reorders the pair such that the data for the given player comes first.
+    /// Equivalent to `player.order_as(pair)`, but does not require `T: Copy`.
+    fn into_perspective(self, player: Player) -> Pair<T>;
+}
+
+impl<T> PairExt<T> for Pair<T> {
+    #[inline(always)]
+    fn zip_with<U, V>(self, other: Pair<U>, mut f: impl FnMut(T, U) -> V) -> Pair<V> {
+        let [a, b] = self;
+        let [c, d] = other;
+        [f(a, c), f(b, d)]
+    }
+
+    #[inline(always)]
+    fn map_per_player<U>(self, mut f: impl FnMut(Player, T) -> U) -> Pair<U> {
+        let [a, b] = self;
+        [f(Player::Me, a), f(Player::You, b)]
+    }
+
+    #[inline(always)]
+    fn into_perspective(self, player: Player) -> Pair<T> {
+        let [a, b] = self;
+        match player {
+            Player::Me => [a, b],
+            Player::You => [b, a],
+        }
+    }
+}
+
+/// Computes a value for each player.
+#[inline(always)]
+pub fn for_player<T>(f: impl FnMut(Player) -> T) -> Pair<T> {
+    Player::PLAYERS.map(f)
+}
+
+/// Similar to `for_player`, but stops at the first error.
+#[inline(always)]
+pub fn try_for_player<T, E>(mut f: impl FnMut(Player) -> Result<T, E>) -> Result<Pair<T>, E> {
+    Ok([f(Player::Me)?, f(Player::You)?])
+}
+// }}}
 // {{{ Other helpes
 /// Returns whether both elements of a pair are equal.
 pub fn are_equal<T: Eq>(pair: Pair<T>) -> bool {
     pair[0] == pair[1]
 }
 // }}}
+
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combinator_examples() {
+        assert_eq!([1, 2].zip_with([3, 4], |a, b| a * b), [3, 8]);
+        assert_eq!(
+            ["a", "b"].map_per_player(|player, x| (player, x)),
+            [(Player::Me, "a"), (Player::You, "b")]
+        );
+        assert_eq!(for_player(|player| !player), [Player::You, Player::Me]);
+    }
+
+    #[test]
+    fn perspectives_agree_with_order_as() {
+        for player in Player::PLAYERS {
+            assert_eq!([1, 2].into_perspective(player), player.order_as([1, 2]));
+        }
+    }
+
+    #[test]
+    fn try_for_player_stops_at_errors() {
+        let mut calls = 0;
+        let result: Result<Pair<()>, Player> = try_for_player(|player| {
+            calls += 1;
+            Err(player)
+        });
+
+        assert_eq!(result, Err(Player::Me));
+        assert_eq!(calls, 1);
+        assert_eq!(try_for_player(Ok::<_, ()>), Ok(Player::PLAYERS));
+    }
+}
+// }}}
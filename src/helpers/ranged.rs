@@ -3,16 +3,28 @@ use super::{
     choose::choose,
 };
 
+/// Packs multiple bounded integers into a single one (think of a number
+/// written in a base which varies from digit to digit).
+///
+/// Mixing multiplies `self` by `max`, so the result only fits if the product
+/// of all the ranges mixed together does. `mix_ranged` assumes that's the case
+/// (overflowing panics in debug builds, and wraps around otherwise), while
+/// `try_mix_ranged` (and everything built on top of it) checks for it.
 pub trait MixRanged: Sized {
     /// Embed an integer inside self given the maximum value of the integer.
+    /// The value must be strictly smaller than `max`.
     fn mix_ranged(self, value: usize, max: usize) -> Self;
 
-    /// The inverse of mix_ranged.
+    /// Similar to `mix_ranged`, but returns `None` if the value is out of range,
+    /// or if the result would not fit inside `Self`.
+    fn try_mix_ranged(self, value: usize, max: usize) -> Option<Self>;
+
+    /// The inverse of mix_ranged. Returns `None` when `max` is `0`.
     fn unmix_ranged(self, max: usize) -> Option<(Self, usize)>;
 
     /// Mix in data about the index of some bit in a bitfield.
     fn mix_indexof<T: Bitfield>(self, index: T::Element, possibilities: T) -> Option<Self> {
-        Some(self.mix_ranged(possibilities.indexof(index)?, possibilities.len()))
+        self.try_mix_ranged(possibilities.indexof(index)?, possibilities.len())
     }
 
     /// Inverse of `mix_indeox`
@@ -22,10 +34,13 @@ pub trait MixRanged: Sized {
     }
 
     /// Generalized version of `mix_indexof` which works with arbitrary sized subsets.
-    fn mix_subset<T: Bitfield>(self, subset: T, of: T) -> Self {
-        assert!(subset.is_subset_of(of));
+    fn mix_subset<T: Bitfield>(self, subset: T, of: T) -> Option<Self> {
+        if !subset.is_subset_of(of) {
+            return None;
+        }
+
         let values = choose(of.len(), subset.len());
-        self.mix_ranged(subset.encode_ones_relative_to(of), values)
+        self.try_mix_ranged(subset.encode_ones_relative_to(of), values)
     }
 
     /// Inverse of `mix_subset`
@@ -38,25 +53,51 @@ pub trait MixRanged: Sized {
     }
 }
 
-impl MixRanged for usize {
-    #[inline(always)]
-    fn mix_ranged(self, value: usize, max: usize) -> Self {
-        max * self + value
-    }
+macro_rules! impl_mix_ranged {
+    ($type: ty) => {
+        impl MixRanged for $type {
+            #[inline(always)]
+            fn mix_ranged(self, value: usize, max: usize) -> Self {
+                debug_assert!(value < max, "{value} is not smaller than {max}");
+                (max as $type) * self + (value as $type)
+            }
 
-    #[inline(always)]
-    fn unmix_ranged(self, max: usize) -> Option<(Self, usize)> {
-        Some((self / max, self % max))
-    }
+            #[inline(always)]
+            fn try_mix_ranged(self, value: usize, max: usize) -> Option<Self> {
+                if value >= max {
+                    return None;
+                }
+
+                (max as $type)
+                    .checked_mul(self)?
+                    .checked_add(value as $type)
+            }
+
+            #[inline(always)]
+            fn unmix_ranged(self, max: usize) -> Option<(Self, usize)> {
+                if max == 0 {
+                    return None;
+                }
+
+                let max = max as $type;
+                Some((self / max, (self % max) as usize))
+            }
+        }
+    };
 }
 
+impl_mix_ranged!(usize);
+
 #[cfg(test)]
 mod tests {
     use super::MixRanged;
+    use crate::game::creature::{Creature, CreatureSet};
+    use crate::helpers::bitfield::Bitfield;
+    use crate::helpers::choose::choose;
 
     #[test]
     fn usize_mix_unmix_inverses() {
-        for i in 0..500 {
+        for i in 0..500usize {
             for max in 1..100 {
                 for j in 0..max {
                     assert_eq!(Some((i, j)), i.mix_ranged(j, max).unmix_ranged(max))
@@ -67,13 +108,49 @@ mod tests {
 
     #[test]
     fn usize_mix_examples() {
-        assert_eq!(53, 10.mix_ranged(3, 5));
-        assert_eq!(90, 9.mix_ranged(0, 10));
+        assert_eq!(53, 10usize.mix_ranged(3, 5));
+        assert_eq!(90, 9usize.mix_ranged(0, 10));
     }
 
     #[test]
     fn usize_unmix_examples() {
-        assert_eq!(Some((4, 9)), 53.unmix_ranged(11));
-        assert_eq!(Some((8, 22)), 222.unmix_ranged(25));
+        assert_eq!(Some((4, 9)), 53usize.unmix_ranged(11));
+        assert_eq!(Some((8, 22)), 222usize.unmix_ranged(25));
+        assert_eq!(None, 222usize.unmix_ranged(0));
+    }
+
+    #[test]
+    fn try_mix_detects_overflow() {
+        let top = usize::MAX / 10;
+
+        assert_eq!(Some(usize::MAX - 5), top.try_mix_ranged(0, 10));
+        assert_eq!(Some(usize::MAX), top.try_mix_ranged(5, 10));
+        assert_eq!(None, top.try_mix_ranged(6, 10));
+        assert_eq!(None, (top + 1).try_mix_ranged(0, 10));
+        assert_eq!(None, 3usize.try_mix_ranged(10, 10));
+        assert_eq!(
+            Some((top, 5)),
+            top.try_mix_ranged(5, 10).unwrap().unmix_ranged(10)
+        );
+    }
+
+    #[test]
+    fn bitfield_mixing_detects_overflow() {
+        let all = CreatureSet::all();
+        let pair = CreatureSet::singleton(Creature::Wall) | CreatureSet::singleton(Creature::Seer);
+        let top = usize::MAX / all.len();
+
+        assert_eq!(Some(top * all.len()), top.mix_indexof(Creature::Wall, all));
+        assert_eq!(None, (top + 1).mix_indexof(Creature::Wall, all));
+        assert_eq!(
+            Some((top, Creature::Wall)),
+            top.mix_indexof(Creature::Wall, all)
+                .and_then(|mixed| mixed.unmix_indexof(all))
+        );
+
+        let subsets = choose(all.len(), pair.len());
+        assert!((usize::MAX / subsets - 1).mix_subset(pair, all).is_some());
+        assert_eq!(None, (usize::MAX / subsets + 1).mix_subset(pair, all));
+        assert_eq!(None, 0usize.mix_subset(all, pair));
     }
 }
@@ -0,0 +1,192 @@
+//! Sampling indices from discrete probability distributions.
+//!
+//! Weights do not have to be normalized. Negative or non-finite weights
+//! are treated as zero, and distributions with no positive weights at all
+//! are treated as uniform (instead of panicking).
+use rand::Rng;
+
+/// Returns the weight of some entry, clamping degenerate values to zero.
+#[inline(always)]
+fn sanitize(weight: f32) -> f64 {
+    if weight.is_finite() && weight > 0.0 {
+        weight as f64
+    } else {
+        0.0
+    }
+}
+
+/// Picks a random index using a (not necessarily normalized) distribution.
+///
+/// Takes linear time, so prefer building an `AliasTable`
+/// when sampling the same distribution many times.
+pub fn sample<R: Rng>(weights: &[f32], rng: &mut R) -> usize {
+    assert!(!weights.is_empty(), "Cannot sample an empty distribution");
+
+    let total: f64 = weights.iter().map(|weight| sanitize(*weight)).sum();
+    if total <= 0.0 {
+        return rng.gen_range(0..weights.len());
+    }
+
+    let target = rng.gen::<f64>() * total;
+    let mut cumulative = 0.0;
+    let mut last_positive = 0;
+
+    for (index, weight) in weights.iter().enumerate() {
+        let weight = sanitize(*weight);
+        if weight > 0.0 {
+            cumulative += weight;
+            last_positive = index;
+
+            if target < cumulative {
+                return index;
+            }
+        }
+    }
+
+    // Rounding errors might make the cumulative sum fall short of `total`
+    last_positive
+}
+
+/// Samples a distribution in constant time using Vose's alias method,
+/// after spending linear time building up the table.
+#[derive(Debug, Clone)]
+pub struct AliasTable {
+    /// Probability of keeping the index picked uniformly at random.
+    thresholds: Vec<f64>,
+    /// The index to return instead of the one picked uniformly at random.
+    aliases: Vec<usize>,
+}
+
+impl AliasTable {
+    pub fn new(weights: &[f32]) -> Self {
+        assert!(!weights.is_empty(), "Cannot sample an empty distribution");
+
+        let size = weights.len();
+        let total: f64 = weights.iter().map(|weight| sanitize(*weight)).sum();
+
+        // Scale everything such that the average weight is 1
+        let mut scaled: Vec<f64> = if total > 0.0 {
+            weights
+                .iter()
+                .map(|weight| sanitize(*weight) * size as f64 / total)
+                .collect()
+        } else {
+            vec![1.0; size]
+        };
+
+        let mut thresholds = vec![1.0; size];
+        let mut aliases: Vec<usize> = (0..size).collect();
+
+        let (mut small, mut large): (Vec<_>, Vec<_>) =
+            (0..size).partition(|index| scaled[*index] < 1.0);
+
+        while let (Some(less), Some(more)) = (small.pop(), large.pop()) {
+            thresholds[less] = scaled[less];
+            aliases[less] = more;
+
+            // Move the missing probability mass over from the larger entry
+            scaled[more] -= 1.0 - scaled[less];
+            if scaled[more] < 1.0 {
+                small.push(more);
+            } else {
+                large.push(more);
+            }
+        }
+
+        // Whatever is left over only differs from 1 due to rounding errors,
+        // so the default threshold of 1 is already correct.
+        Self {
+            thresholds,
+            aliases,
+        }
+    }
+
+    /// The number of entries in the distribution.
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.thresholds.len()
+    }
+
+    /// Never true, as empty distributions get rejected by `new`.
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.thresholds.is_empty()
+    }
+
+    /// Picks a random index.
+    #[inline(always)]
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> usize {
+        let index = rng.gen_range(0..self.len());
+
+        if rng.gen::<f64>() < self.thresholds[index] {
+            index
+        } else {
+            self.aliases[index]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SAMPLES: usize = 100000;
+
+    /// Returns how often every index got picked.
+    fn frequencies(size: usize, mut sampler: impl FnMut() -> usize) -> Vec<f32> {
+        let mut counts = vec![0; size];
+        for _ in 0..SAMPLES {
+            counts[sampler()] += 1;
+        }
+
+        counts
+            .into_iter()
+            .map(|count| count as f32 / SAMPLES as f32)
+            .collect()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 0.01,
+                "Frequencies {actual:?} are too far from {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn samplers_follow_the_distribution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let weights = [1.0, 0.0, 3.0, 4.0];
+        let expected = [0.125, 0.0, 0.375, 0.5];
+
+        let table = AliasTable::new(&weights);
+        assert_close(&frequencies(4, || table.sample(&mut rng)), &expected);
+        assert_close(&frequencies(4, || sample(&weights, &mut rng)), &expected);
+    }
+
+    #[test]
+    fn degenerate_distributions_are_uniform() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for weights in [[0.0; 4], [-1.0, f32::NAN, 0.0, f32::NEG_INFINITY]] {
+            let table = AliasTable::new(&weights);
+            assert_close(&frequencies(4, || table.sample(&mut rng)), &[0.25; 4]);
+            assert_close(&frequencies(4, || sample(&weights, &mut rng)), &[0.25; 4]);
+        }
+    }
+
+    #[test]
+    fn zero_weights_are_never_picked() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let weights = [0.0, 1e-30, 0.0, f32::NAN];
+
+        let table = AliasTable::new(&weights);
+        for _ in 0..1000 {
+            assert_eq!(table.sample(&mut rng), 1);
+            assert_eq!(sample(&weights, &mut rng), 1);
+        }
+    }
+}
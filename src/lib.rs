@@ -17,6 +17,18 @@
 #![allow(dead_code)]
 
 pub mod ai;
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod cfr;
+pub mod config;
+#[cfg(feature = "database")]
+pub mod database;
+pub mod error;
 pub mod game;
 pub mod helpers;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(feature = "rpc")]
+pub mod rpc;
@@ -2,27 +2,95 @@
 
 use bumpalo::Bump;
 use echo::ai::always_zero_agent::AlwaysZeroAgent;
+use echo::ai::best_response_agent::BestResponseAgent;
+#[cfg(feature = "gui")]
+use echo::ai::clock::TimeControl;
+use echo::ai::echo_ai::EchoAgent;
 use echo::ai::echo_ai::EchoRunner;
+#[cfg(feature = "gui")]
 use echo::ai::human_player::GUIApp;
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
+use echo::ai::human_player::GameDriver;
+#[cfg(feature = "gui")]
+use echo::ai::human_player::GameLauncher;
+#[cfg(feature = "gui")]
 use echo::ai::human_player::HumanAgent;
+#[cfg(feature = "gui")]
+use echo::ai::human_player::OpponentKind;
+use echo::ai::opponent_model_agent::OpponentModelAgent;
 use echo::ai::random_agent::RandomAgent;
+use echo::ai::settings::Settings;
+use echo::ai::strategy_agent::StrategyAgent;
+use echo::ai::strategy_hints::BlueprintStrategyProvider;
+use echo::ai::transcript::AgentStats;
+use echo::cfr::blueprint::{self, write_blueprint, BlockId, BlueprintReader};
+use echo::cfr::decision::Scope;
 use echo::cfr::decision_index::DecisionIndex;
+#[cfg(feature = "deep-cfr")]
+use echo::cfr::deep::{DecisionNetwork, DeepCfrConfig, DeepCfrContext};
+#[cfg(feature = "net")]
+use echo::cfr::distributed::{Coordinator, DistributedTraining, Worker};
 use echo::cfr::generate::EstimationContext;
 use echo::cfr::generate::GenerationContext;
+use echo::cfr::generate::TranspositionTable;
+use echo::cfr::hidden_index::EncodingInfo;
 use echo::cfr::hidden_index::HiddenIndex;
+use echo::cfr::hidden_index::HiddenState;
 use echo::cfr::hidden_index::PerPhaseInfo;
+use echo::cfr::index_check;
+use echo::cfr::opening_book::OpeningBook;
+use echo::cfr::phase::MainPhase;
+use echo::cfr::phase::PerPhase;
 use echo::cfr::phase::Phase;
-use echo::cfr::train::TrainingContext;
+use echo::cfr::phase::PhaseTag;
+use echo::cfr::phase::SabotagePhase;
+use echo::cfr::phase::SeerPhase;
+use echo::cfr::phase::SomePhase;
+use echo::cfr::reveal_index::RevealIndex;
+use echo::cfr::storage::{MappedStorage, WeightStorage};
+use echo::cfr::train::{Telemetry, TelemetryFormat, WarmStart};
+use echo::config::AgentKind;
+use echo::config::Config;
+use echo::config::SolverConfig;
+#[cfg(feature = "database")]
+use echo::database::MatchDatabase;
+use echo::error::EchoError;
 use echo::game::battlefield::Battlefield;
+use echo::game::battlefield::Battlefields;
 use echo::game::creature::Creature;
+use echo::game::creature::CreatureSet;
 use echo::game::edict::Edict;
+use echo::game::edict::EdictSet;
 use echo::game::known_state::KnownState;
-use echo::game::known_state_summary::KnownStateEssentials;
+use echo::game::known_state_summary::{KnownStateEssentials, KnownStateSummary};
+use echo::game::notation;
+use echo::game::record::GameRecord;
+use echo::game::rules::Ruleset;
+use echo::game::status_effect::StatusEffectSet;
 use echo::game::types::Player;
+use echo::game::types::Score;
 use echo::helpers::bitfield::Bitfield;
-use rand::thread_rng;
+use echo::helpers::pair::Pair;
+#[cfg(all(feature = "net", feature = "gui"))]
+use echo::net::client::Connection;
+#[cfg(feature = "net")]
+use echo::net::server::Server;
+use indicatif::HumanBytes;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use serde::Deserialize;
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
 use std::println;
+#[cfg(any(all(target_arch = "wasm32", feature = "gui"), feature = "database"))]
+use std::rc::Rc;
+use std::str::FromStr;
+#[cfg(all(not(target_arch = "wasm32"), feature = "gui"))]
 use std::thread;
+#[cfg(all(not(target_arch = "wasm32"), feature = "gui"))]
+use std::thread::JoinHandle;
 use std::time::Instant;
 use tracing::Level;
 use tracing_subscriber::prelude::*;
@@ -45,19 +113,11 @@ fn b_to_gb(b: usize) -> usize {
 }
 // }}}
 // {{{ Simple generation/estimating routine
-fn simple_generation(from: usize, turns: usize, generate: bool) {
-    let start = Instant::now();
-    let capacity = mb_to_b(4096);
-    let allocator = Bump::with_capacity(2500);
-    allocator.set_allocation_limit(Some(capacity));
-    let allocation_duration = start.elapsed();
-
-    println!("Performance:");
-    println!("Allocation: {:?}", allocation_duration);
-
-    let start = Instant::now();
-    let mut state = KnownState::new_starting([Battlefield::Plains; 4]);
-    state.battlefields.all[3] = Battlefield::LastStrand;
+/// Creates a state at the start of some turn, with a
+/// couple of creatures and edicts already played.
+fn example_state(from: usize) -> KnownState {
+    let mut state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+    state.battlefields.all[Battlefields::COUNT - 1] = Battlefield::LastStrand;
     state.battlefields.current = from;
 
     for i in 0..from {
@@ -71,6 +131,20 @@ fn simple_generation(from: usize, turns: usize, generate: bool) {
         }
     }
 
+    state
+}
+
+fn simple_generation(solver: &SolverConfig, from: usize, generate: bool) {
+    let start = Instant::now();
+    let turns = solver.turns;
+    let allocator = solver.allocator();
+    let allocation_duration = start.elapsed();
+
+    println!("Performance:");
+    println!("Allocation: {:?}", allocation_duration);
+
+    let start = Instant::now();
+    let state = example_state(from);
     let generator = GenerationContext::new(turns, state, &allocator);
     let estimator = EstimationContext::new(turns, state);
     let state_init_duration = start.elapsed();
@@ -101,30 +175,34 @@ fn simple_generation(from: usize, turns: usize, generate: bool) {
 }
 // }}}
 // {{{ Simple training routine
-fn simple_trainig() {
+fn simple_trainig(solver: &SolverConfig) {
     // {{{ State creation
-    let mut state = KnownState::new_starting([Battlefield::Plains; 4]);
-    state.battlefields.current = 2;
-    for creature in Creature::CREATURES.into_iter().take(4) {
+    let turn = Battlefields::COUNT - 2;
+    let mut state = KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]);
+    state.battlefields.current = turn;
+    for creature in Creature::CREATURES.into_iter().take(2 * turn) {
         state.graveyard.insert(creature);
     }
 
     for state in state.player_states.iter_mut() {
-        for edict in Edict::EDICTS.into_iter().take(2) {
+        for edict in Edict::EDICTS.into_iter().take(turn) {
             state.edicts.remove(edict);
         }
     }
     // }}}
     // {{{ Generation
-    let allocator = Bump::new();
-    let generator = GenerationContext::new(2, state, &allocator);
-    let mut scope = generator.generate();
+    let allocator = solver.allocator();
+    let mut arenas = solver.arenas();
+    let transpositions = TranspositionTable::new();
+    let budget = solver.memory_budget();
+    let generator = solver.generation_context(state, &allocator, &transpositions, budget.as_ref());
+    let mut scope = generator.generate_parallel(&mut arenas);
     // }}}
     // {{{ Training
-    let ctx = TrainingContext::new(false);
-    let mut _rng = rand::thread_rng();
-    ctx.cfr(&mut scope, state.to_summary(), 10000);
-    // ctx.cs_cfr(&mut rng, &mut scope, state.to_summary(), 100000);
+    let mut ctx = solver
+        .training_context()
+        .unwrap_or_else(|error| exit_with(error.to_string()));
+    solver.train(&mut ctx, &mut scope, state.to_summary());
     // }}}
     // {{{ Displaying
     let player = Player::Me;
@@ -132,18 +210,14 @@ fn simple_trainig() {
         .subsets_of_size(state.hand_size())
         .next()
         .unwrap();
-    let hidden_index = HiddenIndex::encode(&state, player, PerPhaseInfo::Main(hand));
-    let vector = scope
-        .get_explored()
-        .unwrap()
-        .matrices
-        .get_matrix(player)
-        .get_node(hidden_index)
-        .unwrap();
+    let hidden_index = HiddenIndex::encode(&state, player, PerPhaseInfo::Main(hand))
+        .unwrap_or_else(|error| exit_with(error.to_string()));
+    let explored = scope.get_explored().unwrap();
+    let vector = explored.node(player, hidden_index).unwrap();
 
-    println!("{:?}", vector.strategy_sum);
-    println!("{:?}", vector.regret_sum);
-    let strategy = vector.get_average_strategy();
+    println!("{:?}", vector.strategy_sums());
+    println!("{:?}", vector.regrets());
+    let strategy = explored.strategy_for(player, hidden_index).unwrap();
     for index in 0..vector.len() {
         let decision = DecisionIndex(index);
         let decoded = decision
@@ -156,57 +230,1769 @@ fn simple_trainig() {
     // }}}
 }
 // }}}
+// {{{ Estimate command
+/// Estimates the size of the tree generated starting from some turn
+/// (see `example_state`), without generating anything.
+///
+/// Usage: `estimate [from=<turn>] [--turns <max>]`
+///
+/// Only the configured number of turns gets estimated by default. Passing
+/// `--turns <max>` prints a table sweeping from one turn up to `max` turns
+/// (or until the end of the game, whichever comes first).
+fn estimate(args: &[String], solver: &SolverConfig) -> Result<(), String> {
+    let mut from = 0;
+    let mut sweep = None;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--turns" {
+            let value = args.next().ok_or("Missing value for --turns")?;
+            sweep = Some(
+                value
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid turn count {value:?}"))?,
+            );
+        } else if let Some(value) = arg.strip_prefix("from=") {
+            from = value
+                .parse()
+                .map_err(|_| format!("Invalid turn {value:?}"))?;
+        } else {
+            return Err(format!("Unknown argument {arg:?}"));
+        }
+    }
+
+    if from >= Battlefields::COUNT {
+        return Err(format!("Turn {from} is past the end of the game"));
+    }
+
+    let state = example_state(from);
+    let turns = match sweep {
+        Some(max) => 1..=max.min(Battlefields::COUNT - from),
+        None => solver.turns..=solver.turns,
+    };
+
+    println!(
+        "{:>5} {:>12} {:>12} {:>12} {:>14} {:>12} {:>12}",
+        "turns", "explored", "completed", "unexplored", "weights", "memory", "time"
+    );
+
+    for turns in turns {
+        let start = Instant::now();
+        let mut estimator = EstimationContext::new(turns, state);
+        if solver.regret_pruning_threshold.is_some() {
+            estimator = estimator.with_regret_pruning();
+        }
+
+        let stats = estimator.estimate();
+        let total = stats.total();
+
+        println!(
+            "{:>5} {:>12} {:>12} {:>12} {:>14} {:>12} {:>12}",
+            turns,
+            stats.explored_scopes,
+            stats.completed_scopes,
+            stats.unexplored_scopes,
+            total.total_weights,
+            HumanBytes(total.memory_estimate as u64).to_string(),
+            format!("{:.2?}", start.elapsed())
+        );
+    }
+
+    Ok(())
+}
+// }}}
+// {{{ Analyze command
+/// A position described on the command line as a list of `key=value` pairs.
+/// Values which differ for each player are written as `mine/yours`.
+///
+/// Example: `battlefields=Night,Glade,Urban,LastStrand turn=1 graveyard=Wall,Seer
+/// edicts=Gambit,Ambush,Sabotage,DivertAttention/Gambit,Ambush,Sabotage,RileThePublic
+/// hand=Rogue,Bard,Witch,Monarch`
+///
+/// Alternatively, the whole position can be given using
+/// the notation from `game::notation`: `position=<notation>`.
+///
+/// The `turns` and `iterations` keys override the respective solver settings.
+/// Passing `dot=<path>` additionally writes the solved tree to a graphviz file,
+/// while `telemetry=<path>` writes training statistics to a csv
+/// (or json lines, unless the path ends in `.csv`) file.
+/// Passing `weights=<path>` keeps the weights inside a memory-mapped file,
+/// resuming training if the file already exists (and was created for the
+/// same position and number of turns), while `blueprint=<path>`
+/// saves the trained strategies to a compressed blueprint file.
+/// Training can start from a previously saved blueprint using
+/// `warm_start=<path>[:<weight>]`, optionally purifying it with `purify=true`.
+struct AnalyzeArgs {
+    position: Option<(KnownState, SomePhase, Player, EncodingInfo)>,
+    state: KnownState,
+    hand: CreatureSet,
+    phase: PhaseTag,
+    played_edicts: Option<Pair<Edict>>,
+    choice: Option<CreatureSet>,
+    sabotage_choices: Pair<Option<Creature>>,
+    revealed: Option<Creature>,
+    solver: SolverConfig,
+    dot: Option<String>,
+    telemetry: Option<String>,
+    weights: Option<String>,
+    blueprint: Option<String>,
+    warm_start: Option<String>,
+    warm_start_options: WarmStart,
+}
+
+fn parse_list<T: FromStr<Err = EchoError>>(value: &str) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(|item| T::from_str(item).map_err(|error| error.to_string()))
+        .collect()
+}
+
+fn parse_pair(value: &str) -> Result<Pair<&str>, String> {
+    value
+        .split_once('/')
+        .map(|(mine, yours)| [mine, yours])
+        .ok_or_else(|| format!("Expected a value of the form mine/yours, got {value:?}"))
+}
+
+fn parse_set<B, T>(value: &str) -> Result<B, String>
+where
+    B: Bitfield<Element = T>,
+    T: FromStr<Err = EchoError> + Copy,
+{
+    let mut result = B::empty();
+
+    for element in parse_list::<T>(value)? {
+        if !result.has(element) {
+            result.insert(element);
+        }
+    }
+
+    Ok(result)
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid number {value:?} for {key}"))
+}
+
+impl AnalyzeArgs {
+    /// Puts together the position described by the individual key/value pairs.
+    fn to_position(&self) -> Result<(KnownState, SomePhase, Player, EncodingInfo), String> {
+        let played_edicts = || {
+            self.played_edicts
+                .ok_or("The played edicts are required after the main phase")
+        };
+
+        let choice = || {
+            self.choice
+                .ok_or("The creature choice is required after the main phase")
+        };
+
+        let (phase, hidden) = match self.phase {
+            PhaseTag::Main => (
+                PerPhase::Main(MainPhase::new()),
+                PerPhaseInfo::Main(self.hand),
+            ),
+            PhaseTag::Sabotage => (
+                PerPhase::Sabotage(SabotagePhase::new(played_edicts()?)),
+                PerPhaseInfo::Sabotage(self.hand, choice()?),
+            ),
+            PhaseTag::Seer => {
+                let revealed = self.revealed.ok_or(
+                    "The creature revealed by the opponent is required during the seer phase",
+                )?;
+
+                (
+                    PerPhase::Seer(SeerPhase::new(
+                        played_edicts()?,
+                        self.sabotage_choices,
+                        revealed,
+                    )),
+                    PerPhaseInfo::Seer(self.hand, choice()?, revealed),
+                )
+            }
+        };
+
+        self.state.validate().map_err(|error| error.to_string())?;
+        HiddenState::from_encoding_info(hidden)
+            .validate_against(&self.state)
+            .map_err(|error| error.to_string())?;
+
+        Ok((self.state, phase, Player::Me, hidden))
+    }
+
+    fn parse(args: &[String], solver: SolverConfig) -> Result<Self, String> {
+        let mut result = Self {
+            position: None,
+            state: KnownState::new_starting([Battlefield::Plains; Battlefields::COUNT]),
+            hand: CreatureSet::empty(),
+            phase: PhaseTag::Main,
+            played_edicts: None,
+            choice: None,
+            sabotage_choices: [None; 2],
+            revealed: None,
+            solver,
+            dot: None,
+            telemetry: None,
+            weights: None,
+            blueprint: None,
+            warm_start: None,
+            warm_start_options: WarmStart::default(),
+        };
+
+        for arg in args {
+            let (key, value) = arg
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got {arg:?}"))?;
+
+            match key {
+                "battlefields" => {
+                    result.state.battlefields.all =
+                        parse_list(value)?.try_into().map_err(|_| {
+                            format!("Expected exactly {} battlefields", Battlefields::COUNT)
+                        })?;
+                }
+                "turn" => result.state.battlefields.current = parse_number(key, value)?,
+                "graveyard" => result.state.graveyard = parse_set(value)?,
+                "score" => result.state.score = Score(parse_number(key, value)?),
+                "edicts" => {
+                    for (player_state, value) in result
+                        .state
+                        .player_states
+                        .iter_mut()
+                        .zip(parse_pair(value)?)
+                    {
+                        player_state.edicts = parse_set::<EdictSet, _>(value)?;
+                    }
+                }
+                "effects" => {
+                    for (player_state, value) in result
+                        .state
+                        .player_states
+                        .iter_mut()
+                        .zip(parse_pair(value)?)
+                    {
+                        player_state.effects = parse_set::<StatusEffectSet, _>(value)?;
+                    }
+                }
+                "hand" => result.hand = parse_set(value)?,
+                "phase" => {
+                    result.phase = match value {
+                        "main" => PhaseTag::Main,
+                        "sabotage" => PhaseTag::Sabotage,
+                        "seer" => PhaseTag::Seer,
+                        _ => return Err(format!("Unknown phase {value:?}")),
+                    }
+                }
+                "played" => {
+                    let [mine, yours] = parse_pair(value)?;
+                    let parse_edict =
+                        |value: &str| value.parse().map_err(|error: EchoError| error.to_string());
+                    result.played_edicts = Some([parse_edict(mine)?, parse_edict(yours)?]);
+                }
+                "choice" => result.choice = Some(parse_set(value)?),
+                "sabotage" => {
+                    let parse_guess = |value: &str| match value {
+                        "-" => Ok(None),
+                        value => Creature::from_str(value)
+                            .map(Some)
+                            .map_err(|error| error.to_string()),
+                    };
+
+                    let [mine, yours] = parse_pair(value)?;
+                    result.sabotage_choices = [parse_guess(mine)?, parse_guess(yours)?];
+                }
+                "revealed" => {
+                    result.revealed =
+                        Some(Creature::from_str(value).map_err(|error| error.to_string())?)
+                }
+                "position" => {
+                    result.position =
+                        Some(notation::from_notation(value).map_err(|error| error.to_string())?)
+                }
+                "turns" => result.solver.turns = parse_number(key, value)?,
+                "iterations" => result.solver.iterations = parse_number(key, value)?,
+                "dot" => result.dot = Some(value.to_string()),
+                "telemetry" => result.telemetry = Some(value.to_string()),
+                "weights" => result.weights = Some(value.to_string()),
+                "blueprint" => result.blueprint = Some(value.to_string()),
+                "warm_start" => {
+                    let (path, weight) = match value.rsplit_once(':') {
+                        Some((path, weight)) => (path, parse_number("weight", weight)?),
+                        None => (value, WarmStart::DEFAULT_WEIGHT),
+                    };
+
+                    result.warm_start = Some(path.to_string());
+                    result.warm_start_options.weight = weight;
+                }
+                "purify" => {
+                    result.warm_start_options.purify = value
+                        .parse()
+                        .map_err(|_| format!("Invalid boolean {value:?} for {key}"))?;
+                }
+                _ => return Err(format!("Unknown key {key:?}")),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Solves the subgame starting at the beginning of the given turn, and prints the
+/// strategy the first player should use in the described position.
+fn analyze(args: &[String], solver: SolverConfig, rules: Ruleset) -> Result<(), String> {
+    let args = AnalyzeArgs::parse(args, solver)?;
+    let (mut state, phase, player, hidden) = match args.position {
+        Some(position) => position,
+        None => args.to_position()?,
+    };
+
+    state.rules = rules;
+
+    // {{{ Validation
+    let (hand, choice) = hidden.get_pre_seer();
+
+    if hand.len() != state.hand_size() {
+        return Err(format!(
+            "The hand must contain {} creatures",
+            state.hand_size()
+        ));
+    }
+
+    if let Some(choice) = choice {
+        if choice.len() != state.creature_choice_size(player)
+            || (choice & !hand) != CreatureSet::empty()
+        {
+            return Err(format!(
+                "The choice must contain {} creatures from the hand",
+                state.creature_choice_size(player)
+            ));
+        }
+    }
+    // }}}
+    // {{{ Locate the position
+    // Trees are always generated starting from the main phase,
+    // so we need to know what got revealed in-between.
+    let mut reveals = vec![];
+
+    if let PerPhase::Sabotage(SabotagePhase { edict_choices })
+    | PerPhase::Seer(SeerPhase { edict_choices, .. }) = phase
+    {
+        let reveal = RevealIndex::encode_main_phase_reveal(edict_choices, state.edict_sets())
+            .map_err(|_| "The played edicts must be in the respective player's hand")?;
+
+        reveals.push(reveal);
+    }
+
+    if let PerPhase::Seer(seer) = phase {
+        let reveal = RevealIndex::encode_sabotage_phase_reveal(
+            seer.sabotage_choices,
+            state.last_creature_revealer(),
+            seer.revealed_creature,
+            state.graveyard,
+        )
+        .map_err(|_| "Invalid sabotage choices / revealed creature")?;
+
+        reveals.push(reveal);
+    }
+    // }}}
+    // {{{ Solving
+    let start = Instant::now();
+    let allocator = args.solver.allocator();
+
+    // Weights can optionally live inside a file, in which case
+    // training continues from wherever the previous run left off.
+    let mapped = match &args.weights {
+        Some(path) => {
+            let weight_count = EstimationContext::new(args.solver.turns, state)
+                .estimate()
+                .total()
+                .total_weights;
+
+            let mapped = if Path::new(path).exists() {
+                MappedStorage::open(path, &state, weight_count)
+            } else {
+                MappedStorage::create(path, &state, weight_count)
+            };
+
+            Some(mapped.map_err(|error| error.to_string())?)
+        }
+        None => None,
+    };
+
+    let transpositions = TranspositionTable::new();
+    let budget = args.solver.memory_budget();
+    let mut generator =
+        args.solver
+            .generation_context(state, &allocator, &transpositions, budget.as_ref());
+    if let Some(mapped) = &mapped {
+        generator = generator.with_weight_storage(WeightStorage::Mapped(mapped));
+    }
+
+    let mut arenas = args.solver.arenas();
+    let mut scope = generator.generate_parallel(&mut arenas);
+    generator
+        .check_weights()
+        .map_err(|error| error.to_string())?;
+
+    if args.solver.transpositions {
+        println!(
+            "Generated {} distinct scopes ({} transpositions)",
+            transpositions.len(),
+            transpositions.hits()
+        );
+    }
+
+    let mut trainer = args
+        .solver
+        .training_context()
+        .map_err(|error| error.to_string())?;
+
+    if let Some(path) = &args.warm_start {
+        let initialized = std::fs::File::open(path)
+            .and_then(|file| BlueprintReader::new(std::io::BufReader::new(file)))
+            .and_then(|mut blueprint| {
+                trainer.warm_start(&scope, &mut blueprint, args.warm_start_options)
+            })
+            .map_err(|error| format!("Failed to warm start from {path:?}: {error}"))?;
+
+        println!("Warm started {initialized} scopes from {path:?}");
+    }
+
+    if let Some(path) = args.telemetry {
+        let format = if path.ends_with(".csv") {
+            TelemetryFormat::Csv
+        } else {
+            TelemetryFormat::JsonLines
+        };
+
+        let file = std::fs::File::create(&path)
+            .map_err(|error| format!("Failed to create {path:?}: {error}"))?;
+
+        trainer = trainer.with_telemetry(Telemetry::new(format, std::io::BufWriter::new(file)));
+    }
+
+    args.solver
+        .train(&mut trainer, &mut scope, state.to_summary());
+    println!("Solved in {:?}", start.elapsed());
+
+    if let Some(mapped) = &mapped {
+        // Lazily expanded scopes allocate their weights while training
+        mapped.check().map_err(|error| error.to_string())?;
+        mapped
+            .flush()
+            .map_err(|error| format!("Failed to flush {:?}: {error}", args.weights))?;
+    }
+
+    if let Some(path) = args.dot {
+        std::fs::write(&path, scope.to_dot())
+            .map_err(|error| format!("Failed to write {path:?}: {error}"))?;
+    }
+
+    if let Some(path) = args.blueprint {
+        std::fs::File::create(&path)
+            .and_then(|file| write_blueprint(&scope, &state, 0, std::io::BufWriter::new(file)))
+            .map_err(|error| format!("Failed to write {path:?}: {error}"))?;
+    }
+    // }}}
+    // {{{ Displaying
+    let strategy = scope
+        .descend_path(&reveals)
+        .and_then(Scope::get_explored)
+        .and_then(|scope| {
+            let index = HiddenIndex::encode(&state, player, hidden).ok()?;
+            scope.strategy_for(player, index)
+        })
+        .ok_or("The position is not part of the explored tree")?;
+
+    let mut entries: Vec<_> = strategy.into_iter().enumerate().collect();
+    entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (index, probability) in entries {
+        let description = DecisionIndex(index).describe(&state, &phase, player, hidden);
+
+        println!("{:>6.2}% {description}", probability * 100.0);
+    }
+    // }}}
+
+    Ok(())
+}
+// }}}
+// {{{ Simulate command
+/// Options for running a batch of games between two agents.
+///
+/// Example: `--games 100 --agent-a random --agent-b opponent_model --seed 7 --records games`
+///
+/// Games get saved to the match database from the config (if any),
+/// which can be overridden using `--database <path>`.
+///
+/// Agents are either names from the roster in the config, or agent kinds.
+/// Hands can be fixed using `--deal <mine>/<yours>` (comma separated creatures),
+/// and `--mirror` makes every other game swap the hands of the previous one.
+struct SimulateArgs {
+    games: usize,
+    agents: Pair<String>,
+
+    /// The seed the entire run derives from. Random (and printed) when missing.
+    seed: Option<u64>,
+
+    /// The hands every game gets played with. Random when missing.
+    deal: Option<Pair<CreatureSet>>,
+
+    /// Whether to play every deal twice, with the players swapping hands.
+    mirror: bool,
+
+    /// Directory to write the game records to.
+    records: Option<PathBuf>,
+
+    /// Match database to save the games to.
+    database: Option<PathBuf>,
+}
+
+impl SimulateArgs {
+    fn parse(args: &[String], config: &Config) -> Result<Self, String> {
+        let mut result = Self {
+            games: 100,
+            agents: ["random".to_string(), "random".to_string()],
+            seed: None,
+            deal: None,
+            mirror: false,
+            records: None,
+            database: config.database.clone(),
+        };
+
+        let mut args = args.iter();
+
+        while let Some(key) = args.next() {
+            if key == "--mirror" {
+                result.mirror = true;
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {key}"))?;
+
+            match key.as_str() {
+                "--games" => result.games = parse_number(key, value)?,
+                "--agent-a" => result.agents[0] = value.clone(),
+                "--agent-b" => result.agents[1] = value.clone(),
+                "--seed" => result.seed = Some(parse_number(key, value)?),
+                "--deal" => {
+                    let [mine, yours] = parse_pair(value)?;
+                    result.deal = Some([parse_set(mine)?, parse_set(yours)?]);
+                }
+                "--records" => result.records = Some(PathBuf::from(value)),
+                "--database" => result.database = Some(PathBuf::from(value)),
+                _ => return Err(format!("Unknown option {key:?}")),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Creates the agent some name refers to. Agents which need
+/// a tree (i.e. best response agents) generate it inside the allocator.
+fn create_agent<'a>(
+    config: &Config,
+    name: &str,
+    seed: u64,
+    allocator: &'a Bump,
+) -> Result<Box<dyn EchoAgent + 'a>, String> {
+    let (kind, seed, blueprint, opening_book, network, depth) = match config.agent(name) {
+        Some(agent) => (
+            agent.kind,
+            agent.seed.unwrap_or(seed),
+            agent.blueprint.as_ref(),
+            agent.opening_book.as_ref(),
+            agent.network.as_ref(),
+            agent.depth,
+        ),
+        None => {
+            let kind = AgentKind::deserialize(toml::Value::String(name.to_string()))
+                .map_err(|_| format!("Unknown agent {name:?}"))?;
+
+            (kind, seed, None, None, None, None)
+        }
+    };
+
+    let agent: Box<dyn EchoAgent + 'a> = match kind {
+        AgentKind::Random => Box::new(RandomAgent::new(StdRng::seed_from_u64(seed))),
+        AgentKind::AlwaysZero => Box::new(AlwaysZeroAgent::default()),
+        AgentKind::OpponentModel => {
+            let depth = depth.unwrap_or(OpponentModelAgent::DEFAULT_DEPTH);
+            if depth == 0 {
+                return Err(format!("Agent {name:?} must look at least one turn ahead"));
+            }
+
+            Box::new(OpponentModelAgent::new().with_depth(depth))
+        }
+        AgentKind::Blueprint => {
+            let path = blueprint
+                .ok_or_else(|| format!("Agent {name:?} does not specify a blueprint file"))?;
+
+            Box::new(StrategyAgent::new(
+                open_blueprint(path)?,
+                StdRng::seed_from_u64(seed),
+            ))
+        }
+        AgentKind::BestResponse => {
+            let path = blueprint
+                .ok_or_else(|| format!("Agent {name:?} does not specify a blueprint file"))?;
+
+            let reader = std::fs::File::open(path)
+                .and_then(|file| BlueprintReader::new(std::io::BufReader::new(file)))
+                .map_err(|error| format!("Failed to load blueprint {path:?}: {error}"))?;
+
+            // Games always start from the beginning, and the
+            // response only makes sense from the root of the tree.
+            if reader.root_state() != KnownState::new_with_rules(BATTLEFIELDS, config.rules) {
+                return Err(format!(
+                    "The blueprint of agent {name:?} was not trained from the start of the game"
+                ));
+            }
+
+            let reader = allocator.alloc(reader);
+            let agent = BestResponseAgent::from_blueprint(reader, config.solver.turns, allocator)
+                .map_err(|error| format!("Failed to create agent {name:?}: {error}"))?;
+
+            Box::new(agent)
+        }
+        AgentKind::OpeningBook => {
+            let path = opening_book
+                .ok_or_else(|| format!("Agent {name:?} does not specify an opening book"))?;
+
+            let book: OpeningBook = std::fs::read_to_string(path)
+                .map_err(|error| format!("Failed to read {path:?}: {error}"))?
+                .parse()
+                .map_err(|error| format!("Invalid opening book {path:?}: {error}"))?;
+
+            Box::new(StrategyAgent::new(book, StdRng::seed_from_u64(seed)))
+        }
+        #[cfg(feature = "deep-cfr")]
+        AgentKind::Deep => {
+            let path =
+                network.ok_or_else(|| format!("Agent {name:?} does not specify a network"))?;
+
+            let network = DecisionNetwork::load(path)
+                .map_err(|error| format!("Failed to load network {path:?}: {error}"))?;
+
+            Box::new(StrategyAgent::new(network, StdRng::seed_from_u64(seed)))
+        }
+        #[cfg(not(feature = "deep-cfr"))]
+        AgentKind::Deep => {
+            let _ = network;
+            return Err("Deep agents require the deep-cfr feature".to_string());
+        }
+    };
+
+    Ok(agent)
+}
+
+/// Opens the blueprint at the given path. Public states only get loaded once reached.
+fn open_blueprint(
+    path: &Path,
+) -> Result<BlueprintStrategyProvider<std::io::BufReader<std::fs::File>>, String> {
+    std::fs::File::open(path)
+        .and_then(|file| BlueprintReader::new(std::io::BufReader::new(file)))
+        .and_then(BlueprintStrategyProvider::new)
+        .map_err(|error| format!("Failed to load blueprint {path:?}: {error}"))
+}
+
+fn simulate(args: &[String], config: &Config) -> Result<(), String> {
+    let args = SimulateArgs::parse(args, config)?;
+
+    // Everything random (agents, deals and runners) derives from a single
+    // seed, which gets printed such that any run can be reproduced exactly.
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    println!("Simulating with seed {seed}");
+
+    let allocator = config.solver.allocator();
+    let mut agent_a = create_agent(config, &args.agents[0], rng.gen(), &allocator)?;
+    let mut agent_b = create_agent(config, &args.agents[1], rng.gen(), &allocator)?;
+
+    if let Some(directory) = &args.records {
+        std::fs::create_dir_all(directory)
+            .map_err(|error| format!("Failed to create {directory:?}: {error}"))?;
+    }
+
+    #[cfg(feature = "database")]
+    let database = match &args.database {
+        Some(path) => Some(Rc::new(open_database(path)?)),
+        None => None,
+    };
+
+    #[cfg(not(feature = "database"))]
+    if args.database.is_some() {
+        return Err("Saving games requires the database feature".to_string());
+    }
+
+    // {{{ Running the games
+    let state = KnownState::new_with_rules(BATTLEFIELDS, config.rules);
+    let main_phase = MainPhase::new();
+    let deals: Vec<_> = match args.deal {
+        Some(hands) => vec![
+            KnownState::new_dealt(BATTLEFIELDS, config.rules, hands)
+                .map_err(|error| error.to_string())?
+                .1,
+        ],
+        None => main_phase.valid_hidden_states(state.to_summary()).collect(),
+    };
+
+    let start = Instant::now();
+    let mut results = [0; 3];
+    let mut total_score = 0;
+    let mut agent_stats = [AgentStats::default(); 2];
+    let mut hidden_state = deals[0];
+
+    for game in 0..args.games {
+        hidden_state = if args.mirror && game % 2 == 1 {
+            let [mine, yours] = hidden_state;
+            [yours, mine]
+        } else {
+            deals[rng.gen_range(0..deals.len())]
+        };
+
+        let agents = (&mut *agent_a, &mut *agent_b);
+        let mut runner = EchoRunner::new(state, PerPhase::Main(main_phase), agents, hidden_state)
+            .with_seed(rng.gen());
+
+        if args.records.is_some() || args.database.is_some() {
+            let record = GameRecord::new(BATTLEFIELDS, Some(seed), args.agents.clone());
+
+            runner = match &args.records {
+                Some(directory) => {
+                    let path = directory.join(format!("game_{game:04}.txt"));
+                    let file = std::fs::File::create(&path)
+                        .map_err(|error| format!("Failed to create {path:?}: {error}"))?;
+
+                    runner.record_to(record, file)
+                }
+                None => runner.record_to(record, std::io::sink()),
+            };
+        }
+
+        #[cfg(feature = "database")]
+        if let Some(database) = &database {
+            runner = runner.also_record_with(database_sink(database.clone()));
+        }
+
+        let transcript = runner
+            .run_game_with_transcript()
+            .map_err(|error| format!("Game {game} did not finish properly: {error}"))?;
+        let score = transcript
+            .result
+            .expect("Finished games always have a result");
+
+        for (total, stats) in agent_stats.iter_mut().zip(&transcript.agent_stats()) {
+            total.merge(stats);
+        }
+
+        results[score.to_battle_result() as usize] += 1;
+        total_score += score.0 as i64;
+    }
+    // }}}
+    // {{{ Reporting
+    let [losses, ties, wins] = results;
+    let games = args.games.max(1) as f32;
+
+    println!("Played {} games in {:?}", args.games, start.elapsed());
+    println!(
+        "{} vs {}: {wins} wins ({:.1}%), {losses} losses ({:.1}%), {ties} ties ({:.1}%)",
+        args.agents[0],
+        args.agents[1],
+        100.0 * wins as f32 / games,
+        100.0 * losses as f32 / games,
+        100.0 * ties as f32 / games,
+    );
+    println!("Average score delta: {:+.2}", total_score as f32 / games);
+
+    for (name, stats) in args.agents.iter().zip(agent_stats) {
+        print!(
+            "{name}: {:?} per decision (at most {:?})",
+            stats.average_time(),
+            stats.max_time
+        );
+
+        match stats.nodes_per_decision() {
+            Some(nodes) => println!(", {nodes:.0} nodes per decision"),
+            None => println!(),
+        }
+    }
+    // }}}
+
+    Ok(())
+}
+// }}}
+// {{{ Explain command
+/// Replays a recorded game, explaining how every battle got resolved.
+///
+/// Usage: `explain <record>`
+fn explain(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err("Usage: explain <record>".to_string());
+    };
+
+    let source = std::fs::read_to_string(path)
+        .map_err(|error| format!("Failed to read {path:?}: {error}"))?;
+    let record = GameRecord::from_str(&source).map_err(|error| error.to_string())?;
+    let replay = record.replay().map_err(|error| error.to_string())?;
+
+    for (index, (turn, events)) in record.turns.iter().zip(&replay.events).enumerate() {
+        println!("{}. {turn}", index + 1);
+
+        for event in events {
+            println!("    {event}");
+        }
+    }
+
+    if let Some(score) = replay.final_score {
+        println!("Final score: {:+}", score.0);
+    }
+
+    Ok(())
+}
+// }}}
+// {{{ Check indices command
+/// Decodes and re-encodes every index which can occur in the given
+/// state summary, reporting everything which does not round-trip.
+///
+/// Usage: `check-indices [graveyard=<creatures>] [edicts=<mine>/<yours>] [seer=me|you|-]`
+///
+/// Edicts default to the full set, the graveyard defaults to being empty,
+/// and nobody has the seer effect unless specified otherwise.
+fn check_indices(args: &[String]) -> Result<(), String> {
+    let mut state = KnownStateSummary::new([EdictSet::all(); 2], CreatureSet::empty(), None);
+
+    for arg in args {
+        let (key, value) = arg
+            .split_once('=')
+            .ok_or_else(|| format!("Expected key=value, got {arg:?}"))?;
+
+        match key {
+            "graveyard" => state.graveyard = parse_set(value)?,
+            "edicts" => {
+                for (edicts, value) in state.edict_sets.iter_mut().zip(parse_pair(value)?) {
+                    *edicts = parse_set::<EdictSet, _>(value)?;
+                }
+            }
+            "seer" => {
+                state.seer_player = match value {
+                    "me" => Some(Player::Me),
+                    "you" => Some(Player::You),
+                    "-" => None,
+                    _ => return Err(format!("Unknown player {value:?}")),
+                }
+            }
+            _ => return Err(format!("Unknown key {key:?}")),
+        }
+    }
+
+    if state.hand_size() > (!state.graveyard).len() {
+        return Err("The graveyard contains too many creatures".to_string());
+    }
+
+    let report = index_check::check_indices(&state);
+
+    for error in &report.errors {
+        println!("{error}");
+    }
+
+    println!(
+        "Checked {} indices, {} of which failed to round-trip",
+        report.checked,
+        report.errors.len()
+    );
+
+    if report.errors.is_empty() {
+        Ok(())
+    } else {
+        Err("Some indices failed to round-trip".to_string())
+    }
+}
+// }}}
+// {{{ Merge blueprints command
+/// Averages blueprints trained on the same game (e.g. using different seeds),
+/// which smooths out some of the noise introduced by sampling.
+///
+/// Usage: `merge-blueprints <output> <blueprint>[:<weight>]...`
+///
+/// Weights default to one, and only matter relative to each other.
+fn merge_blueprints(args: &[String]) -> Result<(), String> {
+    let [output, inputs @ ..] = args else {
+        return Err("Usage: merge-blueprints <output> <blueprint>[:<weight>]...".to_string());
+    };
+
+    if inputs.is_empty() {
+        return Err("Expected at least one blueprint to merge".to_string());
+    }
+
+    let mut readers = vec![];
+    for input in inputs {
+        let (path, weight) = match input.rsplit_once(':') {
+            Some((path, weight)) => (path, parse_number("weight", weight)?),
+            None => (input.as_str(), 1.0),
+        };
+
+        let reader = std::fs::File::open(path)
+            .and_then(|file| BlueprintReader::new(std::io::BufReader::new(file)))
+            .map_err(|error| format!("Failed to load blueprint {path:?}: {error}"))?;
+
+        readers.push((reader, weight));
+    }
+
+    let written = std::fs::File::create(output)
+        .and_then(|file| {
+            blueprint::merge_blueprints(&mut readers, 0, std::io::BufWriter::new(file))
+        })
+        .map_err(|error| format!("Failed to merge blueprints into {output:?}: {error}"))?;
+
+    println!(
+        "Merged {} blueprints into {output:?} ({written} public states)",
+        readers.len()
+    );
+
+    Ok(())
+}
+// }}}
+// {{{ Opening book command
+/// Extracts the strategies for the first decision of the game from a blueprint
+/// trained from the start of the game (see `cfr::opening_book`).
+///
+/// Usage: `opening-book <blueprint> <output> <battlefields>`
+///
+/// Blueprints do not remember the battlefields they were trained on,
+/// so these must be passed explicitly (eg: `Night,Glade,Urban,LastStrand`).
+fn opening_book(args: &[String]) -> Result<(), String> {
+    let [blueprint, output, battlefields] = args else {
+        return Err("Usage: opening-book <blueprint> <output> <battlefields>".to_string());
+    };
+
+    let battlefields = parse_list(battlefields)?
+        .try_into()
+        .map_err(|_| format!("Expected exactly {} battlefields", Battlefields::COUNT))?;
+
+    let root = std::fs::File::open(blueprint)
+        .and_then(|file| BlueprintReader::new(std::io::BufReader::new(file)))
+        .and_then(|mut reader| reader.load(BlockId::ROOT))
+        .map_err(|error| format!("Failed to load blueprint {blueprint:?}: {error}"))?;
+
+    let book =
+        OpeningBook::from_blueprint(&root, battlefields).map_err(|error| error.to_string())?;
+
+    std::fs::write(output, book.to_string())
+        .map_err(|error| format!("Failed to write {output:?}: {error}"))?;
+
+    println!(
+        "Saved the openings for {} starting hands to {output:?}",
+        book.lines[0].len()
+    );
+
+    Ok(())
+}
+// }}}
+// {{{ Deep cfr command
+/// Trains both players from the start of the game using deep cfr
+/// (see `cfr::deep`), saving the average strategy network.
+///
+/// Usage: `deep-train <output> [<key> <value>]...`
+///
+/// The keys are `iterations`, `traversals`, `width`,
+/// `reservoir`, `hidden`, `steps`, `batch` and `learning-rate`.
+#[cfg(feature = "deep-cfr")]
+fn deep_train(args: &[String], config: &Config) -> Result<(), String> {
+    let [output, options @ ..] = args else {
+        return Err("Usage: deep-train <output> [<key> <value>]...".to_string());
+    };
+
+    let mut deep = DeepCfrConfig::default();
+
+    for pair in options.chunks(2) {
+        let [key, value] = pair else {
+            return Err(format!("Missing value for {}", pair[0]));
+        };
+
+        match key.as_str() {
+            "iterations" => deep.iterations = parse_number(key, value)?,
+            "traversals" => deep.traversals = parse_number(key, value)?,
+            "width" => deep.exploration_width = Some(parse_number(key, value)?),
+            "reservoir" => deep.reservoir_capacity = parse_number(key, value)?,
+            "hidden" => deep.hidden_size = parse_number(key, value)?,
+            "steps" => deep.training_steps = parse_number(key, value)?,
+            "batch" => deep.batch_size = parse_number(key, value)?,
+            "learning-rate" => deep.learning_rate = parse_number(key, value)?,
+            _ => return Err(format!("Unknown option {key:?}")),
+        }
+    }
+
+    let mut rng = match config.solver.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let state = KnownState::new_with_rules(BATTLEFIELDS, config.rules);
+    let network = DeepCfrContext::new(deep)
+        .train(state, &mut rng)
+        .map_err(|error| format!("Training failed: {error}"))?;
+
+    network
+        .save(output)
+        .map_err(|error| format!("Failed to save the network to {output:?}: {error}"))?;
+
+    println!("Saved the average strategy network to {output:?}");
+
+    Ok(())
+}
+// }}}
+// {{{ Match database
+#[cfg(feature = "database")]
+fn open_database(path: &Path) -> Result<MatchDatabase, String> {
+    MatchDatabase::open(path).map_err(|error| format!("Failed to open {path:?}: {error}"))
+}
+
+/// Saves every record handed to it into the given database.
+#[cfg(feature = "database")]
+fn database_sink(database: Rc<MatchDatabase>) -> impl FnMut(&GameRecord) {
+    move |record| {
+        if let Err(error) = database.insert(record) {
+            tracing::event!(Level::WARN, "Failed to save game: {error}");
+        }
+    }
+}
+
+/// Saves the game to the match database at the given path (if any) once it is over.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_to_database<A: EchoAgent, B: EchoAgent>(
+    runner: EchoRunner<A, B>,
+    path: Option<&Path>,
+) -> EchoRunner<A, B> {
+    let Some(path) = path else {
+        return runner;
+    };
+
+    #[cfg(feature = "database")]
+    match open_database(path) {
+        Ok(database) => return runner.also_record_with(database_sink(Rc::new(database))),
+        Err(error) => tracing::event!(Level::WARN, "{error}"),
+    }
+
+    #[cfg(not(feature = "database"))]
+    tracing::event!(
+        Level::WARN,
+        "Cannot save games to {path:?} without the database feature"
+    );
+
+    runner
+}
+
+/// Prints the results of some agent saved in the match database, day by day.
+///
+/// Usage: `stats <agent> [opponent]`
+#[cfg(feature = "database")]
+fn stats(args: &[String], config: &Config) -> Result<(), String> {
+    let (agent, opponent) = match args {
+        [agent] => (agent, None),
+        [agent, opponent] => (agent, Some(opponent.as_str())),
+        _ => return Err("Usage: stats <agent> [opponent]".to_string()),
+    };
+
+    let path = config
+        .database
+        .as_ref()
+        .ok_or("No match database configured (see the database key of the config)")?;
+
+    let database = open_database(path)?;
+    let days = database
+        .win_rate_over_time(agent, opponent)
+        .map_err(|error| error.to_string())?;
+    let total = database
+        .win_rate(agent, opponent)
+        .map_err(|error| error.to_string())?;
+
+    for (day, win_rate) in days {
+        print_win_rate(&day, win_rate);
+    }
+
+    print_win_rate("total", total);
+
+    Ok(())
+}
+
+#[cfg(feature = "database")]
+fn print_win_rate(label: &str, win_rate: echo::database::WinRate) {
+    println!(
+        "{label:>10}: {:>5} games, {} wins ({:.1}%), {} ties, {} losses",
+        win_rate.games(),
+        win_rate.wins,
+        100.0 * win_rate.rate(),
+        win_rate.ties,
+        win_rate.losses,
+    );
+}
+// }}}
 // {{{ Simple gui routine
-fn show_gui() {
-    let (human_agent, bus) = HumanAgent::create();
-
-    let handle = thread::spawn(|| {
-        let random_agent = RandomAgent::new(thread_rng());
-        let always_zero_agent = AlwaysZeroAgent::default();
-        let opponent_agent = random_agent;
-
-        let battlefields = [
-            Battlefield::Night,
-            Battlefield::Glade,
-            Battlefield::Urban,
-            Battlefield::LastStrand,
-        ];
-
-        let state = KnownState::new_starting(battlefields);
-        let main_phase = echo::cfr::phase::MainPhase::new();
-        let phase = echo::cfr::phase::PerPhase::Main(main_phase);
-        let agents = (human_agent, opponent_agent);
-        let hidden_state = main_phase
-            .valid_hidden_states(state.to_summary())
-            .next()
-            .unwrap();
-        let runner = EchoRunner::new(state, phase, agents, hidden_state);
-        let result = runner.run_game();
+/// The battlefields every game is played on.
+const BATTLEFIELDS: [Battlefield; Battlefields::COUNT] = Battlefields::last_of([
+    Battlefield::Night,
+    Battlefield::Glade,
+    Battlefield::Urban,
+    Battlefield::LastStrand,
+]);
+
+/// How the games started from the gui get played.
+#[cfg(feature = "gui")]
+#[derive(Debug, Clone, Copy)]
+struct GameSetup {
+    rules: Ruleset,
+
+    /// The deal and everything else random about the game derive from this.
+    seed: u64,
+    clock: Option<TimeControl>,
+}
+
+/// Sets up a game between the human and some opponent, dealing
+/// the hands of both players at random (see `GameSetup`).
+///
+/// A record of the game gets printed to stdout once the game is over.
+#[cfg(feature = "gui")]
+fn new_game<B: EchoAgent>(
+    human_agent: HumanAgent,
+    opponent_agent: B,
+    opponent_name: &'static str,
+    setup: GameSetup,
+) -> EchoRunner<HumanAgent, B> {
+    let mut rng = StdRng::seed_from_u64(setup.seed);
+    let state = KnownState::new_with_rules(BATTLEFIELDS, setup.rules);
+    let main_phase = echo::cfr::phase::MainPhase::new();
+    let phase = echo::cfr::phase::PerPhase::Main(main_phase);
+    let cancellation = human_agent.cancellation();
+    let agents = (human_agent, opponent_agent);
+    let deals: Vec<_> = main_phase.valid_hidden_states(state.to_summary()).collect();
+    let hidden_state = deals[rng.gen_range(0..deals.len())];
+    let record = GameRecord::new(
+        BATTLEFIELDS,
+        Some(setup.seed),
+        ["human".to_string(), opponent_name.to_string()],
+    );
+
+    // Closing the window (or starting another game) stops this one.
+    let runner = EchoRunner::new(state, phase, agents, hidden_state)
+        .with_seed(rng.gen())
+        .with_cancellation(cancellation)
+        .record_to(record, std::io::stdout());
+
+    match setup.clock {
+        Some(control) => runner.with_clock(control),
+        None => runner,
+    }
+}
+
+/// Runs a game between the human and some opponent on a separate thread.
+/// The opponent is handed back once the game is over,
+/// such that it can carry over whatever it learned.
+#[cfg(all(not(target_arch = "wasm32"), feature = "gui"))]
+fn spawn_game<B: EchoAgent + Send + 'static>(
+    human_agent: HumanAgent,
+    mut opponent_agent: B,
+    opponent_name: &'static str,
+    setup: GameSetup,
+    database: Option<PathBuf>,
+) -> JoinHandle<B> {
+    thread::spawn(move || {
+        let runner = new_game(human_agent, &mut opponent_agent, opponent_name, setup);
+        let result = save_to_database(runner, database.as_deref()).run_game();
         println!("{result:?}");
-    });
 
+        opponent_agent
+    })
+}
+
+/// Runs a game between the human and some opponent on the ui thread,
+/// one step every frame. The opponent gets handed to `on_finished`
+/// once the game is over.
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
+fn drive_game<B: EchoAgent + 'static>(
+    human_agent: HumanAgent,
+    opponent_agent: B,
+    opponent_name: &'static str,
+    setup: GameSetup,
+    on_finished: impl FnOnce(B) + 'static,
+) -> GameDriver {
+    let mut runner = Some(new_game(human_agent, opponent_agent, opponent_name, setup));
+    let mut on_finished = Some(on_finished);
+
+    Box::new(move || {
+        let Some(game) = &mut runner else {
+            return false;
+        };
+
+        match game.step() {
+            Ok(None) => true,
+            result => {
+                tracing::event!(Level::INFO, "Game over: {result:?}");
+
+                let (_, opponent_agent) = runner.take().unwrap().into_agents();
+                if let Some(on_finished) = on_finished.take() {
+                    on_finished(opponent_agent);
+                }
+
+                false
+            }
+        }
+    })
+}
+
+/// Starts games against the opponent picked on the start screen,
+/// saving them to the given match database (if any). Games against
+/// the blueprint opponent require the path of a blueprint.
+///
+/// Games are played using the configured rules, with deals derived from
+/// the configured seed (or from entropy). The seed gets logged, such that
+/// the sequence of games can be replayed.
+#[cfg(all(not(target_arch = "wasm32"), feature = "gui"))]
+fn game_launcher(config: &Config, blueprint: Option<PathBuf>) -> GameLauncher {
+    let database = config.database.clone();
+    let rules = config.rules;
+    let seed = config.seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    tracing::event!(Level::INFO, "Dealing games with seed {seed}");
+
+    // The opponent model agent learns across games,
+    // so we keep it around in-between rematches.
+    let mut model_agent = Some(OpponentModelAgent::new());
+    let mut model_game: Option<JoinHandle<OpponentModelAgent>> = None;
+
+    Box::new(move |opponent, clock| {
+        let (human_agent, bus) = HumanAgent::create();
+        let setup = GameSetup {
+            rules,
+            seed: rng.gen(),
+            clock,
+        };
+
+        match opponent {
+            OpponentKind::Random => {
+                spawn_game(
+                    human_agent,
+                    RandomAgent::new(StdRng::seed_from_u64(rng.gen())),
+                    "random",
+                    setup,
+                    database.clone(),
+                );
+            }
+            OpponentKind::OpponentModel => {
+                if let Some(handle) = model_game.take() {
+                    model_agent = handle.join().ok();
+                }
+
+                let agent = model_agent.take().unwrap_or_default();
+                model_game = Some(spawn_game(
+                    human_agent,
+                    agent,
+                    "opponent_model",
+                    setup,
+                    database.clone(),
+                ));
+            }
+            OpponentKind::Blueprint => {
+                // Every game reads the blueprint from the start, which only
+                // decompresses the public states the game actually reaches.
+                let provider = open_blueprint(blueprint.as_deref()?)
+                    .map_err(|error| tracing::event!(Level::ERROR, "{error}"))
+                    .ok()?;
+
+                spawn_game(
+                    human_agent,
+                    StrategyAgent::new(provider, StdRng::seed_from_u64(rng.gen())),
+                    "blueprint",
+                    setup,
+                    database.clone(),
+                );
+            }
+        }
+
+        Some(bus)
+    })
+}
+
+/// Starts games against the opponent picked on the start screen.
+/// Browsers give us no threads, so the gui runs the games itself.
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
+fn game_launcher() -> GameLauncher {
+    // The opponent model agent learns across games, so it gets
+    // handed back here whenever a game against it is over.
+    let model_agent: Rc<Cell<Option<OpponentModelAgent>>> = Default::default();
+
+    // There is no config on the web, so we play by the default rules.
+    let mut rng = StdRng::from_entropy();
+
+    Box::new(move |opponent, clock| {
+        let (human_agent, bus) = HumanAgent::create();
+        let setup = GameSetup {
+            rules: Ruleset::default(),
+            seed: rng.gen(),
+            clock,
+        };
+
+        let driver = match opponent {
+            OpponentKind::Random => drive_game(
+                human_agent,
+                RandomAgent::new(StdRng::seed_from_u64(rng.gen())),
+                "random",
+                setup,
+                drop,
+            ),
+            OpponentKind::OpponentModel => {
+                let agent = model_agent.take().unwrap_or_default();
+                let slot = model_agent.clone();
+
+                drive_game(human_agent, agent, "opponent_model", setup, move |agent| {
+                    slot.set(Some(agent))
+                })
+            }
+            // There is no blueprint to load on the web,
+            // so the start screen never offers this opponent.
+            OpponentKind::Blueprint => return None,
+        };
+
+        Some(bus.driven_by(driver))
+    })
+}
+
+/// Opens the gui, letting `configure` plug extra things into it.
+#[cfg(all(not(target_arch = "wasm32"), feature = "gui"))]
+fn show_gui(
+    settings: Settings,
+    launcher: GameLauncher,
+    configure: impl FnOnce(GUIApp) -> GUIApp + 'static,
+) {
     let options = eframe::NativeOptions::default();
     eframe::run_native(
         "million prescient trees",
         options,
-        Box::new(|cc| Box::new(GUIApp::new(cc, bus))),
+        Box::new(move |cc| Box::new(configure(GUIApp::new(cc, launcher, settings)))),
     )
     .unwrap();
+}
+// }}}
+// {{{ Serve command
+/// Either hosts a game (see `host_game`), or serves
+/// the solver over http (see `serve_solver`).
+///
+/// Usage: `serve <address> <agent>` or `serve http <blueprint> [address] [samples]`
+#[cfg(any(feature = "net", feature = "rpc"))]
+fn serve(args: &[String], config: &Config) -> Result<(), String> {
+    match args {
+        #[cfg(feature = "rpc")]
+        [mode, args @ ..] if mode == "http" => serve_solver(args, config),
+        #[cfg(feature = "net")]
+        [address, opponent_name] => host_game(address, opponent_name, config),
+        _ => Err(
+            "Usage: serve <address> <agent> or serve http <blueprint> [address] [samples]"
+                .to_string(),
+        ),
+    }
+}
+
+/// Answers position evaluation requests (see `echo::rpc`) over http. The
+/// blueprint is either a path, or the name of a blueprint agent from the config.
+#[cfg(feature = "rpc")]
+fn serve_solver(args: &[String], config: &Config) -> Result<(), String> {
+    let [blueprint, rest @ ..] = args else {
+        return Err("Usage: serve http <blueprint> [address] [samples]".to_string());
+    };
+
+    let path = match config.agent(blueprint) {
+        Some(agent) => agent
+            .blueprint
+            .clone()
+            .ok_or_else(|| format!("Agent {blueprint:?} does not specify a blueprint file"))?,
+        None => PathBuf::from(blueprint),
+    };
+
+    let address = rest.first().map_or("127.0.0.1:8080", String::as_str);
+    let samples = match rest.get(1) {
+        Some(samples) => parse_number("samples", samples)?,
+        None => 1000,
+    };
 
-    handle.join().unwrap();
+    let service = echo::rpc::SolverService::open(&path, samples)
+        .map_err(|error| format!("Failed to load blueprint {path:?}: {error}"))?;
+
+    println!("Evaluating positions posted to http://{address}/evaluate");
+    echo::rpc::serve(service, address).map_err(|error| error.to_string())
 }
 // }}}
+// {{{ Remote play commands
+/// Hosts a game other machines can join using the `connect` command.
+/// The first player to connect plays against the given local agent,
+/// or against the second player to connect if the agent is `remote`.
+#[cfg(feature = "net")]
+fn host_game(address: &str, opponent_name: &str, config: &Config) -> Result<(), String> {
+    let seed = rand::random();
+    let mut rng = StdRng::seed_from_u64(seed);
+    tracing::event!(Level::INFO, "Hosting a game with seed {seed}");
+    let server = Server::bind(address).map_err(|error| error.to_string())?;
+    let address = server.local_addr().map_err(|error| error.to_string())?;
+    println!("Waiting for players on ws://{address}");
+
+    let remote = server.accept().map_err(|error| error.to_string())?;
+    let allocator = config.solver.allocator();
+    let mut opponent: Box<dyn EchoAgent + '_> = if opponent_name == "remote" {
+        Box::new(server.accept().map_err(|error| error.to_string())?)
+    } else {
+        create_agent(config, opponent_name, rng.gen(), &allocator)?
+    };
+
+    let state = KnownState::new_with_rules(BATTLEFIELDS, config.rules);
+    let main_phase = MainPhase::new();
+    let deals: Vec<_> = main_phase.valid_hidden_states(state.to_summary()).collect();
+    let hidden_state = deals[rng.gen_range(0..deals.len())];
+    let record = GameRecord::new(
+        BATTLEFIELDS,
+        Some(seed),
+        ["remote".to_string(), opponent_name.to_string()],
+    );
+
+    let agents = (remote, &mut *opponent);
+    let runner = EchoRunner::new(state, PerPhase::Main(main_phase), agents, hidden_state)
+        .with_seed(rng.gen())
+        .record_to(record, std::io::stdout());
+    let score = save_to_database(runner, config.database.as_deref())
+        .run_game_with_score()
+        .map_err(|error| format!("The game did not finish properly: {error}"))?;
+
+    println!("Final score: {:+}", score.0);
+
+    Ok(())
+}
+
+/// Joins a game hosted using the `serve` command. The game is played
+/// by a human using the gui, unless some other agent is specified.
+///
+/// Usage: `connect <url> [agent]`
+#[cfg(feature = "net")]
+fn connect(args: &[String], config: &Config, settings: Settings) -> Result<(), String> {
+    let (url, agent_name) = match args {
+        [url] => (url.clone(), "human"),
+        [url, agent_name] => (url.clone(), agent_name.as_str()),
+        _ => return Err("Usage: connect <url> [agent]".to_string()),
+    };
+
+    if agent_name != "human" {
+        let allocator = config.solver.allocator();
+        let mut agent = create_agent(config, agent_name, rand::random(), &allocator)?;
+        return echo::net::client::play(&url, &mut *agent).map_err(|error| error.to_string());
+    }
+
+    show_remote_game(url, settings)
+}
+
+/// Plays a game hosted on some other machine using the gui.
+#[cfg(all(feature = "net", feature = "gui"))]
+fn show_remote_game(url: String, settings: Settings) -> Result<(), String> {
+    // The server decides who we play against (and how long we get to think),
+    // so the opponent and clock picked on the start screen are ignored.
+    let launcher: GameLauncher = Box::new(move |_, _| {
+        let connection = match Connection::open(&url) {
+            Ok(connection) => connection,
+            Err(error) => {
+                tracing::event!(Level::ERROR, "Failed to connect to {url}: {error}");
+                return None;
+            }
+        };
+
+        let (human_agent, bus) = HumanAgent::create();
+        thread::spawn(move || {
+            if let Err(error) = connection.play(human_agent) {
+                tracing::event!(Level::ERROR, "Remote game failed: {error}");
+            }
+        });
+
+        Some(bus)
+    });
+
+    // Remote games might not be played on the battlefields blueprints get loaded for.
+    show_gui(settings, launcher, |app| app);
+
+    Ok(())
+}
+
+#[cfg(all(feature = "net", not(feature = "gui")))]
+fn show_remote_game(_url: String, _settings: Settings) -> Result<(), String> {
+    Err("Playing as a human requires the gui feature".to_string())
+}
+// }}}
+// {{{ Distributed training commands
+/// Splits training a tree starting from the beginning of the game between
+/// machines running the `work` command (see `cfr::distributed`), saving
+/// the resulting blueprint. Every worker runs the configured number of
+/// iterations in total, split evenly between rounds.
+///
+/// Usage: `coordinate <address> <workers> <output> [rounds]`
+#[cfg(feature = "net")]
+fn coordinate(args: &[String], config: &Config) -> Result<(), String> {
+    let (address, workers, output, rounds) = match args {
+        [address, workers, output] => (address, workers, output, 10),
+        [address, workers, output, rounds] => {
+            (address, workers, output, parse_number("rounds", rounds)?)
+        }
+        _ => return Err("Usage: coordinate <address> <workers> <output> [rounds]".to_string()),
+    };
+
+    if config.solver.lazy_expansion {
+        return Err("Distributed training does not support lazy expansion".to_string());
+    }
+
+    let options = DistributedTraining {
+        workers: parse_number("workers", workers)?,
+        rounds,
+        iterations: config.solver.iterations / rounds.max(1),
+        seed: config
+            .solver
+            .seed
+            .unwrap_or_else(|| StdRng::from_entropy().gen()),
+    };
+
+    let state = KnownState::new_with_rules(BATTLEFIELDS, config.rules);
+    let allocator = config.solver.allocator();
+    let transpositions = TranspositionTable::new();
+    let budget = config.solver.memory_budget();
+    let generator =
+        config
+            .solver
+            .generation_context(state, &allocator, &transpositions, budget.as_ref());
+    let mut arenas = config.solver.arenas();
+    let scope = generator.generate_parallel(&mut arenas);
 
+    let coordinator = Coordinator::bind(address.as_str()).map_err(|error| error.to_string())?;
+    let address = coordinator
+        .local_addr()
+        .map_err(|error| error.to_string())?;
+    println!("Waiting for {} workers on {address}", options.workers);
+
+    let start = Instant::now();
+    coordinator
+        .train(&scope, state, options)
+        .map_err(|error| format!("Training failed: {error}"))?;
+    println!("Solved in {:?}", start.elapsed());
+
+    std::fs::File::create(output)
+        .and_then(|file| write_blueprint(&scope, &state, 0, std::io::BufWriter::new(file)))
+        .map_err(|error| format!("Failed to write {output:?}: {error}"))
+}
+
+/// Joins a training run started using the `coordinate` command. The
+/// solver config must generate the same tree as the one of the coordinator.
+///
+/// Usage: `work <address>`
+#[cfg(feature = "net")]
+fn work(args: &[String], config: &Config) -> Result<(), String> {
+    let [address] = args else {
+        return Err("Usage: work <address>".to_string());
+    };
+
+    if config.solver.lazy_expansion {
+        return Err("Distributed training does not support lazy expansion".to_string());
+    }
+
+    let worker = Worker::connect(address.as_str())
+        .map_err(|error| format!("Failed to connect to {address}: {error}"))?;
+    let assignment = worker.assignment();
+    println!(
+        "Training {} subtrees for {} rounds",
+        assignment.branches.len(),
+        assignment.rounds
+    );
+
+    let allocator = config.solver.allocator();
+    let transpositions = TranspositionTable::new();
+    let budget = config.solver.memory_budget();
+    let generator = config.solver.generation_context(
+        assignment.state,
+        &allocator,
+        &transpositions,
+        budget.as_ref(),
+    );
+    let mut arenas = config.solver.arenas();
+    let mut scope = generator.generate_parallel(&mut arenas);
+
+    let mut trainer = config
+        .solver
+        .training_context()
+        .map_err(|error| error.to_string())?;
+    worker
+        .train(&mut trainer, &mut scope)
+        .map_err(|error| format!("Training failed: {error}"))
+}
+// }}}
+
+/// Reports some error and exits the program.
+fn exit_with(error: String) -> ! {
+    eprintln!("{error}");
+    std::process::exit(1)
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "gui"))]
 fn main() {
+    // There is no file system to load the settings from on the web.
+    let settings = Settings::default();
+
+    wasm_bindgen_futures::spawn_local(async move {
+        eframe::WebRunner::new()
+            .start(
+                "echo_canvas",
+                eframe::WebOptions::default(),
+                Box::new(move |cc| Box::new(GUIApp::new(cc, game_launcher(), settings))),
+            )
+            .await
+            .expect("Failed to start the gui");
+    });
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    // {{{ Global options
+    // Usage: echo [--config <path>] [--set <key>=<value>]... [--blueprint <path>] [command] [args]...
+    //
+    // The gui shows the strategies of the given blueprint as hints.
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut config_path = Config::DEFAULT_PATH.to_string();
+    let mut overrides = vec![];
+    let mut blueprint: Option<PathBuf> = None;
+
+    while let Some(option) = args.first().filter(|arg| arg.starts_with("--")).cloned() {
+        let Some(value) = args.get(1).cloned() else {
+            exit_with(format!("Missing value for {option}"));
+        };
+
+        match option.as_str() {
+            "--config" => config_path = value,
+            "--set" => overrides.push(value),
+            "--blueprint" => blueprint = Some(PathBuf::from(value)),
+            _ => exit_with(format!("Unknown option {option}")),
+        }
+
+        args.drain(..2);
+    }
+
+    let config =
+        Config::load(&config_path, &overrides).unwrap_or_else(|error| exit_with(error.to_string()));
+    // }}}
+
+    let mut settings = Settings::load(Settings::DEFAULT_PATH);
+    config.apply_gui_settings(&mut settings);
+
     let filter = tracing_subscriber::filter::Targets::new()
         .with_target("winit", Level::ERROR)
-        .with_target("echo", Level::TRACE);
+        .with_target("echo", settings.log_level);
 
     tracing_subscriber::registry()
         .with(tracing_subscriber::fmt::layer().compact())
         .with(filter)
         .init();
 
-    show_gui();
-    // simple_generation(2, 2, false);
+    match args.first().map(String::as_str) {
+        Some("analyze") => {
+            if let Err(error) = analyze(&args[1..], config.solver, config.rules) {
+                exit_with(error);
+            }
+        }
+        Some("estimate") => {
+            if let Err(error) = estimate(&args[1..], &config.solver) {
+                exit_with(error);
+            }
+        }
+        Some("simulate") => {
+            if let Err(error) = simulate(&args[1..], &config) {
+                exit_with(error);
+            }
+        }
+        Some("explain") => {
+            if let Err(error) = explain(&args[1..]) {
+                exit_with(error);
+            }
+        }
+        Some("check-indices") => {
+            if let Err(error) = check_indices(&args[1..]) {
+                exit_with(error);
+            }
+        }
+        Some("merge-blueprints") => {
+            if let Err(error) = merge_blueprints(&args[1..]) {
+                exit_with(error);
+            }
+        }
+        Some("opening-book") => {
+            if let Err(error) = opening_book(&args[1..]) {
+                exit_with(error);
+            }
+        }
+        #[cfg(feature = "deep-cfr")]
+        Some("deep-train") => {
+            if let Err(error) = deep_train(&args[1..], &config) {
+                exit_with(error);
+            }
+        }
+        #[cfg(feature = "database")]
+        Some("stats") => {
+            if let Err(error) = stats(&args[1..], &config) {
+                exit_with(error);
+            }
+        }
+        #[cfg(any(feature = "net", feature = "rpc"))]
+        Some("serve") => {
+            if let Err(error) = serve(&args[1..], &config) {
+                exit_with(error);
+            }
+        }
+        #[cfg(feature = "net")]
+        Some("connect") => {
+            if let Err(error) = connect(&args[1..], &config, settings) {
+                exit_with(error);
+            }
+        }
+        #[cfg(feature = "net")]
+        Some("coordinate") => {
+            if let Err(error) = coordinate(&args[1..], &config) {
+                exit_with(error);
+            }
+        }
+        #[cfg(feature = "net")]
+        Some("work") => {
+            if let Err(error) = work(&args[1..], &config) {
+                exit_with(error);
+            }
+        }
+        #[cfg(feature = "gui")]
+        _ => {
+            // Hints read the blueprint lazily, just like the blueprint opponent does.
+            let hints = blueprint
+                .as_deref()
+                .map(open_blueprint)
+                .transpose()
+                .unwrap_or_else(|error| exit_with(error));
+
+            let launcher = game_launcher(&config, blueprint);
+
+            // Blueprints can be played against, besides providing hints.
+            show_gui(settings, launcher, move |app| match hints {
+                Some(provider) => app
+                    .with_opponents(&OpponentKind::OPPONENTS)
+                    .with_strategy_provider(Box::new(provider)),
+                None => app,
+            });
+        }
+        #[cfg(not(feature = "gui"))]
+        _ => {
+            let _ = blueprint;
+            exit_with("Unknown command (the gui requires the gui feature)".to_string())
+        }
+    }
+
+    // simple_generation(&config.solver, 2, false);
 }
@@ -0,0 +1,60 @@
+//! The playing side of remote games.
+use super::{receive, send, ServerMessage};
+use crate::ai::echo_ai::EchoAgent;
+use crate::error::EchoResult;
+use std::net::TcpStream;
+use tracing::Level;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::WebSocket;
+
+/// A connection to some server, over which a single game can be played.
+pub struct Connection {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+}
+
+impl Connection {
+    /// Connects to a server using a `ws://` url.
+    pub fn open(url: &str) -> EchoResult<Self> {
+        let (socket, _) = tungstenite::connect(url)?;
+        tracing::event!(Level::INFO, "Connected to {url}");
+
+        Ok(Self { socket })
+    }
+
+    /// Lets a local agent play the game hosted on the server until it's over.
+    pub fn play<A: EchoAgent>(mut self, mut agent: A) -> EchoResult<()> {
+        loop {
+            match receive(&mut self.socket)? {
+                ServerMessage::Choose(agent_input) => {
+                    let decision = agent.choose(agent_input);
+
+                    // The protocol has no way to resign, so we simply leave.
+                    if agent.has_resigned() {
+                        tracing::event!(Level::INFO, "Resigned, leaving the game");
+                        agent.game_finished();
+
+                        let _ = self.socket.close(None);
+                        return Ok(());
+                    }
+
+                    send(&mut self.socket, &decision)?;
+                }
+                ServerMessage::Reveal(reveal_index, updated_score) => {
+                    agent.reveal_info(reveal_index, updated_score);
+                }
+                ServerMessage::GameFinished => {
+                    agent.game_finished();
+
+                    // The game is over either way, so there's nothing to do if this fails.
+                    let _ = self.socket.close(None);
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Connects to a server, and plays the game hosted there using a local agent.
+pub fn play<A: EchoAgent>(url: &str, agent: A) -> EchoResult<()> {
+    Connection::open(url)?.play(agent)
+}
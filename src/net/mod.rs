@@ -0,0 +1,119 @@
+//! Playing games across machines over websockets.
+//!
+//! The machine running the game (using an `EchoRunner`) hosts a `Server`.
+//! Every remote player connects to it, and is represented on the server by a
+//! `RemoteAgent`. On the other end, `client::play` lets any local agent
+//! (be it a human using the gui, or some bot) take part in the game.
+//!
+//! Messages are sent as json text frames. The server sends `ServerMessage`s,
+//! and the client answers every `ServerMessage::Choose` with a `DecisionIndex`.
+use crate::ai::echo_ai::AgentInput;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::types::Score;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use tungstenite::{Message, WebSocket};
+
+pub mod client;
+pub mod server;
+
+/// Messages sent from the server to the remote players.
+/// Mirrors the methods of the `EchoAgent` trait.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Choose(AgentInput),
+    Reveal(RevealIndex, Score),
+    GameFinished,
+}
+
+impl From<tungstenite::Error> for EchoError {
+    fn from(error: tungstenite::Error) -> Self {
+        Self::Network(error.to_string())
+    }
+}
+
+impl From<serde_json::Error> for EchoError {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Network(format!("Invalid message: {error}"))
+    }
+}
+
+// {{{ Helpers
+fn send<S: Read + Write, T: Serialize>(socket: &mut WebSocket<S>, message: &T) -> EchoResult<()> {
+    socket.send(Message::Text(serde_json::to_string(message)?))?;
+    Ok(())
+}
+
+/// Waits for the next message, skipping over control frames.
+fn receive<S: Read + Write, T: DeserializeOwned>(socket: &mut WebSocket<S>) -> EchoResult<T> {
+    loop {
+        match socket.read()? {
+            Message::Text(text) => return Ok(serde_json::from_str(&text)?),
+            Message::Close(_) => {
+                return Err(EchoError::Network(
+                    "The connection was closed mid-game".to_string(),
+                ))
+            }
+            Message::Binary(_) => {
+                return Err(EchoError::Network(
+                    "Expected a text message, got binary data".to_string(),
+                ))
+            }
+            _ => {}
+        }
+    }
+}
+// }}}
+
+#[cfg(test)]
+mod tests {
+    use super::server::Server;
+    use super::*;
+    use crate::ai::echo_ai::EchoRunner;
+    use crate::ai::random_agent::RandomAgent;
+    use crate::cfr::phase::{MainPhase, PerPhase, Phase};
+    use crate::game::battlefield::{Battlefield, Battlefields};
+    use crate::game::known_state::KnownState;
+    use crate::game::known_state_summary::KnownStateEssentials;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const BATTLEFIELDS: [Battlefield; Battlefields::COUNT] = Battlefields::last_of([
+        Battlefield::Night,
+        Battlefield::Glade,
+        Battlefield::Urban,
+        Battlefield::LastStrand,
+    ]);
+
+    #[test]
+    fn remote_games_run_to_completion() {
+        let server = Server::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", server.local_addr().unwrap());
+
+        let client = std::thread::spawn(move || {
+            client::play(&url, RandomAgent::new(StdRng::seed_from_u64(0)))
+        });
+
+        let remote = server.accept().unwrap();
+        let local = RandomAgent::new(StdRng::seed_from_u64(1));
+
+        let state = KnownState::new_starting(BATTLEFIELDS);
+        let main_phase = MainPhase::new();
+        let hidden_state = main_phase
+            .valid_hidden_states(state.to_summary())
+            .next()
+            .unwrap();
+
+        let runner = EchoRunner::new(
+            state,
+            PerPhase::Main(main_phase),
+            (remote, local),
+            hidden_state,
+        );
+
+        assert!(runner.run_game().is_ok());
+        assert_eq!(client.join().unwrap(), Ok(()));
+    }
+}
@@ -0,0 +1,83 @@
+//! The hosting side of remote games.
+use super::{receive, send, ServerMessage};
+use crate::ai::echo_ai::{AgentInput, EchoAgent};
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::types::Score;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use tracing::Level;
+use tungstenite::WebSocket;
+
+/// Waits for remote players to connect.
+pub struct Server {
+    listener: TcpListener,
+}
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs) -> EchoResult<Self> {
+        let listener = TcpListener::bind(address)
+            .map_err(|error| EchoError::Network(format!("Failed to start the server: {error}")))?;
+
+        Ok(Self { listener })
+    }
+
+    /// The address the server is listening on. Useful when binding to port 0.
+    pub fn local_addr(&self) -> EchoResult<SocketAddr> {
+        self.listener.local_addr().map_err(|error| {
+            EchoError::Network(format!("Failed to read the server address: {error}"))
+        })
+    }
+
+    /// Blocks until the next player connects.
+    pub fn accept(&self) -> EchoResult<RemoteAgent> {
+        let (stream, address) = self.listener.accept().map_err(|error| {
+            EchoError::Network(format!("Failed to accept a connection: {error}"))
+        })?;
+
+        let socket = tungstenite::accept(stream).map_err(|error| {
+            EchoError::Network(format!(
+                "Websocket handshake with {address} failed: {error}"
+            ))
+        })?;
+
+        tracing::event!(Level::INFO, "Remote player connected from {address}");
+
+        Ok(RemoteAgent { socket })
+    }
+}
+
+/// Agent forwarding every decision to a player connected to a `Server`.
+///
+/// The `EchoAgent` trait gives us no way to report errors,
+/// so losing the connection mid-game results in a panic.
+pub struct RemoteAgent {
+    socket: WebSocket<TcpStream>,
+}
+
+impl RemoteAgent {
+    fn send(&mut self, message: ServerMessage) {
+        send(&mut self.socket, &message)
+            .unwrap_or_else(|error| panic!("Lost connection to the remote player: {error}"));
+    }
+}
+
+impl EchoAgent for RemoteAgent {
+    fn choose(&mut self, agent_input: AgentInput) -> DecisionIndex {
+        self.send(ServerMessage::Choose(agent_input));
+
+        receive(&mut self.socket)
+            .unwrap_or_else(|error| panic!("Lost connection to the remote player: {error}"))
+    }
+
+    fn reveal_info(&mut self, reveal_index: RevealIndex, updated_score: Score) {
+        self.send(ServerMessage::Reveal(reveal_index, updated_score));
+    }
+
+    fn game_finished(&mut self) {
+        self.send(ServerMessage::GameFinished);
+
+        // The game is over either way, so there's nothing to do if this fails.
+        let _ = self.socket.close(None);
+    }
+}
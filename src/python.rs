@@ -0,0 +1,373 @@
+//! Python bindings, such that experiments can be scripted without writing rust.
+//!
+//! Cards are passed around by name (eg: `"Wall"`, `"Sabotage"`, `"LastStrand"`),
+//! sets of cards as lists of names, and players as either `"me"` or `"you"`.
+//! Positions use the notation from `game::notation`. Errors are raised as `ValueError`s.
+//!
+//! ```python
+//! import echo
+//!
+//! state = echo.KnownState(["Night", "Glade", "Urban", "LastStrand"])
+//! result, score, state, events = echo.simulate(state, ("Wall", "Gambit"), ("Seer", "Ambush"))
+//! ```
+use crate::ai::echo_ai::AgentInput;
+use crate::ai::strategy_hints::{describe_decision, BlueprintStrategyProvider, StrategyProvider};
+use crate::cfr::blueprint::BlueprintReader;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::{DecodingInfo, EncodingInfo, HiddenIndex, PerPhaseInfo};
+use crate::cfr::reveal_index::RevealIndex;
+use crate::error::EchoError;
+use crate::game::battlefield::{Battlefield, Battlefields};
+use crate::game::choice::FinalMainPhaseChoice;
+use crate::game::creature::{Creature, CreatureSet};
+use crate::game::edict::Edict;
+use crate::game::known_state::KnownState;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::notation::from_notation;
+use crate::game::simulate::BattleContext;
+use crate::game::types::{Player, TurnResult};
+use crate::helpers::bitfield::Bitfield;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::fs::File;
+use std::io::BufReader;
+use std::str::FromStr;
+
+// {{{ Conversions
+impl From<EchoError> for PyErr {
+    fn from(error: EchoError) -> Self {
+        PyValueError::new_err(error.to_string())
+    }
+}
+
+fn value_error(message: impl ToString) -> PyErr {
+    PyValueError::new_err(message.to_string())
+}
+
+fn parse<T: FromStr<Err = EchoError>>(name: &str) -> PyResult<T> {
+    name.parse().map_err(value_error)
+}
+
+fn parse_set(names: Vec<String>) -> PyResult<CreatureSet> {
+    let mut result = CreatureSet::empty();
+    for name in names {
+        result.insert(parse(&name)?);
+    }
+
+    Ok(result)
+}
+
+fn names<T: ToString>(elements: impl IntoIterator<Item = T>) -> Vec<String> {
+    elements.into_iter().map(|e| e.to_string()).collect()
+}
+
+fn parse_player(name: &str) -> PyResult<Player> {
+    match name {
+        "me" => Ok(Player::Me),
+        "you" => Ok(Player::You),
+        _ => Err(value_error(format!(
+            "Expected \"me\" or \"you\", got {name:?}"
+        ))),
+    }
+}
+// }}}
+// {{{ Known state
+/// Information about the game known by both players.
+#[pyclass(name = "KnownState")]
+#[derive(Clone, Copy)]
+pub struct PyKnownState(pub KnownState);
+
+#[pymethods]
+impl PyKnownState {
+    /// Creates the state at the start of a game played on the given battlefields.
+    #[new]
+    fn new(battlefields: Vec<String>) -> PyResult<Self> {
+        let battlefields: [Battlefield; Battlefields::COUNT] = battlefields
+            .iter()
+            .map(|name| parse(name))
+            .collect::<PyResult<Vec<_>>>()?
+            .try_into()
+            .map_err(|_| value_error("Expected exactly four battlefields"))?;
+
+        Ok(Self(KnownState::new_starting(battlefields)))
+    }
+
+    /// Raises a `ValueError` if the state could not have been reached by playing.
+    fn validate(&self) -> PyResult<()> {
+        self.0.validate().map_err(value_error)
+    }
+
+    #[getter]
+    fn turn(&self) -> usize {
+        self.0.battlefields.current
+    }
+
+    /// The score, from the perspective of the first player.
+    #[getter]
+    fn score(&self) -> i8 {
+        self.0.score.0
+    }
+
+    #[getter]
+    fn battlefields(&self) -> Vec<String> {
+        names(self.0.battlefields.all)
+    }
+
+    #[getter]
+    fn graveyard(&self) -> Vec<String> {
+        names(self.0.graveyard)
+    }
+
+    #[getter]
+    fn hand_size(&self) -> usize {
+        self.0.hand_size()
+    }
+
+    fn edicts(&self, player: &str) -> PyResult<Vec<String>> {
+        Ok(names(self.0.player_edicts(parse_player(player)?)))
+    }
+
+    fn effects(&self, player: &str) -> PyResult<Vec<String>> {
+        let player = parse_player(player)?;
+        Ok(names(player.select(self.0.player_states).effects))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+// }}}
+// {{{ Simulation
+/// Resolves the battle of the current turn. Every player's choice is given as
+/// a `(creature, edict)` tuple. The sabotage guesses are optional.
+///
+/// Returns a `(result, score, next_state, events)` tuple, where the result
+/// is one of `"Won"`, `"Tied"` or `"Lost"` (from the perspective of the first
+/// player), and the next state is `None` if the game is over.
+#[pyfunction]
+#[pyo3(signature = (state, mine, yours, sabotage_guesses = (None, None)))]
+fn simulate(
+    state: PyKnownState,
+    mine: (String, String),
+    yours: (String, String),
+    sabotage_guesses: (Option<String>, Option<String>),
+) -> PyResult<(String, i8, Option<PyKnownState>, Vec<String>)> {
+    let main_choices = [mine, yours].try_map(|(creature, edict)| {
+        PyResult::Ok(FinalMainPhaseChoice::new(
+            parse::<Creature>(&creature)?,
+            parse::<Edict>(&edict)?,
+        ))
+    })?;
+
+    let (my_guess, your_guess) = sabotage_guesses;
+    let sabotage_choices = [my_guess, your_guess]
+        .try_map(|guess| guess.map(|guess| parse::<Creature>(&guess)).transpose())?;
+
+    let context = BattleContext::new(main_choices, sabotage_choices, state.0, false);
+    let (result, turn_result, events) = context.advance_known_state_with_events();
+
+    let (score, next) = match turn_result {
+        TurnResult::Finished(score) => (score, None),
+        TurnResult::Unfinished(next) => (next.score, Some(PyKnownState(next))),
+    };
+
+    Ok((format!("{result:?}"), score.0, next, names(events)))
+}
+// }}}
+// {{{ Indices
+/// Encodes the hidden information of some player. The choice must be given
+/// for every phase but the main one, and the revealed creature during the seer phase.
+#[pyfunction]
+#[pyo3(signature = (state, player, hand, choice = None, revealed = None))]
+fn encode_hidden_index(
+    state: PyKnownState,
+    player: &str,
+    hand: Vec<String>,
+    choice: Option<Vec<String>>,
+    revealed: Option<String>,
+) -> PyResult<usize> {
+    let hand = parse_set(hand)?;
+    let info: EncodingInfo = match (choice, revealed) {
+        (None, None) => PerPhaseInfo::Main(hand),
+        (Some(choice), None) => PerPhaseInfo::Sabotage(hand, parse_set(choice)?),
+        (Some(choice), Some(revealed)) => {
+            PerPhaseInfo::Seer(hand, parse_set(choice)?, parse(&revealed)?)
+        }
+        (None, Some(_)) => return Err(value_error("Cannot reveal a creature without a choice")),
+    };
+
+    Ok(HiddenIndex::encode(&state.0, parse_player(player)?, info)?.into())
+}
+
+/// Inverse of `encode_hidden_index`, returning a `(hand, choice)` tuple.
+/// The phase is one of `"main"`, `"sabotage"` or `"seer"`.
+#[pyfunction]
+#[pyo3(signature = (state, player, index, phase, revealed = None))]
+fn decode_hidden_index(
+    state: PyKnownState,
+    player: &str,
+    index: usize,
+    phase: &str,
+    revealed: Option<String>,
+) -> PyResult<(Vec<String>, Option<Vec<String>>)> {
+    let info: DecodingInfo =
+        match (phase, revealed) {
+            ("main", None) => PerPhaseInfo::Main(()),
+            ("sabotage", None) => PerPhaseInfo::Sabotage((), ()),
+            ("seer", Some(revealed)) => PerPhaseInfo::Seer((), (), parse(&revealed)?),
+            _ => return Err(value_error(
+                "Expected a main/sabotage/seer phase, with a revealed creature only for the latter",
+            )),
+        };
+
+    let hidden = HiddenIndex::from(index).decode(&state.0, parse_player(player)?, info)?;
+    Ok((names(hidden.hand), hidden.choice.map(names)))
+}
+
+#[pyfunction]
+fn encode_main_decision(
+    state: PyKnownState,
+    player: &str,
+    hand: Vec<String>,
+    creatures: Vec<String>,
+    edict: &str,
+) -> PyResult<usize> {
+    let index = DecisionIndex::encode_main_phase_index(
+        &state.0,
+        parse_player(player)?,
+        parse_set(hand)?,
+        parse_set(creatures)?,
+        parse(edict)?,
+    )?;
+
+    Ok(index.0)
+}
+
+/// Inverse of `encode_main_decision`, returning a `(creatures, edict)` tuple.
+#[pyfunction]
+fn decode_main_decision(
+    state: PyKnownState,
+    player: &str,
+    hand: Vec<String>,
+    index: usize,
+) -> PyResult<(Vec<String>, String)> {
+    let hand = parse_set(hand)?;
+    if hand.len() != state.0.hand_size() {
+        return Err(value_error(format!(
+            "Expected a hand of {} creatures",
+            state.0.hand_size()
+        )));
+    }
+
+    let (creatures, edict) =
+        DecisionIndex(index).decode_main_phase_index(&state.0, parse_player(player)?, hand)?;
+
+    Ok((names(creatures), edict.to_string()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (state, hand, guess = None))]
+fn encode_sabotage_decision(
+    state: PyKnownState,
+    hand: Vec<String>,
+    guess: Option<String>,
+) -> PyResult<usize> {
+    let guess = guess.map(|guess| parse(&guess)).transpose()?;
+    Ok(DecisionIndex::encode_sabotage_index(&state.0, parse_set(hand)?, guess).0)
+}
+
+/// Inverse of `encode_sabotage_decision`. The sabotage status
+/// tells whether the player has played the sabotage edict.
+#[pyfunction]
+fn decode_sabotage_decision(
+    state: PyKnownState,
+    hand: Vec<String>,
+    sabotage_status: bool,
+    index: usize,
+) -> PyResult<Option<String>> {
+    let guess =
+        DecisionIndex(index).decode_sabotage_index(&state.0, parse_set(hand)?, sabotage_status)?;
+
+    Ok(guess.map(|guess| guess.to_string()))
+}
+
+#[pyfunction]
+fn encode_seer_decision(creatures: Vec<String>, choice: &str) -> PyResult<usize> {
+    Ok(DecisionIndex::encode_seer_index(parse_set(creatures)?, parse(choice)?)?.0)
+}
+
+#[pyfunction]
+fn decode_seer_decision(creatures: Vec<String>, index: usize) -> PyResult<String> {
+    Ok(DecisionIndex(index)
+        .decode_seer_index(parse_set(creatures)?)?
+        .to_string())
+}
+// }}}
+// {{{ Blueprints
+/// A trained blueprint file, which can be queried for strategies.
+#[pyclass(name = "Blueprint", unsendable)]
+pub struct PyBlueprint(BlueprintStrategyProvider<BufReader<File>>);
+
+#[pymethods]
+impl PyBlueprint {
+    #[new]
+    fn new(path: &str) -> PyResult<Self> {
+        let provider = File::open(path)
+            .and_then(|file| BlueprintReader::new(BufReader::new(file)))
+            .and_then(BlueprintStrategyProvider::new)
+            .map_err(|error| value_error(format!("Failed to load blueprint {path:?}: {error}")))?;
+
+        Ok(Self(provider))
+    }
+
+    /// Returns the probability of taking every decision in the given position,
+    /// or `None` if the blueprint does not cover it. The reveal indices lead
+    /// from the root of the blueprint to the public state of the position, and
+    /// a `ValueError` is raised if they lead somewhere else.
+    #[pyo3(signature = (position, reveals = vec![]))]
+    fn strategy(&mut self, position: &str, reveals: Vec<usize>) -> PyResult<Option<Vec<f32>>> {
+        let (state, phase, player, hidden) = from_notation(position).map_err(value_error)?;
+        let reveals: Vec<_> = reveals.into_iter().map(RevealIndex).collect();
+
+        let state = self
+            .0
+            .blueprint()
+            .check_position(&state, &phase, &reveals)
+            .map_err(value_error)?;
+
+        self.0.game_finished();
+        for reveal_index in reveals {
+            self.0.reveal_info(reveal_index);
+        }
+
+        let input = AgentInput::new(phase, state, hidden, player);
+        Ok(self.0.strategy(&input))
+    }
+}
+
+/// Returns a short description of what taking some decision in a position means.
+#[pyfunction]
+fn describe(position: &str, decision: usize) -> PyResult<Option<String>> {
+    let (state, phase, player, hidden) = from_notation(position).map_err(value_error)?;
+    let input = AgentInput::new(phase, state, hidden, player);
+
+    Ok(describe_decision(&input, DecisionIndex(decision)))
+}
+// }}}
+
+#[pymodule]
+fn echo(_py: Python, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyKnownState>()?;
+    module.add_class::<PyBlueprint>()?;
+    module.add_function(wrap_pyfunction!(simulate, module)?)?;
+    module.add_function(wrap_pyfunction!(encode_hidden_index, module)?)?;
+    module.add_function(wrap_pyfunction!(decode_hidden_index, module)?)?;
+    module.add_function(wrap_pyfunction!(encode_main_decision, module)?)?;
+    module.add_function(wrap_pyfunction!(decode_main_decision, module)?)?;
+    module.add_function(wrap_pyfunction!(encode_sabotage_decision, module)?)?;
+    module.add_function(wrap_pyfunction!(decode_sabotage_decision, module)?)?;
+    module.add_function(wrap_pyfunction!(encode_seer_decision, module)?)?;
+    module.add_function(wrap_pyfunction!(decode_seer_decision, module)?)?;
+    module.add_function(wrap_pyfunction!(describe, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,516 @@
+//! A JSON-RPC service answering questions about positions using a trained
+//! blueprint, such that external guis and scripts can query the solver.
+//!
+//! Requests get posted over http, using the JSON-RPC 2.0 format. Every method
+//! takes the same parameters:
+//! - `position`: the position to look at, using the notation from `game::notation`
+//! - `reveals`: the reveal indices leading from the root of the blueprint to the
+//!   public state of the position (empty if the position is the root itself)
+//!
+//! The available methods are:
+//! - `get_strategy`: the probability the blueprint assigns to every decision
+//! - `best_move`: the decision the blueprint is most likely to take
+//! - `evaluate_position`: the expected score (from the perspective of the player
+//!   whose hidden information is given), estimated by letting the blueprint play
+//!   against itself. Only positions at the start of a turn can be evaluated.
+//!
+//! For clients which would rather not speak JSON-RPC, posting a position to
+//! `/evaluate` returns both its strategy and evaluation (the latter being `null`
+//! for positions which cannot be evaluated). The body is either the notation
+//! itself, or a json object containing the parameters above.
+use crate::ai::echo_ai::{AgentInput, EchoRunner};
+use crate::ai::strategy_agent::StrategyAgent;
+use crate::ai::strategy_hints::{describe_decision, BlueprintStrategyProvider, StrategyProvider};
+use crate::cfr::blueprint::BlueprintReader;
+use crate::cfr::decision::Probability;
+use crate::cfr::decision_index::DecisionIndex;
+use crate::cfr::hidden_index::{HiddenState, PerPhaseInfo};
+use crate::cfr::phase::PerPhase;
+use crate::cfr::reveal_index::RevealIndex;
+use crate::error::{EchoError, EchoResult};
+use crate::game::creature::CreatureSet;
+use crate::game::known_state_summary::KnownStateEssentials;
+use crate::game::notation::from_notation;
+use crate::game::types::Player;
+use crate::helpers::bitfield::Bitfield;
+use crate::helpers::pair::Pair;
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek};
+use std::path::Path;
+use tracing::Level;
+
+// {{{ Types
+/// The parameters all the methods take.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PositionParams {
+    position: String,
+    #[serde(default)]
+    reveals: Vec<RevealIndex>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Request {
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Value,
+}
+
+/// Some decision, together with how likely the blueprint is to take it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RatedDecision {
+    pub decision: DecisionIndex,
+    pub probability: Probability,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Evaluation {
+    pub expected_score: f32,
+    pub samples: usize,
+}
+
+/// Everything the `/evaluate` endpoint knows about a position.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionReport {
+    pub strategy: Vec<RatedDecision>,
+    pub evaluation: Option<Evaluation>,
+}
+
+/// Error codes defined by the JSON-RPC spec.
+mod codes {
+    pub const PARSE_ERROR: i32 = -32700;
+    pub const INVALID_REQUEST: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
+    /// Used for everything that goes wrong while answering a valid request.
+    pub const SERVER_ERROR: i32 = -32000;
+}
+// }}}
+// {{{ Service
+/// Answers requests using a blueprint.
+///
+/// Every player gets a provider of their own,
+/// such that games can be simulated for evaluating positions.
+pub struct SolverService<R> {
+    providers: Pair<BlueprintStrategyProvider<R>>,
+    samples: usize,
+    rng: StdRng,
+}
+
+impl SolverService<BufReader<File>> {
+    /// Creates a service using the blueprint stored at some path.
+    pub fn open(path: impl AsRef<Path>, samples: usize) -> io::Result<Self> {
+        // Every player navigates the blueprint on their own, so we open it twice.
+        let providers = [(), ()].try_map(|_| {
+            let file = File::open(path.as_ref())?;
+            BlueprintStrategyProvider::new(BlueprintReader::new(BufReader::new(file))?)
+        })?;
+
+        Ok(Self::new(providers, samples))
+    }
+}
+
+impl<R: Read + Seek> SolverService<R> {
+    /// Creates a service which plays `samples` games whenever evaluating a position.
+    pub fn new(providers: Pair<BlueprintStrategyProvider<R>>, samples: usize) -> Self {
+        Self {
+            providers,
+            samples,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Parses and validates a position, making sure the
+    /// reveals lead to it from the root of the blueprint.
+    fn input_for(&self, position: &str, reveals: &[RevealIndex]) -> EchoResult<AgentInput> {
+        let (state, phase, player, hidden) = from_notation(position)?;
+
+        state.validate()?;
+        HiddenState::from_encoding_info(hidden).validate_against(&state)?;
+        let state = self.providers[0]
+            .blueprint()
+            .check_position(&state, &phase, reveals)?;
+
+        Ok(AgentInput::new(phase, state, hidden, player))
+    }
+
+    /// Moves some player's provider to the public state reached by the given reveals.
+    fn locate(&mut self, player: Player, reveals: &[RevealIndex]) {
+        let provider = player.select_mut(&mut self.providers);
+
+        provider.game_finished();
+        for reveal_index in reveals {
+            provider.reveal_info(*reveal_index);
+        }
+    }
+
+    pub fn get_strategy(
+        &mut self,
+        position: &str,
+        reveals: &[RevealIndex],
+    ) -> EchoResult<Vec<RatedDecision>> {
+        let input = self.input_for(position, reveals)?;
+        self.locate(input.player, reveals);
+
+        let strategy = input
+            .player
+            .select_mut(&mut self.providers)
+            .strategy(&input)
+            .ok_or_else(|| {
+                EchoError::InvalidState("The position is not covered by the blueprint".to_string())
+            })?;
+
+        Ok(strategy
+            .into_iter()
+            .enumerate()
+            .map(|(index, probability)| RatedDecision {
+                decision: DecisionIndex(index),
+                probability,
+                description: describe_decision(&input, DecisionIndex(index)),
+            })
+            .collect())
+    }
+
+    pub fn best_move(
+        &mut self,
+        position: &str,
+        reveals: &[RevealIndex],
+    ) -> EchoResult<RatedDecision> {
+        self.get_strategy(position, reveals)?
+            .into_iter()
+            .max_by(|a, b| a.probability.total_cmp(&b.probability))
+            .ok_or_else(|| EchoError::InvalidState("No decisions are available".to_string()))
+    }
+
+    pub fn evaluate_position(
+        &mut self,
+        position: &str,
+        reveals: &[RevealIndex],
+    ) -> EchoResult<Evaluation> {
+        let input = self.input_for(position, reveals)?;
+        let PerPhase::Main(_) = input.phase else {
+            return Err(EchoError::InvalidState(
+                "Only positions at the start of a turn can be evaluated".to_string(),
+            ));
+        };
+
+        let hand = input.hidden.get_main();
+        let remaining = !(input.state.graveyard | hand);
+        let mut total_score = 0.0;
+
+        for _ in 0..self.samples {
+            // The opponent could be holding any of the creatures we don't know the location of
+            let mut opponent_hand = CreatureSet::empty();
+            for creature in remaining
+                .into_iter()
+                .choose_multiple(&mut self.rng, input.state.hand_size())
+            {
+                opponent_hand.insert(creature);
+            }
+
+            let mut hidden_state = [PerPhaseInfo::Main(opponent_hand); 2];
+            *input.player.select_mut(&mut hidden_state) = PerPhaseInfo::Main(hand);
+
+            for player in Player::PLAYERS {
+                self.locate(player, reveals);
+            }
+
+            let rngs = [(), ()].map(|_| StdRng::from_rng(&mut self.rng).unwrap());
+            let [mine, yours] = &mut self.providers;
+            let [my_rng, your_rng] = rngs;
+            let agents = (
+                StrategyAgent::new(mine, my_rng),
+                StrategyAgent::new(yours, your_rng),
+            );
+
+            let score = EchoRunner::new(input.state, input.phase, agents, hidden_state)
+                .run_game_with_score()?;
+
+            total_score += match input.player {
+                Player::Me => score.0,
+                Player::You => -score.0,
+            } as f32;
+        }
+
+        Ok(Evaluation {
+            expected_score: total_score / self.samples.max(1) as f32,
+            samples: self.samples,
+        })
+    }
+
+    /// Returns the strategy of some position, together with its
+    /// evaluation (if the position is at the start of a turn).
+    pub fn report(
+        &mut self,
+        position: &str,
+        reveals: &[RevealIndex],
+    ) -> EchoResult<PositionReport> {
+        let strategy = self.get_strategy(position, reveals)?;
+        let evaluation = match self.input_for(position, reveals)?.phase {
+            PerPhase::Main(_) => Some(self.evaluate_position(position, reveals)?),
+            _ => None,
+        };
+
+        Ok(PositionReport {
+            strategy,
+            evaluation,
+        })
+    }
+
+    /// Answers a request posted to `/evaluate`, returning the
+    /// http status code together with the serialized response.
+    pub fn handle_evaluate(&mut self, body: &str) -> (u16, String) {
+        let params = if body.trim_start().starts_with('{') {
+            match serde_json::from_str(body) {
+                Ok(params) => params,
+                Err(error) => return (400, json!({ "error": error.to_string() }).to_string()),
+            }
+        } else {
+            PositionParams {
+                position: body.trim().to_string(),
+                reveals: vec![],
+            }
+        };
+
+        match self.report(&params.position, &params.reveals) {
+            Ok(report) => (200, json!(report).to_string()),
+            Err(error) => (400, json!({ "error": error.to_string() }).to_string()),
+        }
+    }
+
+    /// Answers a single JSON-RPC request, returning the serialized response.
+    pub fn handle(&mut self, request: &str) -> String {
+        let request: Request = match serde_json::from_str::<Value>(request) {
+            Ok(value) => match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(error) => return error_response(Value::Null, codes::INVALID_REQUEST, error),
+            },
+            Err(error) => return error_response(Value::Null, codes::PARSE_ERROR, error),
+        };
+
+        let params: PositionParams = match serde_json::from_value(request.params) {
+            Ok(params) => params,
+            Err(error) => return error_response(request.id, codes::INVALID_PARAMS, error),
+        };
+
+        let position = params.position.as_str();
+        let reveals = params.reveals.as_slice();
+        let result = match request.method.as_str() {
+            "get_strategy" => self.get_strategy(position, reveals).map(|r| json!(r)),
+            "best_move" => self.best_move(position, reveals).map(|r| json!(r)),
+            "evaluate_position" => self.evaluate_position(position, reveals).map(|r| json!(r)),
+            method => {
+                let message = format!("Unknown method {method:?}");
+                return error_response(request.id, codes::METHOD_NOT_FOUND, message);
+            }
+        };
+
+        match result {
+            Ok(result) => {
+                json!({ "jsonrpc": "2.0", "result": result, "id": request.id }).to_string()
+            }
+            Err(error) => error_response(request.id, codes::SERVER_ERROR, error),
+        }
+    }
+}
+
+fn error_response(id: Value, code: i32, message: impl ToString) -> String {
+    json!({
+        "jsonrpc": "2.0",
+        "error": { "code": code, "message": message.to_string() },
+        "id": id,
+    })
+    .to_string()
+}
+// }}}
+// {{{ Http server
+/// Answers requests posted to the given address until the process gets killed.
+/// Requests posted to `/evaluate` are answered using `handle_evaluate`,
+/// and everything else is treated as JSON-RPC.
+pub fn serve<R: Read + Seek>(mut service: SolverService<R>, address: &str) -> EchoResult<()> {
+    let server = tiny_http::Server::http(address)
+        .map_err(|error| EchoError::Network(format!("Failed to listen on {address}: {error}")))?;
+
+    tracing::event!(Level::INFO, "Listening on http://{address}");
+
+    // Lets web frontends hosted elsewhere query the solver.
+    let allow_origin = tiny_http::Header::from_bytes("Access-Control-Allow-Origin", "*").unwrap();
+    let allow_headers = tiny_http::Header::from_bytes("Access-Control-Allow-Headers", "*").unwrap();
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = match request.method() {
+            tiny_http::Method::Options => (204, String::new()),
+            tiny_http::Method::Post => {
+                let mut body = String::new();
+                match request.as_reader().read_to_string(&mut body) {
+                    Ok(_) if request.url() == "/evaluate" => service.handle_evaluate(&body),
+                    Ok(_) => (200, service.handle(&body)),
+                    Err(error) => (400, error_response(Value::Null, codes::PARSE_ERROR, error)),
+                }
+            }
+            _ => (405, "Requests must be sent using POST".to_string()),
+        };
+
+        let response = tiny_http::Response::from_string(body)
+            .with_status_code(status)
+            .with_header(allow_origin.clone())
+            .with_header(allow_headers.clone());
+
+        if let Err(error) = request.respond(response) {
+            tracing::event!(Level::WARN, "Failed to send response: {error}");
+        }
+    }
+
+    Ok(())
+}
+// }}}
+// {{{ Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfr::blueprint::{write_blueprint, BlueprintReader};
+    use crate::cfr::fixtures::last_turn_state;
+    use crate::cfr::generate::GenerationContext;
+    use crate::cfr::phase::MainPhase;
+    use crate::cfr::train::TrainingContext;
+    use crate::game::battlefield::Battlefields;
+    use crate::game::edict::Edict;
+    use crate::game::notation::to_notation;
+    use bumpalo::Bump;
+    use std::io::Cursor;
+
+    /// Trains a blueprint for the last turn of a game, returning
+    /// a service using it together with the notation of the root.
+    fn last_turn_service() -> (SolverService<Cursor<Vec<u8>>>, String) {
+        let mut state = last_turn_state();
+
+        for player_state in &mut state.player_states {
+            for edict in &Edict::EDICTS[..Battlefields::COUNT - 1] {
+                player_state.edicts.remove(*edict);
+            }
+        }
+
+        let allocator = Bump::new();
+        let mut scope = GenerationContext::new(1, state, &allocator).generate();
+        TrainingContext::new(false).cfr(&mut scope, state.to_summary(), 20);
+
+        let mut file = vec![];
+        write_blueprint(&scope, &state, 0, &mut file).unwrap();
+
+        let providers = [(), ()].map(|_| {
+            let reader = BlueprintReader::new(Cursor::new(file.clone())).unwrap();
+            BlueprintStrategyProvider::new(reader).unwrap()
+        });
+
+        let mut hand = CreatureSet::empty();
+        for creature in (!state.graveyard).into_iter().take(state.hand_size()) {
+            hand.insert(creature);
+        }
+
+        let position = to_notation(
+            &state,
+            &PerPhase::Main(MainPhase::new()),
+            Player::Me,
+            PerPhaseInfo::Main(hand),
+        );
+
+        (SolverService::new(providers, 10), position)
+    }
+
+    #[test]
+    fn requests_get_answered() {
+        let (mut service, position) = last_turn_service();
+
+        let strategy = service.get_strategy(&position, &[]).unwrap();
+        let total: f32 = strategy.iter().map(|d| d.probability).sum();
+        assert!((total - 1.0).abs() < 0.001);
+
+        let best = service.best_move(&position, &[]).unwrap();
+        assert!(strategy.iter().all(|d| d.probability <= best.probability));
+
+        let evaluation = service.evaluate_position(&position, &[]).unwrap();
+        assert_eq!(evaluation.samples, 10);
+
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "best_move",
+            "params": { "position": position },
+            "id": 7,
+        });
+        let response: Value = serde_json::from_str(&service.handle(&request.to_string())).unwrap();
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["result"]["decision"], best.decision.0);
+    }
+
+    #[test]
+    fn positions_must_match_the_blueprint() {
+        let (mut service, position) = last_turn_service();
+
+        // The same hand, in a state the blueprint was not trained for
+        let (mut state, phase, player, hidden) = from_notation(&position).unwrap();
+        state.score.0 += 1;
+        let other = to_notation(&state, &phase, player, hidden);
+        assert!(service.get_strategy(&other, &[]).is_err());
+
+        // The reveals lead to the sabotage or seer phase instead
+        assert!(service.get_strategy(&position, &[RevealIndex(0)]).is_err());
+    }
+
+    #[test]
+    fn positions_get_evaluated() {
+        let (mut service, position) = last_turn_service();
+
+        let (status, response) = service.handle_evaluate(&position);
+        let response: Value = serde_json::from_str(&response).unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(response["evaluation"]["samples"], 10);
+        assert!(response["strategy"]
+            .as_array()
+            .is_some_and(|s| !s.is_empty()));
+
+        let request = json!({ "position": position, "reveals": [] });
+        assert_eq!(service.handle_evaluate(&request.to_string()).0, 200);
+        assert_eq!(service.handle_evaluate("NGUL/0").0, 400);
+    }
+
+    #[test]
+    fn bad_requests_are_reported() {
+        let (mut service, position) = last_turn_service();
+        let error_code = |service: &mut SolverService<_>, request: &str| {
+            let response: Value = serde_json::from_str(&service.handle(request)).unwrap();
+            response["error"]["code"].as_i64()
+        };
+
+        assert_eq!(
+            error_code(&mut service, "not json"),
+            Some(codes::PARSE_ERROR as i64)
+        );
+
+        let unknown = json!({ "method": "resign", "params": { "position": position } });
+        assert_eq!(
+            error_code(&mut service, &unknown.to_string()),
+            Some(codes::METHOD_NOT_FOUND as i64)
+        );
+
+        let missing = json!({ "method": "best_move", "params": {} });
+        assert_eq!(
+            error_code(&mut service, &missing.to_string()),
+            Some(codes::INVALID_PARAMS as i64)
+        );
+
+        let invalid = json!({ "method": "best_move", "params": { "position": "NGUL/0" } });
+        assert_eq!(
+            error_code(&mut service, &invalid.to_string()),
+            Some(codes::SERVER_ERROR as i64)
+        );
+    }
+}
+// }}}